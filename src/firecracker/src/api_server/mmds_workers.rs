@@ -0,0 +1,131 @@
+// Copyright 2024 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Pool of threads serving the requests reading the MMDS contents concurrently, while the other
+//! API requests are handled by the API thread.
+
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
+use std::thread;
+
+use micro_http::{Method, Request, Response, ServerRequest, ServerResponse};
+use seccompiler::BpfProgramRef;
+use vmm::mmds::data_store::Mmds;
+use vmm::rpc_interface::{serve_mmds_read, VmmActionError};
+
+use super::parsed_request::ParsedRequest;
+use super::ApiServer;
+
+/// A request dispatched to the workers, along with the channel on which it is answered.
+struct MmdsJob {
+    server_request: ServerRequest,
+    request_processing_start_us: u64,
+    response_sender: mpsc::Sender<(ServerRequest, ServerResponse)>,
+}
+
+/// Answer to a request dispatched to the workers.
+pub(crate) type PendingMmdsResponse = mpsc::Receiver<(ServerRequest, ServerResponse)>;
+
+/// Handle to the pool of threads serving the MMDS reads.
+#[derive(Debug)]
+pub(crate) struct MmdsWorkers {
+    /// The data store, available once the microVM started.
+    mmds: Arc<OnceLock<Arc<Mutex<Mmds>>>>,
+    job_sender: mpsc::Sender<MmdsJob>,
+}
+
+impl MmdsWorkers {
+    /// Spawns `worker_count` threads, which load `seccomp_filter` before serving any request.
+    /// The threads exit once the returned handle is dropped.
+    pub(crate) fn spawn(
+        mmds: Arc<OnceLock<Arc<Mutex<Mmds>>>>,
+        worker_count: usize,
+        seccomp_filter: BpfProgramRef,
+    ) -> Self {
+        let (job_sender, job_receiver) = mpsc::channel::<MmdsJob>();
+        let job_receiver = Arc::new(Mutex::new(job_receiver));
+
+        for index in 0..worker_count {
+            let job_receiver = job_receiver.clone();
+            let mmds = mmds.clone();
+            let seccomp_filter = seccomp_filter.to_vec();
+            thread::Builder::new()
+                .name(format!("fc_api_mmds{index}"))
+                .spawn(move || {
+                    vmm::seccomp_audit::register_thread();
+                    if let Err(err) =
+                        vmm::seccomp_filters::install_filter("MMDS worker", &seccomp_filter)
+                    {
+                        panic!(
+                            "Failed to set the requested seccomp filters on the MMDS worker \
+                             thread: {}",
+                            err
+                        );
+                    }
+
+                    loop {
+                        // The lock is only held while waiting for a job, not while serving it.
+                        let job = job_receiver.lock().expect("Poisoned lock").recv();
+                        let Ok(job) = job else {
+                            return;
+                        };
+                        let mmds = mmds
+                            .get()
+                            .expect("MMDS read dispatched before the microVM start");
+                        let response = job.server_request.process(|request| {
+                            Self::serve(mmds, request, job.request_processing_start_us)
+                        });
+                        // The API thread only stops waiting for the answer if it is shutting down.
+                        let _ = job.response_sender.send((job.server_request, response));
+                    }
+                })
+                .expect("MMDS worker thread spawn failed.");
+        }
+
+        MmdsWorkers { mmds, job_sender }
+    }
+
+    /// Dispatches `server_request` to the workers if it reads the MMDS contents and the microVM
+    /// started, or gives it back otherwise.
+    pub(crate) fn dispatch(
+        &self,
+        server_request: ServerRequest,
+        request_processing_start_us: u64,
+    ) -> Result<PendingMmdsResponse, ServerRequest> {
+        if self.mmds.get().is_none() || !Self::is_mmds_read(&server_request.request) {
+            return Err(server_request);
+        }
+
+        let (response_sender, response_receiver) = mpsc::channel();
+        self.job_sender
+            .send(MmdsJob {
+                server_request,
+                request_processing_start_us,
+                response_sender,
+            })
+            .map_err(|mpsc::SendError(job)| job.server_request)?;
+        Ok(response_receiver)
+    }
+
+    fn is_mmds_read(request: &Request) -> bool {
+        request.method() == Method::Get
+            && request.body.is_none()
+            && request
+                .uri()
+                .get_abs_path()
+                .trim_start_matches('/')
+                .split_terminator('/')
+                .next()
+                == Some("mmds")
+    }
+
+    fn serve(mmds: &Mutex<Mmds>, request: &Request, request_processing_start_us: u64) -> Response {
+        ApiServer::process_request(request, request_processing_start_us, |vmm_action| {
+            let vmm_outcome = serve_mmds_read(mmds, &vmm_action).unwrap_or_else(|| {
+                Err(VmmActionError::NotSupported(
+                    "Only MMDS reads can be served by the MMDS workers.".to_string(),
+                ))
+            });
+            ParsedRequest::convert_to_response(&vmm_outcome)
+        })
+    }
+}
@@ -6,24 +6,34 @@
 //! It is constructed on top of an HTTP Server that uses Unix Domain Sockets and `EPOLL` to
 //! handle multiple connections on the same thread.
 
+mod mmds_workers;
 pub mod parsed_request;
 pub mod request;
 
 use std::fmt::Debug;
-use std::sync::mpsc;
+use std::sync::{mpsc, Arc, Mutex, OnceLock};
+use std::time::{Duration, Instant};
 
 pub use micro_http::{Body, HttpServer, Request, Response, ServerError, StatusCode, Version};
+use micro_http::{ServerRequest, ServerResponse};
+use mmds_workers::{MmdsWorkers, PendingMmdsResponse};
 use parsed_request::{ParsedRequest, RequestAction};
 use seccompiler::BpfProgramRef;
 use serde_json::json;
 use utils::time::{get_time_us, ClockType};
 use vmm::logger::{
-    debug, error, info, update_metric_with_elapsed_time, warn, ProcessTimeReporter, METRICS,
+    debug, error, info, update_metric_with_elapsed_time, warn, warn_throttled, IncMetric,
+    ProcessTimeReporter, METRICS,
 };
-use vmm::rpc_interface::{ApiRequest, ApiResponse, VmmAction};
+use vmm::mmds::data_store::Mmds;
+use vmm::rpc_interface::{ApiRequest, ApiResponse, VmmAction, VmmActionError, VmmData};
 use vmm::vmm_config::snapshot::SnapshotType;
 use vmm_sys_util::eventfd::EventFd;
 
+/// Minimum interval between two logs of the same deprecation warning, which clients of deprecated
+/// APIs would otherwise get on every request.
+const DEPRECATION_WARNING_INTERVAL: Duration = Duration::from_secs(60);
+
 /// Structure associated with the API server implementation.
 #[derive(Debug)]
 pub struct ApiServer {
@@ -34,6 +44,16 @@ pub struct ApiServer {
     /// FD on which we notify the VMM that we have sent at least one
     /// `VmmRequest`.
     to_vmm_fd: EventFd,
+    /// Requests taking longer than this many microseconds are logged at warn level.
+    slow_request_threshold_us: Option<u64>,
+    /// How long to wait for the VMM to respond to a request before failing it.
+    vmm_response_timeout: Duration,
+    /// Number of requests which timed out, and whose responses the VMM has not sent yet.
+    late_responses: usize,
+    /// The data store the MMDS workers read from, once the microVM started.
+    mmds: Option<Arc<OnceLock<Arc<Mutex<Mmds>>>>>,
+    /// Number of threads serving the MMDS reads, which are handled by the API thread if 0.
+    mmds_worker_count: usize,
 }
 
 impl ApiServer {
@@ -49,9 +69,41 @@ impl ApiServer {
             api_request_sender,
             vmm_response_receiver,
             to_vmm_fd,
+            slow_request_threshold_us: None,
+            vmm_response_timeout: vmm::RECV_TIMEOUT_SEC,
+            late_responses: 0,
+            mmds: None,
+            mmds_worker_count: 0,
         }
     }
 
+    /// Serves the requests reading the MMDS contents on a pool of `worker_count` threads, once
+    /// the data store is set in `mmds`, instead of forwarding them to the VMM.
+    ///
+    /// The requests are still answered in the order they are received, and consecutive MMDS
+    /// reads are served concurrently. Any other request is only sent to the VMM once the MMDS
+    /// reads received before it were served, and the MMDS reads received after it are only
+    /// served once it was handled, so they observe its effects.
+    pub fn set_mmds_workers(&mut self, mmds: Arc<OnceLock<Arc<Mutex<Mmds>>>>, worker_count: usize) {
+        self.mmds = Some(mmds);
+        self.mmds_worker_count = worker_count;
+    }
+
+    /// Sets how long to wait for the VMM to respond to a request before failing it with a
+    /// `503 Service Unavailable` error.
+    ///
+    /// The VMM cannot be interrupted, so it still handles a request which timed out: the
+    /// outcome of such a request is unknown to the client, and only logged once the response
+    /// of the VMM is collected while serving a later request.
+    pub fn set_vmm_response_timeout(&mut self, timeout: Duration) {
+        self.vmm_response_timeout = timeout;
+    }
+
+    /// Sets the duration, in milliseconds, above which requests are logged at warn level.
+    pub fn set_slow_request_threshold_ms(&mut self, threshold_ms: Option<u64>) {
+        self.slow_request_threshold_us = threshold_ms.map(|ms| ms.saturating_mul(1000));
+    }
+
     /// Runs the Api Server.
     ///
     /// # Arguments
@@ -75,10 +127,22 @@ impl ApiServer {
         // Store process CPU start time metric.
         process_time_reporter.report_cpu_start_time();
 
+        // The workers are spawned before the filters are loaded, since these do not allow
+        // creating threads. They load the same filters themselves.
+        let mmds_workers = match self.mmds.take() {
+            Some(mmds) if self.mmds_worker_count > 0 => Some(MmdsWorkers::spawn(
+                mmds,
+                self.mmds_worker_count,
+                seccomp_filter,
+            )),
+            _ => None,
+        };
+
         // Load seccomp filters on the API thread.
-        // Execution panics if filters cannot be loaded, use --no-seccomp if skipping filters
-        // altogether is the desired behaviour.
-        if let Err(err) = seccompiler::apply_filter(seccomp_filter) {
+        // Execution panics if filters cannot be loaded, unless the failure policy is to warn.
+        // Use --no-seccomp if skipping filters altogether is the desired behaviour.
+        vmm::seccomp_audit::register_thread();
+        if let Err(err) = vmm::seccomp_filters::install_filter("API", seccomp_filter) {
             panic!(
                 "Failed to set the requested seccomp filters on the API thread: {}",
                 err
@@ -101,36 +165,104 @@ impl ApiServer {
                     continue;
                 }
             };
+            let mut pending_mmds_responses = Vec::new();
             for server_request in request_vec {
                 let request_processing_start_us = get_time_us(ClockType::Monotonic);
+                let server_request = match &mmds_workers {
+                    Some(workers) => {
+                        match workers.dispatch(server_request, request_processing_start_us) {
+                            Ok(pending) => {
+                                pending_mmds_responses.push((request_processing_start_us, pending));
+                                continue;
+                            }
+                            Err(server_request) => server_request,
+                        }
+                    }
+                    None => server_request,
+                };
+                // The MMDS reads received before this request must not observe its effects.
+                self.respond_to_mmds_reads(&mut server, &mut pending_mmds_responses);
+
                 // Use `self.handle_request()` as the processing callback.
                 let response = server_request
                     .process(|request| self.handle_request(request, request_processing_start_us));
-                if let Err(err) = server.respond(response) {
-                    error!("API Server encountered an error on response: {}", err);
-                };
+                self.respond(
+                    &mut server,
+                    &server_request,
+                    response,
+                    request_processing_start_us,
+                );
+            }
+            self.respond_to_mmds_reads(&mut server, &mut pending_mmds_responses);
+        }
+    }
 
-                let delta_us = get_time_us(ClockType::Monotonic) - request_processing_start_us;
-                debug!("Total previous API call duration: {} us.", delta_us);
+    /// Sends the responses of the MMDS reads dispatched to the workers, in order.
+    fn respond_to_mmds_reads(
+        &self,
+        server: &mut HttpServer,
+        pending_mmds_responses: &mut Vec<(u64, PendingMmdsResponse)>,
+    ) {
+        for (request_processing_start_us, pending) in pending_mmds_responses.drain(..) {
+            match pending.recv() {
+                Ok((server_request, response)) => self.respond(
+                    server,
+                    &server_request,
+                    response,
+                    request_processing_start_us,
+                ),
+                Err(_) => error!("MMDS worker stopped before answering a request."),
             }
         }
     }
 
+    fn respond(
+        &self,
+        server: &mut HttpServer,
+        server_request: &ServerRequest,
+        response: ServerResponse,
+        request_processing_start_us: u64,
+    ) {
+        if let Err(err) = server.respond(response) {
+            error!("API Server encountered an error on response: {}", err);
+        };
+
+        let delta_us = get_time_us(ClockType::Monotonic) - request_processing_start_us;
+        debug!("Total previous API call duration: {} us.", delta_us);
+        self.report_slow_request(&server_request.request, delta_us);
+    }
+
     /// Handles an API request received through the associated socket.
     pub fn handle_request(
         &mut self,
         request: &Request,
         request_processing_start_us: u64,
+    ) -> Response {
+        Self::process_request(request, request_processing_start_us, |vmm_action| {
+            self.serve_vmm_action_request(vmm_action, request_processing_start_us)
+        })
+    }
+
+    /// Parses `request`, then lets `serve_vmm_action` answer the action it translates to.
+    fn process_request(
+        request: &Request,
+        request_processing_start_us: u64,
+        serve_vmm_action: impl FnOnce(Box<VmmAction>) -> Response,
     ) -> Response {
         match ParsedRequest::try_from(request).map(|r| r.into_parts()) {
             Ok((req_action, mut parsing_info)) => {
                 let mut response = match req_action {
                     RequestAction::Sync(vmm_action) => {
-                        self.serve_vmm_action_request(vmm_action, request_processing_start_us)
+                        let latency_histogram = METRICS
+                            .api_latencies
+                            .histogram(Self::latency_metric_key(&vmm_action));
+                        let response = serve_vmm_action(vmm_action);
+                        latency_histogram.update_with_elapsed_time(request_processing_start_us);
+                        response
                     }
                 };
                 if let Some(message) = parsing_info.take_deprecation_message() {
-                    warn!("{}", message);
+                    warn_throttled(&message, &message, DEPRECATION_WARNING_INTERVAL);
                     response.set_deprecation();
                 }
                 response
@@ -142,6 +274,88 @@ impl ApiServer {
         }
     }
 
+    fn report_slow_request(&self, request: &Request, duration_us: u64) {
+        match self.slow_request_threshold_us {
+            Some(threshold_us) if duration_us > threshold_us => {
+                METRICS.api_server.slow_request_count.inc();
+                warn!(
+                    "Slow API request: {} {} took {} us.",
+                    request.method().to_str(),
+                    request.uri().get_abs_path(),
+                    duration_us
+                );
+            }
+            _ => (),
+        }
+    }
+
+    /// The action type under which the latency of the requests translating to `vmm_action` is
+    /// recorded in the metrics.
+    fn latency_metric_key(vmm_action: &VmmAction) -> &'static str {
+        match vmm_action {
+            VmmAction::ApplyConfig(_) => "apply_config",
+            VmmAction::CompactGuestMemory(_) => "compact_guest_memory",
+            VmmAction::ConfigureBootSource(_) => "configure_boot_source",
+            VmmAction::ConfigureLogger(_) => "configure_logger",
+            VmmAction::ConfigureMetrics(_) => "configure_metrics",
+            VmmAction::CreateSnapshot(_) => "create_snapshot",
+            VmmAction::FlushMetrics => "flush_metrics",
+            VmmAction::GetBalloonConfig => "get_balloon_config",
+            VmmAction::GetBalloonFeatures => "get_balloon_features",
+            VmmAction::GetBalloonStats => "get_balloon_stats",
+            VmmAction::GetBlockRateLimiterState(_) => "get_block_rate_limiter_state",
+            VmmAction::GetCpuFrequencies => "get_cpu_frequencies",
+            VmmAction::GetDeviceCapabilities(_) => "get_device_capabilities",
+            VmmAction::GetDeviceConfigSpace(_) => "get_device_config_space",
+            VmmAction::GetDeviceMemory(_) => "get_device_memory",
+            VmmAction::GetDeviceMetrics(_) => "get_device_metrics",
+            VmmAction::GetDeviceStates => "get_device_states",
+            VmmAction::GetFullVmConfig => "get_full_vm_config",
+            VmmAction::GetHealth => "get_health",
+            VmmAction::GetKvmCapabilities => "get_kvm_capabilities",
+            VmmAction::GetMMDS => "get_mmds",
+            VmmAction::GetMMDSPath(..) => "get_mmds_path",
+            VmmAction::GetMemoryLayout => "get_memory_layout",
+            VmmAction::GetNetRateLimiterState(_) => "get_net_rate_limiter_state",
+            VmmAction::GetNetworkInterface(_) => "get_network_interface",
+            VmmAction::GetSeccompConfig => "get_seccomp_config",
+            VmmAction::GetVcpuStates => "get_vcpu_states",
+            VmmAction::GetVmInstanceInfo => "get_vm_instance_info",
+            VmmAction::GetVmMachineConfig => "get_vm_machine_config",
+            VmmAction::GetVmmVersion => "get_vmm_version",
+            #[cfg(feature = "fault-injection")]
+            VmmAction::InjectDeviceFault(_) => "inject_device_fault",
+            VmmAction::InsertBlockDevice(_) => "insert_block_device",
+            VmmAction::InsertNetworkDevice(_) => "insert_network_device",
+            VmmAction::LoadSnapshot(_) => "load_snapshot",
+            VmmAction::PatchMMDS(_) => "patch_mmds",
+            VmmAction::Pause => "pause",
+            VmmAction::PauseVcpu(_) => "pause_vcpu",
+            VmmAction::PutCpuConfiguration(_) => "put_cpu_configuration",
+            VmmAction::PutMMDS(_) => "put_mmds",
+            VmmAction::QuiesceIo => "quiesce_io",
+            VmmAction::ResetHealth => "reset_health",
+            VmmAction::Resume => "resume",
+            VmmAction::ResumeVcpu(_) => "resume_vcpu",
+            #[cfg(target_arch = "x86_64")]
+            VmmAction::SendCtrlAltDel => "send_ctrl_alt_del",
+            VmmAction::SetBalloonDevice(_) => "set_balloon_device",
+            VmmAction::SetEntropyDevice(_) => "set_entropy_device",
+            VmmAction::SetMmdsConfiguration(_) => "set_mmds_configuration",
+            VmmAction::SetVsockDevice(_) => "set_vsock_device",
+            VmmAction::SetWatchdogDevice(_) => "set_watchdog_device",
+            VmmAction::StartMicroVm => "start_microvm",
+            VmmAction::UpdateBalloon(_) => "update_balloon",
+            VmmAction::UpdateBalloonStatistics(_) => "update_balloon_statistics",
+            VmmAction::UpdateBlockDevice(_) => "update_block_device",
+            VmmAction::UpdateEntropyDevice(_) => "update_entropy_device",
+            VmmAction::UpdateLoggerFilter(_) => "update_logger_filter",
+            VmmAction::UpdateNetworkInterface(_) => "update_network_interface",
+            VmmAction::UpdateVmConfiguration(_) => "update_vm_configuration",
+            VmmAction::ValidateConfig(_) => "validate_config",
+        }
+    }
+
     fn serve_vmm_action_request(
         &mut self,
         vmm_action: Box<VmmAction>,
@@ -170,7 +384,18 @@ impl ApiServer {
             .send(vmm_action)
             .expect("Failed to send VMM message");
         self.to_vmm_fd.write(1).expect("Cannot update send VMM fd");
-        let vmm_outcome = *(self.vmm_response_receiver.recv().expect("VMM disconnected"));
+        let Some(vmm_outcome) = self.recv_vmm_response() else {
+            let message = format!(
+                "The VMM did not respond to the request within {} ms. The request is still \
+                 pending and may take effect later, so its outcome is indeterminate.",
+                self.vmm_response_timeout.as_millis()
+            );
+            error!("Received Error. Status code: 503 Service Unavailable. Message: {message}");
+            return Self::json_response(
+                StatusCode::ServiceUnavailable,
+                Self::json_fault_message(message),
+            );
+        };
         let response = ParsedRequest::convert_to_response(&vmm_outcome);
 
         if vmm_outcome.is_ok() {
@@ -183,6 +408,32 @@ impl ApiServer {
         response
     }
 
+    /// Waits for the VMM response to the last request sent, or returns `None` if it does not
+    /// arrive in time.
+    fn recv_vmm_response(&mut self) -> Option<Result<VmmData, VmmActionError>> {
+        let deadline = Instant::now() + self.vmm_response_timeout;
+        loop {
+            let timeout = deadline.saturating_duration_since(Instant::now());
+            match self.vmm_response_receiver.recv_timeout(timeout) {
+                // The VMM handles the requests in order, so the responses to the requests which
+                // timed out come first.
+                Ok(late_outcome) if self.late_responses > 0 => {
+                    self.late_responses -= 1;
+                    match *late_outcome {
+                        Ok(_) => warn!("A request which timed out succeeded."),
+                        Err(err) => warn!("A request which timed out failed: {err}"),
+                    }
+                }
+                Ok(vmm_outcome) => return Some(*vmm_outcome),
+                Err(mpsc::RecvTimeoutError::Timeout) => {
+                    self.late_responses += 1;
+                    return None;
+                }
+                Err(mpsc::RecvTimeoutError::Disconnected) => panic!("VMM disconnected"),
+            }
+        }
+    }
+
     /// An HTTP response which also includes a body.
     pub(crate) fn json_response<T: Into<String> + Debug>(status: StatusCode, body: T) -> Response {
         let mut response = Response::new(Version::Http11, status);
@@ -207,8 +458,8 @@ mod tests {
     use utils::time::ClockType;
     use vmm::builder::StartMicrovmError;
     use vmm::logger::StoreMetric;
-    use vmm::rpc_interface::{VmmActionError, VmmData};
     use vmm::seccomp_filters::get_empty_filters;
+    use vmm::test_utils::{capture_logs, captured_logs};
     use vmm::vmm_config::instance_info::InstanceInfo;
     use vmm::vmm_config::snapshot::CreateSnapshotParams;
     use vmm_sys_util::tempfile::TempFile;
@@ -275,6 +526,8 @@ mod tests {
                 snapshot_type: SnapshotType::Diff,
                 snapshot_path: PathBuf::new(),
                 mem_file_path: PathBuf::new(),
+                baseline_mem_file_path: None,
+                mem_write_chunk_size_kib: None,
             })),
             start_time_us,
         );
@@ -288,6 +541,8 @@ mod tests {
                 snapshot_type: SnapshotType::Diff,
                 snapshot_path: PathBuf::new(),
                 mem_file_path: PathBuf::new(),
+                baseline_mem_file_path: None,
+                mem_write_chunk_size_kib: None,
             })),
             start_time_us,
         );
@@ -296,6 +551,38 @@ mod tests {
         assert_eq!(METRICS.latencies_us.full_create_snapshot.fetch(), 0);
     }
 
+    #[test]
+    fn test_serve_vmm_action_request_timeout() {
+        let to_vmm_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
+        let (api_request_sender, _from_api) = channel();
+        let (to_api, vmm_response_receiver) = channel();
+
+        let mut api_server = ApiServer::new(api_request_sender, vmm_response_receiver, to_vmm_fd);
+        api_server.set_vmm_response_timeout(Duration::from_millis(50));
+
+        // The VMM does not respond.
+        let start = Instant::now();
+        let response = api_server.serve_vmm_action_request(Box::new(VmmAction::Pause), 0);
+        let elapsed = start.elapsed();
+        assert_eq!(response.status(), StatusCode::ServiceUnavailable);
+        assert!(elapsed >= Duration::from_millis(50));
+        assert!(elapsed < vmm::RECV_TIMEOUT_SEC);
+        let mut buf = Vec::new();
+        response.write_all(&mut buf).unwrap();
+        let body = String::from_utf8(buf).unwrap();
+        assert!(body.contains("The VMM did not respond to the request within 50 ms."));
+        assert!(body.contains("its outcome is indeterminate"));
+
+        // The late response to the request which timed out is not mistaken for the response to
+        // the next request.
+        to_api
+            .send(Box::new(Err(VmmActionError::OperationNotSupportedPreBoot)))
+            .unwrap();
+        to_api.send(Box::new(Ok(VmmData::Empty))).unwrap();
+        let response = api_server.serve_vmm_action_request(Box::new(VmmAction::Resume), 0);
+        assert_eq!(response.status(), StatusCode::NoContent);
+    }
+
     #[test]
     fn test_handle_request() {
         let to_vmm_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
@@ -348,6 +635,59 @@ mod tests {
         assert_eq!(response.status(), StatusCode::BadRequest);
     }
 
+    #[test]
+    fn test_handle_request_latency_metrics() {
+        let to_vmm_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
+        let (api_request_sender, _from_api) = channel();
+        let (to_api, vmm_response_receiver) = channel();
+
+        let mut api_server = ApiServer::new(api_request_sender, vmm_response_receiver, to_vmm_fd);
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+
+        let requests: [(&[u8], VmmData, &str); 4] = [
+            (
+                b"GET / HTTP/1.1\r\n\r\n",
+                VmmData::InstanceInformation(InstanceInfo::default()),
+                "get_vm_instance_info",
+            ),
+            (
+                b"GET /mmds HTTP/1.1\r\n\r\n",
+                VmmData::MmdsValue(serde_json::json!({})),
+                "get_mmds",
+            ),
+            (
+                b"PUT /mmds HTTP/1.1\r\n\
+                Content-Type: application/json\r\n\
+                Content-Length: 2\r\n\r\n{}",
+                VmmData::Empty,
+                "put_mmds",
+            ),
+            (
+                b"PATCH /mmds HTTP/1.1\r\n\
+                Content-Type: application/json\r\n\
+                Content-Length: 2\r\n\r\n{}",
+                VmmData::Empty,
+                "patch_mmds",
+            ),
+        ];
+        for (request, vmm_data, action) in requests {
+            let histogram = METRICS.api_latencies.histogram(action);
+            let count = histogram.count();
+            to_api.send(Box::new(Ok(vmm_data))).unwrap();
+            sender.write_all(request).unwrap();
+            connection.try_read().unwrap();
+            let req = connection.pop_parsed_request().unwrap();
+            let response = api_server.handle_request(&req, 0);
+            assert!(matches!(
+                response.status(),
+                StatusCode::OK | StatusCode::NoContent
+            ));
+            // Other tests may handle requests of the same type concurrently.
+            assert!(histogram.count() > count);
+        }
+    }
+
     #[test]
     fn test_handle_request_logging() {
         let cpu_template_json = TEST_UNESCAPED_JSON_TEMPLATE;
@@ -413,6 +753,116 @@ mod tests {
         assert!(sock.read(&mut buf[..]).unwrap() > 0);
     }
 
+    #[test]
+    fn test_concurrent_mmds_reads() {
+        let mut tmp_socket = TempFile::new().unwrap();
+        tmp_socket.remove().unwrap();
+        let path_to_socket = tmp_socket.as_path().to_str().unwrap().to_owned();
+        let api_thread_path_to_socket = path_to_socket.clone();
+
+        let to_vmm_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
+        let (api_request_sender, from_api) = channel();
+        let (_to_api, vmm_response_receiver) = channel();
+        let seccomp_filters = get_empty_filters();
+        let server = HttpServer::new(PathBuf::from(api_thread_path_to_socket)).unwrap();
+
+        let data_store = Arc::new(Mutex::new(Mmds::default()));
+        data_store
+            .lock()
+            .unwrap()
+            .put_data(serde_json::json!({"instance-id": "i-1234"}))
+            .unwrap();
+        let mmds = Arc::new(OnceLock::new());
+        mmds.set(data_store).unwrap();
+
+        thread::Builder::new()
+            .name("fc_api_test".to_owned())
+            .spawn(move || {
+                let mut api_server =
+                    ApiServer::new(api_request_sender, vmm_response_receiver, to_vmm_fd);
+                api_server.set_mmds_workers(mmds, 2);
+                api_server.run(
+                    server,
+                    ProcessTimeReporter::new(Some(1), Some(1), Some(1)),
+                    seccomp_filters.get("api").unwrap(),
+                    vmm::HTTP_MAX_PAYLOAD_SIZE,
+                );
+            })
+            .unwrap();
+
+        // Both requests are sent before any response is read.
+        let mut socks: Vec<_> = (0..2)
+            .map(|_| UnixStream::connect(PathBuf::from(&path_to_socket)).unwrap())
+            .collect();
+        for sock in socks.iter_mut() {
+            sock.write_all(b"GET /mmds HTTP/1.1\r\n\r\n").unwrap();
+        }
+        for sock in socks.iter_mut() {
+            let mut buf: [u8; 200] = [0; 200];
+            let len = sock.read(&mut buf[..]).unwrap();
+            let response = std::str::from_utf8(&buf[..len]).unwrap();
+            assert!(response.starts_with("HTTP/1.1 200"), "{response}");
+            assert!(
+                response.contains(r#"{"instance-id":"i-1234"}"#),
+                "{response}"
+            );
+        }
+        // The requests were served without going through the VMM.
+        from_api.try_recv().unwrap_err();
+    }
+
+    #[test]
+    fn test_slow_request_logging() {
+        capture_logs();
+        let to_vmm_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
+        let (api_request_sender, _from_api) = channel();
+        let (_to_api, vmm_response_receiver) = channel();
+        let mut api_server = ApiServer::new(api_request_sender, vmm_response_receiver, to_vmm_fd);
+        api_server.set_slow_request_threshold_ms(Some(50));
+
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(b"GET /slow-request-logging HTTP/1.1\r\n\r\n")
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        let slow_request_count = METRICS.api_server.slow_request_count.count();
+
+        // Requests lasting up to the threshold are not reported.
+        api_server.report_slow_request(&req, 50_000);
+        assert_eq!(
+            METRICS.api_server.slow_request_count.count(),
+            slow_request_count
+        );
+        assert!(!captured_logs().contains("GET /slow-request-logging"));
+
+        // Requests lasting longer than the threshold are logged at warn level.
+        api_server.report_slow_request(&req, 50_001);
+        assert_eq!(
+            METRICS.api_server.slow_request_count.count(),
+            slow_request_count + 1
+        );
+        let logs = captured_logs();
+        let line = logs
+            .lines()
+            .find(|line| line.contains("GET /slow-request-logging"))
+            .unwrap();
+        assert!(line.contains(":WARN"), "{line}");
+        assert!(
+            line.ends_with("Slow API request: GET /slow-request-logging took 50001 us."),
+            "{line}"
+        );
+
+        // Without a threshold, no request is reported.
+        api_server.set_slow_request_threshold_ms(None);
+        api_server.report_slow_request(&req, u64::MAX);
+        assert_eq!(
+            METRICS.api_server.slow_request_count.count(),
+            slow_request_count + 1
+        );
+    }
+
     #[test]
     fn test_bind_and_run_with_limit() {
         let mut tmp_socket = TempFile::new().unwrap();
@@ -3,29 +3,40 @@
 
 use std::fmt::Debug;
 
-use micro_http::{Body, Method, Request, Response, StatusCode, Version};
+use micro_http::{Body, MediaType, Method, Request, Response, StatusCode, Version};
 use serde::ser::Serialize;
 use serde_json::Value;
 use vmm::logger::{error, info, log_enabled, Level};
+use vmm::mmds::data_store::MmdsDatastoreError;
 use vmm::rpc_interface::{VmmAction, VmmActionError, VmmData};
 
 use super::request::actions::parse_put_actions;
 use super::request::balloon::{parse_get_balloon, parse_patch_balloon, parse_put_balloon};
 use super::request::boot_source::parse_put_boot_source;
-use super::request::cpu_configuration::parse_put_cpu_config;
-use super::request::drive::{parse_patch_drive, parse_put_drive};
-use super::request::entropy::parse_put_entropy;
+use super::request::config::parse_put_config;
+use super::request::cpu_configuration::{parse_get_cpu_config, parse_put_cpu_config};
+#[cfg(feature = "fault-injection")]
+use super::request::devices::parse_put_devices;
+use super::request::devices::{parse_get_device_capabilities, parse_get_devices};
+use super::request::drive::{parse_get_drive, parse_patch_drive, parse_put_drive};
+use super::request::entropy::{parse_patch_entropy, parse_put_entropy};
+use super::request::health::parse_get_health;
+use super::request::host::parse_get_host;
 use super::request::instance_info::parse_get_instance_info;
-use super::request::logger::parse_put_logger;
+use super::request::logger::{parse_patch_logger, parse_put_logger};
 use super::request::machine_configuration::{
     parse_get_machine_config, parse_patch_machine_config, parse_put_machine_config,
 };
+use super::request::memory::parse_get_memory;
 use super::request::metrics::parse_put_metrics;
 use super::request::mmds::{parse_get_mmds, parse_patch_mmds, parse_put_mmds};
-use super::request::net::{parse_patch_net, parse_put_net};
+use super::request::net::{parse_get_net, parse_patch_net, parse_put_net};
+use super::request::seccomp::parse_get_seccomp;
 use super::request::snapshot::{parse_patch_vm_state, parse_put_snapshot};
+use super::request::vcpu::{parse_get_vcpu, parse_put_vcpu};
 use super::request::version::parse_get_version;
 use super::request::vsock::parse_put_vsock;
+use super::request::watchdog::parse_put_watchdog;
 use super::ApiServer;
 
 #[derive(Debug)]
@@ -77,17 +88,39 @@ impl TryFrom<&Request> for ParsedRequest {
         match (request.method(), path, request.body.as_ref()) {
             (Method::Get, "", None) => parse_get_instance_info(),
             (Method::Get, "balloon", None) => parse_get_balloon(path_tokens.next()),
+            (Method::Get, "cpu-config", None) => parse_get_cpu_config(path_tokens.next()),
+            (Method::Get, "device-capabilities", None) => {
+                parse_get_device_capabilities(path_tokens.next())
+            }
+            (Method::Get, "devices", None) => parse_get_devices(&path_tokens.collect::<Vec<_>>()),
+            (Method::Get, "drives", None) => parse_get_drive(&path_tokens.collect::<Vec<_>>()),
+            (Method::Get, "health", None) => parse_get_health(),
+            (Method::Get, "host", None) => parse_get_host(path_tokens.next()),
+            (Method::Get, "vcpu", None) => parse_get_vcpu(path_tokens.next()),
             (Method::Get, "version", None) => parse_get_version(),
             (Method::Get, "vm", None) if path_tokens.next() == Some("config") => {
                 Ok(ParsedRequest::new_sync(VmmAction::GetFullVmConfig))
             }
             (Method::Get, "machine-config", None) => parse_get_machine_config(),
-            (Method::Get, "mmds", None) => parse_get_mmds(),
+            (Method::Get, "memory", None) => parse_get_memory(path_tokens.next()),
+            (Method::Get, "mmds", None) => parse_get_mmds(
+                &path_tokens.collect::<Vec<_>>(),
+                request.headers.custom_entries(),
+            ),
+            (Method::Get, "network-interfaces", None) => {
+                parse_get_net(&path_tokens.collect::<Vec<_>>())
+            }
+            (Method::Get, "seccomp", None) => parse_get_seccomp(),
             (Method::Get, _, Some(_)) => method_to_error(Method::Get),
             (Method::Put, "actions", Some(body)) => parse_put_actions(body),
             (Method::Put, "balloon", Some(body)) => parse_put_balloon(body),
             (Method::Put, "boot-source", Some(body)) => parse_put_boot_source(body),
+            (Method::Put, "config", Some(body)) => parse_put_config(body, path_tokens.next()),
             (Method::Put, "cpu-config", Some(body)) => parse_put_cpu_config(body),
+            #[cfg(feature = "fault-injection")]
+            (Method::Put, "devices", Some(body)) => {
+                parse_put_devices(body, &path_tokens.collect::<Vec<_>>())
+            }
             (Method::Put, "drives", Some(body)) => parse_put_drive(body, path_tokens.next()),
             (Method::Put, "logger", Some(body)) => parse_put_logger(body),
             (Method::Put, "machine-config", Some(body)) => parse_put_machine_config(body),
@@ -97,11 +130,15 @@ impl TryFrom<&Request> for ParsedRequest {
                 parse_put_net(body, path_tokens.next())
             }
             (Method::Put, "snapshot", Some(body)) => parse_put_snapshot(body, path_tokens.next()),
+            (Method::Put, "vcpu", Some(body)) => parse_put_vcpu(body, path_tokens.next()),
             (Method::Put, "vsock", Some(body)) => parse_put_vsock(body),
             (Method::Put, "entropy", Some(body)) => parse_put_entropy(body),
+            (Method::Put, "watchdog", Some(body)) => parse_put_watchdog(body),
             (Method::Put, _, None) => method_to_error(Method::Put),
             (Method::Patch, "balloon", Some(body)) => parse_patch_balloon(body, path_tokens.next()),
             (Method::Patch, "drives", Some(body)) => parse_patch_drive(body, path_tokens.next()),
+            (Method::Patch, "entropy", Some(body)) => parse_patch_entropy(body),
+            (Method::Patch, "logger", Some(body)) => parse_patch_logger(body),
             (Method::Patch, "machine-config", Some(body)) => parse_patch_machine_config(body),
             (Method::Patch, "mmds", Some(body)) => parse_patch_mmds(body),
             (Method::Patch, "network-interfaces", Some(body)) => {
@@ -166,16 +203,51 @@ impl ParsedRequest {
                 VmmData::MachineConfiguration(vm_config) => {
                     Self::success_response_with_data(vm_config)
                 }
+                VmmData::MemoryLayout(layout) => Self::success_response_with_data(layout),
                 VmmData::MmdsValue(value) => Self::success_response_with_mmds_value(value),
+                VmmData::MmdsText(text) => {
+                    info!("The request was executed successfully. Status code: 200 OK.");
+                    let mut response = Response::new(Version::Http11, StatusCode::OK);
+                    response.set_body(Body::new(text.as_str()));
+                    response.set_content_type(MediaType::PlainText);
+                    response
+                }
+                VmmData::NetworkInterfaceConfig(config) => Self::success_response_with_data(config),
+                VmmData::NetRateLimiterState(state) => Self::success_response_with_data(state),
+                VmmData::RateLimiterState(state) => Self::success_response_with_data(state),
                 VmmData::BalloonConfig(balloon_config) => {
                     Self::success_response_with_data(balloon_config)
                 }
                 VmmData::BalloonStats(stats) => Self::success_response_with_data(stats),
+                VmmData::BalloonFeatures(features) => Self::success_response_with_data(features),
+                VmmData::CpuFrequencies(frequencies) => {
+                    Self::success_response_with_data(frequencies)
+                }
+                VmmData::DeviceCapabilities(capabilities) => {
+                    Self::success_response_with_data(capabilities)
+                }
+                VmmData::DeviceConfigSpace(config_space) => {
+                    Self::success_response_with_data(config_space)
+                }
+                VmmData::DeviceMemory(usage) => Self::success_response_with_data(usage),
+                VmmData::DeviceMetrics(metrics) => Self::success_response_with_data(metrics),
+                VmmData::DeviceStates(states) => Self::success_response_with_data(states),
+                VmmData::FlushResult(result) => Self::success_response_with_data(result),
+                VmmData::GuestMemoryCompaction(compaction) => {
+                    Self::success_response_with_data(compaction)
+                }
                 VmmData::InstanceInformation(info) => Self::success_response_with_data(info),
                 VmmData::VmmVersion(version) => Self::success_response_with_data(
                     &serde_json::json!({ "firecracker_version": version.as_str() }),
                 ),
                 VmmData::FullVmConfig(config) => Self::success_response_with_data(config),
+                VmmData::SeccompConfig(info) => Self::success_response_with_data(info),
+                VmmData::SnapshotPaths(paths) => Self::success_response_with_data(paths),
+                VmmData::Health(health) => Self::success_response_with_data(health),
+                VmmData::KvmCapabilities(capabilities) => {
+                    Self::success_response_with_data(capabilities)
+                }
+                VmmData::VcpuStates(states) => Self::success_response_with_data(states),
             },
             Err(vmm_action_error) => {
                 let mut response = match vmm_action_error {
@@ -186,6 +258,13 @@ impl ParsedRequest {
                         );
                         Response::new(Version::Http11, StatusCode::PayloadTooLarge)
                     }
+                    VmmActionError::Mmds(MmdsDatastoreError::NotFound) => {
+                        error!(
+                            "Received Error. Status code: 404 Not Found. Message: {}",
+                            vmm_action_error
+                        );
+                        Response::new(Version::Http11, StatusCode::NotFound)
+                    }
                     _ => {
                         error!(
                             "Received Error. Status code: 400 Bad Request. Message: {}",
@@ -316,6 +395,7 @@ pub(crate) fn checked_id(id: &str) -> Result<&str, RequestError> {
 pub mod tests {
     use std::io::{Cursor, Write};
     use std::os::unix::net::UnixStream;
+    use std::path::PathBuf;
     use std::str::FromStr;
 
     use micro_http::HttpConnection;
@@ -323,9 +403,20 @@ pub mod tests {
     use vmm::cpu_config::templates::test_utils::build_test_template;
     use vmm::resources::VmmConfig;
     use vmm::rpc_interface::VmmActionError;
-    use vmm::vmm_config::balloon::{BalloonDeviceConfig, BalloonStats};
+    use vmm::vmm_config::balloon::{
+        BalloonDeviceConfig, BalloonFeatures, BalloonStats, GuestMemoryCompaction,
+    };
+    use vmm::vmm_config::devices::{DeviceConfigSpace, DeviceMemoryUsage, VirtioDeviceType};
+    use vmm::vmm_config::health::Health;
     use vmm::vmm_config::instance_info::InstanceInfo;
-    use vmm::vmm_config::machine_config::MachineConfig;
+    use vmm::vmm_config::kvm_capabilities::KvmCapabilities;
+    use vmm::vmm_config::machine_config::{CpuFrequencies, MachineConfig, MemoryLayout, VmConfig};
+    use vmm::vmm_config::metrics::FlushResult;
+    use vmm::vmm_config::net::{NetRateLimiterState, NetworkInterfaceConfig};
+    use vmm::vmm_config::seccomp::SeccompInfo;
+    use vmm::vmm_config::snapshot::SnapshotPaths;
+    use vmm::vmm_config::{RateLimiterState, TokenBucketState};
+    use vmm::VcpuRunState;
 
     use super::*;
 
@@ -555,16 +646,57 @@ pub mod tests {
                 VmmData::BalloonStats(stats) => {
                     http_response(&serde_json::to_string(stats).unwrap(), 200)
                 }
+                VmmData::BalloonFeatures(features) => {
+                    http_response(&serde_json::to_string(features).unwrap(), 200)
+                }
+                VmmData::CpuFrequencies(frequencies) => {
+                    http_response(&serde_json::to_string(frequencies).unwrap(), 200)
+                }
+                VmmData::DeviceCapabilities(capabilities) => {
+                    http_response(&serde_json::to_string(capabilities).unwrap(), 200)
+                }
+                VmmData::DeviceConfigSpace(config_space) => {
+                    http_response(&serde_json::to_string(config_space).unwrap(), 200)
+                }
+                VmmData::DeviceMemory(usage) => {
+                    http_response(&serde_json::to_string(usage).unwrap(), 200)
+                }
+                VmmData::DeviceMetrics(metrics) => {
+                    http_response(&serde_json::to_string(metrics).unwrap(), 200)
+                }
+                VmmData::DeviceStates(states) => {
+                    http_response(&serde_json::to_string(states).unwrap(), 200)
+                }
                 VmmData::Empty => http_response("", 204),
+                VmmData::FlushResult(result) => {
+                    http_response(&serde_json::to_string(result).unwrap(), 200)
+                }
                 VmmData::FullVmConfig(cfg) => {
                     http_response(&serde_json::to_string(cfg).unwrap(), 200)
                 }
+                VmmData::GuestMemoryCompaction(compaction) => {
+                    http_response(&serde_json::to_string(compaction).unwrap(), 200)
+                }
                 VmmData::MachineConfiguration(cfg) => {
                     http_response(&serde_json::to_string(cfg).unwrap(), 200)
                 }
+                VmmData::MemoryLayout(layout) => {
+                    http_response(&serde_json::to_string(layout).unwrap(), 200)
+                }
                 VmmData::MmdsValue(value) => {
                     http_response(&serde_json::to_string(value).unwrap(), 200)
                 }
+                VmmData::MmdsText(text) => http_response(text, 200)
+                    .replace("Content-Type: application/json", "Content-Type: text/plain"),
+                VmmData::NetworkInterfaceConfig(cfg) => {
+                    http_response(&serde_json::to_string(cfg).unwrap(), 200)
+                }
+                VmmData::NetRateLimiterState(state) => {
+                    http_response(&serde_json::to_string(state).unwrap(), 200)
+                }
+                VmmData::RateLimiterState(state) => {
+                    http_response(&serde_json::to_string(state).unwrap(), 200)
+                }
                 VmmData::InstanceInformation(info) => {
                     http_response(&serde_json::to_string(info).unwrap(), 200)
                 }
@@ -572,6 +704,21 @@ pub mod tests {
                     &serde_json::json!({ "firecracker_version": version.as_str() }).to_string(),
                     200,
                 ),
+                VmmData::SeccompConfig(info) => {
+                    http_response(&serde_json::to_string(info).unwrap(), 200)
+                }
+                VmmData::SnapshotPaths(paths) => {
+                    http_response(&serde_json::to_string(paths).unwrap(), 200)
+                }
+                VmmData::Health(health) => {
+                    http_response(&serde_json::to_string(health).unwrap(), 200)
+                }
+                VmmData::KvmCapabilities(capabilities) => {
+                    http_response(&serde_json::to_string(capabilities).unwrap(), 200)
+                }
+                VmmData::VcpuStates(states) => {
+                    http_response(&serde_json::to_string(states).unwrap(), 200)
+                }
             };
             let response = ParsedRequest::convert_to_response(&data);
             response.write_all(&mut buf).unwrap();
@@ -584,12 +731,70 @@ pub mod tests {
             swap_out: Some(1),
             ..Default::default()
         }));
+        verify_ok_response_with(VmmData::BalloonFeatures(BalloonFeatures {
+            deflate_on_oom: true,
+            ..Default::default()
+        }));
+        verify_ok_response_with(VmmData::CpuFrequencies(CpuFrequencies {
+            base_mhz: 2100,
+            max_mhz: 3500,
+            bus_mhz: 100,
+        }));
+        verify_ok_response_with(VmmData::DeviceCapabilities(
+            VirtioDeviceType::Block.capabilities(),
+        ));
+        verify_ok_response_with(VmmData::DeviceConfigSpace(DeviceConfigSpace::new(&[0x1])));
+        verify_ok_response_with(VmmData::DeviceMemory(DeviceMemoryUsage::new(&[])));
+        verify_ok_response_with(VmmData::DeviceMetrics(
+            serde_json::json!({ "read_count": 1 }),
+        ));
+        verify_ok_response_with(VmmData::DeviceStates(
+            serde_json::json!({ "block_devices": [] }),
+        ));
         verify_ok_response_with(VmmData::Empty);
+        verify_ok_response_with(VmmData::FlushResult(FlushResult { bytes_written: 1 }));
         verify_ok_response_with(VmmData::FullVmConfig(VmmConfig::default()));
         verify_ok_response_with(VmmData::MachineConfiguration(MachineConfig::default()));
+        verify_ok_response_with(VmmData::MemoryLayout(MemoryLayout::from(
+            &VmConfig::default(),
+        )));
         verify_ok_response_with(VmmData::MmdsValue(serde_json::from_str("{}").unwrap()));
+        verify_ok_response_with(VmmData::MmdsText(String::from("meta-data/")));
+        verify_ok_response_with(VmmData::NetworkInterfaceConfig(NetworkInterfaceConfig {
+            iface_id: String::from("netif"),
+            host_dev_name: String::from("tap0"),
+            guest_mac: None,
+            rx_rate_limiter: None,
+            tx_rate_limiter: None,
+            mtu: Some(1500),
+        }));
+        verify_ok_response_with(VmmData::NetRateLimiterState(NetRateLimiterState::default()));
+        verify_ok_response_with(VmmData::RateLimiterState(RateLimiterState {
+            bandwidth: Some(TokenBucketState {
+                size: 1000,
+                budget: 500,
+                one_time_burst: 0,
+                refill_time: 100,
+                last_update_ms_ago: 10,
+            }),
+            ops: None,
+        }));
+        verify_ok_response_with(VmmData::GuestMemoryCompaction(GuestMemoryCompaction {
+            reclaimed_mib: 6,
+        }));
         verify_ok_response_with(VmmData::InstanceInformation(InstanceInfo::default()));
         verify_ok_response_with(VmmData::VmmVersion(String::default()));
+        verify_ok_response_with(VmmData::SeccompConfig(SeccompInfo::default()));
+        verify_ok_response_with(VmmData::SnapshotPaths(SnapshotPaths {
+            snapshot_path: PathBuf::from("/snap-vm0"),
+            mem_file_path: PathBuf::from("/mem-vm0"),
+        }));
+        verify_ok_response_with(VmmData::Health(Health::default()));
+        verify_ok_response_with(VmmData::KvmCapabilities(KvmCapabilities::default()));
+        verify_ok_response_with(VmmData::VcpuStates(vec![
+            VcpuRunState::Running,
+            VcpuRunState::Paused,
+        ]));
 
         // Error.
         let error = VmmActionError::StartMicrovm(StartMicrovmError::MissingKernelConfig);
@@ -600,6 +805,25 @@ pub mod tests {
 
         let expected_response = http_response(&json, 400);
         assert_eq!(buf.into_inner(), expected_response.as_bytes());
+
+        // MMDS lookup errors.
+        let error = VmmActionError::Mmds(MmdsDatastoreError::NotFound);
+        let mut buf = Cursor::new(vec![0]);
+        let json = ApiServer::json_fault_message(error.to_string());
+        let response = ParsedRequest::convert_to_response(&Err(error));
+        response.write_all(&mut buf).unwrap();
+        assert_eq!(buf.into_inner(), http_response(&json, 404).as_bytes());
+
+        // The fault message of a rejected MMDS patch points at the offending value.
+        let error = VmmActionError::Mmds(MmdsDatastoreError::InvalidPatchAt(
+            "/meta-data/foo".to_string(),
+        ));
+        let mut buf = Cursor::new(vec![0]);
+        let json = ApiServer::json_fault_message(error.to_string());
+        assert!(json.contains(r#"at \"/meta-data/foo\""#));
+        let response = ParsedRequest::convert_to_response(&Err(error));
+        response.write_all(&mut buf).unwrap();
+        assert_eq!(buf.into_inner(), http_response(&json, 400).as_bytes());
     }
 
     #[test]
@@ -638,6 +862,90 @@ pub mod tests {
         ParsedRequest::try_from(&req).unwrap();
     }
 
+    #[test]
+    fn test_try_from_get_balloon_features() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/balloon/features", None).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+    }
+
+    #[test]
+    fn test_try_from_get_device_metrics() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/devices/block/rootfs/metrics", None).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+    }
+
+    #[test]
+    fn test_try_from_get_device_capabilities() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/device-capabilities/block", None).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+    }
+
+    #[test]
+    fn test_try_from_get_device_memory() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/devices/net/eth0/memory", None).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+    }
+
+    #[test]
+    fn test_try_from_get_device_config_space() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/devices/block/rootfs/config-space", None).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+    }
+
+    #[test]
+    fn test_try_from_get_device_states() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/devices/state", None).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+    }
+
+    #[test]
+    fn test_try_from_get_vcpu_states() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/vcpu/states", None).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+    }
+
     #[test]
     fn test_try_from_get_machine_config() {
         let (mut sender, receiver) = UnixStream::pair().unwrap();
@@ -660,6 +968,48 @@ pub mod tests {
         connection.try_read().unwrap();
         let req = connection.pop_parsed_request().unwrap();
         ParsedRequest::try_from(&req).unwrap();
+
+        sender
+            .write_all(b"GET /mmds/latest/meta-data HTTP/1.1\r\nX-mmds-format: imds\r\n\r\n")
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        assert_eq!(
+            vmm_action_from_request(ParsedRequest::try_from(&req).unwrap()),
+            VmmAction::GetMMDSPath(
+                "/latest/meta-data".to_string(),
+                vmm::mmds::data_store::OutputFormat::Imds
+            )
+        );
+    }
+
+    #[test]
+    fn test_try_from_get_net() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/network-interfaces/netif", None).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+    }
+
+    #[test]
+    fn test_try_from_get_rate_limiter_state() {
+        for path in [
+            "/drives/rootfs/rate-limiter/state",
+            "/network-interfaces/netif/rate-limiter/state",
+        ] {
+            let (mut sender, receiver) = UnixStream::pair().unwrap();
+            let mut connection = HttpConnection::new(receiver);
+            sender
+                .write_all(http_request("GET", path, None).as_bytes())
+                .unwrap();
+            connection.try_read().unwrap();
+            let req = connection.pop_parsed_request().unwrap();
+            ParsedRequest::try_from(&req).unwrap();
+        }
     }
 
     #[test]
@@ -674,6 +1024,66 @@ pub mod tests {
         ParsedRequest::try_from(&req).unwrap();
     }
 
+    #[test]
+    fn test_try_from_get_cpu_frequencies() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/cpu-config/frequencies", None).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+    }
+
+    #[test]
+    fn test_try_from_get_memory_layout() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/memory/layout", None).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+    }
+
+    #[test]
+    fn test_try_from_get_seccomp() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/seccomp", None).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+    }
+
+    #[test]
+    fn test_try_from_get_health() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/health", None).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+    }
+
+    #[test]
+    fn test_try_from_get_host_kvm_capabilities() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        sender
+            .write_all(http_request("GET", "/host/kvm-capabilities", None).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+    }
+
     #[test]
     fn test_try_from_put_actions() {
         let (mut sender, receiver) = UnixStream::pair().unwrap();
@@ -716,6 +1126,33 @@ pub mod tests {
         ParsedRequest::try_from(&req).unwrap();
     }
 
+    #[test]
+    fn test_try_from_patch_entropy() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        let body = "{ \"rate_limiter\": { \"bandwidth\" : { \"size\": 1000, \"one_time_burst\": \
+                    0, \"refill_time\": 100 } } }";
+        sender
+            .write_all(http_request("PATCH", "/entropy", Some(body)).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+    }
+
+    #[test]
+    fn test_try_from_put_watchdog() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        let body = "{ \"timeout_s\": 10, \"action\": \"Stop\" }";
+        sender
+            .write_all(http_request("PUT", "/watchdog", Some(body)).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+    }
+
     #[test]
     fn test_try_from_put_boot() {
         let (mut sender, receiver) = UnixStream::pair().unwrap();
@@ -760,6 +1197,19 @@ pub mod tests {
         ParsedRequest::try_from(&req).unwrap();
     }
 
+    #[test]
+    fn test_try_from_patch_logger() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        let body = "{ \"level\": \"Debug\", \"filter\": \"vmm::vstate=trace\" }";
+        sender
+            .write_all(http_request("PATCH", "/logger", Some(body)).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+    }
+
     #[test]
     fn test_try_from_put_machine_config() {
         let (mut sender, receiver) = UnixStream::pair().unwrap();
@@ -882,6 +1332,19 @@ pub mod tests {
         ParsedRequest::try_from(&req).unwrap();
     }
 
+    #[test]
+    fn test_try_from_put_vcpu() {
+        let (mut sender, receiver) = UnixStream::pair().unwrap();
+        let mut connection = HttpConnection::new(receiver);
+        let body = "{ \"state\": \"Paused\" }";
+        sender
+            .write_all(http_request("PUT", "/vcpu/0", Some(body)).as_bytes())
+            .unwrap();
+        connection.try_read().unwrap();
+        let req = connection.pop_parsed_request().unwrap();
+        ParsedRequest::try_from(&req).unwrap();
+    }
+
     #[test]
     fn test_try_from_put_vsock() {
         let (mut sender, receiver) = UnixStream::pair().unwrap();
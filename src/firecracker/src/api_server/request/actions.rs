@@ -6,17 +6,18 @@ use vmm::logger::{IncMetric, METRICS};
 use vmm::rpc_interface::VmmAction;
 
 use super::super::parsed_request::{ParsedRequest, RequestError};
-use super::Body;
-#[cfg(target_arch = "aarch64")]
-use super::StatusCode;
+use super::{Body, StatusCode};
 
 // The names of the members from this enum must precisely correspond (as a string) to the possible
 // values of "action_type" from the json request body. This is useful to get a strongly typed
 // struct from the Serde deserialization process.
 #[derive(Debug, Deserialize, Serialize)]
 enum ActionType {
+    CompactGuestMemory,
     FlushMetrics,
     InstanceStart,
+    QuiesceIo,
+    ResetHealth,
     SendCtrlAltDel,
 }
 
@@ -26,6 +27,9 @@ enum ActionType {
 #[serde(deny_unknown_fields)]
 struct ActionBody {
     action_type: ActionType,
+    // Only used by `CompactGuestMemory`.
+    #[serde(default)]
+    target_free_mib: Option<u32>,
 }
 
 pub(crate) fn parse_put_actions(body: &Body) -> Result<ParsedRequest, RequestError> {
@@ -35,8 +39,22 @@ pub(crate) fn parse_put_actions(body: &Body) -> Result<ParsedRequest, RequestErr
     })?;
 
     match action_body.action_type {
+        ActionType::CompactGuestMemory => match action_body.target_free_mib {
+            Some(target_free_mib) => Ok(ParsedRequest::new_sync(VmmAction::CompactGuestMemory(
+                target_free_mib,
+            ))),
+            None => {
+                METRICS.put_api_requests.actions_fails.inc();
+                Err(RequestError::Generic(
+                    StatusCode::BadRequest,
+                    "CompactGuestMemory requires a target_free_mib.".to_string(),
+                ))
+            }
+        },
         ActionType::FlushMetrics => Ok(ParsedRequest::new_sync(VmmAction::FlushMetrics)),
         ActionType::InstanceStart => Ok(ParsedRequest::new_sync(VmmAction::StartMicroVm)),
+        ActionType::QuiesceIo => Ok(ParsedRequest::new_sync(VmmAction::QuiesceIo)),
+        ActionType::ResetHealth => Ok(ParsedRequest::new_sync(VmmAction::ResetHealth)),
         ActionType::SendCtrlAltDel => {
             // SendCtrlAltDel not supported on aarch64.
             #[cfg(target_arch = "aarch64")]
@@ -99,5 +117,42 @@ mod tests {
             let result = parse_put_actions(&Body::new(json));
             assert_eq!(result.unwrap(), req);
         }
+
+        {
+            let json = r#"{
+                "action_type": "QuiesceIo"
+            }"#;
+
+            let req: ParsedRequest = ParsedRequest::new_sync(VmmAction::QuiesceIo);
+            let result = parse_put_actions(&Body::new(json));
+            assert_eq!(result.unwrap(), req);
+        }
+
+        {
+            let json = r#"{
+                "action_type": "ResetHealth"
+            }"#;
+
+            let req: ParsedRequest = ParsedRequest::new_sync(VmmAction::ResetHealth);
+            let result = parse_put_actions(&Body::new(json));
+            assert_eq!(result.unwrap(), req);
+        }
+
+        {
+            let json = r#"{
+                "action_type": "CompactGuestMemory",
+                "target_free_mib": 64
+            }"#;
+
+            let req: ParsedRequest = ParsedRequest::new_sync(VmmAction::CompactGuestMemory(64));
+            let result = parse_put_actions(&Body::new(json));
+            assert_eq!(result.unwrap(), req);
+
+            // The amount of memory to reclaim is mandatory.
+            let json = r#"{
+                "action_type": "CompactGuestMemory"
+            }"#;
+            parse_put_actions(&Body::new(json)).unwrap_err();
+        }
     }
 }
@@ -16,6 +16,7 @@ pub(crate) fn parse_get_balloon(
     match path_second_token {
         Some(stats_path) => match stats_path {
             "statistics" => Ok(ParsedRequest::new_sync(VmmAction::GetBalloonStats)),
+            "features" => Ok(ParsedRequest::new_sync(VmmAction::GetBalloonFeatures)),
             _ => Err(RequestError::Generic(
                 StatusCode::BadRequest,
                 format!("Unrecognized GET request path `{}`.", stats_path),
@@ -63,6 +64,11 @@ mod tests {
         parse_get_balloon(Some("unrelated")).unwrap_err();
 
         parse_get_balloon(Some("statistics")).unwrap();
+
+        assert_eq!(
+            vmm_action_from_request(parse_get_balloon(Some("features")).unwrap()),
+            VmmAction::GetBalloonFeatures
+        );
     }
 
     #[test]
@@ -34,6 +34,23 @@ mod tests {
             kernel_image_path: String::from("/foo/bar"),
             initrd_path: Some(String::from("/bar/foo")),
             boot_args: Some(String::from("foobar")),
+            rng_seed: false,
+        };
+        let parsed_req = parse_put_boot_source(&Body::new(body)).unwrap();
+
+        assert_eq!(
+            parsed_req,
+            ParsedRequest::new_sync(VmmAction::ConfigureBootSource(same_body))
+        );
+
+        let body = r#"{
+            "kernel_image_path": "/foo/bar",
+            "rng_seed": true
+        }"#;
+        let same_body = BootSourceConfig {
+            kernel_image_path: String::from("/foo/bar"),
+            rng_seed: true,
+            ..Default::default()
         };
         let parsed_req = parse_put_boot_source(&Body::new(body)).unwrap();
 
@@ -0,0 +1,71 @@
+// Copyright 2024 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use vmm::rpc_interface::VmmAction;
+
+use super::super::parsed_request::{ParsedRequest, RequestError};
+use super::{Body, StatusCode};
+
+pub(crate) fn parse_put_config(
+    body: &Body,
+    path_second_token: Option<&str>,
+) -> Result<ParsedRequest, RequestError> {
+    let config_json = || {
+        std::str::from_utf8(body.raw())
+            .map(str::to_string)
+            .map_err(|err| {
+                RequestError::Generic(
+                    StatusCode::BadRequest,
+                    format!("The config is not valid UTF-8: {err}"),
+                )
+            })
+    };
+    match path_second_token {
+        Some("validate") => Ok(ParsedRequest::new_sync(VmmAction::ValidateConfig(
+            config_json()?,
+        ))),
+        Some(unrecognized) => Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            format!("Unrecognized PUT request path `{}`.", unrecognized),
+        )),
+        None => Ok(ParsedRequest::new_sync(VmmAction::ApplyConfig(
+            config_json()?,
+        ))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_server::parsed_request::tests::vmm_action_from_request;
+
+    #[test]
+    fn test_parse_put_config_request() {
+        let body = r#"{
+            "boot-source": {
+                "kernel_image_path": "/path/to/kernel"
+            }
+        }"#;
+        assert_eq!(
+            vmm_action_from_request(parse_put_config(&Body::new(body), Some("validate")).unwrap()),
+            VmmAction::ValidateConfig(body.to_string())
+        );
+
+        // The config is only parsed when it is validated.
+        assert_eq!(
+            vmm_action_from_request(
+                parse_put_config(&Body::new("invalid_payload"), Some("validate")).unwrap()
+            ),
+            VmmAction::ValidateConfig(String::from("invalid_payload"))
+        );
+
+        parse_put_config(&Body::new(vec![0xff, 0xfe]), Some("validate")).unwrap_err();
+        parse_put_config(&Body::new(body), Some("apply")).unwrap_err();
+
+        assert_eq!(
+            vmm_action_from_request(parse_put_config(&Body::new(body), None).unwrap()),
+            VmmAction::ApplyConfig(body.to_string())
+        );
+        parse_put_config(&Body::new(vec![0xff, 0xfe]), None).unwrap_err();
+    }
+}
@@ -6,7 +6,26 @@ use vmm::logger::{IncMetric, METRICS};
 use vmm::rpc_interface::VmmAction;
 
 use super::super::parsed_request::{ParsedRequest, RequestError};
-use super::Body;
+use super::{Body, StatusCode};
+
+pub(crate) fn parse_get_cpu_config(
+    path_second_token: Option<&str>,
+) -> Result<ParsedRequest, RequestError> {
+    match path_second_token {
+        Some("frequencies") => Ok(ParsedRequest::new_sync(VmmAction::GetCpuFrequencies)),
+        Some(unrecognized) => Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            format!(
+                "Unrecognized GET request path `/cpu-config/{}`.",
+                unrecognized
+            ),
+        )),
+        None => Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            "Unrecognized GET request path `/cpu-config`.".to_string(),
+        )),
+    }
+}
 
 pub(crate) fn parse_put_cpu_config(body: &Body) -> Result<ParsedRequest, RequestError> {
     METRICS.put_api_requests.cpu_cfg_count.inc();
@@ -30,6 +49,16 @@ mod tests {
     use super::*;
     use crate::api_server::parsed_request::tests::vmm_action_from_request;
 
+    #[test]
+    fn test_parse_get_cpu_config_request() {
+        assert_eq!(
+            vmm_action_from_request(parse_get_cpu_config(Some("frequencies")).unwrap()),
+            VmmAction::GetCpuFrequencies
+        );
+        parse_get_cpu_config(Some("topology")).unwrap_err();
+        parse_get_cpu_config(None).unwrap_err();
+    }
+
     #[test]
     fn test_parse_put_cpu_config_request() {
         let cpu_template = build_test_template();
@@ -0,0 +1,277 @@
+// Copyright 2024 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+#[cfg(feature = "fault-injection")]
+use vmm::devices::virtio::fault_injection::DeviceFault;
+use vmm::rpc_interface::VmmAction;
+#[cfg(feature = "fault-injection")]
+use vmm::vmm_config::devices::DeviceFaultRequest;
+use vmm::vmm_config::devices::{DeviceConfigSpaceRequest, DeviceMemoryRequest, VirtioDeviceType};
+use vmm::vmm_config::metrics::{DeviceMetricsRequest, MetricsDeviceType};
+
+use super::super::parsed_request::{checked_id, ParsedRequest, RequestError};
+#[cfg(feature = "fault-injection")]
+use super::Body;
+use super::StatusCode;
+
+fn parse_metrics_device_type(device_type: &str) -> Result<MetricsDeviceType, RequestError> {
+    match device_type {
+        "block" => Ok(MetricsDeviceType::Block),
+        "net" => Ok(MetricsDeviceType::Net),
+        _ => Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            format!(
+                "Metrics are not available for device type `{}`.",
+                device_type
+            ),
+        )),
+    }
+}
+
+fn parse_virtio_device_type(device_type: &str) -> Result<VirtioDeviceType, RequestError> {
+    match device_type {
+        "block" => Ok(VirtioDeviceType::Block),
+        "net" => Ok(VirtioDeviceType::Net),
+        "vsock" => Ok(VirtioDeviceType::Vsock),
+        "balloon" => Ok(VirtioDeviceType::Balloon),
+        "rng" => Ok(VirtioDeviceType::Rng),
+        "watchdog" => Ok(VirtioDeviceType::Watchdog),
+        _ => Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            format!("Unknown device type `{}`.", device_type),
+        )),
+    }
+}
+
+pub(crate) fn parse_get_device_capabilities(
+    device_type: Option<&str>,
+) -> Result<ParsedRequest, RequestError> {
+    match device_type {
+        Some(device_type) => Ok(ParsedRequest::new_sync(VmmAction::GetDeviceCapabilities(
+            parse_virtio_device_type(device_type)?,
+        ))),
+        None => Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            "Missing device type.".to_string(),
+        )),
+    }
+}
+
+pub(crate) fn parse_get_devices(path_tokens: &[&str]) -> Result<ParsedRequest, RequestError> {
+    match path_tokens {
+        ["state"] => Ok(ParsedRequest::new_sync(VmmAction::GetDeviceStates)),
+        [device_type, device_id, "metrics"] => Ok(ParsedRequest::new_sync(
+            VmmAction::GetDeviceMetrics(DeviceMetricsRequest {
+                device_type: parse_metrics_device_type(device_type)?,
+                device_id: checked_id(device_id)?.to_string(),
+            }),
+        )),
+        [device_type, device_id, "memory"] => Ok(ParsedRequest::new_sync(
+            VmmAction::GetDeviceMemory(DeviceMemoryRequest {
+                device_type: parse_virtio_device_type(device_type)?,
+                device_id: checked_id(device_id)?.to_string(),
+            }),
+        )),
+        [device_type, device_id, "config-space"] => Ok(ParsedRequest::new_sync(
+            VmmAction::GetDeviceConfigSpace(DeviceConfigSpaceRequest {
+                device_type: parse_virtio_device_type(device_type)?,
+                device_id: checked_id(device_id)?.to_string(),
+            }),
+        )),
+        _ => Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            format!(
+                "Unrecognized GET request path `/devices/{}`.",
+                path_tokens.join("/")
+            ),
+        )),
+    }
+}
+
+#[cfg(feature = "fault-injection")]
+pub(crate) fn parse_put_devices(
+    body: &Body,
+    path_tokens: &[&str],
+) -> Result<ParsedRequest, RequestError> {
+    match path_tokens {
+        [device_type, device_id, "inject-fault"] => Ok(ParsedRequest::new_sync(
+            VmmAction::InjectDeviceFault(DeviceFaultRequest {
+                device_type: parse_virtio_device_type(device_type)?,
+                device_id: checked_id(device_id)?.to_string(),
+                fault: serde_json::from_slice::<DeviceFault>(body.raw())?,
+            }),
+        )),
+        _ => Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            format!(
+                "Unrecognized PUT request path `/devices/{}`.",
+                path_tokens.join("/")
+            ),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_server::parsed_request::tests::vmm_action_from_request;
+
+    #[test]
+    fn test_parse_get_devices_request() {
+        let expected = VmmAction::GetDeviceMetrics(DeviceMetricsRequest {
+            device_type: MetricsDeviceType::Block,
+            device_id: String::from("rootfs"),
+        });
+        assert_eq!(
+            vmm_action_from_request(parse_get_devices(&["block", "rootfs", "metrics"]).unwrap()),
+            expected
+        );
+
+        let expected = VmmAction::GetDeviceMetrics(DeviceMetricsRequest {
+            device_type: MetricsDeviceType::Net,
+            device_id: String::from("eth0"),
+        });
+        assert_eq!(
+            vmm_action_from_request(parse_get_devices(&["net", "eth0", "metrics"]).unwrap()),
+            expected
+        );
+
+        // Unknown device type.
+        parse_get_devices(&["balloon", "balloon0", "metrics"]).unwrap_err();
+        // Invalid device id.
+        parse_get_devices(&["block", "root-fs", "metrics"]).unwrap_err();
+        // Unknown or incomplete paths.
+        parse_get_devices(&[]).unwrap_err();
+        parse_get_devices(&["block", "rootfs"]).unwrap_err();
+        parse_get_devices(&["block", "rootfs", "unknown"]).unwrap_err();
+        parse_get_devices(&["block", "rootfs", "metrics", "extra"]).unwrap_err();
+    }
+
+    #[test]
+    fn test_parse_get_device_states_request() {
+        assert_eq!(
+            vmm_action_from_request(parse_get_devices(&["state"]).unwrap()),
+            VmmAction::GetDeviceStates
+        );
+        parse_get_devices(&["state", "extra"]).unwrap_err();
+    }
+
+    #[test]
+    fn test_parse_get_device_capabilities_request() {
+        assert_eq!(
+            vmm_action_from_request(parse_get_device_capabilities(Some("block")).unwrap()),
+            VmmAction::GetDeviceCapabilities(VirtioDeviceType::Block)
+        );
+        assert_eq!(
+            vmm_action_from_request(parse_get_device_capabilities(Some("watchdog")).unwrap()),
+            VmmAction::GetDeviceCapabilities(VirtioDeviceType::Watchdog)
+        );
+
+        // Unknown or missing device type.
+        parse_get_device_capabilities(Some("serial")).unwrap_err();
+        parse_get_device_capabilities(None).unwrap_err();
+    }
+
+    #[test]
+    fn test_parse_get_device_memory_request() {
+        for (name, device_type) in [
+            ("block", VirtioDeviceType::Block),
+            ("net", VirtioDeviceType::Net),
+            ("vsock", VirtioDeviceType::Vsock),
+            ("balloon", VirtioDeviceType::Balloon),
+            ("rng", VirtioDeviceType::Rng),
+            ("watchdog", VirtioDeviceType::Watchdog),
+        ] {
+            let expected = VmmAction::GetDeviceMemory(DeviceMemoryRequest {
+                device_type,
+                device_id: String::from("dev0"),
+            });
+            assert_eq!(
+                vmm_action_from_request(parse_get_devices(&[name, "dev0", "memory"]).unwrap()),
+                expected
+            );
+        }
+
+        // Unknown device type.
+        parse_get_devices(&["serial", "dev0", "memory"]).unwrap_err();
+        // Invalid device id.
+        parse_get_devices(&["block", "root-fs", "memory"]).unwrap_err();
+        parse_get_devices(&["block", "rootfs", "memory", "extra"]).unwrap_err();
+    }
+
+    #[test]
+    fn test_parse_get_device_config_space_request() {
+        for (name, device_type) in [
+            ("block", VirtioDeviceType::Block),
+            ("net", VirtioDeviceType::Net),
+            ("vsock", VirtioDeviceType::Vsock),
+            ("balloon", VirtioDeviceType::Balloon),
+            ("rng", VirtioDeviceType::Rng),
+            ("watchdog", VirtioDeviceType::Watchdog),
+        ] {
+            let expected = VmmAction::GetDeviceConfigSpace(DeviceConfigSpaceRequest {
+                device_type,
+                device_id: String::from("dev0"),
+            });
+            assert_eq!(
+                vmm_action_from_request(
+                    parse_get_devices(&[name, "dev0", "config-space"]).unwrap()
+                ),
+                expected
+            );
+        }
+
+        // Unknown device type.
+        parse_get_devices(&["serial", "dev0", "config-space"]).unwrap_err();
+        // Invalid device id.
+        parse_get_devices(&["block", "root-fs", "config-space"]).unwrap_err();
+        parse_get_devices(&["block", "rootfs", "config-space", "extra"]).unwrap_err();
+    }
+
+    #[test]
+    #[cfg(feature = "fault-injection")]
+    fn test_parse_put_devices_request() {
+        let body = Body::new(r#"{"fault": "block_read_error", "count": 3}"#);
+        let expected = VmmAction::InjectDeviceFault(DeviceFaultRequest {
+            device_type: VirtioDeviceType::Block,
+            device_id: String::from("rootfs"),
+            fault: DeviceFault::BlockReadError { count: 3 },
+        });
+        assert_eq!(
+            vmm_action_from_request(
+                parse_put_devices(&body, &["block", "rootfs", "inject-fault"]).unwrap()
+            ),
+            expected
+        );
+
+        let expected = VmmAction::InjectDeviceFault(DeviceFaultRequest {
+            device_type: VirtioDeviceType::Net,
+            device_id: String::from("eth0"),
+            fault: DeviceFault::Clear,
+        });
+        assert_eq!(
+            vmm_action_from_request(
+                parse_put_devices(
+                    &Body::new(r#"{"fault": "clear"}"#),
+                    &["net", "eth0", "inject-fault"]
+                )
+                .unwrap()
+            ),
+            expected
+        );
+
+        // Invalid fault.
+        parse_put_devices(&Body::new("{}"), &["block", "rootfs", "inject-fault"]).unwrap_err();
+        parse_put_devices(
+            &Body::new(r#"{"fault": "block_read_error"}"#),
+            &["block", "rootfs", "inject-fault"],
+        )
+        .unwrap_err();
+        // Unknown device type or invalid device id.
+        parse_put_devices(&body, &["serial", "rootfs", "inject-fault"]).unwrap_err();
+        parse_put_devices(&body, &["block", "root-fs", "inject-fault"]).unwrap_err();
+        // Unknown or incomplete paths.
+        parse_put_devices(&body, &["block", "rootfs"]).unwrap_err();
+        parse_put_devices(&body, &["block", "rootfs", "metrics"]).unwrap_err();
+    }
+}
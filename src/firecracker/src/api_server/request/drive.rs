@@ -8,6 +8,22 @@ use vmm::vmm_config::drive::{BlockDeviceConfig, BlockDeviceUpdateConfig};
 use super::super::parsed_request::{checked_id, ParsedRequest, RequestError};
 use super::{Body, StatusCode};
 
+pub(crate) fn parse_get_drive(path_tokens: &[&str]) -> Result<ParsedRequest, RequestError> {
+    match path_tokens {
+        [] => Err(RequestError::EmptyID),
+        [id, "rate-limiter", "state"] => Ok(ParsedRequest::new_sync(
+            VmmAction::GetBlockRateLimiterState(checked_id(id)?.to_string()),
+        )),
+        _ => Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            format!(
+                "Unrecognized GET request path `/drives/{}`.",
+                path_tokens.join("/")
+            ),
+        )),
+    }
+}
+
 pub(crate) fn parse_put_drive(
     body: &Body,
     id_from_path: Option<&str>,
@@ -72,6 +88,18 @@ mod tests {
     use super::*;
     use crate::api_server::parsed_request::tests::vmm_action_from_request;
 
+    #[test]
+    fn test_parse_get_drive_request() {
+        assert_eq!(
+            vmm_action_from_request(parse_get_drive(&["foo", "rate-limiter", "state"]).unwrap()),
+            VmmAction::GetBlockRateLimiterState(String::from("foo"))
+        );
+        parse_get_drive(&[]).unwrap_err();
+        parse_get_drive(&["foo"]).unwrap_err();
+        parse_get_drive(&["foo", "rate-limiter"]).unwrap_err();
+        parse_get_drive(&["inv@lid", "rate-limiter", "state"]).unwrap_err();
+    }
+
     #[test]
     fn test_parse_patch_drive_request() {
         parse_patch_drive(&Body::new("invalid_payload"), None).unwrap_err();
@@ -137,6 +165,7 @@ mod tests {
             drive_id: "foo".to_string(),
             path_on_host: Some("dummy".to_string()),
             rate_limiter: None,
+            resize: None,
         };
         assert_eq!(
             vmm_action_from_request(parse_patch_drive(&Body::new(body), Some("foo")).unwrap()),
@@ -150,6 +179,20 @@ mod tests {
         // Must fail since the drive id differs from id_from_path (foo vs bar).
         parse_patch_drive(&Body::new(body), Some("bar")).unwrap_err();
 
+        let body = r#"{
+            "drive_id": "foo",
+            "resize": true
+        }"#;
+        let expected_config = BlockDeviceUpdateConfig {
+            drive_id: "foo".to_string(),
+            resize: Some(true),
+            ..Default::default()
+        };
+        assert_eq!(
+            vmm_action_from_request(parse_patch_drive(&Body::new(body), Some("foo")).unwrap()),
+            VmmAction::UpdateBlockDevice(expected_config)
+        );
+
         let body = r#"{
             "drive_id": "foo",
             "rate_limiter": {
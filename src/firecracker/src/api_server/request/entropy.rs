@@ -12,9 +12,17 @@ pub(crate) fn parse_put_entropy(body: &Body) -> Result<ParsedRequest, RequestErr
     Ok(ParsedRequest::new_sync(VmmAction::SetEntropyDevice(cfg)))
 }
 
+pub(crate) fn parse_patch_entropy(body: &Body) -> Result<ParsedRequest, RequestError> {
+    let cfg = serde_json::from_slice::<EntropyDeviceConfig>(body.raw())?;
+    Ok(ParsedRequest::new_sync(VmmAction::UpdateEntropyDevice(cfg)))
+}
+
 #[cfg(test)]
 mod tests {
+    use vmm::vmm_config::{RateLimiterConfig, TokenBucketConfig};
+
     use super::*;
+    use crate::api_server::parsed_request::tests::vmm_action_from_request;
 
     #[test]
     fn test_parse_put_entropy_request() {
@@ -30,4 +38,41 @@ mod tests {
         let body = r#"{}"#;
         parse_put_entropy(&Body::new(body)).unwrap();
     }
+
+    #[test]
+    fn test_parse_patch_entropy_request() {
+        parse_patch_entropy(&Body::new("invalid_payload")).unwrap_err();
+
+        // PATCH with invalid fields.
+        let body = r#"{
+            "rate_limiter": {
+                "size": 1000
+            }
+        }"#;
+        parse_patch_entropy(&Body::new(body)).unwrap_err();
+
+        // PATCH with valid fields.
+        let body = r#"{
+            "rate_limiter": {
+                "bandwidth": {
+                    "size": 1000,
+                    "refill_time": 100
+                }
+            }
+        }"#;
+        let expected_config = EntropyDeviceConfig {
+            rate_limiter: Some(RateLimiterConfig {
+                bandwidth: Some(TokenBucketConfig {
+                    size: 1000,
+                    one_time_burst: None,
+                    refill_time: 100,
+                }),
+                ops: None,
+            }),
+        };
+        assert_eq!(
+            vmm_action_from_request(parse_patch_entropy(&Body::new(body)).unwrap()),
+            VmmAction::UpdateEntropyDevice(expected_config)
+        );
+    }
 }
@@ -16,11 +16,22 @@ pub(crate) fn parse_put_logger(body: &Body) -> Result<ParsedRequest, RequestErro
     Ok(ParsedRequest::new_sync(VmmAction::ConfigureLogger(config)))
 }
 
+pub(crate) fn parse_patch_logger(body: &Body) -> Result<ParsedRequest, RequestError> {
+    METRICS.patch_api_requests.logger_count.inc();
+    let res = serde_json::from_slice::<vmm::logger::LoggerFilterUpdate>(body.raw());
+    let update = res.inspect_err(|_| {
+        METRICS.patch_api_requests.logger_fails.inc();
+    })?;
+    Ok(ParsedRequest::new_sync(VmmAction::UpdateLoggerFilter(
+        update,
+    )))
+}
+
 #[cfg(test)]
 mod tests {
     use std::path::PathBuf;
 
-    use vmm::logger::{LevelFilter, LoggerConfig};
+    use vmm::logger::{LevelFilter, LogDirectives, LogFormat, LoggerConfig, LoggerFilterUpdate};
 
     use super::*;
     use crate::api_server::parsed_request::tests::vmm_action_from_request;
@@ -40,6 +51,8 @@ mod tests {
             show_level: Some(false),
             show_log_origin: Some(false),
             module: None,
+            format: None,
+            filter: None,
         };
         assert_eq!(
             vmm_action_from_request(parse_put_logger(&Body::new(body)).unwrap()),
@@ -50,7 +63,8 @@ mod tests {
                 "log_path": "log",
                 "level": "DEBUG",
                 "show_level": false,
-                "show_log_origin": false
+                "show_log_origin": false,
+                "format": "json"
               }"#;
 
         let expected_config = LoggerConfig {
@@ -59,12 +73,44 @@ mod tests {
             show_level: Some(false),
             show_log_origin: Some(false),
             module: None,
+            format: Some(LogFormat::Json),
+            filter: None,
+        };
+        assert_eq!(
+            vmm_action_from_request(parse_put_logger(&Body::new(body)).unwrap()),
+            VmmAction::ConfigureLogger(expected_config)
+        );
+
+        let body = r#"{
+                "level": "Warning",
+                "filter": "vmm::devices::virtio::net=debug"
+              }"#;
+
+        let expected_config = LoggerConfig {
+            log_path: None,
+            level: Some(LevelFilter::Warn),
+            show_level: None,
+            show_log_origin: None,
+            module: None,
+            format: None,
+            filter: Some(LogDirectives {
+                level: None,
+                modules: vec![(
+                    String::from("vmm::devices::virtio::net"),
+                    LevelFilter::Debug,
+                )],
+            }),
         };
         assert_eq!(
             vmm_action_from_request(parse_put_logger(&Body::new(body)).unwrap()),
             VmmAction::ConfigureLogger(expected_config)
         );
 
+        let invalid_body = r#"{
+                "filter": "vmm::devices=verbose"
+              }"#;
+        parse_put_logger(&Body::new(invalid_body)).unwrap_err();
+
         let invalid_body = r#"{
             "invalid_field": "log",
             "level": "Warning",
@@ -73,4 +119,42 @@ mod tests {
         }"#;
         parse_put_logger(&Body::new(invalid_body)).unwrap_err();
     }
+
+    #[test]
+    fn test_parse_patch_logger_request() {
+        let body = r#"{
+                "level": "Debug",
+                "filter": "vmm::vstate=trace"
+              }"#;
+        let expected_update = LoggerFilterUpdate {
+            level: Some(LevelFilter::Debug),
+            filter: Some(LogDirectives {
+                level: None,
+                modules: vec![(String::from("vmm::vstate"), LevelFilter::Trace)],
+            }),
+        };
+        assert_eq!(
+            vmm_action_from_request(parse_patch_logger(&Body::new(body)).unwrap()),
+            VmmAction::UpdateLoggerFilter(expected_update)
+        );
+
+        let body = r#"{
+                "level": "Warning"
+              }"#;
+        let expected_update = LoggerFilterUpdate {
+            level: Some(LevelFilter::Warn),
+            filter: None,
+        };
+        assert_eq!(
+            vmm_action_from_request(parse_patch_logger(&Body::new(body)).unwrap()),
+            VmmAction::UpdateLoggerFilter(expected_update)
+        );
+
+        // Only the levels can be updated.
+        let invalid_body = r#"{
+                "log_path": "log"
+              }"#;
+        parse_patch_logger(&Body::new(invalid_body)).unwrap_err();
+        assert!(METRICS.patch_api_requests.logger_fails.count() > 0);
+    }
 }
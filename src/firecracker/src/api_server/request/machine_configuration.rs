@@ -71,8 +71,13 @@ pub(crate) fn parse_patch_machine_config(body: &Body) -> Result<ParsedRequest, R
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
+
     use vmm::cpu_config::templates::StaticCpuTemplate;
-    use vmm::vmm_config::machine_config::HugePageConfig;
+    use vmm::vmm_config::machine_config::{
+        AvxFeature, CpuSignature, CpuTopology, CpuidMaxLeaves, DeviceMemoryConfig, HugePageConfig,
+        ThpAdvice, VcpuIdleStrategy,
+    };
 
     use super::*;
     use crate::api_server::parsed_request::tests::{depr_action_from_req, vmm_action_from_request};
@@ -121,6 +126,34 @@ mod tests {
                 cpu_template: None,
                 track_dirty_pages: Some(false),
                 huge_pages: Some(expected),
+                device_memory: Some(None),
+                vendor_id: Some(None),
+                brand_string: Some(None),
+                cache_levels: Some(None),
+                memslot_size_mib: Some(None),
+                cpu_signature: Some(None),
+                cpu_frequencies: Some(None),
+                cpu_topology: Some(None),
+                min_mem_size_mib: Some(None),
+                host_memory_check: Some(None),
+                kvm_run_retries: Some(None),
+                invariant_tsc: Some(None),
+                thp_advice: Some(None),
+                max_cpuid_leaves: Some(None),
+                vcpu_idle_strategy: Some(None),
+                #[cfg(feature = "mem-poison")]
+                mem_poison: Some(None),
+                cache_line_size: Some(None),
+                mem_backing_file: Some(None),
+                avx_features: Some(None),
+                hypervisor_bit: Some(None),
+                rdrand: Some(None),
+                rdseed: Some(None),
+                monitor_mwait: Some(None),
+                pmu: Some(None),
+                tsc_deadline: Some(None),
+                serial_output_rate_limiter: Some(None),
+                max_desc_chain_len: Some(None),
             };
             assert_eq!(
                 vmm_action_from_request(parse_put_machine_config(&Body::new(body)).unwrap()),
@@ -140,6 +173,34 @@ mod tests {
             cpu_template: Some(StaticCpuTemplate::None),
             track_dirty_pages: Some(false),
             huge_pages: Some(HugePageConfig::None),
+            device_memory: Some(None),
+            vendor_id: Some(None),
+            brand_string: Some(None),
+            cache_levels: Some(None),
+            memslot_size_mib: Some(None),
+            cpu_signature: Some(None),
+            cpu_frequencies: Some(None),
+            cpu_topology: Some(None),
+            min_mem_size_mib: Some(None),
+            host_memory_check: Some(None),
+            kvm_run_retries: Some(None),
+            invariant_tsc: Some(None),
+            thp_advice: Some(None),
+            max_cpuid_leaves: Some(None),
+            vcpu_idle_strategy: Some(None),
+            #[cfg(feature = "mem-poison")]
+            mem_poison: Some(None),
+            cache_line_size: Some(None),
+            mem_backing_file: Some(None),
+            avx_features: Some(None),
+            hypervisor_bit: Some(None),
+            rdrand: Some(None),
+            rdseed: Some(None),
+            monitor_mwait: Some(None),
+            pmu: Some(None),
+            tsc_deadline: Some(None),
+            serial_output_rate_limiter: Some(None),
+            max_desc_chain_len: Some(None),
         };
         assert_eq!(
             vmm_action_from_request(parse_put_machine_config(&Body::new(body)).unwrap()),
@@ -159,6 +220,34 @@ mod tests {
             cpu_template: None,
             track_dirty_pages: Some(true),
             huge_pages: Some(HugePageConfig::None),
+            device_memory: Some(None),
+            vendor_id: Some(None),
+            brand_string: Some(None),
+            cache_levels: Some(None),
+            memslot_size_mib: Some(None),
+            cpu_signature: Some(None),
+            cpu_frequencies: Some(None),
+            cpu_topology: Some(None),
+            min_mem_size_mib: Some(None),
+            host_memory_check: Some(None),
+            kvm_run_retries: Some(None),
+            invariant_tsc: Some(None),
+            thp_advice: Some(None),
+            max_cpuid_leaves: Some(None),
+            vcpu_idle_strategy: Some(None),
+            #[cfg(feature = "mem-poison")]
+            mem_poison: Some(None),
+            cache_line_size: Some(None),
+            mem_backing_file: Some(None),
+            avx_features: Some(None),
+            hypervisor_bit: Some(None),
+            rdrand: Some(None),
+            rdseed: Some(None),
+            monitor_mwait: Some(None),
+            pmu: Some(None),
+            tsc_deadline: Some(None),
+            serial_output_rate_limiter: Some(None),
+            max_desc_chain_len: Some(None),
         };
         assert_eq!(
             vmm_action_from_request(parse_put_machine_config(&Body::new(body)).unwrap()),
@@ -182,6 +271,34 @@ mod tests {
                 cpu_template: Some(StaticCpuTemplate::T2),
                 track_dirty_pages: Some(true),
                 huge_pages: Some(HugePageConfig::None),
+                device_memory: Some(None),
+                vendor_id: Some(None),
+                brand_string: Some(None),
+                cache_levels: Some(None),
+                memslot_size_mib: Some(None),
+                cpu_signature: Some(None),
+                cpu_frequencies: Some(None),
+                cpu_topology: Some(None),
+                min_mem_size_mib: Some(None),
+                host_memory_check: Some(None),
+                kvm_run_retries: Some(None),
+                invariant_tsc: Some(None),
+                thp_advice: Some(None),
+                max_cpuid_leaves: Some(None),
+                vcpu_idle_strategy: Some(None),
+                #[cfg(feature = "mem-poison")]
+                mem_poison: Some(None),
+                cache_line_size: Some(None),
+                mem_backing_file: Some(None),
+                avx_features: Some(None),
+                hypervisor_bit: Some(None),
+                rdrand: Some(None),
+                rdseed: Some(None),
+                monitor_mwait: Some(None),
+                pmu: Some(None),
+                tsc_deadline: Some(None),
+                serial_output_rate_limiter: Some(None),
+                max_desc_chain_len: Some(None),
             };
             assert_eq!(
                 vmm_action_from_request(parse_put_machine_config(&Body::new(body)).unwrap()),
@@ -207,6 +324,34 @@ mod tests {
             cpu_template: None,
             track_dirty_pages: Some(true),
             huge_pages: Some(HugePageConfig::None),
+            device_memory: Some(None),
+            vendor_id: Some(None),
+            brand_string: Some(None),
+            cache_levels: Some(None),
+            memslot_size_mib: Some(None),
+            cpu_signature: Some(None),
+            cpu_frequencies: Some(None),
+            cpu_topology: Some(None),
+            min_mem_size_mib: Some(None),
+            host_memory_check: Some(None),
+            kvm_run_retries: Some(None),
+            invariant_tsc: Some(None),
+            thp_advice: Some(None),
+            max_cpuid_leaves: Some(None),
+            vcpu_idle_strategy: Some(None),
+            #[cfg(feature = "mem-poison")]
+            mem_poison: Some(None),
+            cache_line_size: Some(None),
+            mem_backing_file: Some(None),
+            avx_features: Some(None),
+            hypervisor_bit: Some(None),
+            rdrand: Some(None),
+            rdseed: Some(None),
+            monitor_mwait: Some(None),
+            pmu: Some(None),
+            tsc_deadline: Some(None),
+            serial_output_rate_limiter: Some(None),
+            max_desc_chain_len: Some(None),
         };
         assert_eq!(
             vmm_action_from_request(parse_put_machine_config(&Body::new(body)).unwrap()),
@@ -220,6 +365,691 @@ mod tests {
             "huge_pages": "7M"
         }"#;
         parse_put_machine_config(&Body::new(body)).unwrap_err();
+
+        // 7. Test that a device memory window can be configured
+        let body = r#"{
+            "vcpu_count": 8,
+            "mem_size_mib": 1024,
+            "device_memory": {
+                "guest_address": 68719476736,
+                "size_mib": 1024
+            }
+        }"#;
+        let expected_config = MachineConfigUpdate {
+            vcpu_count: Some(8),
+            mem_size_mib: Some(1024),
+            smt: Some(false),
+            cpu_template: None,
+            track_dirty_pages: Some(false),
+            huge_pages: Some(HugePageConfig::None),
+            device_memory: Some(Some(DeviceMemoryConfig {
+                guest_address: 64 << 30,
+                size_mib: 1024,
+            })),
+            vendor_id: Some(None),
+            brand_string: Some(None),
+            cache_levels: Some(None),
+            memslot_size_mib: Some(None),
+            cpu_signature: Some(None),
+            cpu_frequencies: Some(None),
+            cpu_topology: Some(None),
+            min_mem_size_mib: Some(None),
+            host_memory_check: Some(None),
+            kvm_run_retries: Some(None),
+            invariant_tsc: Some(None),
+            thp_advice: Some(None),
+            max_cpuid_leaves: Some(None),
+            vcpu_idle_strategy: Some(None),
+            #[cfg(feature = "mem-poison")]
+            mem_poison: Some(None),
+            cache_line_size: Some(None),
+            mem_backing_file: Some(None),
+            avx_features: Some(None),
+            hypervisor_bit: Some(None),
+            rdrand: Some(None),
+            rdseed: Some(None),
+            monitor_mwait: Some(None),
+            pmu: Some(None),
+            tsc_deadline: Some(None),
+            serial_output_rate_limiter: Some(None),
+            max_desc_chain_len: Some(None),
+        };
+        assert_eq!(
+            vmm_action_from_request(parse_put_machine_config(&Body::new(body)).unwrap()),
+            VmmAction::UpdateVmConfiguration(expected_config)
+        );
+
+        // 8. Test that the CPU vendor ID and brand string can be configured
+        let body = r#"{
+            "vcpu_count": 8,
+            "mem_size_mib": 1024,
+            "vendor_id": "AuthenticAMD",
+            "brand_string": "Fleet Baseline CPU"
+        }"#;
+        let expected_config = MachineConfigUpdate {
+            vcpu_count: Some(8),
+            mem_size_mib: Some(1024),
+            smt: Some(false),
+            cpu_template: None,
+            track_dirty_pages: Some(false),
+            huge_pages: Some(HugePageConfig::None),
+            device_memory: Some(None),
+            vendor_id: Some(Some("AuthenticAMD".to_string())),
+            brand_string: Some(Some("Fleet Baseline CPU".to_string())),
+            cache_levels: Some(None),
+            memslot_size_mib: Some(None),
+            cpu_signature: Some(None),
+            cpu_frequencies: Some(None),
+            cpu_topology: Some(None),
+            min_mem_size_mib: Some(None),
+            host_memory_check: Some(None),
+            kvm_run_retries: Some(None),
+            invariant_tsc: Some(None),
+            thp_advice: Some(None),
+            max_cpuid_leaves: Some(None),
+            vcpu_idle_strategy: Some(None),
+            #[cfg(feature = "mem-poison")]
+            mem_poison: Some(None),
+            cache_line_size: Some(None),
+            mem_backing_file: Some(None),
+            avx_features: Some(None),
+            hypervisor_bit: Some(None),
+            rdrand: Some(None),
+            rdseed: Some(None),
+            monitor_mwait: Some(None),
+            pmu: Some(None),
+            tsc_deadline: Some(None),
+            serial_output_rate_limiter: Some(None),
+            max_desc_chain_len: Some(None),
+        };
+        assert_eq!(
+            vmm_action_from_request(parse_put_machine_config(&Body::new(body)).unwrap()),
+            VmmAction::UpdateVmConfiguration(expected_config)
+        );
+
+        // 9. Test that the number of cache levels can be configured
+        let body = r#"{
+            "vcpu_count": 8,
+            "mem_size_mib": 1024,
+            "cache_levels": 2
+        }"#;
+        let expected_config = MachineConfigUpdate {
+            vcpu_count: Some(8),
+            mem_size_mib: Some(1024),
+            smt: Some(false),
+            cpu_template: None,
+            track_dirty_pages: Some(false),
+            huge_pages: Some(HugePageConfig::None),
+            device_memory: Some(None),
+            vendor_id: Some(None),
+            brand_string: Some(None),
+            cache_levels: Some(Some(2)),
+            memslot_size_mib: Some(None),
+            cpu_signature: Some(None),
+            cpu_frequencies: Some(None),
+            cpu_topology: Some(None),
+            min_mem_size_mib: Some(None),
+            host_memory_check: Some(None),
+            kvm_run_retries: Some(None),
+            invariant_tsc: Some(None),
+            thp_advice: Some(None),
+            max_cpuid_leaves: Some(None),
+            vcpu_idle_strategy: Some(None),
+            #[cfg(feature = "mem-poison")]
+            mem_poison: Some(None),
+            cache_line_size: Some(None),
+            mem_backing_file: Some(None),
+            avx_features: Some(None),
+            hypervisor_bit: Some(None),
+            rdrand: Some(None),
+            rdseed: Some(None),
+            monitor_mwait: Some(None),
+            pmu: Some(None),
+            tsc_deadline: Some(None),
+            serial_output_rate_limiter: Some(None),
+            max_desc_chain_len: Some(None),
+        };
+        assert_eq!(
+            vmm_action_from_request(parse_put_machine_config(&Body::new(body)).unwrap()),
+            VmmAction::UpdateVmConfiguration(expected_config)
+        );
+
+        // 10. Test that the memory slot size can be configured
+        let body = r#"{
+            "vcpu_count": 8,
+            "mem_size_mib": 1024,
+            "memslot_size_mib": 256
+        }"#;
+        let expected_config = MachineConfigUpdate {
+            vcpu_count: Some(8),
+            mem_size_mib: Some(1024),
+            smt: Some(false),
+            cpu_template: None,
+            track_dirty_pages: Some(false),
+            huge_pages: Some(HugePageConfig::None),
+            device_memory: Some(None),
+            vendor_id: Some(None),
+            brand_string: Some(None),
+            cache_levels: Some(None),
+            memslot_size_mib: Some(Some(256)),
+            cpu_signature: Some(None),
+            cpu_frequencies: Some(None),
+            cpu_topology: Some(None),
+            min_mem_size_mib: Some(None),
+            host_memory_check: Some(None),
+            kvm_run_retries: Some(None),
+            invariant_tsc: Some(None),
+            thp_advice: Some(None),
+            max_cpuid_leaves: Some(None),
+            vcpu_idle_strategy: Some(None),
+            #[cfg(feature = "mem-poison")]
+            mem_poison: Some(None),
+            cache_line_size: Some(None),
+            mem_backing_file: Some(None),
+            avx_features: Some(None),
+            hypervisor_bit: Some(None),
+            rdrand: Some(None),
+            rdseed: Some(None),
+            monitor_mwait: Some(None),
+            pmu: Some(None),
+            tsc_deadline: Some(None),
+            serial_output_rate_limiter: Some(None),
+            max_desc_chain_len: Some(None),
+        };
+        assert_eq!(
+            vmm_action_from_request(parse_put_machine_config(&Body::new(body)).unwrap()),
+            VmmAction::UpdateVmConfiguration(expected_config)
+        );
+
+        // 11. Test that the CPU family, model and stepping can be configured
+        let body = r#"{
+            "vcpu_count": 8,
+            "mem_size_mib": 1024,
+            "cpu_signature": {
+                "family": 6,
+                "model": 85,
+                "stepping": 7
+            }
+        }"#;
+        let expected_config = MachineConfigUpdate {
+            vcpu_count: Some(8),
+            mem_size_mib: Some(1024),
+            smt: Some(false),
+            cpu_template: None,
+            track_dirty_pages: Some(false),
+            huge_pages: Some(HugePageConfig::None),
+            device_memory: Some(None),
+            vendor_id: Some(None),
+            brand_string: Some(None),
+            cache_levels: Some(None),
+            memslot_size_mib: Some(None),
+            cpu_signature: Some(Some(CpuSignature {
+                family: 6,
+                model: 85,
+                stepping: 7,
+            })),
+            cpu_topology: Some(None),
+            min_mem_size_mib: Some(None),
+            host_memory_check: Some(None),
+            kvm_run_retries: Some(None),
+            invariant_tsc: Some(None),
+            thp_advice: Some(None),
+            max_cpuid_leaves: Some(None),
+            vcpu_idle_strategy: Some(None),
+            #[cfg(feature = "mem-poison")]
+            mem_poison: Some(None),
+            cache_line_size: Some(None),
+            mem_backing_file: Some(None),
+            avx_features: Some(None),
+            hypervisor_bit: Some(None),
+            rdrand: Some(None),
+            rdseed: Some(None),
+            monitor_mwait: Some(None),
+            pmu: Some(None),
+            tsc_deadline: Some(None),
+            serial_output_rate_limiter: Some(None),
+            max_desc_chain_len: Some(None),
+        };
+        assert_eq!(
+            vmm_action_from_request(parse_put_machine_config(&Body::new(body)).unwrap()),
+            VmmAction::UpdateVmConfiguration(expected_config)
+        );
+
+        // 12. Test that the CPU signature must be complete
+        let body = r#"{
+            "vcpu_count": 8,
+            "mem_size_mib": 1024,
+            "cpu_signature": {
+                "family": 6
+            }
+        }"#;
+        parse_put_machine_config(&Body::new(body)).unwrap_err();
+
+        // 13. Test that the CPU topology can be configured
+        let body = r#"{
+            "vcpu_count": 8,
+            "mem_size_mib": 1024,
+            "cpu_topology": {
+                "sockets": 2,
+                "cores_per_socket": 4,
+                "threads_per_core": 1
+            }
+        }"#;
+        let expected_config = MachineConfigUpdate {
+            vcpu_count: Some(8),
+            mem_size_mib: Some(1024),
+            smt: Some(false),
+            cpu_template: None,
+            track_dirty_pages: Some(false),
+            huge_pages: Some(HugePageConfig::None),
+            device_memory: Some(None),
+            vendor_id: Some(None),
+            brand_string: Some(None),
+            cache_levels: Some(None),
+            memslot_size_mib: Some(None),
+            cpu_signature: Some(None),
+            cpu_frequencies: Some(None),
+            cpu_topology: Some(Some(CpuTopology {
+                sockets: 2,
+                cores_per_socket: 4,
+                threads_per_core: 1,
+            })),
+            min_mem_size_mib: Some(None),
+            host_memory_check: Some(None),
+            kvm_run_retries: Some(None),
+            invariant_tsc: Some(None),
+            thp_advice: Some(None),
+            max_cpuid_leaves: Some(None),
+            vcpu_idle_strategy: Some(None),
+            #[cfg(feature = "mem-poison")]
+            mem_poison: Some(None),
+            cache_line_size: Some(None),
+            mem_backing_file: Some(None),
+            avx_features: Some(None),
+            hypervisor_bit: Some(None),
+            rdrand: Some(None),
+            rdseed: Some(None),
+            monitor_mwait: Some(None),
+            pmu: Some(None),
+            tsc_deadline: Some(None),
+            serial_output_rate_limiter: Some(None),
+            max_desc_chain_len: Some(None),
+        };
+        assert_eq!(
+            vmm_action_from_request(parse_put_machine_config(&Body::new(body)).unwrap()),
+            VmmAction::UpdateVmConfiguration(expected_config)
+        );
+
+        // 14. Test that the minimum memory size can be configured
+        let body = r#"{
+            "vcpu_count": 8,
+            "mem_size_mib": 1024,
+            "min_mem_size_mib": 512
+        }"#;
+        let expected_config = MachineConfigUpdate {
+            vcpu_count: Some(8),
+            mem_size_mib: Some(1024),
+            smt: Some(false),
+            cpu_template: None,
+            track_dirty_pages: Some(false),
+            huge_pages: Some(HugePageConfig::None),
+            device_memory: Some(None),
+            vendor_id: Some(None),
+            brand_string: Some(None),
+            cache_levels: Some(None),
+            memslot_size_mib: Some(None),
+            cpu_signature: Some(None),
+            cpu_frequencies: Some(None),
+            cpu_topology: Some(None),
+            min_mem_size_mib: Some(Some(512)),
+            host_memory_check: Some(None),
+            kvm_run_retries: Some(None),
+            invariant_tsc: Some(None),
+            thp_advice: Some(None),
+            max_cpuid_leaves: Some(None),
+            vcpu_idle_strategy: Some(None),
+            #[cfg(feature = "mem-poison")]
+            mem_poison: Some(None),
+            cache_line_size: Some(None),
+            mem_backing_file: Some(None),
+            avx_features: Some(None),
+            hypervisor_bit: Some(None),
+            rdrand: Some(None),
+            rdseed: Some(None),
+            monitor_mwait: Some(None),
+            pmu: Some(None),
+            tsc_deadline: Some(None),
+            serial_output_rate_limiter: Some(None),
+            max_desc_chain_len: Some(None),
+        };
+        assert_eq!(
+            vmm_action_from_request(parse_put_machine_config(&Body::new(body)).unwrap()),
+            VmmAction::UpdateVmConfiguration(expected_config)
+        );
+
+        // 15. Test that the number of KVM_RUN retries can be configured
+        let body = r#"{
+            "vcpu_count": 8,
+            "mem_size_mib": 1024,
+            "kvm_run_retries": 16
+        }"#;
+        let expected_config = MachineConfigUpdate {
+            vcpu_count: Some(8),
+            mem_size_mib: Some(1024),
+            smt: Some(false),
+            cpu_template: None,
+            track_dirty_pages: Some(false),
+            huge_pages: Some(HugePageConfig::None),
+            device_memory: Some(None),
+            vendor_id: Some(None),
+            brand_string: Some(None),
+            cache_levels: Some(None),
+            memslot_size_mib: Some(None),
+            cpu_signature: Some(None),
+            cpu_frequencies: Some(None),
+            cpu_topology: Some(None),
+            min_mem_size_mib: Some(None),
+            host_memory_check: Some(None),
+            kvm_run_retries: Some(Some(16)),
+            invariant_tsc: Some(None),
+            thp_advice: Some(None),
+            max_cpuid_leaves: Some(None),
+            vcpu_idle_strategy: Some(None),
+            #[cfg(feature = "mem-poison")]
+            mem_poison: Some(None),
+            cache_line_size: Some(None),
+            mem_backing_file: Some(None),
+            avx_features: Some(None),
+            hypervisor_bit: Some(None),
+            rdrand: Some(None),
+            rdseed: Some(None),
+            monitor_mwait: Some(None),
+            pmu: Some(None),
+            tsc_deadline: Some(None),
+            serial_output_rate_limiter: Some(None),
+            max_desc_chain_len: Some(None),
+        };
+        assert_eq!(
+            vmm_action_from_request(parse_put_machine_config(&Body::new(body)).unwrap()),
+            VmmAction::UpdateVmConfiguration(expected_config)
+        );
+
+        // 16. Test that the invariant TSC can be configured
+        let body = r#"{
+            "vcpu_count": 8,
+            "mem_size_mib": 1024,
+            "invariant_tsc": true
+        }"#;
+        let expected_config = MachineConfigUpdate {
+            vcpu_count: Some(8),
+            mem_size_mib: Some(1024),
+            smt: Some(false),
+            cpu_template: None,
+            track_dirty_pages: Some(false),
+            huge_pages: Some(HugePageConfig::None),
+            device_memory: Some(None),
+            vendor_id: Some(None),
+            brand_string: Some(None),
+            cache_levels: Some(None),
+            memslot_size_mib: Some(None),
+            cpu_signature: Some(None),
+            cpu_frequencies: Some(None),
+            cpu_topology: Some(None),
+            min_mem_size_mib: Some(None),
+            host_memory_check: Some(None),
+            kvm_run_retries: Some(None),
+            invariant_tsc: Some(Some(true)),
+            thp_advice: Some(None),
+            max_cpuid_leaves: Some(None),
+            vcpu_idle_strategy: Some(None),
+            #[cfg(feature = "mem-poison")]
+            mem_poison: Some(None),
+            cache_line_size: Some(None),
+            mem_backing_file: Some(None),
+            avx_features: Some(None),
+            hypervisor_bit: Some(None),
+            rdrand: Some(None),
+            rdseed: Some(None),
+            monitor_mwait: Some(None),
+            pmu: Some(None),
+            tsc_deadline: Some(None),
+            serial_output_rate_limiter: Some(None),
+            max_desc_chain_len: Some(None),
+        };
+        assert_eq!(
+            vmm_action_from_request(parse_put_machine_config(&Body::new(body)).unwrap()),
+            VmmAction::UpdateVmConfiguration(expected_config)
+        );
+
+        // 17. Test that the transparent huge page advice can be configured
+        let body = r#"{
+            "vcpu_count": 8,
+            "mem_size_mib": 1024,
+            "thp_advice": "Hugepage"
+        }"#;
+        let expected_config = MachineConfigUpdate {
+            vcpu_count: Some(8),
+            mem_size_mib: Some(1024),
+            smt: Some(false),
+            cpu_template: None,
+            track_dirty_pages: Some(false),
+            huge_pages: Some(HugePageConfig::None),
+            device_memory: Some(None),
+            vendor_id: Some(None),
+            brand_string: Some(None),
+            cache_levels: Some(None),
+            memslot_size_mib: Some(None),
+            cpu_signature: Some(None),
+            cpu_frequencies: Some(None),
+            cpu_topology: Some(None),
+            min_mem_size_mib: Some(None),
+            host_memory_check: Some(None),
+            kvm_run_retries: Some(None),
+            invariant_tsc: Some(None),
+            thp_advice: Some(Some(ThpAdvice::Hugepage)),
+            max_cpuid_leaves: Some(None),
+            vcpu_idle_strategy: Some(None),
+            #[cfg(feature = "mem-poison")]
+            mem_poison: Some(None),
+            cache_line_size: Some(None),
+            mem_backing_file: Some(None),
+            avx_features: Some(None),
+            hypervisor_bit: Some(None),
+            rdrand: Some(None),
+            rdseed: Some(None),
+            monitor_mwait: Some(None),
+            pmu: Some(None),
+            tsc_deadline: Some(None),
+            serial_output_rate_limiter: Some(None),
+            max_desc_chain_len: Some(None),
+        };
+        assert_eq!(
+            vmm_action_from_request(parse_put_machine_config(&Body::new(body)).unwrap()),
+            VmmAction::UpdateVmConfiguration(expected_config)
+        );
+
+        // 18. Test that the maximum CPUID leaves can be configured
+        let body = r#"{
+            "vcpu_count": 8,
+            "mem_size_mib": 1024,
+            "max_cpuid_leaves": {
+                "standard": 13,
+                "extended": 2147483656
+            }
+        }"#;
+        let expected_config = MachineConfigUpdate {
+            vcpu_count: Some(8),
+            mem_size_mib: Some(1024),
+            smt: Some(false),
+            cpu_template: None,
+            track_dirty_pages: Some(false),
+            huge_pages: Some(HugePageConfig::None),
+            device_memory: Some(None),
+            vendor_id: Some(None),
+            brand_string: Some(None),
+            cache_levels: Some(None),
+            memslot_size_mib: Some(None),
+            cpu_signature: Some(None),
+            cpu_frequencies: Some(None),
+            cpu_topology: Some(None),
+            min_mem_size_mib: Some(None),
+            host_memory_check: Some(None),
+            kvm_run_retries: Some(None),
+            invariant_tsc: Some(None),
+            thp_advice: Some(None),
+            max_cpuid_leaves: Some(Some(CpuidMaxLeaves {
+                standard: Some(0xd),
+                extended: Some(0x8000_0008),
+            })),
+            vcpu_idle_strategy: Some(None),
+            #[cfg(feature = "mem-poison")]
+            mem_poison: Some(None),
+            cache_line_size: Some(None),
+            mem_backing_file: Some(None),
+            avx_features: Some(None),
+            hypervisor_bit: Some(None),
+            rdrand: Some(None),
+            rdseed: Some(None),
+            monitor_mwait: Some(None),
+            pmu: Some(None),
+            tsc_deadline: Some(None),
+            serial_output_rate_limiter: Some(None),
+            max_desc_chain_len: Some(None),
+        };
+        assert_eq!(
+            vmm_action_from_request(parse_put_machine_config(&Body::new(body)).unwrap()),
+            VmmAction::UpdateVmConfiguration(expected_config)
+        );
+
+        // Only one of the maximums can be configured.
+        let body = r#"{
+            "vcpu_count": 8,
+            "mem_size_mib": 1024,
+            "max_cpuid_leaves": {
+                "standard": 13
+            }
+        }"#;
+        let VmmAction::UpdateVmConfiguration(config) =
+            vmm_action_from_request(parse_put_machine_config(&Body::new(body)).unwrap())
+        else {
+            panic!("Unexpected action");
+        };
+        assert_eq!(
+            config.max_cpuid_leaves,
+            Some(CpuidMaxLeaves {
+                standard: Some(0xd),
+                extended: None,
+            })
+        );
+
+        // Unknown fields are rejected.
+        let body = r#"{
+            "vcpu_count": 8,
+            "mem_size_mib": 1024,
+            "max_cpuid_leaves": {
+                "hypervisor": 1073741825
+            }
+        }"#;
+        parse_put_machine_config(&Body::new(body)).unwrap_err();
+
+        // 19. Test that the vCPU idle strategy can be configured
+        let body = r#"{
+            "vcpu_count": 1,
+            "mem_size_mib": 1024,
+            "vcpu_idle_strategy": "Block"
+        }"#;
+        let VmmAction::UpdateVmConfiguration(config) =
+            vmm_action_from_request(parse_put_machine_config(&Body::new(body)).unwrap())
+        else {
+            panic!("Unexpected action");
+        };
+        assert_eq!(config.vcpu_idle_strategy, Some(VcpuIdleStrategy::Block));
+
+        let body = r#"{
+            "vcpu_count": 1,
+            "mem_size_mib": 1024,
+            "vcpu_idle_strategy": "Spin"
+        }"#;
+        parse_put_machine_config(&Body::new(body)).unwrap_err();
+
+        // 20. Test that the memory poison pattern can only be configured in builds with the
+        // `mem-poison` feature
+        let body = r#"{
+            "vcpu_count": 1,
+            "mem_size_mib": 1024,
+            "mem_poison": 165
+        }"#;
+        #[cfg(feature = "mem-poison")]
+        {
+            let VmmAction::UpdateVmConfiguration(config) =
+                vmm_action_from_request(parse_put_machine_config(&Body::new(body)).unwrap())
+            else {
+                panic!("Unexpected action");
+            };
+            assert_eq!(config.mem_poison, Some(0xa5));
+
+            let body = r#"{
+                "vcpu_count": 1,
+                "mem_size_mib": 1024,
+                "mem_poison": 256
+            }"#;
+            parse_put_machine_config(&Body::new(body)).unwrap_err();
+        }
+        #[cfg(not(feature = "mem-poison"))]
+        parse_put_machine_config(&Body::new(body)).unwrap_err();
+
+        // 21. Test that the cache line size can be configured
+        let body = r#"{
+            "vcpu_count": 1,
+            "mem_size_mib": 1024,
+            "cache_line_size": 128
+        }"#;
+        let VmmAction::UpdateVmConfiguration(config) =
+            vmm_action_from_request(parse_put_machine_config(&Body::new(body)).unwrap())
+        else {
+            panic!("Unexpected action");
+        };
+        assert_eq!(config.cache_line_size, Some(128));
+
+        // 22. Test that the AVX features can be configured
+        let body = r#"{
+            "vcpu_count": 1,
+            "mem_size_mib": 1024,
+            "avx_features": {"avx512f": false, "avx512_4vnniw": false, "avx2": true}
+        }"#;
+        let VmmAction::UpdateVmConfiguration(config) =
+            vmm_action_from_request(parse_put_machine_config(&Body::new(body)).unwrap())
+        else {
+            panic!("Unexpected action");
+        };
+        assert_eq!(
+            config.avx_features,
+            Some(BTreeMap::from([
+                (AvxFeature::Avx2, true),
+                (AvxFeature::Avx512f, false),
+                (AvxFeature::Avx5124Vnniw, false),
+            ]))
+        );
+
+        let body = r#"{
+            "vcpu_count": 1,
+            "mem_size_mib": 1024,
+            "avx_features": {"sse2": false}
+        }"#;
+        parse_put_machine_config(&Body::new(body)).unwrap_err();
+
+        // 23. Test that the hypervisor present bit can be configured
+        let body = r#"{
+            "vcpu_count": 1,
+            "mem_size_mib": 1024,
+            "hypervisor_bit": false
+        }"#;
+        let VmmAction::UpdateVmConfiguration(config) =
+            vmm_action_from_request(parse_put_machine_config(&Body::new(body)).unwrap())
+        else {
+            panic!("Unexpected action");
+        };
+        assert_eq!(config.hypervisor_bit, Some(false));
     }
 
     #[test]
@@ -0,0 +1,40 @@
+// Copyright 2024 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use vmm::rpc_interface::VmmAction;
+
+use super::super::parsed_request::{ParsedRequest, RequestError};
+use super::StatusCode;
+
+pub(crate) fn parse_get_memory(
+    path_second_token: Option<&str>,
+) -> Result<ParsedRequest, RequestError> {
+    match path_second_token {
+        Some("layout") => Ok(ParsedRequest::new_sync(VmmAction::GetMemoryLayout)),
+        Some(unrecognized) => Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            format!("Unrecognized GET request path `/memory/{}`.", unrecognized),
+        )),
+        None => Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            "Unrecognized GET request path `/memory`.".to_string(),
+        )),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_server::parsed_request::tests::vmm_action_from_request;
+
+    #[test]
+    fn test_parse_get_memory_request() {
+        assert_eq!(
+            vmm_action_from_request(parse_get_memory(Some("layout")).unwrap()),
+            VmmAction::GetMemoryLayout
+        );
+
+        parse_get_memory(None).unwrap_err();
+        parse_get_memory(Some("unknown")).unwrap_err();
+    }
+}
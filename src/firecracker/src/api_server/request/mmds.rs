@@ -1,18 +1,54 @@
 // Copyright 2019 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+use std::collections::HashMap;
+
 use micro_http::StatusCode;
 use vmm::logger::{IncMetric, METRICS};
-use vmm::mmds::data_store::MmdsVersion;
+use vmm::mmds::data_store::{MmdsVersion, OutputFormat};
 use vmm::rpc_interface::VmmAction;
 use vmm::vmm_config::mmds::MmdsConfig;
 
 use super::super::parsed_request::{ParsedRequest, RequestError};
 use super::Body;
 
-pub(crate) fn parse_get_mmds() -> Result<ParsedRequest, RequestError> {
+/// Header selecting the format of the MMDS contents returned by a GET request, `json` or
+/// `imds`. The `Accept` header cannot be used for this, since requests without one are
+/// parsed as accepting plain text.
+const MMDS_FORMAT_HEADER: &str = "X-mmds-format";
+
+fn output_format(headers: &HashMap<String, String>) -> Result<OutputFormat, RequestError> {
+    let format = headers
+        .iter()
+        .find(|(name, _)| name.eq_ignore_ascii_case(MMDS_FORMAT_HEADER))
+        .map(|(_, value)| value.trim());
+    match format {
+        None => Ok(OutputFormat::Json),
+        Some(format) if format.eq_ignore_ascii_case("json") => Ok(OutputFormat::Json),
+        Some(format) if format.eq_ignore_ascii_case("imds") => Ok(OutputFormat::Imds),
+        Some(format) => Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            format!("Invalid {} header value `{}`.", MMDS_FORMAT_HEADER, format),
+        )),
+    }
+}
+
+/// Parses a GET request on `/mmds` or on a path below it. The contents are returned as JSON,
+/// unless the IMDS plain text format is requested through the `X-mmds-format` header.
+pub(crate) fn parse_get_mmds(
+    path_tokens: &[&str],
+    headers: &HashMap<String, String>,
+) -> Result<ParsedRequest, RequestError> {
     METRICS.get_api_requests.mmds_count.inc();
-    Ok(ParsedRequest::new_sync(VmmAction::GetMMDS))
+    let format = output_format(headers)?;
+    if path_tokens.is_empty() && format == OutputFormat::Json {
+        return Ok(ParsedRequest::new_sync(VmmAction::GetMMDS));
+    }
+
+    Ok(ParsedRequest::new_sync(VmmAction::GetMMDSPath(
+        format!("/{}", path_tokens.join("/")),
+        format,
+    )))
 }
 
 fn parse_put_mmds_config(body: &Body) -> Result<ParsedRequest, RequestError> {
@@ -68,12 +104,45 @@ pub(crate) fn parse_patch_mmds(body: &Body) -> Result<ParsedRequest, RequestErro
 #[cfg(test)]
 mod tests {
     use super::*;
-    use crate::api_server::parsed_request::tests::depr_action_from_req;
+    use crate::api_server::parsed_request::tests::{depr_action_from_req, vmm_action_from_request};
 
     #[test]
     fn test_parse_get_mmds_request() {
-        parse_get_mmds().unwrap();
+        let headers =
+            |format: &str| HashMap::from([(MMDS_FORMAT_HEADER.to_string(), format.to_string())]);
+
+        // The contents are returned as JSON by default.
+        for headers in [HashMap::new(), headers("json"), headers("JSON")] {
+            assert_eq!(
+                vmm_action_from_request(parse_get_mmds(&[], &headers).unwrap()),
+                VmmAction::GetMMDS
+            );
+            assert_eq!(
+                vmm_action_from_request(
+                    parse_get_mmds(&["latest", "meta-data"], &headers).unwrap()
+                ),
+                VmmAction::GetMMDSPath("/latest/meta-data".to_string(), OutputFormat::Json)
+            );
+        }
         assert!(METRICS.get_api_requests.mmds_count.count() > 0);
+
+        // The IMDS format is also served at the root path.
+        let headers_imds = HashMap::from([("x-mmds-format".to_string(), "imds".to_string())]);
+        assert_eq!(
+            vmm_action_from_request(parse_get_mmds(&[], &headers_imds).unwrap()),
+            VmmAction::GetMMDSPath("/".to_string(), OutputFormat::Imds)
+        );
+        assert_eq!(
+            vmm_action_from_request(
+                parse_get_mmds(&["latest", "meta-data", "instance-id"], &headers("imds")).unwrap()
+            ),
+            VmmAction::GetMMDSPath(
+                "/latest/meta-data/instance-id".to_string(),
+                OutputFormat::Imds
+            )
+        );
+
+        parse_get_mmds(&[], &headers("xml")).unwrap_err();
     }
 
     #[test]
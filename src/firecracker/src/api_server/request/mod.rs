@@ -4,16 +4,24 @@
 pub mod actions;
 pub mod balloon;
 pub mod boot_source;
+pub mod config;
 pub mod cpu_configuration;
+pub mod devices;
 pub mod drive;
 pub mod entropy;
+pub mod health;
+pub mod host;
 pub mod instance_info;
 pub mod logger;
 pub mod machine_configuration;
+pub mod memory;
 pub mod metrics;
 pub mod mmds;
 pub mod net;
+pub mod seccomp;
 pub mod snapshot;
+pub mod vcpu;
 pub mod version;
 pub mod vsock;
+pub mod watchdog;
 pub use micro_http::{Body, Method, StatusCode};
@@ -8,6 +8,25 @@ use vmm::vmm_config::net::{NetworkInterfaceConfig, NetworkInterfaceUpdateConfig}
 use super::super::parsed_request::{checked_id, ParsedRequest, RequestError};
 use super::{Body, StatusCode};
 
+pub(crate) fn parse_get_net(path_tokens: &[&str]) -> Result<ParsedRequest, RequestError> {
+    match path_tokens {
+        [] => Err(RequestError::EmptyID),
+        [id] => Ok(ParsedRequest::new_sync(VmmAction::GetNetworkInterface(
+            checked_id(id)?.to_string(),
+        ))),
+        [id, "rate-limiter", "state"] => Ok(ParsedRequest::new_sync(
+            VmmAction::GetNetRateLimiterState(checked_id(id)?.to_string()),
+        )),
+        _ => Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            format!(
+                "Unrecognized GET request path `/network-interfaces/{}`.",
+                path_tokens.join("/")
+            ),
+        )),
+    }
+}
+
 pub(crate) fn parse_put_net(
     body: &Body,
     id_from_path: Option<&str>,
@@ -76,6 +95,23 @@ mod tests {
     use super::*;
     use crate::api_server::parsed_request::tests::vmm_action_from_request;
 
+    #[test]
+    fn test_parse_get_net_request() {
+        assert_eq!(
+            vmm_action_from_request(parse_get_net(&["foo"]).unwrap()),
+            VmmAction::GetNetworkInterface(String::from("foo"))
+        );
+        assert_eq!(
+            vmm_action_from_request(parse_get_net(&["foo", "rate-limiter", "state"]).unwrap()),
+            VmmAction::GetNetRateLimiterState(String::from("foo"))
+        );
+        parse_get_net(&[]).unwrap_err();
+        parse_get_net(&["inv@lid"]).unwrap_err();
+        parse_get_net(&["inv@lid", "rate-limiter", "state"]).unwrap_err();
+        parse_get_net(&["foo", "rate-limiter"]).unwrap_err();
+        parse_get_net(&["foo", "unknown"]).unwrap_err();
+    }
+
     #[test]
     fn test_parse_put_net_request() {
         let body = r#"{
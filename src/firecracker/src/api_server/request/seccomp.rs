@@ -0,0 +1,24 @@
+// Copyright 2024 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use vmm::rpc_interface::VmmAction;
+
+use super::super::parsed_request::{ParsedRequest, RequestError};
+
+pub(crate) fn parse_get_seccomp() -> Result<ParsedRequest, RequestError> {
+    Ok(ParsedRequest::new_sync(VmmAction::GetSeccompConfig))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::super::parsed_request::RequestAction;
+    use super::*;
+
+    #[test]
+    fn test_parse_get_seccomp_request() {
+        match parse_get_seccomp().unwrap().into_parts() {
+            (RequestAction::Sync(action), _) if *action == VmmAction::GetSeccompConfig => {}
+            _ => panic!("Test failed."),
+        }
+    }
+}
@@ -0,0 +1,93 @@
+// Copyright 2024 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use vmm::rpc_interface::VmmAction;
+use vmm::vmm_config::snapshot::{Vm, VmState};
+
+use super::super::parsed_request::{ParsedRequest, RequestError};
+use super::{Body, StatusCode};
+
+pub(crate) fn parse_get_vcpu(
+    path_second_token: Option<&str>,
+) -> Result<ParsedRequest, RequestError> {
+    match path_second_token {
+        Some("states") => Ok(ParsedRequest::new_sync(VmmAction::GetVcpuStates)),
+        Some(unrecognized) => Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            format!("Unrecognized GET request path `/vcpu/{}`.", unrecognized),
+        )),
+        None => Err(RequestError::Generic(
+            StatusCode::BadRequest,
+            "Unrecognized GET request path `/vcpu`.".to_string(),
+        )),
+    }
+}
+
+pub(crate) fn parse_put_vcpu(
+    body: &Body,
+    index_from_path: Option<&str>,
+) -> Result<ParsedRequest, RequestError> {
+    let index = match index_from_path {
+        Some(index) => index.parse::<usize>().map_err(|_| {
+            RequestError::Generic(
+                StatusCode::BadRequest,
+                format!("Invalid vCPU index: {index}"),
+            )
+        })?,
+        None => return Err(RequestError::EmptyID),
+    };
+
+    let vm = serde_json::from_slice::<Vm>(body.raw())?;
+
+    match vm.state {
+        VmState::Paused => Ok(ParsedRequest::new_sync(VmmAction::PauseVcpu(index))),
+        VmState::Resumed => Ok(ParsedRequest::new_sync(VmmAction::ResumeVcpu(index))),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::api_server::parsed_request::tests::vmm_action_from_request;
+
+    #[test]
+    fn test_parse_get_vcpu_request() {
+        assert_eq!(
+            vmm_action_from_request(parse_get_vcpu(Some("states")).unwrap()),
+            VmmAction::GetVcpuStates
+        );
+
+        parse_get_vcpu(None).unwrap_err();
+        parse_get_vcpu(Some("0")).unwrap_err();
+    }
+
+    #[test]
+    fn test_parse_put_vcpu_request() {
+        let body = r#"{
+            "state": "Paused"
+        }"#;
+        parse_put_vcpu(&Body::new(body), None).unwrap_err();
+        parse_put_vcpu(&Body::new(body), Some("first")).unwrap_err();
+        parse_put_vcpu(&Body::new(body), Some("-1")).unwrap_err();
+        parse_put_vcpu(&Body::new("invalid_payload"), Some("0")).unwrap_err();
+
+        // PUT with an invalid state.
+        let body_invalid = r#"{
+            "state": "Running"
+        }"#;
+        parse_put_vcpu(&Body::new(body_invalid), Some("0")).unwrap_err();
+
+        assert_eq!(
+            vmm_action_from_request(parse_put_vcpu(&Body::new(body), Some("1")).unwrap()),
+            VmmAction::PauseVcpu(1)
+        );
+
+        let body = r#"{
+            "state": "Resumed"
+        }"#;
+        assert_eq!(
+            vmm_action_from_request(parse_put_vcpu(&Body::new(body), Some("1")).unwrap()),
+            VmmAction::ResumeVcpu(1)
+        );
+    }
+}
@@ -0,0 +1,72 @@
+// Copyright 2024 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use vmm::rpc_interface::VmmAction;
+use vmm::vmm_config::watchdog::WatchdogDeviceConfig;
+
+use super::super::parsed_request::{ParsedRequest, RequestError};
+use super::Body;
+
+pub(crate) fn parse_put_watchdog(body: &Body) -> Result<ParsedRequest, RequestError> {
+    let cfg = serde_json::from_slice::<WatchdogDeviceConfig>(body.raw())?;
+    Ok(ParsedRequest::new_sync(VmmAction::SetWatchdogDevice(cfg)))
+}
+
+#[cfg(test)]
+mod tests {
+    use vmm::vmm_config::watchdog::WatchdogAction;
+
+    use super::*;
+    use crate::api_server::parsed_request::tests::vmm_action_from_request;
+
+    #[test]
+    fn test_parse_put_watchdog_request() {
+        parse_put_watchdog(&Body::new("invalid_payload")).unwrap_err();
+
+        // PUT with invalid fields.
+        let body = r#"{
+            "timeout_s": 10,
+            "some_id": 4
+        }"#;
+        parse_put_watchdog(&Body::new(body)).unwrap_err();
+
+        // PUT with an invalid action.
+        let body = r#"{
+            "timeout_s": 10,
+            "action": "Explode"
+        }"#;
+        parse_put_watchdog(&Body::new(body)).unwrap_err();
+
+        // PUT without the mandatory timeout.
+        let body = r#"{
+            "action": "Stop"
+        }"#;
+        parse_put_watchdog(&Body::new(body)).unwrap_err();
+
+        // PUT with valid fields, the action defaults to `Log`.
+        let body = r#"{
+            "timeout_s": 10
+        }"#;
+        let expected = VmmAction::SetWatchdogDevice(WatchdogDeviceConfig {
+            timeout_s: 10,
+            action: WatchdogAction::Log,
+        });
+        assert_eq!(
+            vmm_action_from_request(parse_put_watchdog(&Body::new(body)).unwrap()),
+            expected
+        );
+
+        let body = r#"{
+            "timeout_s": 10,
+            "action": "Reset"
+        }"#;
+        let expected = VmmAction::SetWatchdogDevice(WatchdogDeviceConfig {
+            timeout_s: 10,
+            action: WatchdogAction::Reset,
+        });
+        assert_eq!(
+            vmm_action_from_request(parse_put_watchdog(&Body::new(body)).unwrap()),
+            expected
+        );
+    }
+}
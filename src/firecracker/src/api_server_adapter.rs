@@ -3,9 +3,10 @@
 
 use std::os::unix::io::AsRawFd;
 use std::path::PathBuf;
-use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
-use std::sync::{Arc, Mutex};
+use std::sync::mpsc::{channel, Receiver, RecvTimeoutError, Sender, TryRecvError};
+use std::sync::{Arc, Mutex, OnceLock};
 use std::thread;
+use std::time::{Duration, Instant};
 
 use event_manager::{EventOps, Events, MutEventSubscriber, SubscriberOps};
 use seccompiler::BpfThreadMap;
@@ -13,7 +14,7 @@ use vmm::logger::{error, warn, ProcessTimeReporter};
 use vmm::resources::VmResources;
 use vmm::rpc_interface::{
     ApiRequest, ApiResponse, BuildMicrovmFromRequestsError, PrebootApiController,
-    RuntimeApiController, VmmAction,
+    RuntimeApiController, VmmAction, VmmActionError, VmmData,
 };
 use vmm::vmm_config::instance_info::InstanceInfo;
 use vmm::{EventManager, FcExitCode, Vmm};
@@ -36,31 +37,57 @@ pub enum ApiServerError {
     BuildFromJson(crate::BuildFromJsonError),
 }
 
+/// How often the requests sent while the API server is shutting down are checked for.
+const REJECT_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
+/// The error the API requests are answered with once the microVM has shut down.
+fn shutdown_error() -> VmmActionError {
+    VmmActionError::NotSupported("The microVM has shut down.".to_string())
+}
+
 #[derive(Debug)]
 struct ApiServerAdapter {
     api_event_fd: EventFd,
     from_api: Receiver<ApiRequest>,
     to_api: Sender<ApiResponse>,
     controller: RuntimeApiController,
+    // Signaled by the balloon device, if any, when a guest memory compaction completes.
+    compaction_evt: Option<EventFd>,
+    // Whether the response to a `CompactGuestMemory` request waits for the compaction to
+    // complete.
+    compaction_pending: bool,
 }
 
 impl ApiServerAdapter {
-    /// Runs the vmm to completion, while any arising control events are deferred
-    /// to a `RuntimeApiController`.
-    fn run_microvm(
+    fn new(
         api_event_fd: EventFd,
         from_api: Receiver<ApiRequest>,
         to_api: Sender<ApiResponse>,
         vm_resources: VmResources,
         vmm: Arc<Mutex<Vmm>>,
-        event_manager: &mut EventManager,
-    ) -> Result<(), ApiServerError> {
-        let api_adapter = Arc::new(Mutex::new(Self {
+    ) -> Arc<Mutex<Self>> {
+        let compaction_evt = vmm
+            .lock()
+            .expect("Poisoned lock")
+            .balloon_compaction_event()
+            .ok();
+        Arc::new(Mutex::new(Self {
             api_event_fd,
             from_api,
             to_api,
-            controller: RuntimeApiController::new(vm_resources, vmm.clone()),
-        }));
+            controller: RuntimeApiController::new(vm_resources, vmm),
+            compaction_evt,
+            compaction_pending: false,
+        }))
+    }
+
+    /// Runs the vmm to completion, while any arising control events are deferred
+    /// to a `RuntimeApiController`.
+    fn run_microvm(
+        api_adapter: Arc<Mutex<Self>>,
+        vmm: Arc<Mutex<Vmm>>,
+        event_manager: &mut EventManager,
+    ) -> Result<(), ApiServerError> {
         event_manager.add_subscriber(api_adapter);
         loop {
             event_manager
@@ -78,22 +105,106 @@ impl ApiServerAdapter {
 
     fn handle_request(&mut self, req_action: VmmAction) {
         let response = self.controller.handle_request(req_action);
+        self.send_response(response);
+    }
+
+    fn send_response(&self, response: Result<VmmData, VmmActionError>) {
         // Send back the result.
         self.to_api
             .send(Box::new(response))
             .map_err(|_| ())
             .expect("one-shot channel closed");
     }
+
+    /// Handles a `CompactGuestMemory` request, whose response is only sent once the balloon
+    /// device signals that the compaction completed. The API events are not handled until then,
+    /// so that the other requests are queued and answered in order.
+    fn start_compaction(&mut self, req_action: VmmAction, ops: &mut EventOps) {
+        let response = self.controller.handle_request(req_action);
+        if response.is_ok() && self.compaction_evt.is_some() {
+            match ops.remove(Events::new(&self.api_event_fd, EventSet::IN)) {
+                Ok(()) => {
+                    self.compaction_pending = true;
+                    return;
+                }
+                Err(err) => error!("Failed to unregister API event: {}", err),
+            }
+        }
+        self.send_response(response);
+    }
+
+    /// Answers the pending `CompactGuestMemory` request with the memory the compaction
+    /// reclaimed, and resumes handling the API events.
+    fn complete_compaction(&mut self, ops: &mut EventOps) {
+        if let Some(compaction_evt) = &self.compaction_evt {
+            let _ = compaction_evt.read();
+        }
+        if !std::mem::take(&mut self.compaction_pending) {
+            return;
+        }
+        let response = self.controller.guest_memory_compaction();
+        self.send_response(response);
+        if let Err(err) = ops.add(Events::new(&self.api_event_fd, EventSet::IN)) {
+            error!("Failed to register API event: {}", err);
+        }
+    }
+
+    /// Fails the pending `CompactGuestMemory` request, if any, whose compaction did not complete
+    /// before the microVM shut down.
+    fn fail_pending_compaction(&mut self) {
+        if std::mem::take(&mut self.compaction_pending) {
+            self.send_response(Err(shutdown_error()));
+        }
+    }
+
+    /// Keeps handling the API requests until `grace_period` ends, once the microVM shut down.
+    /// The events of the other subscribers are not handled anymore, and a pause request does
+    /// not wait for the microVM to be resumed, so that no request is handled after the
+    /// deadline, except the one being handled when it passes.
+    fn serve_requests_for(&mut self, grace_period: Duration) {
+        let deadline = Instant::now() + grace_period;
+        loop {
+            let remaining = deadline.saturating_duration_since(Instant::now());
+            if remaining.is_zero() {
+                break;
+            }
+            match self.from_api.recv_timeout(remaining) {
+                // The balloon device events are not handled anymore, so a compaction would not
+                // complete.
+                Ok(api_request) if matches!(*api_request, VmmAction::CompactGuestMemory(_)) => {
+                    self.send_response(Err(shutdown_error()))
+                }
+                Ok(api_request) => self.handle_request(*api_request),
+                Err(RecvTimeoutError::Timeout) | Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+    }
+
+    /// Fails the API requests sent while the API server is shutting down, until `api_finished`
+    /// returns true, so that the API server answers them without waiting for the VMM response
+    /// timeout.
+    fn reject_requests_until(&mut self, api_finished: impl Fn() -> bool) {
+        while !api_finished() {
+            match self.from_api.recv_timeout(REJECT_POLL_INTERVAL) {
+                Ok(_) => self.send_response(Err(shutdown_error())),
+                Err(RecvTimeoutError::Timeout) => (),
+                Err(RecvTimeoutError::Disconnected) => break,
+            }
+        }
+    }
 }
 impl MutEventSubscriber for ApiServerAdapter {
     /// Handle a read event (EPOLLIN).
-    fn process(&mut self, event: Events, _: &mut EventOps) {
+    fn process(&mut self, event: Events, ops: &mut EventOps) {
         let source = event.fd();
         let event_set = event.event_set();
 
         if source == self.api_event_fd.as_raw_fd() && event_set == EventSet::IN {
             let _ = self.api_event_fd.read();
             match self.from_api.try_recv() {
+                Ok(api_request) if matches!(*api_request, VmmAction::CompactGuestMemory(_)) => {
+                    self.start_compaction(*api_request, ops);
+                }
                 Ok(api_request) => {
                     let request_is_pause = *api_request == VmmAction::Pause;
                     self.handle_request(*api_request);
@@ -123,6 +234,13 @@ impl MutEventSubscriber for ApiServerAdapter {
                     panic!("The channel's sending half was disconnected. Cannot receive data.");
                 }
             };
+        } else if self
+            .compaction_evt
+            .as_ref()
+            .is_some_and(|compaction_evt| source == compaction_evt.as_raw_fd())
+            && event_set == EventSet::IN
+        {
+            self.complete_compaction(ops);
         } else {
             error!("Spurious EventManager event for handler: ApiServerAdapter");
         }
@@ -132,6 +250,11 @@ impl MutEventSubscriber for ApiServerAdapter {
         if let Err(err) = ops.add(Events::new(&self.api_event_fd, EventSet::IN)) {
             error!("Failed to register activate event: {}", err);
         }
+        if let Some(compaction_evt) = &self.compaction_evt {
+            if let Err(err) = ops.add(Events::new(compaction_evt, EventSet::IN)) {
+                error!("Failed to register compaction event: {}", err);
+            }
+        }
     }
 }
 
@@ -144,6 +267,10 @@ pub(crate) fn run_with_api(
     process_time_reporter: ProcessTimeReporter,
     boot_timer_enabled: bool,
     api_payload_limit: usize,
+    slow_request_threshold_ms: Option<u64>,
+    vmm_response_timeout_ms: Option<u64>,
+    shutdown_grace_period_ms: Option<u64>,
+    mmds_worker_count: usize,
     mmds_size_limit: usize,
     metadata_json: Option<&str>,
 ) -> Result<(), ApiServerError> {
@@ -184,11 +311,21 @@ pub(crate) fn run_with_api(
         .add_kill_switch(api_kill_switch_clone)
         .expect("Cannot add HTTP server kill switch");
 
+    // The data store the API thread serves the MMDS reads from, once the microVM started.
+    let mmds = Arc::new(OnceLock::new());
+    let api_thread_mmds = mmds.clone();
+
     // Start the separate API thread.
     let api_thread = thread::Builder::new()
         .name("fc_api".to_owned())
         .spawn(move || {
-            ApiServer::new(to_vmm, from_vmm, to_vmm_event_fd).run(
+            let mut api_server = ApiServer::new(to_vmm, from_vmm, to_vmm_event_fd);
+            api_server.set_slow_request_threshold_ms(slow_request_threshold_ms);
+            if let Some(timeout_ms) = vmm_response_timeout_ms {
+                api_server.set_vmm_response_timeout(Duration::from_millis(timeout_ms));
+            }
+            api_server.set_mmds_workers(api_thread_mmds, mmds_worker_count);
+            api_server.run(
                 server,
                 process_time_reporter,
                 &api_seccomp_filter,
@@ -229,20 +366,32 @@ pub(crate) fn run_with_api(
         .map_err(ApiServerError::BuildMicroVmError),
     };
 
-    let result = build_result.and_then(|(vm_resources, vmm)| {
+    let result = build_result.and_then(|(mut vm_resources, vmm)| {
+        // The microVM is only built once, so the data store cannot be set already.
+        let _ = mmds.set(vm_resources.mmds_or_default().clone());
+
         firecracker_metrics
             .lock()
             .expect("Poisoned lock")
             .start(super::metrics::WRITE_METRICS_PERIOD_MS);
 
-        ApiServerAdapter::run_microvm(
-            api_event_fd,
-            from_api,
-            to_api,
-            vm_resources,
-            vmm,
-            &mut event_manager,
-        )
+        let api_adapter =
+            ApiServerAdapter::new(api_event_fd, from_api, to_api, vm_resources, vmm.clone());
+        let result = ApiServerAdapter::run_microvm(api_adapter.clone(), vmm, &mut event_manager);
+
+        // Whether the microVM stopped successfully or not, answer the requests clients sent
+        // while it was shutting down, instead of dropping them along with the API thread. The
+        // API thread keeps running until the grace period ends, so that the requests it did not
+        // read yet are handled too.
+        let mut api_adapter = api_adapter.lock().expect("Poisoned lock");
+        api_adapter.fail_pending_compaction();
+        if let Some(grace_period_ms) = shutdown_grace_period_ms {
+            api_adapter.serve_requests_for(Duration::from_millis(grace_period_ms));
+        }
+        api_kill_switch.write(1).unwrap();
+        api_adapter.reject_requests_until(|| api_thread.is_finished());
+
+        result
     });
 
     api_kill_switch.write(1).unwrap();
@@ -252,3 +401,131 @@ pub(crate) fn run_with_api(
 
     result
 }
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+
+    use vmm::test_utils::default_vmm;
+
+    use super::*;
+
+    #[test]
+    fn test_serve_requests_after_shutdown() {
+        for exit_code in [FcExitCode::Ok, FcExitCode::GenericError] {
+            let (vmm, mut event_manager) = default_vmm(None);
+            let (to_vmm, from_api) = channel();
+            let (to_api, from_vmm) = channel();
+            let api_event_fd = EventFd::new(libc::EFD_NONBLOCK).unwrap();
+            let to_vmm_event_fd = api_event_fd.try_clone().unwrap();
+            let api_adapter = ApiServerAdapter::new(
+                api_event_fd,
+                from_api,
+                to_api,
+                VmResources::default(),
+                vmm.clone(),
+            );
+
+            // The microVM shuts down while a request is sent.
+            vmm.lock().unwrap().stop(exit_code);
+            to_vmm.send(Box::new(VmmAction::GetVmmVersion)).unwrap();
+            to_vmm_event_fd.write(1).unwrap();
+            let result =
+                ApiServerAdapter::run_microvm(api_adapter.clone(), vmm, &mut event_manager);
+            match exit_code {
+                FcExitCode::Ok => result.unwrap(),
+                _ => assert!(matches!(
+                    result,
+                    Err(ApiServerError::MicroVMStoppedWithError(code)) if code == exit_code
+                )),
+            }
+
+            // Two more requests are queued, the first one pausing the microVM, and another one
+            // arrives during the grace period. They are all handled, without waiting for the
+            // microVM to be resumed.
+            to_vmm.send(Box::new(VmmAction::Pause)).unwrap();
+            to_vmm.send(Box::new(VmmAction::GetVmmVersion)).unwrap();
+            let late_to_vmm = to_vmm.clone();
+            let late_request = thread::spawn(move || {
+                thread::sleep(Duration::from_millis(20));
+                late_to_vmm
+                    .send(Box::new(VmmAction::GetVmmVersion))
+                    .unwrap();
+            });
+
+            let start = Instant::now();
+            api_adapter
+                .lock()
+                .unwrap()
+                .serve_requests_for(Duration::from_millis(100));
+            let elapsed = start.elapsed();
+            assert!(elapsed >= Duration::from_millis(100));
+            assert!(elapsed < Duration::from_secs(1));
+            late_request.join().unwrap();
+            let responses: Vec<ApiResponse> = from_vmm.try_iter().collect();
+            assert_eq!(responses.len(), 4);
+            let versions = responses
+                .iter()
+                .filter(|response| matches!(***response, Ok(VmmData::VmmVersion(_))))
+                .count();
+            assert_eq!(versions, 3);
+
+            // Without a grace period, no request is handled.
+            to_vmm.send(Box::new(VmmAction::GetVmmVersion)).unwrap();
+            api_adapter
+                .lock()
+                .unwrap()
+                .serve_requests_for(Duration::ZERO);
+            assert!(from_vmm.try_recv().is_err());
+
+            // The requests sent once the API server is told to stop are failed, until it stops.
+            let polls = Cell::new(0);
+            api_adapter.lock().unwrap().reject_requests_until(|| {
+                polls.set(polls.get() + 1);
+                polls.get() > 2
+            });
+            assert!(matches!(
+                *from_vmm.try_recv().unwrap(),
+                Err(VmmActionError::NotSupported(_))
+            ));
+        }
+    }
+
+    #[test]
+    fn test_compaction_after_shutdown() {
+        let (vmm, _) = default_vmm(None);
+        let (to_vmm, from_api) = channel();
+        let (to_api, from_vmm) = channel();
+        let api_adapter = ApiServerAdapter::new(
+            EventFd::new(libc::EFD_NONBLOCK).unwrap(),
+            from_api,
+            to_api,
+            VmResources::default(),
+            vmm,
+        );
+        let mut api_adapter = api_adapter.lock().unwrap();
+        // There is no balloon device to signal the completion of a compaction.
+        assert!(api_adapter.compaction_evt.is_none());
+
+        // The compaction a request waits for is failed once the microVM shut down.
+        api_adapter.compaction_pending = true;
+        api_adapter.fail_pending_compaction();
+        assert!(!api_adapter.compaction_pending);
+        assert!(matches!(
+            *from_vmm.try_recv().unwrap(),
+            Err(VmmActionError::NotSupported(_))
+        ));
+        api_adapter.fail_pending_compaction();
+        assert!(from_vmm.try_recv().is_err());
+
+        // No compaction is started during the grace period, as it would not complete.
+        to_vmm
+            .send(Box::new(VmmAction::CompactGuestMemory(8)))
+            .unwrap();
+        api_adapter.serve_requests_for(Duration::from_millis(20));
+        assert!(matches!(
+            *from_vmm.try_recv().unwrap(),
+            Err(VmmActionError::NotSupported(_))
+        ));
+    }
+}
@@ -8,7 +8,7 @@ mod metrics;
 mod seccomp;
 
 use std::fs::{self, File};
-use std::path::PathBuf;
+use std::path::{Path, PathBuf};
 use std::process::ExitCode;
 use std::str::FromStr;
 use std::sync::{Arc, Mutex};
@@ -30,6 +30,7 @@ use vmm::signal_handler::register_signal_handlers;
 use vmm::snapshot::{Snapshot, SnapshotError};
 use vmm::vmm_config::instance_info::{InstanceInfo, VmState};
 use vmm::vmm_config::metrics::{init_metrics, MetricsConfig, MetricsConfigError};
+use vmm::vmm_config::seccomp::SeccompInfo;
 use vmm::{EventManager, FcExitCode, HTTP_MAX_PAYLOAD_SIZE};
 use vmm_sys_util::terminal::Terminal;
 
@@ -54,12 +55,20 @@ enum MainError {
     PrintSnapshotDataFormat(#[from] SnapshotVersionError),
     /// Invalid value for logger level: {0}.Possible values: [Error, Warning, Info, Debug]
     InvalidLogLevel(vmm::logger::LevelFilterFromStrError),
+    /// Invalid value for logger format: {0}. Possible values: [human, json]
+    InvalidLogFormat(vmm::logger::LogFormatFromStrError),
+    /// Invalid value for logger filter: {0}. Expected comma separated module=level directives
+    InvalidLogFilter(vmm::logger::LogDirectivesFromStrError),
     /// Could not initialize logger: {0}
     LoggerInitialization(vmm::logger::LoggerUpdateError),
     /// Could not initialize metrics: {0}
     MetricsInitialization(MetricsConfigError),
     /// Seccomp error: {0}
     SeccompFilter(FilterError),
+    /// Seccomp audit error: {0}
+    SeccompAudit(vmm::seccomp_audit::SeccompAuditError),
+    /// Invalid value for seccomp failure policy: {0}. Expected fail or warn
+    InvalidSeccompFailurePolicy(vmm::seccomp_filters::SeccompFailurePolicyFromStrError),
     /// Failed to resize fd table: {0}
     ResizeFdtable(ResizeFdTableError),
     /// RunWithApiError error: {0}
@@ -83,6 +92,7 @@ impl From<MainError> for FcExitCode {
         match value {
             MainError::ParseArguments(_) => FcExitCode::ArgParsing,
             MainError::InvalidLogLevel(_) => FcExitCode::BadConfiguration,
+            MainError::InvalidLogFormat(_) => FcExitCode::BadConfiguration,
             MainError::RunWithApi(ApiServerError::MicroVMStoppedWithError(code)) => code,
             MainError::RunWithoutApiError(RunWithoutApiError::Shutdown(code)) => code,
             _ => FcExitCode::GenericError,
@@ -153,16 +163,62 @@ fn main_exec() -> Result<(), MainError> {
             .arg(
                 Argument::new("seccomp-filter")
                     .takes_value(true)
-                    .forbids(vec!["no-seccomp"])
+                    .forbids(vec!["no-seccomp", "seccomp-log"])
                     .help(
                         "Optional parameter which allows specifying the path to a custom seccomp \
                          filter. For advanced users.",
                     ),
             )
+            .arg(
+                Argument::new("seccomp-extra-syscalls")
+                    .takes_value(true)
+                    .forbids(vec!["no-seccomp"])
+                    .help(
+                        "Optional parameter which allows specifying the path to a JSON file \
+                         mapping thread categories to syscalls allowed on top of their seccomp \
+                         filter. For advanced users.",
+                    ),
+            )
+            .arg(
+                Argument::new("seccomp-audit-log")
+                    .takes_value(true)
+                    .forbids(vec!["no-seccomp"])
+                    .help(
+                        "Optional parameter which allows specifying the path to a file where each \
+                         syscall denied by the seccomp filters is recorded.",
+                    ),
+            )
+            .arg(
+                Argument::new("seccomp-log")
+                    .takes_value(false)
+                    .forbids(vec!["no-seccomp", "seccomp-filter"])
+                    .help(
+                        "Optional parameter which makes the default seccomp filters log the \
+                         syscalls they deny to the kernel audit log, instead of terminating. \
+                         Debugging aid, not recommended.",
+                    ),
+            )
+            .arg(
+                Argument::new("seccomp-failure-policy")
+                    .takes_value(true)
+                    .forbids(vec!["no-seccomp"])
+                    .help(
+                        "Optional parameter which sets what happens when the seccomp filter of a \
+                         thread cannot be installed: fail (default) terminates Firecracker or \
+                         fails the microVM start, warn logs a warning and runs the thread without \
+                         filter.",
+                    ),
+            )
             .arg(
                 Argument::new("no-seccomp")
                     .takes_value(false)
-                    .forbids(vec!["seccomp-filter"])
+                    .forbids(vec![
+                        "seccomp-filter",
+                        "seccomp-extra-syscalls",
+                        "seccomp-audit-log",
+                        "seccomp-log",
+                        "seccomp-failure-policy",
+                    ])
                     .help(
                         "Optional parameter which allows starting and using a microVM without \
                          seccomp filtering. Not recommended.",
@@ -221,6 +277,15 @@ fn main_exec() -> Result<(), MainError> {
             .arg(Argument::new("show-log-origin").takes_value(false).help(
                 "Whether or not to include the file path and line number of the log's origin.",
             ))
+            .arg(
+                Argument::new("log-format")
+                    .takes_value(true)
+                    .help("Set the format of the log lines: human or json."),
+            )
+            .arg(Argument::new("log-filter").takes_value(true).help(
+                "Set the logger level of specific modules, as comma separated module=level \
+                 directives.",
+            ))
             .arg(
                 Argument::new("metrics-path")
                     .takes_value(true)
@@ -255,6 +320,42 @@ fn main_exec() -> Result<(), MainError> {
                 Argument::new("mmds-size-limit")
                     .takes_value(true)
                     .help("Mmds data store limit, in bytes."),
+            )
+            .arg(
+                Argument::new("api-slow-request-threshold-ms")
+                    .takes_value(true)
+                    .help(
+                        "API requests taking longer than this many milliseconds are logged at \
+                         warn level.",
+                    ),
+            )
+            .arg(
+                Argument::new("api-vmm-response-timeout-ms")
+                    .takes_value(true)
+                    .help(
+                        "Time, in milliseconds, the API server waits for the VMM to handle a \
+                         request before failing it with a 503 Service Unavailable error. The VMM \
+                         still handles a request which timed out, so its outcome is \
+                         indeterminate. Defaults to 30 seconds.",
+                    ),
+            )
+            .arg(
+                Argument::new("api-shutdown-grace-period-ms")
+                    .takes_value(true)
+                    .help(
+                        "Time, in milliseconds, during which API requests are still handled after \
+                         the microVM shuts down, before the API server stops.",
+                    ),
+            )
+            .arg(
+                Argument::new("api-mmds-workers")
+                    .takes_value(true)
+                    .default_value("0")
+                    .help(
+                        "Number of threads serving the API requests reading the MMDS contents \
+                         concurrently, once the microVM started. These requests are handled by \
+                         the VMM like the other ones if 0.",
+                    ),
             );
 
     arg_parser.parse_from_cmdline()?;
@@ -298,6 +399,16 @@ fn main_exec() -> Result<(), MainError> {
     let show_level = arguments.flag_present("show-level").then_some(true);
     let show_log_origin = arguments.flag_present("show-log-origin").then_some(true);
     let module = arguments.single_value("module").cloned();
+    let format = arguments
+        .single_value("log-format")
+        .map(|s| vmm::logger::LogFormat::from_str(s))
+        .transpose()
+        .map_err(MainError::InvalidLogFormat)?;
+    let filter = arguments
+        .single_value("log-filter")
+        .map(|s| vmm::logger::LogDirectives::from_str(s))
+        .transpose()
+        .map_err(MainError::InvalidLogFilter)?;
     LOGGER
         .update(LoggerConfig {
             log_path,
@@ -305,6 +416,8 @@ fn main_exec() -> Result<(), MainError> {
             show_level,
             show_log_origin,
             module,
+            format,
+            filter,
         })
         .map_err(MainError::LoggerInitialization)?;
     info!("Running Firecracker v{FIRECRACKER_VERSION}");
@@ -332,13 +445,6 @@ fn main_exec() -> Result<(), MainError> {
     // deprecating one.
     // warn_deprecated_parameters(&arguments);
 
-    let instance_info = InstanceInfo {
-        id: instance_id.clone(),
-        state: VmState::NotStarted,
-        vmm_version: FIRECRACKER_VERSION.to_string(),
-        app_name: "Firecracker".to_string(),
-    };
-
     if let Some(metrics_path) = arguments.single_value("metrics-path") {
         let metrics_config = MetricsConfig {
             metrics_path: PathBuf::from(metrics_path),
@@ -346,12 +452,44 @@ fn main_exec() -> Result<(), MainError> {
         init_metrics(metrics_config).map_err(MainError::MetricsInitialization)?;
     }
 
-    let mut seccomp_filters: BpfThreadMap = SeccompConfig::from_args(
+    if let Some(policy) = arguments.single_value("seccomp-failure-policy") {
+        let policy = vmm::seccomp_filters::SeccompFailurePolicy::from_str(policy)
+            .map_err(MainError::InvalidSeccompFailurePolicy)?;
+        vmm::seccomp_filters::set_failure_policy(policy);
+    }
+
+    let seccomp_config = SeccompConfig::from_args(
         arguments.flag_present("no-seccomp"),
+        arguments.flag_present("seccomp-log"),
         arguments.single_value("seccomp-filter"),
     )
-    .and_then(seccomp::get_filters)
     .map_err(MainError::SeccompFilter)?;
+    let seccomp_level = seccomp_config.level();
+    let mut seccomp_filters: BpfThreadMap =
+        seccomp::get_filters(seccomp_config).map_err(MainError::SeccompFilter)?;
+    if let Some(extra_syscalls_path) = arguments.single_value("seccomp-extra-syscalls") {
+        let extra_syscalls = File::open(extra_syscalls_path)
+            .map_err(|err| MainError::SeccompFilter(FilterError::FileOpen(err)))?;
+        seccomp::add_extra_syscalls(&mut seccomp_filters, extra_syscalls)
+            .map_err(MainError::SeccompFilter)?;
+    }
+    if let Some(audit_log_path) = arguments.single_value("seccomp-audit-log") {
+        // The audit thread only writes to the audit file, like the VMM thread does for logs.
+        let audit_filter = seccomp_filters.get("vmm").cloned().unwrap_or_default();
+        vmm::seccomp_audit::init(Path::new(audit_log_path), audit_filter)
+            .map_err(MainError::SeccompAudit)?;
+    }
+
+    let instance_info = InstanceInfo {
+        id: instance_id.clone(),
+        state: VmState::NotStarted,
+        vmm_version: FIRECRACKER_VERSION.to_string(),
+        app_name: "Firecracker".to_string(),
+        boot_timestamp_us: None,
+        uptime_us: None,
+        // Record the seccomp configuration so that it can be reported through the API.
+        seccomp: SeccompInfo::new(seccomp_level, &seccomp_filters),
+    };
 
     let vmm_config_json = arguments
         .single_value("config-file")
@@ -410,6 +548,40 @@ fn main_exec() -> Result<(), MainError> {
         let process_time_reporter =
             ProcessTimeReporter::new(start_time_us, start_time_cpu_us, parent_cpu_time_us);
 
+        let slow_request_threshold_ms = arguments
+            .single_value("api-slow-request-threshold-ms")
+            .map(|s| {
+                s.parse::<u64>().expect(
+                    "'api-slow-request-threshold-ms' parameter expected to be of 'u64' type.",
+                )
+            });
+
+        let vmm_response_timeout_ms =
+            arguments
+                .single_value("api-vmm-response-timeout-ms")
+                .map(|s| {
+                    s.parse::<u64>().expect(
+                        "'api-vmm-response-timeout-ms' parameter expected to be of 'u64' type.",
+                    )
+                });
+
+        let shutdown_grace_period_ms =
+            arguments
+                .single_value("api-shutdown-grace-period-ms")
+                .map(|s| {
+                    s.parse::<u64>().expect(
+                        "'api-shutdown-grace-period-ms' parameter expected to be of 'u64' type.",
+                    )
+                });
+
+        let mmds_worker_count = arguments
+            .single_value("api-mmds-workers")
+            .map(|s| {
+                s.parse::<usize>()
+                    .expect("'api-mmds-workers' parameter expected to be of 'usize' type.")
+            })
+            .unwrap_or_default();
+
         api_server_adapter::run_with_api(
             &mut seccomp_filters,
             vmm_config_json,
@@ -418,6 +590,10 @@ fn main_exec() -> Result<(), MainError> {
             process_time_reporter,
             boot_timer_enabled,
             api_payload_limit,
+            slow_request_threshold_ms,
+            vmm_response_timeout_ms,
+            shutdown_grace_period_ms,
+            mmds_worker_count,
             mmds_size_limit,
             metadata_json.as_deref(),
         )
@@ -1,12 +1,20 @@
 // Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
+use std::collections::HashMap;
 use std::fmt::Debug;
 use std::fs::File;
 use std::io::{BufReader, Read};
 use std::path::Path;
+use std::sync::Arc;
 
-use seccompiler::{deserialize_binary, BpfThreadMap, DeserializationError};
+use seccompiler::backend::{TargetArch, TargetArchError};
+use seccompiler::common::BPF_MAX_LEN;
+use seccompiler::syscall_table::SyscallTable;
+use seccompiler::{
+    deserialize_binary, sock_filter, BpfProgram, BpfThreadMap, DeserializationError,
+};
 use vmm::seccomp_filters::get_empty_filters;
+use vmm::vmm_config::seccomp::SeccompLevel;
 
 const THREAD_CATEGORIES: [&str; 3] = ["vmm", "api", "vcpu"];
 
@@ -16,6 +24,26 @@ const THREAD_CATEGORIES: [&str; 3] = ["vmm", "api", "vcpu"];
 // filter is 4096 instructions and Firecracker has a finite number of threads.
 const DESERIALIZATION_BYTES_LIMIT: Option<u64> = Some(100_000);
 
+// BPF instruction codes used when composing extra syscalls into a filter.
+// See /usr/include/linux/bpf_common.h .
+const BPF_LD_W_ABS: u16 = 0x20;
+const BPF_JMP_JA: u16 = 0x05;
+const BPF_JMP_JEQ_K: u16 = 0x15;
+const BPF_RET_K: u16 = 0x06;
+
+// See /usr/include/linux/seccomp.h .
+const SECCOMP_RET_ALLOW: u32 = 0x7fff_0000;
+const SECCOMP_RET_LOG: u32 = 0x7ffc_0000;
+const SECCOMP_RET_TRAP: u32 = 0x0003_0000;
+
+// `struct seccomp_data` offsets of the syscall number and architecture fields.
+const SECCOMP_DATA_NR_OFFSET: u32 = 0;
+const SECCOMP_DATA_ARCH_OFFSET: u32 = 4;
+
+// Each extra syscall is matched by a jump to the common allow statement, and the
+// architecture check jumps over all of them, so both offsets must fit in a `u8`.
+const MAX_EXTRA_SYSCALLS: usize = 252;
+
 /// Error retrieving seccomp filters.
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
 pub enum FilterError {
@@ -27,6 +55,16 @@ pub enum FilterError {
     MissingThreadCategory(String),
     /// Filter file open error: {0}
     FileOpen(std::io::Error),
+    /// Extra syscalls deserialization failed: {0}
+    ExtraSyscallsDeserialization(serde_json::Error),
+    /// Unsupported target architecture: {0}
+    TargetArch(TargetArchError),
+    /// Unknown syscall: {0}
+    UnknownSyscall(String),
+    /// Too many extra syscalls for thread category: {0}
+    TooManyExtraSyscalls(String),
+    /// Filter of thread category {0} exceeds {BPF_MAX_LEN:} instructions with extra syscalls
+    FilterTooLarge(String),
 }
 
 /// Seccomp filter configuration.
@@ -36,6 +74,8 @@ pub enum SeccompConfig {
     None,
     /// Default, advanced filters.
     Advanced,
+    /// Default filters, logging the denied syscalls instead of terminating.
+    Log,
     /// Custom, user-provided filters.
     Custom(File),
 }
@@ -44,10 +84,13 @@ impl SeccompConfig {
     /// Given the relevant command line args, return the appropriate config type.
     pub fn from_args<T: AsRef<Path> + Debug>(
         no_seccomp: bool,
+        seccomp_log: bool,
         seccomp_filter: Option<T>,
     ) -> Result<Self, FilterError> {
         if no_seccomp {
             Ok(SeccompConfig::None)
+        } else if seccomp_log {
+            Ok(SeccompConfig::Log)
         } else {
             match seccomp_filter {
                 Some(path) => Ok(SeccompConfig::Custom(
@@ -57,6 +100,16 @@ impl SeccompConfig {
             }
         }
     }
+
+    /// Return the seccomp filtering level of this config.
+    pub fn level(&self) -> SeccompLevel {
+        match self {
+            SeccompConfig::None => SeccompLevel::None,
+            SeccompConfig::Advanced => SeccompLevel::Advanced,
+            SeccompConfig::Log => SeccompLevel::Log,
+            SeccompConfig::Custom(_) => SeccompLevel::Custom,
+        }
+    }
 }
 
 /// Retrieve the appropriate filters, based on the SeccompConfig.
@@ -64,10 +117,37 @@ pub fn get_filters(config: SeccompConfig) -> Result<BpfThreadMap, FilterError> {
     match config {
         SeccompConfig::None => Ok(get_empty_filters()),
         SeccompConfig::Advanced => get_default_filters(),
+        SeccompConfig::Log => get_default_filters().map(log_denied_syscalls),
         SeccompConfig::Custom(reader) => get_custom_filters(reader),
     }
 }
 
+/// Make the filters log the syscalls they deny instead of trapping them, so that the syscalls
+/// proceed and are recorded by the kernel audit log. Architecture mismatches still kill the
+/// process.
+fn log_denied_syscalls(filters: BpfThreadMap) -> BpfThreadMap {
+    filters
+        .into_iter()
+        .map(|(category, filter)| {
+            let filter = filter
+                .iter()
+                .map(|insn| match insn {
+                    sock_filter {
+                        code: BPF_RET_K,
+                        k: SECCOMP_RET_TRAP,
+                        ..
+                    } => sock_filter {
+                        k: SECCOMP_RET_LOG,
+                        ..*insn
+                    },
+                    _ => insn.clone(),
+                })
+                .collect();
+            (category, Arc::new(filter))
+        })
+        .collect()
+}
+
 /// Retrieve the default filters containing the syscall rules required by `Firecracker`
 /// to function. The binary file is generated via the `build.rs` script of this crate.
 fn get_default_filters() -> Result<BpfThreadMap, FilterError> {
@@ -85,6 +165,99 @@ fn get_custom_filters<R: Read + Debug>(reader: R) -> Result<BpfThreadMap, Filter
     filter_thread_categories(map)
 }
 
+/// Allow additional syscalls on top of the filters of some thread categories.
+///
+/// The reader provides a JSON object mapping thread categories to lists of syscall names.
+/// The extra syscalls are allowed unconditionally, before the original filter of the thread is
+/// evaluated. Empty filters already allow every syscall and are left untouched.
+pub fn add_extra_syscalls<R: Read + Debug>(
+    filters: &mut BpfThreadMap,
+    reader: R,
+) -> Result<(), FilterError> {
+    let extra_syscalls: HashMap<String, Vec<String>> =
+        serde_json::from_reader(BufReader::new(reader))
+            .map_err(FilterError::ExtraSyscallsDeserialization)?;
+
+    let invalid_categories: Vec<&str> = extra_syscalls
+        .keys()
+        .map(String::as_str)
+        .filter(|category| !THREAD_CATEGORIES.contains(category))
+        .collect();
+    if !invalid_categories.is_empty() {
+        return Err(FilterError::ThreadCategories(invalid_categories.join(",")));
+    }
+
+    let arch: TargetArch = std::env::consts::ARCH
+        .try_into()
+        .map_err(FilterError::TargetArch)?;
+    let syscall_table = SyscallTable::new(arch);
+
+    for (category, syscalls) in extra_syscalls {
+        let syscall_nrs = syscalls
+            .iter()
+            .map(|name| {
+                syscall_table
+                    .get_syscall_nr(name)
+                    .and_then(|nr| u32::try_from(nr).ok())
+                    .ok_or_else(|| FilterError::UnknownSyscall(name.clone()))
+            })
+            .collect::<Result<Vec<_>, _>>()?;
+
+        if syscall_nrs.len() > MAX_EXTRA_SYSCALLS {
+            return Err(FilterError::TooManyExtraSyscalls(category));
+        }
+
+        let filter = filters
+            .get(&category)
+            .ok_or_else(|| FilterError::MissingThreadCategory(category.clone()))?;
+        if filter.is_empty() || syscall_nrs.is_empty() {
+            continue;
+        }
+
+        let composed = compose_filter(filter, &syscall_nrs, arch.get_audit_value())
+            .ok_or_else(|| FilterError::FilterTooLarge(category.clone()))?;
+        filters.insert(category, Arc::new(composed));
+    }
+
+    Ok(())
+}
+
+/// Prepend to `filter` the statements allowing the given syscall numbers.
+///
+/// Returns `None` if the syscalls or the resulting filter do not fit in a BPF program.
+fn compose_filter(
+    filter: &[sock_filter],
+    syscall_nrs: &[u32],
+    audit_arch: u32,
+) -> Option<BpfProgram> {
+    let count = u8::try_from(syscall_nrs.len())
+        .ok()
+        .filter(|&count| usize::from(count) <= MAX_EXTRA_SYSCALLS)?;
+
+    let stmt = |code, k| sock_filter {
+        code,
+        jt: 0,
+        jf: 0,
+        k,
+    };
+    let jump = |code, k, jt, jf| sock_filter { code, jt, jf, k };
+
+    let mut composed = Vec::with_capacity(filter.len() + syscall_nrs.len() + 5);
+    // Syscalls issued for another architecture are left to the original filter.
+    composed.push(stmt(BPF_LD_W_ABS, SECCOMP_DATA_ARCH_OFFSET));
+    composed.push(jump(BPF_JMP_JEQ_K, audit_arch, 0, count + 3));
+    composed.push(stmt(BPF_LD_W_ABS, SECCOMP_DATA_NR_OFFSET));
+    for (index, &nr) in (0..count).zip(syscall_nrs) {
+        composed.push(jump(BPF_JMP_JEQ_K, nr, count - index, 0));
+    }
+    // None of the extra syscalls matched, skip the allow statement.
+    composed.push(stmt(BPF_JMP_JA, 1));
+    composed.push(stmt(BPF_RET_K, SECCOMP_RET_ALLOW));
+    composed.extend_from_slice(filter);
+
+    (composed.len() <= usize::from(BPF_MAX_LEN)).then_some(composed)
+}
+
 /// Return an error if the BpfThreadMap contains invalid thread categories.
 fn filter_thread_categories(map: BpfThreadMap) -> Result<BpfThreadMap, FilterError> {
     let (filters, invalid_filters): (BpfThreadMap, BpfThreadMap) = map
@@ -117,8 +290,9 @@ fn filter_thread_categories(map: BpfThreadMap) -> Result<BpfThreadMap, FilterErr
 #[cfg(test)]
 mod tests {
     use std::sync::Arc;
+    use std::thread;
 
-    use seccompiler::BpfThreadMap;
+    use seccompiler::{apply_filter, BpfThreadMap};
     use vmm_sys_util::tempfile::TempFile;
 
     use super::*;
@@ -142,6 +316,26 @@ mod tests {
         get_filters(SeccompConfig::Custom(file)).unwrap_err();
     }
 
+    #[test]
+    fn test_get_log_filters() {
+        let default_filters = get_filters(SeccompConfig::Advanced).unwrap();
+        let log_filters = get_filters(SeccompConfig::Log).unwrap();
+        assert_eq!(log_filters.len(), THREAD_CATEGORIES.len());
+
+        for (category, filter) in log_filters {
+            let default_filter = &default_filters[&category];
+            assert_eq!(filter.len(), default_filter.len());
+            // Syscalls matching no rule reach the final `BPF_RET`, which now logs them.
+            assert_eq!(default_filter.last().unwrap().k, SECCOMP_RET_TRAP);
+            let last = filter.last().unwrap();
+            assert_eq!((last.code, last.k), (BPF_RET_K, SECCOMP_RET_LOG));
+            // No denied syscall traps anymore.
+            assert!(!filter
+                .iter()
+                .any(|insn| insn.code == BPF_RET_K && insn.k == SECCOMP_RET_TRAP));
+        }
+    }
+
     #[test]
     fn test_filter_thread_categories() {
         // correct categories
@@ -177,27 +371,172 @@ mod tests {
         }
     }
 
+    // Allows every syscall, except for `getppid` which fails with `EPERM`.
+    fn deny_getppid_filter() -> BpfProgram {
+        vec![
+            sock_filter {
+                code: BPF_LD_W_ABS,
+                jt: 0,
+                jf: 0,
+                k: SECCOMP_DATA_NR_OFFSET,
+            },
+            sock_filter {
+                code: BPF_JMP_JEQ_K,
+                jt: 0,
+                jf: 1,
+                k: u32::try_from(libc::SYS_getppid).unwrap(),
+            },
+            sock_filter {
+                code: BPF_RET_K,
+                jt: 0,
+                jf: 0,
+                k: 0x0005_0000 | u32::try_from(libc::EPERM).unwrap(),
+            },
+            sock_filter {
+                code: BPF_RET_K,
+                jt: 0,
+                jf: 0,
+                k: SECCOMP_RET_ALLOW,
+            },
+        ]
+    }
+
+    fn getppid_allowed(filter: BpfProgram) -> bool {
+        thread::spawn(move || {
+            apply_filter(&filter).unwrap();
+            // SAFETY: `getppid` takes no arguments and has no side effects.
+            let ret = unsafe { libc::syscall(libc::SYS_getppid) };
+            ret != -1
+        })
+        .join()
+        .unwrap()
+    }
+
+    #[test]
+    fn test_add_extra_syscalls() {
+        let mut filters = get_empty_filters();
+        filters.insert("vmm".to_string(), Arc::new(deny_getppid_filter()));
+        filters.insert("api".to_string(), Arc::new(deny_getppid_filter()));
+        assert!(!getppid_allowed(filters["vmm"].to_vec()));
+
+        add_extra_syscalls(
+            &mut filters,
+            r#"{"vmm": ["getppid", "gettid"], "vcpu": ["getppid"]}"#.as_bytes(),
+        )
+        .unwrap();
+        // The extra syscalls are allowed on the threads they were supplied for.
+        assert_eq!(filters["vmm"].len(), deny_getppid_filter().len() + 7);
+        assert!(getppid_allowed(filters["vmm"].to_vec()));
+        // Other thread categories keep their original filter.
+        assert_eq!(*filters["api"], deny_getppid_filter());
+        assert!(!getppid_allowed(filters["api"].to_vec()));
+        // Empty filters already allow everything.
+        assert!(filters["vcpu"].is_empty());
+
+        // Malformed file.
+        assert!(matches!(
+            add_extra_syscalls(&mut filters, "[]".as_bytes()),
+            Err(FilterError::ExtraSyscallsDeserialization(_))
+        ));
+
+        // Invalid thread category.
+        match add_extra_syscalls(&mut filters, r#"{"thread1": []}"#.as_bytes()).unwrap_err() {
+            FilterError::ThreadCategories(err) => assert_eq!(err, "thread1"),
+            err => panic!("Unexpected error: {err}"),
+        }
+
+        // Unknown syscall.
+        match add_extra_syscalls(&mut filters, r#"{"api": ["invalid"]}"#.as_bytes()).unwrap_err() {
+            FilterError::UnknownSyscall(name) => assert_eq!(name, "invalid"),
+            err => panic!("Unexpected error: {err}"),
+        }
+
+        // Too many extra syscalls.
+        let syscalls = vec!["read"; MAX_EXTRA_SYSCALLS + 1];
+        let json = serde_json::json!({ "api": syscalls }).to_string();
+        match add_extra_syscalls(&mut filters, json.as_bytes()).unwrap_err() {
+            FilterError::TooManyExtraSyscalls(category) => assert_eq!(category, "api"),
+            err => panic!("Unexpected error: {err}"),
+        }
+        assert_eq!(*filters["api"], deny_getppid_filter());
+    }
+
+    #[test]
+    fn test_compose_filter() {
+        let filter = deny_getppid_filter();
+        let composed = compose_filter(&filter, &[1, 2], 0xc000_003e).unwrap();
+        assert_eq!(composed.len(), filter.len() + 7);
+        assert_eq!(composed[7..], filter[..]);
+        // A mismatching architecture skips to the original filter.
+        assert_eq!((composed[1].k, composed[1].jf), (0xc000_003e, 5));
+        // Every match jumps to the allow statement.
+        assert_eq!((composed[3].k, composed[3].jt), (1, 2));
+        assert_eq!((composed[4].k, composed[4].jt), (2, 1));
+        assert_eq!(composed[6].k, SECCOMP_RET_ALLOW);
+
+        // The composed filter must fit in a BPF program.
+        let max_len = usize::from(BPF_MAX_LEN);
+        let filter = vec![filter[3].clone(); max_len - 6];
+        assert_eq!(compose_filter(&filter, &[1], 0).unwrap().len(), max_len);
+        let filter = vec![filter[0].clone(); max_len - 5];
+        assert!(compose_filter(&filter, &[1], 0).is_none());
+
+        assert!(compose_filter(&[], &[1; MAX_EXTRA_SYSCALLS + 1], 0).is_none());
+    }
+
     #[test]
     fn test_seccomp_config() {
         assert!(matches!(
-            SeccompConfig::from_args(true, Option::<&str>::None),
+            SeccompConfig::from_args(true, false, Option::<&str>::None),
             Ok(SeccompConfig::None)
         ));
 
         assert!(matches!(
-            SeccompConfig::from_args(false, Some("/dev/null")),
+            SeccompConfig::from_args(false, false, Some("/dev/null")),
             Ok(SeccompConfig::Custom(_))
         ));
 
         assert!(matches!(
-            SeccompConfig::from_args(false, Some("invalid_path")),
+            SeccompConfig::from_args(false, false, Some("invalid_path")),
             Err(FilterError::FileOpen(_))
         ));
 
+        // Logging applies to the default filters.
+        assert!(matches!(
+            SeccompConfig::from_args(false, true, Option::<&str>::None),
+            Ok(SeccompConfig::Log)
+        ));
+
         // test the default case, no parametes -> default advanced.
         assert!(matches!(
-            SeccompConfig::from_args(false, Option::<&str>::None),
+            SeccompConfig::from_args(false, false, Option::<&str>::None),
             Ok(SeccompConfig::Advanced)
         ));
+
+        // The reported level matches the selected config.
+        assert_eq!(
+            SeccompConfig::from_args(true, false, Option::<&str>::None)
+                .unwrap()
+                .level(),
+            SeccompLevel::None
+        );
+        assert_eq!(
+            SeccompConfig::from_args(false, false, Some("/dev/null"))
+                .unwrap()
+                .level(),
+            SeccompLevel::Custom
+        );
+        assert_eq!(
+            SeccompConfig::from_args(false, false, Option::<&str>::None)
+                .unwrap()
+                .level(),
+            SeccompLevel::Advanced
+        );
+        assert_eq!(
+            SeccompConfig::from_args(false, true, Option::<&str>::None)
+                .unwrap()
+                .level(),
+            SeccompLevel::Log
+        );
     }
 }
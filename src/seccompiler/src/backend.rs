@@ -128,7 +128,7 @@ pub enum TargetArchError {
 
 impl TargetArch {
     /// Get the arch audit value.
-    fn get_audit_value(self) -> u32 {
+    pub fn get_audit_value(self) -> u32 {
         match self {
             TargetArch::x86_64 => AUDIT_ARCH_X86_64,
             TargetArch::aarch64 => AUDIT_ARCH_AARCH64,
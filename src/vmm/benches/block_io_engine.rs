@@ -0,0 +1,66 @@
+// Copyright 2024 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+//
+// Benchmarking cases:
+//   * Sequential writes with the `Sync` and `Async` block IO engines
+//   * Sequential reads with the `Sync` and `Async` block IO engines
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use vm_memory::GuestAddress;
+use vmm::devices::virtio::block::virtio::device::FileEngineType;
+use vmm::devices::virtio::block::virtio::io::FileEngine;
+use vmm::devices::virtio::block::virtio::metrics::BlockDeviceMetrics;
+use vmm::devices::virtio::block::virtio::IO_URING_NUM_ENTRIES;
+use vmm::test_utils::single_region_mem;
+use vmm::vstate::memory::GuestMemoryMmap;
+use vmm_sys_util::tempfile::TempFile;
+
+const REQ_LEN: u32 = 4096;
+
+// Submits as many sequential requests as the io_uring can hold, and waits for them to complete.
+fn run_batch(engine: &mut FileEngine<()>, mem: &GuestMemoryMmap, write: bool) {
+    for req in 0..u32::from(IO_URING_NUM_ENTRIES) {
+        let offset = u64::from(req) * u64::from(REQ_LEN);
+        let res = if write {
+            engine.write(offset, mem, GuestAddress(0), REQ_LEN, ())
+        } else {
+            engine.read(offset, mem, GuestAddress(0), REQ_LEN, ())
+        };
+        if let Err(err) = res {
+            panic!("{:?}", err.error);
+        }
+    }
+    engine.drain(true).unwrap();
+}
+
+pub fn block_io_engine_benchmark(c: &mut Criterion) {
+    let mem = single_region_mem(REQ_LEN as usize);
+    let mut group = c.benchmark_group("block_io_engine");
+    group.throughput(Throughput::Bytes(
+        u64::from(IO_URING_NUM_ENTRIES) * u64::from(REQ_LEN),
+    ));
+
+    for engine_type in [FileEngineType::Sync, FileEngineType::Async] {
+        let metrics = BlockDeviceMetrics::default();
+        let file = TempFile::new().unwrap().into_file();
+        let mut engine = FileEngine::<()>::from_file(file, engine_type, false, &metrics).unwrap();
+
+        // The file is written first, so that the reads are not served from a hole.
+        for (name, write) in [("write", true), ("read", false)] {
+            group.bench_function(BenchmarkId::new(name, format!("{:?}", engine_type)), |b| {
+                b.iter(|| run_batch(&mut engine, &mem, write))
+            });
+        }
+    }
+    group.finish();
+}
+
+criterion_group! {
+    name = block_io_engine_benches;
+    config = Criterion::default().noise_threshold(0.05);
+    targets = block_io_engine_benchmark
+}
+
+criterion_main! {
+    block_io_engine_benches
+}
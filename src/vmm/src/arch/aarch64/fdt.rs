@@ -22,6 +22,8 @@ use crate::vstate::memory::{Address, GuestMemory, GuestMemoryMmap};
 const GIC_PHANDLE: u32 = 1;
 // This is a value for uniquely identifying the FDT node containing the clock definition.
 const CLOCK_PHANDLE: u32 = 2;
+// This is a value for uniquely identifying the FDT node declaring the GPIO controller.
+const GPIO_PHANDLE: u32 = 3;
 // You may be wondering why this big value?
 // This phandle is used to uniquely identify the FDT nodes containing cache information. Each cpu
 // can have a variable number of caches, some of these caches may be shared with other cpus.
@@ -42,6 +44,9 @@ const GIC_FDT_IRQ_TYPE_PPI: u32 = 1;
 const IRQ_TYPE_EDGE_RISING: u32 = 1;
 const IRQ_TYPE_LEVEL_HI: u32 = 4;
 
+// From https://elixir.bootlin.com/linux/v4.9.62/source/include/uapi/linux/input-event-codes.h#L193
+const KEY_POWER: u32 = 116;
+
 /// Trait for devices to be added to the Flattened Device Tree.
 pub trait DeviceInfoForFDT {
     /// Returns the address where this device will be loaded.
@@ -418,6 +423,52 @@ fn create_rtc_node<T: DeviceInfoForFDT + Clone + Debug>(
     Ok(())
 }
 
+fn create_gpio_node<T: DeviceInfoForFDT + Clone + Debug>(
+    fdt: &mut FdtWriter,
+    dev_info: &T,
+) -> Result<(), FdtError> {
+    // Driver requirements:
+    // https://elixir.bootlin.com/linux/latest/source/Documentation/devicetree/bindings/gpio/pl061-gpio.yaml
+    let compatible = b"arm,pl061\0arm,primecell\0";
+
+    let gpio = fdt.begin_node(&format!("pl061@{:x}", dev_info.addr()))?;
+    fdt.property("compatible", compatible)?;
+    fdt.property_array_u64("reg", &[dev_info.addr(), dev_info.length()])?;
+    fdt.property_array_u32(
+        "interrupts",
+        &[GIC_FDT_IRQ_TYPE_SPI, dev_info.irq(), IRQ_TYPE_EDGE_RISING],
+    )?;
+    fdt.property_null("gpio-controller")?;
+    fdt.property_u32("#gpio-cells", 2)?;
+    fdt.property_u32("clocks", CLOCK_PHANDLE)?;
+    fdt.property_string("clock-names", "apb_pclk")?;
+    fdt.property_u32("phandle", GPIO_PHANDLE)?;
+    fdt.end_node(gpio)?;
+
+    // The power button is wired to the GPIO controller through the `gpio-keys` driver:
+    // https://elixir.bootlin.com/linux/latest/source/Documentation/devicetree/bindings/input/gpio-keys.yaml
+    let gpio_keys = fdt.begin_node("gpio-keys")?;
+    fdt.property_string("compatible", "gpio-keys")?;
+    fdt.property_u32("#address-cells", 1)?;
+    fdt.property_u32("#size-cells", 0)?;
+    let poweroff = fdt.begin_node("button@1")?;
+    fdt.property_string("label", "GPIO Key Poweroff")?;
+    fdt.property_u32("linux,code", KEY_POWER)?;
+    fdt.property_u32("reg", 1)?;
+    fdt.property_array_u32(
+        "gpios",
+        &[
+            GPIO_PHANDLE,
+            crate::devices::legacy::gpio_pl061::POWER_KEY_LINE,
+            0,
+        ],
+    )?;
+    fdt.end_node(poweroff)?;
+    fdt.end_node(gpio_keys)?;
+
+    Ok(())
+}
+
 fn create_devices_node<T: DeviceInfoForFDT + Clone + Debug, S: std::hash::BuildHasher>(
     fdt: &mut FdtWriter,
     dev_info: &HashMap<(DeviceType, String), T, S>,
@@ -429,6 +480,7 @@ fn create_devices_node<T: DeviceInfoForFDT + Clone + Debug, S: std::hash::BuildH
         match device_type {
             DeviceType::BootTimer => (), // since it's not a real device
             DeviceType::Rtc => create_rtc_node(fdt, info)?,
+            DeviceType::Gpio => create_gpio_node(fdt, info)?,
             DeviceType::Serial => create_serial_node(fdt, info)?,
             DeviceType::Virtio(_) => {
                 ordered_virtio_device.push(info);
@@ -506,6 +558,13 @@ mod tests {
                     irq: 3,
                 },
             ),
+            (
+                (DeviceType::Gpio, "gpio".to_string()),
+                MMIODeviceInfo {
+                    addr: 3 * LEN,
+                    irq: 4,
+                },
+            ),
         ]
         .iter()
         .cloned()
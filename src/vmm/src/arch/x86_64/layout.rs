@@ -15,6 +15,9 @@ pub const CMDLINE_START: u64 = 0x20000;
 /// Kernel command line maximum size.
 pub const CMDLINE_MAX_SIZE: usize = 2048;
 
+/// Start of the setup_data entry holding the kernel RNG seed, right after the kernel command line.
+pub const RNG_SEED_START: u64 = CMDLINE_START + CMDLINE_MAX_SIZE as u64;
+
 /// Start of the high memory.
 pub const HIMEM_START: u64 = 0x0010_0000; // 1 MB.
 
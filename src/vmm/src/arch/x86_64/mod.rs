@@ -29,7 +29,7 @@ use crate::arch::InitrdConfig;
 use crate::device_manager::resources::ResourceAllocator;
 use crate::utils::u64_to_usize;
 use crate::vstate::memory::{
-    Address, GuestAddress, GuestMemory, GuestMemoryMmap, GuestMemoryRegion,
+    Address, Bytes, GuestAddress, GuestMemory, GuestMemoryMmap, GuestMemoryRegion,
 };
 
 // Value taken from https://elixir.bootlin.com/linux/v5.10.68/source/arch/x86/include/uapi/asm/e820.h#L31
@@ -38,6 +38,14 @@ const E820_RAM: u32 = 1;
 // Reserved area that should be avoided during memory allocations
 const E820_RESERVED: u32 = 2;
 
+// Value taken from https://elixir.bootlin.com/linux/v6.1/source/arch/x86/include/uapi/asm/bootparam.h#L14
+// setup_data entry holding a seed for the kernel RNG
+const SETUP_RNG_SEED: u32 = 9;
+// Size of the `next`, `type` and `len` fields of a setup_data entry
+const SETUP_DATA_HEADER_SIZE: usize = 16;
+// Size, in bytes, of the seed passed to the kernel RNG
+const RNG_SEED_SIZE: usize = 32;
+
 /// Errors thrown while configuring x86_64 system.
 #[derive(Debug, PartialEq, Eq, thiserror::Error, displaydoc::Display)]
 pub enum ConfigurationError {
@@ -49,6 +57,10 @@ pub enum ConfigurationError {
     ZeroPageSetup,
     /// Failed to compute initrd address.
     InitrdAddress,
+    /// Failed to read the kernel RNG seed from the host RNG.
+    RngSeedGeneration,
+    /// Error writing the kernel RNG seed to guest memory.
+    RngSeedSetup,
 }
 
 const FIRST_ADDR_PAST_32BITS: u64 = 1 << 32;
@@ -110,6 +122,7 @@ pub fn initrd_load_addr(
 /// * `cmdline_size` - Size of the kernel command line in bytes including the null terminator.
 /// * `initrd` - Information about where the ramdisk image was loaded in the `guest_mem`.
 /// * `num_cpus` - Number of virtual CPUs the guest will have.
+/// * `rng_seed` - Whether to pass a seed for the kernel RNG through the boot parameters.
 pub fn configure_system(
     guest_mem: &GuestMemoryMmap,
     resource_allocator: &mut ResourceAllocator,
@@ -117,6 +130,7 @@ pub fn configure_system(
     cmdline_size: usize,
     initrd: &Option<InitrdConfig>,
     num_cpus: u8,
+    rng_seed: bool,
 ) -> Result<(), ConfigurationError> {
     const KERNEL_BOOT_FLAG_MAGIC: u16 = 0xaa55;
     const KERNEL_HDR_MAGIC: u32 = 0x5372_6448;
@@ -146,6 +160,11 @@ pub fn configure_system(
         params.hdr.ramdisk_image = u32::try_from(initrd_config.address.raw_value()).unwrap();
         params.hdr.ramdisk_size = u32::try_from(initrd_config.size).unwrap();
     }
+    if rng_seed {
+        let rng_seed_addr = GuestAddress(layout::RNG_SEED_START);
+        setup_rng_seed(guest_mem, rng_seed_addr)?;
+        params.hdr.setup_data = rng_seed_addr.raw_value();
+    }
 
     // We mark first [0x0, SYSTEM_MEM_START) region as usable RAM and the subsequent
     // [SYSTEM_MEM_START, (SYSTEM_MEM_START + SYSTEM_MEM_SIZE)) as reserved (note
@@ -197,6 +216,26 @@ pub fn configure_system(
     .map_err(|_| ConfigurationError::ZeroPageSetup)
 }
 
+/// Writes, at `addr`, a setup_data entry holding a seed for the kernel RNG read from the host RNG.
+fn setup_rng_seed(
+    guest_mem: &GuestMemoryMmap,
+    addr: GuestAddress,
+) -> Result<(), ConfigurationError> {
+    let mut seed = [0u8; RNG_SEED_SIZE];
+    aws_lc_rs::rand::fill(&mut seed).map_err(|_| ConfigurationError::RngSeedGeneration)?;
+
+    let mut setup_data = Vec::with_capacity(SETUP_DATA_HEADER_SIZE + RNG_SEED_SIZE);
+    // This is the only entry in the setup_data list.
+    setup_data.extend_from_slice(&0u64.to_le_bytes());
+    setup_data.extend_from_slice(&SETUP_RNG_SEED.to_le_bytes());
+    setup_data.extend_from_slice(&u32::try_from(RNG_SEED_SIZE).unwrap().to_le_bytes());
+    setup_data.extend_from_slice(&seed);
+
+    guest_mem
+        .write_slice(&setup_data, addr)
+        .map_err(|_| ConfigurationError::RngSeedSetup)
+}
+
 /// Add an e820 region to the e820 map.
 /// Returns Ok(()) if successful, or an error if there is no space left in the map.
 fn add_e820_entry(
@@ -245,8 +284,15 @@ mod tests {
         let no_vcpus = 4;
         let gm = single_region_mem(0x10000);
         let mut resource_allocator = ResourceAllocator::new().unwrap();
-        let config_err =
-            configure_system(&gm, &mut resource_allocator, GuestAddress(0), 0, &None, 1);
+        let config_err = configure_system(
+            &gm,
+            &mut resource_allocator,
+            GuestAddress(0),
+            0,
+            &None,
+            1,
+            false,
+        );
         assert_eq!(
             config_err.unwrap_err(),
             super::ConfigurationError::MpTableSetup(mptable::MptableError::NotEnoughMemory)
@@ -263,6 +309,7 @@ mod tests {
             0,
             &None,
             no_vcpus,
+            false,
         )
         .unwrap();
 
@@ -277,6 +324,7 @@ mod tests {
             0,
             &None,
             no_vcpus,
+            false,
         )
         .unwrap();
 
@@ -291,8 +339,57 @@ mod tests {
             0,
             &None,
             no_vcpus,
+            false,
+        )
+        .unwrap();
+    }
+
+    #[test]
+    fn test_system_configuration_rng_seed() {
+        let gm = arch_mem(128 << 20);
+        let zero_page_addr = GuestAddress(layout::ZERO_PAGE_START);
+        let rng_seed_addr = GuestAddress(layout::RNG_SEED_START);
+
+        let mut resource_allocator = ResourceAllocator::new().unwrap();
+        configure_system(
+            &gm,
+            &mut resource_allocator,
+            GuestAddress(0),
+            0,
+            &None,
+            1,
+            false,
+        )
+        .unwrap();
+        let params: boot_params = gm.read_obj(zero_page_addr).unwrap();
+        assert_eq!({ params.hdr.setup_data }, 0);
+
+        let mut resource_allocator = ResourceAllocator::new().unwrap();
+        configure_system(
+            &gm,
+            &mut resource_allocator,
+            GuestAddress(0),
+            0,
+            &None,
+            1,
+            true,
         )
         .unwrap();
+        let params: boot_params = gm.read_obj(zero_page_addr).unwrap();
+        assert_eq!({ params.hdr.setup_data }, rng_seed_addr.raw_value());
+
+        let next: u64 = gm.read_obj(rng_seed_addr).unwrap();
+        let type_: u32 = gm.read_obj(rng_seed_addr.unchecked_add(8)).unwrap();
+        let len: u32 = gm.read_obj(rng_seed_addr.unchecked_add(12)).unwrap();
+        assert_eq!(next, 0);
+        assert_eq!(type_, SETUP_RNG_SEED);
+        assert_eq!(len as usize, RNG_SEED_SIZE);
+
+        let mut seed = [0u8; RNG_SEED_SIZE];
+        gm.read_slice(&mut seed, rng_seed_addr.unchecked_add(16))
+            .unwrap();
+        // The odds of the host RNG returning only zeroes are negligible.
+        assert_ne!(seed, [0u8; RNG_SEED_SIZE]);
     }
 
     #[test]
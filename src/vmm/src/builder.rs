@@ -30,7 +30,7 @@ use vmm_sys_util::eventfd::EventFd;
 
 #[cfg(target_arch = "x86_64")]
 use crate::acpi;
-use crate::arch::InitrdConfig;
+use crate::arch::{DeviceType, InitrdConfig};
 #[cfg(target_arch = "aarch64")]
 use crate::construct_kvm_mpidrs;
 use crate::cpu_config::templates::{
@@ -47,9 +47,9 @@ use crate::device_manager::persist::{
 use crate::device_manager::resources::ResourceAllocator;
 use crate::devices::acpi::vmgenid::{VmGenId, VmGenIdError};
 use crate::devices::legacy::serial::SerialOut;
-#[cfg(target_arch = "aarch64")]
-use crate::devices::legacy::RTCDevice;
 use crate::devices::legacy::{EventFdTrigger, SerialEventsWrapper, SerialWrapper};
+#[cfg(target_arch = "aarch64")]
+use crate::devices::legacy::{Gpio, RTCDevice};
 use crate::devices::virtio::balloon::Balloon;
 use crate::devices::virtio::block::device::Block;
 use crate::devices::virtio::device::VirtioDevice;
@@ -57,16 +57,20 @@ use crate::devices::virtio::mmio::MmioTransport;
 use crate::devices::virtio::net::Net;
 use crate::devices::virtio::rng::Entropy;
 use crate::devices::virtio::vsock::{Vsock, VsockUnixBackend};
+use crate::devices::virtio::watchdog::Watchdog;
 use crate::devices::BusDevice;
 #[cfg(feature = "gdb")]
 use crate::gdb;
 use crate::logger::{debug, error};
 use crate::persist::{MicrovmState, MicrovmStateError};
+use crate::rate_limiter::TokenBucket;
 use crate::resources::VmResources;
 use crate::snapshot::Persist;
 use crate::utils::u64_to_usize;
 use crate::vmm_config::boot_source::BootConfig;
 use crate::vmm_config::instance_info::InstanceInfo;
+#[cfg(target_arch = "x86_64")]
+use crate::vmm_config::machine_config::CpuFrequencies;
 use crate::vmm_config::machine_config::{VmConfig, VmConfigError};
 use crate::vstate::memory::{GuestAddress, GuestMemory, GuestMemoryMmap};
 use crate::vstate::vcpu::{Vcpu, VcpuConfig, VcpuError};
@@ -148,18 +152,39 @@ impl std::convert::From<linux_loader::cmdline::Error> for StartMicrovmError {
     }
 }
 
+/// Settings of the microVM used to create its VM, vCPUs and legacy devices.
+#[derive(Debug)]
+struct VmmCreateConfig {
+    /// Whether the dirty pages of the guest memory are tracked.
+    track_dirty_pages: bool,
+    /// Number of vCPUs to create.
+    vcpu_count: u8,
+    /// KVM capabilities to check or disable on the VM.
+    kvm_capabilities: Vec<KvmCapability>,
+    /// Whether the PMU is enabled. When not set, it is disabled wherever KVM allows it.
+    pmu: Option<bool>,
+    /// Rate limiter of the serial console output.
+    serial_rate_limiter: Option<TokenBucket>,
+}
+
 #[cfg_attr(target_arch = "aarch64", allow(unused))]
 fn create_vmm_and_vcpus(
     instance_info: &InstanceInfo,
     event_manager: &mut EventManager,
     guest_memory: GuestMemoryMmap,
     uffd: Option<Uffd>,
-    track_dirty_pages: bool,
-    vcpu_count: u8,
-    kvm_capabilities: Vec<KvmCapability>,
+    config: VmmCreateConfig,
 ) -> Result<(Vmm, Vec<Vcpu>), StartMicrovmError> {
     use self::StartMicrovmError::*;
 
+    let VmmCreateConfig {
+        track_dirty_pages,
+        vcpu_count,
+        kvm_capabilities,
+        pmu,
+        serial_rate_limiter,
+    } = config;
+
     // Set up Kvm Vm and register memory regions.
     // Build custom CPU config if a custom template is provided.
     let mut vm = Vm::new(kvm_capabilities)
@@ -185,6 +210,11 @@ fn create_vmm_and_vcpus(
     // while on aarch64 we need to do it the other way around.
     #[cfg(target_arch = "x86_64")]
     let (vcpus, pio_device_manager) = {
+        // The PMU can only be disabled before creating the vCPUs. Unless it is enabled, it is
+        // disabled wherever KVM allows it.
+        if pmu == Some(false) || (pmu.is_none() && vm.can_disable_pmu()) {
+            vm.disable_pmu().map_err(VmmError::Vm).map_err(Internal)?;
+        }
         setup_interrupt_controller(&mut vm)?;
         let vcpus = create_vcpus(&vm, vcpu_count, &vcpus_exit_evt).map_err(Internal)?;
 
@@ -192,8 +222,13 @@ fn create_vmm_and_vcpus(
         set_stdout_nonblocking();
 
         // Serial device setup.
-        let serial_device =
-            setup_serial_device(event_manager, std::io::stdin(), io::stdout()).map_err(Internal)?;
+        let serial_device = setup_serial_device(
+            event_manager,
+            std::io::stdin(),
+            io::stdout(),
+            serial_rate_limiter,
+        )
+        .map_err(Internal)?;
 
         // x86_64 uses the i8042 reset event as the Vmm exit event.
         let reset_evt = vcpus_exit_evt
@@ -227,8 +262,12 @@ fn create_vmm_and_vcpus(
         events_observer: Some(std::io::stdin()),
         instance_info: instance_info.clone(),
         shutdown_exit_code: None,
+        boot_time_us: None,
+        #[cfg(target_arch = "x86_64")]
+        cpu_frequencies: None,
         vm,
         guest_memory,
+        fetched_dirty_log: Mutex::default(),
         uffd,
         vcpus_handles: Vec::new(),
         vcpus_exit_evt,
@@ -281,11 +320,28 @@ pub fn build_microvm_for_boot(
         event_manager,
         guest_memory,
         None,
-        vm_resources.vm_config.track_dirty_pages,
-        vm_resources.vm_config.vcpu_count,
-        cpu_template.kvm_capabilities.clone(),
+        VmmCreateConfig {
+            track_dirty_pages: vm_resources.vm_config.track_dirty_pages,
+            vcpu_count: vm_resources.vm_config.vcpu_count,
+            kvm_capabilities: cpu_template.kvm_capabilities.clone(),
+            pmu: vm_resources.vm_config.cpuid.pmu,
+            serial_rate_limiter: serial_output_rate_limiter(&vm_resources.vm_config),
+        },
     )?;
 
+    if let Some(kvm_run_retries) = vm_resources.vm_config.kvm_run_retries {
+        vcpus
+            .iter_mut()
+            .for_each(|vcpu| vcpu.set_kvm_run_retries(kvm_run_retries));
+    }
+
+    if let Some(vcpu_idle_strategy) = vm_resources.vm_config.vcpu_idle_strategy {
+        vmm.vm
+            .set_halt_poll_ns(vcpu_idle_strategy.halt_poll_ns())
+            .map_err(VmmError::Vm)
+            .map_err(StartMicrovmError::Internal)?;
+    }
+
     #[cfg(feature = "gdb")]
     let (gdb_tx, gdb_rx) = mpsc::channel();
     #[cfg(feature = "gdb")]
@@ -298,6 +354,9 @@ pub fn build_microvm_for_boot(
         .map(|vcpu| vcpu.copy_kvm_vcpu_fd(vmm.vm()))
         .collect::<Result<Vec<_>, _>>()?;
 
+    // The limit applies to the virtio devices attached at boot and to the ones attached later.
+    vmm.mmio_device_manager.max_desc_chain_len = vm_resources.vm_config.max_desc_chain_len;
+
     // The boot timer device needs to be the first device attached in order
     // to maintain the same MMIO address referenced in the documentation
     // and tests.
@@ -330,8 +389,18 @@ pub fn build_microvm_for_boot(
         attach_entropy_device(&mut vmm, &mut boot_cmdline, entropy, event_manager)?;
     }
 
+    if let Some(watchdog) = vm_resources.watchdog.get() {
+        attach_watchdog_device(&mut vmm, &mut boot_cmdline, watchdog, event_manager)?;
+    }
+
     #[cfg(target_arch = "aarch64")]
-    attach_legacy_devices_aarch64(event_manager, &mut vmm, &mut boot_cmdline).map_err(Internal)?;
+    attach_legacy_devices_aarch64(
+        event_manager,
+        &mut vmm,
+        &mut boot_cmdline,
+        serial_output_rate_limiter(&vm_resources.vm_config),
+    )
+    .map_err(Internal)?;
 
     attach_vmgenid_device(&mut vmm)?;
 
@@ -343,6 +412,7 @@ pub fn build_microvm_for_boot(
         entry_addr,
         &initrd,
         boot_cmdline,
+        boot_config.rng_seed,
     )?;
 
     let vmm = Arc::new(Mutex::new(vmm));
@@ -369,10 +439,12 @@ pub fn build_microvm_for_boot(
         .map_err(Internal)?;
 
     // Load seccomp filters for the VMM thread.
-    // Execution panics if filters cannot be loaded, use --no-seccomp if skipping filters
-    // altogether is the desired behaviour.
+    // The microVM start fails if filters cannot be loaded, unless the failure policy is to warn.
+    // Use --no-seccomp if skipping filters altogether is the desired behaviour.
     // Keep this as the last step before resuming vcpus.
-    seccompiler::apply_filter(
+    crate::seccomp_audit::register_thread();
+    crate::seccomp_filters::install_filter(
+        "VMM",
         seccomp_filters
             .get("vmm")
             .ok_or_else(|| MissingSeccompFilters("vmm".to_string()))?,
@@ -464,6 +536,12 @@ pub fn build_microvm_from_snapshot(
     seccomp_filters: &BpfThreadMap,
     vm_resources: &mut VmResources,
 ) -> Result<Arc<Mutex<Vmm>>, BuildMicrovmFromSnapshotError> {
+    // The vCPUs keep their snapshotted CPUID, and the PMU is disabled if it was.
+    #[cfg(target_arch = "x86_64")]
+    let pmu = Some(!microvm_state.vm_state.pmu_disabled);
+    #[cfg(target_arch = "aarch64")]
+    let pmu = None;
+
     // Build Vmm.
     debug!("event_start: build microvm from snapshot");
     let (mut vmm, mut vcpus) = create_vmm_and_vcpus(
@@ -471,11 +549,28 @@ pub fn build_microvm_from_snapshot(
         event_manager,
         guest_memory.clone(),
         uffd,
-        vm_resources.vm_config.track_dirty_pages,
-        vm_resources.vm_config.vcpu_count,
-        microvm_state.vm_state.kvm_cap_modifiers.clone(),
+        VmmCreateConfig {
+            track_dirty_pages: vm_resources.vm_config.track_dirty_pages,
+            vcpu_count: vm_resources.vm_config.vcpu_count,
+            kvm_capabilities: microvm_state.vm_state.kvm_cap_modifiers.clone(),
+            pmu,
+            serial_rate_limiter: serial_output_rate_limiter(&vm_resources.vm_config),
+        },
     )?;
 
+    if let Some(kvm_run_retries) = vm_resources.vm_config.kvm_run_retries {
+        vcpus
+            .iter_mut()
+            .for_each(|vcpu| vcpu.set_kvm_run_retries(kvm_run_retries));
+    }
+
+    if let Some(vcpu_idle_strategy) = vm_resources.vm_config.vcpu_idle_strategy {
+        vmm.vm
+            .set_halt_poll_ns(vcpu_idle_strategy.halt_poll_ns())
+            .map_err(VmmError::Vm)
+            .map_err(StartMicrovmError::Internal)?;
+    }
+
     #[cfg(target_arch = "x86_64")]
     {
         // Scale TSC to match, extract the TSC freq from the state if specified
@@ -497,6 +592,13 @@ pub fn build_microvm_from_snapshot(
             .map_err(VcpuError::VcpuResponse)
             .map_err(BuildMicrovmFromSnapshotError::RestoreVcpus)?;
     }
+    #[cfg(target_arch = "x86_64")]
+    {
+        vmm.cpu_frequencies = microvm_state
+            .vcpu_states
+            .first()
+            .and_then(|state| CpuFrequencies::from_cpuid(&state.cpuid));
+    }
 
     #[cfg(target_arch = "aarch64")]
     {
@@ -520,6 +622,7 @@ pub fn build_microvm_from_snapshot(
         resource_allocator: &mut vmm.resource_allocator,
         vm_resources,
         instance_id: &instance_info.id,
+        exit_evt: &vmm.vcpus_exit_evt,
     };
 
     vmm.mmio_device_manager =
@@ -559,7 +662,9 @@ pub fn build_microvm_from_snapshot(
 
     // Load seccomp filters for the VMM thread.
     // Keep this as the last step of the building process.
-    seccompiler::apply_filter(
+    crate::seccomp_audit::register_thread();
+    crate::seccomp_filters::install_filter(
+        "VMM",
         seccomp_filters
             .get("vmm")
             .ok_or(BuildMicrovmFromSnapshotError::MissingVmmSeccompFilters)?,
@@ -678,12 +783,28 @@ pub fn setup_interrupt_controller(vm: &mut Vm, vcpu_count: u8) -> Result<(), Sta
         .map_err(StartMicrovmError::Internal)
 }
 
-/// Sets up the serial device.
+/// Builds the rate limiter of the guest output to the serial console, if one is configured.
+pub(crate) fn serial_output_rate_limiter(vm_config: &VmConfig) -> Option<TokenBucket> {
+    vm_config.serial_output_rate_limiter.and_then(|tb_cfg| {
+        TokenBucket::new(
+            tb_cfg.size,
+            tb_cfg.one_time_burst.unwrap_or(0),
+            tb_cfg.refill_time,
+        )
+    })
+}
+
+/// Sets up the serial device, dropping the output exceeding `rate_limiter` if any.
 pub fn setup_serial_device(
     event_manager: &mut EventManager,
     input: std::io::Stdin,
     out: std::io::Stdout,
+    rate_limiter: Option<TokenBucket>,
 ) -> Result<Arc<Mutex<BusDevice>>, VmmError> {
+    let out = match rate_limiter {
+        Some(bucket) => SerialOut::RateLimited(Box::new(SerialOut::Stdout(out)), bucket),
+        None => SerialOut::Stdout(out),
+    };
     let interrupt_evt = EventFdTrigger::new(EventFd::new(EFD_NONBLOCK).map_err(VmmError::EventFd)?);
     let kick_stdin_read_evt =
         EventFdTrigger::new(EventFd::new(EFD_NONBLOCK).map_err(VmmError::EventFd)?);
@@ -693,7 +814,7 @@ pub fn setup_serial_device(
             SerialEventsWrapper {
                 buffer_ready_event_fd: Some(kick_stdin_read_evt),
             },
-            SerialOut::Stdout(out),
+            out,
         ),
         input: Some(input),
     })));
@@ -706,6 +827,7 @@ fn attach_legacy_devices_aarch64(
     event_manager: &mut EventManager,
     vmm: &mut Vmm,
     cmdline: &mut LoaderKernelCmdline,
+    serial_rate_limiter: Option<TokenBucket>,
 ) -> Result<(), VmmError> {
     // Serial device setup.
     let cmdline_contains_console = cmdline
@@ -718,7 +840,12 @@ fn attach_legacy_devices_aarch64(
     if cmdline_contains_console {
         // Make stdout non-blocking.
         set_stdout_nonblocking();
-        let serial = setup_serial_device(event_manager, std::io::stdin(), std::io::stdout())?;
+        let serial = setup_serial_device(
+            event_manager,
+            std::io::stdin(),
+            std::io::stdout(),
+            serial_rate_limiter,
+        )?;
         vmm.mmio_device_manager
             .register_mmio_serial(vmm.vm.fd(), &mut vmm.resource_allocator, serial, None)
             .map_err(VmmError::RegisterMMIODevice)?;
@@ -732,6 +859,13 @@ fn attach_legacy_devices_aarch64(
     ));
     vmm.mmio_device_manager
         .register_mmio_rtc(&mut vmm.resource_allocator, rtc, None)
+        .map_err(VmmError::RegisterMMIODevice)?;
+
+    let gpio = Gpio::new(EventFdTrigger::new(
+        EventFd::new(libc::EFD_NONBLOCK).map_err(VmmError::EventFd)?,
+    ));
+    vmm.mmio_device_manager
+        .register_mmio_gpio(vmm.vm.fd(), &mut vmm.resource_allocator, gpio, None)
         .map_err(VmmError::RegisterMMIODevice)
 }
 
@@ -755,6 +889,7 @@ pub fn configure_system_for_boot(
     entry_addr: GuestAddress,
     initrd: &Option<InitrdConfig>,
     boot_cmdline: LoaderKernelCmdline,
+    rng_seed: bool,
 ) -> Result<(), StartMicrovmError> {
     use self::StartMicrovmError::*;
 
@@ -796,6 +931,8 @@ pub fn configure_system_for_boot(
         vcpu_count: vm_config.vcpu_count,
         smt: vm_config.smt,
         cpu_config,
+        #[cfg(target_arch = "x86_64")]
+        cpuid_options: vm_config.cpuid.clone(),
     };
 
     // Configure vCPUs with normalizing and setting the generated CPU configuration.
@@ -806,6 +943,16 @@ pub fn configure_system_for_boot(
             .map_err(Internal)?;
     }
 
+    // All vCPUs advertise the same frequencies, read back from KVM since a CPU template can
+    // alter the leaf too.
+    #[cfg(target_arch = "x86_64")]
+    {
+        vmm.cpu_frequencies = vcpus
+            .first()
+            .and_then(|vcpu| vcpu.kvm_vcpu.get_cpuid().ok())
+            .and_then(|cpuid| CpuFrequencies::from_cpuid(&cpuid));
+    }
+
     #[cfg(target_arch = "x86_64")]
     {
         // Write the kernel command line to guest memory. This is x86_64 specific, since on
@@ -827,6 +974,7 @@ pub fn configure_system_for_boot(
             cmdline_size,
             initrd,
             vcpu_config.vcpu_count,
+            rng_seed,
         )
         .map_err(ConfigureSystem)?;
 
@@ -872,7 +1020,11 @@ fn attach_virtio_device<T: 'static + VirtioDevice + MutEventSubscriber + Debug>(
 ) -> Result<(), StartMicrovmError> {
     use self::StartMicrovmError::*;
 
-    event_manager.add_subscriber(device.clone());
+    let subscriber_id = event_manager.add_subscriber(device.clone());
+    let device_type = DeviceType::Virtio(device.lock().expect("Poisoned lock").device_type());
+    vmm.mmio_device_manager
+        .subscriber_ids
+        .insert((device_type, id.clone()), subscriber_id);
 
     // The device mutex mustn't be locked here otherwise it will deadlock.
     let device = MmioTransport::new(vmm.guest_memory().clone(), device, is_vhost_user);
@@ -936,6 +1088,35 @@ fn attach_entropy_device(
     )
 }
 
+fn attach_watchdog_device(
+    vmm: &mut Vmm,
+    cmdline: &mut LoaderKernelCmdline,
+    watchdog_device: &Arc<Mutex<Watchdog>>,
+    event_manager: &mut EventManager,
+) -> Result<(), StartMicrovmError> {
+    // The watchdog uses the Vmm exit event to reset or stop the microVM on expiry.
+    let exit_evt = vmm
+        .vcpus_exit_evt
+        .try_clone()
+        .map_err(VmmError::EventFd)
+        .map_err(StartMicrovmError::Internal)?;
+
+    let id = {
+        let mut locked = watchdog_device.lock().expect("Poisoned lock");
+        locked.set_exit_evt(exit_evt);
+        locked.id().to_string()
+    };
+
+    attach_virtio_device(
+        event_manager,
+        vmm,
+        id,
+        watchdog_device.clone(),
+        cmdline,
+        false,
+    )
+}
+
 fn attach_block_devices<'a, I: Iterator<Item = &'a Arc<Mutex<Block>>> + Debug>(
     vmm: &mut Vmm,
     cmdline: &mut LoaderKernelCmdline,
@@ -1031,21 +1212,29 @@ pub(crate) mod tests {
 
     use super::*;
     use crate::arch::DeviceType;
+    use crate::device_manager::mmio::MmioError;
     use crate::device_manager::resources::ResourceAllocator;
     use crate::devices::virtio::block::CacheType;
+    use crate::devices::virtio::queue::FIRECRACKER_MAX_QUEUE_SIZE;
     use crate::devices::virtio::rng::device::ENTROPY_DEV_ID;
-    use crate::devices::virtio::vsock::{TYPE_VSOCK, VSOCK_DEV_ID};
-    use crate::devices::virtio::{TYPE_BALLOON, TYPE_BLOCK, TYPE_RNG};
+    use crate::devices::virtio::vsock::{VsockStats, TYPE_VSOCK, VSOCK_DEV_ID};
+    use crate::devices::virtio::watchdog::WATCHDOG_DEV_ID;
+    use crate::devices::virtio::{TYPE_BALLOON, TYPE_BLOCK, TYPE_NET, TYPE_RNG, TYPE_WATCHDOG};
     use crate::mmds::data_store::{Mmds, MmdsVersion};
     use crate::mmds::ns::MmdsNetworkStack;
-    use crate::test_utils::{arch_mem, single_region_mem, single_region_mem_at};
+    use crate::rate_limiter::{BucketUpdate, TokenBucket, TokenType};
+    use crate::test_utils::{arch_mem, multi_region_mem, single_region_mem, single_region_mem_at};
     use crate::vmm_config::balloon::{BalloonBuilder, BalloonDeviceConfig, BALLOON_DEV_ID};
     use crate::vmm_config::boot_source::DEFAULT_KERNEL_CMDLINE;
+    use crate::vmm_config::devices::{QueueMemoryUsage, VirtioDeviceType};
     use crate::vmm_config::drive::{BlockBuilder, BlockDeviceConfig};
     use crate::vmm_config::entropy::{EntropyDeviceBuilder, EntropyDeviceConfig};
+    use crate::vmm_config::instance_info::VmState;
     use crate::vmm_config::net::{NetBuilder, NetworkInterfaceConfig};
     use crate::vmm_config::vsock::tests::default_config;
     use crate::vmm_config::vsock::{VsockBuilder, VsockDeviceConfig};
+    use crate::vmm_config::watchdog::{WatchdogDeviceBuilder, WatchdogDeviceConfig};
+    use crate::vmm_config::{RateLimiterConfig, RateLimiterState, TokenBucketConfig};
 
     #[derive(Debug)]
     pub(crate) struct CustomBlockConfig {
@@ -1142,8 +1331,12 @@ pub(crate) mod tests {
             events_observer: Some(std::io::stdin()),
             instance_info: InstanceInfo::default(),
             shutdown_exit_code: None,
+            boot_time_us: None,
+            #[cfg(target_arch = "x86_64")]
+            cpu_frequencies: None,
             vm,
             guest_memory,
+            fetched_dirty_log: Mutex::default(),
             uffd: None,
             vcpus_handles: Vec::new(),
             vcpus_exit_evt,
@@ -1184,6 +1377,8 @@ pub(crate) mod tests {
                 ),
                 rate_limiter: None,
                 file_engine_type: None,
+                direct: None,
+                discard: None,
 
                 socket: None,
             };
@@ -1229,6 +1424,7 @@ pub(crate) mod tests {
         net.lock().unwrap().configure_mmds_network_stack(
             MmdsNetworkStack::default_ipv4_addr(),
             Arc::new(Mutex::new(mmds)),
+            None,
         );
 
         attach_net_devices(vmm, cmdline, net_builder.iter(), event_manager).unwrap();
@@ -1269,6 +1465,23 @@ pub(crate) mod tests {
             .is_some());
     }
 
+    pub(crate) fn insert_watchdog_device(
+        vmm: &mut Vmm,
+        cmdline: &mut Cmdline,
+        event_manager: &mut EventManager,
+        watchdog_config: WatchdogDeviceConfig,
+    ) {
+        let mut builder = WatchdogDeviceBuilder::new();
+        let watchdog = builder.build(watchdog_config).unwrap();
+
+        attach_watchdog_device(vmm, cmdline, &watchdog, event_manager).unwrap();
+
+        assert!(vmm
+            .mmio_device_manager
+            .get_device(DeviceType::Virtio(TYPE_WATCHDOG), WATCHDOG_DEV_ID)
+            .is_some());
+    }
+
     #[cfg(target_arch = "x86_64")]
     pub(crate) fn insert_vmgenid_device(vmm: &mut Vmm) {
         attach_vmgenid_device(vmm).unwrap();
@@ -1382,6 +1595,7 @@ pub(crate) mod tests {
             guest_mac: None,
             rx_rate_limiter: None,
             tx_rate_limiter: None,
+            mtu: None,
         };
 
         let mut cmdline = default_kernel_cmdline();
@@ -1397,6 +1611,249 @@ pub(crate) mod tests {
         net_builder.build(network_interface).unwrap_err();
     }
 
+    #[test]
+    fn test_remove_net_device() {
+        let mut event_manager = EventManager::new().expect("Unable to create EventManager");
+        let mut vmm = default_vmm();
+        let mut vm_resources = VmResources::default();
+
+        let network_interface = NetworkInterfaceConfig {
+            iface_id: String::from("netif"),
+            host_dev_name: String::from("hostname"),
+            guest_mac: None,
+            rx_rate_limiter: None,
+            tx_rate_limiter: None,
+            mtu: None,
+        };
+
+        let mut cmdline = default_kernel_cmdline();
+        vm_resources
+            .build_net_device(network_interface.clone())
+            .unwrap();
+        attach_net_devices(
+            &mut vmm,
+            &mut cmdline,
+            vm_resources.net_builder.iter(),
+            &mut event_manager,
+        )
+        .unwrap();
+        let net = vm_resources.net_builder.iter().next().unwrap().clone();
+        let device_info = vmm.mmio_device_manager.id_to_dev_info
+            [&(DeviceType::Virtio(TYPE_NET), String::from("netif"))]
+            .clone();
+
+        // Unknown devices cannot be removed.
+        assert!(matches!(
+            vmm.remove_net_device("unknown", &mut vm_resources, &mut event_manager),
+            Err(VmmError::DeviceManager(MmioError::DeviceNotFound))
+        ));
+
+        vmm.remove_net_device("netif", &mut vm_resources, &mut event_manager)
+            .unwrap();
+        assert!(vmm
+            .get_bus_device(DeviceType::Virtio(TYPE_NET), "netif")
+            .is_none());
+        assert_eq!(vm_resources.net_builder.iter().count(), 0);
+        // The bus, the event manager and the network interfaces no longer hold the device, which
+        // is dropped along with its tap.
+        assert_eq!(Arc::strong_count(&net), 1);
+        drop(net);
+        assert!(matches!(
+            vmm.remove_net_device("netif", &mut vm_resources, &mut event_manager),
+            Err(VmmError::DeviceManager(MmioError::DeviceNotFound))
+        ));
+
+        // The same interface can be added again, on the same tap, and uses the MMIO resources of
+        // the removed device.
+        vm_resources.build_net_device(network_interface).unwrap();
+        attach_net_devices(
+            &mut vmm,
+            &mut cmdline,
+            vm_resources.net_builder.iter(),
+            &mut event_manager,
+        )
+        .unwrap();
+        assert_eq!(
+            vmm.mmio_device_manager.id_to_dev_info
+                [&(DeviceType::Virtio(TYPE_NET), String::from("netif"))],
+            device_info
+        );
+        assert!(vmm
+            .get_bus_device(DeviceType::Virtio(TYPE_NET), "netif")
+            .is_some());
+    }
+
+    #[test]
+    fn test_memory_regions() {
+        let mut vmm = default_vmm();
+        vmm.guest_memory = multi_region_mem(&[
+            (GuestAddress(0), 0x10_0000),
+            (GuestAddress(0x1_0000_0000), 0x20_0000),
+        ]);
+
+        assert_eq!(
+            vmm.memory_regions(),
+            vec![(0, 0x10_0000), (0x1_0000_0000, 0x20_0000)]
+        );
+    }
+
+    #[test]
+    fn test_block_rate_limiter_config() {
+        let mut event_manager = EventManager::new().expect("Unable to create EventManager");
+        let mut vmm = default_vmm();
+        let mut cmdline = default_kernel_cmdline();
+        let block_configs = vec![CustomBlockConfig::new(
+            String::from("root"),
+            true,
+            None,
+            true,
+            CacheType::Unsafe,
+        )];
+        let _block_files =
+            insert_block_devices(&mut vmm, &mut cmdline, &mut event_manager, block_configs);
+
+        assert_eq!(
+            vmm.block_rate_limiter_config("root").unwrap(),
+            RateLimiterConfig::default()
+        );
+        assert!(matches!(
+            vmm.block_rate_limiter_config("unknown"),
+            Err(VmmError::DeviceManager(MmioError::DeviceNotFound))
+        ));
+
+        vmm.update_block_rate_limiter(
+            "root",
+            BucketUpdate::Update(TokenBucket::new(1000, 100, 10).unwrap()),
+            BucketUpdate::Update(TokenBucket::new(200, 0, 20).unwrap()),
+        )
+        .unwrap();
+        assert_eq!(
+            vmm.block_rate_limiter_config("root").unwrap(),
+            RateLimiterConfig {
+                bandwidth: Some(TokenBucketConfig {
+                    size: 1000,
+                    one_time_burst: Some(100),
+                    refill_time: 10,
+                }),
+                ops: Some(TokenBucketConfig {
+                    size: 200,
+                    one_time_burst: None,
+                    refill_time: 20,
+                }),
+            }
+        );
+
+        // Buckets left untouched keep their configuration, disabled ones are reported as such.
+        vmm.update_block_rate_limiter("root", BucketUpdate::None, BucketUpdate::Disabled)
+            .unwrap();
+        assert_eq!(
+            vmm.block_rate_limiter_config("root").unwrap(),
+            RateLimiterConfig {
+                bandwidth: Some(TokenBucketConfig {
+                    size: 1000,
+                    one_time_burst: Some(100),
+                    refill_time: 10,
+                }),
+                ops: None,
+            }
+        );
+    }
+
+    #[test]
+    fn test_block_rate_limiter_state() {
+        let mut event_manager = EventManager::new().expect("Unable to create EventManager");
+        let mut vmm = default_vmm();
+        let mut cmdline = default_kernel_cmdline();
+        let block_configs = vec![CustomBlockConfig::new(
+            String::from("root"),
+            true,
+            None,
+            true,
+            CacheType::Unsafe,
+        )];
+        let _block_files =
+            insert_block_devices(&mut vmm, &mut cmdline, &mut event_manager, block_configs);
+
+        assert_eq!(
+            vmm.block_rate_limiter_state("root").unwrap(),
+            RateLimiterState::default()
+        );
+        assert!(matches!(
+            vmm.block_rate_limiter_state("unknown"),
+            Err(VmmError::DeviceManager(MmioError::DeviceNotFound))
+        ));
+
+        // The bucket takes long enough to refill for no token to be added during the test.
+        vmm.update_block_rate_limiter(
+            "root",
+            BucketUpdate::Update(TokenBucket::new(1000, 0, 1_000_000).unwrap()),
+            BucketUpdate::None,
+        )
+        .unwrap();
+        let state = vmm.block_rate_limiter_state("root").unwrap();
+        assert_eq!(state.bandwidth.unwrap().budget, 1000);
+        assert_eq!(state.ops, None);
+
+        vmm.mmio_device_manager
+            .with_virtio_device_with_id(TYPE_BLOCK, "root", |block: &mut Block| {
+                match block {
+                    Block::Virtio(block) => {
+                        assert!(block.rate_limiter.consume(300, TokenType::Bytes));
+                    }
+                    Block::VhostUser(_) => unreachable!(),
+                }
+                Ok(())
+            })
+            .unwrap();
+        let bandwidth = vmm
+            .block_rate_limiter_state("root")
+            .unwrap()
+            .bandwidth
+            .unwrap();
+        assert_eq!(bandwidth.size, 1000);
+        assert_eq!(bandwidth.budget, 700);
+    }
+
+    #[test]
+    fn test_quiesce_io() {
+        let mut event_manager = EventManager::new().expect("Unable to create EventManager");
+        let mut vmm = default_vmm();
+        let mut cmdline = default_kernel_cmdline();
+        let block_configs = vec![CustomBlockConfig::new(
+            String::from("root"),
+            true,
+            None,
+            true,
+            CacheType::Unsafe,
+        )];
+        let _block_files =
+            insert_block_devices(&mut vmm, &mut cmdline, &mut event_manager, block_configs);
+
+        let is_io_quiesced = |vmm: &Vmm| {
+            let mut quiesced = false;
+            vmm.mmio_device_manager
+                .with_virtio_device_with_id(TYPE_BLOCK, "root", |block: &mut Block| {
+                    match block {
+                        Block::Virtio(block) => {
+                            quiesced = block.is_io_quiesced;
+                            // Either way, no request is left in flight.
+                            assert!(!block.is_io_engine_throttled);
+                        }
+                        Block::VhostUser(_) => unreachable!(),
+                    }
+                    Ok(())
+                })
+                .unwrap();
+            quiesced
+        };
+
+        assert!(!is_io_quiesced(&vmm));
+        vmm.quiesce_io();
+        assert!(is_io_quiesced(&vmm));
+        vmm.mmio_device_manager.resume_io();
+        assert!(!is_io_quiesced(&vmm));
+    }
+
     #[test]
     fn test_attach_block_devices() {
         let mut event_manager = EventManager::new().expect("Unable to create EventManager");
@@ -1597,6 +2054,7 @@ pub(crate) mod tests {
             amount_mib: 0,
             deflate_on_oom: false,
             stats_polling_interval_s: 0,
+            free_page_reporting: false,
         };
 
         let mut cmdline = default_kernel_cmdline();
@@ -1609,6 +2067,114 @@ pub(crate) mod tests {
         ));
     }
 
+    #[test]
+    fn test_balloon_inflation_progress() {
+        let mut event_manager = EventManager::new().expect("Unable to create EventManager");
+        let mut vmm = default_vmm();
+
+        assert_eq!(
+            format!("{:?}", vmm.balloon_inflation_progress()),
+            "Err(DeviceNotFound)"
+        );
+
+        let balloon_config = BalloonDeviceConfig {
+            amount_mib: 16,
+            deflate_on_oom: false,
+            stats_polling_interval_s: 0,
+            free_page_reporting: false,
+        };
+        let mut cmdline = default_kernel_cmdline();
+        insert_balloon_device(&mut vmm, &mut cmdline, &mut event_manager, balloon_config);
+
+        // The guest has not given back any memory yet.
+        assert_eq!(vmm.balloon_inflation_progress().unwrap(), (0, 16));
+
+        // The progress follows the `actual` register written by the guest driver.
+        vmm.get_bus_device(DeviceType::Virtio(TYPE_BALLOON), BALLOON_DEV_ID)
+            .unwrap()
+            .lock()
+            .unwrap()
+            .mmio_transport_ref()
+            .unwrap()
+            .device()
+            .lock()
+            .unwrap()
+            .as_mut_any()
+            .downcast_mut::<Balloon>()
+            .unwrap()
+            .update_actual_pages(2048);
+        assert_eq!(vmm.balloon_inflation_progress().unwrap(), (8, 16));
+    }
+
+    #[test]
+    fn test_compact_guest_memory() {
+        let mut event_manager = EventManager::new().expect("Unable to create EventManager");
+        let mut vmm = default_vmm();
+
+        assert_eq!(
+            format!("{:?}", vmm.compact_guest_memory(8)),
+            "Err(DeviceNotFound)"
+        );
+
+        let balloon_config = BalloonDeviceConfig {
+            amount_mib: 4,
+            deflate_on_oom: false,
+            stats_polling_interval_s: 0,
+            free_page_reporting: false,
+        };
+        let mut cmdline = default_kernel_cmdline();
+        insert_balloon_device(&mut vmm, &mut cmdline, &mut event_manager, balloon_config);
+        let balloon = vmm
+            .get_bus_device(DeviceType::Virtio(TYPE_BALLOON), BALLOON_DEV_ID)
+            .unwrap()
+            .lock()
+            .unwrap()
+            .mmio_transport_ref()
+            .unwrap()
+            .device();
+
+        assert_eq!(
+            format!("{:?}", vmm.compact_guest_memory(8)),
+            "Err(DeviceNotActive)"
+        );
+
+        balloon
+            .lock()
+            .unwrap()
+            .activate(vmm.guest_memory().clone())
+            .unwrap();
+        assert_eq!(
+            format!("{:?}", vmm.compact_guest_memory(u32::MAX)),
+            "Err(TooManyPagesRequested)"
+        );
+        assert_eq!(vmm.balloon_inflation_progress().unwrap(), (0, 4));
+
+        // The compaction is started without waiting for the guest driver, the balloon device
+        // deflates the balloon back once it completes.
+        vmm.compact_guest_memory(8).unwrap();
+        assert_eq!(vmm.balloon_inflation_progress().unwrap(), (0, 12));
+        assert_eq!(
+            format!("{:?}", vmm.compact_guest_memory(8)),
+            "Err(CompactionInProgress)"
+        );
+        // Updating the target size meanwhile would be undone once the compaction completes.
+        assert_eq!(
+            format!("{:?}", vmm.update_balloon_config(2)),
+            "Err(CompactionInProgress)"
+        );
+        assert_eq!(vmm.balloon_inflation_progress().unwrap(), (0, 12));
+        // No compaction completed yet.
+        assert_eq!(vmm.guest_memory_compaction().unwrap().reclaimed_mib, 0);
+        vmm.balloon_compaction_event().unwrap();
+
+        // The compaction would not progress while the microVM is paused.
+        vmm.instance_info.state = VmState::Paused;
+        assert_eq!(
+            format!("{:?}", vmm.compact_guest_memory(8)),
+            "Err(CompactionWhilePaused)"
+        );
+    }
+
     #[test]
     fn test_attach_entropy_device() {
         let mut event_manager = EventManager::new().expect("Unable to create EventManager");
@@ -1626,6 +2192,212 @@ pub(crate) mod tests {
         ));
     }
 
+    #[test]
+    fn test_update_rng_rate_limiter() {
+        let mut event_manager = EventManager::new().expect("Unable to create EventManager");
+        let mut vmm = default_vmm();
+        let mut cmdline = default_kernel_cmdline();
+
+        let bucket = || BucketUpdate::Update(TokenBucket::new(1000, 0, 100).unwrap());
+        assert!(matches!(
+            vmm.update_rng_rate_limiter(bucket()),
+            Err(VmmError::DeviceManager(MmioError::DeviceNotFound))
+        ));
+
+        insert_entropy_device(
+            &mut vmm,
+            &mut cmdline,
+            &mut event_manager,
+            EntropyDeviceConfig::default(),
+        );
+        vmm.update_rng_rate_limiter(bucket()).unwrap();
+        vmm.mmio_device_manager
+            .with_virtio_device_with_id(TYPE_RNG, ENTROPY_DEV_ID, |entropy: &mut Entropy| {
+                assert_eq!(entropy.rate_limiter().bandwidth().unwrap().capacity(), 1000);
+                assert!(entropy.rate_limiter().ops().is_none());
+                Ok(())
+            })
+            .unwrap();
+    }
+
+    #[test]
+    fn test_device_memory_usage() {
+        let mut event_manager = EventManager::new().expect("Unable to create EventManager");
+        let mut vmm = default_vmm();
+
+        assert!(matches!(
+            vmm.device_memory_usage(VirtioDeviceType::Rng, ENTROPY_DEV_ID),
+            Err(VmmError::DeviceManager(MmioError::DeviceNotFound))
+        ));
+
+        let mut cmdline = default_kernel_cmdline();
+        insert_entropy_device(
+            &mut vmm,
+            &mut cmdline,
+            &mut event_manager,
+            EntropyDeviceConfig::default(),
+        );
+
+        // The driver did not select a queue size yet, so only the ring headers are accounted.
+        let usage = vmm
+            .device_memory_usage(VirtioDeviceType::Rng, ENTROPY_DEV_ID)
+            .unwrap();
+        assert_eq!(
+            usage.queues,
+            vec![QueueMemoryUsage {
+                max_size: FIRECRACKER_MAX_QUEUE_SIZE,
+                size: 0,
+                bytes: 12,
+            }]
+        );
+
+        vmm.get_bus_device(DeviceType::Virtio(TYPE_RNG), ENTROPY_DEV_ID)
+            .unwrap()
+            .lock()
+            .unwrap()
+            .mmio_transport_ref()
+            .unwrap()
+            .device()
+            .lock()
+            .unwrap()
+            .queues_mut()[0]
+            .size = 64;
+        let usage = vmm
+            .device_memory_usage(VirtioDeviceType::Rng, ENTROPY_DEV_ID)
+            .unwrap();
+        let bytes = 64 * 16 + (6 + 64 * 2) + (6 + 64 * 8);
+        assert_eq!(
+            usage.queues,
+            vec![QueueMemoryUsage {
+                max_size: FIRECRACKER_MAX_QUEUE_SIZE,
+                size: 64,
+                bytes,
+            }]
+        );
+        assert_eq!(usage.total_bytes, bytes);
+
+        assert!(matches!(
+            vmm.device_memory_usage(VirtioDeviceType::Block, ENTROPY_DEV_ID),
+            Err(VmmError::DeviceManager(MmioError::DeviceNotFound))
+        ));
+    }
+
+    #[test]
+    fn test_max_desc_chain_len() {
+        let mut event_manager = EventManager::new().expect("Unable to create EventManager");
+        let mut vmm = default_vmm();
+        let mut cmdline = default_kernel_cmdline();
+        vmm.mmio_device_manager.max_desc_chain_len = Some(16);
+        insert_entropy_device(
+            &mut vmm,
+            &mut cmdline,
+            &mut event_manager,
+            EntropyDeviceConfig::default(),
+        );
+        insert_watchdog_device(
+            &mut vmm,
+            &mut cmdline,
+            &mut event_manager,
+            WatchdogDeviceConfig {
+                timeout_s: 30,
+                action: Default::default(),
+            },
+        );
+
+        let _: Result<(), std::convert::Infallible> = vmm
+            .mmio_device_manager
+            .for_each_virtio_device(|_, _, _, device| {
+                let mut device = device.lock().unwrap();
+                assert!(!device.queues_mut().is_empty());
+                for queue in device.queues_mut() {
+                    assert_eq!(queue.max_chain_len, 16);
+                }
+                Ok(())
+            });
+    }
+
+    #[test]
+    fn test_device_config_space() {
+        let mut event_manager = EventManager::new().expect("Unable to create EventManager");
+        let mut vmm = default_vmm();
+        let drive_id = String::from("rootfs");
+
+        assert!(matches!(
+            vmm.device_config_space(VirtioDeviceType::Block, &drive_id),
+            Err(VmmError::DeviceManager(MmioError::DeviceNotFound))
+        ));
+
+        let mut cmdline = default_kernel_cmdline();
+        let block_files = insert_block_devices(
+            &mut vmm,
+            &mut cmdline,
+            &mut event_manager,
+            vec![CustomBlockConfig::new(
+                drive_id.clone(),
+                true,
+                None,
+                false,
+                CacheType::Unsafe,
+            )],
+        );
+
+        // The first field of the block configuration space is the capacity, in 512-byte sectors,
+        // as a little-endian 64-bit integer.
+        let config_space = vmm
+            .device_config_space(VirtioDeviceType::Block, &drive_id)
+            .unwrap()
+            .config_space;
+        assert!(
+            config_space.starts_with("0000000000000000"),
+            "{config_space}"
+        );
+
+        block_files[0].as_file().set_len(0x1000).unwrap();
+        vmm.notify_block_device_resize(&drive_id).unwrap();
+        let config_space = vmm
+            .device_config_space(VirtioDeviceType::Block, &drive_id)
+            .unwrap()
+            .config_space;
+        assert!(
+            config_space.starts_with("0800000000000000"),
+            "{config_space}"
+        );
+
+        // Devices without a configuration space report an empty one.
+        insert_entropy_device(
+            &mut vmm,
+            &mut cmdline,
+            &mut event_manager,
+            EntropyDeviceConfig::default(),
+        );
+        assert_eq!(
+            vmm.device_config_space(VirtioDeviceType::Rng, ENTROPY_DEV_ID)
+                .unwrap()
+                .config_space,
+            ""
+        );
+    }
+
+    #[test]
+    fn test_attach_watchdog_device() {
+        let mut event_manager = EventManager::new().expect("Unable to create EventManager");
+        let mut vmm = default_vmm();
+
+        let watchdog_config = WatchdogDeviceConfig {
+            timeout_s: 30,
+            action: Default::default(),
+        };
+
+        let mut cmdline = default_kernel_cmdline();
+        insert_watchdog_device(&mut vmm, &mut cmdline, &mut event_manager, watchdog_config);
+        // Check if the watchdog device is described in kernel_cmdline.
+        #[cfg(any(target_arch = "x86", target_arch = "x86_64"))]
+        assert!(cmdline_contains(
+            &cmdline,
+            "virtio_mmio.device=4K@0xd0000000:5"
+        ));
+    }
+
     #[test]
     fn test_attach_vsock_device() {
         let mut event_manager = EventManager::new().expect("Unable to create EventManager");
@@ -1644,4 +2416,72 @@ pub(crate) mod tests {
             "virtio_mmio.device=4K@0xd0000000:5"
         ));
     }
+
+    #[test]
+    fn test_vsock_stats() {
+        let mut event_manager = EventManager::new().expect("Unable to create EventManager");
+        let mut vmm = default_vmm();
+        assert!(matches!(
+            vmm.vsock_stats(),
+            Err(VmmError::DeviceManager(MmioError::DeviceNotFound))
+        ));
+
+        let mut tmp_sock_file = TempFile::new().unwrap();
+        tmp_sock_file.remove().unwrap();
+        let vsock_config = default_config(&tmp_sock_file);
+        let mut cmdline = default_kernel_cmdline();
+        insert_vsock_device(&mut vmm, &mut cmdline, &mut event_manager, vsock_config);
+        assert_eq!(vmm.vsock_stats().unwrap(), VsockStats::default());
+    }
+
+    #[test]
+    #[cfg(target_arch = "x86_64")]
+    fn test_request_shutdown() {
+        let mut vmm = default_vmm();
+        vmm.request_shutdown().unwrap();
+
+        // The guest reads the CTRL+ALT+DEL scan codes from the i8042 data port.
+        let mut i8042 = vmm.pio_device_manager.i8042.lock().unwrap();
+        let scan_codes: Vec<u8> = (0..5)
+            .map(|_| {
+                let mut data = [0u8; 1];
+                i8042.read(0, &mut data);
+                data[0]
+            })
+            .collect();
+        assert_eq!(scan_codes, [0x14, 0x11, 0xe0, 0x71, 0x00]);
+    }
+
+    #[test]
+    #[cfg(target_arch = "aarch64")]
+    fn test_request_shutdown() {
+        let mut event_manager = EventManager::new().expect("Unable to create EventManager");
+        let mut vmm = default_vmm();
+        let mut cmdline = default_kernel_cmdline();
+
+        // There is no power button to press without the GPIO controller.
+        assert!(matches!(
+            vmm.request_shutdown(),
+            Err(VmmError::DeviceManager(MmioError::DeviceNotFound))
+        ));
+
+        attach_legacy_devices_aarch64(&mut event_manager, &mut vmm, &mut cmdline, None).unwrap();
+        vmm.request_shutdown().unwrap();
+
+        // The power button line reads as pressed.
+        let mut gpio = vmm
+            .get_bus_device(DeviceType::Gpio, "Gpio")
+            .unwrap()
+            .lock()
+            .unwrap();
+        let mut data = [0u8; 4];
+        gpio.read(0x3fc, &mut data);
+        assert_eq!(
+            u32::from_le_bytes(data),
+            1 << crate::devices::legacy::gpio_pl061::POWER_KEY_LINE
+        );
+        // The power button is released once the guest read it.
+        gpio.read(0x3fc, &mut data);
+        assert_eq!(u32::from_le_bytes(data), 0);
+    }
 }
@@ -3,6 +3,8 @@
 
 use serde::{Deserialize, Serialize};
 
+use crate::cpu_config::templates::CustomCpuTemplate;
+
 /// Module with V1N1 CPU template for aarch64
 pub mod v1n1;
 
@@ -16,11 +18,35 @@ pub enum StaticCpuTemplate {
     None,
 }
 
+/// Templates that can be looked up by name.
+const TEMPLATES: [StaticCpuTemplate; 1] = [StaticCpuTemplate::V1N1];
+
 impl StaticCpuTemplate {
     /// Check if no template specified
     pub fn is_none(&self) -> bool {
         self == &StaticCpuTemplate::None
     }
+
+    /// Looks up a template by name, ignoring case. Returns `None` for unknown names.
+    pub fn from_name(name: &str) -> Option<Self> {
+        TEMPLATES
+            .into_iter()
+            .find(|template| template.to_string().eq_ignore_ascii_case(name))
+    }
+
+    /// Names of the templates that can be looked up with [`StaticCpuTemplate::from_name`].
+    pub fn available_templates() -> &'static [&'static str] {
+        &["V1N1"]
+    }
+
+    /// Returns the modifiers of this template as a custom CPU template, which serializes into the
+    /// custom CPU template JSON format. Returns `None` for [`StaticCpuTemplate::None`].
+    pub fn custom_template(&self) -> Option<CustomCpuTemplate> {
+        match self {
+            StaticCpuTemplate::V1N1 => Some(v1n1::v1n1()),
+            StaticCpuTemplate::None => None,
+        }
+    }
 }
 
 impl std::fmt::Display for StaticCpuTemplate {
@@ -46,4 +72,19 @@ mod tests {
             assert_eq!(hardcoded_template, json_template);
         }
     }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(
+            StaticCpuTemplate::from_name("v1n1"),
+            Some(StaticCpuTemplate::V1N1)
+        );
+        assert_eq!(StaticCpuTemplate::from_name("None"), None);
+        assert_eq!(StaticCpuTemplate::from_name("V1N2"), None);
+
+        for name in StaticCpuTemplate::available_templates() {
+            let template = StaticCpuTemplate::from_name(name).unwrap();
+            assert_eq!(template.to_string(), *name);
+        }
+    }
 }
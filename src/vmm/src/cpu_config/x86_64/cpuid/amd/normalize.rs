@@ -461,4 +461,94 @@ mod tests {
             0
         );
     }
+
+    fn entry(leaf: u32, subleaf: u32, eax: u32) -> (CpuidKey, CpuidEntry) {
+        (
+            CpuidKey { leaf, subleaf },
+            CpuidEntry {
+                flags: KvmCpuidFlags::SIGNIFICANT_INDEX,
+                result: CpuidRegisters {
+                    eax,
+                    ebx: 0,
+                    ecx: 0,
+                    edx: 0,
+                },
+            },
+        )
+    }
+
+    #[test]
+    fn test_update_amd_feature_entry() {
+        let mut cpuid = AmdCpuid(BTreeMap::new());
+        assert_eq!(
+            cpuid.update_amd_feature_entry(4).unwrap_err(),
+            FeatureEntryError::MissingLeaf0x80000008
+        );
+
+        let mut cpuid = AmdCpuid(BTreeMap::from([entry(0x80000008, 0, 0)]));
+        cpuid.update_amd_feature_entry(4).unwrap();
+        let ecx = cpuid.get(&CpuidKey::leaf(0x80000008)).unwrap().result.ecx;
+        // NC, the number of logical processors minus 1.
+        assert_eq!(get_range(ecx, 0..8), 3);
+        // ApicIdSize.
+        assert_eq!(get_range(ecx, 12..16), 7);
+
+        assert_eq!(
+            cpuid.update_amd_feature_entry(0).unwrap_err(),
+            FeatureEntryError::NumberOfPhysicalThreadsOverflow
+        );
+    }
+
+    #[test]
+    fn test_update_extended_cache_topology_entry() {
+        // L1, L2 and L3 caches, identified by their level in bits 5..8.
+        let mut cpuid = AmdCpuid(BTreeMap::from([
+            entry(0x8000001d, 0, 1 << 5),
+            entry(0x8000001d, 1, 2 << 5),
+            entry(0x8000001d, 2, 3 << 5),
+        ]));
+        cpuid.update_extended_cache_topology_entry(4, 2).unwrap();
+        let num_sharing_cache = |subleaf| {
+            get_range(
+                cpuid
+                    .get(&CpuidKey::subleaf(0x8000001d, subleaf))
+                    .unwrap()
+                    .result
+                    .eax,
+                14..26,
+            )
+        };
+        assert_eq!(num_sharing_cache(0), 1);
+        assert_eq!(num_sharing_cache(1), 1);
+        assert_eq!(num_sharing_cache(2), 3);
+    }
+
+    #[test]
+    fn test_update_extended_apic_id_entry() {
+        let mut cpuid = AmdCpuid(BTreeMap::new());
+        assert_eq!(
+            cpuid.update_extended_apic_id_entry(0, 2).unwrap_err(),
+            ExtendedApicIdError::MissingLeaf0x8000001e
+        );
+
+        // 4 vCPUs, as (cpus_per_core, cpu_index, expected compute unit id).
+        for (cpus_per_core, cpu_index, compute_unit_id) in [
+            (2, 0, 0),
+            (2, 1, 0),
+            (2, 2, 1),
+            (2, 3, 1),
+            (1, 2, 2),
+            (1, 3, 3),
+        ] {
+            let mut cpuid = AmdCpuid(BTreeMap::from([entry(0x8000001e, 0, 0)]));
+            cpuid
+                .update_extended_apic_id_entry(cpu_index, cpus_per_core)
+                .unwrap();
+            let result = &cpuid.get(&CpuidKey::leaf(0x8000001e)).unwrap().result;
+            assert_eq!(result.eax, u32::from(cpu_index));
+            assert_eq!(get_range(result.ebx, 0..8), compute_unit_id);
+            assert_eq!(get_range(result.ebx, 8..16), u32::from(cpus_per_core - 1));
+            assert_eq!(get_range(result.ecx, 0..11), 0);
+        }
+    }
 }
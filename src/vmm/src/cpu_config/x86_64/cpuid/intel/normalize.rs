@@ -5,8 +5,8 @@ use crate::cpu_config::x86_64::cpuid::normalize::{
     get_range, set_bit, set_range, CheckedAssignError,
 };
 use crate::cpu_config::x86_64::cpuid::{
-    host_brand_string, CpuidKey, CpuidRegisters, CpuidTrait, MissingBrandStringLeaves,
-    BRAND_STRING_LENGTH,
+    host_brand_string, CpuidEntry, CpuidKey, CpuidRegisters, CpuidTrait, KvmCpuidFlags,
+    MissingBrandStringLeaves, BRAND_STRING_LENGTH,
 };
 
 /// Error type for [`super::IntelCpuid::normalize`].
@@ -20,6 +20,8 @@ pub enum NormalizeCpuidError {
     MissingLeaf7,
     /// Leaf 0xA is missing from CPUID.
     MissingLeafA,
+    /// The architectural PMU is not supported by KVM, it can only be disabled.
+    PmuNotSupported,
     /// Failed to get brand string: {0}
     GetBrandString(DefaultBrandStringError),
     /// Failed to set brand string: {0}
@@ -42,6 +44,12 @@ pub enum DeterministicCacheError {
     MaxCorePerPackageUnderflow,
     /// Failed to set `Maximum number of addressable IDs for processor cores in the physical package`: {0}.
     MaxCorePerPackage(CheckedAssignError),
+    /// Leaf 0x4 is missing from CPUID.
+    MissingLeaf4,
+    /// Failed to set `Cache Level`: {0}.
+    CacheLevel(CheckedAssignError),
+    /// Failed to set `Cache Type Field`: {0}.
+    CacheType(CheckedAssignError),
 }
 
 /// We always use this brand string.
@@ -68,16 +76,82 @@ impl super::IntelCpuid {
         cpu_count: u8,
         // The number of logical CPUs per core.
         cpus_per_core: u8,
+        // The number of cache levels to advertise instead of the host ones.
+        cache_levels: Option<u8>,
+        // Whether to advertise the architectural PMU reported by KVM.
+        pmu: bool,
     ) -> Result<(), NormalizeCpuidError> {
         self.update_deterministic_cache_entry(cpu_count, cpus_per_core)?;
+        // Applied after the deterministic cache entry is normalized, so that the synthesized
+        // levels share the normalized topology of the last level cache.
+        if let Some(cache_levels) = cache_levels {
+            self.update_cache_levels(cache_levels)?;
+        }
         self.update_power_management_entry()?;
         self.update_extended_feature_flags_entry()?;
-        self.update_performance_monitoring_entry()?;
+        self.update_performance_monitoring_entry(pmu)?;
         self.update_brand_string_entry()?;
 
         Ok(())
     }
 
+    /// Advertises exactly `cache_levels` cache levels in the deterministic cache entry. The
+    /// subleaves describing deeper levels are dropped, and missing levels are synthesized from
+    /// the last level cache.
+    fn update_cache_levels(&mut self, cache_levels: u8) -> Result<(), DeterministicCacheError> {
+        let cache_levels = u32::from(cache_levels);
+
+        // Collect the valid subleaves, which are terminated by an invalid (all zero) one.
+        let subleaves: Vec<CpuidEntry> = (0..)
+            .map_while(|i| self.get(&CpuidKey::subleaf(0x4, i)))
+            .take_while(|subleaf| subleaf.result != CpuidRegisters::default())
+            .cloned()
+            .collect();
+        let flags = subleaves
+            .first()
+            .map_or(KvmCpuidFlags::SIGNIFICANT_INDEX, |subleaf| subleaf.flags);
+
+        // Cache Level (Starts at 1)
+        //
+        // cache_level: 5..8
+        let mut subleaves: Vec<CpuidEntry> = subleaves
+            .into_iter()
+            .filter(|subleaf| get_range(subleaf.result.eax, 5..8) <= cache_levels)
+            .collect();
+        let last_level_cache = subleaves
+            .iter()
+            .max_by_key(|subleaf| get_range(subleaf.result.eax, 5..8))
+            .cloned()
+            .ok_or(DeterministicCacheError::MissingLeaf4)?;
+
+        // The missing levels are described as unified caches with the same parameters as the
+        // last level cache.
+        for level in (get_range(last_level_cache.result.eax, 5..8) + 1)..=cache_levels {
+            let mut subleaf = last_level_cache.clone();
+            set_range(&mut subleaf.result.eax, 5..8, level)
+                .map_err(DeterministicCacheError::CacheLevel)?;
+            // Cache Type Field
+            //
+            // cache_type: 0..5, 3 = Unified Cache
+            set_range(&mut subleaf.result.eax, 0..5, 3)
+                .map_err(DeterministicCacheError::CacheType)?;
+            subleaves.push(subleaf);
+        }
+
+        // Terminate the enumeration with an invalid subleaf.
+        subleaves.push(CpuidEntry {
+            flags,
+            result: CpuidRegisters::default(),
+        });
+
+        self.0.retain(|key, _| key.leaf != 0x4);
+        for (index, subleaf) in (0..).zip(subleaves) {
+            self.0.insert(CpuidKey::subleaf(0x4, index), subleaf);
+        }
+
+        Ok(())
+    }
+
     /// Update deterministic cache entry
     #[allow(clippy::unwrap_in_result)]
     fn update_deterministic_cache_entry(
@@ -200,11 +274,24 @@ impl super::IntelCpuid {
         Ok(())
     }
 
-    /// Update performance monitoring entry
-    fn update_performance_monitoring_entry(&mut self) -> Result<(), NormalizeCpuidError> {
+    /// Update performance monitoring entry. The architectural PMU reported by KVM is kept if
+    /// `pmu` is set, and disabled otherwise.
+    fn update_performance_monitoring_entry(
+        &mut self,
+        pmu: bool,
+    ) -> Result<(), NormalizeCpuidError> {
         let leaf_a = self
             .get_mut(&CpuidKey::leaf(0xA))
             .ok_or(NormalizeCpuidError::MissingLeafA)?;
+        if pmu {
+            // Version ID of architectural performance monitoring
+            //
+            // version_id: 0..8, 0 = not supported
+            if get_range(leaf_a.result.eax, 0..8) == 0 {
+                return Err(NormalizeCpuidError::PmuNotSupported);
+            }
+            return Ok(());
+        }
         leaf_a.result = CpuidRegisters {
             eax: 0,
             ebx: 0,
@@ -373,6 +460,172 @@ mod tests {
         );
     }
 
+    fn cache_subleaf(cache_type: u32, level: u32, ebx: u32) -> CpuidEntry {
+        CpuidEntry {
+            flags: KvmCpuidFlags::SIGNIFICANT_INDEX,
+            result: CpuidRegisters {
+                eax: cache_type | (level << 5),
+                ebx,
+                ecx: 0x3f,
+                edx: 0,
+            },
+        }
+    }
+
+    fn cache_cpuid() -> super::super::IntelCpuid {
+        let subleaves = [
+            cache_subleaf(1, 1, 0x1),
+            cache_subleaf(2, 1, 0x2),
+            cache_subleaf(3, 2, 0x3),
+            cache_subleaf(3, 3, 0x4),
+            CpuidEntry {
+                flags: KvmCpuidFlags::SIGNIFICANT_INDEX,
+                ..Default::default()
+            },
+        ];
+        super::super::IntelCpuid(
+            (0..)
+                .zip(subleaves)
+                .map(|(i, subleaf)| (CpuidKey::subleaf(0x4, i), subleaf))
+                .collect(),
+        )
+    }
+
+    // Returns the subleaves of leaf 0x4, up to and including the invalid one.
+    fn cache_subleaves(cpuid: &super::super::IntelCpuid) -> Vec<CpuidEntry> {
+        cpuid
+            .0
+            .iter()
+            .filter(|(key, _)| key.leaf == 0x4)
+            .map(|(_, subleaf)| subleaf.clone())
+            .collect()
+    }
+
+    fn advertised_cache_levels(cpuid: &super::super::IntelCpuid) -> u32 {
+        cache_subleaves(cpuid)
+            .iter()
+            .take_while(|subleaf| subleaf.result != CpuidRegisters::default())
+            .map(|subleaf| get_range(subleaf.result.eax, 5..8))
+            .max()
+            .unwrap_or(0)
+    }
+
+    #[test]
+    fn test_update_cache_levels() {
+        // Keeping the host levels does not change the entry.
+        let mut cpuid = cache_cpuid();
+        cpuid.update_cache_levels(3).unwrap();
+        assert_eq!(cpuid, cache_cpuid());
+        assert_eq!(advertised_cache_levels(&cpuid), 3);
+
+        // Deeper levels are trimmed.
+        for levels in [1, 2] {
+            let mut cpuid = cache_cpuid();
+            cpuid.update_cache_levels(levels).unwrap();
+            assert_eq!(advertised_cache_levels(&cpuid), u32::from(levels));
+            let subleaves = cache_subleaves(&cpuid);
+            assert_eq!(subleaves.last().unwrap().result, CpuidRegisters::default());
+            assert_eq!(
+                subleaves[..subleaves.len() - 1],
+                cache_subleaves(&cache_cpuid())[..subleaves.len() - 1]
+            );
+        }
+
+        // Missing levels are synthesized as unified caches like the last level cache.
+        let mut cpuid = cache_cpuid();
+        cpuid.update_cache_levels(5).unwrap();
+        assert_eq!(advertised_cache_levels(&cpuid), 5);
+        let subleaves = cache_subleaves(&cpuid);
+        assert_eq!(
+            subleaves,
+            [
+                cache_subleaf(1, 1, 0x1),
+                cache_subleaf(2, 1, 0x2),
+                cache_subleaf(3, 2, 0x3),
+                cache_subleaf(3, 3, 0x4),
+                cache_subleaf(3, 4, 0x4),
+                cache_subleaf(3, 5, 0x4),
+                CpuidEntry {
+                    flags: KvmCpuidFlags::SIGNIFICANT_INDEX,
+                    ..Default::default()
+                },
+            ]
+        );
+
+        // The cache levels cannot be set without a host cache description.
+        let mut cpuid = super::super::IntelCpuid(std::collections::BTreeMap::new());
+        assert_eq!(
+            cpuid.update_cache_levels(2),
+            Err(DeterministicCacheError::MissingLeaf4)
+        );
+    }
+
+    #[test]
+    fn test_normalize_cache_levels() {
+        let mut cpuid = cache_cpuid();
+        cpuid.update_deterministic_cache_entry(4, 2).unwrap();
+        let l3 = cache_subleaves(&cpuid)[3].clone();
+
+        let mut cpuid = cache_cpuid();
+        cpuid.update_deterministic_cache_entry(4, 2).unwrap();
+        cpuid.update_cache_levels(4).unwrap();
+        // The synthesized level shares the normalized topology of the last level cache.
+        let l4 = cache_subleaves(&cpuid)[4].clone();
+        assert_eq!(get_range(l4.result.eax, 5..8), 4);
+        assert_eq!(
+            get_range(l4.result.eax, 14..32),
+            get_range(l3.result.eax, 14..32)
+        );
+    }
+
+    #[test]
+    fn test_update_performance_monitoring_entry() {
+        // Version 2 with 8 general purpose counters and 3 fixed function counters.
+        let leaf_a = CpuidEntry {
+            flags: KvmCpuidFlags::EMPTY,
+            result: CpuidRegisters {
+                eax: 0x0730_0802,
+                ebx: 0x0,
+                ecx: 0x0,
+                edx: 0x0603,
+            },
+        };
+        let pmu_cpuid = |leaf_a: &CpuidEntry| {
+            super::super::IntelCpuid(std::collections::BTreeMap::from([(
+                CpuidKey::leaf(0xA),
+                leaf_a.clone(),
+            )]))
+        };
+
+        // The PMU reported by KVM is kept if enabled.
+        let mut cpuid = pmu_cpuid(&leaf_a);
+        cpuid.update_performance_monitoring_entry(true).unwrap();
+        assert_eq!(cpuid, pmu_cpuid(&leaf_a));
+
+        // The leaf is zeroed otherwise.
+        let mut cpuid = pmu_cpuid(&leaf_a);
+        cpuid.update_performance_monitoring_entry(false).unwrap();
+        assert_eq!(
+            cpuid.get(&CpuidKey::leaf(0xA)).unwrap().result,
+            CpuidRegisters::default()
+        );
+
+        // The PMU can only be disabled if KVM does not support it.
+        let no_pmu = CpuidEntry::default();
+        let mut cpuid = pmu_cpuid(&no_pmu);
+        assert_eq!(
+            cpuid.update_performance_monitoring_entry(true),
+            Err(NormalizeCpuidError::PmuNotSupported)
+        );
+        cpuid.update_performance_monitoring_entry(false).unwrap();
+
+        let mut cpuid = super::super::IntelCpuid(std::collections::BTreeMap::new());
+        assert_eq!(
+            cpuid.update_performance_monitoring_entry(true),
+            Err(NormalizeCpuidError::MissingLeafA)
+        );
+    }
+
     #[test]
     fn test_update_extended_feature_flags_entry() {
         let mut cpuid =
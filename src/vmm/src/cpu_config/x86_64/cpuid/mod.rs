@@ -109,6 +109,45 @@ pub fn host_brand_string() -> [u8; BRAND_STRING_LENGTH] {
     unsafe { std::mem::transmute(arr) }
 }
 
+/// Compares the registers of two [`Cpuid`]s leaf by leaf.
+///
+/// Returns the leaves whose registers differ, sorted by [`CpuidKey`], along with the registers of
+/// `a` and `b` respectively. A leaf missing from one of them is compared as if all its registers
+/// were zero.
+#[must_use]
+pub fn diff(a: &Cpuid, b: &Cpuid) -> Vec<(CpuidKey, CpuidRegisters, CpuidRegisters)> {
+    let mut a_entries = a.inner().iter().peekable();
+    let mut b_entries = b.inner().iter().peekable();
+    let mut diff = Vec::new();
+
+    loop {
+        let ordering = match (a_entries.peek(), b_entries.peek()) {
+            (None, None) => break,
+            (Some(_), None) => std::cmp::Ordering::Less,
+            (None, Some(_)) => std::cmp::Ordering::Greater,
+            (Some((a_key, _)), Some((b_key, _))) => a_key.cmp(b_key),
+        };
+        // Advance the side(s) holding the smallest key.
+        let a_entry = a_entries.next_if(|_| ordering.is_le());
+        let b_entry = b_entries.next_if(|_| ordering.is_ge());
+        let Some((key, _)) = a_entry.or(b_entry) else {
+            break;
+        };
+
+        let a_registers = a_entry
+            .map(|(_, entry)| entry.result.clone())
+            .unwrap_or_default();
+        let b_registers = b_entry
+            .map(|(_, entry)| entry.result.clone())
+            .unwrap_or_default();
+        if a_registers != b_registers {
+            diff.push((key.clone(), a_registers, b_registers));
+        }
+    }
+
+    diff
+}
+
 /// Trait defining shared behaviour between CPUID structures.
 pub trait CpuidTrait {
     /// Returns the CPUID manufacturers ID (e.g. `GenuineIntel` or `AuthenticAMD`) or `None` if it
@@ -768,6 +807,67 @@ mod tests {
         assert_eq!(kvm_cpuid, build_sample_amd_kvmcpuid());
     }
 
+    #[test]
+    fn test_diff() {
+        let entry = |eax, ebx, ecx, edx| CpuidEntry {
+            flags: KvmCpuidFlags::EMPTY,
+            result: CpuidRegisters { eax, ebx, ecx, edx },
+        };
+        let a = Cpuid::Intel(IntelCpuid(BTreeMap::from([
+            (CpuidKey::leaf(0x0), entry(0xd, 0x1, 0x2, 0x3)),
+            (CpuidKey::leaf(0x1), entry(0x1, 0x1, 0x1, 0x1)),
+            (CpuidKey::subleaf(0x7, 0x0), entry(0x0, 0xff, 0x0, 0x0)),
+            (CpuidKey::subleaf(0x7, 0x1), entry(0x0, 0x0, 0x0, 0x0)),
+            (CpuidKey::leaf(0x80000001), entry(0x0, 0x0, 0x1, 0x0)),
+        ])));
+        let b = Cpuid::Intel(IntelCpuid(BTreeMap::from([
+            (CpuidKey::leaf(0x0), entry(0xd, 0x1, 0x2, 0x3)),
+            (CpuidKey::leaf(0x1), entry(0x1, 0x1, 0x0, 0x1)),
+            (CpuidKey::subleaf(0x7, 0x0), entry(0x0, 0x0f, 0x0, 0x0)),
+            (CpuidKey::leaf(0xd), entry(0x7, 0x0, 0x0, 0x0)),
+            (CpuidKey::leaf(0x80000008), entry(0x0, 0x0, 0x0, 0x0)),
+        ])));
+
+        // Identical leaves, and leaves missing on one side with all-zero registers on the other,
+        // are not reported.
+        let expected = vec![
+            (
+                CpuidKey::leaf(0x1),
+                entry(0x1, 0x1, 0x1, 0x1).result,
+                entry(0x1, 0x1, 0x0, 0x1).result,
+            ),
+            (
+                CpuidKey::subleaf(0x7, 0x0),
+                entry(0x0, 0xff, 0x0, 0x0).result,
+                entry(0x0, 0x0f, 0x0, 0x0).result,
+            ),
+            (
+                CpuidKey::leaf(0xd),
+                CpuidRegisters::default(),
+                entry(0x7, 0x0, 0x0, 0x0).result,
+            ),
+            (
+                CpuidKey::leaf(0x80000001),
+                entry(0x0, 0x0, 0x1, 0x0).result,
+                CpuidRegisters::default(),
+            ),
+        ];
+        assert_eq!(diff(&a, &b), expected);
+
+        // Swapping the sides swaps the registers.
+        let swapped: Vec<_> = expected
+            .into_iter()
+            .map(|(key, a_registers, b_registers)| (key, b_registers, a_registers))
+            .collect();
+        assert_eq!(diff(&b, &a), swapped);
+
+        assert!(diff(&a, &a).is_empty());
+        assert!(diff(&build_sample_intel_cpuid(), &build_sample_intel_cpuid()).is_empty());
+        let empty = Cpuid::Amd(AmdCpuid(BTreeMap::new()));
+        assert!(diff(&empty, &empty).is_empty());
+        assert_eq!(diff(&a, &empty).len(), 4);
+    }
+
     #[test]
     fn test_invalid_kvmcpuid_to_cpuid() {
         // If leaf 0 contains invalid vendor ID, the type conversion should fail.
@@ -1,8 +1,15 @@
 // Copyright 2023 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+use std::collections::BTreeMap;
+
 use crate::cpu_config::x86_64::cpuid::{
     cpuid, CpuidEntry, CpuidKey, CpuidRegisters, CpuidTrait, KvmCpuidFlags,
+    MissingBrandStringLeaves, BRAND_STRING_LENGTH,
+};
+use crate::vmm_config::machine_config::{
+    AvxFeature, CpuFrequencies, CpuSignature, CpuTopology, CpuidMaxLeaves, CpuidNormalizeOptions,
+    EXTENDED_CPUID_LEAVES, STANDARD_CPUID_LEAVES,
 };
 
 /// Error type for [`super::Cpuid::normalize`].
@@ -23,6 +30,28 @@ pub enum NormalizeCpuidError {
     ExtendedCacheFeatures(#[from] ExtendedCacheFeaturesError),
     /// Failed to set vendor ID in leaf 0x0: {0}
     VendorId(#[from] VendorIdError),
+    /// Configuring the number of cache levels is only supported on Intel CPUs.
+    CacheLevelsNotSupported,
+    /// Advertising the architectural PMU is only supported on Intel CPUs.
+    PmuNotSupported,
+    /// Leaf 0x80000007 is missing from CPUID.
+    MissingLeaf0x80000007,
+    /// An invariant TSC is not supported by the host, it can only be masked.
+    InvariantTscNotSupported,
+    /// Failed to set the cache line size: {0}
+    CacheLineSize(CheckedAssignError),
+    /// The {0:?} feature is not supported by the host, it can only be masked.
+    AvxFeatureNotSupported(AvxFeature),
+    /// The {0} instruction is not supported by the host, it can only be masked.
+    RngInstructionNotSupported(&'static str),
+    /// The MONITOR and MWAIT instructions are not supported by KVM, they can only be masked.
+    MonitorMwaitNotSupported,
+    /// The TSC deadline timer cannot be advertised without the local APIC.
+    TscDeadlineWithoutApic,
+    /// The brand string is {0} bytes long, it does not fit in CPUID leaves 0x80000002-0x80000004.
+    BrandStringTooLong(usize),
+    /// Failed to set the brand string: {0}
+    BrandString(MissingBrandStringLeaves),
 }
 
 /// Error type for setting leaf 0 section.
@@ -45,6 +74,16 @@ pub enum FeatureInformationError {
     GetMaxCpusPerPackage(GetMaxCpusPerPackageError),
     /// Failed to set max CPUs per package: {0}
     SetMaxCpusPerPackage(CheckedAssignError),
+    /// Failed to set `Stepping ID`: {0}
+    SteppingId(CheckedAssignError),
+    /// Failed to set `Model`: {0}
+    Model(CheckedAssignError),
+    /// Failed to set `Family ID`: {0}
+    FamilyId(CheckedAssignError),
+    /// Failed to set `Extended Model ID`: {0}
+    ExtendedModelId(CheckedAssignError),
+    /// Failed to set `Extended Family ID`: {0}
+    ExtendedFamilyId(CheckedAssignError),
 }
 
 /// Error type for `get_max_cpus_per_package`.
@@ -177,40 +216,100 @@ impl super::Cpuid {
         cpu_count: u8,
         // The number of bits needed to enumerate logical CPUs per core.
         cpu_bits: u8,
+        // The CPUID settings of the machine configuration.
+        options: &CpuidNormalizeOptions,
     ) -> Result<(), NormalizeCpuidError> {
         let cpus_per_core = 1u8
             .checked_shl(u32::from(cpu_bits))
             .ok_or(NormalizeCpuidError::CpuBits(cpu_bits))?;
-        self.update_vendor_id()?;
-        self.update_feature_info_entry(cpu_index, cpu_count)?;
-        self.update_extended_topology_entry(cpu_index, cpu_count, cpu_bits, cpus_per_core)?;
+        let cpu_topology = options.cpu_topology.as_ref();
+        // Package level information describes the logical CPUs of a single socket.
+        let cpu_count = cpu_topology.map_or(cpu_count, CpuTopology::cpus_per_socket);
+        // The vendor ID is validated to be exactly 12 bytes long when configured.
+        let vendor_id = options
+            .vendor_id
+            .as_deref()
+            .and_then(|vendor_id| <&[u8; 12]>::try_from(vendor_id.as_bytes()).ok());
+        self.update_vendor_id(vendor_id)?;
+        self.update_feature_info_entry(cpu_index, cpu_count, options.cpu_signature.as_ref())?;
+        self.update_hypervisor_bit(options.hypervisor_bit)?;
+        self.update_extended_topology_entry(
+            cpu_index,
+            cpu_count,
+            cpu_bits,
+            cpus_per_core,
+            cpu_topology,
+        )?;
+        self.update_extended_topology_v2_entry();
         self.update_extended_cache_features()?;
+        self.update_invariant_tsc(options.invariant_tsc, host_invariant_tsc())?;
 
         // Apply manufacturer specific modifications.
         match self {
             // Apply Intel specific modifications.
             Self::Intel(intel_cpuid) => {
-                intel_cpuid.normalize(cpu_index, cpu_count, cpus_per_core)?;
+                intel_cpuid.normalize(
+                    cpu_index,
+                    cpu_count,
+                    cpus_per_core,
+                    options.cache_levels,
+                    options.pmu.unwrap_or(false),
+                )?;
+            }
+            // AMD describes its cache hierarchy in leaf 0x8000001D instead of leaf 0x4.
+            Self::Amd(_) if options.cache_levels.is_some() => {
+                return Err(NormalizeCpuidError::CacheLevelsNotSupported);
+            }
+            // AMD does not describe its PMU in leaf 0xA.
+            Self::Amd(_) if options.pmu == Some(true) => {
+                return Err(NormalizeCpuidError::PmuNotSupported);
             }
             // Apply AMD specific modifications.
             Self::Amd(amd_cpuid) => amd_cpuid.normalize(cpu_index, cpu_count, cpus_per_core)?,
         }
 
+        // Override the line size after the vendor specific normalization rewrote the cache leaves,
+        // and the brand string after it wrote the default one.
+        self.update_cache_line_size(options.cache_line_size)?;
+        self.update_brand_string(options.brand_string.as_deref())?;
+        self.update_avx_features(options.avx_features.as_ref())?;
+        self.update_rng_instructions(options.rdrand, options.rdseed)?;
+        self.update_monitor_mwait(options.monitor_mwait)?;
+        self.update_tsc_deadline(options.tsc_deadline)?;
+        self.update_cpu_frequencies(options.cpu_frequencies.as_ref());
+
+        // Trim the leaves last, so that the normalization above finds all the leaves it updates.
+        self.update_max_leaves(options.max_cpuid_leaves.as_ref());
+
         Ok(())
     }
 
-    /// Pass-through the vendor ID from the host. This is used to prevent modification of the vendor
-    /// ID via custom CPU templates.
-    fn update_vendor_id(&mut self) -> Result<(), VendorIdError> {
+    /// Sets the vendor ID configured by the user, or passes through the vendor ID from the host if
+    /// none is configured. This is used to prevent modification of the vendor ID via custom CPU
+    /// templates.
+    ///
+    /// The vendor-specific normalization keeps following the host vendor, as it describes the
+    /// hardware the guest actually runs on.
+    fn update_vendor_id(&mut self, vendor_id: Option<&[u8; 12]>) -> Result<(), VendorIdError> {
         let leaf_0 = self
             .get_mut(&CpuidKey::leaf(0x0))
             .ok_or(VendorIdError::MissingLeaf0)?;
 
-        let host_leaf_0 = cpuid(0x0);
+        match vendor_id {
+            Some(id) => {
+                // The ordering of the vendor string is ebx,edx,ecx this is not a mistake.
+                leaf_0.result.ebx = u32::from_ne_bytes([id[0], id[1], id[2], id[3]]);
+                leaf_0.result.edx = u32::from_ne_bytes([id[4], id[5], id[6], id[7]]);
+                leaf_0.result.ecx = u32::from_ne_bytes([id[8], id[9], id[10], id[11]]);
+            }
+            None => {
+                let host_leaf_0 = cpuid(0x0);
 
-        leaf_0.result.ebx = host_leaf_0.ebx;
-        leaf_0.result.ecx = host_leaf_0.ecx;
-        leaf_0.result.edx = host_leaf_0.edx;
+                leaf_0.result.ebx = host_leaf_0.ebx;
+                leaf_0.result.ecx = host_leaf_0.ecx;
+                leaf_0.result.edx = host_leaf_0.edx;
+            }
+        }
 
         Ok(())
     }
@@ -220,6 +319,7 @@ impl super::Cpuid {
         &mut self,
         cpu_index: u8,
         cpu_count: u8,
+        cpu_signature: Option<&CpuSignature>,
     ) -> Result<(), FeatureInformationError> {
         // Flush a cache line size.
         const EBX_CLFLUSH_CACHELINE: u32 = 8;
@@ -296,6 +396,219 @@ impl super::Cpuid {
         // is valid for the package
         set_bit(&mut leaf_1.result.edx, 28, cpu_count > 1);
 
+        if let Some(signature) = cpu_signature {
+            Self::update_cpu_signature(&mut leaf_1.result.eax, signature)?;
+        }
+
+        Ok(())
+    }
+
+    /// Clears the hypervisor present bit of leaf 0x1 if the user configured it as not advertised.
+    /// Guests relying on it to discover the paravirtualized features of KVM, like the KVM clock,
+    /// then run as on bare metal.
+    fn update_hypervisor_bit(
+        &mut self,
+        hypervisor_bit: Option<bool>,
+    ) -> Result<(), FeatureInformationError> {
+        /// CPU is running on a hypervisor.
+        const ECX_HYPERVISOR_BITINDEX: u8 = 31;
+
+        if let Some(hypervisor_bit) = hypervisor_bit {
+            let leaf_1 = self
+                .get_mut(&CpuidKey::leaf(0x1))
+                .ok_or(FeatureInformationError::MissingLeaf1)?;
+            set_bit(
+                &mut leaf_1.result.ecx,
+                ECX_HYPERVISOR_BITINDEX,
+                hypervisor_bit,
+            );
+        }
+        Ok(())
+    }
+
+    /// Sets the brand string configured by the user in leaves 0x80000002-0x80000004, padded with
+    /// NUL bytes.
+    fn update_brand_string(
+        &mut self,
+        brand_string: Option<&str>,
+    ) -> Result<(), NormalizeCpuidError> {
+        let Some(brand_string) = brand_string else {
+            return Ok(());
+        };
+        // The last byte is kept for the NUL terminator.
+        if brand_string.len() >= BRAND_STRING_LENGTH {
+            return Err(NormalizeCpuidError::BrandStringTooLong(brand_string.len()));
+        }
+        let mut bytes = [0u8; BRAND_STRING_LENGTH];
+        bytes[..brand_string.len()].copy_from_slice(brand_string.as_bytes());
+        self.apply_brand_string(&bytes)
+            .map_err(NormalizeCpuidError::BrandString)
+    }
+
+    /// Masks the MONITOR and MWAIT instructions (leaf 0x1 ECX) and their parameters (leaf 0x5) if
+    /// the user configured them as not advertised, so that the guest does not use MWAIT based
+    /// idle. Configured as advertised, they must be supported by KVM.
+    fn update_monitor_mwait(
+        &mut self,
+        monitor_mwait: Option<bool>,
+    ) -> Result<(), NormalizeCpuidError> {
+        /// MONITOR and MWAIT instructions supported.
+        const ECX_MONITOR_BITINDEX: u8 = 3;
+
+        let Some(enabled) = monitor_mwait else {
+            return Ok(());
+        };
+        // Without the leaf there is nothing to mask, and the instructions are not supported.
+        let supported = self.get_mut(&CpuidKey::leaf(0x1)).is_some_and(|leaf_1| {
+            let supported = leaf_1.result.ecx & (1 << ECX_MONITOR_BITINDEX) != 0;
+            if !enabled {
+                set_bit(&mut leaf_1.result.ecx, ECX_MONITOR_BITINDEX, false);
+            }
+            supported
+        });
+        if enabled && !supported {
+            return Err(NormalizeCpuidError::MonitorMwaitNotSupported);
+        }
+        if !enabled {
+            // The MONITOR/MWAIT leaf describes the monitor line sizes and the MWAIT C-states.
+            if let Some(leaf_5) = self.get_mut(&CpuidKey::leaf(0x5)) {
+                leaf_5.result = CpuidRegisters::default();
+            }
+        }
+        Ok(())
+    }
+
+    /// Masks the TSC deadline mode of the local APIC timer (leaf 0x1 ECX) if the user configured
+    /// it as not advertised. Since the mode is a feature of the local APIC timer, it can only be
+    /// advertised along with the local APIC (leaf 0x1 EDX).
+    fn update_tsc_deadline(
+        &mut self,
+        tsc_deadline: Option<bool>,
+    ) -> Result<(), NormalizeCpuidError> {
+        /// TSC deadline mode of the local APIC timer supported.
+        const ECX_TSC_DEADLINE_BITINDEX: u8 = 24;
+        /// Local APIC present.
+        const EDX_APIC_BITINDEX: u8 = 9;
+
+        let Some(enabled) = tsc_deadline else {
+            return Ok(());
+        };
+        // The feature information normalization fails without the leaf.
+        let Some(leaf_1) = self.get_mut(&CpuidKey::leaf(0x1)) else {
+            return Ok(());
+        };
+        if enabled && leaf_1.result.edx & (1 << EDX_APIC_BITINDEX) == 0 {
+            return Err(NormalizeCpuidError::TscDeadlineWithoutApic);
+        }
+        set_bit(&mut leaf_1.result.ecx, ECX_TSC_DEADLINE_BITINDEX, enabled);
+        Ok(())
+    }
+
+    /// Sets the base (EAX), maximum (EBX) and bus (ECX) frequencies of the processor frequency
+    /// information leaf 0x16 to the ones configured by the user. The leaf is added, and the
+    /// maximum standard leaf raised to it, if KVM does not report it. This is always the case on
+    /// AMD, which does not define the leaf: the guest then also sees the leaves between the host
+    /// maximum and 0x16, which KVM reports as zeroes.
+    ///
+    /// The configuration is rejected if the user configured a maximum standard leaf below 0x16,
+    /// which would trim the leaf.
+    fn update_cpu_frequencies(&mut self, cpu_frequencies: Option<&CpuFrequencies>) {
+        /// Processor frequency information leaf.
+        const FREQUENCY_LEAF: u32 = 0x16;
+
+        let Some(frequencies) = cpu_frequencies else {
+            return;
+        };
+        if let Some(leaf_0) = self.get_mut(&CpuidKey::leaf(0x0)) {
+            leaf_0.result.eax = leaf_0.result.eax.max(FREQUENCY_LEAF);
+        }
+        self.inner_mut().insert(
+            CpuidKey::leaf(FREQUENCY_LEAF),
+            CpuidEntry {
+                flags: KvmCpuidFlags::EMPTY,
+                result: CpuidRegisters {
+                    eax: u32::from(frequencies.base_mhz),
+                    ebx: u32::from(frequencies.max_mhz),
+                    ecx: u32::from(frequencies.bus_mhz),
+                    edx: 0,
+                },
+            },
+        );
+    }
+
+    /// Masks the RDRAND (leaf 0x1 ECX) and RDSEED (leaf 0x7 EBX) instructions the user configured
+    /// as not advertised, so that the guest has to fall back to other entropy sources, like a
+    /// virtio-rng device. Instructions configured as advertised must be supported by the host.
+    fn update_rng_instructions(
+        &mut self,
+        rdrand: Option<bool>,
+        rdseed: Option<bool>,
+    ) -> Result<(), NormalizeCpuidError> {
+        /// RDRAND instruction supported.
+        const ECX_RDRAND_BITINDEX: u8 = 30;
+        /// RDSEED instruction supported.
+        const EBX_RDSEED_BITINDEX: u8 = 18;
+
+        let instructions = [
+            ("RDRAND", rdrand, CpuidKey::leaf(0x1), ECX_RDRAND_BITINDEX),
+            (
+                "RDSEED",
+                rdseed,
+                CpuidKey::subleaf(0x7, 0x0),
+                EBX_RDSEED_BITINDEX,
+            ),
+        ];
+        for (name, enabled, key, bit_index) in instructions {
+            let Some(enabled) = enabled else {
+                continue;
+            };
+            // Without the leaf there is nothing to mask, and the instruction is not supported.
+            let supported = self.get_mut(&key).is_some_and(|entry| {
+                let register = if key.leaf == 0x1 {
+                    &mut entry.result.ecx
+                } else {
+                    &mut entry.result.ebx
+                };
+                let supported = *register & (1 << bit_index) != 0;
+                if !enabled {
+                    set_bit(register, bit_index, false);
+                }
+                supported
+            });
+            if enabled && !supported {
+                return Err(NormalizeCpuidError::RngInstructionNotSupported(name));
+            }
+        }
+        Ok(())
+    }
+
+    /// Sets the family, model and stepping configured by the user in leaf 1 EAX.
+    fn update_cpu_signature(
+        eax: &mut u32,
+        signature: &CpuSignature,
+    ) -> Result<(), FeatureInformationError> {
+        // The displayed family is the sum of the base and extended fields when the base family is
+        // 0xf, while the extended model holds the upper bits of the displayed model.
+        let family = u32::from(signature.family);
+        let (family_id, extended_family_id) = match family.checked_sub(0xf) {
+            Some(extended_family_id) => (0xf, extended_family_id),
+            None => (family, 0),
+        };
+        let model = u32::from(signature.model);
+
+        // stepping_id: 0..4,
+        set_range(eax, 0..4, u32::from(signature.stepping))
+            .map_err(FeatureInformationError::SteppingId)?;
+        // model: 4..8,
+        set_range(eax, 4..8, model & 0xf).map_err(FeatureInformationError::Model)?;
+        // family_id: 8..12,
+        set_range(eax, 8..12, family_id).map_err(FeatureInformationError::FamilyId)?;
+        // extended_model_id: 16..20,
+        set_range(eax, 16..20, model >> 4).map_err(FeatureInformationError::ExtendedModelId)?;
+        // extended_family_id: 20..28,
+        set_range(eax, 20..28, extended_family_id)
+            .map_err(FeatureInformationError::ExtendedFamilyId)?;
+
         Ok(())
     }
 
@@ -306,6 +619,7 @@ impl super::Cpuid {
         cpu_count: u8,
         cpu_bits: u8,
         cpus_per_core: u8,
+        cpu_topology: Option<&CpuTopology>,
     ) -> Result<(), ExtendedTopologyError> {
         /// Level type used for setting thread level processor topology.
         const LEVEL_TYPE_THREAD: u32 = 1;
@@ -316,6 +630,12 @@ impl super::Cpuid {
         /// processors/package.
         const LEAFBH_INDEX1_APICID: u32 = 7;
 
+        // With a configured topology, the APIC ID bits above the core level identify the socket.
+        // The number of logical CPUs per socket is validated to be a power of 2.
+        let core_level_apic_id_shift = cpu_topology.map_or(LEAFBH_INDEX1_APICID, |topology| {
+            topology.cpus_per_socket().trailing_zeros()
+        });
+
         // The following commit changed the behavior of KVM_GET_SUPPORTED_CPUID to no longer
         // include leaf 0xB / sub-leaf 1.
         // https://lore.kernel.org/all/20221027092036.2698180-1-pbonzini@redhat.com/
@@ -405,7 +725,7 @@ impl super::Cpuid {
                     }
                     // Core Level Processor Topology; index = 1
                     1 => {
-                        set_range(&mut subleaf.result.eax, 0..5, LEAFBH_INDEX1_APICID)
+                        set_range(&mut subleaf.result.eax, 0..5, core_level_apic_id_shift)
                             .map_err(ExtendedTopologyError::ApicId)?;
 
                         set_range(&mut subleaf.result.ebx, 0..16, u32::from(cpu_count))
@@ -440,6 +760,27 @@ impl super::Cpuid {
         Ok(())
     }
 
+    /// Update extended topology v2 entry
+    ///
+    /// Leaf 0x1F is a superset of leaf 0xB, which Intel recommends using when available. As the
+    /// guest topology only has the levels of leaf 0xB, leaf 0x1F is replaced by a copy of it,
+    /// dropping any other level of the host, like the module or die ones.
+    fn update_extended_topology_v2_entry(&mut self) {
+        // Skip if leaf 0x1F does not exist.
+        if self.get(&CpuidKey::leaf(0x1F)).is_none() {
+            return;
+        }
+
+        let cpuid = self.inner_mut();
+        cpuid.retain(|key, _| key.leaf != 0x1F);
+        let topology_subleaves: Vec<_> = cpuid
+            .iter()
+            .filter(|(key, _)| key.leaf == 0xB)
+            .map(|(key, entry)| (CpuidKey::subleaf(0x1F, key.subleaf), entry.clone()))
+            .collect();
+        cpuid.extend(topology_subleaves);
+    }
+
     // Update extended cache features entry
     fn update_extended_cache_features(&mut self) -> Result<(), ExtendedCacheFeaturesError> {
         // Leaf 0x800000005 indicates L1 Cache and TLB Information.
@@ -456,6 +797,164 @@ impl super::Cpuid {
         guest_leaf_0x80000006.result.edx &= !0x00030000; // bits [17:16] are reserved
         Ok(())
     }
+
+    /// Advertises an invariant TSC in leaf 0x80000007 as configured by the user, or leaves the
+    /// host value in place if not configured. An invariant TSC can only be advertised if the host
+    /// has one, as the guest would otherwise rely on a TSC that drifts.
+    fn update_invariant_tsc(
+        &mut self,
+        invariant_tsc: Option<bool>,
+        host_invariant_tsc: bool,
+    ) -> Result<(), NormalizeCpuidError> {
+        if let Some(invariant_tsc) = invariant_tsc {
+            if invariant_tsc && !host_invariant_tsc {
+                return Err(NormalizeCpuidError::InvariantTscNotSupported);
+            }
+            let leaf_0x80000007 = self
+                .get_mut(&CpuidKey::leaf(0x80000007))
+                .ok_or(NormalizeCpuidError::MissingLeaf0x80000007)?;
+            set_bit(
+                &mut leaf_0x80000007.result.edx,
+                EDX_INVARIANT_TSC_BITINDEX,
+                invariant_tsc,
+            );
+        }
+        Ok(())
+    }
+
+    /// Advertises the cache line size configured by the user in the CLFLUSH line size of leaf
+    /// 0x1, the deterministic cache parameters of leaves 0x4 and 0x8000001D, and the L1, L2 and
+    /// L3 line sizes of leaves 0x80000005 and 0x80000006. Line sizes the host reports as zero are
+    /// not implemented by the host CPU vendor, and are left untouched.
+    fn update_cache_line_size(
+        &mut self,
+        cache_line_size: Option<u16>,
+    ) -> Result<(), NormalizeCpuidError> {
+        let Some(size) = cache_line_size.map(u32::from) else {
+            return Ok(());
+        };
+
+        // CLFLUSH line size (Value ∗ 8 = cache line size in bytes; used also by CLFLUSHOPT).
+        //
+        // clflush: 8..16,
+        let leaf_1 = self
+            .get_mut(&CpuidKey::leaf(0x1))
+            .ok_or(FeatureInformationError::MissingLeaf1)?;
+        set_range(&mut leaf_1.result.ebx, 8..16, size / 8)
+            .map_err(NormalizeCpuidError::CacheLineSize)?;
+
+        // System Coherency Line Size (Bytes, Plus 1 encoding).
+        //
+        // coherency_line_size: 0..12,
+        for (key, entry) in self.inner_mut().iter_mut() {
+            if (key.leaf == 0x4 || key.leaf == 0x8000001d) && get_range(entry.result.eax, 0..5) != 0
+            {
+                set_range(&mut entry.result.ebx, 0..12, size - 1)
+                    .map_err(NormalizeCpuidError::CacheLineSize)?;
+            }
+        }
+
+        // L1 data (ECX) and instruction (EDX) cache line sizes, and L2 (ECX) and L3 (EDX) cache
+        // line sizes, in bytes.
+        //
+        // line_size: 0..8,
+        for leaf in [0x80000005, 0x80000006] {
+            if let Some(entry) = self.get_mut(&CpuidKey::leaf(leaf)) {
+                for register in [&mut entry.result.ecx, &mut entry.result.edx] {
+                    if get_range(*register, 0..8) != 0 {
+                        set_range(register, 0..8, size)
+                            .map_err(NormalizeCpuidError::CacheLineSize)?;
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Masks the AVX features of leaf 0x7 the user configured as disabled. The features configured
+    /// as enabled are left untouched, and must already be supported by the host, so that the guest
+    /// is never offered a feature the host CPU lacks.
+    fn update_avx_features(
+        &mut self,
+        avx_features: Option<&BTreeMap<AvxFeature, bool>>,
+    ) -> Result<(), NormalizeCpuidError> {
+        let Some(avx_features) = avx_features else {
+            return Ok(());
+        };
+
+        // Without leaf 0x7 there is nothing to mask, and no feature is supported.
+        let mut leaf_7 = self.get_mut(&CpuidKey::subleaf(0x7, 0x0));
+        for (&feature, &enabled) in avx_features {
+            let supported = leaf_7.as_deref_mut().is_some_and(|entry| {
+                let (register, bit_index) = avx_feature_bit(&mut entry.result, feature);
+                let supported = *register & (1 << bit_index) != 0;
+                if !enabled {
+                    set_bit(register, bit_index, false);
+                }
+                supported
+            });
+            if enabled && !supported {
+                return Err(NormalizeCpuidError::AvxFeatureNotSupported(feature));
+            }
+        }
+        Ok(())
+    }
+
+    /// Advertises the maximum standard and extended leaves configured by the user in leaves 0x0
+    /// and 0x80000000, and removes the leaves above them. The host maximums are kept if lower.
+    fn update_max_leaves(&mut self, max_cpuid_leaves: Option<&CpuidMaxLeaves>) {
+        let Some(max_leaves) = max_cpuid_leaves else {
+            return;
+        };
+
+        for (leaves, max_leaf) in [
+            (STANDARD_CPUID_LEAVES, max_leaves.standard),
+            (EXTENDED_CPUID_LEAVES, max_leaves.extended),
+        ] {
+            let Some(max_leaf) = max_leaf else {
+                continue;
+            };
+            if let Some(base_leaf) = self.get_mut(&CpuidKey::leaf(leaves.start)) {
+                base_leaf.result.eax = base_leaf.result.eax.min(max_leaf);
+            }
+            self.inner_mut()
+                .retain(|key, _| !leaves.contains(&key.leaf) || key.leaf <= max_leaf);
+        }
+    }
+}
+
+/// The TSC rate is invariant across P-, C- and T-states.
+const EDX_INVARIANT_TSC_BITINDEX: u8 = 8;
+
+/// Returns whether the host CPU has an invariant TSC, as reported in CPUID.80000007H:EDX[8].
+fn host_invariant_tsc() -> bool {
+    cpuid(0x80000000).eax >= 0x80000007
+        && cpuid(0x80000007).edx & (1 << EDX_INVARIANT_TSC_BITINDEX) != 0
+}
+
+/// Returns the register of leaf 0x7, subleaf 0x0 enumerating the AVX feature, and the index of
+/// its bit.
+fn avx_feature_bit(registers: &mut CpuidRegisters, feature: AvxFeature) -> (&mut u32, u8) {
+    match feature {
+        AvxFeature::Avx2 => (&mut registers.ebx, 5),
+        AvxFeature::Avx512f => (&mut registers.ebx, 16),
+        AvxFeature::Avx512dq => (&mut registers.ebx, 17),
+        AvxFeature::Avx512ifma => (&mut registers.ebx, 21),
+        AvxFeature::Avx512pf => (&mut registers.ebx, 26),
+        AvxFeature::Avx512er => (&mut registers.ebx, 27),
+        AvxFeature::Avx512cd => (&mut registers.ebx, 28),
+        AvxFeature::Avx512bw => (&mut registers.ebx, 30),
+        AvxFeature::Avx512vl => (&mut registers.ebx, 31),
+        AvxFeature::Avx512vbmi => (&mut registers.ecx, 1),
+        AvxFeature::Avx512Vbmi2 => (&mut registers.ecx, 6),
+        AvxFeature::Avx512Vnni => (&mut registers.ecx, 11),
+        AvxFeature::Avx512Bitalg => (&mut registers.ecx, 12),
+        AvxFeature::Avx512Vpopcntdq => (&mut registers.ecx, 14),
+        AvxFeature::Avx5124Vnniw => (&mut registers.edx, 2),
+        AvxFeature::Avx5124Fmaps => (&mut registers.edx, 3),
+        AvxFeature::Avx512Vp2intersect => (&mut registers.edx, 8),
+        AvxFeature::Avx512Fp16 => (&mut registers.edx, 23),
+    }
 }
 
 /// The maximum number of logical processors per package is computed as the closest
@@ -477,6 +976,7 @@ mod tests {
     use std::collections::BTreeMap;
 
     use super::*;
+    use crate::arch::x86_64::cpu_model::CpuModel;
     use crate::cpu_config::x86_64::cpuid::{AmdCpuid, Cpuid, IntelCpuid};
 
     #[test]
@@ -531,7 +1031,7 @@ mod tests {
         )])));
 
         // Pass through vendor ID from host.
-        guest_cpuid.update_vendor_id().unwrap();
+        guest_cpuid.update_vendor_id(None).unwrap();
 
         // Check if the guest vendor ID matches the host one.
         let guest_leaf_0 = guest_cpuid
@@ -546,6 +1046,182 @@ mod tests {
         assert_eq!(guest_leaf_0.result.edx, host_leaf_0.edx);
     }
 
+    #[test]
+    fn test_update_vendor_id_configured() {
+        // Check `update_vendor_id()` sets the configured vendor ID for both vendor-specific
+        // structures, without changing which one is used.
+        let leaf_0 = || {
+            BTreeMap::from([(
+                CpuidKey::leaf(0x0),
+                CpuidEntry {
+                    flags: KvmCpuidFlags::EMPTY,
+                    result: CpuidRegisters {
+                        eax: 0xd,
+                        ebx: 0x0123_4567,
+                        ecx: 0x89ab_cdef,
+                        edx: 0x55aa_55aa,
+                    },
+                },
+            )])
+        };
+
+        for (mut guest_cpuid, vendor_id) in [
+            (Cpuid::Intel(IntelCpuid(leaf_0())), b"AuthenticAMD"),
+            (Cpuid::Amd(AmdCpuid(leaf_0())), b"GenuineIntel"),
+            (Cpuid::Intel(IntelCpuid(leaf_0())), b"  Shanghai  "),
+        ] {
+            let was_intel = matches!(guest_cpuid, Cpuid::Intel(_));
+            guest_cpuid.update_vendor_id(Some(vendor_id)).unwrap();
+
+            assert_eq!(guest_cpuid.vendor_id().as_ref(), Some(vendor_id));
+            assert_eq!(matches!(guest_cpuid, Cpuid::Intel(_)), was_intel);
+            // The maximum supported leaf is left untouched.
+            assert_eq!(
+                guest_cpuid.get(&CpuidKey::leaf(0x0)).unwrap().result.eax,
+                0xd
+            );
+        }
+
+        // The leaf has to be present.
+        let mut guest_cpuid = Cpuid::Intel(IntelCpuid(BTreeMap::new()));
+        assert_eq!(
+            guest_cpuid.update_vendor_id(Some(b"AuthenticAMD")),
+            Err(VendorIdError::MissingLeaf0)
+        );
+    }
+
+    #[test]
+    fn test_update_cpu_signature() {
+        // Bits outside of the signature are left untouched.
+        let reserved = 0xf000_f000;
+        for (family, model, stepping, expected) in [
+            (
+                0x6,
+                0x55,
+                0x7,
+                CpuModel {
+                    extended_family: 0,
+                    extended_model: 0x5,
+                    family: 0x6,
+                    model: 0x5,
+                    stepping: 0x7,
+                },
+            ),
+            (
+                0x19,
+                0x11,
+                0x1,
+                CpuModel {
+                    extended_family: 0xa,
+                    extended_model: 0x1,
+                    family: 0xf,
+                    model: 0x1,
+                    stepping: 0x1,
+                },
+            ),
+            (
+                0x10e,
+                0xff,
+                0xf,
+                CpuModel {
+                    extended_family: 0xff,
+                    extended_model: 0xf,
+                    family: 0xf,
+                    model: 0xf,
+                    stepping: 0xf,
+                },
+            ),
+            (
+                0x5,
+                0x2,
+                0x0,
+                CpuModel {
+                    extended_family: 0,
+                    extended_model: 0,
+                    family: 0x5,
+                    model: 0x2,
+                    stepping: 0,
+                },
+            ),
+        ] {
+            let signature = CpuSignature {
+                family,
+                model,
+                stepping,
+            };
+            let mut eax = u32::MAX;
+            Cpuid::update_cpu_signature(&mut eax, &signature).unwrap();
+            assert_eq!(CpuModel::from(&eax), expected);
+            assert_eq!(eax & reserved, reserved);
+        }
+
+        // Out of range values cannot be encoded.
+        let mut eax = 0;
+        assert_eq!(
+            Cpuid::update_cpu_signature(
+                &mut eax,
+                &CpuSignature {
+                    family: 0x10f,
+                    model: 0,
+                    stepping: 0,
+                }
+            ),
+            Err(FeatureInformationError::ExtendedFamilyId(
+                CheckedAssignError
+            ))
+        );
+        assert_eq!(
+            Cpuid::update_cpu_signature(
+                &mut eax,
+                &CpuSignature {
+                    family: 0x6,
+                    model: 0,
+                    stepping: 0x10,
+                }
+            ),
+            Err(FeatureInformationError::SteppingId(CheckedAssignError))
+        );
+    }
+
+    #[test]
+    fn test_update_feature_info_entry_cpu_signature() {
+        let leaf_1 = || {
+            BTreeMap::from([(
+                CpuidKey::leaf(0x1),
+                CpuidEntry {
+                    flags: KvmCpuidFlags::EMPTY,
+                    result: CpuidRegisters {
+                        eax: 0x0005_0657,
+                        ebx: 0,
+                        ecx: 0,
+                        edx: 0,
+                    },
+                },
+            )])
+        };
+        let leaf_1_eax = |cpuid: &Cpuid| cpuid.get(&CpuidKey::leaf(0x1)).unwrap().result.eax;
+
+        // The host signature is kept if none is configured.
+        let mut guest_cpuid = Cpuid::Intel(IntelCpuid(leaf_1()));
+        guest_cpuid.update_feature_info_entry(0, 1, None).unwrap();
+        assert_eq!(leaf_1_eax(&guest_cpuid), 0x0005_0657);
+
+        let signature = CpuSignature {
+            family: 0x19,
+            model: 0x1,
+            stepping: 0x2,
+        };
+        for mut guest_cpuid in [
+            Cpuid::Intel(IntelCpuid(leaf_1())),
+            Cpuid::Amd(AmdCpuid(leaf_1())),
+        ] {
+            guest_cpuid
+                .update_feature_info_entry(0, 1, Some(&signature))
+                .unwrap();
+            assert_eq!(leaf_1_eax(&guest_cpuid), 0x00a0_0f12);
+        }
+    }
+
     #[test]
     fn check_leaf_0xb_subleaf_0x1_added() {
         // Check leaf 0xb / subleaf 0x1 is added in `update_extended_topology_entry()` even when it
@@ -582,6 +1258,7 @@ mod tests {
             cpu_count,
             cpu_bits,
             cpus_per_core,
+            None,
         );
         result.unwrap();
         assert!(intel_cpuid.inner().contains_key(&CpuidKey {
@@ -605,12 +1282,712 @@ mod tests {
                 },
             },
         )])));
-        let result =
-            amd_cpuid.update_extended_topology_entry(cpu_index, cpu_count, cpu_bits, cpus_per_core);
+        let result = amd_cpuid.update_extended_topology_entry(
+            cpu_index,
+            cpu_count,
+            cpu_bits,
+            cpus_per_core,
+            None,
+        );
         result.unwrap();
         assert!(amd_cpuid.inner().contains_key(&CpuidKey {
             leaf: 0xb,
             subleaf: 0x1
         }));
     }
+
+    #[test]
+    fn test_update_extended_topology_entry_cpu_topology() {
+        let topology = CpuTopology {
+            sockets: 2,
+            cores_per_socket: 4,
+            threads_per_core: 2,
+        };
+        let cpu_count = topology.sockets * topology.cpus_per_socket();
+        let cpu_bits = 1;
+        let cpus_per_core = 2;
+
+        for cpu_index in 0..cpu_count {
+            let mut cpuid = Cpuid::Intel(IntelCpuid(BTreeMap::new()));
+            cpuid.inner_mut().insert(
+                CpuidKey::subleaf(0xb, 0x0),
+                CpuidEntry {
+                    flags: KvmCpuidFlags::SIGNIFICANT_INDEX,
+                    result: CpuidRegisters::default(),
+                },
+            );
+            cpuid
+                .update_extended_topology_entry(
+                    cpu_index,
+                    topology.cpus_per_socket(),
+                    cpu_bits,
+                    cpus_per_core,
+                    Some(&topology),
+                )
+                .unwrap();
+
+            let smt_level = &cpuid.get(&CpuidKey::subleaf(0xb, 0x0)).unwrap().result;
+            let core_level = &cpuid.get(&CpuidKey::subleaf(0xb, 0x1)).unwrap().result;
+
+            // The number of logical CPUs at each level matches the configuration.
+            assert_eq!(smt_level.ebx & 0xffff, u32::from(topology.threads_per_core));
+            assert_eq!(
+                core_level.ebx & 0xffff,
+                u32::from(topology.cpus_per_socket())
+            );
+            assert_eq!(
+                u32::from(topology.sockets) * (core_level.ebx & 0xffff),
+                u32::from(cpu_count)
+            );
+
+            // The x2APIC ID splits into thread, core and socket IDs within the configured ranges.
+            let apic_id = smt_level.edx;
+            assert_eq!(apic_id, core_level.edx);
+            assert_eq!(apic_id, u32::from(cpu_index));
+            let smt_shift = smt_level.eax & 0x1f;
+            let core_shift = core_level.eax & 0x1f;
+            let thread_id = apic_id & ((1 << smt_shift) - 1);
+            let core_id = (apic_id >> smt_shift) & ((1 << (core_shift - smt_shift)) - 1);
+            let socket_id = apic_id >> core_shift;
+            assert_eq!(thread_id, u32::from(cpu_index % 2));
+            assert_eq!(core_id, u32::from(cpu_index / 2 % 4));
+            assert_eq!(socket_id, u32::from(cpu_index / 8));
+            assert!(socket_id < u32::from(topology.sockets));
+        }
+    }
+
+    #[test]
+    fn test_update_extended_topology_v2_entry() {
+        let entry = |ecx| CpuidEntry {
+            flags: KvmCpuidFlags::SIGNIFICANT_INDEX,
+            result: CpuidRegisters {
+                ecx,
+                ..Default::default()
+            },
+        };
+        let topology = CpuTopology {
+            sockets: 1,
+            cores_per_socket: 4,
+            threads_per_core: 2,
+        };
+
+        // The host leaf 0x1F also describes the module and die levels.
+        let mut cpuid = Cpuid::Intel(IntelCpuid(BTreeMap::from([
+            (CpuidKey::subleaf(0xb, 0x0), entry(0x100)),
+            (CpuidKey::subleaf(0xb, 0x1), entry(0x201)),
+            (CpuidKey::subleaf(0xb, 0x2), entry(0x2)),
+            (CpuidKey::subleaf(0x1f, 0x0), entry(0x100)),
+            (CpuidKey::subleaf(0x1f, 0x1), entry(0x201)),
+            (CpuidKey::subleaf(0x1f, 0x2), entry(0x302)),
+            (CpuidKey::subleaf(0x1f, 0x3), entry(0x503)),
+            (CpuidKey::subleaf(0x1f, 0x4), entry(0x4)),
+        ])));
+        cpuid
+            .update_extended_topology_entry(5, 8, 1, 2, Some(&topology))
+            .unwrap();
+        cpuid.update_extended_topology_v2_entry();
+
+        // Leaf 0x1F presents the same topology as leaf 0xB.
+        for subleaf in 0x0..0x3 {
+            assert_eq!(
+                cpuid.get(&CpuidKey::subleaf(0x1f, subleaf)),
+                cpuid.get(&CpuidKey::subleaf(0xb, subleaf))
+            );
+        }
+        assert_eq!(
+            cpuid.get(&CpuidKey::subleaf(0x1f, 0x1)).unwrap().result.edx,
+            5
+        );
+        assert!(cpuid.get(&CpuidKey::subleaf(0x1f, 0x3)).is_none());
+        assert!(cpuid.get(&CpuidKey::subleaf(0x1f, 0x4)).is_none());
+
+        // Leaf 0x1F is not added when the host does not have it.
+        let mut cpuid = Cpuid::Intel(IntelCpuid(BTreeMap::from([(
+            CpuidKey::subleaf(0xb, 0x0),
+            entry(0x100),
+        )])));
+        cpuid.update_extended_topology_v2_entry();
+        assert!(cpuid.inner().keys().all(|key| key.leaf != 0x1f));
+    }
+
+    #[test]
+    fn test_update_max_leaves() {
+        let entry = |eax| CpuidEntry {
+            flags: KvmCpuidFlags::EMPTY,
+            result: CpuidRegisters {
+                eax,
+                ebx: 0,
+                ecx: 0,
+                edx: 0,
+            },
+        };
+        let host_cpuid = Cpuid::Intel(IntelCpuid(BTreeMap::from([
+            (CpuidKey::leaf(0x0), entry(0x1f)),
+            (CpuidKey::leaf(0x1), entry(0)),
+            (CpuidKey::subleaf(0xd, 0x1), entry(0)),
+            (CpuidKey::leaf(0x1f), entry(0)),
+            (CpuidKey::leaf(0x4000_0000), entry(0x4000_0001)),
+            (CpuidKey::leaf(0x8000_0000), entry(0x8000_0008)),
+            (CpuidKey::leaf(0x8000_0001), entry(0)),
+            (CpuidKey::leaf(0x8000_0008), entry(0)),
+        ])));
+        let leaves =
+            |cpuid: &Cpuid| -> Vec<u32> { cpuid.inner().keys().map(|key| key.leaf).collect() };
+        let max_leaf = |cpuid: &Cpuid, leaf| cpuid.get(&CpuidKey::leaf(leaf)).unwrap().result.eax;
+
+        // The host leaves are kept if not configured.
+        let mut cpuid = host_cpuid.clone();
+        cpuid.update_max_leaves(None);
+        assert_eq!(cpuid, host_cpuid);
+        cpuid.update_max_leaves(Some(&CpuidMaxLeaves::default()));
+        assert_eq!(cpuid, host_cpuid);
+
+        // Leaves above the maximums are removed, and the maximums are reported.
+        let mut cpuid = host_cpuid.clone();
+        cpuid.update_max_leaves(Some(&CpuidMaxLeaves {
+            standard: Some(0xd),
+            extended: Some(0x8000_0001),
+        }));
+        assert_eq!(
+            leaves(&cpuid),
+            vec![0x0, 0x1, 0xd, 0x4000_0000, 0x8000_0000, 0x8000_0001]
+        );
+        assert_eq!(max_leaf(&cpuid, 0x0), 0xd);
+        assert_eq!(max_leaf(&cpuid, 0x8000_0000), 0x8000_0001);
+        // The hypervisor leaves are not affected.
+        assert_eq!(max_leaf(&cpuid, 0x4000_0000), 0x4000_0001);
+
+        // Maximums above the host ones are not advertised.
+        let mut cpuid = host_cpuid.clone();
+        cpuid.update_max_leaves(Some(&CpuidMaxLeaves {
+            standard: Some(0x20),
+            extended: None,
+        }));
+        assert_eq!(cpuid, host_cpuid);
+        assert_eq!(max_leaf(&cpuid, 0x0), 0x1f);
+    }
+
+    #[test]
+    fn test_update_invariant_tsc() {
+        let host_edx = 0x0000_0100;
+        let mut cpuid = Cpuid::Intel(IntelCpuid(BTreeMap::from([(
+            CpuidKey::leaf(0x80000007),
+            CpuidEntry {
+                flags: KvmCpuidFlags::EMPTY,
+                result: CpuidRegisters {
+                    eax: 0,
+                    ebx: 0,
+                    ecx: 0,
+                    edx: host_edx,
+                },
+            },
+        )])));
+        let edx = |cpuid: &Cpuid| cpuid.get(&CpuidKey::leaf(0x80000007)).unwrap().result.edx;
+
+        // The host value is kept if not configured.
+        cpuid.update_invariant_tsc(None, true).unwrap();
+        assert_eq!(edx(&cpuid), host_edx);
+
+        cpuid.update_invariant_tsc(Some(false), true).unwrap();
+        assert_eq!(edx(&cpuid), 0);
+
+        cpuid.update_invariant_tsc(Some(true), true).unwrap();
+        assert_eq!(edx(&cpuid), 1 << 8);
+
+        // Without an invariant TSC on the host, it can be masked, but not advertised.
+        cpuid.update_invariant_tsc(None, false).unwrap();
+        cpuid.update_invariant_tsc(Some(false), false).unwrap();
+        assert_eq!(edx(&cpuid), 0);
+        assert_eq!(
+            cpuid.update_invariant_tsc(Some(true), false),
+            Err(NormalizeCpuidError::InvariantTscNotSupported)
+        );
+        assert_eq!(edx(&cpuid), 0);
+
+        let mut cpuid = Cpuid::Intel(IntelCpuid(BTreeMap::new()));
+        cpuid.update_invariant_tsc(None, true).unwrap();
+        assert_eq!(
+            cpuid.update_invariant_tsc(Some(true), true),
+            Err(NormalizeCpuidError::MissingLeaf0x80000007)
+        );
+    }
+
+    #[test]
+    fn test_update_hypervisor_bit() {
+        let mut cpuid = Cpuid::Intel(IntelCpuid(BTreeMap::from([(
+            CpuidKey::leaf(0x1),
+            CpuidEntry {
+                flags: KvmCpuidFlags::EMPTY,
+                result: CpuidRegisters::default(),
+            },
+        )])));
+        let ecx = |cpuid: &Cpuid| cpuid.get(&CpuidKey::leaf(0x1)).unwrap().result.ecx;
+
+        // The bit is advertised by default.
+        cpuid.update_feature_info_entry(0, 1, None).unwrap();
+        cpuid.update_hypervisor_bit(None).unwrap();
+        assert_eq!(ecx(&cpuid) & (1 << 31), 1 << 31);
+
+        cpuid.update_hypervisor_bit(Some(false)).unwrap();
+        assert_eq!(ecx(&cpuid) & (1 << 31), 0);
+        // The other feature bits are left untouched.
+        assert_eq!(ecx(&cpuid), 1 << 24);
+
+        cpuid.update_hypervisor_bit(Some(true)).unwrap();
+        assert_eq!(ecx(&cpuid) & (1 << 31), 1 << 31);
+
+        let mut cpuid = Cpuid::Intel(IntelCpuid(BTreeMap::new()));
+        cpuid.update_hypervisor_bit(None).unwrap();
+        assert_eq!(
+            cpuid.update_hypervisor_bit(Some(false)),
+            Err(FeatureInformationError::MissingLeaf1)
+        );
+    }
+
+    #[test]
+    fn test_update_rng_instructions() {
+        let host_cpuid = Cpuid::Intel(IntelCpuid(BTreeMap::from([
+            (
+                CpuidKey::leaf(0x1),
+                CpuidEntry {
+                    flags: KvmCpuidFlags::EMPTY,
+                    result: CpuidRegisters {
+                        ecx: (1 << 30) | 1,
+                        ..Default::default()
+                    },
+                },
+            ),
+            (
+                CpuidKey::subleaf(0x7, 0x0),
+                CpuidEntry {
+                    flags: KvmCpuidFlags::SIGNIFICANT_INDEX,
+                    result: CpuidRegisters {
+                        ebx: (1 << 18) | 1,
+                        ..Default::default()
+                    },
+                },
+            ),
+        ])));
+        let ecx = |cpuid: &Cpuid| cpuid.get(&CpuidKey::leaf(0x1)).unwrap().result.ecx;
+        let ebx = |cpuid: &Cpuid| cpuid.get(&CpuidKey::subleaf(0x7, 0x0)).unwrap().result.ebx;
+
+        // The host values are passed through by default, or when required.
+        let mut cpuid = host_cpuid.clone();
+        cpuid.update_rng_instructions(None, None).unwrap();
+        cpuid
+            .update_rng_instructions(Some(true), Some(true))
+            .unwrap();
+        assert_eq!(cpuid, host_cpuid);
+
+        // Each instruction is masked independently, leaving the other feature bits untouched.
+        let mut cpuid = host_cpuid.clone();
+        cpuid.update_rng_instructions(Some(false), None).unwrap();
+        assert_eq!(ecx(&cpuid), 1);
+        assert_eq!(ebx(&cpuid), (1 << 18) | 1);
+
+        let mut cpuid = host_cpuid.clone();
+        cpuid.update_rng_instructions(None, Some(false)).unwrap();
+        assert_eq!(ecx(&cpuid), (1 << 30) | 1);
+        assert_eq!(ebx(&cpuid), 1);
+
+        // Once both are masked, the guest has no hardware RNG instruction left to rely on, and
+        // has to fall back to another entropy source, like a virtio-rng device.
+        let mut cpuid = host_cpuid.clone();
+        cpuid
+            .update_rng_instructions(Some(false), Some(false))
+            .unwrap();
+        assert_eq!(ecx(&cpuid) & (1 << 30), 0);
+        assert_eq!(ebx(&cpuid) & (1 << 18), 0);
+
+        // Instructions the host lacks can be masked, but not required.
+        cpuid
+            .update_rng_instructions(Some(false), Some(false))
+            .unwrap();
+        assert_eq!(
+            cpuid.update_rng_instructions(Some(true), None),
+            Err(NormalizeCpuidError::RngInstructionNotSupported("RDRAND"))
+        );
+        assert_eq!(
+            cpuid.update_rng_instructions(None, Some(true)),
+            Err(NormalizeCpuidError::RngInstructionNotSupported("RDSEED"))
+        );
+
+        let mut cpuid = Cpuid::Intel(IntelCpuid(BTreeMap::new()));
+        cpuid
+            .update_rng_instructions(Some(false), Some(false))
+            .unwrap();
+        assert_eq!(
+            cpuid.update_rng_instructions(None, Some(true)),
+            Err(NormalizeCpuidError::RngInstructionNotSupported("RDSEED"))
+        );
+    }
+
+    #[test]
+    fn test_update_brand_string() {
+        let brand_string_leaf = |leaf| {
+            (
+                CpuidKey::leaf(leaf),
+                CpuidEntry {
+                    flags: KvmCpuidFlags::EMPTY,
+                    result: CpuidRegisters {
+                        eax: u32::MAX,
+                        ebx: u32::MAX,
+                        ecx: u32::MAX,
+                        edx: u32::MAX,
+                    },
+                },
+            )
+        };
+        let host_cpuid = Cpuid::Amd(AmdCpuid(BTreeMap::from([
+            brand_string_leaf(0x80000002),
+            brand_string_leaf(0x80000003),
+            brand_string_leaf(0x80000004),
+        ])));
+        let brand_string = |cpuid: &Cpuid| {
+            (0x80000002..=0x80000004)
+                .flat_map(|leaf| {
+                    let result = &cpuid.get(&CpuidKey::leaf(leaf)).unwrap().result;
+                    [result.eax, result.ebx, result.ecx, result.edx]
+                })
+                .flat_map(u32::to_ne_bytes)
+                .collect::<Vec<u8>>()
+        };
+
+        // The default brand string is kept when none is configured.
+        let mut cpuid = host_cpuid.clone();
+        cpuid.update_brand_string(None).unwrap();
+        assert_eq!(cpuid, host_cpuid);
+
+        // The configured brand string is NUL padded.
+        let custom = "Fleet Baseline CPU @ 2.40GHz";
+        cpuid.update_brand_string(Some(custom)).unwrap();
+        let mut expected = custom.as_bytes().to_vec();
+        expected.resize(BRAND_STRING_LENGTH, 0);
+        assert_eq!(brand_string(&cpuid), expected);
+
+        // The longest brand string leaves room for the NUL terminator.
+        let longest = "a".repeat(BRAND_STRING_LENGTH - 1);
+        cpuid.update_brand_string(Some(&longest)).unwrap();
+        assert_eq!(
+            brand_string(&cpuid)[..BRAND_STRING_LENGTH - 1],
+            *longest.as_bytes()
+        );
+        assert_eq!(brand_string(&cpuid)[BRAND_STRING_LENGTH - 1], 0);
+        assert_eq!(
+            cpuid.update_brand_string(Some(&"a".repeat(BRAND_STRING_LENGTH))),
+            Err(NormalizeCpuidError::BrandStringTooLong(BRAND_STRING_LENGTH))
+        );
+
+        let mut cpuid = Cpuid::Intel(IntelCpuid(BTreeMap::new()));
+        assert_eq!(
+            cpuid.update_brand_string(Some(custom)),
+            Err(NormalizeCpuidError::BrandString(MissingBrandStringLeaves))
+        );
+    }
+
+    #[test]
+    fn test_update_monitor_mwait() {
+        let kvm_cpuid = Cpuid::Intel(IntelCpuid(BTreeMap::from([
+            (
+                CpuidKey::leaf(0x1),
+                CpuidEntry {
+                    flags: KvmCpuidFlags::EMPTY,
+                    result: CpuidRegisters {
+                        ecx: (1 << 3) | 1,
+                        ..Default::default()
+                    },
+                },
+            ),
+            (
+                CpuidKey::leaf(0x5),
+                CpuidEntry {
+                    flags: KvmCpuidFlags::EMPTY,
+                    result: CpuidRegisters {
+                        eax: 0x40,
+                        ebx: 0x40,
+                        ecx: 0x3,
+                        edx: 0x11142120,
+                    },
+                },
+            ),
+        ])));
+        let ecx = |cpuid: &Cpuid| cpuid.get(&CpuidKey::leaf(0x1)).unwrap().result.ecx;
+
+        // What KVM reports is passed through by default, or when required.
+        let mut cpuid = kvm_cpuid.clone();
+        cpuid.update_monitor_mwait(None).unwrap();
+        cpuid.update_monitor_mwait(Some(true)).unwrap();
+        assert_eq!(cpuid, kvm_cpuid);
+
+        // Masking hides the instructions and their parameters, leaving the other bits untouched.
+        cpuid.update_monitor_mwait(Some(false)).unwrap();
+        assert_eq!(ecx(&cpuid), 1);
+        assert_eq!(
+            cpuid.get(&CpuidKey::leaf(0x5)).unwrap().result,
+            CpuidRegisters::default()
+        );
+
+        // Instructions KVM does not report can be masked, but not required.
+        cpuid.update_monitor_mwait(Some(false)).unwrap();
+        assert_eq!(
+            cpuid.update_monitor_mwait(Some(true)),
+            Err(NormalizeCpuidError::MonitorMwaitNotSupported)
+        );
+        let mut cpuid = Cpuid::Intel(IntelCpuid(BTreeMap::new()));
+        cpuid.update_monitor_mwait(Some(false)).unwrap();
+        assert_eq!(
+            cpuid.update_monitor_mwait(Some(true)),
+            Err(NormalizeCpuidError::MonitorMwaitNotSupported)
+        );
+    }
+
+    #[test]
+    fn test_update_tsc_deadline() {
+        let leaf_1 = |ecx, edx| {
+            Cpuid::Intel(IntelCpuid(BTreeMap::from([(
+                CpuidKey::leaf(0x1),
+                CpuidEntry {
+                    flags: KvmCpuidFlags::EMPTY,
+                    result: CpuidRegisters {
+                        ecx,
+                        edx,
+                        ..Default::default()
+                    },
+                },
+            )])))
+        };
+        let ecx = |cpuid: &Cpuid| cpuid.get(&CpuidKey::leaf(0x1)).unwrap().result.ecx;
+
+        // The normalized value is kept by default.
+        let mut cpuid = leaf_1((1 << 24) | 1, 1 << 9);
+        cpuid.update_tsc_deadline(None).unwrap();
+        assert_eq!(ecx(&cpuid), (1 << 24) | 1);
+
+        // Masking leaves the other bits untouched.
+        cpuid.update_tsc_deadline(Some(false)).unwrap();
+        assert_eq!(ecx(&cpuid), 1);
+        cpuid.update_tsc_deadline(Some(true)).unwrap();
+        assert_eq!(ecx(&cpuid), (1 << 24) | 1);
+
+        // The TSC deadline timer is only advertised along with the local APIC.
+        let mut cpuid = leaf_1(1 << 24, 0);
+        assert_eq!(
+            cpuid.update_tsc_deadline(Some(true)),
+            Err(NormalizeCpuidError::TscDeadlineWithoutApic)
+        );
+        cpuid.update_tsc_deadline(Some(false)).unwrap();
+        assert_eq!(ecx(&cpuid), 0);
+    }
+
+    #[test]
+    fn test_update_cpu_frequencies() {
+        let frequencies = CpuFrequencies {
+            base_mhz: 2100,
+            max_mhz: 3500,
+            bus_mhz: 100,
+        };
+        let frequency_leaf = |cpuid: &Cpuid| cpuid.get(&CpuidKey::leaf(0x16)).unwrap().result;
+        let max_leaf = |cpuid: &Cpuid| cpuid.get(&CpuidKey::leaf(0x0)).unwrap().result.eax;
+
+        // The host frequencies are passed through by default.
+        let kvm_cpuid = Cpuid::Intel(IntelCpuid(BTreeMap::from([
+            (
+                CpuidKey::leaf(0x0),
+                CpuidEntry {
+                    flags: KvmCpuidFlags::EMPTY,
+                    result: CpuidRegisters {
+                        eax: 0x1f,
+                        ..Default::default()
+                    },
+                },
+            ),
+            (
+                CpuidKey::leaf(0x16),
+                CpuidEntry {
+                    flags: KvmCpuidFlags::EMPTY,
+                    result: CpuidRegisters {
+                        eax: 0xffff_0bb8,
+                        ebx: 0xffff_0e10,
+                        ecx: 0xffff_0064,
+                        edx: 0xffff_ffff,
+                    },
+                },
+            ),
+        ])));
+        let mut cpuid = kvm_cpuid.clone();
+        cpuid.update_cpu_frequencies(None);
+        assert_eq!(cpuid, kvm_cpuid);
+
+        // The configured frequencies replace the whole leaf, including its reserved bits.
+        cpuid.update_cpu_frequencies(Some(&frequencies));
+        assert_eq!(
+            frequency_leaf(&cpuid),
+            CpuidRegisters {
+                eax: 2100,
+                ebx: 3500,
+                ecx: 100,
+                edx: 0,
+            }
+        );
+        assert_eq!(max_leaf(&cpuid), 0x1f);
+
+        // The leaf is added if KVM does not report it.
+        let mut cpuid = Cpuid::Intel(IntelCpuid(BTreeMap::from([(
+            CpuidKey::leaf(0x0),
+            CpuidEntry {
+                flags: KvmCpuidFlags::EMPTY,
+                result: CpuidRegisters {
+                    eax: 0xd,
+                    ..Default::default()
+                },
+            },
+        )])));
+        cpuid.update_cpu_frequencies(Some(&frequencies));
+        assert_eq!(frequency_leaf(&cpuid).eax, 2100);
+        assert_eq!(max_leaf(&cpuid), 0x16);
+        assert_eq!(CpuFrequencies::from_cpuid(&cpuid), Some(frequencies));
+
+        // On AMD, which does not define the leaf, the maximum standard leaf is raised the same.
+        let mut cpuid = Cpuid::Amd(AmdCpuid(BTreeMap::from([(
+            CpuidKey::leaf(0x0),
+            CpuidEntry {
+                flags: KvmCpuidFlags::EMPTY,
+                result: CpuidRegisters {
+                    eax: 0x10,
+                    ..Default::default()
+                },
+            },
+        )])));
+        assert_eq!(CpuFrequencies::from_cpuid(&cpuid), None);
+        cpuid.update_cpu_frequencies(Some(&frequencies));
+        assert_eq!(max_leaf(&cpuid), 0x16);
+        assert_eq!(CpuFrequencies::from_cpuid(&cpuid), Some(frequencies));
+
+        // The leaf is not advertised above the maximum standard leaf.
+        cpuid.get_mut(&CpuidKey::leaf(0x0)).unwrap().result.eax = 0x15;
+        assert_eq!(CpuFrequencies::from_cpuid(&cpuid), None);
+    }
+
+    #[test]
+    fn test_update_cache_line_size() {
+        let entry = |eax, ebx, ecx, edx| CpuidEntry {
+            flags: KvmCpuidFlags::EMPTY,
+            result: CpuidRegisters { eax, ebx, ecx, edx },
+        };
+        // A host with 64 bytes cache lines, reporting only the L2 line size in leaf 0x80000006.
+        let host_cpuid = Cpuid::Intel(IntelCpuid(BTreeMap::from([
+            (CpuidKey::leaf(0x1), entry(0, 0x0001_0800, 0, 0)),
+            (
+                CpuidKey::subleaf(0x4, 0),
+                entry(0x121, 0x01c0_003f, 0x3f, 0),
+            ),
+            (
+                CpuidKey::subleaf(0x4, 1),
+                entry(0x143, 0x03c0_003f, 0x3ff, 0),
+            ),
+            (CpuidKey::subleaf(0x4, 2), entry(0, 0, 0, 0)),
+            (CpuidKey::leaf(0x80000005), entry(0, 0, 0, 0)),
+            (CpuidKey::leaf(0x80000006), entry(0, 0, 0x0100_6040, 0)),
+        ])));
+        let result = |cpuid: &Cpuid, key| cpuid.get(&key).unwrap().result.clone();
+
+        // The host values are kept if not configured.
+        let mut cpuid = host_cpuid.clone();
+        cpuid.update_cache_line_size(None).unwrap();
+        assert_eq!(cpuid, host_cpuid);
+
+        cpuid.update_cache_line_size(Some(128)).unwrap();
+        assert_eq!(result(&cpuid, CpuidKey::leaf(0x1)).ebx, 0x0001_1000);
+        assert_eq!(result(&cpuid, CpuidKey::subleaf(0x4, 0)).ebx, 0x01c0_007f);
+        assert_eq!(result(&cpuid, CpuidKey::subleaf(0x4, 1)).ebx, 0x03c0_007f);
+        // The subleaf terminating the cache descriptions stays invalid.
+        assert_eq!(
+            result(&cpuid, CpuidKey::subleaf(0x4, 2)),
+            CpuidRegisters::default()
+        );
+        // Only the line sizes reported by the host are overridden.
+        assert_eq!(
+            result(&cpuid, CpuidKey::leaf(0x80000005)),
+            CpuidRegisters::default()
+        );
+        assert_eq!(result(&cpuid, CpuidKey::leaf(0x80000006)).ecx, 0x0100_6080);
+        assert_eq!(result(&cpuid, CpuidKey::leaf(0x80000006)).edx, 0);
+
+        let mut cpuid = Cpuid::Intel(IntelCpuid(BTreeMap::new()));
+        cpuid.update_cache_line_size(None).unwrap();
+        assert_eq!(
+            cpuid.update_cache_line_size(Some(64)),
+            Err(NormalizeCpuidError::FeatureInformation(
+                FeatureInformationError::MissingLeaf1
+            ))
+        );
+    }
+
+    #[test]
+    fn test_update_avx_features() {
+        // A host supporting AVX2, AVX-512F, AVX-512VL and AVX-512 VBMI2.
+        let host_registers = CpuidRegisters {
+            eax: 0,
+            ebx: (1 << 5) | (1 << 16) | (1 << 31),
+            ecx: 1 << 6,
+            edx: 0,
+        };
+        let host_cpuid = Cpuid::Intel(IntelCpuid(BTreeMap::from([(
+            CpuidKey::subleaf(0x7, 0x0),
+            CpuidEntry {
+                flags: KvmCpuidFlags::SIGNIFICANT_INDEX,
+                result: host_registers.clone(),
+            },
+        )])));
+        let registers = |cpuid: &Cpuid| {
+            cpuid
+                .get(&CpuidKey::subleaf(0x7, 0x0))
+                .unwrap()
+                .result
+                .clone()
+        };
+
+        // The host features are kept if not configured.
+        let mut cpuid = host_cpuid.clone();
+        cpuid.update_avx_features(None).unwrap();
+        assert_eq!(cpuid, host_cpuid);
+
+        // Only the features configured as disabled are masked.
+        let features = BTreeMap::from([
+            (AvxFeature::Avx2, true),
+            (AvxFeature::Avx512f, false),
+            (AvxFeature::Avx512Vbmi2, false),
+            (AvxFeature::Avx512Fp16, false),
+        ]);
+        cpuid.update_avx_features(Some(&features)).unwrap();
+        assert_eq!(
+            registers(&cpuid),
+            CpuidRegisters {
+                ebx: (1 << 5) | (1 << 31),
+                ecx: 0,
+                ..host_registers
+            }
+        );
+
+        // Features the host does not support cannot be enabled.
+        for feature in [AvxFeature::Avx512bw, AvxFeature::Avx5124Fmaps] {
+            let mut cpuid = host_cpuid.clone();
+            assert_eq!(
+                cpuid.update_avx_features(Some(&BTreeMap::from([(feature, true)]))),
+                Err(NormalizeCpuidError::AvxFeatureNotSupported(feature))
+            );
+        }
+
+        // Without leaf 0x7, features can only be disabled.
+        let mut cpuid = Cpuid::Intel(IntelCpuid(BTreeMap::new()));
+        cpuid
+            .update_avx_features(Some(&BTreeMap::from([(AvxFeature::Avx2, false)])))
+            .unwrap();
+        assert_eq!(
+            cpuid.update_avx_features(Some(&BTreeMap::from([(AvxFeature::Avx2, true)]))),
+            Err(NormalizeCpuidError::AvxFeatureNotSupported(
+                AvxFeature::Avx2
+            ))
+        );
+    }
 }
@@ -4,6 +4,8 @@
 use derive_more::Display;
 use serde::{Deserialize, Serialize};
 
+use crate::cpu_config::templates::CustomCpuTemplate;
+
 /// Module with C3 CPU template for x86_64
 pub mod c3;
 /// Module with T2 CPU template for x86_64
@@ -40,11 +42,47 @@ pub enum StaticCpuTemplate {
     T2A,
 }
 
+/// Templates that can be looked up by name.
+const TEMPLATES: [StaticCpuTemplate; 5] = [
+    StaticCpuTemplate::C3,
+    StaticCpuTemplate::T2,
+    StaticCpuTemplate::T2S,
+    StaticCpuTemplate::T2CL,
+    StaticCpuTemplate::T2A,
+];
+
 impl StaticCpuTemplate {
     /// Check if no template specified
     pub fn is_none(&self) -> bool {
         self == &StaticCpuTemplate::None
     }
+
+    /// Looks up a template by name, ignoring case. Returns `None` for unknown names.
+    pub fn from_name(name: &str) -> Option<Self> {
+        TEMPLATES
+            .into_iter()
+            .find(|template| template.to_string().eq_ignore_ascii_case(name))
+    }
+
+    /// Names of the templates that can be looked up with [`StaticCpuTemplate::from_name`].
+    pub fn available_templates() -> &'static [&'static str] {
+        &["C3", "T2", "T2S", "T2CL", "T2A"]
+    }
+
+    /// Returns the modifiers of this template as a custom CPU template, which serializes into the
+    /// custom CPU template JSON format. Returns `None` for [`StaticCpuTemplate::None`].
+    ///
+    /// Unlike when the template is applied, the host CPU is not checked to be compatible with it.
+    pub fn custom_template(&self) -> Option<CustomCpuTemplate> {
+        match self {
+            StaticCpuTemplate::C3 => Some(c3::c3()),
+            StaticCpuTemplate::T2 => Some(t2::t2()),
+            StaticCpuTemplate::T2S => Some(t2s::t2s()),
+            StaticCpuTemplate::T2CL => Some(t2cl::t2cl()),
+            StaticCpuTemplate::T2A => Some(t2a::t2a()),
+            StaticCpuTemplate::None => None,
+        }
+    }
 }
 
 #[cfg(test)]
@@ -67,4 +105,78 @@ mod tests {
             assert_eq!(hardcoded_template, json_template);
         }
     }
+
+    #[test]
+    fn test_custom_template_json() {
+        assert_eq!(StaticCpuTemplate::None.custom_template(), None);
+
+        let template = StaticCpuTemplate::T2.custom_template().unwrap();
+        assert_eq!(template, t2::t2());
+        let json = serde_json::to_string_pretty(&template).unwrap();
+
+        let value: serde_json::Value = serde_json::from_str(&json).unwrap();
+        let modifiers = value["cpuid_modifiers"].as_array().unwrap();
+        let bitmap = |leaf: &str, register: &str| {
+            let leaf = modifiers
+                .iter()
+                .find(|modifier| modifier["leaf"] == leaf && modifier["subleaf"] == "0x0")
+                .unwrap();
+            leaf["modifiers"]
+                .as_array()
+                .unwrap()
+                .iter()
+                .find(|modifier| modifier["register"] == register)
+                .unwrap()["bitmap"]
+                .clone()
+        };
+        assert_eq!(
+            bitmap("0x1", "eax"),
+            serde_json::json!("0bxxxx000000000011xx00011011110010")
+        );
+        assert_eq!(
+            bitmap("0x7", "ebx"),
+            serde_json::json!("0b00000000000x000000x00x1xxxx0x0xx")
+        );
+
+        // Loading the JSON back gives the same template.
+        for name in StaticCpuTemplate::available_templates() {
+            let template = StaticCpuTemplate::from_name(name)
+                .unwrap()
+                .custom_template()
+                .unwrap();
+            let json = serde_json::to_string(&template).unwrap();
+            assert_eq!(
+                serde_json::from_str::<CustomCpuTemplate>(&json).unwrap(),
+                template
+            );
+        }
+    }
+
+    #[test]
+    fn test_from_name() {
+        assert_eq!(
+            StaticCpuTemplate::from_name("C3"),
+            Some(StaticCpuTemplate::C3)
+        );
+        assert_eq!(
+            StaticCpuTemplate::from_name("t2s"),
+            Some(StaticCpuTemplate::T2S)
+        );
+        assert_eq!(
+            StaticCpuTemplate::from_name("T2cl"),
+            Some(StaticCpuTemplate::T2CL)
+        );
+        assert_eq!(StaticCpuTemplate::from_name("None"), None);
+        assert_eq!(StaticCpuTemplate::from_name("T3"), None);
+        assert_eq!(StaticCpuTemplate::from_name(""), None);
+
+        assert_eq!(
+            StaticCpuTemplate::available_templates().len(),
+            TEMPLATES.len()
+        );
+        for name in StaticCpuTemplate::available_templates() {
+            let template = StaticCpuTemplate::from_name(name).unwrap();
+            assert_eq!(template.to_string(), *name);
+        }
+    }
 }
@@ -8,9 +8,11 @@
 use std::collections::HashMap;
 use std::fmt::Debug;
 use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
 
 #[cfg(target_arch = "x86_64")]
 use acpi_tables::{aml, Aml};
+use event_manager::SubscriberId;
 use kvm_ioctls::{IoEventAddress, VmFd};
 use linux_loader::cmdline as kernel_cmdline;
 #[cfg(target_arch = "x86_64")]
@@ -25,7 +27,7 @@ use crate::arch::aarch64::DeviceInfoForFDT;
 use crate::arch::DeviceType;
 use crate::arch::DeviceType::Virtio;
 #[cfg(target_arch = "aarch64")]
-use crate::devices::legacy::RTCDevice;
+use crate::devices::legacy::{Gpio, RTCDevice};
 use crate::devices::pseudo::BootTimer;
 use crate::devices::virtio::balloon::Balloon;
 use crate::devices::virtio::block::device::Block;
@@ -34,7 +36,8 @@ use crate::devices::virtio::mmio::MmioTransport;
 use crate::devices::virtio::net::Net;
 use crate::devices::virtio::rng::Entropy;
 use crate::devices::virtio::vsock::{Vsock, VsockUnixBackend, TYPE_VSOCK};
-use crate::devices::virtio::{TYPE_BALLOON, TYPE_BLOCK, TYPE_NET, TYPE_RNG};
+use crate::devices::virtio::watchdog::Watchdog;
+use crate::devices::virtio::{TYPE_BALLOON, TYPE_BLOCK, TYPE_NET, TYPE_RNG, TYPE_WATCHDOG};
 use crate::devices::BusDevice;
 #[cfg(target_arch = "x86_64")]
 use crate::vstate::memory::GuestAddress;
@@ -44,8 +47,8 @@ use crate::vstate::memory::GuestAddress;
 pub enum MmioError {
     /// Failed to allocate requested resource: {0}
     Allocator(#[from] vm_allocator::Error),
-    /// Failed to insert device on the bus: {0}
-    BusInsert(crate::devices::BusError),
+    /// Failed to insert device {0} on the bus: {1}
+    BusInsert(String, crate::devices::BusError),
     /// Failed to allocate requested resourc: {0}
     Cmdline(linux_loader::cmdline::Error),
     /// Failed to find the device on the bus.
@@ -56,10 +59,20 @@ pub enum MmioError {
     InternalDeviceError(String),
     /// Invalid MMIO IRQ configuration.
     InvalidIrqConfig,
+    /// No IRQ left for device {0}: {1}
+    IrqExhausted(String, vm_allocator::Error),
+    /// No MMIO address space left for device {0}: {1}
+    AddressSpaceExhausted(String, vm_allocator::Error),
     /// Failed to register IO event: {0}
     RegisterIoEvent(kvm_ioctls::Error),
     /// Failed to register irqfd: {0}
     RegisterIrqFd(kvm_ioctls::Error),
+    /// Failed to unregister IO event: {0}
+    UnregisterIoEvent(kvm_ioctls::Error),
+    /// Failed to unregister irqfd: {0}
+    UnregisterIrqFd(kvm_ioctls::Error),
+    /// The driver did not release the device in time.
+    DeviceNotReleased,
     #[cfg(target_arch = "x86_64")]
     /// Failed to create AML code for device
     AmlError(#[from] aml::AmlError),
@@ -72,6 +85,11 @@ pub enum MmioError {
 /// Currently hardcoded to 4K.
 pub const MMIO_LEN: u64 = 0x1000;
 
+/// How long the removal of a virtio device waits for the driver to release the device.
+pub const DEVICE_RELEASE_TIMEOUT: Duration = Duration::from_millis(500);
+/// How often the removal of a virtio device checks whether the driver released the device.
+const DEVICE_RELEASE_POLL_INTERVAL: Duration = Duration::from_millis(10);
+
 /// Stores the address range and irq allocated to this device.
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct MMIODeviceInfo {
@@ -122,6 +140,9 @@ fn add_virtio_aml(
 pub struct MMIODeviceManager {
     pub(crate) bus: crate::devices::Bus,
     pub(crate) id_to_dev_info: HashMap<(DeviceType, String), MMIODeviceInfo>,
+    // The event manager subscribers of the virtio devices, needed to stop handling the events
+    // of a device when it is removed.
+    pub(crate) subscriber_ids: HashMap<(DeviceType, String), SubscriberId>,
     // We create the AML byte code for every VirtIO device in the order we build
     // it, so that we ensure the root block device is appears first in the DSDT.
     // This is needed, so that the root device appears as `/dev/vda` in the guest
@@ -131,6 +152,9 @@ pub struct MMIODeviceManager {
     // devices in the order they were added.
     #[cfg(target_arch = "x86_64")]
     pub(crate) dsdt_data: Vec<u8>,
+    // The maximum length of the descriptor chains processed by the virtio devices registered
+    // from now on, if limited.
+    pub(crate) max_desc_chain_len: Option<u16>,
 }
 
 impl MMIODeviceManager {
@@ -139,8 +163,10 @@ impl MMIODeviceManager {
         MMIODeviceManager {
             bus: crate::devices::Bus::new(),
             id_to_dev_info: HashMap::new(),
+            subscriber_ids: HashMap::new(),
             #[cfg(target_arch = "x86_64")]
             dsdt_data: vec![],
+            max_desc_chain_len: None,
         }
     }
 
@@ -148,19 +174,29 @@ impl MMIODeviceManager {
     fn allocate_mmio_resources(
         &mut self,
         resource_allocator: &mut ResourceAllocator,
+        device_id: &str,
         irq_count: u32,
     ) -> Result<MMIODeviceInfo, MmioError> {
-        let irqs = resource_allocator.allocate_gsi(irq_count)?;
-        let device_info = MMIODeviceInfo {
-            addr: resource_allocator.allocate_mmio_memory(
-                MMIO_LEN,
-                MMIO_LEN,
-                AllocPolicy::FirstMatch,
-            )?,
+        let irqs = resource_allocator
+            .allocate_gsi(irq_count)
+            .map_err(|err| MmioError::IrqExhausted(device_id.to_string(), err))?;
+        let addr = match resource_allocator.allocate_mmio_memory(
+            MMIO_LEN,
+            MMIO_LEN,
+            AllocPolicy::FirstMatch,
+        ) {
+            Ok(addr) => addr,
+            Err(err) => {
+                // The IRQs are of no use without an address range, give them back.
+                resource_allocator.free_gsi(&irqs)?;
+                return Err(MmioError::AddressSpaceExhausted(device_id.to_string(), err));
+            }
+        };
+        Ok(MMIODeviceInfo {
+            addr,
             len: MMIO_LEN,
             irqs,
-        };
-        Ok(device_info)
+        })
     }
 
     /// Register a device at some MMIO address.
@@ -172,7 +208,7 @@ impl MMIODeviceManager {
     ) -> Result<(), MmioError> {
         self.bus
             .insert(device, device_info.addr, device_info.len)
-            .map_err(MmioError::BusInsert)?;
+            .map_err(|err| MmioError::BusInsert(identifier.1.clone(), err))?;
         self.id_to_dev_info.insert(identifier, device_info);
         Ok(())
     }
@@ -192,7 +228,13 @@ impl MMIODeviceManager {
         }
         let identifier;
         {
-            let locked_device = mmio_device.locked_device();
+            let mut locked_device = mmio_device.locked_device();
+            if let Some(max_desc_chain_len) = self.max_desc_chain_len {
+                locked_device
+                    .queues_mut()
+                    .iter_mut()
+                    .for_each(|queue| queue.max_chain_len = max_desc_chain_len);
+            }
             identifier = (DeviceType::Virtio(locked_device.device_type()), device_id);
             for (i, queue_evt) in locked_device.queue_events().iter().enumerate() {
                 let io_addr = IoEventAddress::Mmio(
@@ -215,6 +257,114 @@ impl MMIODeviceManager {
         )
     }
 
+    /// Unregister the virtio-over-MMIO device matching `virtio_type` and `device_id`, and free
+    /// the MMIO resources allocated to it, so that they can be used by a later device.
+    ///
+    /// The driver is told that the device can no longer be used through the status register of
+    /// the transport and a configuration change interrupt, and the device is only removed once
+    /// the driver released it, by resetting it or giving up on it. If the driver does not release
+    /// the device within [`DEVICE_RELEASE_TIMEOUT`], the device is kept and
+    /// [`MmioError::DeviceNotReleased`] is returned, so that the removal can be retried, e.g.
+    /// after unbinding the driver in the guest.
+    ///
+    /// The IO events and irqfd of the device are unregistered from KVM before the device is
+    /// removed from the bus, so that its MMIO range and IRQ are only freed once KVM no longer
+    /// signals the device through them. If any of these steps fails, the previous ones are
+    /// undone, so that the device is either fully removed or left as it was.
+    pub fn remove_mmio_virtio(
+        &mut self,
+        vm: &VmFd,
+        resource_allocator: &mut ResourceAllocator,
+        virtio_type: u32,
+        device_id: &str,
+    ) -> Result<(), MmioError> {
+        let identifier = (DeviceType::Virtio(virtio_type), device_id.to_string());
+        let device_info = self
+            .id_to_dev_info
+            .get(&identifier)
+            .ok_or(MmioError::DeviceNotFound)?
+            .clone();
+        let (_, bus_device) = self
+            .bus
+            .get_device(device_info.addr)
+            .ok_or(MmioError::DeviceNotFound)?;
+
+        bus_device
+            .lock()
+            .expect("Poisoned lock")
+            .mmio_transport_mut()
+            .ok_or(MmioError::InvalidDeviceType)?
+            .set_needs_reset();
+        // The driver releases the device from a vCPU thread, which needs the lock of the bus
+        // device to do so.
+        let deadline = Instant::now() + DEVICE_RELEASE_TIMEOUT;
+        while !bus_device
+            .lock()
+            .expect("Poisoned lock")
+            .mmio_transport_ref()
+            .ok_or(MmioError::InvalidDeviceType)?
+            .is_released()
+        {
+            if Instant::now() >= deadline {
+                return Err(MmioError::DeviceNotReleased);
+            }
+            std::thread::sleep(DEVICE_RELEASE_POLL_INTERVAL);
+        }
+
+        {
+            let locked_bus_device = bus_device.lock().expect("Poisoned lock");
+            let locked_device = locked_bus_device
+                .mmio_transport_ref()
+                .ok_or(MmioError::InvalidDeviceType)?
+                .locked_device();
+            let io_addr = IoEventAddress::Mmio(
+                device_info.addr + u64::from(crate::devices::virtio::NOTIFY_REG_OFFSET),
+            );
+            let queue_events = locked_device.queue_events();
+            let reregister_ioevents = |count: usize| {
+                for (i, queue_evt) in queue_events.iter().enumerate().take(count) {
+                    let _ = vm.register_ioevent(queue_evt, &io_addr, u32::try_from(i).unwrap());
+                }
+            };
+            for (i, queue_evt) in queue_events.iter().enumerate() {
+                if let Err(err) =
+                    vm.unregister_ioevent(queue_evt, &io_addr, u32::try_from(i).unwrap())
+                {
+                    reregister_ioevents(i);
+                    return Err(MmioError::UnregisterIoEvent(err));
+                }
+            }
+            let irq_evt = &locked_device.interrupt_trigger().irq_evt;
+            if let Err(err) = vm.unregister_irqfd(irq_evt, device_info.irqs[0]) {
+                reregister_ioevents(queue_events.len());
+                return Err(MmioError::UnregisterIrqFd(err));
+            }
+            let reregister_all = || {
+                let _ = vm.register_irqfd(irq_evt, device_info.irqs[0]);
+                reregister_ioevents(queue_events.len());
+            };
+
+            if let Err(err) = resource_allocator.free_mmio_memory(device_info.addr, device_info.len)
+            {
+                reregister_all();
+                return Err(MmioError::Allocator(err));
+            }
+            if let Err(err) = resource_allocator.free_gsi(&device_info.irqs) {
+                let _ = resource_allocator.allocate_mmio_memory(
+                    device_info.len,
+                    MMIO_LEN,
+                    AllocPolicy::ExactMatch(device_info.addr),
+                );
+                reregister_all();
+                return Err(MmioError::Allocator(err));
+            }
+        }
+
+        self.bus.remove(device_info.addr, device_info.len);
+        self.id_to_dev_info.remove(&identifier);
+        Ok(())
+    }
+
     /// Append a registered virtio-over-MMIO device to the kernel cmdline.
     #[cfg(target_arch = "x86_64")]
     pub fn add_virtio_device_to_cmdline(
@@ -246,7 +396,7 @@ impl MMIODeviceManager {
         mmio_device: MmioTransport,
         _cmdline: &mut kernel_cmdline::Cmdline,
     ) -> Result<MMIODeviceInfo, MmioError> {
-        let device_info = self.allocate_mmio_resources(resource_allocator, 1)?;
+        let device_info = self.allocate_mmio_resources(resource_allocator, &device_id, 1)?;
         self.register_mmio_virtio(vm, device_id, mmio_device, &device_info)?;
         #[cfg(target_arch = "x86_64")]
         {
@@ -278,7 +428,7 @@ impl MMIODeviceManager {
         let device_info = if let Some(device_info) = device_info_opt {
             device_info
         } else {
-            self.allocate_mmio_resources(resource_allocator, 1)?
+            self.allocate_mmio_resources(resource_allocator, &DeviceType::Serial.to_string(), 1)?
         };
 
         vm.register_irqfd(
@@ -327,7 +477,7 @@ impl MMIODeviceManager {
         let device_info = if let Some(device_info) = device_info_opt {
             device_info
         } else {
-            self.allocate_mmio_resources(resource_allocator, 1)?
+            self.allocate_mmio_resources(resource_allocator, &DeviceType::Rtc.to_string(), 1)?
         };
 
         // Create a new identifier for the RTC device.
@@ -340,16 +490,45 @@ impl MMIODeviceManager {
         )
     }
 
+    #[cfg(target_arch = "aarch64")]
+    /// Create and register a MMIO GPIO device at the specified MMIO configuration if
+    /// given as parameter, otherwise allocate a new MMIO resources for it.
+    pub fn register_mmio_gpio(
+        &mut self,
+        vm: &VmFd,
+        resource_allocator: &mut ResourceAllocator,
+        gpio: Gpio,
+        device_info_opt: Option<MMIODeviceInfo>,
+    ) -> Result<(), MmioError> {
+        // Create a new MMIODeviceInfo object on boot path or unwrap the
+        // existing object on restore path.
+        let device_info = if let Some(device_info) = device_info_opt {
+            device_info
+        } else {
+            self.allocate_mmio_resources(resource_allocator, &DeviceType::Gpio.to_string(), 1)?
+        };
+
+        vm.register_irqfd(gpio.interrupt_evt(), device_info.irqs[0])
+            .map_err(MmioError::RegisterIrqFd)?;
+
+        let identifier = (DeviceType::Gpio, DeviceType::Gpio.to_string());
+        self.register_mmio_device(
+            identifier,
+            device_info,
+            Arc::new(Mutex::new(BusDevice::Gpio(gpio))),
+        )
+    }
+
     /// Register a boot timer device.
     pub fn register_mmio_boot_timer(
         &mut self,
         resource_allocator: &mut ResourceAllocator,
         device: BootTimer,
     ) -> Result<(), MmioError> {
+        let identifier = (DeviceType::BootTimer, DeviceType::BootTimer.to_string());
         // Attach a new boot timer device.
-        let device_info = self.allocate_mmio_resources(resource_allocator, 0)?;
+        let device_info = self.allocate_mmio_resources(resource_allocator, &identifier.1, 0)?;
 
-        let identifier = (DeviceType::BootTimer, DeviceType::BootTimer.to_string());
         self.register_mmio_device(
             identifier,
             device_info,
@@ -445,6 +624,67 @@ impl MMIODeviceManager {
         Ok(())
     }
 
+    /// Stop processing the queues of the block devices and complete their in-flight requests.
+    pub fn quiesce_io(&self) {
+        let _: Result<(), MmioError> =
+            self.for_each_virtio_device(|virtio_type, id, _info, dev| {
+                if virtio_type == TYPE_BLOCK {
+                    let mut virtio = dev.lock().expect("Poisoned lock");
+                    if let Some(block) = virtio.as_mut_any().downcast_mut::<Block>() {
+                        info!("quiesce block {}.", id);
+                        block.quiesce_io();
+                    }
+                }
+                Ok(())
+            });
+    }
+
+    /// Resume processing the queues of the block devices after `quiesce_io`.
+    pub fn resume_io(&self) {
+        let _: Result<(), MmioError> =
+            self.for_each_virtio_device(|virtio_type, _id, _info, dev| {
+                if virtio_type == TYPE_BLOCK {
+                    let mut virtio = dev.lock().expect("Poisoned lock");
+                    if let Some(block) = virtio.as_mut_any().downcast_mut::<Block>() {
+                        block.resume_io();
+                    }
+                }
+                Ok(())
+            });
+    }
+
+    /// Stops the watchdog timer while the microVM is paused.
+    pub fn pause_watchdog(&self) {
+        let _: Result<(), MmioError> =
+            self.for_each_virtio_device(|virtio_type, _id, _info, dev| {
+                if virtio_type == TYPE_WATCHDOG {
+                    let mut virtio = dev.lock().expect("Poisoned lock");
+                    virtio
+                        .as_mut_any()
+                        .downcast_mut::<Watchdog>()
+                        .unwrap()
+                        .pause();
+                }
+                Ok(())
+            });
+    }
+
+    /// Restarts the watchdog timer after `pause_watchdog`.
+    pub fn resume_watchdog(&self) {
+        let _: Result<(), MmioError> =
+            self.for_each_virtio_device(|virtio_type, _id, _info, dev| {
+                if virtio_type == TYPE_WATCHDOG {
+                    let mut virtio = dev.lock().expect("Poisoned lock");
+                    virtio
+                        .as_mut_any()
+                        .downcast_mut::<Watchdog>()
+                        .unwrap()
+                        .resume();
+                }
+                Ok(())
+            });
+    }
+
     /// Artificially kick devices as if they had external events.
     pub fn kick_devices(&self) {
         info!("Artificially kick devices.");
@@ -541,6 +781,7 @@ mod tests {
 
     use super::*;
     use crate::devices::virtio::device::{IrqTrigger, VirtioDevice};
+    use crate::devices::virtio::mmio::VIRTIO_MMIO_INT_CONFIG;
     use crate::devices::virtio::queue::Queue;
     use crate::devices::virtio::ActivateError;
     use crate::test_utils::multi_region_mem;
@@ -685,6 +926,107 @@ mod tests {
             .unwrap();
     }
 
+    #[test]
+    fn test_remove_virtio_device() {
+        let start_addr1 = GuestAddress(0x0);
+        let start_addr2 = GuestAddress(0x1000);
+        let guest_mem = multi_region_mem(&[(start_addr1, 0x1000), (start_addr2, 0x1000)]);
+        let mut vm = Vm::new(vec![]).unwrap();
+        vm.memory_init(&guest_mem, false).unwrap();
+        let mut device_manager = MMIODeviceManager::new();
+        let mut resource_allocator = ResourceAllocator::new().unwrap();
+
+        let mut cmdline = kernel_cmdline::Cmdline::new(4096).unwrap();
+        #[cfg(target_arch = "x86_64")]
+        builder::setup_interrupt_controller(&mut vm).unwrap();
+        #[cfg(target_arch = "aarch64")]
+        builder::setup_interrupt_controller(&mut vm, 1).unwrap();
+
+        let dummy = Arc::new(Mutex::new(DummyDevice::new()));
+        device_manager
+            .register_virtio_test_device(
+                vm.fd(),
+                guest_mem.clone(),
+                &mut resource_allocator,
+                dummy,
+                &mut cmdline,
+                "dummy",
+            )
+            .unwrap();
+        let device_info = device_manager.id_to_dev_info[&(Virtio(0), "dummy".to_string())].clone();
+        let mmio_device = device_manager
+            .get_device(Virtio(0), "dummy")
+            .unwrap()
+            .lock()
+            .unwrap()
+            .mmio_transport_ref()
+            .unwrap()
+            .device();
+
+        // Unknown devices cannot be removed.
+        assert!(matches!(
+            device_manager.remove_mmio_virtio(vm.fd(), &mut resource_allocator, 0, "unknown"),
+            Err(MmioError::DeviceNotFound)
+        ));
+
+        // A device that is still driven is kept, until the driver resets it.
+        let write_status = |device_manager: &MMIODeviceManager, status: u32| {
+            device_manager
+                .get_device(Virtio(0), "dummy")
+                .unwrap()
+                .lock()
+                .unwrap()
+                .mmio_transport_mut()
+                .unwrap()
+                .bus_write(0x70, &status.to_le_bytes());
+        };
+        write_status(&device_manager, 1);
+        assert!(matches!(
+            device_manager.remove_mmio_virtio(vm.fd(), &mut resource_allocator, 0, "dummy"),
+            Err(MmioError::DeviceNotReleased)
+        ));
+        assert!(device_manager.get_device(Virtio(0), "dummy").is_some());
+        assert!(device_manager.bus.get_device(device_info.addr).is_some());
+        write_status(&device_manager, 0);
+
+        device_manager
+            .remove_mmio_virtio(vm.fd(), &mut resource_allocator, 0, "dummy")
+            .unwrap();
+        assert!(device_manager.get_device(Virtio(0), "dummy").is_none());
+        assert!(device_manager.bus.get_device(device_info.addr).is_none());
+        // The driver is notified through a configuration change interrupt.
+        assert_ne!(
+            mmio_device
+                .lock()
+                .unwrap()
+                .interrupt_trigger()
+                .irq_status
+                .load(std::sync::atomic::Ordering::SeqCst)
+                & VIRTIO_MMIO_INT_CONFIG,
+            0
+        );
+        assert!(matches!(
+            device_manager.remove_mmio_virtio(vm.fd(), &mut resource_allocator, 0, "dummy"),
+            Err(MmioError::DeviceNotFound)
+        ));
+
+        // The MMIO address range and IRQ of the removed device can be used by a new device.
+        device_manager
+            .register_virtio_test_device(
+                vm.fd(),
+                guest_mem,
+                &mut resource_allocator,
+                Arc::new(Mutex::new(DummyDevice::new())),
+                &mut cmdline,
+                "dummy2",
+            )
+            .unwrap();
+        assert_eq!(
+            device_manager.id_to_dev_info[&(Virtio(0), "dummy2".to_string())],
+            device_info
+        );
+    }
+
     #[test]
     fn test_register_too_many_devices() {
         let start_addr1 = GuestAddress(0x0);
@@ -727,11 +1069,65 @@ mod tests {
                     )
                     .unwrap_err()
             ),
-            "Failed to allocate requested resource: The requested resource is not available."
-                .to_string()
+            "No IRQ left for device dummy2: The requested resource is not available.".to_string()
+        );
+    }
+
+    #[test]
+    fn test_register_without_mmio_address_space() {
+        let mut device_manager = MMIODeviceManager::new();
+        let mut resource_allocator = ResourceAllocator::new().unwrap();
+        // Exhaust the MMIO address space.
+        resource_allocator
+            .allocate_mmio_memory(
+                crate::arch::MMIO_MEM_SIZE,
+                MMIO_LEN,
+                AllocPolicy::FirstMatch,
+            )
+            .unwrap();
+
+        assert!(matches!(
+            device_manager.allocate_mmio_resources(&mut resource_allocator, "foo", 1),
+            Err(MmioError::AddressSpaceExhausted(id, vm_allocator::Error::ResourceNotAvailable))
+                if id == "foo"
+        ));
+        // The IRQ allocated to the device is given back.
+        assert_eq!(
+            resource_allocator.allocate_gsi(1).unwrap(),
+            vec![crate::arch::IRQ_BASE]
         );
     }
 
+    #[test]
+    fn test_register_bus_conflict() {
+        let mut device_manager = MMIODeviceManager::new();
+        let mut resource_allocator = ResourceAllocator::new().unwrap();
+        let device_info = device_manager
+            .allocate_mmio_resources(&mut resource_allocator, "foo", 0)
+            .unwrap();
+
+        device_manager
+            .register_mmio_device(
+                (DeviceType::BootTimer, "foo".to_string()),
+                device_info.clone(),
+                Arc::new(Mutex::new(BusDevice::BootTimer(BootTimer::new(
+                    Default::default(),
+                )))),
+            )
+            .unwrap();
+        // A second device cannot be registered over the address range of the first one.
+        assert!(matches!(
+            device_manager.register_mmio_device(
+                (DeviceType::BootTimer, "bar".to_string()),
+                device_info,
+                Arc::new(Mutex::new(BusDevice::BootTimer(BootTimer::new(
+                    Default::default(),
+                )))),
+            ),
+            Err(MmioError::BusInsert(id, crate::devices::BusError::Overlap)) if id == "bar"
+        ));
+    }
+
     #[test]
     fn test_dummy_device() {
         let dummy = DummyDevice::new();
@@ -821,11 +1217,11 @@ mod tests {
         let mut device_manager = MMIODeviceManager::new();
         let mut resource_allocator = ResourceAllocator::new().unwrap();
         let device_info = device_manager
-            .allocate_mmio_resources(&mut resource_allocator, 0)
+            .allocate_mmio_resources(&mut resource_allocator, "foo", 0)
             .unwrap();
         assert_eq!(device_info.irqs.len(), 0);
         let device_info = device_manager
-            .allocate_mmio_resources(&mut resource_allocator, 1)
+            .allocate_mmio_resources(&mut resource_allocator, "foo", 1)
             .unwrap();
         assert_eq!(device_info.irqs[0], crate::arch::IRQ_BASE);
         assert_eq!(
@@ -834,17 +1230,18 @@ mod tests {
                 device_manager
                     .allocate_mmio_resources(
                         &mut resource_allocator,
+                        "foo",
                         crate::arch::IRQ_MAX - crate::arch::IRQ_BASE + 1
                     )
                     .unwrap_err()
             ),
-            "Failed to allocate requested resource: The requested resource is not available."
-                .to_string()
+            "No IRQ left for device foo: The requested resource is not available.".to_string()
         );
 
         let device_info = device_manager
             .allocate_mmio_resources(
                 &mut resource_allocator,
+                "foo",
                 crate::arch::IRQ_MAX - crate::arch::IRQ_BASE - 1,
             )
             .unwrap();
@@ -853,14 +1250,13 @@ mod tests {
             format!(
                 "{}",
                 device_manager
-                    .allocate_mmio_resources(&mut resource_allocator, 2)
+                    .allocate_mmio_resources(&mut resource_allocator, "foo", 2)
                     .unwrap_err()
             ),
-            "Failed to allocate requested resource: The requested resource is not available."
-                .to_string()
+            "No IRQ left for device foo: The requested resource is not available.".to_string()
         );
         device_manager
-            .allocate_mmio_resources(&mut resource_allocator, 0)
+            .allocate_mmio_resources(&mut resource_allocator, "foo", 0)
             .unwrap();
     }
 }
@@ -3,6 +3,7 @@
 
 //! Provides functionality for saving/restoring the MMIO device manager and its devices.
 
+use std::collections::BTreeSet;
 use std::fmt::{self, Debug};
 use std::sync::{Arc, Mutex};
 
@@ -11,6 +12,7 @@ use kvm_ioctls::VmFd;
 use log::{error, warn};
 use serde::{Deserialize, Serialize};
 use vm_allocator::AllocPolicy;
+use vmm_sys_util::eventfd::EventFd;
 
 use super::acpi::ACPIDeviceManager;
 use super::mmio::*;
@@ -18,6 +20,10 @@ use super::resources::ResourceAllocator;
 #[cfg(target_arch = "aarch64")]
 use crate::arch::DeviceType;
 use crate::devices::acpi::vmgenid::{VMGenIDState, VMGenIdConstructorArgs, VmGenId, VmGenIdError};
+#[cfg(target_arch = "aarch64")]
+use crate::devices::legacy::gpio_pl061::GpioState;
+#[cfg(target_arch = "aarch64")]
+use crate::devices::legacy::Gpio;
 use crate::devices::virtio::balloon::persist::{BalloonConstructorArgs, BalloonState};
 use crate::devices::virtio::balloon::{Balloon, BalloonError};
 use crate::devices::virtio::block::device::Block;
@@ -40,7 +46,11 @@ use crate::devices::virtio::vsock::persist::{
 use crate::devices::virtio::vsock::{
     Vsock, VsockError, VsockUnixBackend, VsockUnixBackendError, TYPE_VSOCK,
 };
-use crate::devices::virtio::{TYPE_BALLOON, TYPE_BLOCK, TYPE_NET, TYPE_RNG};
+use crate::devices::virtio::watchdog::persist::{
+    WatchdogConstructorArgs, WatchdogPersistError as WatchdogError, WatchdogState,
+};
+use crate::devices::virtio::watchdog::Watchdog;
+use crate::devices::virtio::{TYPE_BALLOON, TYPE_BLOCK, TYPE_NET, TYPE_RNG, TYPE_WATCHDOG};
 use crate::mmds::data_store::MmdsVersion;
 use crate::resources::{ResourcesError, VmResources};
 use crate::snapshot::Persist;
@@ -72,6 +82,10 @@ pub enum DevicePersistError {
     MmdsConfig(#[from] MmdsConfigError),
     /// Entropy: {0}
     Entropy(#[from] EntropyError),
+    /// Watchdog: {0}
+    Watchdog(#[from] WatchdogError),
+    /// Could not clone the exit event of the watchdog: {0}
+    WatchdogExitEvent(std::io::Error),
     /// Resource misconfiguration: {0}. Is the snapshot file corrupted?
     ResourcesError(#[from] ResourcesError),
 }
@@ -141,6 +155,19 @@ pub struct ConnectedEntropyState {
     pub device_info: MMIODeviceInfo,
 }
 
+/// Holds the state of a watchdog device connected to the MMIO space.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct ConnectedWatchdogState {
+    /// Device identifier.
+    pub device_id: String,
+    /// Device state.
+    pub device_state: WatchdogState,
+    /// Mmio transport state.
+    pub transport_state: MmioTransportState,
+    /// VmmResources.
+    pub device_info: MMIODeviceInfo,
+}
+
 /// Holds the state of a legacy device connected to the MMIO space.
 #[cfg(target_arch = "aarch64")]
 #[derive(Debug, Clone, Serialize, Deserialize)]
@@ -149,6 +176,8 @@ pub struct ConnectedLegacyState {
     pub type_: DeviceType,
     /// VmmResources.
     pub device_info: MMIODeviceInfo,
+    /// State of the GPIO controller registers, only set for the GPIO controller.
+    pub gpio_state: Option<GpioState>,
 }
 
 /// Holds the MMDS data store version.
@@ -194,6 +223,38 @@ pub struct DeviceStates {
     pub mmds_version: Option<MmdsVersionState>,
     /// Entropy device state.
     pub entropy_device: Option<ConnectedEntropyState>,
+    /// Watchdog device state.
+    pub watchdog_device: Option<ConnectedWatchdogState>,
+}
+
+impl DeviceStates {
+    /// Identifiers of the saved devices, e.g. `block/rootfs` for the block device `rootfs`.
+    pub fn device_ids(&self) -> BTreeSet<String> {
+        let mut ids = BTreeSet::new();
+        ids.extend(
+            self.block_devices
+                .iter()
+                .map(|block| format!("block/{}", block.device_id)),
+        );
+        ids.extend(
+            self.net_devices
+                .iter()
+                .map(|net| format!("net/{}", net.device_id)),
+        );
+        if self.vsock_device.is_some() {
+            ids.insert(String::from("vsock"));
+        }
+        if self.balloon_device.is_some() {
+            ids.insert(String::from("balloon"));
+        }
+        if self.entropy_device.is_some() {
+            ids.insert(String::from("entropy"));
+        }
+        if self.watchdog_device.is_some() {
+            ids.insert(String::from("watchdog"));
+        }
+        ids
+    }
 }
 
 /// A type used to extract the concrete `Arc<Mutex<T>>` for each of the device
@@ -205,6 +266,7 @@ pub enum SharedDeviceType {
     Balloon(Arc<Mutex<Balloon>>),
     Vsock(Arc<Mutex<Vsock<VsockUnixBackend>>>),
     Entropy(Arc<Mutex<Entropy>>),
+    Watchdog(Arc<Mutex<Watchdog>>),
 }
 
 pub struct MMIODevManagerConstructorArgs<'a> {
@@ -214,6 +276,7 @@ pub struct MMIODevManagerConstructorArgs<'a> {
     pub resource_allocator: &'a mut ResourceAllocator,
     pub vm_resources: &'a mut VmResources,
     pub instance_id: &'a str,
+    pub exit_evt: &'a EventFd,
 }
 impl fmt::Debug for MMIODevManagerConstructorArgs<'_> {
     fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
@@ -224,6 +287,7 @@ impl fmt::Debug for MMIODevManagerConstructorArgs<'_> {
             .field("for_each_restored_device", &"?")
             .field("vm_resources", &self.vm_resources)
             .field("instance_id", &self.instance_id)
+            .field("exit_evt", &self.exit_evt)
             .finish()
     }
 }
@@ -277,12 +341,10 @@ impl<'a> Persist<'a> for ACPIDeviceManager {
     }
 }
 
-impl<'a> Persist<'a> for MMIODeviceManager {
-    type State = DeviceStates;
-    type ConstructorArgs = MMIODevManagerConstructorArgs<'a>;
-    type Error = DevicePersistError;
-
-    fn save(&self) -> Self::State {
+impl MMIODeviceManager {
+    /// Saves the states of the devices. The devices are only prepared for a snapshot if
+    /// `for_snapshot` is set: the block devices are flushed and the vsock connections are reset.
+    pub fn save_device_states(&self, for_snapshot: bool) -> DeviceStates {
         let mut states = DeviceStates::default();
         let _: Result<(), ()> = self.for_each_device(|devtype, devid, device_info, bus_dev| {
             if *devtype == crate::arch::DeviceType::BootTimer {
@@ -292,10 +354,19 @@ impl<'a> Persist<'a> for MMIODeviceManager {
 
             #[cfg(target_arch = "aarch64")]
             {
-                if *devtype == DeviceType::Serial || *devtype == DeviceType::Rtc {
+                if *devtype == DeviceType::Serial
+                    || *devtype == DeviceType::Rtc
+                    || *devtype == DeviceType::Gpio
+                {
+                    let gpio_state = bus_dev
+                        .lock()
+                        .expect("Poisoned lock")
+                        .gpio_ref()
+                        .map(|gpio| gpio.save());
                     states.legacy_devices.push(ConnectedLegacyState {
                         type_: *devtype,
                         device_info: device_info.clone(),
+                        gpio_state,
                     });
                     return Ok(());
                 }
@@ -333,7 +404,9 @@ impl<'a> Persist<'a> for MMIODeviceManager {
                              snapshotting yet"
                         );
                     } else {
-                        block.prepare_save();
+                        if for_snapshot {
+                            block.prepare_save();
+                        }
                         states.block_devices.push(ConnectedBlockState {
                             device_id: devid.clone(),
                             device_state: block.save(),
@@ -367,7 +440,7 @@ impl<'a> Persist<'a> for MMIODeviceManager {
 
                     // Send Transport event to reset connections if device
                     // is activated.
-                    if vsock.is_activated() {
+                    if for_snapshot && vsock.is_activated() {
                         vsock.send_transport_reset_event().unwrap_or_else(|err| {
                             error!("Failed to send reset transport event: {:?}", err);
                         });
@@ -400,6 +473,19 @@ impl<'a> Persist<'a> for MMIODeviceManager {
                         device_info: device_info.clone(),
                     });
                 }
+                TYPE_WATCHDOG => {
+                    let watchdog = locked_device
+                        .as_mut_any()
+                        .downcast_mut::<Watchdog>()
+                        .unwrap();
+
+                    states.watchdog_device = Some(ConnectedWatchdogState {
+                        device_id: devid.clone(),
+                        device_state: watchdog.save(),
+                        transport_state,
+                        device_info: device_info.clone(),
+                    });
+                }
                 _ => unreachable!(),
             };
 
@@ -407,6 +493,16 @@ impl<'a> Persist<'a> for MMIODeviceManager {
         });
         states
     }
+}
+
+impl<'a> Persist<'a> for MMIODeviceManager {
+    type State = DeviceStates;
+    type ConstructorArgs = MMIODevManagerConstructorArgs<'a>;
+    type Error = DevicePersistError;
+
+    fn save(&self) -> Self::State {
+        self.save_device_states(true)
+    }
 
     fn restore(
         constructor_args: Self::ConstructorArgs,
@@ -424,6 +520,9 @@ impl<'a> Persist<'a> for MMIODeviceManager {
                         constructor_args.event_manager,
                         std::io::stdin(),
                         std::io::stdout(),
+                        crate::builder::serial_output_rate_limiter(
+                            &constructor_args.vm_resources.vm_config,
+                        ),
                     )?;
 
                     constructor_args
@@ -464,6 +563,26 @@ impl<'a> Persist<'a> for MMIODeviceManager {
                         Some(state.device_info.clone()),
                     )?;
                 }
+                if state.type_ == DeviceType::Gpio {
+                    let gpio = Gpio::restore((), &state.gpio_state.clone().unwrap_or_default())
+                        .map_err(crate::VmmError::EventFd)?;
+                    constructor_args
+                        .resource_allocator
+                        .allocate_mmio_memory(
+                            MMIO_LEN,
+                            MMIO_LEN,
+                            AllocPolicy::ExactMatch(state.device_info.addr),
+                        )
+                        .map_err(|e| {
+                            DevicePersistError::DeviceManager(super::mmio::MmioError::Allocator(e))
+                        })?;
+                    dev_manager.register_mmio_gpio(
+                        vm,
+                        constructor_args.resource_allocator,
+                        gpio,
+                        Some(state.device_info.clone()),
+                    )?;
+                }
             }
         }
 
@@ -475,6 +594,7 @@ impl<'a> Persist<'a> for MMIODeviceManager {
                                   device_info: &MMIODeviceInfo,
                                   event_manager: &mut EventManager|
          -> Result<(), Self::Error> {
+            let device_type = device.lock().expect("Poisoned lock").device_type();
             let restore_args = MmioTransportConstructorArgs {
                 mem: mem.clone(),
                 device,
@@ -506,7 +626,10 @@ impl<'a> Persist<'a> for MMIODeviceManager {
 
             dev_manager.register_mmio_virtio(vm, id.clone(), mmio_transport, device_info)?;
 
-            event_manager.add_subscriber(as_subscriber);
+            let subscriber_id = event_manager.add_subscriber(as_subscriber);
+            dev_manager
+                .subscriber_ids
+                .insert((DeviceType::Virtio(device_type), id.clone()), subscriber_id);
             Ok(())
         };
 
@@ -648,6 +771,34 @@ impl<'a> Persist<'a> for MMIODeviceManager {
             )?;
         }
 
+        if let Some(watchdog_state) = &state.watchdog_device {
+            // The watchdog uses the Vmm exit event to reset or stop the microVM on expiry.
+            let exit_evt = constructor_args
+                .exit_evt
+                .try_clone()
+                .map_err(DevicePersistError::WatchdogExitEvent)?;
+            let ctor_args = WatchdogConstructorArgs::new(mem.clone(), exit_evt);
+
+            let device = Arc::new(Mutex::new(Watchdog::restore(
+                ctor_args,
+                &watchdog_state.device_state,
+            )?));
+
+            constructor_args
+                .vm_resources
+                .update_from_restored_device(SharedDeviceType::Watchdog(device.clone()))?;
+
+            restore_helper(
+                device.clone(),
+                false,
+                device,
+                &watchdog_state.device_id,
+                &watchdog_state.transport_state,
+                &watchdog_state.device_info,
+                constructor_args.event_manager,
+            )?;
+        }
+
         Ok(dev_manager)
     }
 }
@@ -665,6 +816,7 @@ mod tests {
     use crate::vmm_config::entropy::EntropyDeviceConfig;
     use crate::vmm_config::net::NetworkInterfaceConfig;
     use crate::vmm_config::vsock::VsockDeviceConfig;
+    use crate::vmm_config::watchdog::{WatchdogAction, WatchdogDeviceConfig};
 
     impl PartialEq for ConnectedBalloonState {
         fn eq(&self, other: &ConnectedBalloonState) -> bool {
@@ -694,12 +846,20 @@ mod tests {
         }
     }
 
+    impl PartialEq for ConnectedWatchdogState {
+        fn eq(&self, other: &ConnectedWatchdogState) -> bool {
+            // Actual device state equality is checked by the device's tests.
+            self.transport_state == other.transport_state && self.device_info == other.device_info
+        }
+    }
+
     impl PartialEq for DeviceStates {
         fn eq(&self, other: &DeviceStates) -> bool {
             self.balloon_device == other.balloon_device
                 && self.block_devices == other.block_devices
                 && self.net_devices == other.net_devices
                 && self.vsock_device == other.vsock_device
+                && self.watchdog_device == other.watchdog_device
         }
     }
 
@@ -749,6 +909,7 @@ mod tests {
                 amount_mib: 123,
                 deflate_on_oom: false,
                 stats_polling_interval_s: 1,
+                free_page_reporting: false,
             };
             insert_balloon_device(&mut vmm, &mut cmdline, &mut event_manager, balloon_cfg);
             // Add a block device.
@@ -769,6 +930,7 @@ mod tests {
                 guest_mac: None,
                 rx_rate_limiter: None,
                 tx_rate_limiter: None,
+                mtu: None,
             };
             insert_net_device_with_mmds(
                 &mut vmm,
@@ -788,6 +950,12 @@ mod tests {
             // Add an entropy device.
             let entropy_config = EntropyDeviceConfig::default();
             insert_entropy_device(&mut vmm, &mut cmdline, &mut event_manager, entropy_config);
+            // Add a watchdog device.
+            let watchdog_config = WatchdogDeviceConfig {
+                timeout_s: 30,
+                action: WatchdogAction::Stop,
+            };
+            insert_watchdog_device(&mut vmm, &mut cmdline, &mut event_manager, watchdog_config);
 
             Snapshot::serialize(&mut buf.as_mut_slice(), &vmm.mmio_device_manager.save()).unwrap();
 
@@ -807,6 +975,7 @@ mod tests {
             resource_allocator: &mut resource_allocator,
             vm_resources,
             instance_id: "microvm-id",
+            exit_evt: &vmm.vcpus_exit_evt,
         };
         let restored_dev_manager =
             MMIODeviceManager::restore(restore_args, &device_states).unwrap();
@@ -816,7 +985,8 @@ mod tests {
   "balloon": {{
     "amount_mib": 123,
     "deflate_on_oom": false,
-    "stats_polling_interval_s": 1
+    "stats_polling_interval_s": 1,
+    "free_page_reporting": false
   }},
   "drives": [
     {{
@@ -868,6 +1038,10 @@ mod tests {
   }},
   "entropy": {{
     "rate_limiter": null
+  }},
+  "watchdog": {{
+    "timeout_s": 30,
+    "action": "Stop"
   }}
 }}"#,
             _block_files.last().unwrap().as_path().to_str().unwrap(),
@@ -887,6 +1061,11 @@ mod tests {
         assert_eq!(device_states.mmds_version.unwrap(), MmdsVersion::V2.into());
 
         assert_eq!(restored_dev_manager, original_mmio_device_manager);
+        // The restore path checks that all the devices of the snapshot were restored.
+        assert_eq!(
+            vm_resources.snapshot_device_ids(),
+            device_states.device_ids()
+        );
         assert_eq!(
             expected_vm_resources,
             serde_json::to_string_pretty(&VmmConfig::from(&*vm_resources)).unwrap()
@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 pub use vm_allocator::AllocPolicy;
-use vm_allocator::{AddressAllocator, IdAllocator};
+use vm_allocator::{AddressAllocator, IdAllocator, RangeInclusive};
 
 use crate::arch;
 
@@ -57,6 +57,16 @@ impl ResourceAllocator {
         Ok(gsis)
     }
 
+    /// Free GSIs previously allocated with [`Self::allocate_gsi`]
+    ///
+    /// # Arguments
+    ///
+    /// * `gsis` - The GSIs to free
+    pub fn free_gsi(&mut self, gsis: &[u32]) -> Result<(), vm_allocator::Error> {
+        gsis.iter()
+            .try_for_each(|&gsi| self.gsi_allocator.free_id(gsi).map(|_| ()))
+    }
+
     /// Allocate a memory range in MMIO address space
     ///
     /// If it succeeds, it returns the first address of the allocated range
@@ -75,6 +85,18 @@ impl ResourceAllocator {
         Ok(self.mmio_memory.allocate(size, alignment, policy)?.start())
     }
 
+    /// Free a memory range in MMIO address space previously allocated with
+    /// [`Self::allocate_mmio_memory`]
+    ///
+    /// # Arguments
+    ///
+    /// * `addr` - The first address of the range
+    /// * `size` - The size in bytes of the range
+    pub fn free_mmio_memory(&mut self, addr: u64, size: u64) -> Result<(), vm_allocator::Error> {
+        self.mmio_memory
+            .free(&RangeInclusive::new(addr, addr + size - 1)?)
+    }
+
     /// Allocate a memory range for system data
     ///
     /// If it succeeds, it returns the first address of the allocated range
@@ -99,7 +121,7 @@ impl ResourceAllocator {
 
 #[cfg(test)]
 mod tests {
-    use super::ResourceAllocator;
+    use super::{AllocPolicy, ResourceAllocator};
     use crate::arch;
 
     const MAX_IRQS: u32 = arch::IRQ_MAX - arch::IRQ_BASE + 1;
@@ -142,4 +164,28 @@ mod tests {
             assert_eq!(allocator.allocate_gsi(1), Ok(vec![i]));
         }
     }
+
+    #[test]
+    fn test_free_resources() {
+        let mut allocator = ResourceAllocator::new().unwrap();
+        let gsis = allocator.allocate_gsi(2).unwrap();
+        let addr = allocator
+            .allocate_mmio_memory(0x1000, 0x1000, AllocPolicy::FirstMatch)
+            .unwrap();
+
+        // Freed resources can be allocated again.
+        allocator.free_gsi(&gsis).unwrap();
+        allocator.free_mmio_memory(addr, 0x1000).unwrap();
+        assert_eq!(allocator.allocate_gsi(2).unwrap(), gsis);
+        assert_eq!(
+            allocator.allocate_mmio_memory(0x1000, 0x1000, AllocPolicy::FirstMatch),
+            Ok(addr)
+        );
+
+        // Resources cannot be freed twice.
+        allocator.free_gsi(&gsis).unwrap();
+        allocator.free_gsi(&gsis).unwrap_err();
+        allocator.free_mmio_memory(addr, 0x1000).unwrap();
+        allocator.free_mmio_memory(addr, 0x1000).unwrap_err();
+    }
 }
@@ -53,7 +53,7 @@ pub struct Bus {
 use event_manager::{EventOps, Events, MutEventSubscriber};
 
 #[cfg(target_arch = "aarch64")]
-use super::legacy::RTCDevice;
+use super::legacy::{Gpio, RTCDevice};
 use super::legacy::{I8042Device, SerialDevice};
 use super::pseudo::BootTimer;
 use super::virtio::mmio::MmioTransport;
@@ -63,6 +63,8 @@ pub enum BusDevice {
     I8042Device(I8042Device),
     #[cfg(target_arch = "aarch64")]
     RTCDevice(RTCDevice),
+    #[cfg(target_arch = "aarch64")]
+    Gpio(Gpio),
     BootTimer(BootTimer),
     MmioTransport(MmioTransport),
     Serial(SerialDevice<std::io::Stdin>),
@@ -115,6 +117,13 @@ impl BusDevice {
             _ => None,
         }
     }
+    #[cfg(target_arch = "aarch64")]
+    pub fn gpio_ref(&self) -> Option<&Gpio> {
+        match self {
+            Self::Gpio(x) => Some(x),
+            _ => None,
+        }
+    }
     pub fn boot_timer_ref(&self) -> Option<&BootTimer> {
         match self {
             Self::BootTimer(x) => Some(x),
@@ -147,6 +156,13 @@ impl BusDevice {
             _ => None,
         }
     }
+    #[cfg(target_arch = "aarch64")]
+    pub fn gpio_mut(&mut self) -> Option<&mut Gpio> {
+        match self {
+            Self::Gpio(x) => Some(x),
+            _ => None,
+        }
+    }
     pub fn boot_timer_mut(&mut self) -> Option<&mut BootTimer> {
         match self {
             Self::BootTimer(x) => Some(x),
@@ -171,6 +187,8 @@ impl BusDevice {
             Self::I8042Device(x) => x.bus_read(offset, data),
             #[cfg(target_arch = "aarch64")]
             Self::RTCDevice(x) => x.bus_read(offset, data),
+            #[cfg(target_arch = "aarch64")]
+            Self::Gpio(x) => x.bus_read(offset, data),
             Self::BootTimer(x) => x.bus_read(offset, data),
             Self::MmioTransport(x) => x.bus_read(offset, data),
             Self::Serial(x) => x.bus_read(offset, data),
@@ -186,6 +204,8 @@ impl BusDevice {
             Self::I8042Device(x) => x.bus_write(offset, data),
             #[cfg(target_arch = "aarch64")]
             Self::RTCDevice(x) => x.bus_write(offset, data),
+            #[cfg(target_arch = "aarch64")]
+            Self::Gpio(x) => x.bus_write(offset, data),
             Self::BootTimer(x) => x.bus_write(offset, data),
             Self::MmioTransport(x) => x.bus_write(offset, data),
             Self::Serial(x) => x.bus_write(offset, data),
@@ -276,6 +296,11 @@ impl Bus {
         Ok(())
     }
 
+    /// Removes the device inserted at the given address space.
+    pub fn remove(&mut self, base: u64, len: u64) -> Option<Arc<Mutex<BusDevice>>> {
+        self.devices.remove(&BusRange(base, len))
+    }
+
     /// Reads data from the device that owns the range containing `addr` and puts it into `data`.
     ///
     /// Returns true on success, otherwise `data` is untouched.
@@ -0,0 +1,362 @@
+// Copyright 2024 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Minimal emulation of the ARM PrimeCell PL061 GPIO controller.
+//!
+//! The device is only used to expose a power button to the guest, which is wired through the
+//! `gpio-keys` driver to the `KEY_POWER` key code.
+
+use std::io;
+
+use serde::{Deserialize, Serialize};
+use vm_superio::Trigger;
+use vmm_sys_util::eventfd::EventFd;
+
+use super::EventFdTrigger;
+use crate::logger::warn;
+use crate::snapshot::Persist;
+
+/// GPIO line the power button is wired to.
+pub const POWER_KEY_LINE: u32 = 3;
+
+const GPIO_LINES: u32 = 8;
+
+// Register offsets, as described in the PL061 Technical Reference Manual.
+const GPIODATA: u64 = 0x000;
+const GPIODATA_END: u64 = 0x3fc;
+const GPIODIR: u64 = 0x400;
+const GPIOIS: u64 = 0x404;
+const GPIOIBE: u64 = 0x408;
+const GPIOIEV: u64 = 0x40c;
+const GPIOIE: u64 = 0x410;
+const GPIORIS: u64 = 0x414;
+const GPIOMIS: u64 = 0x418;
+const GPIOIC: u64 = 0x41c;
+const GPIOAFSEL: u64 = 0x420;
+const GPIO_ID_LOW: u64 = 0xfe0;
+const GPIO_ID_HIGH: u64 = 0xffc;
+
+// Peripheral and PrimeCell identification values, one byte per 32-bit register.
+const GPIO_ID: [u8; 8] = [0x61, 0x10, 0x04, 0x00, 0x0d, 0xf0, 0x05, 0xb1];
+
+/// Emulated PL061 GPIO controller.
+#[derive(Debug)]
+pub struct Gpio {
+    data: u32,
+    dir: u32,
+    is: u32,
+    ibe: u32,
+    iev: u32,
+    ie: u32,
+    ris: u32,
+    afsel: u32,
+    // Lines of the keys pressed and not released yet.
+    pressed: u32,
+    interrupt_evt: EventFdTrigger,
+}
+
+/// State of the registers of the GPIO controller.
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct GpioState {
+    data: u32,
+    dir: u32,
+    is: u32,
+    ibe: u32,
+    iev: u32,
+    ie: u32,
+    ris: u32,
+    afsel: u32,
+    pressed: u32,
+}
+
+impl Gpio {
+    /// Creates a GPIO controller signaling its interrupt through `interrupt_evt`.
+    pub fn new(interrupt_evt: EventFdTrigger) -> Self {
+        Gpio {
+            data: 0,
+            dir: 0,
+            is: 0,
+            ibe: 0,
+            iev: 0,
+            ie: 0,
+            ris: 0,
+            afsel: 0,
+            pressed: 0,
+            interrupt_evt,
+        }
+    }
+
+    /// The event signaled when the GPIO controller raises its interrupt.
+    pub fn interrupt_evt(&self) -> &EventFdTrigger {
+        &self.interrupt_evt
+    }
+
+    /// Presses the key wired to `line`, raising the GPIO interrupt if the guest enabled it.
+    ///
+    /// The key is released once the guest reads the state of the line, which the `gpio-keys`
+    /// driver does when handling the interrupt. The release raises the interrupt again if the
+    /// guest detects both edges on the line.
+    pub fn trigger_key(&mut self, line: u32) -> io::Result<()> {
+        if line >= GPIO_LINES {
+            return Err(io::Error::from(io::ErrorKind::InvalidInput));
+        }
+        let mask = 1 << line;
+        // Lines configured as outputs are driven by the guest.
+        if self.dir & mask != 0 {
+            return Ok(());
+        }
+
+        self.data |= mask;
+        self.pressed |= mask;
+        self.raise_interrupt(mask)
+    }
+
+    fn raise_interrupt(&mut self, mask: u32) -> io::Result<()> {
+        self.ris |= mask;
+        if self.ie & mask != 0 {
+            self.interrupt_evt.trigger()?;
+        }
+        Ok(())
+    }
+
+    // Releases the pressed keys wired to the lines in `mask`.
+    fn release_keys(&mut self, mask: u32) {
+        let released = self.pressed & mask;
+        if released == 0 {
+            return;
+        }
+        self.pressed &= !released;
+        self.data &= !released;
+        if let Err(err) = self.raise_interrupt(released & self.ibe) {
+            warn!("Failed to signal the GPIO key release: {}", err);
+        }
+    }
+
+    fn read(&mut self, offset: u64) -> u32 {
+        match offset {
+            // Bits [9:2] of the address mask the data bits being read.
+            GPIODATA..=GPIODATA_END => {
+                let mask = (offset >> 2) as u32;
+                let value = self.data & mask;
+                self.release_keys(mask);
+                value
+            }
+            GPIODIR => self.dir,
+            GPIOIS => self.is,
+            GPIOIBE => self.ibe,
+            GPIOIEV => self.iev,
+            GPIOIE => self.ie,
+            GPIORIS => self.ris,
+            GPIOMIS => self.ris & self.ie,
+            GPIOAFSEL => self.afsel,
+            GPIO_ID_LOW..=GPIO_ID_HIGH => {
+                u32::from(GPIO_ID[((offset - GPIO_ID_LOW) >> 2) as usize])
+            }
+            _ => {
+                warn!("Guest read at invalid GPIO offset: {:#x}", offset);
+                0
+            }
+        }
+    }
+
+    fn write(&mut self, offset: u64, value: u32) {
+        let value = value & ((1 << GPIO_LINES) - 1);
+        match offset {
+            // Bits [9:2] of the address mask the data bits being written.
+            GPIODATA..=GPIODATA_END => {
+                let mask = (offset >> 2) as u32 & self.dir;
+                self.data = (self.data & !mask) | (value & mask);
+            }
+            GPIODIR => self.dir = value,
+            GPIOIS => self.is = value,
+            GPIOIBE => self.ibe = value,
+            GPIOIEV => self.iev = value,
+            GPIOIE => self.ie = value,
+            GPIOIC => self.ris &= !value,
+            GPIOAFSEL => self.afsel = value,
+            _ => warn!("Guest write at invalid GPIO offset: {:#x}", offset),
+        }
+    }
+
+    pub fn bus_read(&mut self, offset: u64, data: &mut [u8]) {
+        if data.len() == 4 {
+            data.copy_from_slice(&self.read(offset).to_le_bytes());
+        } else {
+            warn!(
+                "Found invalid data length while trying to read from the GPIO: {}",
+                data.len()
+            );
+        }
+    }
+
+    pub fn bus_write(&mut self, offset: u64, data: &[u8]) {
+        if let Ok(value) = <[u8; 4]>::try_from(data) {
+            self.write(offset, u32::from_le_bytes(value));
+        } else {
+            warn!(
+                "Found invalid data length while trying to write to the GPIO: {}",
+                data.len()
+            );
+        }
+    }
+}
+
+impl Persist<'_> for Gpio {
+    type State = GpioState;
+    type ConstructorArgs = ();
+    type Error = io::Error;
+
+    fn save(&self) -> Self::State {
+        GpioState {
+            data: self.data,
+            dir: self.dir,
+            is: self.is,
+            ibe: self.ibe,
+            iev: self.iev,
+            ie: self.ie,
+            ris: self.ris,
+            afsel: self.afsel,
+            pressed: self.pressed,
+        }
+    }
+
+    fn restore(_: Self::ConstructorArgs, state: &Self::State) -> Result<Self, Self::Error> {
+        Ok(Gpio {
+            data: state.data,
+            dir: state.dir,
+            is: state.is,
+            ibe: state.ibe,
+            iev: state.iev,
+            ie: state.ie,
+            ris: state.ris,
+            afsel: state.afsel,
+            pressed: state.pressed,
+            interrupt_evt: EventFdTrigger::new(EventFd::new(libc::EFD_NONBLOCK)?),
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn read(gpio: &mut Gpio, offset: u64) -> u32 {
+        let mut data = [0; 4];
+        gpio.bus_read(offset, &mut data);
+        u32::from_le_bytes(data)
+    }
+
+    fn write(gpio: &mut Gpio, offset: u64, value: u32) {
+        gpio.bus_write(offset, &value.to_le_bytes());
+    }
+
+    #[test]
+    fn test_gpio_id() {
+        let mut gpio = Gpio::new(EventFdTrigger::new(
+            EventFd::new(libc::EFD_NONBLOCK).unwrap(),
+        ));
+        let id: Vec<u8> = (0..8)
+            .map(|i| u8::try_from(read(&mut gpio, GPIO_ID_LOW + i * 4)).unwrap())
+            .collect();
+        assert_eq!(id, GPIO_ID);
+    }
+
+    #[test]
+    fn test_gpio_data() {
+        let mut gpio = Gpio::new(EventFdTrigger::new(
+            EventFd::new(libc::EFD_NONBLOCK).unwrap(),
+        ));
+
+        // Writes to input lines are ignored.
+        write(&mut gpio, GPIODATA_END, 0xff);
+        assert_eq!(read(&mut gpio, GPIODATA_END), 0);
+
+        // Only the lines selected by the address are written and read.
+        write(&mut gpio, GPIODIR, 0x0f);
+        write(&mut gpio, GPIODATA + (0x03 << 2), 0xff);
+        assert_eq!(read(&mut gpio, GPIODATA_END), 0x03);
+        assert_eq!(read(&mut gpio, GPIODATA + (0x01 << 2)), 0x01);
+
+        // Invalid accesses are ignored.
+        let mut data = [0xff; 2];
+        gpio.bus_read(GPIODATA_END, &mut data);
+        assert_eq!(data, [0xff; 2]);
+        gpio.bus_write(GPIODIR, &data);
+        assert_eq!(read(&mut gpio, GPIODIR), 0x0f);
+        assert_eq!(read(&mut gpio, 0x800), 0);
+    }
+
+    #[test]
+    fn test_gpio_trigger_key() {
+        let mut gpio = Gpio::new(EventFdTrigger::new(
+            EventFd::new(libc::EFD_NONBLOCK).unwrap(),
+        ));
+        let mask = 1 << POWER_KEY_LINE;
+
+        // The key press is latched, but no interrupt is raised while it is masked.
+        gpio.trigger_key(POWER_KEY_LINE).unwrap();
+        assert_eq!(read(&mut gpio, GPIORIS), mask);
+        assert_eq!(read(&mut gpio, GPIOMIS), 0);
+        gpio.interrupt_evt().read().unwrap_err();
+
+        write(&mut gpio, GPIOIC, mask);
+        assert_eq!(read(&mut gpio, GPIORIS), 0);
+
+        // Reading the other lines does not release the key.
+        assert_eq!(
+            read(&mut gpio, GPIODATA + (u64::from(!mask & 0xff) << 2)),
+            0
+        );
+        assert_eq!(read(&mut gpio, GPIODATA_END), mask);
+        // The key was released once the guest read the line.
+        assert_eq!(read(&mut gpio, GPIODATA_END), 0);
+        assert_eq!(read(&mut gpio, GPIORIS), 0);
+
+        write(&mut gpio, GPIOIE, mask);
+        gpio.trigger_key(POWER_KEY_LINE).unwrap();
+        assert_eq!(read(&mut gpio, GPIOMIS), mask);
+        assert_eq!(gpio.interrupt_evt().read().unwrap(), 1);
+
+        // The release raises the interrupt if the guest detects both edges.
+        write(&mut gpio, GPIOIC, mask);
+        write(&mut gpio, GPIOIBE, mask);
+        assert_eq!(read(&mut gpio, GPIODATA_END), mask);
+        assert_eq!(read(&mut gpio, GPIOMIS), mask);
+        assert_eq!(gpio.interrupt_evt().read().unwrap(), 1);
+        assert_eq!(read(&mut gpio, GPIODATA_END), 0);
+
+        // Output lines cannot be pressed.
+        write(&mut gpio, GPIOIC, mask);
+        write(&mut gpio, GPIODIR, mask);
+        gpio.trigger_key(POWER_KEY_LINE).unwrap();
+        assert_eq!(read(&mut gpio, GPIORIS), 0);
+
+        gpio.trigger_key(GPIO_LINES).unwrap_err();
+    }
+
+    #[test]
+    fn test_gpio_persistence() {
+        let mut gpio = Gpio::new(EventFdTrigger::new(
+            EventFd::new(libc::EFD_NONBLOCK).unwrap(),
+        ));
+        let mask = 1 << POWER_KEY_LINE;
+        write(&mut gpio, GPIODIR, 0x01);
+        write(&mut gpio, GPIOIS, 0x02);
+        write(&mut gpio, GPIOIBE, mask);
+        write(&mut gpio, GPIOIEV, 0x04);
+        write(&mut gpio, GPIOIE, mask);
+        write(&mut gpio, GPIOAFSEL, 0x10);
+        gpio.trigger_key(POWER_KEY_LINE).unwrap();
+
+        let mut restored = Gpio::restore((), &gpio.save()).unwrap();
+        assert_eq!(restored.save(), gpio.save());
+        assert_eq!(read(&mut restored, GPIOIE), mask);
+        assert_eq!(read(&mut restored, GPIOMIS), mask);
+
+        // The key pressed before the snapshot is released in the restored device.
+        write(&mut restored, GPIOIC, mask);
+        assert_eq!(read(&mut restored, GPIODATA_END), mask);
+        assert_eq!(read(&mut restored, GPIODATA_END), 0);
+        assert_eq!(restored.interrupt_evt().read().unwrap(), 1);
+    }
+}
@@ -20,6 +20,7 @@ use vmm_sys_util::epoll::EventSet;
 
 use crate::devices::legacy::EventFdTrigger;
 use crate::logger::{IncMetric, SharedIncMetric};
+use crate::rate_limiter::{BucketReduction, TokenBucket};
 
 /// Received Data Available interrupt - for letting the driver know that
 /// there is some pending data to be processed.
@@ -42,6 +43,8 @@ pub struct SerialDeviceMetrics {
     pub read_count: SharedIncMetric,
     /// Number of succeeded write calls.
     pub write_count: SharedIncMetric,
+    /// Number of bytes of guest output dropped by the rate limiter.
+    pub rate_limited_bytes: SharedIncMetric,
 }
 impl SerialDeviceMetrics {
     /// Const default construction.
@@ -53,6 +56,7 @@ impl SerialDeviceMetrics {
             missed_write_count: SharedIncMetric::new(),
             read_count: SharedIncMetric::new(),
             write_count: SharedIncMetric::new(),
+            rate_limited_bytes: SharedIncMetric::new(),
         }
     }
 }
@@ -127,18 +131,38 @@ impl SerialEvents for SerialEventsWrapper {
 pub enum SerialOut {
     Sink(std::io::Sink),
     Stdout(std::io::Stdout),
+    /// Output written to the inner `SerialOut` as long as the bucket has enough tokens (one per
+    /// byte), and dropped otherwise.
+    RateLimited(Box<SerialOut>, TokenBucket),
+    /// Output kept in memory, so that tests can check what was written.
+    #[cfg(test)]
+    Buffer(std::sync::Arc<std::sync::Mutex<Vec<u8>>>),
 }
 impl std::io::Write for SerialOut {
     fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
         match self {
             Self::Sink(sink) => sink.write(buf),
             Self::Stdout(stdout) => stdout.write(buf),
+            Self::RateLimited(out, bucket) => {
+                // The guest cannot be throttled without stalling the vCPU writing to the
+                // transmitter, so the output exceeding the limit is reported as written.
+                if bucket.reduce(buf.len() as u64) == BucketReduction::Failure {
+                    METRICS.rate_limited_bytes.add(buf.len() as u64);
+                    return Ok(buf.len());
+                }
+                out.write(buf)
+            }
+            #[cfg(test)]
+            Self::Buffer(buffer) => buffer.lock().unwrap().write(buf),
         }
     }
     fn flush(&mut self) -> std::io::Result<()> {
         match self {
             Self::Sink(sink) => sink.flush(),
             Self::Stdout(stdout) => stdout.flush(),
+            Self::RateLimited(out, _) => out.flush(),
+            #[cfg(test)]
+            Self::Buffer(_) => Ok(()),
         }
     }
 }
@@ -404,6 +428,43 @@ mod tests {
         assert_eq!(invalid_reads_after_2, invalid_reads_after);
     }
 
+    #[test]
+    fn test_serial_output_rate_limit() {
+        let intr_evt = EventFdTrigger::new(EventFd::new(libc::EFD_NONBLOCK).unwrap());
+        // The bucket does not refill for the duration of the test.
+        let bucket = TokenBucket::new(100, 0, 1_000_000).unwrap();
+
+        let output = std::sync::Arc::new(std::sync::Mutex::new(Vec::new()));
+
+        let metrics = &METRICS;
+
+        let mut serial = SerialDevice {
+            serial: Serial::with_events(
+                intr_evt,
+                SerialEventsWrapper {
+                    buffer_ready_event_fd: None,
+                },
+                SerialOut::RateLimited(Box::new(SerialOut::Buffer(output.clone())), bucket),
+            ),
+            input: None::<std::io::Stdin>,
+        };
+
+        let rate_limited_before = metrics.rate_limited_bytes.count();
+        // Output within the limit passes through.
+        for _ in 0..100 {
+            serial.bus_write(0u64, b"a");
+        }
+        assert_eq!(metrics.rate_limited_bytes.count(), rate_limited_before);
+        assert_eq!(*output.lock().unwrap(), vec![b'a'; 100]);
+
+        // Flooding the serial output engages the rate limiter, and the excess output is dropped.
+        for _ in 0..50 {
+            serial.bus_write(0u64, b"b");
+        }
+        assert_eq!(metrics.rate_limited_bytes.count(), rate_limited_before + 50);
+        assert_eq!(*output.lock().unwrap(), vec![b'a'; 100]);
+    }
+
     #[test]
     fn test_is_fifo() {
         // invalid file descriptors arent fifos
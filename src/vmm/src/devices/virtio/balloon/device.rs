@@ -2,7 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use std::fmt;
-use std::time::Duration;
+use std::time::{Duration, Instant};
 
 use log::error;
 use serde::Serialize;
@@ -15,13 +15,14 @@ use super::super::{ActivateError, TYPE_BALLOON};
 use super::metrics::METRICS;
 use super::util::{compact_page_frame_numbers, remove_range};
 use super::{
-    BALLOON_DEV_ID, BALLOON_NUM_QUEUES, BALLOON_QUEUE_SIZES, DEFLATE_INDEX, INFLATE_INDEX,
-    MAX_PAGES_IN_DESC, MAX_PAGE_COMPACT_BUFFER, MIB_TO_4K_PAGES, STATS_INDEX,
-    VIRTIO_BALLOON_F_DEFLATE_ON_OOM, VIRTIO_BALLOON_F_STATS_VQ, VIRTIO_BALLOON_PFN_SHIFT,
-    VIRTIO_BALLOON_S_AVAIL, VIRTIO_BALLOON_S_CACHES, VIRTIO_BALLOON_S_HTLB_PGALLOC,
-    VIRTIO_BALLOON_S_HTLB_PGFAIL, VIRTIO_BALLOON_S_MAJFLT, VIRTIO_BALLOON_S_MEMFREE,
-    VIRTIO_BALLOON_S_MEMTOT, VIRTIO_BALLOON_S_MINFLT, VIRTIO_BALLOON_S_SWAP_IN,
-    VIRTIO_BALLOON_S_SWAP_OUT,
+    BALLOON_DEV_ID, BALLOON_NUM_QUEUES, BALLOON_QUEUE_SIZES, COMPACTION_POLL_INTERVAL,
+    COMPACTION_STALL_TIMEOUT, COMPACTION_TIMEOUT, DEFLATE_INDEX, INFLATE_INDEX, MAX_PAGES_IN_DESC,
+    MAX_PAGE_COMPACT_BUFFER, MIB_TO_4K_PAGES, REPORTING_INDEX, STATS_INDEX,
+    VIRTIO_BALLOON_F_DEFLATE_ON_OOM, VIRTIO_BALLOON_F_PAGE_POISON, VIRTIO_BALLOON_F_REPORTING,
+    VIRTIO_BALLOON_F_STATS_VQ, VIRTIO_BALLOON_PFN_SHIFT, VIRTIO_BALLOON_S_AVAIL,
+    VIRTIO_BALLOON_S_CACHES, VIRTIO_BALLOON_S_HTLB_PGALLOC, VIRTIO_BALLOON_S_HTLB_PGFAIL,
+    VIRTIO_BALLOON_S_MAJFLT, VIRTIO_BALLOON_S_MEMFREE, VIRTIO_BALLOON_S_MEMTOT,
+    VIRTIO_BALLOON_S_MINFLT, VIRTIO_BALLOON_S_SWAP_IN, VIRTIO_BALLOON_S_SWAP_OUT,
 };
 use crate::devices::virtio::balloon::BalloonError;
 use crate::devices::virtio::device::{IrqTrigger, IrqType};
@@ -30,6 +31,16 @@ use crate::logger::IncMetric;
 use crate::utils::u64_to_usize;
 use crate::vstate::memory::{Address, ByteValued, Bytes, GuestAddress, GuestMemoryMmap};
 
+/// Features offered by the balloon device regardless of its configuration.
+pub(crate) const AVAIL_FEATURES: u64 = 1u64 << VIRTIO_F_VERSION_1;
+/// Features offered depending on the configuration of the device, with the configuration option
+/// enabling each of them.
+pub(crate) const OPTIONAL_FEATURES: [(u32, &str); 3] = [
+    (VIRTIO_BALLOON_F_STATS_VQ, "stats_polling_interval_s"),
+    (VIRTIO_BALLOON_F_DEFLATE_ON_OOM, "deflate_on_oom"),
+    (VIRTIO_BALLOON_F_REPORTING, "free_page_reporting"),
+];
+
 const SIZE_OF_U32: usize = std::mem::size_of::<u32>();
 const SIZE_OF_STAT: usize = std::mem::size_of::<BalloonStat>();
 
@@ -74,6 +85,21 @@ pub struct BalloonConfig {
     pub deflate_on_oom: bool,
     /// Interval of time in seconds at which the balloon statistics are updated.
     pub stats_polling_interval_s: u16,
+    /// Whether or not the guest can report its free pages.
+    pub free_page_reporting: bool,
+}
+
+/// Balloon features negotiated with the guest driver.
+#[derive(Clone, Copy, Default, Debug, PartialEq, Eq, Serialize)]
+pub struct BalloonFeatures {
+    /// Whether the guest deflates the balloon when running out of memory.
+    pub deflate_on_oom: bool,
+    /// Whether the guest reports memory statistics.
+    pub stats: bool,
+    /// Whether the guest reports free pages.
+    pub free_page_reporting: bool,
+    /// Whether the guest poisons freed pages.
+    pub page_poison: bool,
 }
 
 /// BalloonStats holds statistics returned from the stats_queue.
@@ -88,6 +114,12 @@ pub struct BalloonStats {
     pub target_mib: u32,
     /// The number of MiB the device is currently holding.
     pub actual_mib: u32,
+    /// The number of deflate requests processed while deflate on OOM was negotiated.
+    pub oom_deflate_count: u64,
+    /// The amount of memory, in MiB, the guest gave back during the last completed guest
+    /// memory compaction.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub compaction_reclaimed_mib: Option<u32>,
     /// Amount of memory swapped in.
     #[serde(skip_serializing_if = "Option::is_none")]
     pub swap_in: Option<u64>,
@@ -149,6 +181,22 @@ impl BalloonStats {
     }
 }
 
+/// Progress of a guest memory compaction, during which the balloon is inflated then deflated
+/// back to its previous target size.
+#[derive(Clone, Copy, Debug)]
+pub(crate) struct Compaction {
+    /// Target size of the balloon before the compaction, in 4K pages.
+    initial_num_pages: u32,
+    /// Size of the balloon before the compaction, in MiB.
+    initial_actual_mib: u32,
+    /// Target size of the inflated balloon, in MiB.
+    target_mib: u32,
+    /// Size of the balloon when last checked, in MiB.
+    actual_mib: u32,
+    started: Instant,
+    last_progress: Instant,
+}
+
 /// Virtio balloon device.
 pub struct Balloon {
     // Virtio fields.
@@ -171,6 +219,10 @@ pub struct Balloon {
     // it is acknowledged after the stats queue is processed.
     pub(crate) stats_desc_index: Option<u16>,
     pub(crate) latest_stats: BalloonStats,
+    pub(crate) compaction: Option<Compaction>,
+    pub(crate) compaction_timer: TimerFd,
+    // Signaled when a guest memory compaction completes.
+    pub(crate) compaction_evt: EventFd,
     // A buffer used as pfn accumulator during descriptor processing.
     pub(crate) pfn_buffer: [u32; MAX_PAGE_COMPACT_BUFFER],
 }
@@ -193,6 +245,8 @@ impl fmt::Debug for Balloon {
             .field("stats_polling_interval_s", &self.stats_polling_interval_s)
             .field("stats_desc_index", &self.stats_desc_index)
             .field("latest_stats", &self.latest_stats)
+            .field("compaction", &self.compaction)
+            .field("compaction_evt", &self.compaction_evt)
             .field("pfn_buffer", &self.pfn_buffer)
             .finish()
     }
@@ -204,9 +258,10 @@ impl Balloon {
         amount_mib: u32,
         deflate_on_oom: bool,
         stats_polling_interval_s: u16,
+        free_page_reporting: bool,
         restored: bool,
     ) -> Result<Balloon, BalloonError> {
-        let mut avail_features = 1u64 << VIRTIO_F_VERSION_1;
+        let mut avail_features = AVAIL_FEATURES;
 
         if deflate_on_oom {
             avail_features |= 1u64 << VIRTIO_BALLOON_F_DEFLATE_ON_OOM;
@@ -216,22 +271,33 @@ impl Balloon {
             avail_features |= 1u64 << VIRTIO_BALLOON_F_STATS_VQ;
         }
 
+        if free_page_reporting {
+            avail_features |= 1u64 << VIRTIO_BALLOON_F_REPORTING;
+        }
+
         let queue_evts = [
             EventFd::new(libc::EFD_NONBLOCK).map_err(BalloonError::EventFd)?,
             EventFd::new(libc::EFD_NONBLOCK).map_err(BalloonError::EventFd)?,
             EventFd::new(libc::EFD_NONBLOCK).map_err(BalloonError::EventFd)?,
+            EventFd::new(libc::EFD_NONBLOCK).map_err(BalloonError::EventFd)?,
         ];
 
         let mut queues: Vec<Queue> = BALLOON_QUEUE_SIZES.iter().map(|&s| Queue::new(s)).collect();
 
-        // The VirtIO specification states that the statistics queue should
-        // not be present at all if the statistics are not enabled.
+        // The VirtIO specification states that the statistics and free page
+        // reporting queues should not be present at all if the features are
+        // not enabled. The queues that follow a missing one take its place.
+        if !free_page_reporting {
+            let _ = queues.remove(REPORTING_INDEX);
+        }
         if stats_polling_interval_s == 0 {
             let _ = queues.remove(STATS_INDEX);
         }
 
         let stats_timer =
             TimerFd::new_custom(ClockId::Monotonic, true, true).map_err(BalloonError::Timer)?;
+        let compaction_timer =
+            TimerFd::new_custom(ClockId::Monotonic, true, true).map_err(BalloonError::Timer)?;
 
         Ok(Balloon {
             avail_features,
@@ -250,6 +316,9 @@ impl Balloon {
             stats_timer,
             stats_desc_index: None,
             latest_stats: BalloonStats::default(),
+            compaction: None,
+            compaction_timer,
+            compaction_evt: EventFd::new(libc::EFD_NONBLOCK).map_err(BalloonError::EventFd)?,
             pfn_buffer: [0u32; MAX_PAGE_COMPACT_BUFFER],
         })
     }
@@ -258,7 +327,8 @@ impl Balloon {
         self.queue_evts[INFLATE_INDEX]
             .read()
             .map_err(BalloonError::EventFd)?;
-        self.process_inflate()
+        self.process_inflate()?;
+        self.update_compaction()
     }
 
     pub(crate) fn process_deflate_queue_event(&mut self) -> Result<(), BalloonError> {
@@ -275,11 +345,23 @@ impl Balloon {
         self.process_stats_queue()
     }
 
+    pub(crate) fn process_reporting_queue_event(&mut self) -> Result<(), BalloonError> {
+        self.queue_evts[self.reporting_queue_index()]
+            .read()
+            .map_err(BalloonError::EventFd)?;
+        self.process_reporting_queue()
+    }
+
     pub(crate) fn process_stats_timer_event(&mut self) -> Result<(), BalloonError> {
         self.stats_timer.read();
         self.trigger_stats_update()
     }
 
+    pub(crate) fn process_compaction_timer_event(&mut self) -> Result<(), BalloonError> {
+        self.compaction_timer.read();
+        self.update_compaction()
+    }
+
     pub(crate) fn process_inflate(&mut self) -> Result<(), BalloonError> {
         // This is safe since we checked in the event handler that the device is activated.
         let mem = self.device_state.mem().unwrap();
@@ -372,11 +454,15 @@ impl Balloon {
     pub(crate) fn process_deflate_queue(&mut self) -> Result<(), BalloonError> {
         METRICS.deflate_count.inc();
 
+        let oom_deflate = self.acked_features & (1u64 << VIRTIO_BALLOON_F_DEFLATE_ON_OOM) != 0;
         let queue = &mut self.queues[DEFLATE_INDEX];
         let mut needs_interrupt = false;
 
         while let Some(head) = queue.pop() {
             queue.add_used(head.index, 0).map_err(BalloonError::Queue)?;
+            if oom_deflate {
+                self.latest_stats.oom_deflate_count += 1;
+            }
             needs_interrupt = true;
         }
 
@@ -425,6 +511,45 @@ impl Balloon {
         Ok(())
     }
 
+    pub(crate) fn process_reporting_queue(&mut self) -> Result<(), BalloonError> {
+        // This is safe since we checked in the event handler that the device is activated.
+        let mem = self.device_state.mem().unwrap();
+        METRICS.free_page_report_count.inc();
+
+        let queue_index = self.reporting_queue_index();
+        let queue = &mut self.queues[queue_index];
+        let mut needs_interrupt = false;
+
+        while let Some(head) = queue.pop() {
+            let head_index = head.index;
+            let mut desc = Some(head);
+
+            // Each descriptor of the chain holds a range of free guest memory.
+            while let Some(range) = desc {
+                if range.is_write_only() {
+                    if let Err(err) =
+                        remove_range(mem, (range.addr, u64::from(range.len)), self.restored)
+                    {
+                        error!("Error removing reported memory range: {:?}", err);
+                    }
+                } else {
+                    error!("Free page reporting descriptor is not write-only, skipping.");
+                }
+                desc = range.next_descriptor();
+            }
+
+            // The device does not write to the reported ranges.
+            queue.add_used(head_index, 0).map_err(BalloonError::Queue)?;
+            needs_interrupt = true;
+        }
+
+        if needs_interrupt {
+            self.signal_used_queue()
+        } else {
+            Ok(())
+        }
+    }
+
     pub(crate) fn signal_used_queue(&self) -> Result<(), BalloonError> {
         self.irq_trigger.trigger_irq(IrqType::Vring).map_err(|err| {
             METRICS.event_fails.inc();
@@ -436,6 +561,9 @@ impl Balloon {
     pub fn process_virtio_queues(&mut self) {
         let _ = self.process_inflate();
         let _ = self.process_deflate_queue();
+        if self.free_page_reporting() {
+            let _ = self.process_reporting_queue();
+        }
     }
 
     /// Provides the ID of this balloon device.
@@ -469,6 +597,95 @@ impl Balloon {
         }
     }
 
+    /// Starts compacting the guest memory by inflating the balloon by `target_free_mib` MiB.
+    ///
+    /// The pages given back by the guest are removed from the guest memory as the inflate queue
+    /// is processed. The balloon is deflated back to its current target size once the guest gave
+    /// the memory back, stopped doing so, or took too long to do so, as checked on the inflate
+    /// queue and compaction timer events. The amount of memory given back is then reported in
+    /// the statistics and metrics, and [`Self::compaction_evt`] is signaled.
+    pub fn start_compaction(&mut self, target_free_mib: u32) -> Result<(), BalloonError> {
+        if self.compaction.is_some() {
+            return Err(BalloonError::CompactionInProgress);
+        }
+
+        let initial_num_pages = self.num_pages();
+        let target_mib = self
+            .size_mb()
+            .checked_add(target_free_mib)
+            .ok_or(BalloonError::TooManyPagesRequested)?;
+        self.update_size(target_mib)?;
+
+        let now = Instant::now();
+        self.compaction = Some(Compaction {
+            initial_num_pages,
+            initial_actual_mib: self.actual_mb(),
+            target_mib,
+            actual_mib: self.actual_mb(),
+            started: now,
+            last_progress: now,
+        });
+        self.compaction_timer.set_state(
+            TimerState::Periodic {
+                current: COMPACTION_POLL_INTERVAL,
+                interval: COMPACTION_POLL_INTERVAL,
+            },
+            SetTimeFlags::Default,
+        );
+        Ok(())
+    }
+
+    /// Deflates the balloon back if the compaction in progress, if any, is complete.
+    fn update_compaction(&mut self) -> Result<(), BalloonError> {
+        let Some(mut compaction) = self.compaction else {
+            return Ok(());
+        };
+
+        let now = Instant::now();
+        let actual_mib = self.actual_mb();
+        if actual_mib != compaction.actual_mib {
+            compaction.actual_mib = actual_mib;
+            compaction.last_progress = now;
+        }
+        if actual_mib < compaction.target_mib
+            && now.duration_since(compaction.last_progress) < COMPACTION_STALL_TIMEOUT
+            && now.duration_since(compaction.started) < COMPACTION_TIMEOUT
+        {
+            self.compaction = Some(compaction);
+            return Ok(());
+        }
+
+        self.compaction = None;
+        self.compaction_timer
+            .set_state(TimerState::Disarmed, SetTimeFlags::Default);
+        let reclaimed_mib = actual_mib.saturating_sub(compaction.initial_actual_mib);
+        self.latest_stats.compaction_reclaimed_mib = Some(reclaimed_mib);
+        METRICS.compaction_count.inc();
+        METRICS
+            .compaction_reclaimed_mib
+            .add(u64::from(reclaimed_mib));
+        self.compaction_evt
+            .write(1)
+            .map_err(BalloonError::EventFd)?;
+        self.update_size(pages_to_mib(compaction.initial_num_pages))
+    }
+
+    /// Returns whether a guest memory compaction is in progress.
+    pub fn compaction_in_progress(&self) -> bool {
+        self.compaction.is_some()
+    }
+
+    /// Returns the amount of memory, in MiB, the guest gave back during the last completed
+    /// guest memory compaction, if any.
+    pub fn compaction_reclaimed_mib(&self) -> Option<u32> {
+        self.latest_stats.compaction_reclaimed_mib
+    }
+
+    /// Returns the event signaled when a guest memory compaction completes.
+    pub fn compaction_evt(&self) -> &EventFd {
+        &self.compaction_evt
+    }
+
     /// Update the statistics polling interval.
     pub fn update_stats_polling_interval(&mut self, interval_s: u16) -> Result<(), BalloonError> {
         if self.stats_polling_interval_s == interval_s {
@@ -500,11 +717,30 @@ impl Balloon {
         self.config_space.num_pages
     }
 
+    /// Obtain the number of 4K pages the device was holding before the compaction in progress, if
+    /// any.
+    pub(crate) fn num_pages_before_compaction(&self) -> u32 {
+        self.compaction
+            .map_or(self.config_space.num_pages, |compaction| {
+                compaction.initial_num_pages
+            })
+    }
+
     /// Obtain the size of 4K pages the device is currently holding in MIB.
     pub fn size_mb(&self) -> u32 {
         pages_to_mib(self.config_space.num_pages)
     }
 
+    /// Obtain the number of 4K pages the guest reports as given back to the device.
+    pub fn actual_pages(&self) -> u32 {
+        self.config_space.actual_pages
+    }
+
+    /// Obtain the size of 4K pages the guest reports as given back to the device in MIB.
+    pub fn actual_mb(&self) -> u32 {
+        pages_to_mib(self.config_space.actual_pages)
+    }
+
     pub fn deflate_on_oom(&self) -> bool {
         self.avail_features & (1u64 << VIRTIO_BALLOON_F_DEFLATE_ON_OOM) != 0
     }
@@ -513,6 +749,10 @@ impl Balloon {
         self.stats_polling_interval_s
     }
 
+    pub fn free_page_reporting(&self) -> bool {
+        self.avail_features & (1u64 << VIRTIO_BALLOON_F_REPORTING) != 0
+    }
+
     /// Retrieve latest stats for the balloon device.
     pub fn latest_stats(&mut self) -> Option<&BalloonStats> {
         if self.stats_enabled() {
@@ -532,13 +772,39 @@ impl Balloon {
             amount_mib: self.size_mb(),
             deflate_on_oom: self.deflate_on_oom(),
             stats_polling_interval_s: self.stats_polling_interval_s(),
+            free_page_reporting: self.free_page_reporting(),
         }
     }
 
+    /// Return the features negotiated with the guest driver when the device was activated.
+    pub fn features(&self) -> Result<BalloonFeatures, BalloonError> {
+        if !self.is_activated() {
+            return Err(BalloonError::DeviceNotActive);
+        }
+
+        let acked = |feature: u32| self.acked_features & (1u64 << feature) != 0;
+        Ok(BalloonFeatures {
+            deflate_on_oom: acked(VIRTIO_BALLOON_F_DEFLATE_ON_OOM),
+            stats: acked(VIRTIO_BALLOON_F_STATS_VQ),
+            free_page_reporting: acked(VIRTIO_BALLOON_F_REPORTING),
+            page_poison: acked(VIRTIO_BALLOON_F_PAGE_POISON),
+        })
+    }
+
     pub(crate) fn stats_enabled(&self) -> bool {
         self.stats_polling_interval_s > 0
     }
 
+    /// Index of the free page reporting queue, which takes the place of the statistics queue
+    /// when the statistics are disabled.
+    pub(crate) fn reporting_queue_index(&self) -> usize {
+        if self.stats_enabled() {
+            REPORTING_INDEX
+        } else {
+            STATS_INDEX
+        }
+    }
+
     pub(crate) fn set_stats_desc_index(&mut self, stats_desc_index: Option<u16>) {
         self.stats_desc_index = stats_desc_index;
     }
@@ -601,6 +867,10 @@ impl VirtioDevice for Balloon {
         dst.copy_from_slice(data);
     }
 
+    fn config_space(&self) -> Vec<u8> {
+        self.config_space.as_slice().to_vec()
+    }
+
     fn activate(&mut self, mem: GuestMemoryMmap) -> Result<(), ActivateError> {
         for q in self.queues.iter_mut() {
             q.initialize(&mem)
@@ -645,10 +915,6 @@ pub(crate) mod tests {
             self.queues[idx] = q;
         }
 
-        pub(crate) fn actual_pages(&self) -> u32 {
-            self.config_space.actual_pages
-        }
-
         pub fn update_num_pages(&mut self, num_pages: u32) {
             self.config_space.num_pages = num_pages;
         }
@@ -671,6 +937,8 @@ pub(crate) mod tests {
             actual_pages: 2560,
             target_mib: 20,
             actual_mib: 10,
+            oom_deflate_count: 0,
+            compaction_reclaimed_mib: None,
             swap_in: Some(0),
             swap_out: Some(0),
             major_faults: Some(0),
@@ -724,39 +992,54 @@ pub(crate) mod tests {
         // Test all feature combinations.
         for deflate_on_oom in [true, false].iter() {
             for stats_interval in [0, 1].iter() {
-                let mut balloon = Balloon::new(0, *deflate_on_oom, *stats_interval, false).unwrap();
-                assert_eq!(balloon.device_type(), TYPE_BALLOON);
-
-                let features: u64 = (1u64 << VIRTIO_F_VERSION_1)
-                    | (u64::from(*deflate_on_oom) << VIRTIO_BALLOON_F_DEFLATE_ON_OOM)
-                    | ((u64::from(*stats_interval)) << VIRTIO_BALLOON_F_STATS_VQ);
-
-                assert_eq!(
-                    balloon.avail_features_by_page(0),
-                    (features & 0xFFFFFFFF) as u32
-                );
-                assert_eq!(balloon.avail_features_by_page(1), (features >> 32) as u32);
-                for i in 2..10 {
-                    assert_eq!(balloon.avail_features_by_page(i), 0u32);
-                }
+                for free_page_reporting in [true, false] {
+                    let mut balloon = Balloon::new(
+                        0,
+                        *deflate_on_oom,
+                        *stats_interval,
+                        free_page_reporting,
+                        false,
+                    )
+                    .unwrap();
+                    assert_eq!(balloon.device_type(), TYPE_BALLOON);
+                    assert_eq!(
+                        balloon.queues().len(),
+                        2 + usize::from(*stats_interval) + usize::from(free_page_reporting)
+                    );
+
+                    let features: u64 = (1u64 << VIRTIO_F_VERSION_1)
+                        | (u64::from(*deflate_on_oom) << VIRTIO_BALLOON_F_DEFLATE_ON_OOM)
+                        | ((u64::from(*stats_interval)) << VIRTIO_BALLOON_F_STATS_VQ)
+                        | (u64::from(free_page_reporting) << VIRTIO_BALLOON_F_REPORTING);
+
+                    assert_eq!(
+                        balloon.avail_features_by_page(0),
+                        (features & 0xFFFFFFFF) as u32
+                    );
+                    assert_eq!(balloon.avail_features_by_page(1), (features >> 32) as u32);
+                    for i in 2..10 {
+                        assert_eq!(balloon.avail_features_by_page(i), 0u32);
+                    }
 
-                for i in 0..10 {
-                    balloon.ack_features_by_page(i, u32::MAX);
+                    for i in 0..10 {
+                        balloon.ack_features_by_page(i, u32::MAX);
+                    }
+                    // Only present features should be acknowledged.
+                    assert_eq!(balloon.acked_features, features);
                 }
-                // Only present features should be acknowledged.
-                assert_eq!(balloon.acked_features, features);
             }
         }
     }
 
     #[test]
     fn test_virtio_read_config() {
-        let balloon = Balloon::new(0x10, true, 0, false).unwrap();
+        let balloon = Balloon::new(0x10, true, 0, false, false).unwrap();
 
         let cfg = BalloonConfig {
             amount_mib: 16,
             deflate_on_oom: true,
             stats_polling_interval_s: 0,
+            free_page_reporting: false,
         };
         assert_eq!(balloon.config(), cfg);
 
@@ -785,7 +1068,7 @@ pub(crate) mod tests {
 
     #[test]
     fn test_virtio_write_config() {
-        let mut balloon = Balloon::new(0, true, 0, false).unwrap();
+        let mut balloon = Balloon::new(0, true, 0, false, false).unwrap();
 
         let expected_config_space: [u8; BALLOON_CONFIG_SPACE_SIZE] =
             [0x00, 0x50, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00];
@@ -811,7 +1094,7 @@ pub(crate) mod tests {
 
     #[test]
     fn test_invalid_request() {
-        let mut balloon = Balloon::new(0, true, 0, false).unwrap();
+        let mut balloon = Balloon::new(0, true, 0, false, false).unwrap();
         let mem = default_mem();
         // Only initialize the inflate queue to demonstrate invalid request handling.
         let infq = VirtQueue::new(GuestAddress(0), &mem, 16);
@@ -870,7 +1153,7 @@ pub(crate) mod tests {
 
     #[test]
     fn test_inflate() {
-        let mut balloon = Balloon::new(0, true, 0, false).unwrap();
+        let mut balloon = Balloon::new(0, true, 0, false, false).unwrap();
         let mem = default_mem();
         let infq = VirtQueue::new(GuestAddress(0), &mem, 16);
         balloon.set_queue(INFLATE_INDEX, infq.create_queue());
@@ -940,7 +1223,7 @@ pub(crate) mod tests {
 
     #[test]
     fn test_deflate() {
-        let mut balloon = Balloon::new(0, true, 0, false).unwrap();
+        let mut balloon = Balloon::new(0, true, 0, false, false).unwrap();
         let mem = default_mem();
         let defq = VirtQueue::new(GuestAddress(0), &mem, 16);
         balloon.set_queue(DEFLATE_INDEX, defq.create_queue());
@@ -986,9 +1269,39 @@ pub(crate) mod tests {
         }
     }
 
+    #[test]
+    fn test_oom_deflate_count() {
+        for deflate_on_oom in [true, false] {
+            let mut balloon = Balloon::new(0, deflate_on_oom, 1, false, false).unwrap();
+            balloon.ack_features_by_page(0, u32::MAX);
+            let mem = default_mem();
+            let defq = VirtQueue::new(GuestAddress(0), &mem, 16);
+            balloon.set_queue(DEFLATE_INDEX, defq.create_queue());
+            balloon.activate(mem.clone()).unwrap();
+
+            set_request(
+                &defq,
+                0,
+                0x10,
+                SIZE_OF_U32.try_into().unwrap(),
+                VIRTQ_DESC_F_NEXT,
+            );
+            invoke_handler_for_queue_event(&mut balloon, DEFLATE_INDEX);
+            check_request_completion(&defq, 0);
+
+            // Deflations are only counted when the guest negotiated deflate on OOM.
+            let stats = balloon.latest_stats().unwrap();
+            assert_eq!(stats.oom_deflate_count, u64::from(deflate_on_oom));
+            assert_eq!(
+                serde_json::to_value(stats).unwrap()["oom_deflate_count"],
+                u64::from(deflate_on_oom)
+            );
+        }
+    }
+
     #[test]
     fn test_stats() {
-        let mut balloon = Balloon::new(0, true, 1, false).unwrap();
+        let mut balloon = Balloon::new(0, true, 1, false, false).unwrap();
         let mem = default_mem();
         let statsq = VirtQueue::new(GuestAddress(0), &mem, 16);
         balloon.set_queue(STATS_INDEX, statsq.create_queue());
@@ -1073,9 +1386,63 @@ pub(crate) mod tests {
         }
     }
 
+    #[test]
+    fn test_free_page_reporting() {
+        let mut balloon = Balloon::new(0, true, 0, true, false).unwrap();
+        assert_eq!(balloon.reporting_queue_index(), STATS_INDEX);
+        let mem = default_mem();
+        let reportq = VirtQueue::new(GuestAddress(0), &mem, 16);
+        balloon.set_queue(balloon.reporting_queue_index(), reportq.create_queue());
+        balloon.activate(mem.clone()).unwrap();
+
+        // Fill the second page with non-zero bytes.
+        for i in 0..0x1000 {
+            mem.write_obj::<u8>(1, GuestAddress((1 << 12) + i)).unwrap();
+        }
+
+        // Invalid case: the descriptor is not write-only.
+        {
+            set_request(&reportq, 0, 1 << 12, 0x1000, 0);
+            invoke_handler_for_queue_event(&mut balloon, STATS_INDEX);
+            check_request_completion(&reportq, 0);
+
+            // Check that the page was not discarded.
+            for i in 0..0x1000 {
+                assert_eq!(mem.read_obj::<u8>(GuestAddress((1 << 12) + i)).unwrap(), 1);
+            }
+        }
+
+        // Invalid case: the reported range is outside of the guest memory.
+        {
+            set_request(&reportq, 1, 0x10000, 0x1000, VIRTQ_DESC_F_WRITE);
+            invoke_handler_for_queue_event(&mut balloon, STATS_INDEX);
+            check_request_completion(&reportq, 1);
+        }
+
+        // Happy case.
+        {
+            set_request(&reportq, 2, 1 << 12, 0x1000, VIRTQ_DESC_F_WRITE);
+            check_metric_after_block!(
+                METRICS.free_page_report_count,
+                1,
+                invoke_handler_for_queue_event(&mut balloon, STATS_INDEX)
+            );
+            check_request_completion(&reportq, 2);
+
+            // Check that the page was discarded.
+            for i in 0..0x1000 {
+                assert_eq!(mem.read_obj::<u8>(GuestAddress((1 << 12) + i)).unwrap(), 0);
+            }
+        }
+
+        // The reporting queue follows the statistics queue when the latter is present.
+        let balloon = Balloon::new(0, true, 1, true, false).unwrap();
+        assert_eq!(balloon.reporting_queue_index(), REPORTING_INDEX);
+    }
+
     #[test]
     fn test_process_balloon_queues() {
-        let mut balloon = Balloon::new(0x10, true, 0, false).unwrap();
+        let mut balloon = Balloon::new(0x10, true, 0, false, false).unwrap();
         let mem = default_mem();
         let infq = VirtQueue::new(GuestAddress(0), &mem, 16);
         let defq = VirtQueue::new(GuestAddress(0), &mem, 16);
@@ -1089,7 +1456,7 @@ pub(crate) mod tests {
 
     #[test]
     fn test_update_stats_interval() {
-        let mut balloon = Balloon::new(0, true, 0, false).unwrap();
+        let mut balloon = Balloon::new(0, true, 0, false, false).unwrap();
         let mem = default_mem();
         balloon.activate(mem).unwrap();
         assert_eq!(
@@ -1098,7 +1465,7 @@ pub(crate) mod tests {
         );
         balloon.update_stats_polling_interval(0).unwrap();
 
-        let mut balloon = Balloon::new(0, true, 1, false).unwrap();
+        let mut balloon = Balloon::new(0, true, 1, false, false).unwrap();
         let mem = default_mem();
         balloon.activate(mem).unwrap();
         assert_eq!(
@@ -1109,16 +1476,125 @@ pub(crate) mod tests {
         balloon.update_stats_polling_interval(2).unwrap();
     }
 
+    #[test]
+    fn test_compaction() {
+        let mut balloon = Balloon::new(4, true, 1, false, false).unwrap();
+        assert_eq!(
+            format!("{:?}", balloon.start_compaction(8)),
+            "Err(DeviceNotActive)"
+        );
+        balloon.activate(default_mem()).unwrap();
+        assert_eq!(
+            format!("{:?}", balloon.start_compaction(u32::MAX)),
+            "Err(TooManyPagesRequested)"
+        );
+        assert!(balloon.compaction.is_none());
+
+        // Emulate a guest driver already holding 4 MiB, which only gives back 6 of the 8 MiB
+        // requested before stalling.
+        balloon.update_actual_pages(1024);
+        balloon.start_compaction(8).unwrap();
+        assert_eq!(balloon.size_mb(), 12);
+        assert_eq!(
+            format!("{:?}", balloon.start_compaction(8)),
+            "Err(CompactionInProgress)"
+        );
+        // A snapshot of the balloon does not hold the inflation of the compaction.
+        assert_eq!(balloon.num_pages_before_compaction(), 1024);
+
+        balloon.update_actual_pages(2560);
+        balloon.process_compaction_timer_event().unwrap();
+        assert_eq!(balloon.size_mb(), 12);
+
+        // The balloon is deflated back once the guest stopped giving memory back.
+        assert!(balloon.compaction_in_progress());
+        balloon.compaction.as_mut().unwrap().last_progress -= COMPACTION_STALL_TIMEOUT;
+        check_metric_after_block!(
+            METRICS.compaction_reclaimed_mib,
+            6,
+            balloon.process_compaction_timer_event().unwrap()
+        );
+        assert!(!balloon.compaction_in_progress());
+        assert_eq!(balloon.size_mb(), 4);
+        assert_eq!(
+            balloon.latest_stats().unwrap().compaction_reclaimed_mib,
+            Some(6)
+        );
+        assert_eq!(balloon.compaction_reclaimed_mib(), Some(6));
+        // The completion of the compaction is signaled.
+        assert_eq!(balloon.compaction_evt().read().unwrap(), 1);
+
+        // The balloon is deflated back as soon as the guest gave all the memory back.
+        balloon.update_actual_pages(1024);
+        balloon.start_compaction(2).unwrap();
+        balloon.update_actual_pages(1536);
+        check_metric_after_block!(
+            METRICS.compaction_count,
+            1,
+            balloon.process_compaction_timer_event().unwrap()
+        );
+        assert_eq!(balloon.size_mb(), 4);
+        assert_eq!(
+            balloon.latest_stats().unwrap().compaction_reclaimed_mib,
+            Some(2)
+        );
+
+        // The balloon is deflated back once the compaction took too long.
+        balloon.start_compaction(2).unwrap();
+        balloon.compaction.as_mut().unwrap().started -= COMPACTION_TIMEOUT;
+        balloon.process_compaction_timer_event().unwrap();
+        assert!(balloon.compaction.is_none());
+        assert_eq!(balloon.size_mb(), 4);
+        assert_eq!(
+            balloon.latest_stats().unwrap().compaction_reclaimed_mib,
+            Some(0)
+        );
+    }
+
+    #[test]
+    fn test_negotiated_features() {
+        let mut balloon = Balloon::new(0, true, 0, false, false).unwrap();
+        // Nothing is negotiated before the device is activated.
+        assert_eq!(format!("{:?}", balloon.features()), "Err(DeviceNotActive)");
+
+        // The guest acknowledges all the features offered, but only offered features are
+        // reported.
+        balloon.ack_features_by_page(0, u32::MAX);
+        balloon.activate(default_mem()).unwrap();
+        assert_eq!(
+            balloon.features().unwrap(),
+            BalloonFeatures {
+                deflate_on_oom: true,
+                stats: false,
+                free_page_reporting: false,
+                page_poison: false,
+            }
+        );
+
+        let mut balloon = Balloon::new(0, false, 1, false, false).unwrap();
+        balloon.ack_features_by_page(0, 1 << VIRTIO_BALLOON_F_STATS_VQ);
+        balloon.activate(default_mem()).unwrap();
+        assert_eq!(
+            balloon.features().unwrap(),
+            BalloonFeatures {
+                deflate_on_oom: false,
+                stats: true,
+                free_page_reporting: false,
+                page_poison: false,
+            }
+        );
+    }
+
     #[test]
     fn test_cannot_update_inactive_device() {
-        let mut balloon = Balloon::new(0, true, 0, false).unwrap();
+        let mut balloon = Balloon::new(0, true, 0, false, false).unwrap();
         // Assert that we can't update an inactive device.
         balloon.update_size(1).unwrap_err();
     }
 
     #[test]
     fn test_num_pages() {
-        let mut balloon = Balloon::new(0, true, 0, false).unwrap();
+        let mut balloon = Balloon::new(0, true, 0, false, false).unwrap();
         // Switch the state to active.
         balloon.device_state = DeviceState::Activated(single_region_mem(0x1));
 
@@ -15,6 +15,8 @@ impl Balloon {
     const PROCESS_VIRTQ_DEFLATE: u32 = 2;
     const PROCESS_VIRTQ_STATS: u32 = 3;
     const PROCESS_STATS_TIMER: u32 = 4;
+    const PROCESS_VIRTQ_REPORTING: u32 = 5;
+    const PROCESS_COMPACTION_TIMER: u32 = 6;
 
     fn register_runtime_events(&self, ops: &mut EventOps) {
         if let Err(err) = ops.add(Events::with_data(
@@ -47,6 +49,22 @@ impl Balloon {
                 error!("Failed to register stats timerfd event: {}", err);
             }
         }
+        if self.free_page_reporting() {
+            if let Err(err) = ops.add(Events::with_data(
+                &self.queue_evts[self.reporting_queue_index()],
+                Self::PROCESS_VIRTQ_REPORTING,
+                EventSet::IN,
+            )) {
+                error!("Failed to register reporting queue event: {}", err);
+            }
+        }
+        if let Err(err) = ops.add(Events::with_data(
+            &self.compaction_timer,
+            Self::PROCESS_COMPACTION_TIMER,
+            EventSet::IN,
+        )) {
+            error!("Failed to register compaction timerfd event: {}", err);
+        }
     }
 
     fn register_activate_event(&self, ops: &mut EventOps) {
@@ -103,6 +121,12 @@ impl MutEventSubscriber for Balloon {
                 Self::PROCESS_STATS_TIMER => self
                     .process_stats_timer_event()
                     .unwrap_or_else(report_balloon_event_fail),
+                Self::PROCESS_VIRTQ_REPORTING => self
+                    .process_reporting_queue_event()
+                    .unwrap_or_else(report_balloon_event_fail),
+                Self::PROCESS_COMPACTION_TIMER => self
+                    .process_compaction_timer_event()
+                    .unwrap_or_else(report_balloon_event_fail),
                 _ => {
                     warn!("Balloon: Spurious event received: {:?}", source);
                 }
@@ -142,7 +166,7 @@ pub mod tests {
     #[test]
     fn test_event_handler() {
         let mut event_manager = EventManager::new().unwrap();
-        let mut balloon = Balloon::new(0, true, 10, false).unwrap();
+        let mut balloon = Balloon::new(0, true, 10, false, false).unwrap();
         let mem = default_mem();
         let infq = VirtQueue::new(GuestAddress(0), &mem, 16);
         balloon.set_queue(INFLATE_INDEX, infq.create_queue());
@@ -61,8 +61,14 @@ pub(super) struct BalloonDeviceMetrics {
     pub stats_update_fails: SharedIncMetric,
     /// Number of balloon device deflations.
     pub deflate_count: SharedIncMetric,
+    /// Number of free page reports from the driver.
+    pub free_page_report_count: SharedIncMetric,
     /// Number of times when handling events on a balloon device failed.
     pub event_fails: SharedIncMetric,
+    /// Number of completed guest memory compactions.
+    pub compaction_count: SharedIncMetric,
+    /// Amount of memory the guest gave back during guest memory compactions, in MiB.
+    pub compaction_reclaimed_mib: SharedIncMetric,
 }
 impl BalloonDeviceMetrics {
     /// Const default construction.
@@ -73,7 +79,10 @@ impl BalloonDeviceMetrics {
             stats_updates_count: SharedIncMetric::new(),
             stats_update_fails: SharedIncMetric::new(),
             deflate_count: SharedIncMetric::new(),
+            free_page_report_count: SharedIncMetric::new(),
             event_fails: SharedIncMetric::new(),
+            compaction_count: SharedIncMetric::new(),
+            compaction_reclaimed_mib: SharedIncMetric::new(),
         }
     }
 }
@@ -10,10 +10,12 @@ pub mod persist;
 pub mod test_utils;
 mod util;
 
+use std::time::Duration;
+
 use log::error;
 use vm_memory::GuestMemoryError;
 
-pub use self::device::{Balloon, BalloonConfig, BalloonStats};
+pub use self::device::{Balloon, BalloonConfig, BalloonFeatures, BalloonStats};
 use super::queue::QueueError;
 use crate::devices::virtio::balloon::metrics::METRICS;
 use crate::devices::virtio::queue::FIRECRACKER_MAX_QUEUE_SIZE;
@@ -25,13 +27,14 @@ pub const BALLOON_DEV_ID: &str = "balloon";
 /// The size of the config space.
 pub const BALLOON_CONFIG_SPACE_SIZE: usize = 8;
 /// Number of virtio queues.
-pub const BALLOON_NUM_QUEUES: usize = 3;
+pub const BALLOON_NUM_QUEUES: usize = 4;
 /// Virtio queue sizes, in number of descriptor chain heads.
-//  There are 3 queues for a virtio device (in this order): RX, TX, Event
+//  There are 4 queues for a virtio device (in this order): inflate, deflate, stats, reporting
 pub const BALLOON_QUEUE_SIZES: [u16; BALLOON_NUM_QUEUES] = [
     FIRECRACKER_MAX_QUEUE_SIZE,
     FIRECRACKER_MAX_QUEUE_SIZE,
     FIRECRACKER_MAX_QUEUE_SIZE,
+    FIRECRACKER_MAX_QUEUE_SIZE,
 ];
 // Number of 4K pages in a MiB.
 pub const MIB_TO_4K_PAGES: u32 = 256;
@@ -48,10 +51,22 @@ pub const INFLATE_INDEX: usize = 0;
 pub const DEFLATE_INDEX: usize = 1;
 /// The index of the deflate queue from Balloon device queues/queues_evts vector.
 pub const STATS_INDEX: usize = 2;
+/// The index of the free page reporting queue from Balloon device queues/queues_evts vector,
+/// when the statistics queue is present.
+pub const REPORTING_INDEX: usize = 3;
+/// Interval between two checks of the balloon size while compacting the guest memory.
+pub const COMPACTION_POLL_INTERVAL: Duration = Duration::from_millis(10);
+/// Time after which the guest memory compaction stops waiting for a guest driver that stopped
+/// giving memory back to the balloon.
+pub const COMPACTION_STALL_TIMEOUT: Duration = Duration::from_secs(1);
+/// Upper bound of the time spent inflating the balloon while compacting the guest memory.
+pub const COMPACTION_TIMEOUT: Duration = Duration::from_secs(10);
 
 // The feature bitmap for virtio balloon.
 const VIRTIO_BALLOON_F_STATS_VQ: u32 = 1; // Enable statistics.
 const VIRTIO_BALLOON_F_DEFLATE_ON_OOM: u32 = 2; // Deflate balloon on OOM.
+const VIRTIO_BALLOON_F_PAGE_POISON: u32 = 4; // Guest is using page poisoning.
+const VIRTIO_BALLOON_F_REPORTING: u32 = 5; // Page reporting virtqueue.
 
 // The statistics tags.
 const VIRTIO_BALLOON_S_SWAP_IN: u16 = 0;
@@ -70,6 +85,10 @@ const VIRTIO_BALLOON_S_HTLB_PGFAIL: u16 = 9;
 pub enum BalloonError {
     /// Activation error: {0}
     Activate(super::ActivateError),
+    /// A guest memory compaction is already in progress.
+    CompactionInProgress,
+    /// Cannot compact the guest memory while the microVM is paused.
+    CompactionWhilePaused,
     /// No balloon device found.
     DeviceNotFound,
     /// Device not activated yet.
@@ -96,7 +115,7 @@ pub enum BalloonError {
     Queue(QueueError),
     /// Error removing a memory region at inflate time: {0}
     RemoveMemoryRegion(RemoveRegionError),
-    /// Error creating the statistics timer: {0}
+    /// Error creating the statistics or compaction timer: {0}
     Timer(std::io::Error),
 }
 
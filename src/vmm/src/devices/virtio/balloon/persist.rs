@@ -31,6 +31,7 @@ pub struct BalloonConfigSpaceState {
 /// at snapshot.
 #[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct BalloonStatsState {
+    oom_deflate_count: u64,
     swap_in: Option<u64>,
     swap_out: Option<u64>,
     major_faults: Option<u64>,
@@ -46,6 +47,7 @@ pub struct BalloonStatsState {
 impl BalloonStatsState {
     fn from_stats(stats: &BalloonStats) -> Self {
         Self {
+            oom_deflate_count: stats.oom_deflate_count,
             swap_in: stats.swap_in,
             swap_out: stats.swap_out,
             major_faults: stats.major_faults,
@@ -65,6 +67,8 @@ impl BalloonStatsState {
             actual_pages: 0,
             target_mib: 0,
             actual_mib: 0,
+            oom_deflate_count: self.oom_deflate_count,
+            compaction_reclaimed_mib: None,
             swap_in: self.swap_in,
             swap_out: self.swap_out,
             major_faults: self.major_faults,
@@ -108,7 +112,9 @@ impl Persist<'_> for Balloon {
             stats_desc_index: self.stats_desc_index,
             latest_stats: BalloonStatsState::from_stats(&self.latest_stats),
             config_space: BalloonConfigSpaceState {
-                num_pages: self.config_space.num_pages,
+                // A compaction in progress is not resumed on restore, the balloon is restored with
+                // its target size from before the compaction instead.
+                num_pages: self.num_pages_before_compaction(),
                 actual_pages: self.config_space.actual_pages,
             },
             virtio_state: VirtioDeviceState::from_device(self),
@@ -119,16 +125,27 @@ impl Persist<'_> for Balloon {
         constructor_args: Self::ConstructorArgs,
         state: &Self::State,
     ) -> Result<Self, Self::Error> {
+        let free_page_reporting =
+            state.virtio_state.avail_features & (1u64 << VIRTIO_BALLOON_F_REPORTING) != 0;
         // We can safely create the balloon with arbitrary flags and
         // num_pages because we will overwrite them after.
-        let mut balloon = Balloon::new(0, false, state.stats_polling_interval_s, true)?;
+        let mut balloon = Balloon::new(
+            0,
+            false,
+            state.stats_polling_interval_s,
+            free_page_reporting,
+            true,
+        )?;
 
         let mut num_queues = BALLOON_NUM_QUEUES;
-        // As per the virtio 1.1 specification, the statistics queue
-        // should not exist if the statistics are not enabled.
+        // As per the virtio 1.1 specification, the statistics and free page
+        // reporting queues should not exist if the features are not enabled.
         if state.stats_polling_interval_s == 0 {
             num_queues -= 1;
         }
+        if !free_page_reporting {
+            num_queues -= 1;
+        }
         balloon.queues = state
             .virtio_state
             .build_queues_checked(
@@ -186,7 +203,7 @@ mod tests {
         let mut mem = vec![0; 4096];
 
         // Create and save the balloon device.
-        let balloon = Balloon::new(0x42, false, 2, false).unwrap();
+        let balloon = Balloon::new(0x42, false, 2, true, false).unwrap();
 
         Snapshot::serialize(&mut mem.as_mut_slice(), &balloon.save()).unwrap();
 
@@ -216,5 +233,6 @@ mod tests {
         );
         assert_eq!(restored_balloon.stats_desc_index, balloon.stats_desc_index);
         assert_eq!(restored_balloon.latest_stats, balloon.latest_stats);
+        assert!(restored_balloon.free_page_reporting());
     }
 }
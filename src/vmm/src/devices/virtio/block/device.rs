@@ -6,14 +6,20 @@ use vmm_sys_util::eventfd::EventFd;
 
 use super::persist::{BlockConstructorArgs, BlockState};
 use super::vhost_user::device::{VhostUserBlock, VhostUserBlockConfig};
+use super::vhost_user::VhostUserBlockError;
 use super::virtio::device::{VirtioBlock, VirtioBlockConfig};
+use super::virtio::VirtioBlockError;
 use super::BlockError;
 use crate::devices::virtio::device::{IrqTrigger, VirtioDevice};
+#[cfg(feature = "fault-injection")]
+use crate::devices::virtio::fault_injection::DeviceFault;
 use crate::devices::virtio::queue::Queue;
+use crate::devices::virtio::vhost_user::VhostUserError;
 use crate::devices::virtio::{ActivateError, TYPE_BLOCK};
 use crate::rate_limiter::BucketUpdate;
 use crate::snapshot::Persist;
 use crate::vmm_config::drive::BlockDeviceConfig;
+use crate::vmm_config::{RateLimiterConfig, RateLimiterState};
 use crate::vstate::memory::GuestMemoryMmap;
 
 // Clippy thinks that values of the enum are too different in size.
@@ -39,6 +45,30 @@ impl Block {
         }
     }
 
+    /// Checks `config` without creating the device, and returns it completed with the default
+    /// values. The backing file or the vhost-user socket must exist, but are not opened.
+    pub fn check_config(config: &BlockDeviceConfig) -> Result<BlockDeviceConfig, BlockError> {
+        if let Ok(config) = VirtioBlockConfig::try_from(config) {
+            config.check().map_err(BlockError::VirtioBackend)?;
+            std::fs::metadata(&config.path_on_host).map_err(|err| {
+                BlockError::VirtioBackend(VirtioBlockError::BackingFile(
+                    err,
+                    config.path_on_host.clone(),
+                ))
+            })?;
+            Ok(config.into())
+        } else if let Ok(config) = VhostUserBlockConfig::try_from(config) {
+            std::fs::metadata(&config.socket).map_err(|err| {
+                BlockError::VhostUserBackend(VhostUserBlockError::VhostUser(
+                    VhostUserError::Connect(err),
+                ))
+            })?;
+            Ok(config.into())
+        } else {
+            Err(BlockError::InvalidBlockConfig)
+        }
+    }
+
     pub fn config(&self) -> BlockDeviceConfig {
         match self {
             Self::Virtio(b) => b.config().into(),
@@ -55,6 +85,13 @@ impl Block {
         }
     }
 
+    pub fn notify_resize(&mut self) -> Result<(), BlockError> {
+        match self {
+            Self::Virtio(b) => b.notify_resize().map_err(BlockError::VirtioBackend),
+            Self::VhostUser(_) => Err(BlockError::InvalidBlockBackend),
+        }
+    }
+
     pub fn update_rate_limiter(
         &mut self,
         bytes: BucketUpdate,
@@ -69,6 +106,20 @@ impl Block {
         }
     }
 
+    pub fn rate_limiter_config(&self) -> Result<RateLimiterConfig, BlockError> {
+        match self {
+            Self::Virtio(b) => Ok(RateLimiterConfig::from(&b.rate_limiter)),
+            Self::VhostUser(_) => Err(BlockError::InvalidBlockBackend),
+        }
+    }
+
+    pub fn rate_limiter_state(&self) -> Result<RateLimiterState, BlockError> {
+        match self {
+            Self::Virtio(b) => Ok(RateLimiterState::from(&b.rate_limiter)),
+            Self::VhostUser(_) => Err(BlockError::InvalidBlockBackend),
+        }
+    }
+
     pub fn update_config(&mut self) -> Result<(), BlockError> {
         match self {
             Self::Virtio(_) => Err(BlockError::InvalidBlockBackend),
@@ -83,6 +134,21 @@ impl Block {
         }
     }
 
+    pub fn quiesce_io(&mut self) {
+        match self {
+            Self::Virtio(b) => b.quiesce_io(),
+            // The vhost-user backend processes the queues on its own.
+            Self::VhostUser(_) => {}
+        }
+    }
+
+    pub fn resume_io(&mut self) {
+        match self {
+            Self::Virtio(b) => b.resume_io(),
+            Self::VhostUser(_) => {}
+        }
+    }
+
     pub fn process_virtio_queues(&mut self) {
         match self {
             Self::Virtio(b) => b.process_virtio_queues(),
@@ -90,6 +156,18 @@ impl Block {
         }
     }
 
+    #[cfg(feature = "fault-injection")]
+    pub fn inject_fault(&mut self, fault: DeviceFault) -> Result<(), BlockError> {
+        match self {
+            Self::Virtio(b) => {
+                b.inject_fault(fault);
+                Ok(())
+            }
+            // The requests are processed by the vhost-user backend.
+            Self::VhostUser(_) => Err(BlockError::InvalidBlockBackend),
+        }
+    }
+
     pub fn id(&self) -> &str {
         match self {
             Self::Virtio(b) => &b.id,
@@ -194,6 +272,13 @@ impl VirtioDevice for Block {
         }
     }
 
+    fn config_space(&self) -> Vec<u8> {
+        match self {
+            Self::Virtio(b) => b.config_space(),
+            Self::VhostUser(b) => b.config_space(),
+        }
+    }
+
     fn activate(&mut self, mem: GuestMemoryMmap) -> Result<(), ActivateError> {
         match self {
             Self::Virtio(b) => b.activate(mem),
@@ -71,6 +71,8 @@ impl TryFrom<&BlockDeviceConfig> for VhostUserBlockConfig {
             && value.path_on_host.is_none()
             && value.rate_limiter.is_none()
             && value.file_engine_type.is_none()
+            && value.direct.is_none()
+            && value.discard.is_none()
         {
             Ok(Self {
                 drive_id: value.drive_id.clone(),
@@ -98,6 +100,8 @@ impl From<VhostUserBlockConfig> for BlockDeviceConfig {
             path_on_host: None,
             rate_limiter: None,
             file_engine_type: None,
+            direct: None,
+            discard: None,
 
             socket: Some(value.socket),
         }
@@ -331,6 +335,10 @@ impl<T: VhostUserHandleBackend + Send + 'static> VirtioDevice for VhostUserBlock
         // Other block config fields are immutable.
     }
 
+    fn config_space(&self) -> Vec<u8> {
+        self.config_space.clone()
+    }
+
     fn activate(&mut self, mem: GuestMemoryMmap) -> Result<(), ActivateError> {
         for q in self.queues.iter_mut() {
             q.initialize(&mem)
@@ -392,6 +400,8 @@ mod tests {
             path_on_host: None,
             rate_limiter: None,
             file_engine_type: None,
+            direct: None,
+            discard: None,
 
             socket: Some("sock".to_string()),
         };
@@ -407,6 +417,8 @@ mod tests {
             path_on_host: Some("path".to_string()),
             rate_limiter: None,
             file_engine_type: Some(FileEngineType::Sync),
+            direct: Some(false),
+            discard: None,
 
             socket: None,
         };
@@ -422,6 +434,8 @@ mod tests {
             path_on_host: Some("path".to_string()),
             rate_limiter: None,
             file_engine_type: Some(FileEngineType::Sync),
+            direct: Some(false),
+            discard: None,
 
             socket: Some("sock".to_string()),
         };
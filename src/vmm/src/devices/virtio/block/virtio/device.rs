@@ -10,6 +10,7 @@ use std::convert::From;
 use std::fs::{File, OpenOptions};
 use std::io::{Seek, SeekFrom, Write};
 use std::os::linux::fs::MetadataExt;
+use std::os::unix::fs::OpenOptionsExt;
 use std::path::PathBuf;
 use std::sync::Arc;
 
@@ -20,14 +21,17 @@ use vmm_sys_util::eventfd::EventFd;
 use super::io::async_io;
 use super::request::*;
 use super::{
-    io as block_io, VirtioBlockError, BLOCK_CONFIG_SPACE_SIZE, BLOCK_QUEUE_SIZES, SECTOR_SHIFT,
-    SECTOR_SIZE,
+    io as block_io, VirtioBlockError, BLOCK_CONFIG_SPACE_SIZE, BLOCK_DISCARD_CONFIG_SPACE_SIZE,
+    BLOCK_QUEUE_SIZES, SECTOR_SHIFT, SECTOR_SIZE,
 };
 use crate::devices::virtio::block::virtio::metrics::{BlockDeviceMetrics, BlockMetricsPerDevice};
 use crate::devices::virtio::block::CacheType;
 use crate::devices::virtio::device::{DeviceState, IrqTrigger, IrqType, VirtioDevice};
+#[cfg(feature = "fault-injection")]
+use crate::devices::virtio::fault_injection::{DeviceFault, FaultInjector};
 use crate::devices::virtio::gen::virtio_blk::{
-    VIRTIO_BLK_F_FLUSH, VIRTIO_BLK_F_RO, VIRTIO_BLK_ID_BYTES, VIRTIO_F_VERSION_1,
+    VIRTIO_BLK_F_DISCARD, VIRTIO_BLK_F_FLUSH, VIRTIO_BLK_F_RO, VIRTIO_BLK_F_WRITE_ZEROES,
+    VIRTIO_BLK_ID_BYTES, VIRTIO_F_VERSION_1,
 };
 use crate::devices::virtio::gen::virtio_ring::VIRTIO_RING_F_EVENT_IDX;
 use crate::devices::virtio::queue::Queue;
@@ -49,27 +53,53 @@ pub enum FileEngineType {
     Sync,
 }
 
+/// Offset of the discard and write zeroes limits in the config space.
+const DISCARD_LIMITS_OFFSET: usize = 36;
+/// Maximum number of sectors of a discard or write zeroes segment.
+const MAX_DISCARD_SECTORS: u32 = u32::MAX;
+/// Maximum number of segments of a discard or write zeroes request.
+const MAX_DISCARD_SEGMENTS: u32 = 1;
+/// Alignment, in sectors, of the discarded ranges, so that the guest discards whole pages of the
+/// backing file.
+const DISCARD_SECTOR_ALIGNMENT: u32 = 8;
+
 /// Helper object for setting up all `Block` fields derived from its backing file.
 #[derive(Debug)]
 pub struct DiskProperties {
     pub file_path: String,
     pub file_engine: FileEngine<PendingRequest>,
+    pub direct: bool,
+    pub read_only: bool,
+    pub discard: bool,
     pub nsectors: u64,
     pub image_id: [u8; VIRTIO_BLK_ID_BYTES as usize],
 }
 
 impl DiskProperties {
     // Helper function that opens the file with the proper access permissions
-    fn open_file(disk_image_path: &str, is_disk_read_only: bool) -> Result<File, VirtioBlockError> {
-        OpenOptions::new()
-            .read(true)
-            .write(!is_disk_read_only)
+    fn open_file(
+        disk_image_path: &str,
+        is_disk_read_only: bool,
+        direct: bool,
+    ) -> Result<File, VirtioBlockError> {
+        let mut options = OpenOptions::new();
+        options.read(true).write(!is_disk_read_only);
+        if direct {
+            options.custom_flags(libc::O_DIRECT);
+        }
+        options
             .open(PathBuf::from(&disk_image_path))
-            .map_err(|x| VirtioBlockError::BackingFile(x, disk_image_path.to_string()))
+            .map_err(|x| match x.raw_os_error() {
+                // open(2) fails with EINVAL if the filesystem does not support O_DIRECT.
+                Some(libc::EINVAL) if direct => {
+                    VirtioBlockError::DirectIoNotSupported(disk_image_path.to_string())
+                }
+                _ => VirtioBlockError::BackingFile(x, disk_image_path.to_string()),
+            })
     }
 
     // Helper function that gets the size of the file
-    fn file_size(disk_image_path: &str, disk_image: &mut File) -> Result<u64, VirtioBlockError> {
+    fn file_size(disk_image_path: &str, mut disk_image: &File) -> Result<u64, VirtioBlockError> {
         let disk_size = disk_image
             .seek(SeekFrom::End(0))
             .map_err(|x| VirtioBlockError::BackingFile(x, disk_image_path.to_string()))?;
@@ -92,15 +122,28 @@ impl DiskProperties {
         disk_image_path: String,
         is_disk_read_only: bool,
         file_engine_type: FileEngineType,
+        direct: bool,
+        discard: bool,
+        metrics: &BlockDeviceMetrics,
     ) -> Result<Self, VirtioBlockError> {
-        let mut disk_image = Self::open_file(&disk_image_path, is_disk_read_only)?;
-        let disk_size = Self::file_size(&disk_image_path, &mut disk_image)?;
+        if direct && file_engine_type != FileEngineType::Sync {
+            return Err(VirtioBlockError::DirectIoEngine);
+        }
+        if discard && is_disk_read_only {
+            return Err(VirtioBlockError::DiscardReadOnly);
+        }
+
+        let disk_image = Self::open_file(&disk_image_path, is_disk_read_only, direct)?;
+        let disk_size = Self::file_size(&disk_image_path, &disk_image)?;
         let image_id = Self::build_disk_image_id(&disk_image);
 
         Ok(Self {
             file_path: disk_image_path,
-            file_engine: FileEngine::from_file(disk_image, file_engine_type)
+            file_engine: FileEngine::from_file(disk_image, file_engine_type, direct, metrics)
                 .map_err(VirtioBlockError::FileEngine)?,
+            direct,
+            read_only: is_disk_read_only,
+            discard,
             nsectors: disk_size >> SECTOR_SHIFT,
             image_id,
         })
@@ -112,19 +155,28 @@ impl DiskProperties {
         disk_image_path: String,
         is_disk_read_only: bool,
     ) -> Result<(), VirtioBlockError> {
-        let mut disk_image = Self::open_file(&disk_image_path, is_disk_read_only)?;
-        let disk_size = Self::file_size(&disk_image_path, &mut disk_image)?;
+        let disk_image = Self::open_file(&disk_image_path, is_disk_read_only, self.direct)?;
+        let disk_size = Self::file_size(&disk_image_path, &disk_image)?;
 
         self.image_id = Self::build_disk_image_id(&disk_image);
         self.file_engine
             .update_file_path(disk_image)
             .map_err(VirtioBlockError::FileEngine)?;
         self.nsectors = disk_size >> SECTOR_SHIFT;
+        self.read_only = is_disk_read_only;
         self.file_path = disk_image_path;
 
         Ok(())
     }
 
+    /// Re-read the size of the file backing the block device
+    pub fn update_size(&mut self) -> Result<(), VirtioBlockError> {
+        let disk_size = Self::file_size(&self.file_path, self.file_engine.file())?;
+        self.nsectors = disk_size >> SECTOR_SHIFT;
+
+        Ok(())
+    }
+
     fn build_device_id(disk_file: &File) -> Result<String, VirtioBlockError> {
         let blk_metadata = disk_file
             .metadata()
@@ -158,13 +210,31 @@ impl DiskProperties {
 
     /// Provides vec containing the virtio block configuration space
     /// buffer. The config space is populated with the disk size based
-    /// on the backing file size.
+    /// on the backing file size, followed by the discard and write zeroes
+    /// limits if discard is enabled.
     pub fn virtio_block_config_space(&self) -> Vec<u8> {
         // The config space is little endian.
-        let mut config = Vec::with_capacity(BLOCK_CONFIG_SPACE_SIZE);
+        let mut config = Vec::with_capacity(BLOCK_DISCARD_CONFIG_SPACE_SIZE);
         for i in 0..BLOCK_CONFIG_SPACE_SIZE {
             config.push(((self.nsectors >> (8 * i)) & 0xff) as u8);
         }
+        if self.discard {
+            // The fields of the features the device does not offer are left zeroed.
+            config.resize(DISCARD_LIMITS_OFFSET, 0);
+            for limit in [
+                MAX_DISCARD_SECTORS,
+                MAX_DISCARD_SEGMENTS,
+                DISCARD_SECTOR_ALIGNMENT,
+                // Write zeroes requests have the same limits as discard requests.
+                MAX_DISCARD_SECTORS,
+                MAX_DISCARD_SEGMENTS,
+            ] {
+                config.extend_from_slice(&limit.to_le_bytes());
+            }
+            // Zeroed ranges may be deallocated.
+            config.push(1);
+            config.resize(BLOCK_DISCARD_CONFIG_SPACE_SIZE, 0);
+        }
         config
     }
 }
@@ -198,6 +268,14 @@ pub struct VirtioBlockConfig {
     #[serde(default)]
     #[serde(rename = "io_engine")]
     pub file_engine_type: FileEngineType,
+    /// If set to true, the backing file is opened with `O_DIRECT`, bypassing the host page
+    /// cache. Only supported by the Sync IO engine.
+    #[serde(default)]
+    pub direct: bool,
+    /// If set to true, the discard and write zeroes requests of the guest deallocate the ranges
+    /// from the backing file. Not supported by read-only drives and the Unsafe cache type.
+    #[serde(default)]
+    pub discard: bool,
 }
 
 impl TryFrom<&BlockDeviceConfig> for VirtioBlockConfig {
@@ -215,6 +293,8 @@ impl TryFrom<&BlockDeviceConfig> for VirtioBlockConfig {
                 path_on_host: value.path_on_host.as_ref().unwrap().clone(),
                 rate_limiter: value.rate_limiter,
                 file_engine_type: value.file_engine_type.unwrap_or_default(),
+                direct: value.direct.unwrap_or(false),
+                discard: value.discard.unwrap_or(false),
             })
         } else {
             Err(VirtioBlockError::Config)
@@ -222,6 +302,24 @@ impl TryFrom<&BlockDeviceConfig> for VirtioBlockConfig {
     }
 }
 
+impl VirtioBlockConfig {
+    /// Checks that the options of the configuration can be combined.
+    pub fn check(&self) -> Result<(), VirtioBlockError> {
+        // Requests are not ordered with the Unsafe cache type, so a discard could be overtaken by
+        // the writes the guest issued before it.
+        if self.discard && self.cache_type == CacheType::Unsafe {
+            return Err(VirtioBlockError::DiscardUnsafeCache);
+        }
+        if self.direct && self.file_engine_type != FileEngineType::Sync {
+            return Err(VirtioBlockError::DirectIoEngine);
+        }
+        if self.discard && self.is_read_only {
+            return Err(VirtioBlockError::DiscardReadOnly);
+        }
+        Ok(())
+    }
+}
+
 impl From<VirtioBlockConfig> for BlockDeviceConfig {
     fn from(value: VirtioBlockConfig) -> Self {
         Self {
@@ -234,12 +332,26 @@ impl From<VirtioBlockConfig> for BlockDeviceConfig {
             path_on_host: Some(value.path_on_host),
             rate_limiter: value.rate_limiter,
             file_engine_type: Some(value.file_engine_type),
+            direct: Some(value.direct),
+            discard: Some(value.discard),
 
             socket: None,
         }
     }
 }
 
+/// Features offered by the virtio block device regardless of its configuration.
+pub(crate) const AVAIL_FEATURES: u64 =
+    (1u64 << VIRTIO_F_VERSION_1) | (1u64 << VIRTIO_RING_F_EVENT_IDX);
+/// Features offered depending on the configuration of the device, with the configuration option
+/// enabling each of them.
+pub(crate) const OPTIONAL_FEATURES: [(u32, &str); 4] = [
+    (VIRTIO_BLK_F_FLUSH, "cache_type"),
+    (VIRTIO_BLK_F_RO, "is_read_only"),
+    (VIRTIO_BLK_F_DISCARD, "discard"),
+    (VIRTIO_BLK_F_WRITE_ZEROES, "discard"),
+];
+
 /// Virtio device for exposing block level read/write operations on a host file.
 #[derive(Debug)]
 pub struct VirtioBlock {
@@ -266,7 +378,10 @@ pub struct VirtioBlock {
     pub disk: DiskProperties,
     pub rate_limiter: RateLimiter,
     pub is_io_engine_throttled: bool,
+    pub is_io_quiesced: bool,
     pub metrics: Arc<BlockDeviceMetrics>,
+    #[cfg(feature = "fault-injection")]
+    pub faults: FaultInjector,
 }
 
 macro_rules! unwrap_async_file_engine_or_return {
@@ -286,10 +401,16 @@ impl VirtioBlock {
     ///
     /// The given file must be seekable and sizable.
     pub fn new(config: VirtioBlockConfig) -> Result<VirtioBlock, VirtioBlockError> {
+        config.check()?;
+
+        let metrics = BlockMetricsPerDevice::alloc(config.drive_id.clone());
         let disk_properties = DiskProperties::new(
             config.path_on_host,
             config.is_read_only,
             config.file_engine_type,
+            config.direct,
+            config.discard,
+            &metrics,
         )?;
 
         let rate_limiter = config
@@ -299,7 +420,7 @@ impl VirtioBlock {
             .map_err(VirtioBlockError::RateLimiter)?
             .unwrap_or_default();
 
-        let mut avail_features = (1u64 << VIRTIO_F_VERSION_1) | (1u64 << VIRTIO_RING_F_EVENT_IDX);
+        let mut avail_features = AVAIL_FEATURES;
 
         if config.cache_type == CacheType::Writeback {
             avail_features |= 1u64 << VIRTIO_BLK_F_FLUSH;
@@ -309,6 +430,10 @@ impl VirtioBlock {
             avail_features |= 1u64 << VIRTIO_BLK_F_RO;
         };
 
+        if config.discard {
+            avail_features |= (1u64 << VIRTIO_BLK_F_DISCARD) | (1u64 << VIRTIO_BLK_F_WRITE_ZEROES);
+        }
+
         let queue_evts = [EventFd::new(libc::EFD_NONBLOCK).map_err(VirtioBlockError::EventFd)?];
 
         let queues = BLOCK_QUEUE_SIZES.iter().map(|&s| Queue::new(s)).collect();
@@ -333,7 +458,10 @@ impl VirtioBlock {
             disk: disk_properties,
             rate_limiter,
             is_io_engine_throttled: false,
-            metrics: BlockMetricsPerDevice::alloc(config.drive_id),
+            is_io_quiesced: false,
+            metrics,
+            #[cfg(feature = "fault-injection")]
+            faults: FaultInjector::default(),
         })
     }
 
@@ -349,6 +477,8 @@ impl VirtioBlock {
             cache_type: self.cache_type,
             rate_limiter: rl.into_option(),
             file_engine_type: self.file_engine_type(),
+            direct: self.disk.direct,
+            discard: self.disk.discard,
         }
     }
 
@@ -365,7 +495,7 @@ impl VirtioBlock {
             self.metrics.rate_limiter_throttled_events.inc();
         } else if self.is_io_engine_throttled {
             self.metrics.io_engine_throttled_events.inc();
-        } else {
+        } else if !self.is_io_quiesced {
             self.process_virtio_queues();
         }
     }
@@ -379,7 +509,7 @@ impl VirtioBlock {
         self.metrics.rate_limiter_event_count.inc();
         // Upon rate limiter event, call the rate limiter handler
         // and restart processing the queue.
-        if self.rate_limiter.event_handler().is_ok() {
+        if self.rate_limiter.event_handler().is_ok() && !self.is_io_quiesced {
             self.process_queue(0);
         }
     }
@@ -407,6 +537,11 @@ impl VirtioBlock {
         // This is safe since we checked in the event handler that the device is activated.
         let mem = self.device_state.mem().unwrap();
 
+        #[cfg(feature = "fault-injection")]
+        if self.faults.is_queue_stalled() {
+            return;
+        }
+
         let queue = &mut self.queues[queue_index];
         let mut used_any = false;
 
@@ -423,6 +558,18 @@ impl VirtioBlock {
                     }
 
                     used_any = true;
+                    #[cfg(feature = "fault-injection")]
+                    if request.r#type == RequestType::In && self.faults.take_block_read_error() {
+                        let finished = request.fail_injected(head.index, mem, &self.metrics);
+                        Self::add_used_descriptor(
+                            queue,
+                            head.index,
+                            finished.num_bytes_to_mem,
+                            &self.irq_trigger,
+                            &self.metrics,
+                        );
+                        continue;
+                    }
                     request.process(&mut self.disk, head.index, mem, &self.metrics)
                 }
                 Err(err) => {
@@ -471,6 +618,7 @@ impl VirtioBlock {
         // This is safe since we checked in the event handler that the device is activated.
         let mem = self.device_state.mem().unwrap();
         let queue = &mut self.queues[0];
+        let mut resubmitted = false;
 
         loop {
             match engine.pop(mem) {
@@ -484,7 +632,44 @@ impl VirtioBlock {
                     let user_data = cqe.user_data();
 
                     let (pending, res) = match res {
-                        Ok(count) => (user_data, Ok(count)),
+                        Ok(count) => {
+                            let mut pending = user_data;
+                            // Short reads and writes are resumed until they complete or stop
+                            // making progress.
+                            match pending.resume_transfer(count) {
+                                None => (pending, Ok(count)),
+                                Some(rest) => {
+                                    let pushed = match rest.r#type {
+                                        RequestType::In => engine.push_read(
+                                            rest.offset,
+                                            mem,
+                                            rest.addr,
+                                            rest.len,
+                                            pending,
+                                        ),
+                                        _ => engine.push_write(
+                                            rest.offset,
+                                            mem,
+                                            rest.addr,
+                                            rest.len,
+                                            pending,
+                                        ),
+                                    };
+                                    match pushed {
+                                        Ok(()) => {
+                                            resubmitted = true;
+                                            continue;
+                                        }
+                                        Err(err) => (
+                                            err.user_data,
+                                            Err(IoErr::FileEngine(block_io::BlockIoError::Async(
+                                                err.error,
+                                            ))),
+                                        ),
+                                    }
+                                }
+                            }
+                        }
                         Err(error) => (
                             user_data,
                             Err(IoErr::FileEngine(block_io::BlockIoError::Async(
@@ -504,6 +689,12 @@ impl VirtioBlock {
                 }
             }
         }
+
+        if resubmitted {
+            if let Err(err) = engine.kick_submission_queue() {
+                error!("BlockError submitting resumed block requests: {:?}", err);
+            }
+        }
     }
 
     pub fn process_async_completion_event(&mut self) {
@@ -516,7 +707,9 @@ impl VirtioBlock {
 
             if self.is_io_engine_throttled {
                 self.is_io_engine_throttled = false;
-                self.process_queue(0);
+                if !self.is_io_quiesced {
+                    self.process_queue(0);
+                }
             }
         }
     }
@@ -533,6 +726,22 @@ impl VirtioBlock {
         Ok(())
     }
 
+    /// Re-read the size of the backing file and notify the driver if the capacity changed.
+    pub fn notify_resize(&mut self) -> Result<(), VirtioBlockError> {
+        let old_nsectors = self.disk.nsectors;
+        self.disk.update_size()?;
+        if self.disk.nsectors == old_nsectors {
+            return Ok(());
+        }
+        self.config_space = self.disk.virtio_block_config_space();
+
+        // Kick the driver to rescan the capacity.
+        self.irq_trigger.trigger_irq(IrqType::Config).unwrap();
+
+        self.metrics.update_count.inc();
+        Ok(())
+    }
+
     /// Updates the parameters for the rate limiter
     pub fn update_rate_limiter(&mut self, bytes: BucketUpdate, ops: BucketUpdate) {
         self.rate_limiter.update_buckets(bytes, ops);
@@ -561,6 +770,56 @@ impl VirtioBlock {
         self.drain_and_flush(false);
         if let FileEngine::Async(ref _engine) = self.disk.file_engine {
             self.process_async_completion_queue();
+            // Completing short reads or writes resubmits their remaining parts, so keep draining
+            // until no operation is in flight.
+            while self.has_inflight_async_ops() {
+                if let Err(err) = self.disk.file_engine.drain_and_flush(false) {
+                    error!(
+                        "Failed to drain resumed ops and flush block data: {:?}",
+                        err
+                    );
+                    break;
+                }
+                self.process_async_completion_queue();
+            }
+        }
+    }
+
+    /// Stop processing the virtio queue and complete the in-flight requests.
+    ///
+    /// Requests made available by the driver in the meantime are processed by `resume_io`.
+    pub fn quiesce_io(&mut self) {
+        self.is_io_quiesced = true;
+        self.prepare_save();
+        // No request is in flight anymore, so the IO engine has room for new ones.
+        self.is_io_engine_throttled = false;
+    }
+
+    /// Resume processing the virtio queue after `quiesce_io`.
+    pub fn resume_io(&mut self) {
+        if !self.is_io_quiesced {
+            return;
+        }
+        self.is_io_quiesced = false;
+        if self.is_activated() {
+            self.process_virtio_queues();
+        }
+    }
+
+    /// Injects `fault` into the processing of the virtio queue.
+    #[cfg(feature = "fault-injection")]
+    pub fn inject_fault(&mut self, fault: DeviceFault) {
+        self.faults.inject(fault);
+        // Process the requests made available while the queue was stalled.
+        if !self.faults.is_queue_stalled() && self.is_activated() && !self.is_io_quiesced {
+            self.process_virtio_queues();
+        }
+    }
+
+    fn has_inflight_async_ops(&self) -> bool {
+        match self.disk.file_engine {
+            FileEngine::Async(ref engine) => engine.num_ops() > 0,
+            FileEngine::Sync(_) => false,
         }
     }
 }
@@ -629,6 +888,10 @@ impl VirtioDevice for VirtioBlock {
         dst.copy_from_slice(data);
     }
 
+    fn config_space(&self) -> Vec<u8> {
+        self.config_space.clone()
+    }
+
     fn activate(&mut self, mem: GuestMemoryMmap) -> Result<(), ActivateError> {
         for q in self.queues.iter_mut() {
             q.initialize(&mem)
@@ -683,9 +946,9 @@ mod tests {
     use super::*;
     use crate::check_metric_after_block;
     use crate::devices::virtio::block::virtio::test_utils::{
-        default_block, read_blk_req_descriptors, set_queue, set_rate_limiter,
-        simulate_async_completion_event, simulate_queue_and_async_completion_events,
-        simulate_queue_event,
+        default_block, default_block_with_path, read_blk_req_descriptors, set_queue,
+        set_rate_limiter, simulate_async_completion_event,
+        simulate_queue_and_async_completion_events, simulate_queue_event,
     };
     use crate::devices::virtio::block::virtio::IO_URING_NUM_ENTRIES;
     use crate::devices::virtio::queue::{VIRTQ_DESC_F_NEXT, VIRTQ_DESC_F_WRITE};
@@ -705,6 +968,8 @@ mod tests {
             path_on_host: Some("path".to_string()),
             rate_limiter: None,
             file_engine_type: Default::default(),
+            direct: Default::default(),
+            discard: Default::default(),
 
             socket: None,
         };
@@ -720,6 +985,8 @@ mod tests {
             path_on_host: None,
             rate_limiter: None,
             file_engine_type: Default::default(),
+            direct: Default::default(),
+            discard: Default::default(),
 
             socket: Some("sock".to_string()),
         };
@@ -735,6 +1002,8 @@ mod tests {
             path_on_host: Some("path".to_string()),
             rate_limiter: None,
             file_engine_type: Default::default(),
+            direct: Default::default(),
+            discard: Default::default(),
 
             socket: Some("sock".to_string()),
         };
@@ -749,9 +1018,15 @@ mod tests {
         f.as_file().set_len(size).unwrap();
 
         for engine in [FileEngineType::Sync, FileEngineType::Async] {
-            let disk_properties =
-                DiskProperties::new(String::from(f.as_path().to_str().unwrap()), true, engine)
-                    .unwrap();
+            let disk_properties = DiskProperties::new(
+                String::from(f.as_path().to_str().unwrap()),
+                true,
+                engine,
+                false,
+                false,
+                &BlockDeviceMetrics::default(),
+            )
+            .unwrap();
 
             assert_eq!(size, u64::from(SECTOR_SIZE) * num_sectors);
             assert_eq!(disk_properties.nsectors, num_sectors);
@@ -763,13 +1038,84 @@ mod tests {
             // Testing `backing_file.virtio_block_disk_image_id()` implies
             // duplicating that logic in tests, so skipping it.
 
-            let res = DiskProperties::new("invalid-disk-path".to_string(), true, engine);
+            let res = DiskProperties::new(
+                "invalid-disk-path".to_string(),
+                true,
+                engine,
+                false,
+                false,
+                &BlockDeviceMetrics::default(),
+            );
             assert!(
                 matches!(res, Err(VirtioBlockError::BackingFile(_, _))),
                 "{:?}",
                 res
             );
         }
+
+        // O_DIRECT is only supported by the Sync engine.
+        let res = DiskProperties::new(
+            String::from(f.as_path().to_str().unwrap()),
+            true,
+            FileEngineType::Async,
+            true,
+            false,
+            &BlockDeviceMetrics::default(),
+        );
+        assert!(
+            matches!(res, Err(VirtioBlockError::DirectIoEngine)),
+            "{:?}",
+            res
+        );
+        match DiskProperties::new(
+            String::from(f.as_path().to_str().unwrap()),
+            true,
+            FileEngineType::Sync,
+            true,
+            false,
+            &BlockDeviceMetrics::default(),
+        ) {
+            Ok(disk_properties) => assert!(disk_properties.direct),
+            // The filesystem backing the temporary file does not support O_DIRECT.
+            Err(VirtioBlockError::DirectIoNotSupported(_)) => (),
+            Err(err) => panic!("{:?}", err),
+        }
+
+        // Discard is not supported by read-only drives.
+        let res = DiskProperties::new(
+            String::from(f.as_path().to_str().unwrap()),
+            true,
+            FileEngineType::Sync,
+            false,
+            true,
+            &BlockDeviceMetrics::default(),
+        );
+        assert!(
+            matches!(res, Err(VirtioBlockError::DiscardReadOnly)),
+            "{:?}",
+            res
+        );
+
+        // The discard limits follow the capacity in the config space.
+        let disk_properties = DiskProperties::new(
+            String::from(f.as_path().to_str().unwrap()),
+            false,
+            FileEngineType::Sync,
+            false,
+            true,
+            &BlockDeviceMetrics::default(),
+        )
+        .unwrap();
+        let cfg = disk_properties.virtio_block_config_space();
+        assert_eq!(cfg.len(), BLOCK_DISCARD_CONFIG_SPACE_SIZE);
+        assert_eq!(cfg[..BLOCK_CONFIG_SPACE_SIZE], num_sectors.to_le_bytes());
+        assert_eq!(cfg[BLOCK_CONFIG_SPACE_SIZE..36], [0u8; 28]);
+        let limits: Vec<u32> = cfg[36..56]
+            .chunks(4)
+            .map(|limit| u32::from_le_bytes(limit.try_into().unwrap()))
+            .collect();
+        assert_eq!(limits, [u32::MAX, 1, 8, u32::MAX, 1]);
+        assert_eq!(cfg[56..], [1, 0, 0, 0]);
     }
 
     #[test]
@@ -795,6 +1141,24 @@ mod tests {
                 block.ack_features_by_page(i, u32::MAX);
             }
             assert_eq!(block.acked_features, features);
+
+            // Discard and write zeroes are offered if discard is enabled.
+            let f = TempFile::new().unwrap();
+            f.as_file().set_len(0x1000).unwrap();
+            let config = VirtioBlockConfig {
+                discard: true,
+                cache_type: CacheType::Writeback,
+                ..default_block_with_path(f.as_path().to_str().unwrap().to_string(), engine)
+                    .config()
+            };
+            let block = VirtioBlock::new(config).unwrap();
+            assert_eq!(
+                block.avail_features(),
+                features
+                    | (1u64 << VIRTIO_BLK_F_FLUSH)
+                    | (1u64 << VIRTIO_BLK_F_DISCARD)
+                    | (1u64 << VIRTIO_BLK_F_WRITE_ZEROES)
+            );
         }
     }
 
@@ -1033,6 +1397,60 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_read_only_write() {
+        for engine in [FileEngineType::Sync, FileEngineType::Async] {
+            let f = TempFile::new().unwrap();
+            f.as_file().set_len(0x1000).unwrap();
+            let config = VirtioBlockConfig {
+                is_read_only: true,
+                ..default_block_with_path(f.as_path().to_str().unwrap().to_string(), engine)
+                    .config()
+            };
+            let mut block = VirtioBlock::new(config).unwrap();
+            let mem = default_mem();
+            let vq = VirtQueue::new(GuestAddress(0), &mem, 16);
+            set_queue(&mut block, 0, vq.create_queue());
+            block.activate(mem.clone()).unwrap();
+            read_blk_req_descriptors(&vq);
+
+            let request_type_addr = GuestAddress(vq.dtable[0].addr.get());
+            let data_addr = GuestAddress(vq.dtable[1].addr.get());
+            let status_addr = GuestAddress(vq.dtable[2].addr.get());
+
+            mem.write_obj::<u32>(VIRTIO_BLK_T_OUT, request_type_addr)
+                .unwrap();
+            vq.dtable[1].flags.set(VIRTQ_DESC_F_NEXT);
+            mem.write_slice(&[0xff; 512], data_addr).unwrap();
+
+            check_metric_after_block!(
+                &block.metrics.readonly_write_rejects,
+                1,
+                simulate_queue_event(&mut block, Some(true))
+            );
+
+            assert_eq!(vq.used.idx.get(), 1);
+            assert_eq!(vq.used.ring[0].get().id, 0);
+            // Only the status byte was written.
+            assert_eq!(vq.used.ring[0].get().len, 1);
+            assert_eq!(
+                mem.read_obj::<u8>(status_addr).unwrap(),
+                u8::try_from(VIRTIO_BLK_S_IOERR).unwrap()
+            );
+
+            // The data wasn't written to the file.
+            let mut buf = [0u8; 512];
+            block
+                .disk
+                .file_engine
+                .file()
+                .seek(SeekFrom::Start(0))
+                .unwrap();
+            block.disk.file_engine.file().read_exact(&mut buf).unwrap();
+            assert_eq!(buf, [0u8; 512]);
+        }
+    }
+
     #[test]
     fn test_end_of_region() {
         for engine in [FileEngineType::Sync, FileEngineType::Async] {
@@ -1396,6 +1814,48 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_short_read() {
+        for engine in [FileEngineType::Sync, FileEngineType::Async] {
+            let mut block = default_block(engine);
+            let mem = default_mem();
+            let vq = VirtQueue::new(GuestAddress(0), &mem, 16);
+            set_queue(&mut block, 0, vq.create_queue());
+            block.activate(mem.clone()).unwrap();
+            read_blk_req_descriptors(&vq);
+
+            let request_type_addr = GuestAddress(vq.dtable[0].addr.get());
+            let status_addr = GuestAddress(vq.dtable[2].addr.get());
+            mem.write_obj::<u32>(VIRTIO_BLK_T_IN, request_type_addr)
+                .unwrap();
+
+            // Shrink the backing file so that only half of the requested data can be read.
+            block.disk.file_engine.file().set_len(0x800).unwrap();
+
+            check_metric_after_block!(
+                &block.metrics.invalid_reqs_count,
+                1,
+                match engine {
+                    FileEngineType::Sync => simulate_queue_event(&mut block, Some(true)),
+                    FileEngineType::Async => {
+                        simulate_queue_event(&mut block, None);
+                        // The remaining part of the short read is resubmitted.
+                        simulate_async_completion_event(&mut block, false);
+                        // It does not make any progress, so the request fails.
+                        simulate_async_completion_event(&mut block, true);
+                    }
+                }
+            );
+
+            assert_eq!(vq.used.idx.get(), 1);
+            assert_eq!(vq.used.ring[0].get().id, 0);
+            assert_eq!(
+                mem.read_obj::<u32>(status_addr).unwrap(),
+                VIRTIO_BLK_S_IOERR
+            );
+        }
+    }
+
     #[test]
     fn test_flush() {
         for engine in [FileEngineType::Sync, FileEngineType::Async] {
@@ -1442,6 +1902,122 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_discard() {
+        for engine in [FileEngineType::Sync, FileEngineType::Async] {
+            let f = TempFile::new().unwrap();
+            f.as_file().write_all(&[0xff; 0x2000]).unwrap();
+            f.as_file().sync_all().unwrap();
+            let config = VirtioBlockConfig {
+                discard: true,
+                cache_type: CacheType::Writeback,
+                ..default_block_with_path(f.as_path().to_str().unwrap().to_string(), engine)
+                    .config()
+            };
+            let mut block = VirtioBlock::new(config).unwrap();
+            let mem = default_mem();
+            let vq = VirtQueue::new(GuestAddress(0), &mem, 16);
+            set_queue(&mut block, 0, vq.create_queue());
+            block.activate(mem.clone()).unwrap();
+            read_blk_req_descriptors(&vq);
+
+            let request_type_addr = GuestAddress(vq.dtable[0].addr.get());
+            let data_addr = GuestAddress(vq.dtable[1].addr.get());
+            let status_addr = GuestAddress(vq.dtable[2].addr.get());
+
+            // The segment is read from a device-readable data descriptor.
+            vq.dtable[1].flags.set(VIRTQ_DESC_F_NEXT);
+            vq.dtable[1].len.set(DISCARD_SEGMENT_SIZE);
+            mem.write_obj::<u32>(VIRTIO_BLK_T_DISCARD, request_type_addr)
+                .unwrap();
+            mem.write_obj(DiscardSegment::new(0, 8), data_addr).unwrap();
+
+            let blocks_before = f.as_file().metadata().unwrap().st_blocks();
+            check_metric_after_block!(
+                &block.metrics.discard_count,
+                1,
+                simulate_queue_event(&mut block, Some(true))
+            );
+
+            assert_eq!(vq.used.idx.get(), 1);
+            assert_eq!(vq.used.ring[0].get().id, 0);
+            assert_eq!(vq.used.ring[0].get().len, 1);
+            assert_eq!(mem.read_obj::<u32>(status_addr).unwrap(), VIRTIO_BLK_S_OK);
+
+            // The discarded range reads as zeros and was deallocated, the rest of the file is
+            // left untouched.
+            let mut buf = [0u8; 0x2000];
+            f.as_file().seek(SeekFrom::Start(0)).unwrap();
+            f.as_file().read_exact(&mut buf).unwrap();
+            assert_eq!(buf[..0x1000], [0u8; 0x1000]);
+            assert_eq!(buf[0x1000..], [0xffu8; 0x1000]);
+            assert!(f.as_file().metadata().unwrap().st_blocks() < blocks_before);
+            assert_eq!(f.as_file().metadata().unwrap().len(), 0x2000);
+
+            // Segments past the end of the disk are rejected.
+            vq.used.idx.set(0);
+            set_queue(&mut block, 0, vq.create_queue());
+            mem.write_obj::<u32>(VIRTIO_BLK_T_WRITE_ZEROES, request_type_addr)
+                .unwrap();
+            mem.write_obj(DiscardSegment::new(8, 16), data_addr)
+                .unwrap();
+            simulate_queue_event(&mut block, Some(true));
+            assert_eq!(vq.used.idx.get(), 1);
+            assert_eq!(
+                mem.read_obj::<u32>(status_addr).unwrap(),
+                VIRTIO_BLK_S_IOERR
+            );
+        }
+    }
+
+    #[test]
+    fn test_discard_not_enabled() {
+        for engine in [FileEngineType::Sync, FileEngineType::Async] {
+            let mut block = default_block(engine);
+            let mem = default_mem();
+            let vq = VirtQueue::new(GuestAddress(0), &mem, 16);
+            set_queue(&mut block, 0, vq.create_queue());
+            block.activate(mem.clone()).unwrap();
+            read_blk_req_descriptors(&vq);
+
+            let request_type_addr = GuestAddress(vq.dtable[0].addr.get());
+            let data_addr = GuestAddress(vq.dtable[1].addr.get());
+            let status_addr = GuestAddress(vq.dtable[2].addr.get());
+
+            vq.dtable[1].flags.set(VIRTQ_DESC_F_NEXT);
+            vq.dtable[1].len.set(DISCARD_SEGMENT_SIZE);
+            mem.write_obj::<u32>(VIRTIO_BLK_T_DISCARD, request_type_addr)
+                .unwrap();
+            mem.write_obj(DiscardSegment::new(0, 8), data_addr).unwrap();
+
+            simulate_queue_event(&mut block, Some(true));
+            assert_eq!(vq.used.idx.get(), 1);
+            assert_eq!(
+                mem.read_obj::<u32>(status_addr).unwrap(),
+                VIRTIO_BLK_S_UNSUPP
+            );
+            assert_eq!(block.metrics.discard_count.count(), 0);
+        }
+
+        // The Unsafe cache type does not order the requests.
+        let f = TempFile::new().unwrap();
+        f.as_file().set_len(0x1000).unwrap();
+        let config = VirtioBlockConfig {
+            discard: true,
+            ..default_block_with_path(
+                f.as_path().to_str().unwrap().to_string(),
+                FileEngineType::Sync,
+            )
+            .config()
+        };
+        let res = VirtioBlock::new(config);
+        assert!(
+            matches!(res, Err(VirtioBlockError::DiscardUnsafeCache)),
+            "{:?}",
+            res
+        );
+    }
+
     #[test]
     fn test_get_device_id() {
         for engine in [FileEngineType::Sync, FileEngineType::Async] {
@@ -1646,6 +2222,100 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_quiesce_io() {
+        for engine in [FileEngineType::Sync, FileEngineType::Async] {
+            let mut block = default_block(engine);
+
+            let mem = default_mem();
+            let vq = VirtQueue::new(GuestAddress(0), &mem, 16);
+            block.activate(mem.clone()).unwrap();
+
+            // In-flight requests are completed when IO is quiesced.
+            add_flush_requests_batch(&mut block, &vq, 5);
+            simulate_queue_event(&mut block, None);
+            block.quiesce_io();
+            check_flush_requests_batch(5, &vq);
+            assert!(!block.has_inflight_async_ops());
+            assert!(!block.is_io_engine_throttled);
+
+            // Requests made available while IO is quiesced are not processed.
+            add_flush_requests_batch(&mut block, &vq, 5);
+            simulate_queue_event(&mut block, None);
+            assert_eq!(vq.used.idx.get(), 0);
+
+            // They are processed once IO is resumed.
+            block.resume_io();
+            if engine == FileEngineType::Async {
+                simulate_async_completion_event(&mut block, true);
+            }
+            check_flush_requests_batch(5, &vq);
+            assert!(!block.is_io_quiesced);
+        }
+    }
+
+    #[test]
+    #[cfg(feature = "fault-injection")]
+    fn test_fault_injection() {
+        for engine in [FileEngineType::Sync, FileEngineType::Async] {
+            let mut block = default_block(engine);
+            let mem = default_mem();
+            let vq = VirtQueue::new(GuestAddress(0), &mem, 16);
+            set_queue(&mut block, 0, vq.create_queue());
+            block.activate(mem.clone()).unwrap();
+            read_blk_req_descriptors(&vq);
+
+            let request_type_addr = GuestAddress(vq.dtable[0].addr.get());
+            let status_addr = GuestAddress(vq.dtable[2].addr.get());
+            mem.write_obj::<u32>(VIRTIO_BLK_T_IN, request_type_addr)
+                .unwrap();
+
+            // The injected read failure is reported to the guest as an IO error, without
+            // reaching the IO engine.
+            block.inject_fault(DeviceFault::BlockReadError { count: 2 });
+            check_metric_after_block!(
+                &block.metrics.invalid_reqs_count,
+                1,
+                simulate_queue_event(&mut block, Some(true))
+            );
+            assert_eq!(vq.used.idx.get(), 1);
+            assert_eq!(vq.used.ring[0].get().len, 1);
+            assert_eq!(
+                u32::from(mem.read_obj::<u8>(status_addr).unwrap()),
+                VIRTIO_BLK_S_IOERR
+            );
+
+            // Once the faults are cleared, the same read succeeds.
+            block.inject_fault(DeviceFault::Clear);
+            vq.used.idx.set(0);
+            set_queue(&mut block, 0, vq.create_queue());
+            simulate_queue_and_async_completion_events(&mut block, true);
+            assert_eq!(vq.used.idx.get(), 1);
+            assert_eq!(vq.used.ring[0].get().len, vq.dtable[1].len.get() + 1);
+            assert_eq!(
+                u32::from(mem.read_obj::<u8>(status_addr).unwrap()),
+                VIRTIO_BLK_S_OK
+            );
+
+            // Requests made available while the queue is stalled are processed once the faults
+            // are cleared.
+            block.inject_fault(DeviceFault::QueueStall);
+            vq.used.idx.set(0);
+            set_queue(&mut block, 0, vq.create_queue());
+            simulate_queue_event(&mut block, Some(false));
+            assert_eq!(vq.used.idx.get(), 0);
+            block.inject_fault(DeviceFault::Clear);
+            if engine == FileEngineType::Async {
+                simulate_async_completion_event(&mut block, true);
+            }
+            assert_eq!(vq.used.idx.get(), 1);
+            assert_eq!(
+                u32::from(mem.read_obj::<u8>(status_addr).unwrap()),
+                VIRTIO_BLK_S_OK
+            );
+        }
+    }
+
     #[test]
     fn test_bandwidth_rate_limiter() {
         for engine in [FileEngineType::Sync, FileEngineType::Async] {
@@ -1823,4 +2493,36 @@ mod tests {
             assert_eq!(block.disk.image_id, id.as_slice());
         }
     }
+
+    #[test]
+    fn test_notify_resize() {
+        for engine in [FileEngineType::Sync, FileEngineType::Async] {
+            let mut block = default_block(engine);
+            assert_eq!(block.disk.nsectors, 0x1000 >> SECTOR_SHIFT);
+
+            // Nothing changed, so the driver is not notified.
+            check_metric_after_block!(
+                block.metrics.update_count,
+                0,
+                block.notify_resize().unwrap()
+            );
+            assert!(!block.irq_trigger.has_pending_irq(IrqType::Config));
+
+            // Grow the backing file.
+            block.disk.file_engine.file().set_len(0x4000).unwrap();
+            check_metric_after_block!(
+                block.metrics.update_count,
+                1,
+                block.notify_resize().unwrap()
+            );
+            assert!(block.irq_trigger.has_pending_irq(IrqType::Config));
+
+            let new_nsectors: u64 = 0x4000 >> SECTOR_SHIFT;
+            assert_eq!(block.disk.nsectors, new_nsectors);
+            assert_eq!(block.config_space, new_nsectors.to_le_bytes());
+            let mut capacity = [0u8; BLOCK_CONFIG_SPACE_SIZE];
+            block.read_config(0, &mut capacity);
+            assert_eq!(u64::from_le_bytes(capacity), new_nsectors);
+        }
+    }
 }
@@ -9,7 +9,7 @@ use std::os::unix::io::AsRawFd;
 use vm_memory::GuestMemoryError;
 use vmm_sys_util::eventfd::EventFd;
 
-use crate::devices::virtio::block::virtio::io::UserDataError;
+use crate::devices::virtio::block::virtio::io::{punch_hole, write_zeroes, UserDataError};
 use crate::devices::virtio::block::virtio::IO_URING_NUM_ENTRIES;
 use crate::io_uring::operation::{Cqe, OpCode, Operation};
 use crate::io_uring::restriction::Restriction;
@@ -31,6 +31,10 @@ pub enum AsyncIoError {
     EventFd(std::io::Error),
     /// GuestMemory: {0}
     GuestMemory(GuestMemoryError),
+    /// PunchHole: {0}
+    PunchHole(std::io::Error),
+    /// WriteZeroes: {0}
+    WriteZeroes(std::io::Error),
 }
 
 #[derive(Debug)]
@@ -118,6 +122,21 @@ impl<T: Debug> AsyncFileEngine<T> {
         &self.file
     }
 
+    /// Deallocates `len` bytes of the file at `offset`. The ring does not allow fallocate
+    /// operations, so the hole is punched synchronously, once the operations in flight completed
+    /// so that none of them lands in the hole. Their completions are still reported by `pop`.
+    pub fn punch_hole(&mut self, offset: u64, len: u64) -> Result<(), AsyncIoError> {
+        self.drain(false)?;
+        punch_hole(&self.file, offset, len).map_err(AsyncIoError::PunchHole)
+    }
+
+    /// Zeroes `len` bytes of the file at `offset`, synchronously for the same reasons as
+    /// `punch_hole`.
+    pub fn write_zeroes(&mut self, offset: u64, len: u64) -> Result<(), AsyncIoError> {
+        self.drain(false)?;
+        write_zeroes(&self.file, offset, len).map_err(AsyncIoError::WriteZeroes)
+    }
+
     pub fn completion_evt(&self) -> &EventFd {
         &self.completion_evt
     }
@@ -201,6 +220,11 @@ impl<T: Debug> AsyncFileEngine<T> {
             })
     }
 
+    /// Number of operations that were pushed and not yet popped.
+    pub fn num_ops(&self) -> u32 {
+        self.ring.num_ops()
+    }
+
     pub fn kick_submission_queue(&mut self) -> Result<(), AsyncIoError> {
         self.ring
             .submit()
@@ -6,10 +6,16 @@ pub mod sync_io;
 
 use std::fmt::Debug;
 use std::fs::File;
+use std::io::{Seek, SeekFrom, Write};
+use std::os::unix::io::AsRawFd;
 
 pub use self::async_io::{AsyncFileEngine, AsyncIoError};
+use self::sync_io::AlignedBuffer;
 pub use self::sync_io::{SyncFileEngine, SyncIoError};
 use crate::devices::virtio::block::virtio::device::FileEngineType;
+use crate::devices::virtio::block::virtio::metrics::BlockDeviceMetrics;
+use crate::logger::{warn, IncMetric};
+use crate::utils::u64_to_usize;
 use crate::vstate::memory::{GuestAddress, GuestMemoryMmap};
 
 #[derive(Debug, PartialEq, Eq)]
@@ -41,6 +47,66 @@ impl BlockIoError {
     }
 }
 
+/// Maximum size of the buffer of zeroes written when the file system cannot zero a range itself.
+const WRITE_ZEROES_BUFFER_LEN: u64 = 1 << 16;
+
+/// Changes the allocation of `len` bytes of `file` at `offset` with `fallocate` in `mode`.
+fn fallocate(file: &File, mode: libc::c_int, offset: u64, len: u64) -> std::io::Result<()> {
+    let (Ok(offset), Ok(len)) = (libc::off_t::try_from(offset), libc::off_t::try_from(len)) else {
+        return Err(std::io::Error::from_raw_os_error(libc::EINVAL));
+    };
+    // SAFETY: `fallocate` does not access memory, and the file descriptor is valid while `file`
+    // is borrowed.
+    let ret = unsafe { libc::fallocate(file.as_raw_fd(), mode, offset, len) };
+    if ret < 0 {
+        return Err(std::io::Error::last_os_error());
+    }
+    Ok(())
+}
+
+/// Deallocates `len` bytes of `file` at `offset`, which then read as zeroes, without changing the
+/// size of the file.
+fn punch_hole(file: &File, offset: u64, len: u64) -> std::io::Result<()> {
+    fallocate(
+        file,
+        libc::FALLOC_FL_KEEP_SIZE | libc::FALLOC_FL_PUNCH_HOLE,
+        offset,
+        len,
+    )
+}
+
+/// Zeroes `len` bytes of `file` at `offset` without changing the size of the file. The range is
+/// deallocated if the file system supports it, or else zeroed by the file system, and zeroes are
+/// only written as a last resort.
+fn write_zeroes(file: &File, offset: u64, len: u64) -> std::io::Result<()> {
+    write_zeroes_with(file, offset, len, fallocate)
+}
+
+/// Same as `write_zeroes`, changing the allocation of the range with `fallocate`.
+fn write_zeroes_with<F>(file: &File, offset: u64, len: u64, fallocate: F) -> std::io::Result<()>
+where
+    F: Fn(&File, libc::c_int, u64, u64) -> std::io::Result<()>,
+{
+    for mode in [libc::FALLOC_FL_PUNCH_HOLE, libc::FALLOC_FL_ZERO_RANGE] {
+        match fallocate(file, libc::FALLOC_FL_KEEP_SIZE | mode, offset, len) {
+            Err(err) if err.raw_os_error() == Some(libc::EOPNOTSUPP) => {}
+            res => return res,
+        }
+    }
+
+    // The buffer is aligned so that it can also be written to files opened with `O_DIRECT`.
+    let mut buf = AlignedBuffer::new(u64_to_usize(len.min(WRITE_ZEROES_BUFFER_LEN)));
+    let mut file = file;
+    file.seek(SeekFrom::Start(offset))?;
+    let mut remaining = len;
+    while remaining > 0 {
+        let count = remaining.min(WRITE_ZEROES_BUFFER_LEN);
+        file.write_all(buf.as_mut_slice(u64_to_usize(count)))?;
+        remaining -= count;
+    }
+    Ok(())
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct UserDataError<T, E> {
     pub user_data: T,
@@ -56,15 +122,57 @@ pub enum FileEngine<T> {
 }
 
 impl<T: Debug> FileEngine<T> {
+    /// Create a new engine of the given type. `direct` must be set if `file` was opened with
+    /// `O_DIRECT`, which is only supported by the Sync engine.
+    ///
+    /// Falls back to the Sync engine if the host kernel does not support io_uring, which is
+    /// counted in the `io_engine_fallbacks` metric.
     pub fn from_file(
         file: File,
         engine_type: FileEngineType,
+        direct: bool,
+        metrics: &BlockDeviceMetrics,
     ) -> Result<FileEngine<T>, BlockIoError> {
+        Self::from_file_with(
+            file,
+            engine_type,
+            direct,
+            metrics,
+            AsyncFileEngine::from_file,
+        )
+    }
+
+    /// Same as `from_file`, creating the Async engine with `async_engine`.
+    fn from_file_with<F>(
+        file: File,
+        engine_type: FileEngineType,
+        direct: bool,
+        metrics: &BlockDeviceMetrics,
+        async_engine: F,
+    ) -> Result<FileEngine<T>, BlockIoError>
+    where
+        F: FnOnce(File) -> Result<AsyncFileEngine<T>, AsyncIoError>,
+    {
         match engine_type {
-            FileEngineType::Async => Ok(FileEngine::Async(
-                AsyncFileEngine::from_file(file).map_err(BlockIoError::Async)?,
-            )),
-            FileEngineType::Sync => Ok(FileEngine::Sync(SyncFileEngine::from_file(file))),
+            FileEngineType::Async => {
+                // The Async engine takes ownership of the file, keep a handle for the fallback.
+                let async_file = file
+                    .try_clone()
+                    .map_err(|err| BlockIoError::Async(AsyncIoError::IO(err)))?;
+                match async_engine(async_file) {
+                    Ok(engine) => Ok(FileEngine::Async(engine)),
+                    Err(AsyncIoError::IoUring(err)) if err.is_unsupported_err() => {
+                        warn!(
+                            "The host kernel does not support the Async block IO engine ({err}), \
+                             falling back to the Sync engine."
+                        );
+                        metrics.io_engine_fallbacks.inc();
+                        Ok(FileEngine::Sync(SyncFileEngine::from_file(file, direct)))
+                    }
+                    Err(err) => Err(BlockIoError::Async(err)),
+                }
+            }
+            FileEngineType::Sync => Ok(FileEngine::Sync(SyncFileEngine::from_file(file, direct))),
         }
     }
 
@@ -166,6 +274,54 @@ impl<T: Debug> FileEngine<T> {
         }
     }
 
+    /// Deallocates `len` bytes of the backing file at `offset`. The operation is executed
+    /// synchronously by both engines.
+    pub fn punch_hole(
+        &mut self,
+        offset: u64,
+        len: u64,
+        user_data: T,
+    ) -> Result<FileEngineOk<T>, UserDataError<T, BlockIoError>> {
+        let res = match self {
+            FileEngine::Async(engine) => {
+                engine.punch_hole(offset, len).map_err(BlockIoError::Async)
+            }
+            FileEngine::Sync(engine) => engine.punch_hole(offset, len).map_err(BlockIoError::Sync),
+        };
+        match res {
+            Ok(()) => Ok(FileEngineOk::Executed(UserDataOk {
+                user_data,
+                count: 0,
+            })),
+            Err(error) => Err(UserDataError { user_data, error }),
+        }
+    }
+
+    /// Zeroes `len` bytes of the backing file at `offset`. The operation is executed
+    /// synchronously by both engines.
+    pub fn write_zeroes(
+        &mut self,
+        offset: u64,
+        len: u64,
+        user_data: T,
+    ) -> Result<FileEngineOk<T>, UserDataError<T, BlockIoError>> {
+        let res = match self {
+            FileEngine::Async(engine) => engine
+                .write_zeroes(offset, len)
+                .map_err(BlockIoError::Async),
+            FileEngine::Sync(engine) => {
+                engine.write_zeroes(offset, len).map_err(BlockIoError::Sync)
+            }
+        };
+        match res {
+            Ok(()) => Ok(FileEngineOk::Executed(UserDataOk {
+                user_data,
+                count: 0,
+            })),
+            Err(error) => Err(UserDataError { user_data, error }),
+        }
+    }
+
     pub fn drain(&mut self, discard: bool) -> Result<(), BlockIoError> {
         match self {
             FileEngine::Async(engine) => engine.drain(discard).map_err(BlockIoError::Async),
@@ -192,7 +348,8 @@ pub mod tests {
 
     use super::*;
     use crate::devices::virtio::block::virtio::device::FileEngineType;
-    use crate::utils::u64_to_usize;
+    use crate::devices::virtio::block::virtio::SECTOR_SIZE;
+    use crate::io_uring::IoUringError;
     use crate::vmm_config::machine_config::HugePageConfig;
     use crate::vstate::memory::{Bitmap, Bytes, GuestMemory, GuestMemoryExtension};
 
@@ -253,7 +410,13 @@ pub mod tests {
         let mem = create_mem();
         // Create backing file.
         let file = TempFile::new().unwrap().into_file();
-        let mut engine = FileEngine::from_file(file, FileEngineType::Sync).unwrap();
+        let mut engine = FileEngine::from_file(
+            file,
+            FileEngineType::Sync,
+            false,
+            &BlockDeviceMetrics::default(),
+        )
+        .unwrap();
 
         let data = vmm_sys_util::rand::rand_alphanumerics(FILE_LEN as usize)
             .as_bytes()
@@ -315,11 +478,82 @@ pub mod tests {
         engine.drain_and_flush(true).unwrap();
     }
 
+    #[test]
+    fn test_sync_direct() {
+        use std::fs::OpenOptions;
+        use std::os::unix::fs::OpenOptionsExt;
+
+        let tmp = TempFile::new().unwrap();
+        let file = match OpenOptions::new()
+            .read(true)
+            .write(true)
+            .custom_flags(libc::O_DIRECT)
+            .open(tmp.as_path())
+        {
+            Ok(file) => file,
+            // The filesystem backing the temporary file does not support O_DIRECT.
+            Err(err) if err.raw_os_error() == Some(libc::EINVAL) => return,
+            Err(err) => panic!("{err}"),
+        };
+        let mut engine = FileEngine::from_file(
+            file,
+            FileEngineType::Sync,
+            true,
+            &BlockDeviceMetrics::default(),
+        )
+        .unwrap();
+
+        let len = 2 * SECTOR_SIZE;
+        let data = vmm_sys_util::rand::rand_alphanumerics(len as usize)
+            .as_bytes()
+            .to_vec();
+
+        // Guest buffers do not need to be aligned, only the offset and size of the transfer.
+        for addr in [GuestAddress(0), GuestAddress(100)] {
+            let mem = create_mem();
+            mem.write(&data, addr).unwrap();
+            assert_sync_execution!(
+                engine.write(u64::from(SECTOR_SIZE), &mem, addr, len, ()),
+                len
+            );
+
+            let mem = create_mem();
+            assert_sync_execution!(
+                engine.read(u64::from(SECTOR_SIZE), &mem, addr, len, ()),
+                len
+            );
+            let mut buf = vec![0u8; len as usize];
+            mem.read_slice(&mut buf, addr).unwrap();
+            assert_eq!(buf, data);
+            check_dirty_mem(&mem, addr, len);
+        }
+
+        // Transfers that are not aligned to the sector size are rejected.
+        let mem = create_mem();
+        for (offset, count) in [(1, SECTOR_SIZE), (0, 50)] {
+            let err = engine
+                .read(offset, &mem, GuestAddress(0), count, ())
+                .unwrap_err();
+            assert!(matches!(
+                err.error,
+                BlockIoError::Sync(SyncIoError::UnalignedDirectIo(o, c)) if o == offset && c == count
+            ));
+        }
+
+        engine.flush(()).unwrap();
+    }
+
     #[test]
     fn test_async() {
         // Create backing file.
         let file = TempFile::new().unwrap().into_file();
-        let mut engine = FileEngine::<()>::from_file(file, FileEngineType::Async).unwrap();
+        let mut engine = FileEngine::<()>::from_file(
+            file,
+            FileEngineType::Async,
+            false,
+            &BlockDeviceMetrics::default(),
+        )
+        .unwrap();
 
         let data = vmm_sys_util::rand::rand_alphanumerics(FILE_LEN as usize)
             .as_bytes()
@@ -372,4 +606,117 @@ pub mod tests {
         engine.drain(true).unwrap();
         engine.drain_and_flush(true).unwrap();
     }
+
+    #[test]
+    fn test_async_fallback() {
+        let metrics = BlockDeviceMetrics::default();
+
+        // The Sync engine is used if the host kernel does not support io_uring.
+        let file = TempFile::new().unwrap().into_file();
+        let mut engine =
+            FileEngine::<()>::from_file_with(file, FileEngineType::Async, false, &metrics, |_| {
+                Err(AsyncIoError::IoUring(IoUringError::Setup(
+                    std::io::Error::from_raw_os_error(libc::ENOSYS),
+                )))
+            })
+            .unwrap();
+        assert!(matches!(engine, FileEngine::Sync(_)));
+        assert_eq!(metrics.io_engine_fallbacks.count(), 1);
+
+        let data = vmm_sys_util::rand::rand_alphanumerics(FILE_LEN as usize)
+            .as_bytes()
+            .to_vec();
+        let mem = create_mem();
+        mem.write(&data, GuestAddress(0)).unwrap();
+        assert_sync_execution!(
+            engine.write(0, &mem, GuestAddress(0), FILE_LEN, ()),
+            FILE_LEN
+        );
+        let mem = create_mem();
+        assert_sync_execution!(
+            engine.read(0, &mem, GuestAddress(0), FILE_LEN, ()),
+            FILE_LEN
+        );
+        let mut buf = vec![0u8; FILE_LEN as usize];
+        mem.read_slice(&mut buf, GuestAddress(0)).unwrap();
+        assert_eq!(buf, data);
+
+        // Other errors are reported.
+        let file = TempFile::new().unwrap().into_file();
+        let res =
+            FileEngine::<()>::from_file_with(file, FileEngineType::Async, false, &metrics, |_| {
+                Err(AsyncIoError::IoUring(IoUringError::Setup(
+                    std::io::Error::from_raw_os_error(libc::ENOMEM),
+                )))
+            });
+        assert!(matches!(
+            res,
+            Err(BlockIoError::Async(AsyncIoError::IoUring(
+                IoUringError::Setup(_)
+            )))
+        ));
+        assert_eq!(metrics.io_engine_fallbacks.count(), 1);
+
+        // The Async engine is used if it can be created.
+        let file = TempFile::new().unwrap().into_file();
+        let engine =
+            FileEngine::<()>::from_file(file, FileEngineType::Async, false, &metrics).unwrap();
+        assert!(matches!(engine, FileEngine::Async(_)));
+        assert_eq!(metrics.io_engine_fallbacks.count(), 1);
+    }
+
+    #[test]
+    fn test_write_zeroes_fallback() {
+        let unsupported = || Err(std::io::Error::from_raw_os_error(libc::EOPNOTSUPP));
+
+        for supported_mode in [libc::FALLOC_FL_PUNCH_HOLE, libc::FALLOC_FL_ZERO_RANGE, 0] {
+            let file = TempFile::new().unwrap().into_file();
+            (&file).write_all(&[0xff; 0x3000]).unwrap();
+
+            // The modes are tried in order until one is supported, and zeroes are written if none
+            // is.
+            let modes = std::cell::RefCell::new(Vec::new());
+            write_zeroes_with(&file, 0x800, 0x2000, |_, mode, offset, len| {
+                assert_eq!((offset, len), (0x800, 0x2000));
+                modes.borrow_mut().push(mode & !libc::FALLOC_FL_KEEP_SIZE);
+                match mode & !libc::FALLOC_FL_KEEP_SIZE {
+                    mode if mode == supported_mode => Ok(()),
+                    _ => unsupported(),
+                }
+            })
+            .unwrap();
+            let expected_modes = match supported_mode {
+                libc::FALLOC_FL_PUNCH_HOLE => vec![libc::FALLOC_FL_PUNCH_HOLE],
+                _ => vec![libc::FALLOC_FL_PUNCH_HOLE, libc::FALLOC_FL_ZERO_RANGE],
+            };
+            assert_eq!(modes.into_inner(), expected_modes);
+
+            let mut buf = vec![0u8; 0x3000];
+            (&file).seek(SeekFrom::Start(0)).unwrap();
+            std::io::Read::read_exact(&mut &file, &mut buf).unwrap();
+            let zeroed = supported_mode == 0;
+            assert_eq!(buf[..0x800], [0xffu8; 0x800]);
+            assert_eq!(buf[0x800..0x2800].iter().all(|&b| b == 0), zeroed);
+            assert_eq!(buf[0x2800..], [0xffu8; 0x800]);
+            assert_eq!(file.metadata().unwrap().len(), 0x3000);
+        }
+
+        // Other errors are returned without falling back.
+        let file = TempFile::new().unwrap().into_file();
+        let err = write_zeroes_with(&file, 0, 0x200, |_, _, _, _| {
+            Err(std::io::Error::from_raw_os_error(libc::EIO))
+        })
+        .unwrap_err();
+        assert_eq!(err.raw_os_error(), Some(libc::EIO));
+
+        // The range really is zeroed by the file system.
+        let file = TempFile::new().unwrap().into_file();
+        (&file).write_all(&[0xff; 0x2000]).unwrap();
+        write_zeroes(&file, 0x1000, 0x1000).unwrap();
+        let mut buf = vec![0u8; 0x2000];
+        (&file).seek(SeekFrom::Start(0)).unwrap();
+        std::io::Read::read_exact(&mut &file, &mut buf).unwrap();
+        assert_eq!(buf[..0x1000], [0xffu8; 0x1000]);
+        assert_eq!(buf[0x1000..], [0u8; 0x1000]);
+    }
 }
@@ -1,12 +1,24 @@
 // Copyright 2021 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+use std::alloc::{self, Layout};
 use std::fs::File;
-use std::io::{Seek, SeekFrom, Write};
+use std::io::{Read, Seek, SeekFrom, Write};
+use std::ptr::NonNull;
 
 use vm_memory::{GuestMemoryError, ReadVolatile, WriteVolatile};
 
-use crate::vstate::memory::{GuestAddress, GuestMemory, GuestMemoryMmap};
+use super::{punch_hole, write_zeroes};
+use crate::devices::virtio::block::virtio::SECTOR_SIZE;
+use crate::vstate::memory::{Bytes, GuestAddress, GuestMemory, GuestMemoryMmap};
+
+/// Alignment of the bounce buffer used for `O_DIRECT` transfers.
+///
+/// `O_DIRECT` requires the memory buffer, the file offset and the transfer size to be aligned to
+/// the logical block size of the backing storage. Guest buffers carry no alignment guarantees, so
+/// transfers go through a host buffer aligned to the page size. File offsets and sizes are already
+/// multiples of the sector size for well formed virtio block requests.
+pub const DIRECT_IO_BUFFER_ALIGNMENT: usize = 4096;
 
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
 pub enum SyncIoError {
@@ -18,19 +30,100 @@ pub enum SyncIoError {
     SyncAll(std::io::Error),
     /// Transfer: {0}
     Transfer(GuestMemoryError),
+    /// Transfer stopped making progress after {0} out of {1} bytes.
+    ShortTransfer(u32, u32),
+    /// O_DIRECT transfer at offset {0} of {1} bytes is not aligned to the sector size.
+    UnalignedDirectIo(u64, u32),
+    /// PunchHole: {0}
+    PunchHole(std::io::Error),
+    /// WriteZeroes: {0}
+    WriteZeroes(std::io::Error),
+}
+
+/// Calls `transfer` with the number of bytes already transferred until all `count` bytes are
+/// transferred. Interrupted transfers are retried, while a transfer that makes no progress fails
+/// with `SyncIoError::ShortTransfer`.
+fn transfer_all<F>(count: u32, mut transfer: F) -> Result<(), SyncIoError>
+where
+    F: FnMut(usize) -> Result<usize, GuestMemoryError>,
+{
+    let mut done = 0;
+    while done < count as usize {
+        match transfer(done) {
+            Ok(0) => {
+                // `done` is smaller than `count`, so it fits in a u32.
+                return Err(SyncIoError::ShortTransfer(
+                    u32::try_from(done).unwrap(),
+                    count,
+                ));
+            }
+            Ok(len) => done += len,
+            Err(GuestMemoryError::IOError(err))
+                if err.kind() == std::io::ErrorKind::Interrupted => {}
+            Err(err) => return Err(SyncIoError::Transfer(err)),
+        }
+    }
+    Ok(())
+}
+
+/// Heap allocated buffer aligned to `DIRECT_IO_BUFFER_ALIGNMENT`.
+#[derive(Debug)]
+pub(super) struct AlignedBuffer {
+    ptr: NonNull<u8>,
+    layout: Layout,
+}
+
+impl AlignedBuffer {
+    pub(super) fn new(len: usize) -> Self {
+        // The length is rounded up so that the allocation is never empty.
+        let layout = Layout::from_size_align(
+            len.max(1).next_multiple_of(DIRECT_IO_BUFFER_ALIGNMENT),
+            DIRECT_IO_BUFFER_ALIGNMENT,
+        )
+        .expect("Invalid O_DIRECT buffer layout");
+        // SAFETY: The layout has a non-zero size.
+        let ptr = unsafe { alloc::alloc_zeroed(layout) };
+        let ptr = NonNull::new(ptr).unwrap_or_else(|| alloc::handle_alloc_error(layout));
+        AlignedBuffer { ptr, layout }
+    }
+
+    pub(super) fn len(&self) -> usize {
+        self.layout.size()
+    }
+
+    pub(super) fn as_mut_slice(&mut self, len: usize) -> &mut [u8] {
+        assert!(len <= self.len());
+        // SAFETY: The allocation is valid, initialized and at least `len` bytes long. The mutable
+        // borrow of `self` guarantees exclusive access.
+        unsafe { std::slice::from_raw_parts_mut(self.ptr.as_ptr(), len) }
+    }
+}
+
+impl Drop for AlignedBuffer {
+    fn drop(&mut self) {
+        // SAFETY: The pointer was allocated with the same layout in `AlignedBuffer::new`.
+        unsafe { alloc::dealloc(self.ptr.as_ptr(), self.layout) }
+    }
 }
 
 #[derive(Debug)]
 pub struct SyncFileEngine {
     file: File,
+    // Bounce buffer used when the file was opened with `O_DIRECT`.
+    direct_buffer: Option<AlignedBuffer>,
 }
 
-// SAFETY: `File` is send and ultimately a POD.
+// SAFETY: `File` is send and ultimately a POD. The direct IO buffer is owned by the engine and
+// never shared.
 unsafe impl Send for SyncFileEngine {}
 
 impl SyncFileEngine {
-    pub fn from_file(file: File) -> SyncFileEngine {
-        SyncFileEngine { file }
+    /// Create a new engine. `direct` must be set if `file` was opened with `O_DIRECT`.
+    pub fn from_file(file: File, direct: bool) -> SyncFileEngine {
+        SyncFileEngine {
+            file,
+            direct_buffer: direct.then(|| AlignedBuffer::new(0)),
+        }
     }
 
     #[cfg(test)]
@@ -53,9 +146,21 @@ impl SyncFileEngine {
         self.file
             .seek(SeekFrom::Start(offset))
             .map_err(SyncIoError::Seek)?;
-        mem.get_slice(addr, count as usize)
-            .and_then(|mut slice| Ok(self.file.read_exact_volatile(&mut slice)?))
+        if let Some(buf) = Self::direct_buffer(&mut self.direct_buffer, offset, count)? {
+            transfer_all(count, |done| {
+                self.file
+                    .read(&mut buf[done..])
+                    .map_err(GuestMemoryError::IOError)
+            })?;
+            mem.write_slice(buf, addr).map_err(SyncIoError::Transfer)?;
+            return Ok(count);
+        }
+        let slice = mem
+            .get_slice(addr, count as usize)
             .map_err(SyncIoError::Transfer)?;
+        transfer_all(count, |done| {
+            Ok(self.file.read_volatile(&mut slice.offset(done)?)?)
+        })?;
         Ok(count)
     }
 
@@ -69,12 +174,51 @@ impl SyncFileEngine {
         self.file
             .seek(SeekFrom::Start(offset))
             .map_err(SyncIoError::Seek)?;
-        mem.get_slice(addr, count as usize)
-            .and_then(|slice| Ok(self.file.write_all_volatile(&slice)?))
+        if let Some(buf) = Self::direct_buffer(&mut self.direct_buffer, offset, count)? {
+            mem.read_slice(buf, addr).map_err(SyncIoError::Transfer)?;
+            transfer_all(count, |done| {
+                self.file
+                    .write(&buf[done..])
+                    .map_err(GuestMemoryError::IOError)
+            })?;
+            return Ok(count);
+        }
+        let slice = mem
+            .get_slice(addr, count as usize)
             .map_err(SyncIoError::Transfer)?;
+        transfer_all(count, |done| {
+            Ok(self.file.write_volatile(&slice.offset(done)?)?)
+        })?;
         Ok(count)
     }
 
+    // Returns the bounce buffer to use for a transfer of `count` bytes at `offset`, or `None` if
+    // the engine does not use `O_DIRECT`.
+    fn direct_buffer(
+        direct_buffer: &mut Option<AlignedBuffer>,
+        offset: u64,
+        count: u32,
+    ) -> Result<Option<&mut [u8]>, SyncIoError> {
+        let Some(buf) = direct_buffer else {
+            return Ok(None);
+        };
+        if offset % u64::from(SECTOR_SIZE) != 0 || count % SECTOR_SIZE != 0 {
+            return Err(SyncIoError::UnalignedDirectIo(offset, count));
+        }
+        if buf.len() < count as usize {
+            *buf = AlignedBuffer::new(count as usize);
+        }
+        Ok(Some(buf.as_mut_slice(count as usize)))
+    }
+
+    pub fn punch_hole(&mut self, offset: u64, len: u64) -> Result<(), SyncIoError> {
+        punch_hole(&self.file, offset, len).map_err(SyncIoError::PunchHole)
+    }
+
+    pub fn write_zeroes(&mut self, offset: u64, len: u64) -> Result<(), SyncIoError> {
+        write_zeroes(&self.file, offset, len).map_err(SyncIoError::WriteZeroes)
+    }
+
     pub fn flush(&mut self) -> Result<(), SyncIoError> {
         // flush() first to force any cached data out of rust buffers.
         self.file.flush().map_err(SyncIoError::Flush)?;
@@ -82,3 +226,59 @@ impl SyncFileEngine {
         self.file.sync_all().map_err(SyncIoError::SyncAll)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use std::io::{Error, ErrorKind};
+
+    use super::*;
+
+    #[test]
+    fn test_transfer_all() {
+        // Short transfers are resumed until complete.
+        let mut offsets = vec![];
+        transfer_all(0x1000, |done| {
+            offsets.push(done);
+            Ok(0x400)
+        })
+        .unwrap();
+        assert_eq!(offsets, vec![0, 0x400, 0x800, 0xc00]);
+
+        // Interrupted transfers are retried.
+        let mut interrupted = false;
+        transfer_all(0x1000, |_| {
+            if interrupted {
+                return Ok(0x1000);
+            }
+            interrupted = true;
+            Err(GuestMemoryError::IOError(Error::from(
+                ErrorKind::Interrupted,
+            )))
+        })
+        .unwrap();
+
+        // A transfer that stops making progress fails.
+        let mut remaining = 0x600;
+        let res = transfer_all(0x1000, |_| {
+            let len = remaining.min(0x200);
+            remaining -= len;
+            Ok(len)
+        });
+        assert!(matches!(
+            res,
+            Err(SyncIoError::ShortTransfer(0x600, 0x1000))
+        ));
+
+        // Other errors are propagated.
+        let res = transfer_all(0x1000, |_| {
+            Err(GuestMemoryError::IOError(Error::from(
+                ErrorKind::PermissionDenied,
+            )))
+        });
+        assert!(matches!(
+            res,
+            Err(SyncIoError::Transfer(GuestMemoryError::IOError(err)))
+                if err.kind() == ErrorKind::PermissionDenied
+        ));
+    }
+}
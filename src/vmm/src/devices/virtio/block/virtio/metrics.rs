@@ -84,7 +84,7 @@ use std::sync::{Arc, RwLock};
 use serde::ser::SerializeMap;
 use serde::{Serialize, Serializer};
 
-use crate::logger::{IncMetric, LatencyAggregateMetrics, SharedIncMetric};
+use crate::logger::{IncMetric, LatencyAggregateMetrics, LatencyAggregateValues, SharedIncMetric};
 
 /// map of block drive id and metrics
 /// this should be protected by a lock before accessing.
@@ -110,6 +110,12 @@ impl BlockMetricsPerDevice {
                 .or_insert_with(|| Arc::new(BlockDeviceMetrics::default())),
         )
     }
+
+    /// Get the `BlockDeviceMetrics` of the block device having id `drive_id`,
+    /// if they were allocated.
+    pub fn get(drive_id: &str) -> Option<Arc<BlockDeviceMetrics>> {
+        METRICS.read().unwrap().metrics.get(drive_id).cloned()
+    }
 }
 
 /// Pool of block-related metrics per device behind a lock to
@@ -157,6 +163,8 @@ pub struct BlockDeviceMetrics {
     pub invalid_reqs_count: SharedIncMetric,
     /// Number of flushes operation triggered on this block device.
     pub flush_count: SharedIncMetric,
+    /// Number of successful discard and write zeroes operations.
+    pub discard_count: SharedIncMetric,
     /// Number of events triggered on the queue of this block device.
     pub queue_event_count: SharedIncMetric,
     /// Number of events ratelimiter-related.
@@ -182,8 +190,68 @@ pub struct BlockDeviceMetrics {
     /// Number of virtio events throttled because of the IO engine.
     /// This happens when the io_uring submission queue is full.
     pub io_engine_throttled_events: SharedIncMetric,
+    /// Number of times the Sync IO engine was used instead of the Async one, because the host
+    /// kernel does not support io_uring.
+    pub io_engine_fallbacks: SharedIncMetric,
     /// Number of remaining requests in the queue.
     pub remaining_reqs_count: SharedIncMetric,
+    /// Number of write requests rejected because the device is read-only.
+    pub readonly_write_rejects: SharedIncMetric,
+}
+
+/// Current values of the metrics of a block device, as returned by `BlockDeviceMetrics::values`.
+/// Reading them does not reset the metrics: the counters hold the totals since the device was
+/// created, while a metrics flush reports the increase since the previous flush.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct BlockDeviceMetricsValues {
+    /// Number of times when activate failed on a block device.
+    pub activate_fails: u64,
+    /// Number of times when interacting with the space config of a block device failed.
+    pub cfg_fails: u64,
+    /// No available buffer for the block queue.
+    pub no_avail_buffer: u64,
+    /// Number of times when handling events on a block device failed.
+    pub event_fails: u64,
+    /// Number of failures in executing a request on a block device.
+    pub execute_fails: u64,
+    /// Number of invalid requests received for this block device.
+    pub invalid_reqs_count: u64,
+    /// Number of flushes operation triggered on this block device.
+    pub flush_count: u64,
+    /// Number of successful discard and write zeroes operations.
+    pub discard_count: u64,
+    /// Number of events triggered on the queue of this block device.
+    pub queue_event_count: u64,
+    /// Number of events ratelimiter-related.
+    pub rate_limiter_event_count: u64,
+    /// Number of update operation triggered on this block device.
+    pub update_count: u64,
+    /// Number of failures while doing update on this block device.
+    pub update_fails: u64,
+    /// Number of bytes read by this block device.
+    pub read_bytes: u64,
+    /// Number of bytes written by this block device.
+    pub write_bytes: u64,
+    /// Number of successful read operations.
+    pub read_count: u64,
+    /// Number of successful write operations.
+    pub write_count: u64,
+    /// Duration of all read operations.
+    pub read_agg: LatencyAggregateValues,
+    /// Duration of all write operations.
+    pub write_agg: LatencyAggregateValues,
+    /// Number of rate limiter throttling events.
+    pub rate_limiter_throttled_events: u64,
+    /// Number of virtio events throttled because of the IO engine.
+    /// This happens when the io_uring submission queue is full.
+    pub io_engine_throttled_events: u64,
+    /// Number of times the Sync IO engine was used instead of the Async one, because the host
+    /// kernel does not support io_uring.
+    pub io_engine_fallbacks: u64,
+    /// Number of remaining requests in the queue.
+    pub remaining_reqs_count: u64,
+    /// Number of write requests rejected because the device is read-only.
+    pub readonly_write_rejects: u64,
 }
 
 impl BlockDeviceMetrics {
@@ -210,6 +278,7 @@ impl BlockDeviceMetrics {
         self.invalid_reqs_count
             .add(other.invalid_reqs_count.fetch_diff());
         self.flush_count.add(other.flush_count.fetch_diff());
+        self.discard_count.add(other.discard_count.fetch_diff());
         self.queue_event_count
             .add(other.queue_event_count.fetch_diff());
         self.rate_limiter_event_count
@@ -228,8 +297,41 @@ impl BlockDeviceMetrics {
             .add(other.rate_limiter_throttled_events.fetch_diff());
         self.io_engine_throttled_events
             .add(other.io_engine_throttled_events.fetch_diff());
+        self.io_engine_fallbacks
+            .add(other.io_engine_fallbacks.fetch_diff());
         self.remaining_reqs_count
             .add(other.remaining_reqs_count.fetch_diff());
+        self.readonly_write_rejects
+            .add(other.readonly_write_rejects.fetch_diff());
+    }
+
+    /// Reads the current values of the metrics without resetting them.
+    pub fn values(&self) -> BlockDeviceMetricsValues {
+        BlockDeviceMetricsValues {
+            activate_fails: self.activate_fails.count(),
+            cfg_fails: self.cfg_fails.count(),
+            no_avail_buffer: self.no_avail_buffer.count(),
+            event_fails: self.event_fails.count(),
+            execute_fails: self.execute_fails.count(),
+            invalid_reqs_count: self.invalid_reqs_count.count(),
+            flush_count: self.flush_count.count(),
+            discard_count: self.discard_count.count(),
+            queue_event_count: self.queue_event_count.count(),
+            rate_limiter_event_count: self.rate_limiter_event_count.count(),
+            update_count: self.update_count.count(),
+            update_fails: self.update_fails.count(),
+            read_bytes: self.read_bytes.count(),
+            write_bytes: self.write_bytes.count(),
+            read_count: self.read_count.count(),
+            write_count: self.write_count.count(),
+            read_agg: self.read_agg.values(),
+            write_agg: self.write_agg.values(),
+            rate_limiter_throttled_events: self.rate_limiter_throttled_events.count(),
+            io_engine_throttled_events: self.io_engine_throttled_events.count(),
+            io_engine_fallbacks: self.io_engine_fallbacks.count(),
+            remaining_reqs_count: self.remaining_reqs_count.count(),
+            readonly_write_rejects: self.readonly_write_rejects.count(),
+        }
     }
 }
 
@@ -324,4 +426,19 @@ pub mod tests {
         assert!(test_metrics.read_bytes.count() >= 5);
         assert!(test_metrics.read_bytes.count() <= 15);
     }
+
+    #[test]
+    fn test_block_dev_metrics_values() {
+        // The values read through the API have the same fields as the flushed metrics, which
+        // tests/host_tools/fcmetrics.py checks. Until the first flush, they also hold the same
+        // values.
+        let metrics = BlockDeviceMetrics::default();
+        metrics.read_count.inc();
+        metrics.write_bytes.add(5);
+        metrics.read_agg.update_with_elapsed_time(0);
+
+        let values = serde_json::to_value(metrics.values()).unwrap();
+        let flushed = serde_json::to_value(&metrics).unwrap();
+        assert_eq!(values, flushed);
+    }
 }
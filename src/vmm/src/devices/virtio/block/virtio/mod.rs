@@ -5,7 +5,7 @@
 
 pub mod device;
 mod event_handler;
-mod io;
+pub mod io;
 pub mod metrics;
 pub mod persist;
 pub mod request;
@@ -20,6 +20,9 @@ use crate::devices::virtio::queue::FIRECRACKER_MAX_QUEUE_SIZE;
 
 /// Size of config space for block device.
 pub const BLOCK_CONFIG_SPACE_SIZE: usize = 8;
+/// Size of config space for block device offering discard and write zeroes, which describes
+/// their limits after the fields of the features the device does not offer.
+pub const BLOCK_DISCARD_CONFIG_SPACE_SIZE: usize = 60;
 /// Sector shift for block device.
 pub const SECTOR_SHIFT: u8 = 9;
 /// Size of block sector.
@@ -57,6 +60,14 @@ pub enum VirtioBlockError {
     FileEngine(io::BlockIoError),
     /// Error manipulating the backing file: {0} {1}
     BackingFile(std::io::Error, String),
+    /// The backing file does not support O_DIRECT: {0}
+    DirectIoNotSupported(String),
+    /// O_DIRECT is only supported by the Sync IO engine.
+    DirectIoEngine,
+    /// Discard cannot be enabled on a read-only drive.
+    DiscardReadOnly,
+    /// Discard cannot be enabled with the Unsafe cache type, which does not order the requests.
+    DiscardUnsafeCache,
     /// Error opening eventfd: {0}
     EventFd(std::io::Error),
     /// Error creating an irqfd: {0}
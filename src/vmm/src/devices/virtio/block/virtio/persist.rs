@@ -15,7 +15,7 @@ use crate::devices::virtio::block::persist::BlockConstructorArgs;
 use crate::devices::virtio::block::virtio::device::FileEngineType;
 use crate::devices::virtio::block::virtio::metrics::BlockMetricsPerDevice;
 use crate::devices::virtio::device::{DeviceState, IrqTrigger};
-use crate::devices::virtio::gen::virtio_blk::VIRTIO_BLK_F_RO;
+use crate::devices::virtio::gen::virtio_blk::{VIRTIO_BLK_F_DISCARD, VIRTIO_BLK_F_RO};
 use crate::devices::virtio::persist::VirtioDeviceState;
 use crate::devices::virtio::TYPE_BLOCK;
 use crate::rate_limiter::persist::RateLimiterState;
@@ -63,6 +63,7 @@ pub struct VirtioBlockState {
     virtio_state: VirtioDeviceState,
     rate_limiter_state: RateLimiterState,
     file_engine_type: FileEngineTypeState,
+    direct: bool,
 }
 
 impl Persist<'_> for VirtioBlock {
@@ -81,6 +82,7 @@ impl Persist<'_> for VirtioBlock {
             virtio_state: VirtioDeviceState::from_device(self),
             rate_limiter_state: self.rate_limiter.save(),
             file_engine_type: FileEngineTypeState::from(self.file_engine_type()),
+            direct: self.disk.direct,
         }
     }
 
@@ -89,13 +91,18 @@ impl Persist<'_> for VirtioBlock {
         state: &Self::State,
     ) -> Result<Self, Self::Error> {
         let is_read_only = state.virtio_state.avail_features & (1u64 << VIRTIO_BLK_F_RO) != 0;
+        let discard = state.virtio_state.avail_features & (1u64 << VIRTIO_BLK_F_DISCARD) != 0;
         let rate_limiter = RateLimiter::restore((), &state.rate_limiter_state)
             .map_err(VirtioBlockError::RateLimiter)?;
 
+        let metrics = BlockMetricsPerDevice::alloc(state.id.clone());
         let disk_properties = DiskProperties::new(
             state.disk_path.clone(),
             is_read_only,
             state.file_engine_type.into(),
+            state.direct,
+            discard,
+            &metrics,
         )?;
 
         let queue_evts = [EventFd::new(libc::EFD_NONBLOCK).map_err(VirtioBlockError::EventFd)?];
@@ -142,7 +149,10 @@ impl Persist<'_> for VirtioBlock {
             disk: disk_properties,
             rate_limiter,
             is_io_engine_throttled: false,
-            metrics: BlockMetricsPerDevice::alloc(state.id.clone()),
+            is_io_quiesced: false,
+            metrics,
+            #[cfg(feature = "fault-injection")]
+            faults: Default::default(),
         })
     }
 }
@@ -174,6 +184,8 @@ mod tests {
             cache_type: CacheType::Writeback,
             rate_limiter: None,
             file_engine_type: FileEngineType::default(),
+            direct: false,
+            discard: false,
         };
 
         let block = VirtioBlock::new(config).unwrap();
@@ -216,6 +228,8 @@ mod tests {
             cache_type: CacheType::Unsafe,
             rate_limiter: None,
             file_engine_type: FileEngineType::default(),
+            direct: false,
+            discard: false,
         };
 
         let block = VirtioBlock::new(config).unwrap();
@@ -246,5 +260,7 @@ mod tests {
 
         // Test that block specific fields are the same.
         assert_eq!(restored_block.disk.file_path, block.disk.file_path);
+        assert_eq!(restored_block.disk.direct, block.disk.direct);
+        assert_eq!(restored_block.disk.discard, block.disk.discard);
     }
 }
@@ -14,18 +14,29 @@ use crate::devices::virtio::block::virtio::device::DiskProperties;
 use crate::devices::virtio::block::virtio::metrics::BlockDeviceMetrics;
 pub use crate::devices::virtio::gen::virtio_blk::{
     VIRTIO_BLK_ID_BYTES, VIRTIO_BLK_S_IOERR, VIRTIO_BLK_S_OK, VIRTIO_BLK_S_UNSUPP,
-    VIRTIO_BLK_T_FLUSH, VIRTIO_BLK_T_GET_ID, VIRTIO_BLK_T_IN, VIRTIO_BLK_T_OUT,
+    VIRTIO_BLK_T_DISCARD, VIRTIO_BLK_T_FLUSH, VIRTIO_BLK_T_GET_ID, VIRTIO_BLK_T_IN,
+    VIRTIO_BLK_T_OUT, VIRTIO_BLK_T_WRITE_ZEROES,
 };
 use crate::devices::virtio::queue::DescriptorChain;
 use crate::logger::{error, IncMetric};
 use crate::rate_limiter::{RateLimiter, TokenType};
-use crate::vstate::memory::{ByteValued, Bytes, GuestAddress, GuestMemoryMmap};
+use crate::vstate::memory::{Address, ByteValued, Bytes, GuestAddress, GuestMemoryMmap};
 
 #[derive(Debug, derive_more::From)]
 pub enum IoErr {
     GetId(GuestMemoryError),
-    PartialTransfer { completed: u32, expected: u32 },
+    PartialTransfer {
+        completed: u32,
+        expected: u32,
+    },
     FileEngine(block_io::BlockIoError),
+    DiscardSegment(GuestMemoryError),
+    DiscardOutOfRange {
+        sector: u64,
+        num_sectors: u32,
+    },
+    #[cfg(feature = "fault-injection")]
+    Injected,
 }
 
 #[derive(Clone, Copy, Debug, PartialEq, Eq)]
@@ -34,6 +45,8 @@ pub enum RequestType {
     Out,
     Flush,
     GetDeviceID,
+    Discard,
+    WriteZeroes,
     Unsupported(u32),
 }
 
@@ -44,11 +57,27 @@ impl From<u32> for RequestType {
             VIRTIO_BLK_T_OUT => RequestType::Out,
             VIRTIO_BLK_T_FLUSH => RequestType::Flush,
             VIRTIO_BLK_T_GET_ID => RequestType::GetDeviceID,
+            VIRTIO_BLK_T_DISCARD => RequestType::Discard,
+            VIRTIO_BLK_T_WRITE_ZEROES => RequestType::WriteZeroes,
             t => RequestType::Unsupported(t),
         }
     }
 }
 
+impl From<RequestType> for u32 {
+    fn from(request_type: RequestType) -> u32 {
+        match request_type {
+            RequestType::In => VIRTIO_BLK_T_IN,
+            RequestType::Out => VIRTIO_BLK_T_OUT,
+            RequestType::Flush => VIRTIO_BLK_T_FLUSH,
+            RequestType::GetDeviceID => VIRTIO_BLK_T_GET_ID,
+            RequestType::Discard => VIRTIO_BLK_T_DISCARD,
+            RequestType::WriteZeroes => VIRTIO_BLK_T_WRITE_ZEROES,
+            RequestType::Unsupported(id) => id,
+        }
+    }
+}
+
 #[derive(Debug)]
 pub enum ProcessingResult {
     Submitted,
@@ -67,6 +96,7 @@ enum Status {
     Ok { num_bytes_to_mem: u32 },
     IoErr { num_bytes_to_mem: u32, err: IoErr },
     Unsupported { op: u32 },
+    ReadOnly,
 }
 
 impl Status {
@@ -95,9 +125,46 @@ pub struct PendingRequest {
     data_len: u32,
     status_addr: GuestAddress,
     desc_idx: u16,
+    offset: u64,
+    data_addr: GuestAddress,
+    // Bytes transferred by previous IO engine operations, if the request was resumed after a
+    // short read or write.
+    transferred: u32,
+}
+
+/// The remaining part of a read or write request that was only partially served by the IO engine.
+#[derive(Debug, PartialEq, Eq)]
+pub struct ResumedTransfer {
+    pub r#type: RequestType,
+    pub offset: u64,
+    pub addr: GuestAddress,
+    pub len: u32,
 }
 
 impl PendingRequest {
+    /// Accounts for an IO engine operation that transferred `count` bytes of the request data.
+    /// Returns the remaining part of the transfer if the operation was a short read or write that
+    /// still made progress, in which case the remaining part should be submitted again. Once
+    /// `None` is returned, the request can be finished with the `count` of the last operation.
+    pub fn resume_transfer(&mut self, count: u32) -> Option<ResumedTransfer> {
+        if !matches!(self.r#type, RequestType::In | RequestType::Out) || count == 0 {
+            return None;
+        }
+
+        let transferred = self.transferred.checked_add(count)?;
+        if transferred >= self.data_len {
+            return None;
+        }
+
+        self.transferred = transferred;
+        Some(ResumedTransfer {
+            r#type: self.r#type,
+            offset: self.offset + u64::from(transferred),
+            addr: self.data_addr.unchecked_add(u64::from(transferred)),
+            len: self.data_len - transferred,
+        })
+    }
+
     fn write_status_and_finish(
         self,
         status: &Status,
@@ -124,6 +191,12 @@ impl PendingRequest {
                 error!("Received unsupported virtio block request: {}", op);
                 (0, u8::try_from(VIRTIO_BLK_S_UNSUPP).unwrap())
             }
+            Status::ReadOnly => {
+                block_metrics.readonly_write_rejects.inc();
+                error!("Rejected virtio block write request to a read-only device");
+                // The virtio spec requires writes to a read-only device to fail with an IO error.
+                (0, u8::try_from(VIRTIO_BLK_S_IOERR).unwrap())
+            }
         };
 
         let num_bytes_to_mem = mem
@@ -150,6 +223,8 @@ impl PendingRequest {
         res: Result<u32, IoErr>,
         block_metrics: &BlockDeviceMetrics,
     ) -> FinishedRequest {
+        // Account for the data transferred before the request was resumed.
+        let res = res.map(|count| self.transferred.saturating_add(count));
         let status = match (res, self.r#type) {
             (Ok(transferred_data_len), RequestType::In) => {
                 let status = Status::from_data(self.data_len, transferred_data_len, true);
@@ -176,6 +251,12 @@ impl PendingRequest {
             (Ok(transferred_data_len), RequestType::GetDeviceID) => {
                 Status::from_data(self.data_len, transferred_data_len, true)
             }
+            (Ok(_), RequestType::Discard | RequestType::WriteZeroes) => {
+                block_metrics.discard_count.inc();
+                Status::Ok {
+                    num_bytes_to_mem: 0,
+                }
+            }
             (_, RequestType::Unsupported(op)) => Status::Unsupported { op },
             (Err(err), _) => Status::IoErr {
                 num_bytes_to_mem: 0,
@@ -231,6 +312,55 @@ impl RequestHeader {
     }
 }
 
+/// A segment of a discard or write zeroes request, read from the data descriptor.
+///
+/// A segment contains the following fields:
+///   * sector: an u64 value representing the first sector of the range.
+///   * num_sectors: an u32 value representing the number of sectors of the range.
+///   * flags: an u32 value holding the unmap flag of write zeroes requests.
+///
+/// The range is deallocated from the backing file, whether the unmap flag is set or not, as the
+/// deallocated range reads back as zeroes.
+#[derive(Debug, Copy, Clone, Default)]
+#[repr(C)]
+pub struct DiscardSegment {
+    sector: u64,
+    num_sectors: u32,
+    flags: u32,
+}
+
+// SAFETY: Safe because DiscardSegment only contains plain data.
+unsafe impl ByteValued for DiscardSegment {}
+
+/// Size of a discard or write zeroes segment.
+#[allow(clippy::cast_possible_truncation)]
+pub const DISCARD_SEGMENT_SIZE: u32 = std::mem::size_of::<DiscardSegment>() as u32;
+
+impl DiscardSegment {
+    pub fn new(sector: u64, num_sectors: u32) -> DiscardSegment {
+        DiscardSegment {
+            sector,
+            num_sectors,
+            flags: 0,
+        }
+    }
+
+    /// Returns the offset and length in bytes of the range, which must be within the disk.
+    fn byte_range(&self, num_disk_sectors: u64) -> Result<(u64, u64), IoErr> {
+        let out_of_range = IoErr::DiscardOutOfRange {
+            sector: self.sector,
+            num_sectors: self.num_sectors,
+        };
+        match self.sector.checked_add(u64::from(self.num_sectors)) {
+            Some(top_sector) if top_sector <= num_disk_sectors => Ok((
+                self.sector << SECTOR_SHIFT,
+                u64::from(self.num_sectors) << SECTOR_SHIFT,
+            )),
+            _ => Err(out_of_range),
+        }
+    }
+}
+
 #[derive(Debug, PartialEq, Eq)]
 pub struct Request {
     pub r#type: RequestType,
@@ -278,7 +408,12 @@ impl Request {
                 .next_descriptor()
                 .ok_or(VirtioBlockError::DescriptorChainTooShort)?;
 
-            if data_desc.is_write_only() && req.r#type == RequestType::Out {
+            if data_desc.is_write_only()
+                && matches!(
+                    req.r#type,
+                    RequestType::Out | RequestType::Discard | RequestType::WriteZeroes
+                )
+            {
                 return Err(VirtioBlockError::UnexpectedWriteOnlyDescriptor);
             }
             if !data_desc.is_write_only() && req.r#type == RequestType::In {
@@ -313,6 +448,13 @@ impl Request {
                     return Err(VirtioBlockError::InvalidDataLength);
                 }
             }
+            // The device advertises a single segment per request, whose range is checked when
+            // the request is processed.
+            RequestType::Discard | RequestType::WriteZeroes => {
+                if req.data_len != DISCARD_SEGMENT_SIZE {
+                    return Err(VirtioBlockError::InvalidDataLength);
+                }
+            }
             _ => {}
         }
 
@@ -354,12 +496,28 @@ impl Request {
         self.sector << SECTOR_SHIFT
     }
 
+    /// Finishes the request with an IO error without executing it, as if the IO engine had
+    /// failed it.
+    #[cfg(feature = "fault-injection")]
+    pub(crate) fn fail_injected(
+        &self,
+        desc_idx: u16,
+        mem: &GuestMemoryMmap,
+        block_metrics: &BlockDeviceMetrics,
+    ) -> FinishedRequest {
+        self.to_pending_request(desc_idx)
+            .finish(mem, Err(IoErr::Injected), block_metrics)
+    }
+
     fn to_pending_request(&self, desc_idx: u16) -> PendingRequest {
         PendingRequest {
             r#type: self.r#type,
             data_len: self.data_len,
             status_addr: self.status_addr,
             desc_idx,
+            offset: self.offset(),
+            data_addr: self.data_addr,
+            transferred: 0,
         }
     }
 
@@ -372,6 +530,15 @@ impl Request {
     ) -> ProcessingResult {
         let pending = self.to_pending_request(desc_idx);
         let res = match self.r#type {
+            RequestType::Out | RequestType::Discard | RequestType::WriteZeroes
+                if disk.read_only =>
+            {
+                return ProcessingResult::Executed(pending.write_status_and_finish(
+                    &Status::ReadOnly,
+                    mem,
+                    block_metrics,
+                ));
+            }
             RequestType::In => {
                 let _metric = block_metrics.read_agg.record_latency_metrics();
                 disk.file_engine
@@ -383,6 +550,34 @@ impl Request {
                     .write(self.offset(), mem, self.data_addr, self.data_len, pending)
             }
             RequestType::Flush => disk.file_engine.flush(pending),
+            RequestType::Discard | RequestType::WriteZeroes if !disk.discard => {
+                return ProcessingResult::Executed(pending.write_status_and_finish(
+                    &Status::Unsupported {
+                        op: self.r#type.into(),
+                    },
+                    mem,
+                    block_metrics,
+                ));
+            }
+            RequestType::Discard | RequestType::WriteZeroes => {
+                let range = mem
+                    .read_obj::<DiscardSegment>(self.data_addr)
+                    .map_err(IoErr::DiscardSegment)
+                    .and_then(|segment| segment.byte_range(disk.nsectors));
+                match range {
+                    Ok((offset, len)) if self.r#type == RequestType::Discard => {
+                        disk.file_engine.punch_hole(offset, len, pending)
+                    }
+                    Ok((offset, len)) => disk.file_engine.write_zeroes(offset, len, pending),
+                    Err(err) => {
+                        return ProcessingResult::Executed(pending.finish(
+                            mem,
+                            Err(err),
+                            block_metrics,
+                        ));
+                    }
+                }
+            }
             RequestType::GetDeviceID => {
                 let res = mem
                     .write_slice(&disk.image_id, self.data_addr)
@@ -464,6 +659,14 @@ mod tests {
             RequestType::from(VIRTIO_BLK_T_GET_ID),
             RequestType::GetDeviceID
         );
+        assert_eq!(
+            RequestType::from(VIRTIO_BLK_T_DISCARD),
+            RequestType::Discard
+        );
+        assert_eq!(
+            RequestType::from(VIRTIO_BLK_T_WRITE_ZEROES),
+            RequestType::WriteZeroes
+        );
         assert_eq!(RequestType::from(42), RequestType::Unsupported(42));
     }
 
@@ -658,6 +861,124 @@ mod tests {
         chain.check_parse(true);
     }
 
+    #[test]
+    fn test_parse_discard() {
+        let mem = &default_mem();
+        let queue = VirtQueue::new(GuestAddress(0), mem, 16);
+        let chain = RequestDescriptorChain::new(&queue);
+
+        for request_type in [VIRTIO_BLK_T_DISCARD, VIRTIO_BLK_T_WRITE_ZEROES] {
+            chain.set_header(RequestHeader::new(request_type, 0));
+
+            // Write only data descriptor for discard and write zeroes.
+            chain
+                .data_desc
+                .flags
+                .set(VIRTQ_DESC_F_NEXT | VIRTQ_DESC_F_WRITE);
+            chain.check_parse_err(VirtioBlockError::UnexpectedWriteOnlyDescriptor);
+
+            // Only a single segment is supported.
+            chain.data_desc.flags.set(VIRTQ_DESC_F_NEXT);
+            chain.data_desc.len.set(2 * DISCARD_SEGMENT_SIZE);
+            chain.check_parse_err(VirtioBlockError::InvalidDataLength);
+
+            chain.data_desc.len.set(DISCARD_SEGMENT_SIZE);
+            chain.check_parse(true);
+        }
+    }
+
+    #[test]
+    fn test_discard_segment_byte_range() {
+        assert_eq!(
+            DiscardSegment::new(2, 8)
+                .byte_range(NUM_DISK_SECTORS)
+                .unwrap(),
+            (0x400, 0x1000)
+        );
+        assert_eq!(
+            DiscardSegment::new(0, 1024)
+                .byte_range(NUM_DISK_SECTORS)
+                .unwrap(),
+            (0, NUM_DISK_SECTORS << SECTOR_SHIFT)
+        );
+        assert!(matches!(
+            DiscardSegment::new(1020, 8).byte_range(NUM_DISK_SECTORS),
+            Err(IoErr::DiscardOutOfRange {
+                sector: 1020,
+                num_sectors: 8
+            })
+        ));
+        assert!(matches!(
+            DiscardSegment::new(u64::MAX, 1).byte_range(NUM_DISK_SECTORS),
+            Err(IoErr::DiscardOutOfRange { .. })
+        ));
+    }
+
+    #[test]
+    fn test_resume_transfer() {
+        let mem = &default_mem();
+        let metrics = BlockDeviceMetrics::new();
+        let status_addr = GuestAddress(0x3000);
+        let request = |r#type| Request {
+            r#type,
+            data_len: 0x1000,
+            status_addr,
+            sector: 2,
+            data_addr: GuestAddress(0x2000),
+        };
+
+        // Short reads are resumed for the remaining data as long as they make progress.
+        let mut pending = request(RequestType::In).to_pending_request(0);
+        assert_eq!(
+            pending.resume_transfer(0x400),
+            Some(ResumedTransfer {
+                r#type: RequestType::In,
+                offset: 0x800,
+                addr: GuestAddress(0x2400),
+                len: 0xc00,
+            })
+        );
+        assert_eq!(
+            pending.resume_transfer(0x800),
+            Some(ResumedTransfer {
+                r#type: RequestType::In,
+                offset: 0x1000,
+                addr: GuestAddress(0x2c00),
+                len: 0x400,
+            })
+        );
+        assert_eq!(pending.resume_transfer(0x400), None);
+        let finished = pending.finish(mem, Ok(0x400), &metrics);
+        assert_eq!(finished.num_bytes_to_mem, 0x1000 + 1);
+        assert_eq!(
+            mem.read_obj::<u8>(status_addr).unwrap(),
+            u8::try_from(VIRTIO_BLK_S_OK).unwrap()
+        );
+
+        // A short write that stops making progress fails the request.
+        let mut pending = request(RequestType::Out).to_pending_request(0);
+        assert_eq!(
+            pending.resume_transfer(0x200),
+            Some(ResumedTransfer {
+                r#type: RequestType::Out,
+                offset: 0x600,
+                addr: GuestAddress(0x2200),
+                len: 0xe00,
+            })
+        );
+        assert_eq!(pending.resume_transfer(0), None);
+        let finished = pending.finish(mem, Ok(0), &metrics);
+        assert_eq!(finished.num_bytes_to_mem, 1);
+        assert_eq!(
+            mem.read_obj::<u8>(status_addr).unwrap(),
+            u8::try_from(VIRTIO_BLK_S_IOERR).unwrap()
+        );
+
+        // Other requests are never resumed.
+        let mut pending = request(RequestType::Flush).to_pending_request(0);
+        assert_eq!(pending.resume_transfer(0x400), None);
+    }
+
     use std::convert::TryInto;
 
     /// -------------------------------------
@@ -703,27 +1024,15 @@ mod tests {
                     1u32,
                     std::sync::Arc::new(Strategy::prop_map(any::<u32>(), |id| {
                         // Random unsupported requests for our implementation start at
-                        // VIRTIO_BLK_T_GET_ID + 1 = 9.
-                        // This can be further refined to include unsupported requests ids < 9.
-                        RequestType::Unsupported(id.checked_add(9).unwrap_or(9))
+                        // VIRTIO_BLK_T_WRITE_ZEROES + 1 = 14.
+                        // This can be further refined to include unsupported requests ids < 14.
+                        RequestType::Unsupported(id.checked_add(14).unwrap_or(14))
                     })),
                 ),
             ))
         }
     }
 
-    impl From<RequestType> for u32 {
-        fn from(request_type: RequestType) -> u32 {
-            match request_type {
-                RequestType::In => VIRTIO_BLK_T_IN,
-                RequestType::Out => VIRTIO_BLK_T_OUT,
-                RequestType::Flush => VIRTIO_BLK_T_FLUSH,
-                RequestType::GetDeviceID => VIRTIO_BLK_T_GET_ID,
-                RequestType::Unsupported(id) => id,
-            }
-        }
-    }
-
     // Returns flags based on the request type.
     fn request_type_flags(request_type: RequestType) -> u16 {
         match request_type {
@@ -731,6 +1040,7 @@ mod tests {
             RequestType::Out => VIRTQ_DESC_F_NEXT,
             RequestType::Flush => VIRTQ_DESC_F_NEXT,
             RequestType::GetDeviceID => VIRTQ_DESC_F_NEXT | VIRTQ_DESC_F_WRITE,
+            RequestType::Discard | RequestType::WriteZeroes => VIRTQ_DESC_F_NEXT,
             RequestType::Unsupported(_) => VIRTQ_DESC_F_NEXT,
         }
     }
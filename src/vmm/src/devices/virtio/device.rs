@@ -169,6 +169,11 @@ pub trait VirtioDevice: AsAny + Send {
     /// Writes to this device configuration space at `offset`.
     fn write_config(&mut self, offset: u64, data: &[u8]);
 
+    /// Returns a copy of the whole device configuration space.
+    fn config_space(&self) -> Vec<u8> {
+        Vec::new()
+    }
+
     /// Performs the formal activation for a device, which can be verified also with `is_activated`.
     fn activate(&mut self, mem: GuestMemoryMmap) -> Result<(), ActivateError>;
 
@@ -0,0 +1,127 @@
+// Copyright 2024 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Faults injected into the processing path of virtio devices, used to test how the guest copes
+//! with misbehaving devices.
+//!
+//! Only built with the `fault-injection` feature, which is not meant for production builds.
+
+use serde::{Deserialize, Serialize};
+
+/// Fault injected into a device through PUT `/devices/{type}/{id}/inject-fault`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(tag = "fault", rename_all = "snake_case", deny_unknown_fields)]
+pub enum DeviceFault {
+    /// Fails the next `count` read requests of a block device with an IO error.
+    BlockReadError {
+        /// Number of read requests to fail.
+        count: u32,
+    },
+    /// Drops the next `count` frames transmitted by the guest through a network device.
+    NetTxDrop {
+        /// Number of frames to drop.
+        count: u32,
+    },
+    /// Stops processing the request queue of a block device, or the TX queue of a network
+    /// device, until the faults are cleared.
+    QueueStall,
+    /// Clears the faults injected into the device. Requests made available while the queue was
+    /// stalled are processed.
+    Clear,
+}
+
+/// Faults pending on a device.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct FaultInjector {
+    block_read_errors: u32,
+    net_tx_drops: u32,
+    queue_stalled: bool,
+}
+
+impl FaultInjector {
+    /// Adds `fault` to the faults pending on the device.
+    pub fn inject(&mut self, fault: DeviceFault) {
+        match fault {
+            DeviceFault::BlockReadError { count } => {
+                self.block_read_errors = self.block_read_errors.saturating_add(count)
+            }
+            DeviceFault::NetTxDrop { count } => {
+                self.net_tx_drops = self.net_tx_drops.saturating_add(count)
+            }
+            DeviceFault::QueueStall => self.queue_stalled = true,
+            DeviceFault::Clear => *self = Self::default(),
+        }
+    }
+
+    /// Whether the device should fail the block read request being processed.
+    pub fn take_block_read_error(&mut self) -> bool {
+        Self::take(&mut self.block_read_errors)
+    }
+
+    /// Whether the device should drop the frame being transmitted.
+    pub fn take_net_tx_drop(&mut self) -> bool {
+        Self::take(&mut self.net_tx_drops)
+    }
+
+    /// Whether the device should stop processing its queue.
+    pub fn is_queue_stalled(&self) -> bool {
+        self.queue_stalled
+    }
+
+    fn take(pending: &mut u32) -> bool {
+        match pending.checked_sub(1) {
+            Some(remaining) => {
+                *pending = remaining;
+                true
+            }
+            None => false,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_fault_injector() {
+        let mut faults = FaultInjector::default();
+        assert!(!faults.take_block_read_error());
+        assert!(!faults.take_net_tx_drop());
+        assert!(!faults.is_queue_stalled());
+
+        faults.inject(DeviceFault::BlockReadError { count: 1 });
+        faults.inject(DeviceFault::BlockReadError { count: 1 });
+        faults.inject(DeviceFault::NetTxDrop { count: 1 });
+        assert!(faults.take_block_read_error());
+        assert!(faults.take_block_read_error());
+        assert!(!faults.take_block_read_error());
+        assert!(faults.take_net_tx_drop());
+        assert!(!faults.take_net_tx_drop());
+
+        faults.inject(DeviceFault::QueueStall);
+        faults.inject(DeviceFault::NetTxDrop { count: 3 });
+        assert!(faults.is_queue_stalled());
+        faults.inject(DeviceFault::Clear);
+        assert_eq!(faults, FaultInjector::default());
+    }
+
+    #[test]
+    fn test_device_fault_serde() {
+        assert_eq!(
+            serde_json::from_str::<DeviceFault>(r#"{"fault": "block_read_error", "count": 2}"#)
+                .unwrap(),
+            DeviceFault::BlockReadError { count: 2 }
+        );
+        assert_eq!(
+            serde_json::from_str::<DeviceFault>(r#"{"fault": "queue_stall"}"#).unwrap(),
+            DeviceFault::QueueStall
+        );
+        serde_json::from_str::<DeviceFault>(r#"{"fault": "net_tx_drop"}"#).unwrap_err();
+        serde_json::from_str::<DeviceFault>(
+            r#"{"fault": "net_tx_drop", "count": 1, "delay_ms": 10}"#,
+        )
+        .unwrap_err();
+        serde_json::from_str::<DeviceFault>(r#"{"fault": "unknown"}"#).unwrap_err();
+    }
+}
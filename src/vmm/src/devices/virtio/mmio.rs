@@ -156,11 +156,34 @@ impl MmioTransport {
         // . Keep interrupt_evt and queue_evts as is. There may be pending notifications in those
         //   eventfds, but nothing will happen other than supurious wakeups.
         // . Do not reset config_generation and keep it monotonically increasing
+        // . Keep the maximum length of the descriptor chains, which is not set by the driver
         for queue in self.locked_device().queues_mut() {
+            let max_chain_len = queue.max_chain_len;
             *queue = Queue::new(queue.get_max_size());
+            queue.max_chain_len = max_chain_len;
         }
     }
 
+    /// Tells the driver that the device can no longer be used, by setting the
+    /// `DEVICE_NEEDS_RESET` status bit.
+    pub fn set_needs_reset(&mut self) {
+        self.device_status |= device_status::DEVICE_NEEDS_RESET;
+
+        // Section 2.1.2 of the specification states that we need to send a device
+        // configuration change interrupt
+        let _ = self
+            .locked_device()
+            .interrupt_trigger()
+            .trigger_irq(IrqType::Config);
+    }
+
+    /// Returns whether the driver let go of the device, either because it never started to
+    /// drive it, or because it reset it or gave up on it since.
+    pub fn is_released(&self) -> bool {
+        self.device_status & (device_status::ACKNOWLEDGE | device_status::DRIVER) == 0
+            || self.device_status & device_status::FAILED != 0
+    }
+
     /// Update device status according to the state machine defined by VirtIO Spec 1.0.
     /// Please refer to VirtIO Spec 1.0, section 2.1.1 and 3.1.1.
     ///
@@ -940,13 +963,16 @@ pub(crate) mod tests {
         assert!(!d.are_queues_valid());
         assert!(!d.locked_device().is_activated());
         assert_eq!(d.device_status, 0);
+        assert!(d.is_released());
         activate_device(&mut d);
+        assert!(!d.is_released());
 
         // Marking device as FAILED should not affect device_activated state
         write_le_u32(&mut buf[..], 0x8f);
         d.bus_write(0x70, &buf[..]);
         assert_eq!(d.device_status, 0x8f);
         assert!(d.locked_device().is_activated());
+        assert!(d.is_released());
 
         // Nothing happens when backend driver doesn't support reset
         write_le_u32(&mut buf[..], 0x0);
@@ -955,6 +981,30 @@ pub(crate) mod tests {
         assert!(d.locked_device().is_activated());
     }
 
+    #[test]
+    fn test_reset_keeps_max_chain_len() {
+        let m = single_region_mem(0x1000);
+        let mut d = MmioTransport::new(m, Arc::new(Mutex::new(DummyDevice::new())), false);
+        let mut buf = [0; 4];
+
+        for queue in d.locked_device().queues_mut() {
+            queue.max_chain_len = 3;
+            queue.size = 2;
+        }
+        set_device_status(&mut d, device_status::ACKNOWLEDGE);
+        assert!(!d.is_released());
+
+        // The driver resets the device before activating it.
+        write_le_u32(&mut buf[..], 0x0);
+        d.bus_write(0x70, &buf[..]);
+        assert_eq!(d.device_status, device_status::INIT);
+        assert!(d.is_released());
+        for queue in d.locked_device().queues() {
+            assert_eq!(queue.size, 0);
+            assert_eq!(queue.max_chain_len, 3);
+        }
+    }
+
     #[test]
     fn test_get_avail_features() {
         let dummy_dev = DummyDevice::new();
@@ -15,6 +15,8 @@ use crate::devices::virtio::net::TapError;
 pub mod balloon;
 pub mod block;
 pub mod device;
+#[cfg(feature = "fault-injection")]
+pub mod fault_injection;
 pub mod gen;
 mod iov_deque;
 pub mod iovec;
@@ -27,6 +29,7 @@ pub mod test_utils;
 pub mod vhost_user;
 pub mod vhost_user_metrics;
 pub mod vsock;
+pub mod watchdog;
 
 /// When the driver initializes the device, it lets the device know about the
 /// completed stages using the Device Status Field.
@@ -56,6 +59,8 @@ pub const TYPE_BLOCK: u32 = 2;
 pub const TYPE_RNG: u32 = 4;
 /// Virtio balloon device ID.
 pub const TYPE_BALLOON: u32 = 5;
+/// Virtio watchdog device ID.
+pub const TYPE_WATCHDOG: u32 = 35;
 
 /// Offset from the base MMIO address of a virtio device used by the guest to notify the device of
 /// queue events.
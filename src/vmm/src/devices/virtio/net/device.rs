@@ -16,11 +16,14 @@ use vmm_sys_util::eventfd::EventFd;
 
 use super::NET_QUEUE_MAX_SIZE;
 use crate::devices::virtio::device::{DeviceState, IrqTrigger, IrqType, VirtioDevice};
+#[cfg(feature = "fault-injection")]
+use crate::devices::virtio::fault_injection::{DeviceFault, FaultInjector};
 use crate::devices::virtio::gen::virtio_blk::VIRTIO_F_VERSION_1;
 use crate::devices::virtio::gen::virtio_net::{
     virtio_net_hdr_v1, VIRTIO_NET_F_CSUM, VIRTIO_NET_F_GUEST_CSUM, VIRTIO_NET_F_GUEST_TSO4,
     VIRTIO_NET_F_GUEST_TSO6, VIRTIO_NET_F_GUEST_UFO, VIRTIO_NET_F_HOST_TSO4,
     VIRTIO_NET_F_HOST_TSO6, VIRTIO_NET_F_HOST_UFO, VIRTIO_NET_F_MAC, VIRTIO_NET_F_MRG_RXBUF,
+    VIRTIO_NET_F_MTU,
 };
 use crate::devices::virtio::gen::virtio_ring::VIRTIO_RING_F_EVENT_IDX;
 use crate::devices::virtio::iovec::{
@@ -39,13 +42,30 @@ use crate::dumbo::pdu::ethernet::{EthernetFrame, PAYLOAD_OFFSET};
 use crate::logger::{IncMetric, METRICS};
 use crate::mmds::data_store::Mmds;
 use crate::mmds::ns::MmdsNetworkStack;
-use crate::rate_limiter::{BucketUpdate, RateLimiter, TokenType};
-use crate::utils::net::mac::MacAddr;
+use crate::rate_limiter::{BucketUpdate, RateLimiter, TokenBucket, TokenType};
+use crate::utils::net::mac::{MacAddr, MAC_ADDR_LEN};
 use crate::utils::u64_to_usize;
 use crate::vstate::memory::{ByteValued, GuestMemoryMmap};
 
 const FRAME_HEADER_MAX_LEN: usize = PAYLOAD_OFFSET + ETH_IPV4_FRAME_LEN;
 
+/// Features offered by the net device regardless of its configuration.
+pub(crate) const AVAIL_FEATURES: u64 = 1 << VIRTIO_NET_F_GUEST_CSUM
+    | 1 << VIRTIO_NET_F_CSUM
+    | 1 << VIRTIO_NET_F_GUEST_TSO4
+    | 1 << VIRTIO_NET_F_GUEST_TSO6
+    | 1 << VIRTIO_NET_F_GUEST_UFO
+    | 1 << VIRTIO_NET_F_HOST_TSO4
+    | 1 << VIRTIO_NET_F_HOST_TSO6
+    | 1 << VIRTIO_NET_F_HOST_UFO
+    | 1 << VIRTIO_F_VERSION_1
+    | 1 << VIRTIO_NET_F_MRG_RXBUF
+    | 1 << VIRTIO_RING_F_EVENT_IDX;
+/// Features offered depending on the configuration of the device, with the configuration option
+/// enabling each of them.
+pub(crate) const OPTIONAL_FEATURES: [(u32, &str); 2] =
+    [(VIRTIO_NET_F_MAC, "guest_mac"), (VIRTIO_NET_F_MTU, "mtu")];
+
 pub(crate) const fn vnet_hdr_len() -> usize {
     mem::size_of::<virtio_net_hdr_v1>()
 }
@@ -85,6 +105,10 @@ fn init_vnet_hdr(buf: &mut [u8]) {
 #[repr(C)]
 pub struct ConfigSpace {
     pub guest_mac: MacAddr,
+    // The fields below are only exposed to the guest when an MTU is advertised.
+    pub status: u16,
+    pub max_virtqueue_pairs: u16,
+    pub mtu: u16,
 }
 
 // SAFETY: `ConfigSpace` contains only PODs in `repr(C)` or `repr(transparent)`, without padding.
@@ -252,6 +276,7 @@ pub struct Net {
 
     pub(crate) config_space: ConfigSpace,
     pub(crate) guest_mac: Option<MacAddr>,
+    pub(crate) mtu: Option<u16>,
 
     pub(crate) device_state: DeviceState,
     pub(crate) activate_evt: EventFd,
@@ -263,6 +288,8 @@ pub struct Net {
 
     tx_buffer: IoVecBuffer,
     pub(crate) rx_buffer: RxBuffers,
+    #[cfg(feature = "fault-injection")]
+    pub(crate) faults: FaultInjector,
 }
 
 impl Net {
@@ -274,17 +301,7 @@ impl Net {
         rx_rate_limiter: RateLimiter,
         tx_rate_limiter: RateLimiter,
     ) -> Result<Self, NetError> {
-        let mut avail_features = 1 << VIRTIO_NET_F_GUEST_CSUM
-            | 1 << VIRTIO_NET_F_CSUM
-            | 1 << VIRTIO_NET_F_GUEST_TSO4
-            | 1 << VIRTIO_NET_F_GUEST_TSO6
-            | 1 << VIRTIO_NET_F_GUEST_UFO
-            | 1 << VIRTIO_NET_F_HOST_TSO4
-            | 1 << VIRTIO_NET_F_HOST_TSO6
-            | 1 << VIRTIO_NET_F_HOST_UFO
-            | 1 << VIRTIO_F_VERSION_1
-            | 1 << VIRTIO_NET_F_MRG_RXBUF
-            | 1 << VIRTIO_RING_F_EVENT_IDX;
+        let mut avail_features = AVAIL_FEATURES;
 
         let mut config_space = ConfigSpace::default();
         if let Some(mac) = guest_mac {
@@ -315,12 +332,15 @@ impl Net {
             irq_trigger: IrqTrigger::new().map_err(NetError::EventFd)?,
             config_space,
             guest_mac,
+            mtu: None,
             device_state: DeviceState::Inactive,
             activate_evt: EventFd::new(libc::EFD_NONBLOCK).map_err(NetError::EventFd)?,
             mmds_ns: None,
             metrics: NetMetricsPerDevice::alloc(id),
             tx_buffer: Default::default(),
             rx_buffer: RxBuffers::new()?,
+            #[cfg(feature = "fault-injection")]
+            faults: FaultInjector::default(),
         })
     }
 
@@ -351,6 +371,29 @@ impl Net {
         self.guest_mac.as_ref()
     }
 
+    /// Provides the MTU advertised to the guest by this net device.
+    pub fn mtu(&self) -> Option<u16> {
+        self.mtu
+    }
+
+    /// Advertises `mtu` to the guest. Must be called before the device is activated.
+    pub fn set_mtu(&mut self, mtu: u16) {
+        self.config_space.mtu = mtu;
+        self.mtu = Some(mtu);
+        self.avail_features |= 1 << VIRTIO_NET_F_MTU;
+    }
+
+    // The MTU is part of the config space only when it is advertised, so that devices
+    // without one keep exposing just the MAC address.
+    fn config_space_bytes(&self) -> &[u8] {
+        let len = if self.mtu.is_some() {
+            mem::size_of::<ConfigSpace>()
+        } else {
+            MAC_ADDR_LEN as usize
+        };
+        &self.config_space.as_slice()[..len]
+    }
+
     /// Provides the host IFACE name of this net device.
     pub fn iface_name(&self) -> String {
         self.tap.if_name_as_str().to_string()
@@ -362,13 +405,18 @@ impl Net {
     }
 
     /// Configures the `MmdsNetworkStack` to allow device to forward MMDS requests.
-    /// If the device already supports MMDS, updates the IPv4 address.
-    pub fn configure_mmds_network_stack(&mut self, ipv4_addr: Ipv4Addr, mmds: Arc<Mutex<Mmds>>) {
-        if let Some(mmds_ns) = self.mmds_ns.as_mut() {
-            mmds_ns.set_ipv4_addr(ipv4_addr);
-        } else {
-            self.mmds_ns = Some(MmdsNetworkStack::new_with_defaults(Some(ipv4_addr), mmds))
-        }
+    /// If the device already supports MMDS, updates the IPv4 address and the rate limiter.
+    pub fn configure_mmds_network_stack(
+        &mut self,
+        ipv4_addr: Ipv4Addr,
+        mmds: Arc<Mutex<Mmds>>,
+        rate_limiter: Option<TokenBucket>,
+    ) {
+        let mmds_ns = self
+            .mmds_ns
+            .get_or_insert_with(|| MmdsNetworkStack::new_with_defaults(Some(ipv4_addr), mmds));
+        mmds_ns.set_ipv4_addr(ipv4_addr);
+        mmds_ns.set_rate_limiter(rate_limiter);
     }
 
     /// Disables the `MmdsNetworkStack` to prevent device to forward MMDS requests.
@@ -681,6 +729,11 @@ impl Net {
         // This is safe since we checked in the event handler that the device is activated.
         let mem = self.device_state.mem().unwrap();
 
+        #[cfg(feature = "fault-injection")]
+        if self.faults.is_queue_stalled() {
+            return Ok(());
+        }
+
         // The MMDS network stack works like a state machine, based on synchronous calls, and
         // without being added to any event loop. If any frame is accepted by the MMDS, we also
         // trigger a process_rx() which checks if there are any new frames to be sent, starting
@@ -725,6 +778,16 @@ impl Net {
                 break;
             }
 
+            #[cfg(feature = "fault-injection")]
+            if self.faults.take_net_tx_drop() {
+                // The frame is lost on the way to the tap, as if it was sent.
+                tx_queue
+                    .add_used(head_index, 0)
+                    .map_err(DeviceError::QueueError)?;
+                used_any = true;
+                continue;
+            }
+
             let frame_consumed_by_mmds = Self::write_to_mmds_or_tap(
                 self.mmds_ns.as_mut(),
                 &mut self.tx_rate_limiter,
@@ -930,6 +993,17 @@ impl Net {
         let _ = self.resume_rx();
         let _ = self.process_tx();
     }
+
+    /// Injects `fault` into the processing of the TX queue.
+    #[cfg(feature = "fault-injection")]
+    pub fn inject_fault(&mut self, fault: DeviceFault) {
+        self.faults.inject(fault);
+        // Transmit the frames made available while the TX queue was stalled.
+        if !self.faults.is_queue_stalled() && self.is_activated() {
+            self.process_tx()
+                .unwrap_or_else(|err| report_net_event_fail(&self.metrics, err));
+        }
+    }
 }
 
 impl VirtioDevice for Net {
@@ -965,7 +1039,7 @@ impl VirtioDevice for Net {
         &self.irq_trigger
     }
     fn read_config(&self, offset: u64, data: &mut [u8]) {
-        if let Some(config_space_bytes) = self.config_space.as_slice().get(u64_to_usize(offset)..) {
+        if let Some(config_space_bytes) = self.config_space_bytes().get(u64_to_usize(offset)..) {
             let len = config_space_bytes.len().min(data.len());
             data[..len].copy_from_slice(&config_space_bytes[..len]);
         } else {
@@ -975,7 +1049,8 @@ impl VirtioDevice for Net {
     }
 
     fn write_config(&mut self, offset: u64, data: &[u8]) {
-        let config_space_bytes = self.config_space.as_mut_slice();
+        // Only the MAC address is writable by the driver.
+        let config_space_bytes = &mut self.config_space.as_mut_slice()[..MAC_ADDR_LEN as usize];
         let start = usize::try_from(offset).ok();
         let end = start.and_then(|s| s.checked_add(data.len()));
         let Some(dst) = start
@@ -992,6 +1067,10 @@ impl VirtioDevice for Net {
         self.metrics.mac_address_updates.inc();
     }
 
+    fn config_space(&self) -> Vec<u8> {
+        self.config_space_bytes().to_vec()
+    }
+
     fn activate(&mut self, mem: GuestMemoryMmap) -> Result<(), ActivateError> {
         for q in self.queues.iter_mut() {
             q.initialize(&mem)
@@ -1190,6 +1269,33 @@ pub mod tests {
         assert_eq!(config_mac, [0u8, 0u8, 0u8, 0u8, 0u8, 0u8]);
     }
 
+    #[test]
+    fn test_virtio_device_mtu() {
+        let mut net = default_net();
+        set_mac(&mut net, MacAddr::from_str("11:22:33:44:55:66").unwrap());
+        assert_eq!(net.mtu(), None);
+        assert_eq!(net.avail_features() & (1 << VIRTIO_NET_F_MTU), 0);
+        assert_eq!(net.config_space().len(), MAC_ADDR_LEN as usize);
+
+        net.set_mtu(9000);
+        assert_eq!(net.mtu(), Some(9000));
+        assert_ne!(net.avail_features() & (1 << VIRTIO_NET_F_MTU), 0);
+        assert_eq!(net.config_space().len(), mem::size_of::<ConfigSpace>());
+
+        // The MTU follows the MAC address, the status and the maximum number of queue pairs.
+        let mut mtu = [0u8; 2];
+        net.read_config(10, &mut mtu);
+        assert_eq!(u16::from_le_bytes(mtu), 9000);
+        let mut config_mac = [0u8; MAC_ADDR_LEN as usize];
+        net.read_config(0, &mut config_mac);
+        assert_eq!(config_mac, [0x11, 0x22, 0x33, 0x44, 0x55, 0x66]);
+
+        // The driver cannot overwrite the MTU.
+        net.write_config(10, &[0x0, 0x1]);
+        net.read_config(10, &mut mtu);
+        assert_eq!(u16::from_le_bytes(mtu), 9000);
+    }
+
     #[test]
     fn test_virtio_device_rewrite_config() {
         let mut net = default_net();
@@ -86,7 +86,7 @@ use std::sync::{Arc, RwLock};
 use serde::ser::SerializeMap;
 use serde::{Serialize, Serializer};
 
-use crate::logger::{IncMetric, LatencyAggregateMetrics, SharedIncMetric};
+use crate::logger::{IncMetric, LatencyAggregateMetrics, LatencyAggregateValues, SharedIncMetric};
 
 /// map of network interface id and metrics
 /// this should be protected by a lock before accessing.
@@ -112,6 +112,12 @@ impl NetMetricsPerDevice {
                 .or_insert_with(|| Arc::new(NetDeviceMetrics::default())),
         )
     }
+
+    /// Get the `NetDeviceMetrics` of the net device having id `iface_id`,
+    /// if they were allocated.
+    pub fn get(iface_id: &str) -> Option<Arc<NetDeviceMetrics>> {
+        METRICS.read().unwrap().metrics.get(iface_id).cloned()
+    }
 }
 
 /// Pool of Network-related metrics per device behind a lock to
@@ -205,6 +211,71 @@ pub struct NetDeviceMetrics {
     pub tx_remaining_reqs_count: SharedIncMetric,
 }
 
+/// Current values of the metrics of a network device, as returned by `NetDeviceMetrics::values`.
+/// Reading them does not reset the metrics: the counters hold the totals since the device was
+/// created, while a metrics flush reports the increase since the previous flush.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct NetDeviceMetricsValues {
+    /// Number of times when activate failed on a network device.
+    pub activate_fails: u64,
+    /// Number of times when interacting with the space config of a network device failed.
+    pub cfg_fails: u64,
+    /// Number of times the mac address was updated through the config space.
+    pub mac_address_updates: u64,
+    /// No available buffer for the net device rx queue.
+    pub no_rx_avail_buffer: u64,
+    /// No available buffer for the net device tx queue.
+    pub no_tx_avail_buffer: u64,
+    /// Number of times when handling events on a network device failed.
+    pub event_fails: u64,
+    /// Number of events associated with the receiving queue.
+    pub rx_queue_event_count: u64,
+    /// Number of events associated with the rate limiter installed on the receiving path.
+    pub rx_event_rate_limiter_count: u64,
+    /// Number of RX partial writes to guest.
+    pub rx_partial_writes: u64,
+    /// Number of RX rate limiter throttling events.
+    pub rx_rate_limiter_throttled: u64,
+    /// Number of events received on the associated tap.
+    pub rx_tap_event_count: u64,
+    /// Number of bytes received.
+    pub rx_bytes_count: u64,
+    /// Number of packets received.
+    pub rx_packets_count: u64,
+    /// Number of errors while receiving data.
+    pub rx_fails: u64,
+    /// Number of successful read operations while receiving data.
+    pub rx_count: u64,
+    /// Number of times reading from TAP failed.
+    pub tap_read_fails: u64,
+    /// Number of times writing to TAP failed.
+    pub tap_write_fails: u64,
+    /// Duration of all tap write operations.
+    pub tap_write_agg: LatencyAggregateValues,
+    /// Number of transmitted bytes.
+    pub tx_bytes_count: u64,
+    /// Number of malformed TX frames.
+    pub tx_malformed_frames: u64,
+    /// Number of errors while transmitting data.
+    pub tx_fails: u64,
+    /// Number of successful write operations while transmitting data.
+    pub tx_count: u64,
+    /// Number of transmitted packets.
+    pub tx_packets_count: u64,
+    /// Number of TX partial reads from guest.
+    pub tx_partial_reads: u64,
+    /// Number of events associated with the transmitting queue.
+    pub tx_queue_event_count: u64,
+    /// Number of events associated with the rate limiter installed on the transmitting path.
+    pub tx_rate_limiter_event_count: u64,
+    /// Number of RX rate limiter throttling events.
+    pub tx_rate_limiter_throttled: u64,
+    /// Number of packets with a spoofed mac, sent by the guest.
+    pub tx_spoofed_mac_count: u64,
+    /// Number of remaining requests in the TX queue.
+    pub tx_remaining_reqs_count: u64,
+}
+
 impl NetDeviceMetrics {
     /// Const default construction.
     pub fn new() -> Self {
@@ -269,6 +340,41 @@ impl NetDeviceMetrics {
         self.tx_remaining_reqs_count
             .add(other.tx_remaining_reqs_count.fetch_diff());
     }
+
+    /// Reads the current values of the metrics without resetting them.
+    pub fn values(&self) -> NetDeviceMetricsValues {
+        NetDeviceMetricsValues {
+            activate_fails: self.activate_fails.count(),
+            cfg_fails: self.cfg_fails.count(),
+            mac_address_updates: self.mac_address_updates.count(),
+            no_rx_avail_buffer: self.no_rx_avail_buffer.count(),
+            no_tx_avail_buffer: self.no_tx_avail_buffer.count(),
+            event_fails: self.event_fails.count(),
+            rx_queue_event_count: self.rx_queue_event_count.count(),
+            rx_event_rate_limiter_count: self.rx_event_rate_limiter_count.count(),
+            rx_partial_writes: self.rx_partial_writes.count(),
+            rx_rate_limiter_throttled: self.rx_rate_limiter_throttled.count(),
+            rx_tap_event_count: self.rx_tap_event_count.count(),
+            rx_bytes_count: self.rx_bytes_count.count(),
+            rx_packets_count: self.rx_packets_count.count(),
+            rx_fails: self.rx_fails.count(),
+            rx_count: self.rx_count.count(),
+            tap_read_fails: self.tap_read_fails.count(),
+            tap_write_fails: self.tap_write_fails.count(),
+            tap_write_agg: self.tap_write_agg.values(),
+            tx_bytes_count: self.tx_bytes_count.count(),
+            tx_malformed_frames: self.tx_malformed_frames.count(),
+            tx_fails: self.tx_fails.count(),
+            tx_count: self.tx_count.count(),
+            tx_packets_count: self.tx_packets_count.count(),
+            tx_partial_reads: self.tx_partial_reads.count(),
+            tx_queue_event_count: self.tx_queue_event_count.count(),
+            tx_rate_limiter_event_count: self.tx_rate_limiter_event_count.count(),
+            tx_rate_limiter_throttled: self.tx_rate_limiter_throttled.count(),
+            tx_spoofed_mac_count: self.tx_spoofed_mac_count.count(),
+            tx_remaining_reqs_count: self.tx_remaining_reqs_count.count(),
+        }
+    }
 }
 
 #[cfg(test)]
@@ -443,4 +549,19 @@ pub mod tests {
                 >= 5
         );
     }
+
+    #[test]
+    fn test_net_dev_metrics_values() {
+        // The values read through the API have the same fields as the flushed metrics, which
+        // tests/host_tools/fcmetrics.py checks. Until the first flush, they also hold the same
+        // values.
+        let metrics = NetDeviceMetrics::default();
+        metrics.rx_packets_count.inc();
+        metrics.tx_bytes_count.add(5);
+        metrics.tap_write_agg.update_with_elapsed_time(0);
+
+        let values = serde_json::to_value(metrics.values()).unwrap();
+        let flushed = serde_json::to_value(&metrics).unwrap();
+        assert_eq!(values, flushed);
+    }
 }
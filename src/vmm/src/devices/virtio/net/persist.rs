@@ -28,6 +28,7 @@ use crate::vstate::memory::GuestMemoryMmap;
 #[derive(Debug, Default, Clone, Serialize, Deserialize)]
 pub struct NetConfigSpaceState {
     guest_mac: Option<MacAddr>,
+    mtu: Option<u16>,
 }
 
 /// Information about the parsed RX buffers
@@ -104,6 +105,7 @@ impl Persist<'_> for Net {
             mmds_ns: self.mmds_ns.as_ref().map(|mmds| mmds.save()),
             config_space: NetConfigSpaceState {
                 guest_mac: self.guest_mac,
+                mtu: self.mtu,
             },
             virtio_state: VirtioDeviceState::from_device(self),
             rx_buffers_state: RxBufferState::from_rx_buffers(&self.rx_buffer),
@@ -124,22 +126,20 @@ impl Persist<'_> for Net {
             rx_rate_limiter,
             tx_rate_limiter,
         )?;
+        if let Some(mtu) = state.config_space.mtu {
+            net.set_mtu(mtu);
+        }
 
         // We trust the MMIODeviceManager::restore to pass us an MMDS data store reference if
         // there is at least one net device having the MMDS NS present and/or the mmds version was
         // persisted in the snapshot.
         if let Some(mmds_ns) = &state.mmds_ns {
-            // We're safe calling unwrap() to discard the error, as MmdsNetworkStack::restore()
-            // always returns Ok.
-            net.mmds_ns = Some(
-                MmdsNetworkStack::restore(
-                    constructor_args
-                        .mmds
-                        .map_or_else(|| Err(NetPersistError::NoMmdsDataStore), Ok)?,
-                    mmds_ns,
-                )
-                .unwrap(),
-            );
+            net.mmds_ns = Some(MmdsNetworkStack::restore(
+                constructor_args
+                    .mmds
+                    .map_or_else(|| Err(NetPersistError::NoMmdsDataStore), Ok)?,
+                mmds_ns,
+            )?);
         }
 
         net.queues = state.virtio_state.build_queues_checked(
@@ -190,6 +190,7 @@ mod tests {
         let tap_if_name;
         let has_mmds_ns;
         let allow_mmds_requests;
+        let mtu;
         let virtio_state;
 
         // Create and save the net device.
@@ -201,6 +202,7 @@ mod tests {
             tap_if_name = net.iface_name();
             has_mmds_ns = net.mmds_ns.is_some();
             allow_mmds_requests = has_mmds_ns && mmds_ds.is_some();
+            mtu = net.mtu();
             virtio_state = VirtioDeviceState::from_device(&net);
         }
 
@@ -231,6 +233,7 @@ mod tests {
                     assert_eq!(&restored_net.id, &id);
                     assert_eq!(&restored_net.iface_name(), &tap_if_name);
                     assert_eq!(restored_net.mmds_ns.is_some(), allow_mmds_requests);
+                    assert_eq!(restored_net.mtu(), mtu);
                     assert_eq!(restored_net.rx_rate_limiter, RateLimiter::default());
                     assert_eq!(restored_net.tx_rate_limiter, RateLimiter::default());
                 }
@@ -256,5 +259,10 @@ mod tests {
         // Check what happens if the MMIODeviceManager does not give us the reference to the MMDS
         // data store. This will return an error.
         validate_save_and_restore(default_net(), None);
+
+        // The advertised MTU is restored.
+        let mut net = default_net_no_mmds();
+        net.set_mtu(1500);
+        validate_save_and_restore(net, None);
     }
 }
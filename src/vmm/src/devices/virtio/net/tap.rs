@@ -113,6 +113,11 @@ impl IfReqBuilder {
 }
 
 impl Tap {
+    /// Checks that `if_name` can be the name of a TUN/TAP device, without opening it.
+    pub fn check_if_name(if_name: &str) -> Result<(), TapError> {
+        build_terminated_if_name(if_name).map(|_| ())
+    }
+
     /// Create a TUN/TAP device given the interface name.
     /// # Arguments
     ///
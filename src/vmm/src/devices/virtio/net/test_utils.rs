@@ -50,6 +50,7 @@ pub fn default_net() -> Net {
     net.configure_mmds_network_stack(
         MmdsNetworkStack::default_ipv4_addr(),
         Arc::new(Mutex::new(Mmds::default())),
+        None,
     );
     enable(&net.tap);
 
@@ -52,6 +52,9 @@ pub struct QueueState {
 
     /// The number of added used buffers since last guest kick
     num_added: Wrapping<u16>,
+
+    /// The maximum number of descriptors in a chain the device processes
+    max_chain_len: u16,
 }
 
 /// Auxiliary structure for restoring queues.
@@ -79,6 +82,7 @@ impl Persist<'_> for Queue {
             next_avail: self.next_avail,
             next_used: self.next_used,
             num_added: self.num_added,
+            max_chain_len: self.max_chain_len,
         }
     }
 
@@ -102,6 +106,7 @@ impl Persist<'_> for Queue {
             next_used: state.next_used,
             uses_notif_suppression: false,
             num_added: state.num_added,
+            max_chain_len: state.max_chain_len,
         };
         if constructor_args.is_activated {
             queue.initialize(&constructor_args.mem)?;
@@ -281,6 +286,7 @@ mod tests {
                 next_avail: Wrapping(0),
                 next_used: Wrapping(0),
                 num_added: Wrapping(0),
+                max_chain_len: DEFAULT_QUEUE_MAX_SIZE,
             }
         }
     }
@@ -349,6 +355,7 @@ mod tests {
         let mem = default_mem();
 
         let mut queue = Queue::new(128);
+        queue.max_chain_len = 16;
         queue.initialize(&mem).unwrap();
 
         let mut bytes = vec![0; 4096];
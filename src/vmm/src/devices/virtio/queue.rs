@@ -9,7 +9,7 @@ use std::cmp::min;
 use std::num::Wrapping;
 use std::sync::atomic::{fence, Ordering};
 
-use crate::logger::error;
+use crate::logger::{error, IncMetric, METRICS};
 use crate::vstate::memory::{Address, Bitmap, ByteValued, GuestAddress, GuestMemory};
 
 pub const VIRTQ_DESC_F_NEXT: u16 = 0x1;
@@ -250,6 +250,10 @@ pub struct Queue {
     pub uses_notif_suppression: bool,
     /// The number of added used buffers since last guest kick
     pub num_added: Wrapping<u16>,
+
+    /// The maximum number of descriptors in the chains popped from the queue. Longer chains are
+    /// given back to the driver without being processed.
+    pub max_chain_len: u16,
 }
 
 /// SAFETY: Queue is Send, because we use volatile memory accesses when
@@ -278,6 +282,7 @@ impl Queue {
             next_used: Wrapping(0),
             uses_notif_suppression: false,
             num_added: Wrapping(0),
+            max_chain_len: max_size,
         }
     }
 
@@ -299,6 +304,12 @@ impl Queue {
             + std::mem::size_of::<u16>()
     }
 
+    /// Returns the number of bytes of guest memory used by the descriptor table and the rings of
+    /// the queue, with the size selected by the driver.
+    pub fn guest_memory_size(&self) -> usize {
+        self.desc_table_size() + self.avail_ring_size() + self.used_ring_size()
+    }
+
     fn get_slice_ptr<M: GuestMemory>(
         &self,
         mem: &M,
@@ -521,7 +532,10 @@ impl Queue {
             return None;
         }
 
-        self.pop_unchecked()
+        match self.pop_unchecked() {
+            Some(chain) if self.reject_oversized_chain(&chain) => self.pop(),
+            chain => chain,
+        }
     }
 
     /// Try to pop the first available descriptor chain from the avail ring.
@@ -535,7 +549,35 @@ impl Queue {
             return None;
         }
 
-        self.pop_unchecked()
+        match self.pop_unchecked() {
+            Some(chain) if self.reject_oversized_chain(&chain) => self.pop_or_enable_notification(),
+            chain => chain,
+        }
+    }
+
+    /// Gives the popped `chain` back to the driver, with nothing written to it, if it has more
+    /// than `max_chain_len` descriptors. Returns whether the chain was rejected.
+    fn reject_oversized_chain(&mut self, chain: &DescriptorChain) -> bool {
+        // Chains are already bounded by the queue size, and are walked at most up to the first
+        // descriptor past the limit.
+        if self.max_chain_len >= self.actual_size()
+            || chain
+                .into_iter()
+                .nth(usize::from(self.max_chain_len))
+                .is_none()
+        {
+            return false;
+        }
+
+        error!(
+            "Rejected a descriptor chain longer than {} descriptors",
+            self.max_chain_len
+        );
+        METRICS.vmm.oversized_desc_chains.inc();
+        if let Err(err) = self.add_used(chain.index, 0) {
+            error!("Failed to give back an oversized descriptor chain: {}", err);
+        }
+        true
     }
 
     /// Pop the first available descriptor chain from the avail ring.
@@ -1344,6 +1386,62 @@ mod tests {
         q.used_ring_address = vq.used_start();
     }
 
+    #[test]
+    fn test_oversized_chain() {
+        let m = &default_mem();
+        let vq = VirtQueue::new(GuestAddress(0), m, 16);
+        let mut q = vq.create_queue();
+        q.ready = true;
+        q.max_chain_len = 4;
+
+        for j in 0..16 {
+            vq.dtable[j as usize].set(0x1000 * u64::from(j + 1), 0x1000, VIRTQ_DESC_F_NEXT, j + 1);
+        }
+        // The chains are (0..=7), (8, 9, 10, 11) and (12, 13, 12, 13, ...), which loops.
+        vq.dtable[7].flags.set(0);
+        vq.dtable[11].flags.set(0);
+        vq.dtable[13].next.set(12);
+        vq.avail.ring[0].set(0);
+        vq.avail.ring[1].set(8);
+        vq.avail.ring[2].set(12);
+        vq.avail.idx.set(3);
+
+        let oversized_chains = METRICS.vmm.oversized_desc_chains.count();
+
+        // The first chain is given back to the driver, unprocessed, and the second one is popped.
+        let chain = q.pop().unwrap();
+        assert_eq!(chain.index, 8);
+        assert_eq!(chain.into_iter().count(), 4);
+        assert_eq!(vq.used.idx.get(), 1);
+        let used = vq.used.ring[0].get();
+        assert_eq!((used.id, used.len), (0, 0));
+        assert_eq!(
+            METRICS.vmm.oversized_desc_chains.count(),
+            oversized_chains + 1
+        );
+
+        // The looping chain is rejected as well, without walking it past the limit.
+        assert!(q.pop().is_none());
+        assert_eq!(vq.used.idx.get(), 2);
+        assert_eq!(vq.used.ring[1].get().id, 12);
+        assert_eq!(
+            METRICS.vmm.oversized_desc_chains.count(),
+            oversized_chains + 2
+        );
+    }
+
+    #[test]
+    fn test_guest_memory_size() {
+        let mut q = Queue::new(256);
+        // The driver has not selected a size yet, only the ring headers are accounted.
+        assert_eq!(q.guest_memory_size(), 12);
+
+        q.size = 16;
+        // 16 descriptors of 16 bytes, an available ring with 16 entries of 2 bytes and a used
+        // ring with 16 entries of 8 bytes, plus the flags, index and event fields of the rings.
+        assert_eq!(q.guest_memory_size(), 16 * 16 + (6 + 16 * 2) + (6 + 16 * 8));
+    }
+
     #[test]
     fn test_queue_processing() {
         let m = &default_mem();
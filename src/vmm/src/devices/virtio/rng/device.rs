@@ -19,11 +19,14 @@ use crate::devices::virtio::queue::{Queue, FIRECRACKER_MAX_QUEUE_SIZE};
 use crate::devices::virtio::{ActivateError, TYPE_RNG};
 use crate::devices::DeviceError;
 use crate::logger::{debug, error, IncMetric};
-use crate::rate_limiter::{RateLimiter, TokenType};
+use crate::rate_limiter::{BucketUpdate, RateLimiter, TokenType};
 use crate::vstate::memory::GuestMemoryMmap;
 
 pub const ENTROPY_DEV_ID: &str = "rng";
 
+/// Features offered by the entropy device.
+pub(crate) const AVAIL_FEATURES: u64 = 1 << VIRTIO_F_VERSION_1;
+
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
 pub enum EntropyError {
     /// Error while handling an Event file descriptor: {0}
@@ -72,7 +75,7 @@ impl Entropy {
         let irq_trigger = IrqTrigger::new()?;
 
         Ok(Self {
-            avail_features: 1 << VIRTIO_F_VERSION_1,
+            avail_features: AVAIL_FEATURES,
             acked_features: 0u64,
             activate_event,
             device_state: DeviceState::Inactive,
@@ -228,6 +231,12 @@ impl Entropy {
         &self.rate_limiter
     }
 
+    /// Updates the bandwidth bucket of the rate limiter. Throttled requests are served once the
+    /// new bucket has enough budget.
+    pub fn update_rate_limiter(&mut self, bytes: BucketUpdate) {
+        self.rate_limiter.update_buckets(bytes, BucketUpdate::None);
+    }
+
     pub(crate) fn set_avail_features(&mut self, features: u64) {
         self.avail_features = features;
     }
@@ -316,6 +325,7 @@ mod tests {
     use crate::devices::virtio::test_utils::test::{
         create_virtio_mem, VirtioTestDevice, VirtioTestHelper,
     };
+    use crate::rate_limiter::TokenBucket;
 
     impl VirtioTestDevice for Entropy {
         fn set_queues(&mut self, queues: Vec<Queue>) {
@@ -569,6 +579,43 @@ mod tests {
         assert!(!th.device().rate_limiter().is_blocked());
     }
 
+    #[test]
+    fn test_update_rate_limiter() {
+        let mem = create_virtio_mem();
+        let mut th = VirtioTestHelper::<Entropy>::new(&mem, default_entropy());
+
+        th.activate_device(&mem);
+
+        // 1000 bytes every 100 msec, without initial burst.
+        th.device().update_rate_limiter(BucketUpdate::Update(
+            TokenBucket::new(1000, 0, 100).unwrap(),
+        ));
+        assert_eq!(
+            th.device().rate_limiter().bandwidth().unwrap().capacity(),
+            1000
+        );
+
+        // The first request drains the budget, so the second one is deferred instead of dropped.
+        let entropy_bytes = METRICS.entropy_bytes.count();
+        th.add_desc_chain(RNG_QUEUE, 0, &[(0, 1000, VIRTQ_DESC_F_WRITE)]);
+        th.add_desc_chain(RNG_QUEUE, 1, &[(1, 1000, VIRTQ_DESC_F_WRITE)]);
+        check_metric_after_block!(
+            METRICS.entropy_rate_limiter_throttled,
+            1,
+            th.device().process_entropy_queue()
+        );
+        assert_eq!(METRICS.entropy_bytes.count(), entropy_bytes + 1000);
+        assert!(th.device().rate_limiter().is_blocked());
+
+        // The second request is served once the bucket refilled.
+        check_metric_after_block!(METRICS.entropy_bytes, 1000, th.emulate_for_msec(200));
+        assert!(!th.device().rate_limiter().is_blocked());
+
+        // Disabling the bucket lifts the limit.
+        th.device().update_rate_limiter(BucketUpdate::Disabled);
+        assert!(th.device().rate_limiter().bandwidth().is_none());
+    }
+
     #[test]
     fn test_ops_rate_limiter() {
         let mem = create_virtio_mem();
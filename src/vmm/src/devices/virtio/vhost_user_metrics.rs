@@ -79,7 +79,7 @@ use std::sync::{Arc, RwLock};
 use serde::ser::SerializeMap;
 use serde::{Serialize, Serializer};
 
-use crate::logger::{SharedIncMetric, SharedStoreMetric};
+use crate::logger::{IncMetric, SharedIncMetric, SharedStoreMetric, StoreMetric};
 
 /// map of vhost_user drive id and metrics
 /// this should be protected by a lock before accessing.
@@ -105,6 +105,12 @@ impl VhostUserMetricsPerDevice {
                 .or_insert_with(|| Arc::new(VhostUserDeviceMetrics::default())),
         )
     }
+
+    /// Get the `VhostUserDeviceMetrics` of the vhost_user device having id `drive_id`,
+    /// if they were allocated.
+    pub fn get(drive_id: &str) -> Option<Arc<VhostUserDeviceMetrics>> {
+        METRICS.read().unwrap().metrics.get(drive_id).cloned()
+    }
 }
 
 /// Pool of vhost_user-related metrics per device behind a lock to
@@ -142,6 +148,37 @@ pub struct VhostUserDeviceMetrics {
     pub config_change_time_us: SharedStoreMetric,
 }
 
+/// Current values of the metrics of a vhost-user device, as returned by
+/// `VhostUserDeviceMetrics::values`. Reading them does not reset the metrics: the counters hold the
+/// totals since the device was created, while a metrics flush reports the increase since the
+/// previous flush.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct VhostUserDeviceMetricsValues {
+    /// Number of times when activate failed on a vhost_user device.
+    pub activate_fails: u64,
+    /// Number of times when interacting with the space config of a vhost-user device failed.
+    pub cfg_fails: u64,
+    /// Vhost-user init time in microseconds.
+    pub init_time_us: u64,
+    /// Vhost-user activate time in microseconds.
+    pub activate_time_us: u64,
+    /// Vhost-user config change time in microseconds.
+    pub config_change_time_us: u64,
+}
+
+impl VhostUserDeviceMetrics {
+    /// Reads the current values of the metrics without resetting them.
+    pub fn values(&self) -> VhostUserDeviceMetricsValues {
+        VhostUserDeviceMetricsValues {
+            activate_fails: self.activate_fails.count(),
+            cfg_fails: self.cfg_fails.count(),
+            init_time_us: self.init_time_us.fetch(),
+            activate_time_us: self.activate_time_us.fetch(),
+            config_change_time_us: self.config_change_time_us.fetch(),
+        }
+    }
+}
+
 #[cfg(test)]
 pub mod tests {
     use utils::time::{get_time_us, ClockType};
@@ -186,4 +223,18 @@ pub mod tests {
             serde_json::to_string(&vhost_user_metrics_backup).unwrap();
         assert_eq!(vhost_user_metrics_local, vhost_user_metrics_global);
     }
+
+    #[test]
+    fn test_vhost_user_metrics_values() {
+        // The values read through the API have the same fields as the flushed metrics, which
+        // tests/host_tools/fcmetrics.py checks. Until the first flush, they also hold the same
+        // values.
+        let metrics = VhostUserDeviceMetrics::default();
+        metrics.cfg_fails.inc();
+        metrics.activate_time_us.store(5);
+
+        let values = serde_json::to_value(metrics.values()).unwrap();
+        let flushed = serde_json::to_value(&metrics).unwrap();
+        assert_eq!(values, flushed);
+    }
 }
@@ -328,6 +328,10 @@ where
         );
     }
 
+    fn config_space(&self) -> Vec<u8> {
+        self.cid().to_le_bytes().to_vec()
+    }
+
     fn activate(&mut self, mem: GuestMemoryMmap) -> Result<(), ActivateError> {
         for q in self.queues.iter_mut() {
             q.initialize(&mem)
@@ -22,12 +22,14 @@ mod unix;
 
 use std::os::unix::io::AsRawFd;
 
+use serde::Serialize;
 use vm_memory::GuestMemoryError;
 use vmm_sys_util::epoll::EventSet;
 
 pub use self::defs::uapi::VIRTIO_ID_VSOCK as TYPE_VSOCK;
 pub use self::defs::VSOCK_DEV_ID;
 pub use self::device::Vsock;
+pub(crate) use self::device::AVAIL_FEATURES;
 use self::packet::{VsockPacketRx, VsockPacketTx};
 pub use self::unix::{VsockUnixBackend, VsockUnixBackendError};
 use super::iov_deque::IovDequeError;
@@ -195,3 +197,16 @@ pub trait VsockChannel {
 /// Currently, the only implementation we have is `crate::devices::virtio::unix::muxer::VsockMuxer`,
 /// which translates guest-side vsock connections to host-side Unix domain socket connections.
 pub trait VsockBackend: VsockChannel + VsockEpollListener + Send {}
+
+/// Statistics about the connections handled by the vsock backend.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct VsockStats {
+    /// Number of connections currently open.
+    pub active_connections: u64,
+    /// Number of payload bytes delivered to the guest.
+    pub rx_bytes: u64,
+    /// Number of payload bytes sent by the guest.
+    pub tx_bytes: u64,
+    /// Number of connection requests refused, from either the guest or the host.
+    pub rejected_connections: u64,
+}
@@ -41,7 +41,7 @@ use vmm_sys_util::epoll::{ControlOperation, Epoll, EpollEvent, EventSet};
 
 use super::super::csm::ConnState;
 use super::super::defs::uapi;
-use super::super::{VsockBackend, VsockChannel, VsockEpollListener, VsockError};
+use super::super::{VsockBackend, VsockChannel, VsockEpollListener, VsockError, VsockStats};
 use super::muxer_killq::MuxerKillQ;
 use super::muxer_rxq::MuxerRxQ;
 use super::{defs, MuxerConnection, VsockUnixBackendError};
@@ -108,6 +108,9 @@ pub struct VsockMuxer {
     local_port_set: HashSet<u32>,
     /// The last used host-side port.
     local_port_last: u32,
+    /// Traffic and connection statistics. `active_connections` is only filled in when the
+    /// statistics are queried.
+    stats: VsockStats,
 }
 
 impl VsockChannel for VsockMuxer {
@@ -164,6 +167,10 @@ impl VsockChannel for VsockMuxer {
             };
 
             if res.is_ok() {
+                if pkt.hdr.op() == uapi::VSOCK_OP_RW {
+                    self.stats.rx_bytes += u64::from(pkt.hdr.len());
+                }
+
                 // Inspect traffic, looking for RST packets, since that means we have to
                 // terminate and remove this connection from the active connection pool.
                 //
@@ -246,6 +253,9 @@ impl VsockChannel for VsockMuxer {
         self.apply_conn_mutation(conn_key, |conn| {
             res = conn.send_pkt(pkt);
         });
+        if res.is_ok() && pkt.hdr.op() == uapi::VSOCK_OP_RW {
+            self.stats.tx_bytes += u64::from(pkt.hdr.len());
+        }
 
         res
     }
@@ -321,6 +331,7 @@ impl VsockMuxer {
             killq: MuxerKillQ::new(),
             local_port_last: (1u32 << 30) - 1,
             local_port_set: HashSet::with_capacity(defs::MAX_CONNECTIONS),
+            stats: VsockStats::default(),
         };
 
         // Listen on the host initiated socket, for incoming connections.
@@ -333,6 +344,14 @@ impl VsockMuxer {
         &self.host_sock_path
     }
 
+    /// Return the statistics about the connections handled by the muxer.
+    pub fn stats(&self) -> VsockStats {
+        VsockStats {
+            active_connections: self.conn_map.len() as u64,
+            ..self.stats
+        }
+    }
+
     /// Handle/dispatch an epoll event to its listener.
     fn handle_event(&mut self, fd: RawFd, event_set: EventSet) {
         debug!(
@@ -361,6 +380,7 @@ impl VsockMuxer {
                     // immediately discard this potentially new one.
                     warn!("vsock: connection limit reached; refusing new host connection");
                     self.host_sock.accept().map(|_| 0).unwrap_or(0);
+                    self.stats.rejected_connections += 1;
                     return;
                 }
                 self.host_sock
@@ -407,6 +427,7 @@ impl VsockMuxer {
                         })
                         .unwrap_or_else(|err| {
                             info!("vsock: error adding local-init connection: {:?}", err);
+                            self.stats.rejected_connections += 1;
                         })
                 }
             }
@@ -632,7 +653,10 @@ impl VsockMuxer {
                     ),
                 )
             })
-            .unwrap_or_else(|_| self.enq_rst(pkt.hdr.dst_port(), pkt.hdr.src_port()));
+            .unwrap_or_else(|_| {
+                self.stats.rejected_connections += 1;
+                self.enq_rst(pkt.hdr.dst_port(), pkt.hdr.src_port())
+            });
     }
 
     /// Perform an action that might mutate a connection's state.
@@ -1534,4 +1558,46 @@ mod tests {
         // Check that the connection was removed.
         assert_eq!(METRICS.conns_removed.count(), conns_removed + 1);
     }
+
+    #[test]
+    fn test_vsock_stats() {
+        let mut ctx = MuxerTestContext::new("vsock_stats");
+        assert_eq!(ctx.muxer.stats(), VsockStats::default());
+
+        // Open a host-initiated connection.
+        let peer_port = 1025;
+        let (mut stream, local_port) = ctx.local_connect(peer_port);
+        assert_eq!(ctx.muxer.stats().active_connections, 1);
+
+        // Send some data from the guest to the host, and back.
+        let data = [1, 2, 3, 4];
+        ctx.init_data_tx_pkt(local_port, peer_port, &data);
+        ctx.send();
+        let data = [5, 6, 7, 8, 9, 10];
+        stream.write_all(&data).unwrap();
+        ctx.notify_muxer();
+        ctx.recv();
+
+        // Close the connection from the host side, the guest answers the shutdown with an RST.
+        drop(stream);
+        ctx.notify_muxer();
+        ctx.recv();
+        assert_eq!(ctx.rx_pkt.hdr.op(), uapi::VSOCK_OP_SHUTDOWN);
+        ctx.init_tx_pkt(local_port, peer_port, uapi::VSOCK_OP_RST);
+        ctx.send();
+
+        // Nothing listens on the port the guest connects to.
+        ctx.init_tx_pkt(local_port, peer_port, uapi::VSOCK_OP_REQUEST);
+        ctx.send();
+
+        assert_eq!(
+            ctx.muxer.stats(),
+            VsockStats {
+                active_connections: 0,
+                rx_bytes: 6,
+                tx_bytes: 4,
+                rejected_connections: 1,
+            }
+        );
+    }
 }
@@ -0,0 +1,482 @@
+// Copyright 2024 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::io;
+use std::sync::atomic::AtomicU32;
+use std::sync::Arc;
+use std::time::Duration;
+
+use serde::{Deserialize, Serialize};
+use timerfd::{ClockId, SetTimeFlags, TimerFd, TimerState};
+use vmm_sys_util::eventfd::EventFd;
+
+use super::metrics::METRICS;
+use super::{WATCHDOG_NUM_QUEUES, WATCHDOG_QUEUE};
+use crate::devices::virtio::device::{DeviceState, IrqTrigger, IrqType, VirtioDevice};
+use crate::devices::virtio::gen::virtio_blk::VIRTIO_F_VERSION_1;
+use crate::devices::virtio::queue::{Queue, FIRECRACKER_MAX_QUEUE_SIZE};
+use crate::devices::virtio::{ActivateError, TYPE_WATCHDOG};
+use crate::devices::DeviceError;
+use crate::logger::{error, warn, IncMetric};
+use crate::utils::u64_to_usize;
+use crate::vstate::memory::GuestMemoryMmap;
+
+pub const WATCHDOG_DEV_ID: &str = "watchdog";
+
+/// Features offered by the watchdog device.
+pub(crate) const AVAIL_FEATURES: u64 = 1 << VIRTIO_F_VERSION_1;
+
+/// Action taken by the device when the guest fails to pet the watchdog in time.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Deserialize, Serialize)]
+pub enum WatchdogAction {
+    /// Only log the expiration of the watchdog.
+    #[default]
+    Log,
+    /// Reset the microVM, the same way a guest initiated reset does. Firecracker exits
+    /// successfully.
+    Reset,
+    /// Stop the microVM. Firecracker exits with the `WatchdogExpired` exit code.
+    Stop,
+}
+
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum WatchdogError {
+    /// Error while handling an Event file descriptor: {0}
+    EventFd(#[from] io::Error),
+    /// Error creating the watchdog timer: {0}
+    Timer(io::Error),
+}
+
+/// Virtio device which the guest has to pet, by making a buffer available on its queue, at
+/// least once every `timeout_s` seconds. The timeout is exposed to the guest as a little endian
+/// u32 in the device configuration space.
+#[derive(Debug)]
+pub struct Watchdog {
+    // VirtIO fields
+    avail_features: u64,
+    acked_features: u64,
+    activate_event: EventFd,
+
+    // Transport fields
+    device_state: DeviceState,
+    pub(crate) queues: Vec<Queue>,
+    queue_events: Vec<EventFd>,
+    irq_trigger: IrqTrigger,
+
+    // Device specific fields
+    timeout_s: u32,
+    action: WatchdogAction,
+    timeout: Duration,
+    pub(crate) timer: TimerFd,
+    exit_evt: Option<EventFd>,
+    expired: bool,
+    paused: bool,
+}
+
+impl Watchdog {
+    pub fn new(timeout_s: u32, action: WatchdogAction) -> Result<Self, WatchdogError> {
+        let queues = vec![Queue::new(FIRECRACKER_MAX_QUEUE_SIZE); WATCHDOG_NUM_QUEUES];
+        Self::new_with_queues(queues, timeout_s, action)
+    }
+
+    pub fn new_with_queues(
+        queues: Vec<Queue>,
+        timeout_s: u32,
+        action: WatchdogAction,
+    ) -> Result<Self, WatchdogError> {
+        let activate_event = EventFd::new(libc::EFD_NONBLOCK)?;
+        let queue_events = (0..WATCHDOG_NUM_QUEUES)
+            .map(|_| EventFd::new(libc::EFD_NONBLOCK))
+            .collect::<Result<Vec<EventFd>, io::Error>>()?;
+        let irq_trigger = IrqTrigger::new()?;
+        let timer =
+            TimerFd::new_custom(ClockId::Monotonic, true, true).map_err(WatchdogError::Timer)?;
+
+        Ok(Self {
+            avail_features: AVAIL_FEATURES,
+            acked_features: 0u64,
+            activate_event,
+            device_state: DeviceState::Inactive,
+            queues,
+            queue_events,
+            irq_trigger,
+            timeout_s,
+            action,
+            timeout: Duration::from_secs(u64::from(timeout_s)),
+            timer,
+            exit_evt: None,
+            expired: false,
+            paused: false,
+        })
+    }
+
+    pub fn id(&self) -> &str {
+        WATCHDOG_DEV_ID
+    }
+
+    /// Interval, in seconds, in which the guest has to pet the watchdog.
+    pub fn timeout_s(&self) -> u32 {
+        self.timeout_s
+    }
+
+    /// Action taken when the watchdog expires.
+    pub fn action(&self) -> WatchdogAction {
+        self.action
+    }
+
+    /// Whether the guest failed to pet the watchdog in time at least once.
+    pub fn expired(&self) -> bool {
+        self.expired
+    }
+
+    /// Whether the watchdog expired with the `Stop` action, requesting the microVM to stop.
+    pub fn stop_requested(&self) -> bool {
+        self.expired && self.action == WatchdogAction::Stop
+    }
+
+    /// Sets the event used to request the VMM to exit when the watchdog expires.
+    pub fn set_exit_evt(&mut self, exit_evt: EventFd) {
+        self.exit_evt = Some(exit_evt);
+    }
+
+    /// Stops counting the timeout while the microVM is paused, since the guest cannot pet the
+    /// watchdog.
+    pub fn pause(&mut self) {
+        self.paused = true;
+        self.timer
+            .set_state(TimerState::Disarmed, SetTimeFlags::Default);
+    }
+
+    /// Counts the full timeout again once the microVM is resumed, unless the guest driver is not
+    /// up yet or the microVM is going away after the watchdog expired.
+    pub fn resume(&mut self) {
+        self.paused = false;
+        if self.is_activated() && !(self.expired && self.action != WatchdogAction::Log) {
+            self.arm_timer();
+        }
+    }
+
+    pub(crate) fn set_avail_features(&mut self, features: u64) {
+        self.avail_features = features;
+    }
+
+    pub(crate) fn set_irq_status(&mut self, status: u32) {
+        self.irq_trigger.irq_status = Arc::new(AtomicU32::new(status));
+    }
+
+    pub(crate) fn set_expired(&mut self, expired: bool) {
+        self.expired = expired;
+    }
+
+    // The timer is only armed when the microVM is resumed.
+    pub(crate) fn set_activated(&mut self, mem: GuestMemoryMmap) {
+        self.paused = true;
+        self.device_state = DeviceState::Activated(mem);
+    }
+
+    fn signal_used_queue(&self) -> Result<(), DeviceError> {
+        self.irq_trigger
+            .trigger_irq(IrqType::Vring)
+            .map_err(DeviceError::FailedSignalingIrq)
+    }
+
+    // (Re)starts counting the timeout from now on.
+    fn arm_timer(&mut self) {
+        if self.paused {
+            return;
+        }
+        let timer_state = TimerState::Periodic {
+            current: self.timeout,
+            interval: self.timeout,
+        };
+        self.timer.set_state(timer_state, SetTimeFlags::Default);
+    }
+
+    fn process_watchdog_queue(&mut self) {
+        let mut used_any = false;
+        while let Some(desc) = self.queues[WATCHDOG_QUEUE].pop() {
+            METRICS.pet_count.inc();
+            if let Err(err) = self.queues[WATCHDOG_QUEUE].add_used(desc.index, 0) {
+                error!("watchdog: Could not add used descriptor to queue: {err}");
+                METRICS.event_fails.inc();
+                break;
+            }
+            used_any = true;
+        }
+
+        if used_any {
+            // Any buffer made available by the guest counts as a pet.
+            self.arm_timer();
+            self.signal_used_queue().unwrap_or_else(|err| {
+                error!("watchdog: {err:?}");
+                METRICS.event_fails.inc()
+            });
+        }
+    }
+
+    pub(crate) fn process_watchdog_queue_event(&mut self) {
+        if let Err(err) = self.queue_events[WATCHDOG_QUEUE].read() {
+            error!("Failed to read watchdog queue event: {err}");
+            METRICS.event_fails.inc();
+        } else {
+            self.process_watchdog_queue();
+        }
+    }
+
+    pub(crate) fn process_timer_event(&mut self) {
+        // Nothing to do if the timer did not actually expire.
+        if self.timer.read() == 0 {
+            return;
+        }
+        METRICS.expired_count.inc();
+        self.expired = true;
+
+        match self.action {
+            WatchdogAction::Log => {
+                warn!(
+                    "watchdog: The guest did not pet the watchdog in the last {} seconds.",
+                    self.timeout_s
+                );
+            }
+            WatchdogAction::Reset | WatchdogAction::Stop => {
+                error!(
+                    "watchdog: The guest did not pet the watchdog in the last {} seconds. Taking \
+                     action: {:?}.",
+                    self.timeout_s, self.action
+                );
+                // Stop counting, the microVM is going away.
+                self.timer
+                    .set_state(TimerState::Disarmed, SetTimeFlags::Default);
+                match self.exit_evt.as_ref() {
+                    Some(exit_evt) => exit_evt.write(1).unwrap_or_else(|err| {
+                        error!("watchdog: Failed to signal the VMM exit event: {err}");
+                        METRICS.event_fails.inc();
+                    }),
+                    None => {
+                        error!("watchdog: No VMM exit event set up.");
+                        METRICS.event_fails.inc();
+                    }
+                }
+            }
+        }
+    }
+
+    pub fn process_virtio_queues(&mut self) {
+        self.process_watchdog_queue();
+    }
+
+    pub(crate) fn activate_event(&self) -> &EventFd {
+        &self.activate_event
+    }
+}
+
+impl VirtioDevice for Watchdog {
+    fn device_type(&self) -> u32 {
+        TYPE_WATCHDOG
+    }
+
+    fn queues(&self) -> &[Queue] {
+        &self.queues
+    }
+
+    fn queues_mut(&mut self) -> &mut [Queue] {
+        &mut self.queues
+    }
+
+    fn queue_events(&self) -> &[EventFd] {
+        &self.queue_events
+    }
+
+    fn interrupt_trigger(&self) -> &IrqTrigger {
+        &self.irq_trigger
+    }
+
+    fn avail_features(&self) -> u64 {
+        self.avail_features
+    }
+
+    fn acked_features(&self) -> u64 {
+        self.acked_features
+    }
+
+    fn set_acked_features(&mut self, acked_features: u64) {
+        self.acked_features = acked_features;
+    }
+
+    fn read_config(&self, offset: u64, data: &mut [u8]) {
+        let config_space_bytes = self.timeout_s.to_le_bytes();
+        if let Some(config_space_bytes) = config_space_bytes.get(u64_to_usize(offset)..) {
+            let len = config_space_bytes.len().min(data.len());
+            data[..len].copy_from_slice(&config_space_bytes[..len]);
+        } else {
+            error!("Failed to read config space");
+        }
+    }
+
+    fn write_config(&mut self, _offset: u64, _data: &[u8]) {}
+
+    fn is_activated(&self) -> bool {
+        self.device_state.is_activated()
+    }
+
+    fn config_space(&self) -> Vec<u8> {
+        self.timeout_s.to_le_bytes().to_vec()
+    }
+
+    fn activate(&mut self, mem: GuestMemoryMmap) -> Result<(), ActivateError> {
+        for q in self.queues.iter_mut() {
+            q.initialize(&mem)
+                .map_err(ActivateError::QueueMemoryError)?;
+        }
+
+        self.activate_event.write(1).map_err(|_| {
+            METRICS.activate_fails.inc();
+            ActivateError::EventFd
+        })?;
+        self.device_state = DeviceState::Activated(mem);
+        // The guest driver is up, so it is expected to pet the watchdog from now on.
+        self.arm_timer();
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::check_metric_after_block;
+    use crate::devices::virtio::test_utils::test::{
+        create_virtio_mem, VirtioTestDevice, VirtioTestHelper,
+    };
+
+    impl VirtioTestDevice for Watchdog {
+        fn set_queues(&mut self, queues: Vec<Queue>) {
+            self.queues = queues;
+        }
+
+        fn num_queues() -> usize {
+            WATCHDOG_NUM_QUEUES
+        }
+    }
+
+    fn default_watchdog(action: WatchdogAction) -> Watchdog {
+        let mut watchdog = Watchdog::new(1, action).unwrap();
+        // Use a shorter timeout to keep the tests fast.
+        watchdog.timeout = Duration::from_millis(300);
+        watchdog.set_exit_evt(EventFd::new(libc::EFD_NONBLOCK).unwrap());
+        watchdog
+    }
+
+    #[test]
+    fn test_new() {
+        let watchdog = Watchdog::new(10, WatchdogAction::Reset).unwrap();
+
+        assert_eq!(watchdog.id(), WATCHDOG_DEV_ID);
+        assert_eq!(watchdog.device_type(), TYPE_WATCHDOG);
+        assert_eq!(watchdog.avail_features(), 1 << VIRTIO_F_VERSION_1);
+        assert_eq!(watchdog.acked_features(), 0);
+        assert_eq!(watchdog.timeout_s(), 10);
+        assert_eq!(watchdog.action(), WatchdogAction::Reset);
+        assert!(!watchdog.is_activated());
+        assert!(!watchdog.stop_requested());
+    }
+
+    #[test]
+    fn test_read_config() {
+        let mut watchdog = Watchdog::new(0x1234_5678, WatchdogAction::Log).unwrap();
+        let mut config = vec![0; 4];
+
+        watchdog.read_config(0, &mut config);
+        assert_eq!(config, vec![0x78, 0x56, 0x34, 0x12]);
+
+        let mut config = vec![0; 2];
+        watchdog.read_config(2, &mut config);
+        assert_eq!(config, vec![0x34, 0x12]);
+
+        // Reading out of bounds does not modify the data.
+        watchdog.read_config(1024, &mut config);
+        assert_eq!(config, vec![0x34, 0x12]);
+
+        // The configuration space is read-only.
+        watchdog.write_config(0, &[0; 4]);
+        let mut config = vec![0; 4];
+        watchdog.read_config(0, &mut config);
+        assert_eq!(config, vec![0x78, 0x56, 0x34, 0x12]);
+    }
+
+    #[test]
+    fn test_pet_watchdog() {
+        let mem = create_virtio_mem();
+        let mut th =
+            VirtioTestHelper::<Watchdog>::new(&mem, default_watchdog(WatchdogAction::Stop));
+        th.activate_device(&mem);
+
+        // Keep petting the watchdog more often than the timeout, for longer than the timeout.
+        for i in 0..6 {
+            std::thread::sleep(Duration::from_millis(100));
+            th.add_desc_chain(WATCHDOG_QUEUE, 0, &[(i, 16, 0)]);
+            check_metric_after_block!(METRICS.pet_count, 1, th.emulate_for_msec(100));
+        }
+
+        // No action was taken.
+        assert!(!th.device().expired());
+        assert!(!th.device().stop_requested());
+        assert!(th.device().exit_evt.as_ref().unwrap().read().is_err());
+    }
+
+    #[test]
+    fn test_watchdog_expired() {
+        let mem = create_virtio_mem();
+        let mut th =
+            VirtioTestHelper::<Watchdog>::new(&mem, default_watchdog(WatchdogAction::Stop));
+        th.activate_device(&mem);
+
+        // Let the watchdog lapse.
+        assert_eq!(th.emulate_for_msec(1000).unwrap(), 1);
+
+        // The VMM was requested to stop the microVM.
+        assert!(th.device().expired());
+        assert!(th.device().stop_requested());
+        assert_eq!(th.device().exit_evt.as_ref().unwrap().read().unwrap(), 1);
+    }
+
+    #[test]
+    fn test_pause_resume() {
+        let mem = create_virtio_mem();
+        let mut th =
+            VirtioTestHelper::<Watchdog>::new(&mem, default_watchdog(WatchdogAction::Stop));
+        th.activate_device(&mem);
+
+        // The watchdog does not expire while the microVM is paused, even if the guest pets it.
+        th.device().pause();
+        th.add_desc_chain(WATCHDOG_QUEUE, 0, &[(0, 16, 0)]);
+        assert_eq!(th.emulate_for_msec(1000).unwrap(), 1);
+        assert!(!th.device().expired());
+        assert_eq!(th.device().timer.get_state(), TimerState::Disarmed);
+
+        // Once resumed, the guest has to pet the watchdog again.
+        th.device().resume();
+        assert_eq!(th.emulate_for_msec(1000).unwrap(), 1);
+        assert!(th.device().expired());
+        assert!(th.device().stop_requested());
+
+        // The timer stays disarmed once the microVM is going away.
+        th.device().pause();
+        th.device().resume();
+        assert_eq!(th.device().timer.get_state(), TimerState::Disarmed);
+    }
+
+    #[test]
+    fn test_watchdog_expired_log() {
+        let mem = create_virtio_mem();
+        let mut th = VirtioTestHelper::<Watchdog>::new(&mem, default_watchdog(WatchdogAction::Log));
+        th.activate_device(&mem);
+
+        // Let the watchdog lapse.
+        assert_eq!(th.emulate_for_msec(1000).unwrap(), 1);
+
+        // Only a message was logged, the microVM keeps running.
+        assert!(th.device().expired());
+        assert!(!th.device().stop_requested());
+        assert!(th.device().exit_evt.as_ref().unwrap().read().is_err());
+    }
+}
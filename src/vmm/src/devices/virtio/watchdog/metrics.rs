@@ -0,0 +1,83 @@
+// Copyright 2024 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Defines the metrics system for the watchdog device.
+//!
+//! # Metrics format
+//! The metrics are flushed in JSON when requested by vmm::logger::metrics::METRICS.write().
+//!
+//! ## JSON example with metrics:
+//! ```json
+//!  "watchdog": {
+//!     "activate_fails": "SharedIncMetric",
+//!     "pet_count": "SharedIncMetric",
+//!     "expired_count": "SharedIncMetric",
+//!     ...
+//!  }
+//! }
+//! ```
+//! Each `watchdog` field in the example above is a serializable `WatchdogDeviceMetrics`
+//! structure collecting metrics such as `activate_fails`, `pet_count` etc. for the watchdog
+//! device. Since watchdog doesn't support multiple devices, there is no per device metrics and
+//! `watchdog` represents the aggregate watchdog metrics.
+//!
+//! The system implements 1 type of metrics:
+//! * Shared Incremental Metrics (SharedIncMetrics) - dedicated for the metrics which need a counter
+//!   (i.e the number of times an API request failed). These metrics are reset upon flush.
+
+use serde::ser::SerializeMap;
+use serde::{Serialize, Serializer};
+
+use crate::logger::SharedIncMetric;
+
+/// Stores aggregated watchdog metrics
+pub(super) static METRICS: WatchdogDeviceMetrics = WatchdogDeviceMetrics::new();
+
+/// Called by METRICS.flush(), this function facilitates serialization of watchdog device metrics.
+pub fn flush_metrics<S: Serializer>(serializer: S) -> Result<S::Ok, S::Error> {
+    let mut seq = serializer.serialize_map(Some(1))?;
+    seq.serialize_entry("watchdog", &METRICS)?;
+    seq.end()
+}
+
+#[derive(Debug, Serialize)]
+pub(super) struct WatchdogDeviceMetrics {
+    /// Number of device activation failures
+    pub activate_fails: SharedIncMetric,
+    /// Number of watchdog queue or timer event handling failures
+    pub event_fails: SharedIncMetric,
+    /// Number of times the guest pet the watchdog
+    pub pet_count: SharedIncMetric,
+    /// Number of times the guest failed to pet the watchdog in time
+    pub expired_count: SharedIncMetric,
+}
+impl WatchdogDeviceMetrics {
+    /// Const default construction.
+    const fn new() -> Self {
+        Self {
+            activate_fails: SharedIncMetric::new(),
+            event_fails: SharedIncMetric::new(),
+            pet_count: SharedIncMetric::new(),
+            expired_count: SharedIncMetric::new(),
+        }
+    }
+}
+
+#[cfg(test)]
+pub mod tests {
+    use super::*;
+    use crate::logger::IncMetric;
+
+    #[test]
+    fn test_watchdog_dev_metrics() {
+        let watchdog_metrics: WatchdogDeviceMetrics = WatchdogDeviceMetrics::new();
+        let watchdog_metrics_local: String = serde_json::to_string(&watchdog_metrics).unwrap();
+        // the 1st serialize flushes the metrics and resets values to 0 so that
+        // we can compare the values with local metrics.
+        serde_json::to_string(&METRICS).unwrap();
+        let watchdog_metrics_global: String = serde_json::to_string(&METRICS).unwrap();
+        assert_eq!(watchdog_metrics_local, watchdog_metrics_global);
+        watchdog_metrics.pet_count.inc();
+        assert_eq!(watchdog_metrics.pet_count.count(), 1);
+    }
+}
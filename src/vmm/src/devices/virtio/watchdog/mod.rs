@@ -0,0 +1,13 @@
+// Copyright 2024 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+pub mod device;
+mod event_handler;
+pub mod metrics;
+pub mod persist;
+
+pub use self::device::{Watchdog, WatchdogAction, WatchdogError, WATCHDOG_DEV_ID};
+
+pub(crate) const WATCHDOG_NUM_QUEUES: usize = 1;
+
+pub(crate) const WATCHDOG_QUEUE: usize = 0;
@@ -0,0 +1,138 @@
+// Copyright 2024 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Defines the structures needed for saving/restoring watchdog devices.
+
+use serde::{Deserialize, Serialize};
+use vmm_sys_util::eventfd::EventFd;
+
+use super::device::WatchdogAction;
+use super::{Watchdog, WatchdogError, WATCHDOG_NUM_QUEUES};
+use crate::devices::virtio::device::VirtioDevice;
+use crate::devices::virtio::persist::{PersistError as VirtioStateError, VirtioDeviceState};
+use crate::devices::virtio::queue::FIRECRACKER_MAX_QUEUE_SIZE;
+use crate::devices::virtio::TYPE_WATCHDOG;
+use crate::snapshot::Persist;
+use crate::vstate::memory::GuestMemoryMmap;
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+pub struct WatchdogState {
+    virtio_state: VirtioDeviceState,
+    timeout_s: u32,
+    action: WatchdogAction,
+    expired: bool,
+}
+
+#[derive(Debug)]
+pub struct WatchdogConstructorArgs {
+    mem: GuestMemoryMmap,
+    exit_evt: EventFd,
+}
+
+impl WatchdogConstructorArgs {
+    pub fn new(mem: GuestMemoryMmap, exit_evt: EventFd) -> Self {
+        Self { mem, exit_evt }
+    }
+}
+
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum WatchdogPersistError {
+    /// Create watchdog: {0}
+    CreateWatchdog(#[from] WatchdogError),
+    /// Virtio state: {0}
+    VirtioState(#[from] VirtioStateError),
+}
+
+impl Persist<'_> for Watchdog {
+    type State = WatchdogState;
+    type ConstructorArgs = WatchdogConstructorArgs;
+    type Error = WatchdogPersistError;
+
+    fn save(&self) -> Self::State {
+        WatchdogState {
+            virtio_state: VirtioDeviceState::from_device(self),
+            timeout_s: self.timeout_s(),
+            action: self.action(),
+            expired: self.expired(),
+        }
+    }
+
+    fn restore(
+        constructor_args: Self::ConstructorArgs,
+        state: &Self::State,
+    ) -> Result<Self, Self::Error> {
+        let queues = state.virtio_state.build_queues_checked(
+            &constructor_args.mem,
+            TYPE_WATCHDOG,
+            WATCHDOG_NUM_QUEUES,
+            FIRECRACKER_MAX_QUEUE_SIZE,
+        )?;
+
+        let mut watchdog = Watchdog::new_with_queues(queues, state.timeout_s, state.action)?;
+        watchdog.set_avail_features(state.virtio_state.avail_features);
+        watchdog.set_acked_features(state.virtio_state.acked_features);
+        watchdog.set_irq_status(state.virtio_state.interrupt_status);
+        watchdog.set_expired(state.expired);
+        watchdog.set_exit_evt(constructor_args.exit_evt);
+        if state.virtio_state.activated {
+            watchdog.set_activated(constructor_args.mem);
+        }
+
+        Ok(watchdog)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::atomic::Ordering;
+
+    use timerfd::TimerState;
+
+    use super::*;
+    use crate::devices::virtio::test_utils::test::create_virtio_mem;
+    use crate::devices::virtio::watchdog::WATCHDOG_DEV_ID;
+    use crate::snapshot::Snapshot;
+
+    #[test]
+    fn test_persistence() {
+        let mut mem = vec![0u8; 4096];
+        let mut watchdog = Watchdog::new(30, WatchdogAction::Reset).unwrap();
+        watchdog.set_activated(create_virtio_mem());
+        watchdog.set_expired(true);
+
+        Snapshot::serialize(&mut mem.as_mut_slice(), &watchdog.save()).unwrap();
+
+        let guest_mem = create_virtio_mem();
+        let mut restored = Watchdog::restore(
+            WatchdogConstructorArgs::new(guest_mem, EventFd::new(libc::EFD_NONBLOCK).unwrap()),
+            &Snapshot::deserialize(&mut mem.as_slice()).unwrap(),
+        )
+        .unwrap();
+
+        assert_eq!(restored.device_type(), TYPE_WATCHDOG);
+        assert_eq!(restored.id(), WATCHDOG_DEV_ID);
+        assert_eq!(restored.timeout_s(), 30);
+        assert_eq!(restored.action(), WatchdogAction::Reset);
+        assert!(restored.expired());
+        assert!(restored.is_activated());
+        assert_eq!(restored.avail_features(), watchdog.avail_features());
+        assert_eq!(restored.acked_features(), watchdog.acked_features());
+        assert_eq!(
+            restored.interrupt_status().load(Ordering::Relaxed),
+            watchdog.interrupt_status().load(Ordering::Relaxed)
+        );
+
+        // The restored watchdog only counts the timeout once the microVM is resumed, and not at
+        // all if the microVM was going away when it was snapshotted.
+        restored.resume();
+        assert_eq!(restored.timer.get_state(), TimerState::Disarmed);
+
+        restored.set_expired(false);
+        restored.pause();
+        restored.resume();
+        assert!(matches!(
+            restored.timer.get_state(),
+            TimerState::Periodic { .. }
+        ));
+    }
+}
@@ -72,6 +72,16 @@ impl IoUringError {
             Self::FullCQueue | Self::SQueue(SQueueError::FullQueue)
         )
     }
+
+    /// Whether the error is caused by the host kernel lacking io_uring support, either because
+    /// io_uring is not built in or disabled, or because a required feature is missing.
+    pub fn is_unsupported_err(&self) -> bool {
+        match self {
+            Self::Setup(err) => matches!(err.raw_os_error(), Some(libc::ENOSYS | libc::EPERM)),
+            Self::UnsupportedFeature(_) | Self::UnsupportedOperation(_) => true,
+            _ => false,
+        }
+    }
 }
 
 /// Main object representing an io_uring instance.
@@ -617,4 +627,16 @@ mod tests {
         free_mem_region(sync_read_mem_region);
         free_mem_region(async_read_mem_region);
     }
+
+    #[test]
+    fn test_is_unsupported_err() {
+        assert!(IoUringError::Setup(IOError::from_raw_os_error(libc::ENOSYS)).is_unsupported_err());
+        assert!(IoUringError::Setup(IOError::from_raw_os_error(libc::EPERM)).is_unsupported_err());
+        assert!(IoUringError::UnsupportedFeature("IORING_FEAT_NODROP").is_unsupported_err());
+        assert!(IoUringError::UnsupportedOperation("read").is_unsupported_err());
+        assert!(
+            !IoUringError::Setup(IOError::from_raw_os_error(libc::ENOMEM)).is_unsupported_err()
+        );
+        assert!(!IoUringError::FullCQueue.is_unsupported_err());
+    }
 }
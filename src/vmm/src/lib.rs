@@ -96,6 +96,8 @@ pub mod persist;
 pub mod resources;
 /// microVM RPC API adapters.
 pub mod rpc_interface;
+/// Audit log of the syscalls denied by the seccomp filters.
+pub mod seccomp_audit;
 /// Seccomp filter utilities.
 pub mod seccomp_filters;
 /// Signal handling utilities.
@@ -111,17 +113,21 @@ pub mod vmm_config;
 /// Module with virtual state structs.
 pub mod vstate;
 
+use std::collections::hash_map::Entry;
 use std::collections::HashMap;
 use std::io;
 use std::os::unix::io::AsRawFd;
 use std::sync::mpsc::RecvTimeoutError;
-use std::sync::{Arc, Barrier, Mutex};
+use std::sync::{Arc, Barrier, Mutex, MutexGuard};
 use std::time::Duration;
 
+use ::utils::time::{get_time_us, ClockType};
 use device_manager::acpi::ACPIDeviceManager;
 use device_manager::resources::ResourceAllocator;
 use devices::acpi::vmgenid::VmGenIdError;
-use event_manager::{EventManager as BaseEventManager, EventOps, Events, MutEventSubscriber};
+use event_manager::{
+    EventManager as BaseEventManager, EventOps, Events, MutEventSubscriber, SubscriberOps,
+};
 use seccompiler::BpfProgram;
 use userfaultfd::Uffd;
 use vmm_sys_util::epoll::EventSet;
@@ -136,22 +142,40 @@ use crate::device_manager::legacy::PortIODeviceManager;
 use crate::device_manager::mmio::MMIODeviceManager;
 use crate::devices::legacy::{IER_RDA_BIT, IER_RDA_OFFSET};
 use crate::devices::virtio::balloon::{
-    Balloon, BalloonConfig, BalloonError, BalloonStats, BALLOON_DEV_ID,
+    Balloon, BalloonConfig, BalloonError, BalloonFeatures, BalloonStats, BALLOON_DEV_ID,
 };
 use crate::devices::virtio::block::device::Block;
+use crate::devices::virtio::device::VirtioDevice;
+#[cfg(feature = "fault-injection")]
+use crate::devices::virtio::fault_injection::DeviceFault;
 use crate::devices::virtio::net::Net;
-use crate::devices::virtio::{TYPE_BALLOON, TYPE_BLOCK, TYPE_NET};
+use crate::devices::virtio::rng::device::ENTROPY_DEV_ID;
+use crate::devices::virtio::rng::Entropy;
+use crate::devices::virtio::vsock::{
+    Vsock, VsockStats, VsockUnixBackend, TYPE_VSOCK, VSOCK_DEV_ID,
+};
+use crate::devices::virtio::watchdog::{Watchdog, WATCHDOG_DEV_ID};
+use crate::devices::virtio::{TYPE_BALLOON, TYPE_BLOCK, TYPE_NET, TYPE_RNG, TYPE_WATCHDOG};
 use crate::logger::{error, info, warn, MetricsError, METRICS};
 use crate::persist::{MicrovmState, MicrovmStateError, VmInfo};
 use crate::rate_limiter::BucketUpdate;
+use crate::resources::VmResources;
 use crate::snapshot::Persist;
 use crate::utils::u64_to_usize;
+use crate::vmm_config::balloon::GuestMemoryCompaction;
+use crate::vmm_config::devices::{DeviceConfigSpace, DeviceMemoryUsage, VirtioDeviceType};
 use crate::vmm_config::instance_info::{InstanceInfo, VmState};
+#[cfg(target_arch = "x86_64")]
+use crate::vmm_config::machine_config::CpuFrequencies;
+use crate::vmm_config::net::NetRateLimiterState;
+use crate::vmm_config::{RateLimiterConfig, RateLimiterState};
 use crate::vstate::memory::{
-    GuestMemory, GuestMemoryExtension, GuestMemoryMmap, GuestMemoryRegion,
+    Address, GuestMemory, GuestMemoryExtension, GuestMemoryMmap, GuestMemoryRegion,
 };
 use crate::vstate::vcpu::VcpuState;
-pub use crate::vstate::vcpu::{Vcpu, VcpuConfig, VcpuEvent, VcpuHandle, VcpuResponse};
+pub use crate::vstate::vcpu::{
+    Vcpu, VcpuConfig, VcpuEvent, VcpuHandle, VcpuResponse, VcpuRunState, VcpuRunStats,
+};
 pub use crate::vstate::vm::Vm;
 
 /// Shorthand type for the EventManager flavour used by Firecracker.
@@ -189,6 +213,8 @@ pub enum FcExitCode {
     BadConfiguration = 152,
     /// Command line arguments parsing error.
     ArgParsing = 153,
+    /// Firecracker was stopped because the guest failed to pet the watchdog device in time.
+    WatchdogExpired = 158,
 }
 
 /// Timeout used in recv_timeout, when waiting for a vcpu response on
@@ -209,10 +235,15 @@ pub enum VmmError {
     Cmdline,
     /// Device manager error: {0}
     DeviceManager(device_manager::mmio::MmioError),
+    #[cfg(feature = "fault-injection")]
+    /// The {0:?} fault cannot be injected into {1:?} devices.
+    UnsupportedDeviceFault(DeviceFault, VirtioDeviceType),
     /// Error getting the KVM dirty bitmap. {0}
     DirtyBitmap(kvm_ioctls::Error),
     /// Event fd error: {0}
     EventFd(io::Error),
+    /// Event manager error: {0}
+    EventManager(event_manager::Error),
     /// I8042 error: {0}
     I8042Error(devices::legacy::I8042DeviceError),
     /// Cannot access kernel file: {0}
@@ -222,6 +253,9 @@ pub enum VmmError {
     LegacyIOBus(device_manager::legacy::LegacyDeviceError),
     /// Metrics error: {0}
     Metrics(MetricsError),
+    #[cfg(target_arch = "aarch64")]
+    /// Cannot press the power button: {0}
+    PowerButton(io::Error),
     /// Cannot add a device to the MMIO Bus. {0}
     RegisterMMIODevice(device_manager::mmio::MmioError),
     /// Cannot install seccomp filters: {0}
@@ -251,6 +285,12 @@ pub enum VmmError {
     VcpuResume,
     /// Failed to message the vCPUs.
     VcpuMessage,
+    /// Invalid vCPU index: {0}
+    InvalidVcpuIndex(usize),
+    /// Individual vCPUs can only be paused or resumed while the microVM is running.
+    VcpuStateChangeNotAllowed,
+    /// Cannot serialize the device states: {0}
+    DeviceStates(serde_json::Error),
     /// Cannot spawn Vcpu thread: {0}
     VcpuSpawn(io::Error),
     /// Vm error: {0}
@@ -305,10 +345,18 @@ pub struct Vmm {
     /// The [`InstanceInfo`] state of this [`Vmm`].
     pub instance_info: InstanceInfo,
     shutdown_exit_code: Option<FcExitCode>,
+    // Monotonic time at which the microVM first started running, in microseconds.
+    boot_time_us: Option<u64>,
+    // The frequencies advertised in CPUID leaf 0x16 of the vCPUs, once they are configured.
+    #[cfg(target_arch = "x86_64")]
+    cpu_frequencies: Option<CpuFrequencies>,
 
     // Guest VM core resources.
     vm: Vm,
     guest_memory: GuestMemoryMmap,
+    // The KVM dirty log fetched by `dirty_page_count`, kept for the next diff snapshot since KVM
+    // clears the dirty log when it is fetched.
+    fetched_dirty_log: Mutex<DirtyBitmap>,
     // Save UFFD in order to keep it open in the Firecracker process, as well.
     // Since this field is never read again, we need to allow `dead_code`.
     #[allow(dead_code)]
@@ -334,7 +382,17 @@ impl Vmm {
 
     /// Gets Vmm instance info.
     pub fn instance_info(&self) -> InstanceInfo {
-        self.instance_info.clone()
+        let mut instance_info = self.instance_info.clone();
+        instance_info.uptime_us = self
+            .boot_time_us
+            .map(|boot_time_us| get_time_us(ClockType::Monotonic).saturating_sub(boot_time_us));
+        instance_info
+    }
+
+    /// Returns the frequencies advertised to the guest in CPUID leaf 0x16, if any.
+    #[cfg(target_arch = "x86_64")]
+    pub fn cpu_frequencies(&self) -> Option<CpuFrequencies> {
+        self.cpu_frequencies
     }
 
     /// Provides the Vmm shutdown exit code if there is one.
@@ -351,6 +409,88 @@ impl Vmm {
         self.mmio_device_manager.get_device(device_type, device_id)
     }
 
+    /// Lists the type and id of the devices attached to the MMIO bus, sorted by type and id.
+    pub fn list_devices(&self) -> Vec<(DeviceType, String)> {
+        let mut devices: Vec<_> = self
+            .mmio_device_manager
+            .get_device_info()
+            .keys()
+            .cloned()
+            .collect();
+        devices.sort();
+        devices
+    }
+
+    /// Gets the specified virtio device, through its MMIO transport.
+    fn get_virtio_device(
+        &self,
+        device_type: VirtioDeviceType,
+        device_id: &str,
+    ) -> Result<Arc<Mutex<dyn VirtioDevice>>, VmmError> {
+        let busdev = self
+            .get_bus_device(DeviceType::Virtio(device_type.virtio_type()), device_id)
+            .ok_or(VmmError::DeviceManager(
+                device_manager::mmio::MmioError::DeviceNotFound,
+            ))?;
+        let virtio_device = busdev
+            .lock()
+            .expect("Poisoned lock")
+            .mmio_transport_ref()
+            .expect("Unexpected device type")
+            .device();
+
+        Ok(virtio_device)
+    }
+
+    /// Reports the guest memory used by the virtqueues of the specified virtio device.
+    pub fn device_memory_usage(
+        &self,
+        device_type: VirtioDeviceType,
+        device_id: &str,
+    ) -> Result<DeviceMemoryUsage, VmmError> {
+        let virtio_device = self.get_virtio_device(device_type, device_id)?;
+        let usage = DeviceMemoryUsage::new(virtio_device.lock().expect("Poisoned lock").queues());
+
+        Ok(usage)
+    }
+
+    /// Reports the raw configuration space of the specified virtio device.
+    pub fn device_config_space(
+        &self,
+        device_type: VirtioDeviceType,
+        device_id: &str,
+    ) -> Result<DeviceConfigSpace, VmmError> {
+        let virtio_device = self.get_virtio_device(device_type, device_id)?;
+        let config_space = virtio_device.lock().expect("Poisoned lock").config_space();
+
+        Ok(DeviceConfigSpace::new(&config_space))
+    }
+
+    /// Injects `fault` into the processing path of the specified virtio device.
+    #[cfg(feature = "fault-injection")]
+    pub fn inject_device_fault(
+        &mut self,
+        device_type: VirtioDeviceType,
+        device_id: &str,
+        fault: DeviceFault,
+    ) -> Result<(), VmmError> {
+        let result = match device_type {
+            VirtioDeviceType::Block if !matches!(fault, DeviceFault::NetTxDrop { .. }) => self
+                .mmio_device_manager
+                .with_virtio_device_with_id(TYPE_BLOCK, device_id, |block: &mut Block| {
+                    block.inject_fault(fault).map_err(|err| err.to_string())
+                }),
+            VirtioDeviceType::Net if !matches!(fault, DeviceFault::BlockReadError { .. }) => self
+                .mmio_device_manager
+                .with_virtio_device_with_id(TYPE_NET, device_id, |net: &mut Net| {
+                    net.inject_fault(fault);
+                    Ok(())
+                }),
+            _ => return Err(VmmError::UnsupportedDeviceFault(fault, device_type)),
+        };
+        result.map_err(VmmError::DeviceManager)
+    }
+
     /// Starts the microVM vcpus.
     ///
     /// # Errors
@@ -400,7 +540,9 @@ impl Vmm {
 
     /// Sends a resume command to the vCPUs.
     pub fn resume_vm(&mut self) -> Result<(), VmmError> {
+        self.mmio_device_manager.resume_io();
         self.mmio_device_manager.kick_devices();
+        self.mmio_device_manager.resume_watchdog();
 
         // Send the events.
         self.vcpus_handles
@@ -419,9 +561,19 @@ impl Vmm {
         }
 
         self.instance_info.state = VmState::Running;
+        if self.boot_time_us.is_none() {
+            self.boot_time_us = Some(get_time_us(ClockType::Monotonic));
+            self.instance_info.boot_timestamp_us = Some(get_time_us(ClockType::Real));
+        }
         Ok(())
     }
 
+    /// Stops processing device IO and completes the in-flight requests, while the vCPUs keep
+    /// running. Device IO is processed again once the microVM is resumed.
+    pub fn quiesce_io(&mut self) {
+        self.mmio_device_manager.quiesce_io();
+    }
+
     /// Sends a pause command to the vCPUs.
     pub fn pause_vm(&mut self) -> Result<(), VmmError> {
         // Send the events.
@@ -440,15 +592,65 @@ impl Vmm {
             return Err(VmmError::VcpuMessage);
         }
 
+        self.mmio_device_manager.pause_watchdog();
         self.instance_info.state = VmState::Paused;
         Ok(())
     }
 
+    /// Sends a pause command to the vCPU with the given index. The microVM remains in the
+    /// `Running` state, and pausing or resuming the microVM applies to all vCPUs again.
+    pub fn pause_vcpu(&mut self, index: usize) -> Result<(), VmmError> {
+        match self.send_vcpu_state_event(index, VcpuEvent::Pause)? {
+            VcpuResponse::Paused => Ok(()),
+            _ => Err(VmmError::VcpuMessage),
+        }
+    }
+
+    /// Sends a resume command to the vCPU with the given index.
+    pub fn resume_vcpu(&mut self, index: usize) -> Result<(), VmmError> {
+        match self.send_vcpu_state_event(index, VcpuEvent::Resume)? {
+            VcpuResponse::Resumed => Ok(()),
+            _ => Err(VmmError::VcpuMessage),
+        }
+    }
+
+    fn send_vcpu_state_event(
+        &self,
+        index: usize,
+        event: VcpuEvent,
+    ) -> Result<VcpuResponse, VmmError> {
+        // The microVM state tracks all the vCPUs, so a paused microVM is only resumed as a whole.
+        if self.instance_info.state != VmState::Running {
+            return Err(VmmError::VcpuStateChangeNotAllowed);
+        }
+        let handle = self
+            .vcpus_handles
+            .get(index)
+            .ok_or(VmmError::InvalidVcpuIndex(index))?;
+
+        handle
+            .send_event(event)
+            .map_err(|_| VmmError::VcpuMessage)?;
+        handle
+            .response_receiver()
+            .recv_timeout(RECV_TIMEOUT_SEC)
+            .map_err(|_| VmmError::VcpuMessage)
+    }
+
     /// Returns a reference to the inner `GuestMemoryMmap` object.
     pub fn guest_memory(&self) -> &GuestMemoryMmap {
         &self.guest_memory
     }
 
+    /// Describes the guest memory regions as `(guest_phys_addr, len_bytes)` pairs, ordered by
+    /// guest physical address.
+    pub fn memory_regions(&self) -> Vec<(u64, u64)> {
+        self.guest_memory
+            .iter()
+            .map(|region| (region.start_addr().raw_value(), region.len()))
+            .collect()
+    }
+
     /// Sets RDA bit in serial console
     pub fn emulate_serial_init(&self) -> Result<(), EmulateSerialInitError> {
         // When restoring from a previously saved state, there is no serial
@@ -507,6 +709,32 @@ impl Vmm {
             .map_err(VmmError::I8042Error)
     }
 
+    /// Asks the guest to power off gracefully.
+    ///
+    /// On x86_64 this injects CTRL+ALT+DEL in the i8042 device, while on aarch64 it presses the
+    /// power button wired to the GPIO controller. The microVM keeps running until the guest
+    /// completes its shutdown sequence, so callers should watch for the vcpus exit event rather
+    /// than expect the guest to be stopped when this returns.
+    pub fn request_shutdown(&mut self) -> Result<(), VmmError> {
+        #[cfg(target_arch = "x86_64")]
+        {
+            self.send_ctrl_alt_del()
+        }
+        #[cfg(target_arch = "aarch64")]
+        {
+            self.get_bus_device(DeviceType::Gpio, "Gpio")
+                .ok_or(VmmError::DeviceManager(
+                    device_manager::mmio::MmioError::DeviceNotFound,
+                ))?
+                .lock()
+                .expect("Poisoned lock")
+                .gpio_mut()
+                .expect("Unexpected BusDeviceType")
+                .trigger_key(devices::legacy::gpio_pl061::POWER_KEY_LINE)
+                .map_err(VmmError::PowerButton)
+        }
+    }
+
     /// Saves the state of a paused Microvm.
     pub fn save_state(&mut self, vm_info: &VmInfo) -> Result<MicrovmState, MicrovmStateError> {
         use self::MicrovmStateError::SaveVmState;
@@ -529,7 +757,12 @@ impl Vmm {
         let acpi_dev_state = self.acpi_device_manager.save();
 
         Ok(MicrovmState {
-            vm_info: vm_info.clone(),
+            // The guest has less memory than configured if its allocation fell back to a smaller
+            // size.
+            vm_info: VmInfo {
+                mem_size_mib: mem_size_mib(self.guest_memory()),
+                ..vm_info.clone()
+            },
             memory_state,
             vm_state,
             vcpu_states,
@@ -538,6 +771,12 @@ impl Vmm {
         })
     }
 
+    /// Serializes the states of the devices, as they are persisted in a snapshot.
+    pub fn device_states(&self) -> Result<serde_json::Value, VmmError> {
+        serde_json::to_value(self.mmio_device_manager.save_device_states(false))
+            .map_err(VmmError::DeviceStates)
+    }
+
     fn save_vcpu_states(&mut self) -> Result<Vec<VcpuState>, MicrovmStateError> {
         for handle in self.vcpus_handles.iter() {
             handle
@@ -594,8 +833,38 @@ impl Vmm {
         Ok(cpu_configs)
     }
 
+    /// Retrieves the KVM exit statistics of each vCPU.
+    pub fn vcpu_run_stats(&self) -> Result<Vec<VcpuRunStats>, VmmError> {
+        self.vcpus_handles
+            .iter()
+            .try_for_each(|handle| handle.send_event(VcpuEvent::GetRunStats))
+            .map_err(|_| VmmError::VcpuMessage)?;
+
+        self.vcpus_handles
+            .iter()
+            .map(
+                |handle| match handle.response_receiver().recv_timeout(RECV_TIMEOUT_SEC) {
+                    Ok(VcpuResponse::RunStats(stats)) => Ok(stats),
+                    _ => Err(VmmError::VcpuMessage),
+                },
+            )
+            .collect()
+    }
+
+    /// Returns the run state of each vCPU, without interrupting them.
+    pub fn vcpu_run_states(&self) -> Vec<VcpuRunState> {
+        self.vcpus_handles
+            .iter()
+            .map(VcpuHandle::run_state)
+            .collect()
+    }
+
     /// Retrieves the KVM dirty bitmap for each of the guest's memory regions.
     pub fn reset_dirty_bitmap(&self) {
+        self.fetched_dirty_log
+            .lock()
+            .expect("Poisoned lock")
+            .clear();
         self.guest_memory
             .iter()
             .enumerate()
@@ -607,9 +876,29 @@ impl Vmm {
             });
     }
 
-    /// Retrieves the KVM dirty bitmap for each of the guest's memory regions.
+    /// Retrieves the KVM dirty bitmap for each of the guest's memory regions, including the pages
+    /// counted by [`Vmm::dirty_page_count`] since the last call.
     pub fn get_dirty_bitmap(&self) -> Result<DirtyBitmap, VmmError> {
-        let mut bitmap: DirtyBitmap = HashMap::new();
+        let mut fetched_dirty_log = self.fetch_dirty_log()?;
+        Ok(std::mem::take(&mut *fetched_dirty_log))
+    }
+
+    /// Retrieves the number of guest pages dirtied across all of the guest's memory regions since
+    /// the last call to [`Vmm::get_dirty_bitmap`]. The pages are still reported as dirty by
+    /// [`Vmm::get_dirty_bitmap`].
+    pub fn dirty_page_count(&self) -> Result<u64, VmmError> {
+        let fetched_dirty_log = self.fetch_dirty_log()?;
+        Ok(fetched_dirty_log
+            .values()
+            .flatten()
+            .map(|word| u64::from(word.count_ones()))
+            .sum())
+    }
+
+    /// Fetches the KVM dirty log of each of the guest's memory regions and merges it into the
+    /// dirty log fetched before.
+    fn fetch_dirty_log(&self) -> Result<MutexGuard<'_, DirtyBitmap>, VmmError> {
+        let mut fetched_dirty_log = self.fetched_dirty_log.lock().expect("Poisoned lock");
         self.guest_memory
             .iter()
             .enumerate()
@@ -618,11 +907,20 @@ impl Vmm {
                     .vm
                     .fd()
                     .get_dirty_log(u32::try_from(slot).unwrap(), u64_to_usize(region.len()))?;
-                bitmap.insert(slot, bitmap_region);
+                match fetched_dirty_log.entry(slot) {
+                    Entry::Occupied(mut entry) => entry
+                        .get_mut()
+                        .iter_mut()
+                        .zip(bitmap_region)
+                        .for_each(|(fetched, word)| *fetched |= word),
+                    Entry::Vacant(entry) => {
+                        entry.insert(bitmap_region);
+                    }
+                }
                 Ok(())
             })
             .map_err(VmmError::DirtyBitmap)?;
-        Ok(bitmap)
+        Ok(fetched_dirty_log)
     }
 
     /// Enables or disables KVM dirty page tracking.
@@ -653,6 +951,16 @@ impl Vmm {
             .map_err(VmmError::DeviceManager)
     }
 
+    /// Re-reads the size of the file backing the block device with `drive_id` id and notifies
+    /// the guest if it changed.
+    pub fn notify_block_device_resize(&mut self, drive_id: &str) -> Result<(), VmmError> {
+        self.mmio_device_manager
+            .with_virtio_device_with_id(TYPE_BLOCK, drive_id, |block: &mut Block| {
+                block.notify_resize().map_err(|err| err.to_string())
+            })
+            .map_err(VmmError::DeviceManager)
+    }
+
     /// Updates the rate limiter parameters for block device with `drive_id` id.
     pub fn update_block_rate_limiter(
         &mut self,
@@ -669,6 +977,32 @@ impl Vmm {
             .map_err(VmmError::DeviceManager)
     }
 
+    /// Returns the rate limiter parameters in effect for block device with `drive_id` id.
+    pub fn block_rate_limiter_config(&self, drive_id: &str) -> Result<RateLimiterConfig, VmmError> {
+        let mut config = RateLimiterConfig::default();
+        self.mmio_device_manager
+            .with_virtio_device_with_id(TYPE_BLOCK, drive_id, |block: &mut Block| {
+                config = block.rate_limiter_config().map_err(|err| err.to_string())?;
+                Ok(())
+            })
+            .map_err(VmmError::DeviceManager)?;
+
+        Ok(config)
+    }
+
+    /// Returns the live token bucket state of the rate limiter of block device with `drive_id` id.
+    pub fn block_rate_limiter_state(&self, drive_id: &str) -> Result<RateLimiterState, VmmError> {
+        let mut state = RateLimiterState::default();
+        self.mmio_device_manager
+            .with_virtio_device_with_id(TYPE_BLOCK, drive_id, |block: &mut Block| {
+                state = block.rate_limiter_state().map_err(|err| err.to_string())?;
+                Ok(())
+            })
+            .map_err(VmmError::DeviceManager)?;
+
+        Ok(state)
+    }
+
     /// Updates the rate limiter parameters for block device with `drive_id` id.
     pub fn update_vhost_user_block_config(&mut self, drive_id: &str) -> Result<(), VmmError> {
         self.mmio_device_manager
@@ -695,6 +1029,96 @@ impl Vmm {
             .map_err(VmmError::DeviceManager)
     }
 
+    /// Returns the live token bucket state of the rate limiters of net device with `net_id` id.
+    pub fn net_rate_limiter_state(&self, net_id: &str) -> Result<NetRateLimiterState, VmmError> {
+        let mut state = NetRateLimiterState::default();
+        self.mmio_device_manager
+            .with_virtio_device_with_id(TYPE_NET, net_id, |net: &mut Net| {
+                state = NetRateLimiterState::from(&*net);
+                Ok(())
+            })
+            .map_err(VmmError::DeviceManager)?;
+
+        Ok(state)
+    }
+
+    /// Removes the net device with `net_id` id. The device is removed from the MMIO bus, the
+    /// `event_manager` and the network interfaces of `vm_resources`, which drops it along with
+    /// its tap and queue events, and its MMIO address range and IRQ are freed. The guest driver
+    /// is told that the device can no longer be used, and the device is only removed once the
+    /// driver released it, as described in [`MMIODeviceManager::remove_mmio_virtio`].
+    pub fn remove_net_device(
+        &mut self,
+        net_id: &str,
+        vm_resources: &mut VmResources,
+        event_manager: &mut EventManager,
+    ) -> Result<(), VmmError> {
+        self.mmio_device_manager
+            .remove_mmio_virtio(self.vm.fd(), &mut self.resource_allocator, TYPE_NET, net_id)
+            .map_err(VmmError::DeviceManager)?;
+        let identifier = (DeviceType::Virtio(TYPE_NET), net_id.to_string());
+        if let Some(subscriber_id) = self.mmio_device_manager.subscriber_ids.remove(&identifier) {
+            event_manager
+                .remove_subscriber(subscriber_id)
+                .map_err(VmmError::EventManager)?;
+        }
+        vm_resources.net_builder.remove(net_id);
+        Ok(())
+    }
+
+    /// Updates the bandwidth rate limiter of the entropy device.
+    pub fn update_rng_rate_limiter(&mut self, rl_bytes: BucketUpdate) -> Result<(), VmmError> {
+        self.mmio_device_manager
+            .with_virtio_device_with_id(TYPE_RNG, ENTROPY_DEV_ID, |entropy: &mut Entropy| {
+                entropy.update_rate_limiter(rl_bytes);
+                Ok(())
+            })
+            .map_err(VmmError::DeviceManager)
+    }
+
+    /// Returns the statistics about the connections of the vsock device.
+    pub fn vsock_stats(&self) -> Result<VsockStats, VmmError> {
+        let mut stats = VsockStats::default();
+        self.mmio_device_manager
+            .with_virtio_device_with_id(
+                TYPE_VSOCK,
+                VSOCK_DEV_ID,
+                |vsock: &mut Vsock<VsockUnixBackend>| {
+                    stats = vsock.backend().stats();
+                    Ok(())
+                },
+            )
+            .map_err(VmmError::DeviceManager)?;
+        Ok(stats)
+    }
+
+    /// Returns true if a watchdog device is attached, has expired and is configured to stop
+    /// the microVM.
+    fn watchdog_stop_requested(&self) -> bool {
+        if let Some(busdev) =
+            self.get_bus_device(DeviceType::Virtio(TYPE_WATCHDOG), WATCHDOG_DEV_ID)
+        {
+            let virtio_device = busdev
+                .lock()
+                .expect("Poisoned lock")
+                .mmio_transport_ref()
+                .expect("Unexpected device type")
+                .device();
+
+            let stop_requested = virtio_device
+                .lock()
+                .expect("Poisoned lock")
+                .as_mut_any()
+                .downcast_mut::<Watchdog>()
+                .unwrap()
+                .stop_requested();
+
+            stop_requested
+        } else {
+            false
+        }
+    }
+
     /// Returns a reference to the balloon device if present.
     pub fn balloon_config(&self) -> Result<BalloonConfig, BalloonError> {
         if let Some(busdev) = self.get_bus_device(DeviceType::Virtio(TYPE_BALLOON), BALLOON_DEV_ID)
@@ -720,6 +1144,56 @@ impl Vmm {
         }
     }
 
+    /// Returns the size of the memory given back by the guest to the balloon device, followed by
+    /// the target size of the balloon, both in MiB.
+    ///
+    /// The former is read from the `actual` register of the device, which the guest driver
+    /// updates as it inflates or deflates the balloon, so callers can poll until it reaches the
+    /// target set with [`Vmm::update_balloon_config`].
+    pub fn balloon_inflation_progress(&self) -> Result<(u32, u32), BalloonError> {
+        if let Some(busdev) = self.get_bus_device(DeviceType::Virtio(TYPE_BALLOON), BALLOON_DEV_ID)
+        {
+            let virtio_device = busdev
+                .lock()
+                .expect("Poisoned lock")
+                .mmio_transport_ref()
+                .expect("Unexpected device type")
+                .device();
+
+            let mut device = virtio_device.lock().expect("Poisoned lock");
+            let balloon = device.as_mut_any().downcast_mut::<Balloon>().unwrap();
+
+            Ok((balloon.actual_mb(), balloon.size_mb()))
+        } else {
+            Err(BalloonError::DeviceNotFound)
+        }
+    }
+
+    /// Returns the balloon features negotiated with the guest driver.
+    pub fn balloon_features(&self) -> Result<BalloonFeatures, BalloonError> {
+        if let Some(busdev) = self.get_bus_device(DeviceType::Virtio(TYPE_BALLOON), BALLOON_DEV_ID)
+        {
+            let virtio_device = busdev
+                .lock()
+                .expect("Poisoned lock")
+                .mmio_transport_ref()
+                .expect("Unexpected device type")
+                .device();
+
+            let features = virtio_device
+                .lock()
+                .expect("Poisoned lock")
+                .as_mut_any()
+                .downcast_mut::<Balloon>()
+                .unwrap()
+                .features()?;
+
+            Ok(features)
+        } else {
+            Err(BalloonError::DeviceNotFound)
+        }
+    }
+
     /// Returns the latest balloon statistics if they are enabled.
     pub fn latest_balloon_stats(&self) -> Result<BalloonStats, BalloonError> {
         if let Some(busdev) = self.get_bus_device(DeviceType::Virtio(TYPE_BALLOON), BALLOON_DEV_ID)
@@ -765,13 +1239,14 @@ impl Vmm {
                     .expect("Unexpected device type")
                     .device();
 
-                virtio_device
-                    .lock()
-                    .expect("Poisoned lock")
-                    .as_mut_any()
-                    .downcast_mut::<Balloon>()
-                    .unwrap()
-                    .update_size(amount_mib)?;
+                let mut device = virtio_device.lock().expect("Poisoned lock");
+                let balloon = device.as_mut_any().downcast_mut::<Balloon>().unwrap();
+                // The balloon is deflated back to its size before the compaction once it
+                // completes, which would undo the update.
+                if balloon.compaction_in_progress() {
+                    return Err(BalloonError::CompactionInProgress);
+                }
+                balloon.update_size(amount_mib)?;
 
                 Ok(())
             }
@@ -780,6 +1255,87 @@ impl Vmm {
         }
     }
 
+    /// Starts compacting the guest memory by inflating the balloon by `target_free_mib` MiB, then
+    /// deflating it back to its previous target size.
+    ///
+    /// The balloon device tracks the compaction from its queue and timer events, so this returns
+    /// as soon as the balloon target size is updated, and the event returned by
+    /// [`Vmm::balloon_compaction_event`] is signaled once the compaction completes. See
+    /// [`Balloon::start_compaction`].
+    pub fn compact_guest_memory(&mut self, target_free_mib: u32) -> Result<(), BalloonError> {
+        // The device events, which the compaction progresses on, are not handled while the
+        // microVM is paused.
+        if self.instance_info.state == VmState::Paused {
+            return Err(BalloonError::CompactionWhilePaused);
+        }
+        let (_, target_mib) = self.balloon_inflation_progress()?;
+        // The balloon cannot have a target size greater than the size of
+        // the guest memory.
+        match target_mib.checked_add(target_free_mib) {
+            Some(target_mib) if u64::from(target_mib) <= mem_size_mib(self.guest_memory()) => (),
+            _ => return Err(BalloonError::TooManyPagesRequested),
+        }
+
+        let virtio_device = self
+            .get_bus_device(DeviceType::Virtio(TYPE_BALLOON), BALLOON_DEV_ID)
+            .ok_or(BalloonError::DeviceNotFound)?
+            .lock()
+            .expect("Poisoned lock")
+            .mmio_transport_ref()
+            .expect("Unexpected device type")
+            .device();
+
+        let mut device = virtio_device.lock().expect("Poisoned lock");
+        device
+            .as_mut_any()
+            .downcast_mut::<Balloon>()
+            .unwrap()
+            .start_compaction(target_free_mib)
+    }
+
+    /// Returns a handle to the event the balloon device signals when a guest memory compaction
+    /// completes.
+    pub fn balloon_compaction_event(&self) -> Result<EventFd, BalloonError> {
+        let virtio_device = self
+            .get_bus_device(DeviceType::Virtio(TYPE_BALLOON), BALLOON_DEV_ID)
+            .ok_or(BalloonError::DeviceNotFound)?
+            .lock()
+            .expect("Poisoned lock")
+            .mmio_transport_ref()
+            .expect("Unexpected device type")
+            .device();
+
+        let mut device = virtio_device.lock().expect("Poisoned lock");
+        device
+            .as_mut_any()
+            .downcast_mut::<Balloon>()
+            .unwrap()
+            .compaction_evt()
+            .try_clone()
+            .map_err(BalloonError::EventFd)
+    }
+
+    /// Returns the outcome of the last completed guest memory compaction.
+    pub fn guest_memory_compaction(&self) -> Result<GuestMemoryCompaction, BalloonError> {
+        let virtio_device = self
+            .get_bus_device(DeviceType::Virtio(TYPE_BALLOON), BALLOON_DEV_ID)
+            .ok_or(BalloonError::DeviceNotFound)?
+            .lock()
+            .expect("Poisoned lock")
+            .mmio_transport_ref()
+            .expect("Unexpected device type")
+            .device();
+
+        let mut device = virtio_device.lock().expect("Poisoned lock");
+        let reclaimed_mib = device
+            .as_mut_any()
+            .downcast_mut::<Balloon>()
+            .unwrap()
+            .compaction_reclaimed_mib()
+            .unwrap_or(0);
+        Ok(GuestMemoryCompaction { reclaimed_mib })
+    }
+
     /// Updates configuration for the balloon device as described in `balloon_stats_update`.
     pub fn update_balloon_stats_config(
         &mut self,
@@ -934,6 +1490,11 @@ impl MutEventSubscriber for Vmm {
             let _ = self.vcpus_exit_evt.read();
 
             let exit_code = 'exit_code: {
+                // An expired watchdog configured to stop the microVM takes precedence.
+                if self.watchdog_stop_requested() {
+                    break 'exit_code FcExitCode::WatchdogExpired;
+                }
+
                 // Query each vcpu for their exit_code.
                 for handle in &self.vcpus_handles {
                     // Drain all vcpu responses that are pending from this vcpu until we find an
@@ -28,8 +28,13 @@ pub static INSTANCE_ID: OnceLock<String> = OnceLock::new();
 /// Default values matching the swagger specification (`src/firecracker/swagger/firecracker.yaml`).
 pub static LOGGER: Logger = Logger(Mutex::new(LoggerConfiguration {
     target: None,
-    filter: LogFilter { module: None },
-    format: LogFormat {
+    filter: LogFilter {
+        module: None,
+        level: DEFAULT_LEVEL,
+        modules: Vec::new(),
+    },
+    format: LogFormatting {
+        format: LogFormat::Human,
         show_level: false,
         show_log_origin: false,
     },
@@ -54,12 +59,14 @@ impl Logger {
     /// Applies the given logger configuration the logger.
     pub fn update(&self, config: LoggerConfig) -> Result<(), LoggerUpdateError> {
         let mut guard = self.0.lock().unwrap();
-        log::set_max_level(
-            config
-                .level
-                .map(log::LevelFilter::from)
-                .unwrap_or(DEFAULT_LEVEL),
-        );
+        guard.filter.level = config
+            .level
+            .map(log::LevelFilter::from)
+            .unwrap_or(DEFAULT_LEVEL);
+        if let Some(directives) = config.filter {
+            guard.filter.apply(directives);
+        }
+        log::set_max_level(guard.filter.max_level());
 
         if let Some(log_path) = config.log_path {
             let file = std::fs::OpenOptions::new()
@@ -72,6 +79,10 @@ impl Logger {
             guard.target = Some(file);
         };
 
+        if let Some(format) = config.format {
+            guard.format.format = format;
+        }
+
         if let Some(show_level) = config.show_level {
             guard.format.show_level = show_level;
         }
@@ -90,14 +101,96 @@ impl Logger {
 
         Ok(())
     }
+
+    /// Sets the level of the modules without a filter directive.
+    pub fn set_max_level(&self, level: LevelFilter) {
+        let mut guard = self.0.lock().unwrap();
+        guard.filter.level = log::LevelFilter::from(level);
+        log::set_max_level(guard.filter.max_level());
+    }
+
+    /// Applies the filter directives in the [`LogDirectives`] syntax, replacing the level of
+    /// each module previously set.
+    pub fn set_filter(&self, directive: &str) -> Result<(), LogDirectivesFromStrError> {
+        let directives = LogDirectives::from_str(directive)?;
+        let mut guard = self.0.lock().unwrap();
+        guard.filter.apply(directives);
+        log::set_max_level(guard.filter.max_level());
+        Ok(())
+    }
+
+    /// Applies the given update of the levels, leaving the rest of the configuration untouched.
+    pub fn update_filter(&self, update: LoggerFilterUpdate) {
+        let mut guard = self.0.lock().unwrap();
+        if let Some(level) = update.level {
+            guard.filter.level = log::LevelFilter::from(level);
+        }
+        if let Some(directives) = update.filter {
+            guard.filter.apply(directives);
+        }
+        log::set_max_level(guard.filter.max_level());
+    }
+}
+
+/// Whether `source` is the module path `module` or one of its submodules.
+fn in_module(source: &str, module: &str) -> bool {
+    source
+        .strip_prefix(module)
+        .is_some_and(|rest| rest.is_empty() || rest.starts_with("::"))
 }
 
 #[derive(Debug)]
 pub struct LogFilter {
     pub module: Option<String>,
+    /// Level of the modules without a directive.
+    pub level: log::LevelFilter,
+    /// Level of each module, including its submodules.
+    pub modules: Vec<(String, log::LevelFilter)>,
+}
+
+impl LogFilter {
+    fn apply(&mut self, directives: LogDirectives) {
+        if let Some(level) = directives.level {
+            self.level = log::LevelFilter::from(level);
+        }
+        self.modules = directives
+            .modules
+            .into_iter()
+            .map(|(module, level)| (module, log::LevelFilter::from(level)))
+            .collect();
+    }
+
+    // The most verbose level of any module, which records are filtered by before reaching the
+    // logger.
+    fn max_level(&self) -> log::LevelFilter {
+        self.modules
+            .iter()
+            .map(|(_, level)| *level)
+            .fold(self.level, Ord::max)
+    }
+
+    fn enabled(&self, record: &Record) -> bool {
+        let enabled_module = match (&self.module, record.module_path()) {
+            (Some(filter), Some(source)) => source.starts_with(filter),
+            (Some(_), None) => false,
+            (None, _) => true,
+        };
+        // The directive of the innermost module applies.
+        let level = record
+            .module_path()
+            .and_then(|source| {
+                self.modules
+                    .iter()
+                    .filter(|(module, _)| in_module(source, module))
+                    .max_by_key(|(module, _)| module.len())
+            })
+            .map_or(self.level, |(_, level)| *level);
+        enabled_module && record.level() <= level
+    }
 }
 #[derive(Debug)]
-pub struct LogFormat {
+pub struct LogFormatting {
+    pub format: LogFormat,
     pub show_level: bool,
     pub show_log_origin: bool,
 }
@@ -105,7 +198,22 @@ pub struct LogFormat {
 pub struct LoggerConfiguration {
     pub target: Option<std::fs::File>,
     pub filter: LogFilter,
-    pub format: LogFormat,
+    pub format: LogFormatting,
+}
+
+/// Log line written in the JSON format.
+#[derive(Debug, Serialize)]
+struct JsonLogLine<'a> {
+    timestamp: String,
+    level: &'static str,
+    instance_id: &'a str,
+    thread: &'a str,
+    module: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    file: Option<&'a str>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    line: Option<u32>,
+    message: String,
 }
 #[derive(Debug)]
 pub struct Logger(pub Mutex<LoggerConfiguration>);
@@ -121,48 +229,29 @@ impl Log for Logger {
         let mut guard = self.0.lock().unwrap();
 
         // Check if the log message is enabled
-        {
-            let enabled_module = match (&guard.filter.module, record.module_path()) {
-                (Some(filter), Some(source)) => source.starts_with(filter),
-                (Some(_), None) => false,
-                (None, _) => true,
-            };
-            let enabled = enabled_module;
-            if !enabled {
-                return;
-            }
+        if !guard.filter.enabled(record) {
+            return;
         }
 
         // Prints log message
         {
             let thread = thread::current().name().unwrap_or("-").to_string();
-            let level = match guard.format.show_level {
-                true => format!(":{}", record.level()),
-                false => String::new(),
-            };
-
-            let origin = match guard.format.show_log_origin {
-                true => {
-                    let file = record.file().unwrap_or("?");
-                    let line = match record.line() {
-                        Some(x) => x.to_string(),
-                        None => String::from("?"),
-                    };
-                    format!(":{file}:{line}")
-                }
-                false => String::new(),
+            let instance_id = INSTANCE_ID
+                .get()
+                .map(|s| s.as_str())
+                .unwrap_or(DEFAULT_INSTANCE_ID);
+
+            let message = match guard.format.format {
+                LogFormat::Human => human_line(&guard.format, record, instance_id, &thread),
+                LogFormat::Json => match json_line(&guard.format, record, instance_id, &thread) {
+                    Ok(message) => message,
+                    Err(_) => {
+                        METRICS.logger.missed_log_count.inc();
+                        return;
+                    }
+                },
             };
 
-            let message = format!(
-                "{} [{}:{thread}{level}{origin}] {}\n",
-                LocalTime::now(),
-                INSTANCE_ID
-                    .get()
-                    .map(|s| s.as_str())
-                    .unwrap_or(DEFAULT_INSTANCE_ID),
-                record.args()
-            );
-
             let result = if let Some(file) = &mut guard.target {
                 file.write_all(message.as_bytes())
             } else {
@@ -180,6 +269,57 @@ impl Log for Logger {
     fn flush(&self) {}
 }
 
+fn human_line(format: &LogFormatting, record: &Record, instance_id: &str, thread: &str) -> String {
+    let level = match format.show_level {
+        true => format!(":{}", record.level()),
+        false => String::new(),
+    };
+
+    let origin = match format.show_log_origin {
+        true => {
+            let file = record.file().unwrap_or("?");
+            let line = match record.line() {
+                Some(x) => x.to_string(),
+                None => String::from("?"),
+            };
+            format!(":{file}:{line}")
+        }
+        false => String::new(),
+    };
+
+    format!(
+        "{} [{instance_id}:{thread}{level}{origin}] {}\n",
+        LocalTime::now(),
+        record.args()
+    )
+}
+
+// The level is always part of a JSON log line, while its origin is only added if
+// `show_log_origin` is set, as for the human readable lines.
+fn json_line(
+    format: &LogFormatting,
+    record: &Record,
+    instance_id: &str,
+    thread: &str,
+) -> Result<String, serde_json::Error> {
+    let (file, line) = match format.show_log_origin {
+        true => (record.file(), record.line()),
+        false => (None, None),
+    };
+    let mut message = serde_json::to_string(&JsonLogLine {
+        timestamp: LocalTime::now().to_string(),
+        level: record.level().as_str(),
+        instance_id,
+        thread,
+        module: record.module_path(),
+        file,
+        line,
+        message: record.args().to_string(),
+    })?;
+    message.push('\n');
+    Ok(message)
+}
+
 /// Strongly typed structure used to describe the logger.
 #[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
@@ -194,6 +334,123 @@ pub struct LoggerConfig {
     pub show_log_origin: Option<bool>,
     /// The module to filter logs by.
     pub module: Option<String>,
+    /// The format of the log lines.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub format: Option<LogFormat>,
+    /// The level of specific modules, overriding `level`.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<LogDirectives>,
+}
+
+/// Strongly typed structure used to update the levels of the logger, both before and after the
+/// microVM has booted.
+#[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct LoggerFilterUpdate {
+    /// The level of the modules without a filter directive.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub level: Option<LevelFilter>,
+    /// The level of specific modules, replacing the ones previously set.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub filter: Option<LogDirectives>,
+}
+
+/// Levels of the modules, as comma separated `module=level` directives, e.g.
+/// `vmm::devices::virtio::net=debug,vmm::vstate=trace`. A directive applies to the module and its
+/// submodules, the one of the innermost module the origin of a log line is in taking precedence,
+/// while a directive without a module path sets the level of the modules without a directive.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct LogDirectives {
+    /// The level of the modules without a directive.
+    pub level: Option<LevelFilter>,
+    /// The level of each module path prefix.
+    pub modules: Vec<(String, LevelFilter)>,
+}
+
+/// Error type for [`<LogDirectives as FromStr>::from_str`].
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+#[error("Failed to parse string to log filter directives: {0}")]
+pub struct LogDirectivesFromStrError(String);
+
+impl FromStr for LogDirectives {
+    type Err = LogDirectivesFromStrError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut directives = LogDirectives::default();
+        for directive in s.split(',').map(str::trim).filter(|d| !d.is_empty()) {
+            let invalid = || LogDirectivesFromStrError(String::from(directive));
+            match directive.split_once('=') {
+                Some((module, level)) if !module.is_empty() => {
+                    let level = LevelFilter::from_str(level).map_err(|_| invalid())?;
+                    directives.modules.push((String::from(module), level));
+                }
+                Some(_) => return Err(invalid()),
+                None => {
+                    directives.level =
+                        Some(LevelFilter::from_str(directive).map_err(|_| invalid())?)
+                }
+            }
+        }
+        Ok(directives)
+    }
+}
+
+impl std::fmt::Display for LogDirectives {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let level = self
+            .level
+            .map(|level| log::LevelFilter::from(level).to_string());
+        let modules = self
+            .modules
+            .iter()
+            .map(|(module, level)| format!("{module}={}", log::LevelFilter::from(*level)));
+        let directives = level.into_iter().chain(modules).collect::<Vec<_>>();
+        write!(f, "{}", directives.join(","))
+    }
+}
+
+impl Serialize for LogDirectives {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        serializer.collect_str(self)
+    }
+}
+
+impl<'de> Deserialize<'de> for LogDirectives {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: Deserializer<'de>,
+    {
+        use serde::de::Error;
+        let directives = String::deserialize(deserializer)?;
+        LogDirectives::from_str(&directives).map_err(D::Error::custom)
+    }
+}
+
+/// Format of the log lines.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(rename_all = "lowercase")]
+pub enum LogFormat {
+    /// Human readable lines.
+    #[default]
+    Human,
+    /// Newline-delimited JSON objects, with the `timestamp`, `level`, `instance_id`, `thread`,
+    /// `module` and `message` keys.
+    Json,
+}
+
+/// Error type for [`<LogFormat as FromStr>::from_str`].
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+#[error("Failed to parse string to log format: {0}")]
+pub struct LogFormatFromStrError(String);
+
+impl FromStr for LogFormat {
+    type Err = LogFormatFromStrError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "human" => Ok(Self::Human),
+            "json" => Ok(Self::Json),
+            _ => Err(LogFormatFromStrError(String::from(s))),
+        }
+    }
 }
 
 /// This is required since we originally supported `Warning` and uppercase variants being used as
@@ -368,8 +625,11 @@ mod tests {
             target: Some(target),
             filter: LogFilter {
                 module: Some(String::from("module")),
+                level: DEFAULT_LEVEL,
+                modules: Vec::new(),
             },
-            format: LogFormat {
+            format: LogFormatting {
+                format: LogFormat::Human,
                 show_level: true,
                 show_log_origin: true,
             },
@@ -404,4 +664,188 @@ mod tests {
 
         std::fs::remove_file(path).unwrap();
     }
+
+    #[test]
+    fn test_json_logger() {
+        let file = vmm_sys_util::tempfile::TempFile::new().unwrap();
+        let logger = Logger(Mutex::new(LoggerConfiguration {
+            target: Some(file.as_file().try_clone().unwrap()),
+            filter: LogFilter {
+                module: None,
+                level: DEFAULT_LEVEL,
+                modules: Vec::new(),
+            },
+            format: LogFormatting {
+                format: LogFormat::Json,
+                show_level: false,
+                show_log_origin: false,
+            },
+        }));
+
+        for message in ["Warning!", "Second \"warning\""] {
+            logger.log(
+                &Record::builder()
+                    .args(format_args!("{message}"))
+                    .level(Level::Warn)
+                    .file(Some("dir/app.rs"))
+                    .line(Some(200))
+                    .module_path(Some("module::server"))
+                    .build(),
+            );
+        }
+
+        // Each line is a JSON object.
+        let contents = std::fs::read_to_string(file.as_path()).unwrap();
+        let lines = contents
+            .lines()
+            .map(|line| serde_json::from_str::<serde_json::Value>(line).unwrap())
+            .collect::<Vec<_>>();
+        assert_eq!(lines.len(), 2);
+
+        let thread = thread::current().name().unwrap_or("-").to_string();
+        let line = lines[0].as_object().unwrap();
+        let mut keys = line.keys().cloned().collect::<Vec<_>>();
+        keys.sort();
+        assert_eq!(
+            keys,
+            [
+                "instance_id",
+                "level",
+                "message",
+                "module",
+                "thread",
+                "timestamp"
+            ]
+        );
+        assert_eq!(line["level"], "WARN");
+        assert_eq!(line["instance_id"], DEFAULT_INSTANCE_ID);
+        assert_eq!(line["thread"], thread.as_str());
+        assert_eq!(line["module"], "module::server");
+        assert_eq!(line["message"], "Warning!");
+        assert_eq!(lines[1]["message"], "Second \"warning\"");
+    }
+
+    #[test]
+    fn test_logger_level_and_filter() {
+        let file = vmm_sys_util::tempfile::TempFile::new().unwrap();
+        let logger = Logger(Mutex::new(LoggerConfiguration {
+            target: Some(file.as_file().try_clone().unwrap()),
+            filter: LogFilter {
+                module: None,
+                level: DEFAULT_LEVEL,
+                modules: Vec::new(),
+            },
+            format: LogFormatting {
+                format: LogFormat::Human,
+                show_level: false,
+                show_log_origin: false,
+            },
+        }));
+        let log = |level: Level, module: &str| {
+            logger.log(
+                &Record::builder()
+                    .args(format_args!("{level} {module}"))
+                    .level(level)
+                    .module_path(Some(module))
+                    .build(),
+            )
+        };
+        let messages = || {
+            std::fs::read_to_string(file.as_path())
+                .unwrap()
+                .lines()
+                .map(|line| line.split_once("] ").unwrap().1.to_string())
+                .collect::<Vec<_>>()
+        };
+
+        // Debug events are only emitted once the level allows them.
+        log(Level::Debug, "vmm::devices");
+        assert!(messages().is_empty());
+        logger.set_max_level(LevelFilter::Debug);
+        log(Level::Debug, "vmm::devices");
+        assert_eq!(messages(), ["DEBUG vmm::devices"]);
+
+        logger
+            .set_filter("warn,vmm::devices=info,vmm::devices::virtio=trace")
+            .unwrap();
+        log(Level::Trace, "vmm::devices::virtio::net");
+        log(Level::Debug, "vmm::devices::legacy");
+        log(Level::Info, "vmm::devices::legacy");
+        log(Level::Info, "vmm::vstate");
+        assert_eq!(
+            messages(),
+            [
+                "DEBUG vmm::devices",
+                "TRACE vmm::devices::virtio::net",
+                "INFO vmm::devices::legacy"
+            ]
+        );
+
+        // A directive applies to the module and its submodules only, not to the modules whose
+        // name it prefixes.
+        logger
+            .set_filter("vmm::dev=trace,vmm::vstate=debug")
+            .unwrap();
+        log(Level::Debug, "vmm::devices");
+        log(Level::Debug, "vmm::vstate_extra");
+        log(Level::Debug, "vmm::vstate");
+        log(Level::Trace, "vmm::vstate::vcpu");
+        log(Level::Debug, "vmm::vstate::vcpu");
+        assert_eq!(
+            messages()[3..],
+            ["DEBUG vmm::vstate", "DEBUG vmm::vstate::vcpu"]
+        );
+
+        // An update only replaces the given levels.
+        logger.update_filter(LoggerFilterUpdate {
+            level: Some(LevelFilter::Trace),
+            filter: None,
+        });
+        log(Level::Trace, "vmm::devices");
+        log(Level::Trace, "vmm::vstate");
+        logger.update_filter(LoggerFilterUpdate {
+            level: None,
+            filter: Some(LogDirectives::from_str("vmm::vstate=trace").unwrap()),
+        });
+        log(Level::Trace, "vmm::vstate");
+        assert_eq!(messages()[5..], ["TRACE vmm::devices", "TRACE vmm::vstate"]);
+
+        assert_eq!(
+            logger.set_filter("vmm=verbose"),
+            Err(LogDirectivesFromStrError(String::from("vmm=verbose")))
+        );
+        assert_eq!(
+            logger.set_filter("=debug"),
+            Err(LogDirectivesFromStrError(String::from("=debug")))
+        );
+    }
+
+    #[test]
+    fn test_log_directives_serde() {
+        let directives = LogDirectives {
+            level: Some(LevelFilter::Warn),
+            modules: vec![(String::from("vmm::vstate"), LevelFilter::Debug)],
+        };
+        assert_eq!(
+            LogDirectives::from_str(" WARNING , vmm::vstate=debug,"),
+            Ok(directives.clone())
+        );
+        let json = serde_json::to_string(&directives).unwrap();
+        assert_eq!(json, r#""WARN,vmm::vstate=DEBUG""#);
+        assert_eq!(
+            serde_json::from_str::<LogDirectives>(&json).unwrap(),
+            directives
+        );
+        serde_json::from_str::<LogDirectives>(r#""vmm=""#).unwrap_err();
+    }
+
+    #[test]
+    fn test_log_format_from_str() {
+        assert_eq!(LogFormat::from_str("human"), Ok(LogFormat::Human));
+        assert_eq!(LogFormat::from_str("JSON"), Ok(LogFormat::Json));
+        assert_eq!(
+            LogFormat::from_str("xml"),
+            Err(LogFormatFromStrError(String::from("xml")))
+        );
+    }
 }
@@ -61,12 +61,14 @@
 //! If if turns out this approach is not really what we want, it's pretty easy to resort to
 //! something else, while working behind the same interface.
 
+use std::collections::BTreeMap;
 use std::fmt::Debug;
 use std::io::Write;
 use std::ops::Deref;
 use std::sync::atomic::{AtomicU64, Ordering};
-use std::sync::{Mutex, OnceLock};
+use std::sync::{Arc, Mutex, OnceLock, RwLock};
 
+use serde::ser::SerializeMap;
 use serde::{Serialize, Serializer};
 use utils::time::{get_time_ns, get_time_us, ClockType};
 
@@ -78,6 +80,7 @@ use crate::devices::virtio::net::metrics as net_metrics;
 use crate::devices::virtio::rng::metrics as entropy_metrics;
 use crate::devices::virtio::vhost_user_metrics;
 use crate::devices::virtio::vsock::metrics as vsock_metrics;
+use crate::devices::virtio::watchdog::metrics as watchdog_metrics;
 
 /// Static instance used for handling metrics.
 pub static METRICS: Metrics<FirecrackerMetrics, FcLineWriter> =
@@ -140,6 +143,12 @@ impl<T: Serialize + Debug, M: Write + Send + Debug> Metrics<T, M> {
     /// The alternative is to hold a Mutex over the entire function call, but this increases the
     /// known deadlock potential.
     pub fn write(&self) -> Result<bool, MetricsError> {
+        self.write_and_count().map(|written| written.is_some())
+    }
+
+    /// Writes metrics like [`Metrics::write`], returning the number of bytes written to the
+    /// destination, or `None` if the metrics system was not yet initialized.
+    pub fn write_and_count(&self) -> Result<Option<usize>, MetricsError> {
         if let Some(lock) = self.metrics_buf.get() {
             match serde_json::to_string(&self.app_metrics) {
                 Ok(msg) => {
@@ -148,10 +157,11 @@ impl<T: Serialize + Debug, M: Write + Send + Debug> Metrics<T, M> {
                         // flushes automatically whenever a newline is
                         // detected (and we always end with a newline the
                         // current write).
+                        let line = format!("{msg}\n",);
                         guard
-                            .write_all(format!("{msg}\n",).as_bytes())
+                            .write_all(line.as_bytes())
                             .map_err(MetricsError::Write)
-                            .map(|_| true)
+                            .map(|_| Some(line.len()))
                     } else {
                         // We have not incremented `missed_metrics_count` as there is no way to push
                         // metrics if destination lock got poisoned.
@@ -166,7 +176,7 @@ impl<T: Serialize + Debug, M: Write + Send + Debug> Metrics<T, M> {
         } else {
             // If the metrics are not initialized, no error is thrown but we do let the user know
             // that metrics were not written.
-            Ok(false)
+            Ok(None)
         }
     }
 }
@@ -358,6 +368,8 @@ pub struct ApiServerMetrics {
     pub sync_response_fails: SharedIncMetric,
     /// Number of timeouts during communication with the VMM.
     pub sync_vmm_send_timeout_count: SharedIncMetric,
+    /// Number of requests that took longer than the configured slow request threshold.
+    pub slow_request_count: SharedIncMetric,
 }
 impl ApiServerMetrics {
     /// Const default construction.
@@ -367,6 +379,7 @@ impl ApiServerMetrics {
             process_startup_time_cpu_us: SharedStoreMetric::new(),
             sync_response_fails: SharedIncMetric::new(),
             sync_vmm_send_timeout_count: SharedIncMetric::new(),
+            slow_request_count: SharedIncMetric::new(),
         }
     }
 }
@@ -478,6 +491,10 @@ pub struct PatchRequestsMetrics {
     pub network_count: SharedIncMetric,
     /// Number of failures in PATCHing a net device.
     pub network_fails: SharedIncMetric,
+    /// Number of PATCHs for updating the logger levels.
+    pub logger_count: SharedIncMetric,
+    /// Number of failures in updating the logger levels.
+    pub logger_fails: SharedIncMetric,
     /// Number of PATCHs for configuring the machine.
     pub machine_cfg_count: SharedIncMetric,
     /// Number of failures in configuring the machine.
@@ -495,6 +512,8 @@ impl PatchRequestsMetrics {
             drive_fails: SharedIncMetric::new(),
             network_count: SharedIncMetric::new(),
             network_fails: SharedIncMetric::new(),
+            logger_count: SharedIncMetric::new(),
+            logger_fails: SharedIncMetric::new(),
             machine_cfg_count: SharedIncMetric::new(),
             machine_cfg_fails: SharedIncMetric::new(),
             mmds_count: SharedIncMetric::new(),
@@ -532,6 +551,8 @@ pub struct LoggerSystemMetrics {
     pub missed_log_count: SharedIncMetric,
     /// Number of errors while trying to log human readable content.
     pub log_fails: SharedIncMetric,
+    /// Number of log lines suppressed for repeating a recently logged one.
+    pub suppressed_log_count: SharedIncMetric,
 }
 impl LoggerSystemMetrics {
     /// Const default construction.
@@ -541,6 +562,7 @@ impl LoggerSystemMetrics {
             metrics_fails: SharedIncMetric::new(),
             missed_log_count: SharedIncMetric::new(),
             log_fails: SharedIncMetric::new(),
+            suppressed_log_count: SharedIncMetric::new(),
         }
     }
 }
@@ -558,6 +580,8 @@ pub struct MmdsMetrics {
     pub rx_bad_eth: SharedIncMetric,
     /// The total number of successful receive operations by the MMDS.
     pub rx_count: SharedIncMetric,
+    /// The number of segments carrying request data dropped by the MMDS rate limiter.
+    pub rx_throttled: SharedIncMetric,
     /// The total number of bytes sent by the MMDS.
     pub tx_bytes: SharedIncMetric,
     /// The total number of successful send operations by the MMDS.
@@ -570,6 +594,10 @@ pub struct MmdsMetrics {
     pub connections_created: SharedIncMetric,
     /// The number of connections cleaned up by the MMDS TCP handler.
     pub connections_destroyed: SharedIncMetric,
+    /// The size of the serialized MMDS data store, in bytes, after the last successful write.
+    pub data_store_bytes: SharedStoreMetric,
+    /// The number of PUT and PATCH requests rejected for exceeding the data store limit.
+    pub rejected_writes: SharedIncMetric,
 }
 impl MmdsMetrics {
     /// Const default construction.
@@ -580,17 +608,20 @@ impl MmdsMetrics {
             rx_accepted_unusual: SharedIncMetric::new(),
             rx_bad_eth: SharedIncMetric::new(),
             rx_count: SharedIncMetric::new(),
+            rx_throttled: SharedIncMetric::new(),
             tx_bytes: SharedIncMetric::new(),
             tx_count: SharedIncMetric::new(),
             tx_errors: SharedIncMetric::new(),
             tx_frames: SharedIncMetric::new(),
             connections_created: SharedIncMetric::new(),
             connections_destroyed: SharedIncMetric::new(),
+            data_store_bytes: SharedStoreMetric::new(),
+            rejected_writes: SharedIncMetric::new(),
         }
     }
 }
 
-/// Performance metrics related for the moment only to snapshots.
+/// Performance metrics related to snapshots and API requests.
 // These store the duration of creating/loading a snapshot and of
 // pausing/resuming the microVM.
 // If there are more than one `/snapshot/create` request in a minute
@@ -598,6 +629,8 @@ impl MmdsMetrics {
 // snapshot creation is stored in the metric. If the user is interested
 // in all the durations, a `FlushMetrics` request should be sent after
 // each `create` request.
+// The `api_*` metrics aggregate the handling time of all the requests made
+// to an API resource, from the moment the API server starts processing them.
 #[derive(Debug, Default, Serialize)]
 pub struct PerformanceMetrics {
     /// Measures the snapshot full create time, at the API (user) level, in microseconds.
@@ -644,12 +677,15 @@ impl PerformanceMetrics {
 pub struct SeccompMetrics {
     /// Number of errors inside the seccomp filtering.
     pub num_faults: SharedStoreMetric,
+    /// Number of threads running without seccomp filter, as it could not be installed.
+    pub install_fails: SharedIncMetric,
 }
 impl SeccompMetrics {
     /// Const default construction.
     pub const fn new() -> Self {
         Self {
             num_faults: SharedStoreMetric::new(),
+            install_fails: SharedIncMetric::new(),
         }
     }
 }
@@ -712,16 +748,7 @@ impl Drop for LatencyMetricsRecorder<'_> {
     /// and updates min/max/sum metrics.
     ///  self.start_time is recorded in new() and metrics are updated in drop
     fn drop(&mut self) {
-        let delta_us = get_time_us(ClockType::Monotonic) - self.start_time;
-        self.metric.sum_us.add(delta_us);
-        let min_us = self.metric.min_us.fetch();
-        let max_us = self.metric.max_us.fetch();
-        if (0 == min_us) || (min_us > delta_us) {
-            self.metric.min_us.store(delta_us);
-        }
-        if (0 == max_us) || (max_us < delta_us) {
-            self.metric.max_us.store(delta_us);
-        }
+        self.metric.update_with_elapsed_time(self.start_time);
     }
 }
 
@@ -754,6 +781,154 @@ impl LatencyAggregateMetrics {
     pub fn record_latency_metrics(&self) -> LatencyMetricsRecorder {
         LatencyMetricsRecorder::new(self)
     }
+
+    /// Reads the current values of the aggregate without resetting them.
+    pub fn values(&self) -> LatencyAggregateValues {
+        LatencyAggregateValues {
+            min_us: self.min_us.fetch(),
+            max_us: self.max_us.fetch(),
+            sum_us: self.sum_us.count(),
+        }
+    }
+
+    /// Updates the aggregate with the time elapsed since `start_time_us`, and returns it.
+    pub fn update_with_elapsed_time(&self, start_time_us: u64) -> u64 {
+        let delta_us = get_time_us(ClockType::Monotonic) - start_time_us;
+        self.sum_us.add(delta_us);
+        let min_us = self.min_us.fetch();
+        let max_us = self.max_us.fetch();
+        if (0 == min_us) || (min_us > delta_us) {
+            self.min_us.store(delta_us);
+        }
+        if (0 == max_us) || (max_us < delta_us) {
+            self.max_us.store(delta_us);
+        }
+        delta_us
+    }
+}
+
+/// Current values of a `LatencyAggregateMetrics`, as returned by
+/// `LatencyAggregateMetrics::values`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct LatencyAggregateValues {
+    /// Minimum value of the metrics in microseconds.
+    pub min_us: u64,
+    /// Maximum value of the metrics in microseconds.
+    pub max_us: u64,
+    /// Sum of the metrics in microseconds.
+    pub sum_us: u64,
+}
+
+/// Names and upper bounds, in microseconds, of the buckets of a `LatencyHistogram`.
+const LATENCY_HISTOGRAM_BUCKETS: [(&str, u64); 7] = [
+    ("le_100us", 100),
+    ("le_1ms", 1_000),
+    ("le_10ms", 10_000),
+    ("le_100ms", 100_000),
+    ("le_1s", 1_000_000),
+    ("le_10s", 10_000_000),
+    ("le_inf", u64::MAX),
+];
+
+/// Used to record the distribution of latency metrics, by counting the latencies falling in
+/// each of the `LATENCY_HISTOGRAM_BUCKETS`.
+#[derive(Debug, Default)]
+pub struct LatencyHistogram {
+    /// Number of latencies at most as large as the bound of each bucket, and larger than the
+    /// bound of the previous one.
+    buckets: [SharedIncMetric; LATENCY_HISTOGRAM_BUCKETS.len()],
+    /// Sum of the latencies in microseconds.
+    sum_us: SharedIncMetric,
+}
+impl LatencyHistogram {
+    /// Const default construction.
+    pub const fn new() -> Self {
+        Self {
+            buckets: [const { SharedIncMetric::new() }; LATENCY_HISTOGRAM_BUCKETS.len()],
+            sum_us: SharedIncMetric::new(),
+        }
+    }
+
+    /// Counts `latency_us` in the bucket it falls in.
+    pub fn record(&self, latency_us: u64) {
+        let bucket = LATENCY_HISTOGRAM_BUCKETS
+            .iter()
+            .position(|(_, bound_us)| latency_us <= *bound_us)
+            .unwrap_or(LATENCY_HISTOGRAM_BUCKETS.len() - 1);
+        self.buckets[bucket].inc();
+        self.sum_us.add(latency_us);
+    }
+
+    /// Records the time elapsed since `start_time_us`, and returns it.
+    pub fn update_with_elapsed_time(&self, start_time_us: u64) -> u64 {
+        let delta_us = get_time_us(ClockType::Monotonic) - start_time_us;
+        self.record(delta_us);
+        delta_us
+    }
+
+    /// Number of latencies recorded in the bucket named `bucket`.
+    pub fn bucket_count(&self, bucket: &str) -> Option<u64> {
+        LATENCY_HISTOGRAM_BUCKETS
+            .iter()
+            .position(|(name, _)| *name == bucket)
+            .map(|index| self.buckets[index].count())
+    }
+
+    /// Number of latencies recorded in all buckets.
+    pub fn count(&self) -> u64 {
+        self.buckets.iter().map(IncMetric::count).sum()
+    }
+}
+
+impl Serialize for LatencyHistogram {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let mut map = serializer.serialize_map(Some(self.buckets.len() + 1))?;
+        for ((name, _), bucket) in LATENCY_HISTOGRAM_BUCKETS.iter().zip(self.buckets.iter()) {
+            map.serialize_entry(name, bucket)?;
+        }
+        map.serialize_entry("sum_us", &self.sum_us)?;
+        map.end()
+    }
+}
+
+/// Latency histograms of the API requests, keyed by the type of action they translate to.
+/// A histogram is only allocated, and reported, once a request of its type was handled.
+#[derive(Debug, Default)]
+pub struct ApiLatencyMetrics {
+    histograms: RwLock<BTreeMap<&'static str, Arc<LatencyHistogram>>>,
+}
+impl ApiLatencyMetrics {
+    /// Const default construction.
+    pub const fn new() -> Self {
+        Self {
+            histograms: RwLock::new(BTreeMap::new()),
+        }
+    }
+
+    /// Returns the histogram of the requests of type `action`, allocating it if needed.
+    pub fn histogram(&self, action: &'static str) -> Arc<LatencyHistogram> {
+        if let Some(histogram) = self.histograms.read().unwrap().get(action) {
+            return Arc::clone(histogram);
+        }
+        Arc::clone(
+            self.histograms
+                .write()
+                .unwrap()
+                .entry(action)
+                .or_insert_with(|| Arc::new(LatencyHistogram::new())),
+        )
+    }
+}
+
+impl Serialize for ApiLatencyMetrics {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        let histograms = self.histograms.read().unwrap();
+        let mut map = serializer.serialize_map(Some(histograms.len()))?;
+        for (action, histogram) in histograms.iter() {
+            map.serialize_entry(action, histogram.as_ref())?;
+        }
+        map.end()
+    }
 }
 
 /// Structure provides Metrics specific to VCPUs' mode of functioning.
@@ -781,6 +956,8 @@ pub struct VcpuMetrics {
     pub failures: SharedIncMetric,
     /// Number of times that the `KVM_KVMCLOCK_CTRL` ioctl failed.
     pub kvmclock_ctrl_fails: SharedIncMetric,
+    /// Number of spurious KVM exits after which the VCPU re-entered the guest.
+    pub spurious_exits: SharedIncMetric,
     /// Provides Min/max/sum for KVM exits handling input IO.
     pub exit_io_in_agg: LatencyAggregateMetrics,
     /// Provides Min/max/sum for KVM exits handling output IO.
@@ -800,6 +977,7 @@ impl VcpuMetrics {
             exit_mmio_write: SharedIncMetric::new(),
             failures: SharedIncMetric::new(),
             kvmclock_ctrl_fails: SharedIncMetric::new(),
+            spurious_exits: SharedIncMetric::new(),
             exit_io_in_agg: LatencyAggregateMetrics::new(),
             exit_io_out_agg: LatencyAggregateMetrics::new(),
             exit_mmio_read_agg: LatencyAggregateMetrics::new(),
@@ -815,6 +993,8 @@ pub struct VmmMetrics {
     pub device_events: SharedIncMetric,
     /// Metric for signaling a panic has occurred.
     pub panic_count: SharedStoreMetric,
+    /// Number of descriptor chains rejected for being longer than the limit of their queue.
+    pub oversized_desc_chains: SharedIncMetric,
 }
 impl VmmMetrics {
     /// Const default construction.
@@ -822,6 +1002,7 @@ impl VmmMetrics {
         Self {
             device_events: SharedIncMetric::new(),
             panic_count: SharedStoreMetric::new(),
+            oversized_desc_chains: SharedIncMetric::new(),
         }
     }
 }
@@ -869,6 +1050,7 @@ create_serialize_proxy!(BalloonMetricsSerializeProxy, balloon_metrics);
 create_serialize_proxy!(EntropyMetricsSerializeProxy, entropy_metrics);
 create_serialize_proxy!(VsockMetricsSerializeProxy, vsock_metrics);
 create_serialize_proxy!(LegacyDevMetricsSerializeProxy, legacy);
+create_serialize_proxy!(WatchdogMetricsSerializeProxy, watchdog_metrics);
 
 /// Structure storing all metrics while enforcing serialization support on them.
 #[derive(Debug, Default, Serialize)]
@@ -891,6 +1073,8 @@ pub struct FirecrackerMetrics {
     pub legacy_dev_ser: LegacyDevMetricsSerializeProxy,
     /// Metrics related to performance measurements.
     pub latencies_us: PerformanceMetrics,
+    /// Latency histograms of the API requests, keyed by action type.
+    pub api_latencies: ApiLatencyMetrics,
     /// Logging related metrics.
     pub logger: LoggerSystemMetrics,
     /// Metrics specific to MMDS functionality.
@@ -919,6 +1103,9 @@ pub struct FirecrackerMetrics {
     #[serde(flatten)]
     /// Vhost-user device related metrics.
     pub vhost_user_ser: VhostUserMetricsSerializeProxy,
+    #[serde(flatten)]
+    /// Metrics related to the watchdog device.
+    pub watchdog_ser: WatchdogMetricsSerializeProxy,
 }
 impl FirecrackerMetrics {
     /// Const default construction.
@@ -932,6 +1119,7 @@ impl FirecrackerMetrics {
             get_api_requests: GetRequestsMetrics::new(),
             legacy_dev_ser: LegacyDevMetricsSerializeProxy {},
             latencies_us: PerformanceMetrics::new(),
+            api_latencies: ApiLatencyMetrics::new(),
             logger: LoggerSystemMetrics::new(),
             mmds: MmdsMetrics::new(),
             net_ser: NetMetricsSerializeProxy {},
@@ -944,6 +1132,7 @@ impl FirecrackerMetrics {
             vsock_ser: VsockMetricsSerializeProxy {},
             entropy_ser: EntropyMetricsSerializeProxy {},
             vhost_user_ser: VhostUserMetricsSerializeProxy {},
+            watchdog_ser: WatchdogMetricsSerializeProxy {},
         }
     }
 }
@@ -984,6 +1173,27 @@ mod tests {
         m.init(LineWriter::new(f.into_file())).unwrap_err();
     }
 
+    #[test]
+    fn test_write_and_count() {
+        let m = &Metrics::<_, FcLineWriter>::new(FirecrackerMetrics::new());
+        assert_eq!(m.write_and_count().unwrap(), None);
+
+        let f = TempFile::new().expect("Failed to create temporary metrics file");
+        m.init(LineWriter::new(f.as_file().try_clone().unwrap()))
+            .unwrap();
+
+        let written = m.write_and_count().unwrap().unwrap();
+        let content = std::fs::read_to_string(f.as_path()).unwrap();
+        assert_eq!(written, content.len());
+        assert!(content.ends_with('\n'));
+        serde_json::from_str::<serde_json::Value>(content.trim_end()).unwrap();
+
+        // Subsequent flushes report only the bytes written by them.
+        let written_again = m.write_and_count().unwrap().unwrap();
+        let content = std::fs::read_to_string(f.as_path()).unwrap();
+        assert_eq!(written + written_again, content.len());
+    }
+
     #[test]
     fn test_shared_inc_metric() {
         let metric = Arc::new(SharedIncMetric::default());
@@ -1027,6 +1237,45 @@ mod tests {
         assert_eq!(1, m1.fetch());
     }
 
+    #[test]
+    fn test_latency_histogram() {
+        let histogram = LatencyHistogram::new();
+        for latency_us in [0, 100, 101, 5_000, 20_000_000] {
+            histogram.record(latency_us);
+        }
+        assert_eq!(histogram.bucket_count("le_100us"), Some(2));
+        assert_eq!(histogram.bucket_count("le_1ms"), Some(1));
+        assert_eq!(histogram.bucket_count("le_10ms"), Some(1));
+        assert_eq!(histogram.bucket_count("le_10s"), Some(0));
+        assert_eq!(histogram.bucket_count("le_inf"), Some(1));
+        assert_eq!(histogram.bucket_count("le_1us"), None);
+        assert_eq!(histogram.count(), 5);
+
+        let metrics = ApiLatencyMetrics::new();
+        metrics.histogram("get_mmds").record(10);
+        metrics.histogram("get_mmds").record(2_000);
+        metrics.histogram("put_mmds").record(10);
+        assert_eq!(metrics.histogram("get_mmds").count(), 2);
+        assert_eq!(
+            serde_json::to_value(&metrics).unwrap(),
+            serde_json::json!({
+                "get_mmds": {
+                    "le_100us": 1, "le_1ms": 0, "le_10ms": 1, "le_100ms": 0, "le_1s": 0,
+                    "le_10s": 0, "le_inf": 0, "sum_us": 2010
+                },
+                "put_mmds": {
+                    "le_100us": 1, "le_1ms": 0, "le_10ms": 0, "le_100ms": 0, "le_1s": 0,
+                    "le_10s": 0, "le_inf": 0, "sum_us": 10
+                },
+            })
+        );
+        // The buckets are reset upon flush.
+        assert_eq!(
+            serde_json::to_value(&metrics).unwrap()["get_mmds"]["le_10ms"],
+            0
+        );
+    }
+
     #[test]
     fn test_serialize() {
         let s = serde_json::to_string(&FirecrackerMetrics::default());
@@ -7,14 +7,20 @@
 mod logging;
 mod metrics;
 
+use std::collections::HashMap;
+use std::sync::{LazyLock, Mutex};
+use std::time::{Duration, Instant};
+
 pub use log::{debug, error, info, log_enabled, trace, warn, Level};
 pub use logging::{
-    LevelFilter, LevelFilterFromStrError, LoggerConfig, LoggerInitError, LoggerUpdateError,
+    LevelFilter, LevelFilterFromStrError, LogDirectives, LogDirectivesFromStrError, LogFormat,
+    LogFormatFromStrError, LoggerConfig, LoggerFilterUpdate, LoggerInitError, LoggerUpdateError,
     DEFAULT_INSTANCE_ID, DEFAULT_LEVEL, INSTANCE_ID, LOGGER,
 };
 pub use metrics::{
-    IncMetric, LatencyAggregateMetrics, MetricsError, ProcessTimeReporter, SharedIncMetric,
-    SharedStoreMetric, StoreMetric, METRICS,
+    FirecrackerMetrics, IncMetric, LatencyAggregateMetrics, LatencyAggregateValues,
+    LatencyHistogram, MetricsError, ProcessTimeReporter, SharedIncMetric, SharedStoreMetric,
+    StoreMetric, METRICS,
 };
 use utils::time::{get_time_us, ClockType};
 
@@ -36,6 +42,35 @@ pub fn log_dev_preview_warning(feature_name: &str, msg_opt: Option<String>) {
     }
 }
 
+/// Last time at which the throttled warnings were logged, by key.
+static THROTTLED_WARNINGS: LazyLock<Mutex<HashMap<String, Instant>>> =
+    LazyLock::new(Default::default);
+
+/// Log a warning message, unless a message with the same `key` was logged less than
+/// `min_interval` ago. Suppressed messages are counted in the `logger.suppressed_log_count`
+/// metric.
+pub fn warn_throttled(key: &str, msg: &str, min_interval: Duration) {
+    if throttle(key, min_interval) {
+        METRICS.logger.suppressed_log_count.inc();
+    } else {
+        warn!("{msg}");
+    }
+}
+
+/// Whether a message with the given `key` was logged less than `min_interval` ago. Otherwise,
+/// records that it is logged now.
+fn throttle(key: &str, min_interval: Duration) -> bool {
+    let now = Instant::now();
+    let mut last_logged = THROTTLED_WARNINGS.lock().expect("Poisoned lock");
+    match last_logged.get(key) {
+        Some(last) if now.duration_since(*last) < min_interval => true,
+        _ => {
+            last_logged.insert(key.to_string(), now);
+            false
+        }
+    }
+}
+
 /// Helper function for updating the value of a store metric with elapsed time since some time in a
 /// past.
 pub fn update_metric_with_elapsed_time(metric: &SharedStoreMetric, start_time_us: u64) -> u64 {
@@ -43,3 +78,39 @@ pub fn update_metric_with_elapsed_time(metric: &SharedStoreMetric, start_time_us
     metric.store(delta_us);
     delta_us
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::test_utils::{capture_logs, captured_logs};
+
+    #[test]
+    fn test_warn_throttled() {
+        capture_logs();
+        let message = "test_warn_throttled: repeated warning";
+        let logged = || {
+            captured_logs()
+                .lines()
+                .filter(|line| line.ends_with(&format!(":WARN] {message}")))
+                .count()
+        };
+        let suppressed = METRICS.logger.suppressed_log_count.count();
+        let min_interval = Duration::from_secs(3600);
+
+        // Only the first of two rapid calls with the same key is logged.
+        warn_throttled("test_warn_throttled", message, min_interval);
+        assert_eq!(logged(), 1);
+        assert_eq!(METRICS.logger.suppressed_log_count.count(), suppressed);
+        warn_throttled("test_warn_throttled", message, min_interval);
+        assert_eq!(logged(), 1);
+        assert_eq!(METRICS.logger.suppressed_log_count.count(), suppressed + 1);
+
+        // Other keys are throttled separately.
+        assert!(!throttle("test_warn_throttled_other", min_interval));
+
+        // Once the interval elapsed, the message is logged again.
+        warn_throttled("test_warn_throttled", message, Duration::ZERO);
+        assert_eq!(logged(), 2);
+        assert_eq!(METRICS.logger.suppressed_log_count.count(), suppressed + 1);
+    }
+}
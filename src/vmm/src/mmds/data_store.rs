@@ -7,6 +7,7 @@ use std::fmt::{Display, Formatter};
 use serde::{Deserialize, Serialize};
 use serde_json::{to_vec, Value};
 
+use crate::logger::{StoreMetric, METRICS};
 use crate::mmds::token::{MmdsTokenError as TokenError, TokenAuthority};
 
 /// The Mmds is the Microvm Metadata Service represented as an untyped json.
@@ -17,6 +18,8 @@ pub struct Mmds {
     token_authority: Option<TokenAuthority>,
     is_initialized: bool,
     data_store_limit: usize,
+    // Size of the serialized data store, reported by the `data_store_bytes` metric.
+    data_store_bytes: usize,
 }
 
 /// MMDS version.
@@ -39,7 +42,7 @@ impl Display for MmdsVersion {
 }
 
 /// MMDS possible outputs.
-#[derive(Debug)]
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
 pub enum OutputFormat {
     /// MMDS output format as Json
     Json,
@@ -60,6 +63,8 @@ pub enum MmdsDatastoreError {
     TokenAuthority(#[from] TokenError),
     /// Cannot retrieve value. The value has an unsupported type.
     UnsupportedValueType,
+    /// The MMDS patch changes the type of the value at {0:?}.
+    InvalidPatchAt(String),
 }
 
 // Used for ease of use in tests.
@@ -77,6 +82,7 @@ impl Mmds {
             token_authority: None,
             is_initialized: false,
             data_store_limit,
+            data_store_bytes: 0,
         }
     }
 
@@ -149,11 +155,13 @@ impl Mmds {
     pub fn put_data(&mut self, data: Value) -> Result<(), MmdsDatastoreError> {
         // It is safe to unwrap because any map keys are all strings and
         // we are using default serializer which does not return error.
-        if to_vec(&data).unwrap().len() > self.data_store_limit {
+        let data_store_bytes = to_vec(&data).unwrap().len();
+        if data_store_bytes > self.data_store_limit {
             Err(MmdsDatastoreError::DataStoreLimitExceeded)
         } else {
             self.data_store = data;
             self.is_initialized = true;
+            self.set_data_store_bytes(data_store_bytes);
 
             Ok(())
         }
@@ -162,18 +170,32 @@ impl Mmds {
     /// patch update MMDS data store with `patch_data`
     pub fn patch_data(&mut self, patch_data: Value) -> Result<(), MmdsDatastoreError> {
         self.check_data_store_initialized()?;
+        validate_patch(&self.data_store, &patch_data, "")?;
         let mut data_store_clone = self.data_store.clone();
 
         super::json_patch(&mut data_store_clone, &patch_data);
         // It is safe to unwrap because our data store keys are all strings and
         // we are using default serializer which does not return error.
-        if to_vec(&data_store_clone).unwrap().len() > self.data_store_limit {
+        let data_store_bytes = to_vec(&data_store_clone).unwrap().len();
+        if data_store_bytes > self.data_store_limit {
             return Err(MmdsDatastoreError::DataStoreLimitExceeded);
         }
         self.data_store = data_store_clone;
+        self.set_data_store_bytes(data_store_bytes);
         Ok(())
     }
 
+    fn set_data_store_bytes(&mut self, data_store_bytes: usize) {
+        self.data_store_bytes = data_store_bytes;
+        METRICS.mmds.data_store_bytes.store(data_store_bytes as u64);
+    }
+
+    /// Returns the size of the serialized data store, as last reported by the
+    /// `data_store_bytes` metric.
+    pub fn data_store_bytes(&self) -> usize {
+        self.data_store_bytes
+    }
+
     /// return MMDS data store value
     /// We do not check size of data_store before returning a result because due
     /// to limit from put/patch the data_store can not be bigger than the limit
@@ -250,6 +272,13 @@ impl Mmds {
         }
     }
 
+    fn pointer(&self, path: &str) -> Option<&Value> {
+        // The pointer function splits the input by "/". With a trailing "/", pointer does not
+        // know how to get the object.
+        self.data_store
+            .pointer(path.strip_suffix('/').unwrap_or(path))
+    }
+
     /// Returns the subtree located at path. When the path corresponds to a leaf, it returns the
     /// value. Returns Error::NotFound when the path is invalid.
     pub fn get_value(
@@ -257,15 +286,7 @@ impl Mmds {
         path: String,
         format: OutputFormat,
     ) -> Result<String, MmdsDatastoreError> {
-        // The pointer function splits the input by "/". With a trailing "/", pointer does not
-        // know how to get the object.
-        let value = if path.ends_with('/') {
-            self.data_store.pointer(&path.as_str()[..(path.len() - 1)])
-        } else {
-            self.data_store.pointer(path.as_str())
-        };
-
-        if let Some(json) = value {
+        if let Some(json) = self.pointer(&path) {
             match format {
                 OutputFormat::Json => Ok(json.to_string()),
                 OutputFormat::Imds => Mmds::format_imds(json),
@@ -274,6 +295,50 @@ impl Mmds {
             Err(MmdsDatastoreError::NotFound)
         }
     }
+
+    /// Returns the contents located at path in IMDS plain text format, in which numbers and
+    /// booleans are also rendered as text, or `None` if they have no plain text form, like arrays
+    /// and null values. Returns Error::NotFound when the path is invalid.
+    pub fn get_text(&self, path: &str) -> Result<Option<String>, MmdsDatastoreError> {
+        let json = self.pointer(path).ok_or(MmdsDatastoreError::NotFound)?;
+        match json {
+            Value::Object(_) | Value::String(_) => Mmds::format_imds(json).map(Some),
+            Value::Number(number) => Ok(Some(number.to_string())),
+            Value::Bool(value) => Ok(Some(value.to_string())),
+            Value::Array(_) | Value::Null => Ok(None),
+        }
+    }
+
+    /// Returns a copy of the subtree located at path. Returns Error::NotFound when the path is
+    /// invalid.
+    pub fn get_subtree(&self, path: &str) -> Result<Value, MmdsDatastoreError> {
+        self.pointer(path)
+            .cloned()
+            .ok_or(MmdsDatastoreError::NotFound)
+    }
+}
+
+/// Checks that the JSON merge patch does not change the type of any value already in the data
+/// store, returning the JSON pointer of the first such value. This is stricter than RFC 7396,
+/// which replaces such values. A value can still be replaced by one of another type after
+/// removing it, by patching it with null.
+fn validate_patch(target: &Value, patch: &Value, pointer: &str) -> Result<(), MmdsDatastoreError> {
+    match (target, patch) {
+        (Value::Object(target), Value::Object(patch)) => {
+            for (key, value) in patch {
+                if let Some(target_value) = target.get(key) {
+                    // Escape the key as a JSON pointer reference token (RFC 6901).
+                    let pointer =
+                        format!("{}/{}", pointer, key.replace('~', "~0").replace('/', "~1"));
+                    validate_patch(target_value, value, &pointer)?;
+                }
+            }
+            Ok(())
+        }
+        (Value::Null, _) | (_, Value::Null) => Ok(()),
+        _ if std::mem::discriminant(target) == std::mem::discriminant(patch) => Ok(()),
+        _ => Err(MmdsDatastoreError::InvalidPatchAt(pointer.to_string())),
+    }
 }
 
 #[cfg(test)]
@@ -494,6 +559,61 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_get_subtree() {
+        let mut mmds = Mmds::default();
+        let data = r#"{
+            "latest": {
+                "meta-data": {
+                    "instance-id": "i-1234"
+                }
+            }
+        }"#;
+        mmds.put_data(serde_json::from_str(data).unwrap()).unwrap();
+
+        assert_eq!(
+            mmds.get_subtree("/latest/meta-data/").unwrap(),
+            serde_json::json!({"instance-id": "i-1234"})
+        );
+        assert_eq!(
+            mmds.get_subtree("/latest/meta-data/instance-id").unwrap(),
+            Value::String("i-1234".to_string())
+        );
+        assert_eq!(mmds.get_subtree("/").unwrap(), mmds.data_store_value());
+        assert!(matches!(
+            mmds.get_subtree("/latest/user-data"),
+            Err(MmdsDatastoreError::NotFound)
+        ));
+    }
+
+    #[test]
+    fn test_get_text() {
+        let mut mmds = Mmds::default();
+        let data = r#"{
+            "name": {"first": "John"},
+            "age": 43,
+            "phones": ["+401234567"],
+            "member": false,
+            "nickname": null
+        }"#;
+        mmds.put_data(serde_json::from_str(data).unwrap()).unwrap();
+
+        assert_eq!(
+            mmds.get_text("/").unwrap().unwrap(),
+            "age\nmember\nname/\nnickname\nphones"
+        );
+        assert_eq!(mmds.get_text("/name").unwrap().unwrap(), "first");
+        assert_eq!(mmds.get_text("/name/first").unwrap().unwrap(), "John");
+        assert_eq!(mmds.get_text("/age").unwrap().unwrap(), "43");
+        assert_eq!(mmds.get_text("/member").unwrap().unwrap(), "false");
+        assert_eq!(mmds.get_text("/phones").unwrap(), None);
+        assert_eq!(mmds.get_text("/nickname").unwrap(), None);
+        assert!(matches!(
+            mmds.get_text("/invalid_path"),
+            Err(MmdsDatastoreError::NotFound)
+        ));
+    }
+
     #[test]
     fn test_update_data_store() {
         let mut mmds = Mmds::default();
@@ -565,6 +685,59 @@ mod tests {
         assert_eq!(mmds.get_data_str().len(), 72);
     }
 
+    #[test]
+    fn test_patch_type_mismatch() {
+        let mut mmds = Mmds::default();
+        let data = r#"{
+            "meta-data": {
+                "foo": {"bar": "baz"},
+                "a/b~c": "value",
+                "unset": null
+            },
+            "user-data": "1522850095"
+        }"#;
+        mmds.put_data(serde_json::from_str(data).unwrap()).unwrap();
+        let data_store = mmds.data_store_value();
+
+        for (patch, pointer) in [
+            (r#"{"meta-data": {"foo": "bar"}}"#, "/meta-data/foo"),
+            (
+                r#"{"meta-data": {"a/b~c": ["value"]}}"#,
+                "/meta-data/a~1b~0c",
+            ),
+            (r#"{"user-data": {"key": "value"}}"#, "/user-data"),
+            (r#""meta-data""#, ""),
+        ] {
+            assert_eq!(
+                mmds.patch_data(serde_json::from_str(patch).unwrap())
+                    .unwrap_err()
+                    .to_string(),
+                format!("The MMDS patch changes the type of the value at \"{pointer}\".")
+            );
+        }
+        // The data store is not modified by the rejected patches.
+        assert_eq!(mmds.data_store_value(), data_store);
+
+        // Values can be replaced after being removed, and null values take any type.
+        let patches = [
+            r#"{"meta-data": {"foo": null, "unset": {"key": "value"}}}"#,
+            r#"{"meta-data": {"foo": "bar", "new": {"key": "value"}}}"#,
+        ];
+        for patch in patches {
+            mmds.patch_data(serde_json::from_str(patch).unwrap())
+                .unwrap();
+        }
+        assert_eq!(
+            mmds.data_store_value()["meta-data"],
+            serde_json::json!({
+                "foo": "bar",
+                "a/b~c": "value",
+                "unset": {"key": "value"},
+                "new": {"key": "value"}
+            })
+        );
+    }
+
     #[test]
     fn test_put_size_limit() {
         let mut mmds = Mmds::default();
@@ -22,12 +22,13 @@ use crate::dumbo::pdu::ethernet::{
 use crate::dumbo::pdu::ipv4::{
     test_speculative_dst_addr, IPv4Packet, Ipv4Error as IPv4PacketError, PROTOCOL_TCP,
 };
-use crate::dumbo::pdu::tcp::TcpError as TcpSegmentError;
+use crate::dumbo::pdu::tcp::{TcpError as TcpSegmentError, TcpSegment};
 use crate::dumbo::pdu::Incomplete;
 use crate::dumbo::tcp::handler::{RecvEvent, TcpIPv4Handler, WriteEvent, WriteNextError};
 use crate::dumbo::tcp::NextSegmentStatus;
 use crate::logger::{IncMetric, METRICS};
 use crate::mmds::data_store::Mmds;
+use crate::rate_limiter::{BucketReduction, TokenBucket};
 use crate::utils::net::mac::MacAddr;
 
 const DEFAULT_MAC_ADDR: &str = "06:01:23:45:67:01";
@@ -74,6 +75,8 @@ pub struct MmdsNetworkStack {
     pub(crate) tcp_handler: TcpIPv4Handler,
     // Data store reference shared across all MmdsNetworkStack instances.
     pub mmds: Arc<Mutex<Mmds>>,
+    // Limits the rate of TCP segments carrying request data handled by MMDS.
+    pub(crate) rate_limiter: Option<TokenBucket>,
 }
 
 impl MmdsNetworkStack {
@@ -95,6 +98,7 @@ impl MmdsNetworkStack {
                 NonZeroUsize::new(DEFAULT_MAX_PENDING_RESETS).unwrap(),
             ),
             mmds,
+            rate_limiter: None,
         }
     }
 
@@ -119,6 +123,18 @@ impl MmdsNetworkStack {
         Ipv4Addr::from(DEFAULT_IPV4_ADDR)
     }
 
+    /// Sets the token bucket limiting the rate of MMDS requests, or removes the limit if `None`.
+    ///
+    /// Every TCP segment carrying request data consumes a token. Segments arriving while the
+    /// bucket is empty are dropped, so the guest retransmits them once the bucket refills.
+    pub fn set_rate_limiter(&mut self, rate_limiter: Option<TokenBucket>) {
+        self.rate_limiter = rate_limiter;
+    }
+
+    pub fn rate_limiter(&self) -> Option<&TokenBucket> {
+        self.rate_limiter.as_ref()
+    }
+
     /// Check if a frame is destined for `mmds`
     ///
     /// This returns `true` if the frame is an ARP or IPv4 frame destined for
@@ -173,6 +189,11 @@ impl MmdsNetworkStack {
         // context at some point!
         if let Ok(ip) = IPv4Packet::from_bytes(eth.payload(), false) {
             if ip.protocol() == PROTOCOL_TCP {
+                if self.is_throttled(&ip) {
+                    METRICS.mmds.rx_throttled.inc();
+                    return true;
+                }
+
                 // Note-1: `remote_mac_address` is actually the network device mac address, where
                 // this TCP segment came from.
                 // Note-2: For every routed packet we will have a single source MAC address, because
@@ -210,6 +231,20 @@ impl MmdsNetworkStack {
         false
     }
 
+    // Segments without payload, such as the ones opening or closing connections, are never
+    // throttled, so that connections can still be torn down while the rate limit is exceeded.
+    fn is_throttled(&mut self, ip: &IPv4Packet<&[u8]>) -> bool {
+        let Some(rate_limiter) = self.rate_limiter.as_mut() else {
+            return false;
+        };
+
+        let carries_data = TcpSegment::from_bytes(ip.payload(), None)
+            .map(|segment| segment.payload_len() > 0)
+            .unwrap_or(false);
+
+        carries_data && rate_limiter.reduce(1) == BucketReduction::Failure
+    }
+
     // Allows the MMDS network stack to write a frame to the specified buffer. Will return:
     // - None, if the MMDS network stack has no frame to send at this point. The buffer can be
     // used for something else by the device model.
@@ -317,7 +352,7 @@ mod tests {
     use std::str::FromStr;
 
     use super::*;
-    use crate::dumbo::pdu::tcp::{Flags as TcpFlags, TcpSegment};
+    use crate::dumbo::pdu::tcp::Flags as TcpFlags;
 
     // We use LOCALHOST here because const new() is not stable yet, so just reuse this const, since
     // all we're interested in is having some address different from the MMDS one.
@@ -355,6 +390,16 @@ mod tests {
             buf: &mut [u8],
             addr: Ipv4Addr,
             flags: TcpFlags,
+        ) -> usize {
+            self.write_incoming_tcp_segment_with_payload(buf, addr, flags, None)
+        }
+
+        fn write_incoming_tcp_segment_with_payload(
+            &self,
+            buf: &mut [u8],
+            addr: Ipv4Addr,
+            flags: TcpFlags,
+            payload: Option<&[u8]>,
         ) -> usize {
             let mut eth_unsized = self.prepare_eth_unsized(buf, ETHERTYPE_IPV4).unwrap();
             let packet_len = {
@@ -373,8 +418,8 @@ mod tests {
                     flags,
                     10000,
                     None,
-                    0,
-                    None,
+                    payload.map_or(0, |payload| payload.len().try_into().unwrap()),
+                    payload.map(|payload| (payload, payload.len())),
                 )
                 .unwrap()
                 .finalize(REMOTE_PORT, MMDS_PORT, Some((REMOTE_ADDR, addr)))
@@ -529,6 +574,57 @@ mod tests {
         assert!(ns.write_next_frame(buf.as_mut()).is_none());
     }
 
+    #[test]
+    fn test_rate_limiter() {
+        let mut ns =
+            MmdsNetworkStack::new_with_defaults(None, Arc::new(Mutex::new(Mmds::default())));
+        let mut buf = [0u8; 2000];
+        let mmds_addr = ns.ipv4_addr;
+        let request = b"GET / HTTP/1.1\r\n\r\n".as_slice();
+
+        // Without a rate limiter, requests are never throttled.
+        assert!(ns.rate_limiter().is_none());
+        let throttled = METRICS.mmds.rx_throttled.count();
+        for _ in 0..10 {
+            let len = ns.write_incoming_tcp_segment_with_payload(
+                buf.as_mut(),
+                mmds_addr,
+                TcpFlags::ACK | TcpFlags::PSH,
+                Some(request),
+            );
+            assert!(!ns.is_throttled(&ipv4_packet(&buf[..len])));
+        }
+
+        // Allow 2 requests per second and flood MMDS with requests.
+        ns.set_rate_limiter(TokenBucket::new(2, 0, 1000));
+        for i in 0..10 {
+            let len = ns.write_incoming_tcp_segment_with_payload(
+                buf.as_mut(),
+                mmds_addr,
+                TcpFlags::ACK | TcpFlags::PSH,
+                Some(request),
+            );
+            let rx_count = METRICS.mmds.rx_count.count();
+            // Throttled segments are still consumed, so they do not reach the tap.
+            assert!(ns.detour_frame(&buf[..len]));
+            if i < 2 {
+                assert!(METRICS.mmds.rx_count.count() > rx_count);
+            }
+        }
+        assert_eq!(METRICS.mmds.rx_throttled.count(), throttled + 8);
+
+        // Segments without payload are not throttled even if the bucket is empty.
+        let len = ns.write_incoming_tcp_segment(buf.as_mut(), mmds_addr, TcpFlags::SYN);
+        assert!(!ns.is_throttled(&ipv4_packet(&buf[..len])));
+        assert!(ns.detour_frame(&buf[..len]));
+        assert_eq!(METRICS.mmds.rx_throttled.count(), throttled + 8);
+    }
+
+    fn ipv4_packet(frame: &[u8]) -> IPv4Packet<&[u8]> {
+        let eth = EthernetFrame::from_bytes(frame).unwrap();
+        IPv4Packet::from_bytes(&frame[eth.payload_offset()..], true).unwrap()
+    }
+
     #[test]
     fn test_set_ipv4_addr() {
         let mut ns =
@@ -3,6 +3,7 @@
 
 //! Defines the structures needed for saving/restoring MmdsNetworkStack.
 
+use std::io;
 use std::net::Ipv4Addr;
 use std::sync::{Arc, Mutex};
 
@@ -10,6 +11,8 @@ use serde::{Deserialize, Serialize};
 
 use super::ns::MmdsNetworkStack;
 use crate::mmds::data_store::Mmds;
+use crate::rate_limiter::persist::TokenBucketState;
+use crate::rate_limiter::TokenBucket;
 use crate::snapshot::Persist;
 use crate::utils::net::mac::{MacAddr, MAC_ADDR_LEN};
 
@@ -19,12 +22,13 @@ pub struct MmdsNetworkStackState {
     mac_addr: [u8; MAC_ADDR_LEN as usize],
     ipv4_addr: u32,
     tcp_port: u16,
+    rate_limiter: Option<TokenBucketState>,
 }
 
 impl Persist<'_> for MmdsNetworkStack {
     type State = MmdsNetworkStackState;
     type ConstructorArgs = Arc<Mutex<Mmds>>;
-    type Error = ();
+    type Error = io::Error;
 
     fn save(&self) -> Self::State {
         let mut mac_addr = [0; MAC_ADDR_LEN as usize];
@@ -34,6 +38,7 @@ impl Persist<'_> for MmdsNetworkStack {
             mac_addr,
             ipv4_addr: self.ipv4_addr.into(),
             tcp_port: self.tcp_handler.local_port(),
+            rate_limiter: self.rate_limiter.as_ref().map(TokenBucket::save),
         }
     }
 
@@ -41,12 +46,21 @@ impl Persist<'_> for MmdsNetworkStack {
         mmds: Self::ConstructorArgs,
         state: &Self::State,
     ) -> std::result::Result<Self, Self::Error> {
-        Ok(MmdsNetworkStack::new(
+        let mut ns = MmdsNetworkStack::new(
             MacAddr::from_bytes_unchecked(&state.mac_addr),
             Ipv4Addr::from(state.ipv4_addr),
             state.tcp_port,
             mmds,
-        ))
+        );
+        ns.set_rate_limiter(
+            state
+                .rate_limiter
+                .as_ref()
+                .map(|bucket| TokenBucket::restore((), bucket))
+                .transpose()?,
+        );
+
+        Ok(ns)
     }
 }
 
@@ -58,7 +72,9 @@ mod tests {
 
     #[test]
     fn test_persistence() {
-        let ns = MmdsNetworkStack::new_with_defaults(None, Arc::new(Mutex::new(Mmds::default())));
+        let mut ns =
+            MmdsNetworkStack::new_with_defaults(None, Arc::new(Mutex::new(Mmds::default())));
+        ns.set_rate_limiter(TokenBucket::new(10, 0, 1000));
 
         let mut mem = vec![0; 4096];
 
@@ -76,5 +92,9 @@ mod tests {
             restored_ns.tcp_handler.local_port(),
             ns.tcp_handler.local_port()
         );
+        assert_eq!(
+            restored_ns.rate_limiter().unwrap().capacity(),
+            ns.rate_limiter().unwrap().capacity()
+        );
     }
 }
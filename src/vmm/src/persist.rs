@@ -3,9 +3,11 @@
 
 //! Defines state structures for saving/restoring a Firecracker microVM.
 
+use std::collections::BTreeSet;
 use std::fmt::Debug;
 use std::fs::{File, OpenOptions};
-use std::io::{self, Write};
+use std::io::{self, Seek, Write};
+use std::os::unix::fs::MetadataExt;
 use std::os::unix::io::AsRawFd;
 use std::os::unix::net::UnixStream;
 use std::path::Path;
@@ -15,6 +17,7 @@ use seccompiler::BpfThreadMap;
 use semver::Version;
 use serde::{Deserialize, Serialize};
 use userfaultfd::{FeatureFlags, Uffd, UffdBuilder};
+use vm_memory::WriteVolatile;
 use vmm_sys_util::sock_ctrl_msg::ScmSocket;
 
 #[cfg(target_arch = "aarch64")]
@@ -35,9 +38,11 @@ use crate::vmm_config::instance_info::InstanceInfo;
 use crate::vmm_config::machine_config::{HugePageConfig, MachineConfigUpdate, VmConfigError};
 use crate::vmm_config::snapshot::{
     CreateSnapshotParams, LoadSnapshotParams, MemBackendType, SnapshotType,
+    MAX_MEM_WRITE_CHUNK_SIZE_KIB, MIN_MEM_WRITE_CHUNK_SIZE_KIB,
 };
 use crate::vstate::memory::{
-    GuestMemory, GuestMemoryExtension, GuestMemoryMmap, GuestMemoryState, MemoryError,
+    ChunkedWriter, GuestMemory, GuestMemoryExtension, GuestMemoryMmap, GuestMemoryState,
+    MemoryError,
 };
 use crate::vstate::vcpu::{VcpuSendEventError, VcpuState};
 use crate::vstate::vm::VmState;
@@ -146,6 +151,10 @@ pub enum CreateSnapshotError {
     Memory(MemoryError),
     /// Cannot perform {0} on the memory backing file: {1}
     MemoryBackingFile(&'static str, io::Error),
+    /// Cannot perform {0} on the baseline memory file: {1}
+    BaselineMemoryFile(&'static str, io::Error),
+    /// Size mismatch when diffing against the baseline memory file: guest memory size is {0} but baseline is size {1}.
+    BaselineMemoryFileLengthMismatch(u64, u64),
     /// Cannot save the microVM state: {0}
     MicrovmState(MicrovmStateError),
     /// Cannot serialize the microVM state: {0}
@@ -154,10 +163,12 @@ pub enum CreateSnapshotError {
     SnapshotBackingFile(&'static str, io::Error),
     /// Size mismatch when writing diff snapshot on top of base layer: base layer size is {0} but diff layer is size {1}.
     SnapshotBackingFileLengthMismatch(u64, u64),
+    /// Invalid memory write chunk size of {0} KiB, it must be between 4 KiB and 1 GiB.
+    InvalidMemWriteChunkSize(u32),
 }
 
 /// Snapshot version
-pub const SNAPSHOT_VERSION: Version = Version::new(5, 0, 0);
+pub const SNAPSHOT_VERSION: Version = Version::new(6, 0, 0);
 
 /// Creates a Microvm snapshot.
 pub fn create_snapshot(
@@ -165,13 +176,30 @@ pub fn create_snapshot(
     vm_info: &VmInfo,
     params: &CreateSnapshotParams,
 ) -> Result<(), CreateSnapshotError> {
+    let mem_write_chunk_size = params
+        .mem_write_chunk_size_kib
+        .map(|size_kib| {
+            if (MIN_MEM_WRITE_CHUNK_SIZE_KIB..=MAX_MEM_WRITE_CHUNK_SIZE_KIB).contains(&size_kib) {
+                Ok(size_kib as usize * 1024)
+            } else {
+                Err(CreateSnapshotError::InvalidMemWriteChunkSize(size_kib))
+            }
+        })
+        .transpose()?;
+
     let microvm_state = vmm
         .save_state(vm_info)
         .map_err(CreateSnapshotError::MicrovmState)?;
 
     snapshot_state_to_file(&microvm_state, &params.snapshot_path)?;
 
-    snapshot_memory_to_file(vmm, &params.mem_file_path, params.snapshot_type)?;
+    snapshot_memory_to_file(
+        vmm,
+        &params.mem_file_path,
+        params.snapshot_type,
+        params.baseline_mem_file_path.as_deref(),
+        mem_write_chunk_size,
+    )?;
 
     Ok(())
 }
@@ -206,13 +234,23 @@ fn snapshot_state_to_file(
 /// If `snapshot_type` is [`SnapshotType::Diff`], and `mem_file_path` exists and is a snapshot file
 /// of matching size, then the diff snapshot will be directly merged into the existing snapshot.
 /// Otherwise, existing files are simply overwritten.
+///
+/// If `baseline_mem_file_path` is provided, the diff snapshot contains the pages whose contents
+/// differ from the baseline memory file, instead of the dirty pages. In this case, an existing
+/// `mem_file_path` is always overwritten, unless it is the baseline itself.
 fn snapshot_memory_to_file(
     vmm: &Vmm,
     mem_file_path: &Path,
     snapshot_type: SnapshotType,
+    baseline_mem_file_path: Option<&Path>,
+    mem_write_chunk_size: Option<usize>,
 ) -> Result<(), CreateSnapshotError> {
     use self::CreateSnapshotError::*;
 
+    let baseline_file = baseline_mem_file_path
+        .map(|path| File::open(path).map_err(|err| BaselineMemoryFile("open", err)))
+        .transpose()?;
+
     // Need to check this here, as we create the file in the line below
     let file_existed = mem_file_path.exists();
 
@@ -227,6 +265,26 @@ fn snapshot_memory_to_file(
     let mem_size_mib = mem_size_mib(vmm.guest_memory());
     let expected_size = mem_size_mib * 1024 * 1024;
 
+    // A content diff is only meaningful on top of a baseline of the same size.
+    let mut is_baseline = false;
+    if let Some(baseline_file) = baseline_file.as_ref() {
+        let baseline_metadata = baseline_file
+            .metadata()
+            .map_err(|err| BaselineMemoryFile("get_metadata", err))?;
+        if baseline_metadata.len() != expected_size {
+            return Err(BaselineMemoryFileLengthMismatch(
+                expected_size,
+                baseline_metadata.len(),
+            ));
+        }
+
+        let metadata = file
+            .metadata()
+            .map_err(|e| MemoryBackingFile("get_metadata", e))?;
+        is_baseline =
+            metadata.dev() == baseline_metadata.dev() && metadata.ino() == baseline_metadata.ino();
+    }
+
     if file_existed {
         let file_size = file
             .metadata()
@@ -240,7 +298,10 @@ fn snapshot_memory_to_file(
         //   reflected in the mmap of the file, meaning a truncate operation would zero out guest
         //   memory, and thus corrupt the VM).
         // - For diff snapshots, we want to merge the diff layer directly into the file.
-        if file_size != expected_size {
+        // - For diff snapshots against a baseline, the pages matching the baseline are not written,
+        //   so any stale contents must be dropped. Unless the file is the baseline, in which case
+        //   the diff layer is merged into it.
+        if file_size != expected_size || (baseline_file.is_some() && !is_baseline) {
             file.set_len(0)
                 .map_err(|err| MemoryBackingFile("truncate", err))?;
         }
@@ -250,22 +311,14 @@ fn snapshot_memory_to_file(
     file.set_len(expected_size)
         .map_err(|e| MemoryBackingFile("set_length", e))?;
 
-    match snapshot_type {
-        SnapshotType::Diff => {
-            let dirty_bitmap = vmm.get_dirty_bitmap().map_err(DirtyBitmap)?;
-            vmm.guest_memory()
-                .dump_dirty(&mut file, &dirty_bitmap)
-                .map_err(Memory)
-        }
-        SnapshotType::Full => {
-            let dump_res = vmm.guest_memory().dump(&mut file).map_err(Memory);
-            if dump_res.is_ok() {
-                vmm.reset_dirty_bitmap();
-                vmm.guest_memory().reset_dirty();
-            }
-
-            dump_res
-        }
+    match mem_write_chunk_size {
+        Some(chunk_size) => dump_memory(
+            vmm,
+            &mut ChunkedWriter::new(&mut file, chunk_size),
+            snapshot_type,
+            baseline_file,
+        ),
+        None => dump_memory(vmm, &mut file, snapshot_type, baseline_file),
     }?;
     // We need to mark queues as dirty again for all activated devices. The reason we
     // do it here is because we don't mark pages as dirty during runtime
@@ -290,6 +343,39 @@ fn snapshot_memory_to_file(
         .map_err(|err| MemoryBackingFile("sync_all", err))
 }
 
+fn dump_memory<T: WriteVolatile + Seek>(
+    vmm: &Vmm,
+    writer: &mut T,
+    snapshot_type: SnapshotType,
+    baseline_file: Option<File>,
+) -> Result<(), CreateSnapshotError> {
+    use self::CreateSnapshotError::*;
+
+    match snapshot_type {
+        SnapshotType::Diff => match baseline_file {
+            Some(mut baseline_file) => vmm
+                .guest_memory()
+                .dump_diff(writer, &mut baseline_file)
+                .map_err(Memory),
+            None => {
+                let dirty_bitmap = vmm.get_dirty_bitmap().map_err(DirtyBitmap)?;
+                vmm.guest_memory()
+                    .dump_dirty(writer, &dirty_bitmap)
+                    .map_err(Memory)
+            }
+        },
+        SnapshotType::Full => {
+            let dump_res = vmm.guest_memory().dump(writer).map_err(Memory);
+            if dump_res.is_ok() {
+                vmm.reset_dirty_bitmap();
+                vmm.guest_memory().reset_dirty();
+            }
+
+            dump_res
+        }
+    }
+}
+
 /// Validates that snapshot CPU vendor matches the host CPU vendor.
 ///
 /// # Errors
@@ -385,6 +471,23 @@ pub fn snapshot_state_sanity_check(
     Ok(())
 }
 
+/// Checks that the devices restored in `vm_resources` are exactly the devices saved in the
+/// snapshot. Devices cannot be configured before loading a snapshot, so the resources only hold
+/// the restored devices at this point.
+fn snapshot_device_set_check(
+    saved: &BTreeSet<String>,
+    vm_resources: &VmResources,
+) -> Result<(), RestoreFromSnapshotError> {
+    let restored = vm_resources.snapshot_device_ids();
+    let missing: Vec<String> = saved.difference(&restored).cloned().collect();
+    let extra: Vec<String> = restored.difference(saved).cloned().collect();
+    if missing.is_empty() && extra.is_empty() {
+        Ok(())
+    } else {
+        Err(RestoreFromSnapshotError::DeviceSetMismatch { missing, extra })
+    }
+}
+
 /// Error type for [`restore_from_snapshot`].
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
 pub enum RestoreFromSnapshotError {
@@ -396,6 +499,13 @@ pub enum RestoreFromSnapshotError {
     GuestMemory(#[from] RestoreFromSnapshotGuestMemoryError),
     /// Failed to build microVM from snapshot: {0}
     Build(#[from] BuildMicrovmFromSnapshotError),
+    /// Restored devices do not match the snapshot ones: missing {missing:?}, extra {extra:?}
+    DeviceSetMismatch {
+        /// Devices in the snapshot that were not restored.
+        missing: Vec<String>,
+        /// Restored devices that are not in the snapshot.
+        extra: Vec<String>,
+    },
 }
 /// Sub-Error type for [`restore_from_snapshot`] to contain either [`GuestMemoryFromFileError`] or
 /// [`GuestMemoryFromUffdError`] within [`RestoreFromSnapshotError`].
@@ -418,6 +528,8 @@ pub fn restore_from_snapshot(
     let microvm_state = snapshot_state_from_file(&params.snapshot_path)?;
     let track_dirty_pages = params.enable_diff_snapshots;
 
+    let snapshot_device_ids = microvm_state.device_states.device_ids();
+
     let vcpu_count = microvm_state
         .vcpu_states
         .len()
@@ -433,6 +545,36 @@ pub fn restore_from_snapshot(
             cpu_template: Some(microvm_state.vm_info.cpu_template),
             track_dirty_pages: Some(track_dirty_pages),
             huge_pages: Some(microvm_state.vm_info.huge_pages),
+            device_memory: None,
+            vendor_id: None,
+            brand_string: None,
+            cache_levels: None,
+            memslot_size_mib: None,
+            cpu_signature: None,
+            cpu_frequencies: None,
+            cpu_topology: None,
+            min_mem_size_mib: None,
+            host_memory_check: None,
+            kvm_run_retries: None,
+            invariant_tsc: None,
+            thp_advice: None,
+            max_cpuid_leaves: None,
+            vcpu_idle_strategy: None,
+            #[cfg(feature = "mem-poison")]
+            mem_poison: None,
+            cache_line_size: None,
+            // Snapshots cannot be taken of microVMs with a file backing their guest memory.
+            mem_backing_file: None,
+            avx_features: None,
+            hypervisor_bit: None,
+            rdrand: None,
+            rdseed: None,
+            monitor_mwait: None,
+            pmu: None,
+            tsc_deadline: None,
+            serial_output_rate_limiter: None,
+            // The maximum length of the descriptor chains is restored with the queues.
+            max_desc_chain_len: None,
             #[cfg(feature = "gdb")]
             gdb_socket_path: None,
         })
@@ -466,7 +608,7 @@ pub fn restore_from_snapshot(
         )
         .map_err(RestoreFromSnapshotGuestMemoryError::Uffd)?,
     };
-    builder::build_microvm_from_snapshot(
+    let vmm = builder::build_microvm_from_snapshot(
         instance_info,
         event_manager,
         microvm_state,
@@ -475,7 +617,10 @@ pub fn restore_from_snapshot(
         seccomp_filters,
         vm_resources,
     )
-    .map_err(RestoreFromSnapshotError::Build)
+    .map_err(RestoreFromSnapshotError::Build)?;
+
+    snapshot_device_set_check(&snapshot_device_ids, vm_resources)?;
+    Ok(vmm)
 }
 
 /// Error type for [`snapshot_state_from_file`]
@@ -659,6 +804,7 @@ mod tests {
     use crate::devices::virtio::block::CacheType;
     use crate::snapshot::Persist;
     use crate::vmm_config::balloon::BalloonDeviceConfig;
+    use crate::vmm_config::entropy::EntropyDeviceConfig;
     use crate::vmm_config::net::NetworkInterfaceConfig;
     use crate::vmm_config::vsock::tests::default_config;
     use crate::vstate::memory::GuestMemoryRegionState;
@@ -674,6 +820,7 @@ mod tests {
             amount_mib: 0,
             deflate_on_oom: false,
             stats_polling_interval_s: 0,
+            free_page_reporting: false,
         };
         insert_balloon_device(&mut vmm, &mut cmdline, &mut event_manager, balloon_config);
 
@@ -695,6 +842,7 @@ mod tests {
             guest_mac: None,
             rx_rate_limiter: None,
             tx_rate_limiter: None,
+            mtu: None,
         };
         insert_net_device(
             &mut vmm,
@@ -716,6 +864,91 @@ mod tests {
         vmm
     }
 
+    #[test]
+    fn test_device_states_json() {
+        let vmm = default_vmm_with_devices();
+        let states = vmm.device_states().unwrap();
+
+        assert_eq!(states["block_devices"][0]["device_id"], "root");
+        assert_eq!(states["net_devices"][0]["device_id"], "netif");
+        assert_eq!(states["vsock_device"]["device_id"], "vsock");
+        assert_eq!(states["balloon_device"]["device_id"], "balloon");
+        assert!(states["entropy_device"].is_null());
+        // The dump has the same structure as the device states persisted in a snapshot.
+        let persisted: DeviceStates = serde_json::from_value(states).unwrap();
+        assert_eq!(persisted.block_devices.len(), 1);
+        assert_eq!(persisted.net_devices.len(), 1);
+    }
+
+    #[test]
+    fn test_device_states_side_effects() {
+        use crate::devices::virtio::device::VirtioDevice;
+        use crate::devices::virtio::queue::VIRTQ_DESC_F_WRITE;
+        use crate::devices::virtio::test_utils::VirtQueue;
+        use crate::devices::virtio::vsock::{Vsock, VsockUnixBackend, TYPE_VSOCK};
+        use crate::vstate::memory::GuestAddress;
+
+        let vmm = default_vmm_with_devices();
+        let mem = vmm.guest_memory().clone();
+        let vq = VirtQueue::new(GuestAddress(0x10_0000), &mem, 16);
+        vq.dtable[0].set(0x20_0000, 4, VIRTQ_DESC_F_WRITE, 0);
+        vq.avail.ring[0].set(0);
+        vq.avail.idx.set(1);
+        vmm.mmio_device_manager
+            .with_virtio_device_with_id(
+                TYPE_VSOCK,
+                "vsock",
+                |vsock: &mut Vsock<VsockUnixBackend>| {
+                    vsock.queues = (0..3).map(|_| vq.create_queue()).collect();
+                    vsock.activate(mem.clone()).map_err(|err| err.to_string())
+                },
+            )
+            .unwrap();
+        // The transport reset event is sent through the event queue, the last one.
+        let evq_next_avail = || {
+            let mut next_avail = 0;
+            vmm.mmio_device_manager
+                .with_virtio_device_with_id(
+                    TYPE_VSOCK,
+                    "vsock",
+                    |vsock: &mut Vsock<VsockUnixBackend>| {
+                        next_avail = vsock.queues().last().unwrap().next_avail.0;
+                        Ok(())
+                    },
+                )
+                .unwrap();
+            next_avail
+        };
+
+        // Dumping the device states does not reset the vsock connections.
+        vmm.device_states().unwrap();
+        assert_eq!(evq_next_avail(), 0);
+        assert_eq!(vq.used.idx.get(), 0);
+
+        // Saving them for a snapshot does.
+        vmm.mmio_device_manager.save();
+        assert_eq!(evq_next_avail(), 1);
+        assert_eq!(vq.used.idx.get(), 1);
+    }
+
+    #[test]
+    fn test_list_devices() {
+        use crate::arch::DeviceType;
+        use crate::devices::virtio::{TYPE_BLOCK, TYPE_NET};
+
+        let vmm = default_vmm_with_devices();
+        let devices = vmm.list_devices();
+
+        assert!(devices.contains(&(DeviceType::Virtio(TYPE_BLOCK), "root".to_string())));
+        assert!(devices.contains(&(DeviceType::Virtio(TYPE_NET), "netif".to_string())));
+        // Each device is listed once, in a stable order.
+        assert_eq!(
+            devices.len(),
+            vmm.mmio_device_manager.get_device_info().len()
+        );
+        assert!(devices.windows(2).all(|pair| pair[0] < pair[1]));
+    }
+
     #[test]
     fn test_microvm_state_snapshot() {
         let vmm = default_vmm_with_devices();
@@ -760,6 +993,44 @@ mod tests {
         )
     }
 
+    #[test]
+    fn test_snapshot_device_set_check() {
+        let vmm = default_vmm_with_devices();
+        let states = vmm.mmio_device_manager.save();
+        let saved = states.device_ids();
+
+        // The resources hold a different set of devices than the snapshot.
+        let mut vm_resources = VmResources::default();
+        vm_resources
+            .set_balloon_device(BalloonDeviceConfig::default())
+            .unwrap();
+        vm_resources
+            .build_entropy_device(EntropyDeviceConfig::default())
+            .unwrap();
+        let err = snapshot_device_set_check(&saved, &vm_resources).unwrap_err();
+        assert!(matches!(
+            &err,
+            RestoreFromSnapshotError::DeviceSetMismatch { missing, extra }
+                if missing == &["block/root", "net/netif", "vsock"] && extra == &["entropy"]
+        ));
+        assert_eq!(
+            err.to_string(),
+            "Restored devices do not match the snapshot ones: missing [\"block/root\", \
+             \"net/netif\", \"vsock\"], extra [\"entropy\"]"
+        );
+
+        // The resources hold the devices of the snapshot.
+        let mut vm_resources = VmResources::default();
+        vm_resources
+            .set_balloon_device(BalloonDeviceConfig::default())
+            .unwrap();
+        let states = DeviceStates {
+            balloon_device: states.balloon_device,
+            ..Default::default()
+        };
+        snapshot_device_set_check(&states.device_ids(), &vm_resources).unwrap();
+    }
+
     #[test]
     fn test_create_guest_memory() {
         let mem_state = GuestMemoryState {
@@ -260,6 +260,19 @@ impl TokenBucket {
     pub fn initial_one_time_burst(&self) -> u64 {
         self.initial_one_time_burst
     }
+
+    /// Returns the budget the bucket would hold if it was replenished now, without actually
+    /// replenishing it.
+    pub fn replenished_budget(&self) -> u64 {
+        let mut bucket = self.clone();
+        bucket.auto_replenish();
+        bucket.budget
+    }
+
+    /// Returns the time elapsed since the bucket was last used or replenished.
+    pub fn time_since_last_update(&self) -> Duration {
+        self.last_update.elapsed()
+    }
 }
 
 /// Enum that describes the type of token used.
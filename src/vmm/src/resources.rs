@@ -1,15 +1,18 @@
 // Copyright 2019 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
 
+use std::collections::BTreeSet;
 use std::convert::From;
-use std::path::PathBuf;
+use std::fs::{File, OpenOptions};
+use std::net::Ipv4Addr;
+use std::path::{Path, PathBuf};
 use std::sync::{Arc, Mutex, MutexGuard};
 
 use serde::{Deserialize, Serialize};
 
 use crate::cpu_config::templates::CustomCpuTemplate;
 use crate::device_manager::persist::SharedDeviceType;
-use crate::logger::{info, log_dev_preview_warning};
+use crate::logger::{info, log_dev_preview_warning, warn};
 use crate::mmds;
 use crate::mmds::data_store::{Mmds, MmdsVersion};
 use crate::mmds::ns::MmdsNetworkStack;
@@ -22,12 +25,15 @@ use crate::vmm_config::drive::*;
 use crate::vmm_config::entropy::*;
 use crate::vmm_config::instance_info::InstanceInfo;
 use crate::vmm_config::machine_config::{
-    HugePageConfig, MachineConfig, MachineConfigUpdate, VmConfig, VmConfigError,
+    memory_regions, HostMemoryPolicy, HugePageConfig, MachineConfig, MachineConfigUpdate, VmConfig,
+    VmConfigError,
 };
 use crate::vmm_config::metrics::{init_metrics, MetricsConfig, MetricsConfigError};
 use crate::vmm_config::mmds::{MmdsConfig, MmdsConfigError};
 use crate::vmm_config::net::*;
 use crate::vmm_config::vsock::*;
+use crate::vmm_config::watchdog::*;
+use crate::vmm_config::TokenBucketConfig;
 use crate::vstate::memory::{GuestMemoryExtension, GuestMemoryMmap, MemoryError};
 
 /// Errors encountered when configuring microVM resources.
@@ -43,6 +49,8 @@ pub enum ResourcesError {
     File(#[from] std::io::Error),
     /// Invalid JSON: {0}
     InvalidJson(#[from] serde_json::Error),
+    /// The logger and metrics cannot be configured through a full microVM configuration.
+    LoggerOrMetricsConfig,
     /// Logger error: {0}
     Logger(#[from] crate::logger::LoggerUpdateError),
     /// Metrics error: {0}
@@ -59,6 +67,8 @@ pub enum ResourcesError {
     VsockDevice(#[from] VsockConfigError),
     /// Entropy device error: {0}
     EntropyDevice(#[from] EntropyDeviceError),
+    /// Watchdog device error: {0}
+    WatchdogDevice(#[from] WatchdogDeviceError),
 }
 
 /// Used for configuring a vmm from one single json passed to the Firecracker process.
@@ -66,7 +76,7 @@ pub enum ResourcesError {
 pub struct VmmConfig {
     #[serde(rename = "balloon")]
     balloon_device: Option<BalloonDeviceConfig>,
-    #[serde(rename = "drives")]
+    #[serde(rename = "drives", default)]
     block_devices: Vec<BlockDeviceConfig>,
     #[serde(rename = "boot-source")]
     boot_source: BootSourceConfig,
@@ -86,6 +96,8 @@ pub struct VmmConfig {
     vsock_device: Option<VsockDeviceConfig>,
     #[serde(rename = "entropy")]
     entropy_device: Option<EntropyDeviceConfig>,
+    #[serde(rename = "watchdog")]
+    watchdog_device: Option<WatchdogDeviceConfig>,
 }
 
 /// A data structure that encapsulates the device configurations
@@ -106,6 +118,8 @@ pub struct VmResources {
     pub net_builder: NetBuilder,
     /// The entropy device builder.
     pub entropy: EntropyDeviceBuilder,
+    /// The watchdog device builder.
+    pub watchdog: WatchdogDeviceBuilder,
     /// The optional Mmds data store.
     // This is initialised on demand (if ever used), so that we don't allocate it unless it's
     // actually used.
@@ -124,67 +138,231 @@ impl VmResources {
         mmds_size_limit: usize,
         metadata_json: Option<&str>,
     ) -> Result<Self, ResourcesError> {
-        let vmm_config = serde_json::from_str::<VmmConfig>(config_json)?;
+        let mut vmm_config = serde_json::from_str::<VmmConfig>(config_json)?;
 
-        if let Some(logger_config) = vmm_config.logger {
+        if let Some(logger_config) = vmm_config.logger.take() {
             crate::logger::LOGGER.update(logger_config)?;
         }
 
-        if let Some(metrics) = vmm_config.metrics {
+        if let Some(metrics) = vmm_config.metrics.take() {
             init_metrics(metrics)?;
         }
 
+        Self::from_vmm_config(vmm_config, instance_info, mmds_size_limit, metadata_json)
+    }
+
+    /// Checks that `config_json` describes a valid microVM configuration, and returns the
+    /// configuration completed with the default values.
+    ///
+    /// The device configurations are checked without creating the devices, since that would
+    /// bind the vsock socket, open the tap devices and the backing files, and create file
+    /// descriptors the seccomp filters of a running microVM do not allow. The logger and metrics
+    /// configurations are only parsed, since applying them would change the state of the whole
+    /// process.
+    pub fn validate_json(
+        config_json: &str,
+        instance_info: &InstanceInfo,
+        mmds_size_limit: usize,
+    ) -> Result<VmmConfig, ResourcesError> {
+        let mut vmm_config = serde_json::from_str::<VmmConfig>(config_json)?;
+
+        let block_devices =
+            BlockBuilder::check_configs(std::mem::take(&mut vmm_config.block_devices))?;
+        let net_devices = NetBuilder::check_configs(std::mem::take(&mut vmm_config.net_devices))?;
+        let vsock_device = vmm_config.vsock_device.take();
+        if let Some(vsock_config) = vsock_device.as_ref() {
+            VsockBuilder::check_config(vsock_config)?;
+        }
+        let watchdog_device = vmm_config.watchdog_device.take();
+        if let Some(watchdog_config) = watchdog_device.as_ref() {
+            WatchdogDeviceBuilder::check_config(watchdog_config)?;
+        }
+        let balloon_device = vmm_config.balloon_device.take();
+        let entropy_device = vmm_config.entropy_device.take();
+        let mmds_config = vmm_config.mmds_config.take();
+
+        // The remaining resources are built without devices.
+        let mut resources =
+            Self::from_vmm_config(vmm_config, instance_info, mmds_size_limit, None)?;
+        let mmds_config = match mmds_config {
+            Some(mmds_config) => {
+                let ipv4_addr = Self::check_mmds_network_stack_config(&mmds_config, |id| {
+                    net_devices.iter().any(|net| net.iface_id == id)
+                })?;
+                resources.set_mmds_version(mmds_config.version, &instance_info.id)?;
+                Some(MmdsConfig {
+                    ipv4_address: Some(ipv4_addr),
+                    ..mmds_config
+                })
+            }
+            None => None,
+        };
+
+        Ok(VmmConfig {
+            balloon_device,
+            block_devices,
+            mmds_config,
+            net_devices,
+            vsock_device,
+            entropy_device,
+            watchdog_device,
+            ..VmmConfig::from(&resources)
+        })
+    }
+
+    /// Replaces the configuration with the one described by `config_json`, going through the same
+    /// steps as `from_json`. The configuration is only replaced if it is valid as a whole,
+    /// otherwise the previous one is kept. The contents of the MMDS data store are kept as well.
+    ///
+    /// The logger and metrics configurations are rejected, since applying them could not be
+    /// rolled back.
+    pub fn apply_json(
+        &mut self,
+        config_json: &str,
+        instance_info: &InstanceInfo,
+    ) -> Result<(), ResourcesError> {
+        let vmm_config = serde_json::from_str::<VmmConfig>(config_json)?;
+        if vmm_config.logger.is_some() || vmm_config.metrics.is_some() {
+            return Err(ResourcesError::LoggerOrMetricsConfig);
+        }
+        Self::validate_json(config_json, instance_info, self.mmds_size_limit)?;
+
+        // The new configuration may use the tap devices and the vsock socket of the current one,
+        // so the current net and vsock devices are released before building the new ones, and
+        // rebuilt if that fails.
+        let net_configs = self.net_builder.configs();
+        let vsock_config = self.vsock.config();
+        let mmds_config = self.mmds_config();
+        self.vsock.remove()?;
+        self.net_builder = NetBuilder::new();
+
+        let mut resources =
+            match Self::from_vmm_config(vmm_config, instance_info, self.mmds_size_limit, None) {
+                Ok(resources) => resources,
+                Err(err) => {
+                    if let Err(restore_err) =
+                        self.rebuild_net_and_vsock(net_configs, vsock_config, mmds_config)
+                    {
+                        warn!(
+                            "Could not rebuild the previous net and vsock devices: {restore_err}"
+                        );
+                    }
+                    return Err(err);
+                }
+            };
+        if let Some(mmds) = self.mmds.as_ref() {
+            let data = mmds.lock().expect("Poisoned lock").data_store_value();
+            if !data.is_null() {
+                resources.locked_mmds_or_default().put_data(data)?;
+            }
+        }
+        resources.boot_timer = self.boot_timer;
+
+        *self = resources;
+        Ok(())
+    }
+
+    /// Rebuilds net and vsock devices released by `apply_json`.
+    fn rebuild_net_and_vsock(
+        &mut self,
+        net_configs: Vec<NetworkInterfaceConfig>,
+        vsock_config: Option<VsockDeviceConfig>,
+        mmds_config: Option<MmdsConfig>,
+    ) -> Result<(), ResourcesError> {
+        for net_config in net_configs {
+            self.build_net_device(net_config)?;
+        }
+        if let Some(mmds_config) = mmds_config {
+            self.set_mmds_network_stack_config(&mmds_config)?;
+        }
+        if let Some(vsock_config) = vsock_config {
+            self.set_vsock_device(vsock_config)?;
+        }
+        Ok(())
+    }
+
+    /// Creates Vmm resources configured as described by `vmm_config`, except for the logger and
+    /// metrics. The vsock socket is removed if the configuration fails after binding it.
+    fn from_vmm_config(
+        vmm_config: VmmConfig,
+        instance_info: &InstanceInfo,
+        mmds_size_limit: usize,
+        metadata_json: Option<&str>,
+    ) -> Result<Self, ResourcesError> {
         let mut resources: Self = Self {
             mmds_size_limit,
             ..Default::default()
         };
+        match resources.configure(vmm_config, instance_info, metadata_json) {
+            Ok(()) => Ok(resources),
+            Err(err) => {
+                // Dropping the vsock device does not remove its socket.
+                if let Err(remove_err) = resources.vsock.remove() {
+                    warn!("Could not remove the vsock socket: {remove_err}");
+                }
+                Err(err)
+            }
+        }
+    }
+
+    /// Configures the resources as described by `vmm_config`, except for the logger and metrics.
+    fn configure(
+        &mut self,
+        vmm_config: VmmConfig,
+        instance_info: &InstanceInfo,
+        metadata_json: Option<&str>,
+    ) -> Result<(), ResourcesError> {
         if let Some(machine_config) = vmm_config.machine_config {
             let machine_config = MachineConfigUpdate::from(machine_config);
-            resources.update_vm_config(&machine_config)?;
+            self.update_vm_config(&machine_config)?;
         }
 
         if let Some(cpu_config) = vmm_config.cpu_config {
             let cpu_config_json =
                 std::fs::read_to_string(cpu_config).map_err(ResourcesError::File)?;
             let cpu_template = CustomCpuTemplate::try_from(cpu_config_json.as_str())?;
-            resources.set_custom_cpu_template(cpu_template);
+            self.set_custom_cpu_template(cpu_template);
         }
 
-        resources.build_boot_source(vmm_config.boot_source)?;
+        self.build_boot_source(vmm_config.boot_source)?;
 
         for drive_config in vmm_config.block_devices.into_iter() {
-            resources.set_block_device(drive_config)?;
+            self.set_block_device(drive_config)?;
         }
 
         for net_config in vmm_config.net_devices.into_iter() {
-            resources.build_net_device(net_config)?;
+            self.build_net_device(net_config)?;
         }
 
         if let Some(vsock_config) = vmm_config.vsock_device {
-            resources.set_vsock_device(vsock_config)?;
+            self.set_vsock_device(vsock_config)?;
         }
 
         if let Some(balloon_config) = vmm_config.balloon_device {
-            resources.set_balloon_device(balloon_config)?;
+            self.set_balloon_device(balloon_config)?;
         }
 
         // Init the data store from file, if present.
         if let Some(data) = metadata_json {
-            resources.locked_mmds_or_default().put_data(
+            self.locked_mmds_or_default().put_data(
                 serde_json::from_str(data).expect("MMDS error: metadata provided not valid json"),
             )?;
             info!("Successfully added metadata to mmds from file");
         }
 
         if let Some(mmds_config) = vmm_config.mmds_config {
-            resources.set_mmds_config(mmds_config, &instance_info.id)?;
+            self.set_mmds_config(mmds_config, &instance_info.id)?;
         }
 
         if let Some(entropy_device_config) = vmm_config.entropy_device {
-            resources.build_entropy_device(entropy_device_config)?;
+            self.build_entropy_device(entropy_device_config)?;
+        }
+
+        if let Some(watchdog_device_config) = vmm_config.watchdog_device {
+            self.build_watchdog_device(watchdog_device_config)?;
         }
 
-        Ok(resources)
+        Ok(())
     }
 
     /// If not initialised, create the mmds data store with the default config.
@@ -201,6 +379,36 @@ impl VmResources {
         mmds.lock().expect("Poisoned lock")
     }
 
+    /// Identifiers of the configured devices that are saved in snapshots, in the format of
+    /// [`DeviceStates::device_ids`](crate::device_manager::persist::DeviceStates::device_ids).
+    pub fn snapshot_device_ids(&self) -> BTreeSet<String> {
+        let mut ids = BTreeSet::new();
+        ids.extend(
+            self.block
+                .devices
+                .iter()
+                .map(|block| format!("block/{}", block.lock().expect("Poisoned lock").id())),
+        );
+        ids.extend(
+            self.net_builder
+                .iter()
+                .map(|net| format!("net/{}", net.lock().expect("Poisoned lock").id())),
+        );
+        if self.vsock.get().is_some() {
+            ids.insert(String::from("vsock"));
+        }
+        if self.balloon.get().is_some() {
+            ids.insert(String::from("balloon"));
+        }
+        if self.entropy.get().is_some() {
+            ids.insert(String::from("entropy"));
+        }
+        if self.watchdog.get().is_some() {
+            ids.insert(String::from("watchdog"));
+        }
+        ids
+    }
+
     /// Updates the resources from a restored device (used for configuring resources when
     /// restoring from a snapshot).
     pub fn update_from_restored_device(
@@ -230,6 +438,9 @@ impl VmResources {
             SharedDeviceType::Entropy(entropy) => {
                 self.entropy.set_device(entropy);
             }
+            SharedDeviceType::Watchdog(watchdog) => {
+                self.watchdog.set_device(watchdog);
+            }
         }
 
         Ok(())
@@ -296,6 +507,7 @@ impl VmResources {
                 version: mmds.lock().expect("Poisoned lock").version(),
                 network_interfaces: vec![],
                 ipv4_address: None,
+                rate_limiter: None,
             };
 
             for net_dev in net_devs_with_mmds {
@@ -306,6 +518,11 @@ impl VmResources {
                     // Safe to unwrap the mmds_ns as the filter() explicitly checks for
                     // its existence.
                     inner_mmds_config.ipv4_address = Some(net.mmds_ns().unwrap().ipv4_addr());
+                    inner_mmds_config.rate_limiter = net
+                        .mmds_ns()
+                        .unwrap()
+                        .rate_limiter()
+                        .map(TokenBucketConfig::from);
                 }
             }
 
@@ -384,6 +601,14 @@ impl VmResources {
         self.entropy.insert(body)
     }
 
+    /// Builds a watchdog device to be attached when the VM starts.
+    pub fn build_watchdog_device(
+        &mut self,
+        body: WatchdogDeviceConfig,
+    ) -> Result<(), WatchdogDeviceError> {
+        self.watchdog.insert(body)
+    }
+
     /// Setter for mmds config.
     pub fn set_mmds_config(
         &mut self,
@@ -417,28 +642,12 @@ impl VmResources {
         &mut self,
         config: &MmdsConfig,
     ) -> Result<(), MmdsConfigError> {
-        // Check IPv4 address validity.
-        let ipv4_addr = match config.ipv4_addr() {
-            Some(ipv4_addr) if is_link_local_valid(ipv4_addr) => Ok(ipv4_addr),
-            None => Ok(MmdsNetworkStack::default_ipv4_addr()),
-            _ => Err(MmdsConfigError::InvalidIpv4Addr),
-        }?;
-
-        let network_interfaces = config.network_interfaces();
-        // Ensure that at least one network ID is specified.
-        if network_interfaces.is_empty() {
-            return Err(MmdsConfigError::EmptyNetworkIfaceList);
-        }
-
-        // Ensure all interface IDs specified correspond to existing net devices.
-        if !network_interfaces.iter().all(|id| {
+        let ipv4_addr = Self::check_mmds_network_stack_config(config, |id| {
             self.net_builder
                 .iter()
-                .map(|device| device.lock().expect("Poisoned lock").id().clone())
-                .any(|x| &x == id)
-        }) {
-            return Err(MmdsConfigError::InvalidNetworkInterfaceId);
-        }
+                .any(|device| device.lock().expect("Poisoned lock").id() == id)
+        })?;
+        let network_interfaces = config.network_interfaces();
 
         // Safe to unwrap because we've just made sure that it's initialised.
         let mmds = self.mmds_or_default().clone();
@@ -449,7 +658,11 @@ impl VmResources {
         for net_device in self.net_builder.iter_mut() {
             let mut net_device_lock = net_device.lock().expect("Poisoned lock");
             if network_interfaces.contains(net_device_lock.id()) {
-                net_device_lock.configure_mmds_network_stack(ipv4_addr, mmds.clone());
+                net_device_lock.configure_mmds_network_stack(
+                    ipv4_addr,
+                    mmds.clone(),
+                    config.rate_limiter(),
+                );
             } else {
                 net_device_lock.disable_mmds_network_stack();
             }
@@ -458,11 +671,49 @@ impl VmResources {
         Ok(())
     }
 
+    /// Checks the MMDS network stack configuration, where `iface_exists` tells whether a network
+    /// interface ID corresponds to a net device, and returns the IPv4 address of the MMDS.
+    fn check_mmds_network_stack_config(
+        config: &MmdsConfig,
+        iface_exists: impl Fn(&str) -> bool,
+    ) -> Result<Ipv4Addr, MmdsConfigError> {
+        // Check IPv4 address validity.
+        let ipv4_addr = match config.ipv4_addr() {
+            Some(ipv4_addr) if is_link_local_valid(ipv4_addr) => Ok(ipv4_addr),
+            None => Ok(MmdsNetworkStack::default_ipv4_addr()),
+            _ => Err(MmdsConfigError::InvalidIpv4Addr),
+        }?;
+
+        // Ensure that at least one network ID is specified.
+        if config.network_interfaces.is_empty() {
+            return Err(MmdsConfigError::EmptyNetworkIfaceList);
+        }
+
+        // Ensure all interface IDs specified correspond to existing net devices.
+        if !config.network_interfaces.iter().all(|id| iface_exists(id)) {
+            return Err(MmdsConfigError::InvalidNetworkInterfaceId);
+        }
+
+        Ok(ipv4_addr)
+    }
+
     /// Allocates guest memory in a configuration most appropriate for these [`VmResources`].
     ///
-    /// If vhost-user-blk devices are in use, allocates memfd-backed shared memory, otherwise
-    /// prefers anonymous memory for performance reasons.
+    /// If a memory backing file is configured, maps it as shared memory. Otherwise, if
+    /// vhost-user-blk devices are in use, allocates memfd-backed shared memory, and else prefers
+    /// anonymous memory for performance reasons.
+    ///
+    /// If the configured memory size cannot be allocated, falls back to the largest size that can
+    /// be, as long as it is not below the configured minimum memory size. Otherwise the returned
+    /// error reports the largest size that could be allocated.
     pub fn allocate_guest_memory(&self) -> Result<GuestMemoryMmap, MemoryError> {
+        if let Some(policy) = self.vm_config.host_memory_check {
+            let available_mib = std::fs::read_to_string("/proc/meminfo")
+                .ok()
+                .and_then(|meminfo| host_available_memory_mib(&meminfo, self.vm_config.huge_pages));
+            check_host_memory(policy, self.vm_config.mem_size_mib, available_mib)?;
+        }
+
         let vhost_user_device_used = self
             .block
             .devices
@@ -478,21 +729,194 @@ impl VmResources {
         // because that would require running a backend process. If in the future we converge to
         // a single way of backing guest memory for vhost-user and non-vhost-user cases,
         // that would not be worth the effort.
-        if vhost_user_device_used {
-            GuestMemoryMmap::memfd_backed(
-                self.vm_config.mem_size_mib,
-                self.vm_config.track_dirty_pages,
-                self.vm_config.huge_pages,
-            )
+        let backing_file = self
+            .vm_config
+            .mem_backing_file
+            .as_deref()
+            .map(|path| self.open_mem_backing_file(path))
+            .transpose()?;
+        let allocate = |mem_size_mib| {
+            let regions = memory_regions(mem_size_mib, self.vm_config.memslot_size_mib);
+            if let Some(file) = &backing_file {
+                GuestMemoryMmap::file_backed(&regions, file, self.vm_config.track_dirty_pages)
+            } else if vhost_user_device_used {
+                GuestMemoryMmap::memfd_backed(
+                    &regions,
+                    self.vm_config.track_dirty_pages,
+                    self.vm_config.huge_pages,
+                )
+            } else {
+                GuestMemoryMmap::from_raw_regions(
+                    &regions,
+                    self.vm_config.track_dirty_pages,
+                    self.vm_config.huge_pages,
+                )
+            }
+        };
+
+        self.allocate_guest_memory_with(allocate)
+    }
+
+    // Allocates the guest memory with `allocate`, which maps the given size in MiB, falling back
+    // to a smaller size if allowed by the configuration.
+    fn allocate_guest_memory_with(
+        &self,
+        allocate: impl FnMut(usize) -> Result<GuestMemoryMmap, MemoryError>,
+    ) -> Result<GuestMemoryMmap, MemoryError> {
+        let mem_size_mib = self.vm_config.mem_size_mib;
+        let (guest_memory, allocated_mib) = allocate_with_fallback(
+            mem_size_mib,
+            self.vm_config.min_mem_size_mib.unwrap_or(mem_size_mib),
+            self.vm_config.huge_pages.mem_size_alignment_mib(),
+            allocate,
+        )?;
+        if allocated_mib != mem_size_mib {
+            warn!(
+                "Could not allocate {} MiB of guest memory, falling back to {} MiB",
+                mem_size_mib, allocated_mib
+            );
+        }
+
+        if let Some(advice) = self.vm_config.thp_advice {
+            guest_memory.advise_thp(advice)?;
+        }
+
+        #[cfg(feature = "mem-poison")]
+        if let Some(pattern) = self.vm_config.mem_poison {
+            warn!(
+                "Filling guest memory with {:#04x}. Memory poisoning is insecure and only meant \
+                 for debugging.",
+                pattern
+            );
+            guest_memory.poison(pattern);
+        }
+
+        Ok(guest_memory)
+    }
+
+    /// Opens the memory backing file, creating it if needed. A file that already holds memory
+    /// must be of the configured memory size.
+    fn open_mem_backing_file(&self, path: &Path) -> Result<File, MemoryError> {
+        let file = OpenOptions::new()
+            .read(true)
+            .write(true)
+            .create(true)
+            .truncate(false)
+            .open(path)
+            .map_err(MemoryError::FileError)?;
+
+        let file_size = file.metadata().map_err(MemoryError::FileError)?.len();
+        let mem_size = (self.vm_config.mem_size_mib as u64) << 20;
+        if file_size != 0 && file_size != mem_size {
+            return Err(MemoryError::BackingFileSize(file_size, mem_size));
+        }
+        Ok(file)
+    }
+}
+
+/// Memory used by Firecracker besides the guest memory, in MiB.
+const VMM_MEMORY_OVERHEAD_MIB: usize = 5;
+
+/// Returns the host memory the guest memory can be allocated from, in MiB, as reported by
+/// `meminfo`, the contents of `/proc/meminfo`: the free huge pages if the guest memory is backed
+/// by hugetlbfs, else the available memory. `None` if it cannot be determined, which is also the
+/// case of huge pages of another size than the default one, whose pool is not reported.
+fn host_available_memory_mib(meminfo: &str, huge_pages: HugePageConfig) -> Option<usize> {
+    let field = |name: &str| {
+        meminfo.lines().find_map(|line| {
+            let value = line.strip_prefix(name)?.strip_prefix(':')?;
+            value
+                .trim()
+                .trim_end_matches("kB")
+                .trim_end()
+                .parse::<usize>()
+                .ok()
+        })
+    };
+
+    if huge_pages.is_hugetlbfs() {
+        let page_size_kib = field("Hugepagesize")?;
+        // `HugePageConfig::page_size_kib` is in bytes.
+        if page_size_kib.checked_mul(1024) != Some(huge_pages.page_size_kib()) {
+            return None;
+        }
+        field("HugePages_Free")?
+            .checked_mul(page_size_kib)
+            .map(|free_kib| free_kib / 1024)
+    } else {
+        field("MemAvailable").map(|available_kib| available_kib / 1024)
+    }
+}
+
+/// Applies `policy` if `available_mib` MiB of host memory do not fit `mem_size_mib` MiB of guest
+/// memory and the memory overhead of Firecracker.
+fn check_host_memory(
+    policy: HostMemoryPolicy,
+    mem_size_mib: usize,
+    available_mib: Option<usize>,
+) -> Result<(), MemoryError> {
+    let Some(available_mib) = available_mib else {
+        warn!("Cannot determine the host memory available to the guest memory, skipping the check");
+        return Ok(());
+    };
+
+    let required_mib = mem_size_mib.saturating_add(VMM_MEMORY_OVERHEAD_MIB);
+    if available_mib >= required_mib {
+        return Ok(());
+    }
+
+    let err = MemoryError::InsufficientHostMemory {
+        required_mib,
+        available_mib,
+    };
+    match policy {
+        HostMemoryPolicy::Warn => {
+            warn!("{}", err);
+            Ok(())
+        }
+        HostMemoryPolicy::Fail => Err(err),
+    }
+}
+
+/// Allocates `mem_size_mib` MiB of memory with `allocate`. If that fails, looks for the largest
+/// size, multiple of `alignment_mib`, that can be allocated. That size is allocated instead if it
+/// is at least `min_mem_size_mib`, otherwise it is reported in the returned error. Returns the
+/// allocated memory and its size in MiB.
+fn allocate_with_fallback<T>(
+    mem_size_mib: usize,
+    min_mem_size_mib: usize,
+    alignment_mib: usize,
+    mut allocate: impl FnMut(usize) -> Result<T, MemoryError>,
+) -> Result<(T, usize), MemoryError> {
+    let err = match allocate(mem_size_mib) {
+        Ok(memory) => return Ok((memory, mem_size_mib)),
+        Err(err) => err,
+    };
+
+    // Binary search, in units of `alignment_mib`, the largest size that can be allocated. Each
+    // probe is released right away, so that it does not hold back memory from the next one.
+    let (mut low, mut high) = (0, mem_size_mib / alignment_mib);
+    while high - low > 1 {
+        let mid = low + (high - low) / 2;
+        if allocate(mid * alignment_mib).is_ok() {
+            low = mid;
         } else {
-            let regions = crate::arch::arch_memory_regions(self.vm_config.mem_size_mib << 20);
-            GuestMemoryMmap::from_raw_regions(
-                &regions,
-                self.vm_config.track_dirty_pages,
-                self.vm_config.huge_pages,
-            )
+            high = mid;
         }
     }
+    let allocatable_mib = low * alignment_mib;
+
+    if allocatable_mib >= min_mem_size_mib {
+        if let Ok(memory) = allocate(allocatable_mib) {
+            return Ok((memory, allocatable_mib));
+        }
+    }
+
+    Err(MemoryError::Allocation {
+        requested_mib: mem_size_mib,
+        allocatable_mib,
+        source: Box::new(err),
+    })
 }
 
 impl From<&VmResources> for VmmConfig {
@@ -509,18 +933,21 @@ impl From<&VmResources> for VmmConfig {
             net_devices: resources.net_builder.configs(),
             vsock_device: resources.vsock.config(),
             entropy_device: resources.entropy.config(),
+            watchdog_device: resources.watchdog.config(),
         }
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::collections::BTreeMap;
     use std::fs::File;
     use std::io::Write;
     use std::os::linux::fs::MetadataExt;
     use std::str::FromStr;
 
     use serde_json::{Map, Value};
+    use vmm_sys_util::tempdir::TempDir;
     use vmm_sys_util::tempfile::TempFile;
 
     use super::*;
@@ -535,7 +962,9 @@ mod tests {
         BootConfig, BootSource, BootSourceConfig, DEFAULT_KERNEL_CMDLINE,
     };
     use crate::vmm_config::drive::{BlockBuilder, BlockDeviceConfig};
-    use crate::vmm_config::machine_config::{HugePageConfig, MachineConfig, VmConfigError};
+    use crate::vmm_config::machine_config::{
+        AvxFeature, DeviceMemoryConfig, HugePageConfig, MachineConfig, MemoryLayout, VmConfigError,
+    };
     use crate::vmm_config::net::{NetBuilder, NetworkInterfaceConfig};
     use crate::vmm_config::vsock::tests::default_config;
     use crate::vmm_config::RateLimiterConfig;
@@ -555,6 +984,7 @@ mod tests {
             guest_mac: Some(MacAddr::from_str("01:23:45:67:89:0a").unwrap()),
             rx_rate_limiter: Some(RateLimiterConfig::default()),
             tx_rate_limiter: Some(RateLimiterConfig::default()),
+            mtu: None,
         }
     }
 
@@ -578,6 +1008,8 @@ mod tests {
                 path_on_host: Some(tmp_file.as_path().to_str().unwrap().to_string()),
                 rate_limiter: Some(RateLimiterConfig::default()),
                 file_engine_type: None,
+                direct: None,
+                discard: None,
 
                 socket: None,
             },
@@ -602,6 +1034,7 @@ mod tests {
                 cmdline: kernel_cmdline,
                 kernel_file: File::open(tmp_file.as_path()).unwrap(),
                 initrd_file: Some(File::open(tmp_file.as_path()).unwrap()),
+                rng_seed: false,
             }),
         }
     }
@@ -618,6 +1051,7 @@ mod tests {
             boot_timer: false,
             mmds_size_limit: HTTP_MAX_PAYLOAD_SIZE,
             entropy: Default::default(),
+            watchdog: Default::default(),
         }
     }
 
@@ -1056,20 +1490,32 @@ mod tests {
     }
 
     #[test]
-    fn test_cpu_config_from_invalid_json() {
-        // Invalid cpu config file path.
-        // `VmResources::from_json()` should fail with `Error::File`.
+    fn test_apply_json() {
         let kernel_file = TempFile::new().unwrap();
         let rootfs_file = TempFile::new().unwrap();
-        let default_instance_info = InstanceInfo::default();
+        let instance_info = InstanceInfo::default();
+        let mut vm_resources = VmResources {
+            mmds_size_limit: HTTP_MAX_PAYLOAD_SIZE,
+            boot_timer: true,
+            ..Default::default()
+        };
+        vm_resources
+            .update_vm_config(&MachineConfigUpdate {
+                vcpu_count: Some(4),
+                ..Default::default()
+            })
+            .unwrap();
+        vm_resources
+            .locked_mmds_or_default()
+            .put_data(serde_json::json!({"key": "value"}))
+            .unwrap();
 
-        let json = format!(
-            r#"{{
+        let config_json = |rootfs_path: &str| {
+            format!(
+                r#"{{
                     "boot-source": {{
-                        "kernel_image_path": "{}",
-                        "boot_args": "console=ttyS0 reboot=k panic=1 pci=off"
+                        "kernel_image_path": "{}"
                     }},
-                    "cpu-config": "/invalid/path",
                     "drives": [
                         {{
                             "drive_id": "rootfs",
@@ -1077,67 +1523,302 @@ mod tests {
                             "is_root_device": true,
                             "is_read_only": false
                         }}
-                    ]
-            }}"#,
-            kernel_file.as_path().to_str().unwrap(),
-            rootfs_file.as_path().to_str().unwrap(),
+                    ],
+                    "machine-config": {{
+                        "vcpu_count": 2,
+                        "mem_size_mib": 256
+                    }}
+                }}"#,
+                kernel_file.as_path().to_str().unwrap(),
+                rootfs_path
+            )
+        };
+
+        // The drive is invalid, so the boot source and machine config are not applied either.
+        let error = vm_resources
+            .apply_json(&config_json("/invalid/path"), &instance_info)
+            .unwrap_err();
+        assert!(
+            matches!(error, ResourcesError::BlockDevice(_)),
+            "{:?}",
+            error
+        );
+        assert_eq!(vm_resources.vm_config.vcpu_count, 4);
+        assert!(vm_resources.boot_source.builder.is_none());
+        assert!(vm_resources.block.devices.is_empty());
+
+        // The logger configuration cannot be rolled back.
+        let error = vm_resources
+            .apply_json(
+                r#"{"boot-source": {"kernel_image_path": "/invalid/path"}, "logger": {}}"#,
+                &instance_info,
+            )
+            .unwrap_err();
+        assert!(
+            matches!(error, ResourcesError::LoggerOrMetricsConfig),
+            "{:?}",
+            error
         );
 
-        let error = VmResources::from_json(
-            json.as_str(),
-            &default_instance_info,
-            HTTP_MAX_PAYLOAD_SIZE,
-            None,
-        )
-        .unwrap_err();
-        assert!(matches!(error, ResourcesError::File(_)), "{:?}", error);
+        vm_resources
+            .apply_json(
+                &config_json(rootfs_file.as_path().to_str().unwrap()),
+                &instance_info,
+            )
+            .unwrap();
+        assert_eq!(vm_resources.vm_config.vcpu_count, 2);
+        assert_eq!(vm_resources.vm_config.mem_size_mib, 256);
+        assert!(vm_resources.boot_source.builder.is_some());
+        assert_eq!(vm_resources.block.devices.len(), 1);
+        // The settings which are not part of the configuration are kept.
+        assert!(vm_resources.boot_timer);
+        assert_eq!(
+            vm_resources.locked_mmds_or_default().data_store_value(),
+            serde_json::json!({"key": "value"})
+        );
     }
 
     #[test]
-    fn test_cpu_config_from_valid_json() {
-        // Valid cpu config file path.
-        // `VmResources::from_json()` should succeed and it should have a custom CPU template.
+    fn test_apply_json_twice() {
         let kernel_file = TempFile::new().unwrap();
-        let rootfs_file = TempFile::new().unwrap();
-        let default_instance_info = InstanceInfo::default();
-        let cpu_config_file = TempFile::new().unwrap();
-        cpu_config_file
-            .as_file()
-            .write_all("{}".as_bytes())
-            .unwrap();
+        let vsock_dir = TempDir::new().unwrap();
+        let uds_path = vsock_dir.as_path().join("vsock.sock");
+        let instance_info = InstanceInfo::default();
+        let mut vm_resources = VmResources {
+            mmds_size_limit: HTTP_MAX_PAYLOAD_SIZE,
+            ..Default::default()
+        };
 
-        let json = format!(
-            r#"{{
+        let config_json = |net_devices: &str| {
+            format!(
+                r#"{{
                     "boot-source": {{
-                        "kernel_image_path": "{}",
-                        "boot_args": "console=ttyS0 reboot=k panic=1 pci=off"
+                        "kernel_image_path": "{}"
                     }},
-                    "cpu-config": "{}",
-                    "drives": [
-                        {{
-                            "drive_id": "rootfs",
-                            "path_on_host": "{}",
-                            "is_root_device": true,
-                            "is_read_only": false
-                        }}
-                    ]
-            }}"#,
-            kernel_file.as_path().to_str().unwrap(),
-            cpu_config_file.as_path().to_str().unwrap(),
-            rootfs_file.as_path().to_str().unwrap(),
-        );
+                    "network-interfaces": [{}],
+                    "vsock": {{
+                        "guest_cid": 3,
+                        "uds_path": "{}"
+                    }}
+                }}"#,
+                kernel_file.as_path().to_str().unwrap(),
+                net_devices,
+                uds_path.to_str().unwrap()
+            )
+        };
 
-        let vm_resources = VmResources::from_json(
-            json.as_str(),
-            &default_instance_info,
-            HTTP_MAX_PAYLOAD_SIZE,
-            None,
-        )
-        .unwrap();
-        assert_eq!(
-            vm_resources.vm_config.cpu_template,
-            Some(CpuTemplateType::Custom(CustomCpuTemplate::default()))
-        );
+        // The socket of the current vsock device is released for the new one.
+        for _ in 0..2 {
+            vm_resources
+                .apply_json(&config_json(""), &instance_info)
+                .unwrap();
+            assert!(uds_path.exists());
+            assert_eq!(
+                vm_resources.vsock.config().unwrap().uds_path,
+                uds_path.to_str().unwrap()
+            );
+        }
+
+        // The tap device cannot be created, so the current vsock device is rebuilt.
+        let error = vm_resources
+            .apply_json(
+                &config_json(r#"{"iface_id": "eth0", "host_dev_name": "tap/0"}"#),
+                &instance_info,
+            )
+            .unwrap_err();
+        assert!(matches!(error, ResourcesError::NetDevice(_)), "{:?}", error);
+        assert!(uds_path.exists());
+        assert!(vm_resources.vsock.get().is_some());
+        assert_eq!(vm_resources.net_builder.iter().count(), 0);
+
+        // The socket of a replaced vsock device is removed.
+        vm_resources
+            .apply_json(
+                &format!(
+                    r#"{{"boot-source": {{"kernel_image_path": "{}"}}}}"#,
+                    kernel_file.as_path().to_str().unwrap()
+                ),
+                &instance_info,
+            )
+            .unwrap();
+        assert!(!uds_path.exists());
+        assert!(vm_resources.vsock.get().is_none());
+    }
+
+    #[test]
+    fn test_validate_json() {
+        let kernel_file = TempFile::new().unwrap();
+        let rootfs_file = TempFile::new().unwrap();
+        let vsock_dir = TempDir::new().unwrap();
+        let uds_path = vsock_dir.as_path().join("vsock.sock");
+        let instance_info = InstanceInfo::default();
+
+        let config_json = |drives: &str, net_devices: &str, uds_path: &Path| {
+            format!(
+                r#"{{
+                    "boot-source": {{
+                        "kernel_image_path": "{}"
+                    }},
+                    "drives": [{}],
+                    "network-interfaces": [{}],
+                    "vsock": {{
+                        "guest_cid": 3,
+                        "uds_path": "{}"
+                    }},
+                    "mmds-config": {{
+                        "network_interfaces": ["eth0"]
+                    }}
+                }}"#,
+                kernel_file.as_path().to_str().unwrap(),
+                drives,
+                net_devices,
+                uds_path.to_str().unwrap()
+            )
+        };
+        let drive = |drive_id: &str, path: &str, is_root_device: bool| {
+            format!(
+                r#"{{"drive_id": "{}", "path_on_host": "{}", "is_root_device": {}}}"#,
+                drive_id, path, is_root_device
+            )
+        };
+        let rootfs = drive("rootfs", rootfs_file.as_path().to_str().unwrap(), true);
+        let net = |iface_id: &str, host_dev_name: &str| {
+            format!(
+                r#"{{"iface_id": "{}", "host_dev_name": "{}"}}"#,
+                iface_id, host_dev_name
+            )
+        };
+        let eth0 = net("eth0", "tap0");
+
+        let config = VmResources::validate_json(
+            &config_json(&rootfs, &eth0, &uds_path),
+            &instance_info,
+            HTTP_MAX_PAYLOAD_SIZE,
+        )
+        .unwrap();
+        // The devices are not created, so the tap device and the socket are not needed.
+        assert!(!uds_path.exists());
+        let config = serde_json::to_value(config).unwrap();
+        assert_eq!(config["drives"][0]["drive_id"], "rootfs");
+        assert_eq!(config["drives"][0]["is_read_only"], false);
+        assert_eq!(config["network-interfaces"][0]["host_dev_name"], "tap0");
+        assert_eq!(config["vsock"]["uds_path"], uds_path.to_str().unwrap());
+        assert_eq!(config["mmds-config"]["ipv4_address"], "169.254.169.254");
+
+        let invalid_configs = [
+            config_json(&drive("rootfs", "/invalid/path", true), &eth0, &uds_path),
+            config_json(
+                &format!(
+                    "{}, {}",
+                    rootfs,
+                    drive("data", rootfs_file.as_path().to_str().unwrap(), true)
+                ),
+                &eth0,
+                &uds_path,
+            ),
+            config_json(&rootfs, &net("eth0", "tap_name_too_long0"), &uds_path),
+            config_json(
+                &rootfs,
+                &format!("{}, {}", eth0, net("eth1", "tap0")),
+                &uds_path,
+            ),
+            config_json(&rootfs, &net("eth1", "tap1"), &uds_path),
+            config_json(
+                &rootfs,
+                &eth0,
+                &vsock_dir.as_path().join("invalid/vsock.sock"),
+            ),
+        ];
+        for config_json in invalid_configs {
+            VmResources::validate_json(&config_json, &instance_info, HTTP_MAX_PAYLOAD_SIZE)
+                .unwrap_err();
+        }
+        assert!(!uds_path.exists());
+    }
+
+    #[test]
+    fn test_cpu_config_from_invalid_json() {
+        // Invalid cpu config file path.
+        // `VmResources::from_json()` should fail with `Error::File`.
+        let kernel_file = TempFile::new().unwrap();
+        let rootfs_file = TempFile::new().unwrap();
+        let default_instance_info = InstanceInfo::default();
+
+        let json = format!(
+            r#"{{
+                    "boot-source": {{
+                        "kernel_image_path": "{}",
+                        "boot_args": "console=ttyS0 reboot=k panic=1 pci=off"
+                    }},
+                    "cpu-config": "/invalid/path",
+                    "drives": [
+                        {{
+                            "drive_id": "rootfs",
+                            "path_on_host": "{}",
+                            "is_root_device": true,
+                            "is_read_only": false
+                        }}
+                    ]
+            }}"#,
+            kernel_file.as_path().to_str().unwrap(),
+            rootfs_file.as_path().to_str().unwrap(),
+        );
+
+        let error = VmResources::from_json(
+            json.as_str(),
+            &default_instance_info,
+            HTTP_MAX_PAYLOAD_SIZE,
+            None,
+        )
+        .unwrap_err();
+        assert!(matches!(error, ResourcesError::File(_)), "{:?}", error);
+    }
+
+    #[test]
+    fn test_cpu_config_from_valid_json() {
+        // Valid cpu config file path.
+        // `VmResources::from_json()` should succeed and it should have a custom CPU template.
+        let kernel_file = TempFile::new().unwrap();
+        let rootfs_file = TempFile::new().unwrap();
+        let default_instance_info = InstanceInfo::default();
+        let cpu_config_file = TempFile::new().unwrap();
+        cpu_config_file
+            .as_file()
+            .write_all("{}".as_bytes())
+            .unwrap();
+
+        let json = format!(
+            r#"{{
+                    "boot-source": {{
+                        "kernel_image_path": "{}",
+                        "boot_args": "console=ttyS0 reboot=k panic=1 pci=off"
+                    }},
+                    "cpu-config": "{}",
+                    "drives": [
+                        {{
+                            "drive_id": "rootfs",
+                            "path_on_host": "{}",
+                            "is_root_device": true,
+                            "is_read_only": false
+                        }}
+                    ]
+            }}"#,
+            kernel_file.as_path().to_str().unwrap(),
+            cpu_config_file.as_path().to_str().unwrap(),
+            rootfs_file.as_path().to_str().unwrap(),
+        );
+
+        let vm_resources = VmResources::from_json(
+            json.as_str(),
+            &default_instance_info,
+            HTTP_MAX_PAYLOAD_SIZE,
+            None,
+        )
+        .unwrap();
+        assert_eq!(
+            vm_resources.vm_config.cpu_template,
+            Some(CpuTemplateType::Custom(CustomCpuTemplate::default()))
+        );
     }
 
     #[test]
@@ -1181,7 +1862,11 @@ mod tests {
                         "mem_size_mib": 1024,
                         "smt": false
                     }},
-                    "entropy": {{}}
+                    "entropy": {{}},
+                    "watchdog": {{
+                        "timeout_s": 30,
+                        "action": "Reset"
+                    }}
             }}"#,
                 kernel_file.as_path().to_str().unwrap(),
                 rootfs_file.as_path().to_str().unwrap(),
@@ -1318,7 +2003,11 @@ mod tests {
                     }},
                     "mmds-config": {{
                         "network_interfaces": ["netif1", "netif2"],
-                        "ipv4_address": "169.254.1.1"
+                        "ipv4_address": "169.254.1.1",
+                        "rate_limiter": {{
+                            "size": 100,
+                            "refill_time": 1000
+                        }}
                     }}
             }}"#,
                 kernel_file.as_path().to_str().unwrap(),
@@ -1351,6 +2040,34 @@ mod tests {
             cpu_template: Some(StaticCpuTemplate::V1N1),
             track_dirty_pages: Some(false),
             huge_pages: Some(HugePageConfig::None),
+            device_memory: None,
+            vendor_id: None,
+            brand_string: None,
+            cache_levels: None,
+            memslot_size_mib: None,
+            cpu_signature: None,
+            cpu_frequencies: None,
+            cpu_topology: None,
+            min_mem_size_mib: None,
+            host_memory_check: None,
+            kvm_run_retries: None,
+            invariant_tsc: None,
+            thp_advice: None,
+            max_cpuid_leaves: None,
+            vcpu_idle_strategy: None,
+            #[cfg(feature = "mem-poison")]
+            mem_poison: None,
+            cache_line_size: None,
+            mem_backing_file: None,
+            avx_features: None,
+            hypervisor_bit: None,
+            rdrand: None,
+            rdseed: None,
+            monitor_mwait: None,
+            pmu: None,
+            tsc_deadline: None,
+            serial_output_rate_limiter: None,
+            max_desc_chain_len: None,
         };
 
         assert_ne!(
@@ -1408,6 +2125,7 @@ mod tests {
                 amount_mib: 100,
                 deflate_on_oom: false,
                 stats_polling_interval_s: 0,
+                free_page_reporting: false,
             })
             .unwrap();
         aux_vm_config.mem_size_mib = Some(90);
@@ -1437,76 +2155,1398 @@ mod tests {
     }
 
     #[test]
-    fn test_set_balloon_device() {
+    fn test_update_vm_config_device_memory() {
         let mut vm_resources = default_vm_resources();
         vm_resources.balloon = BalloonBuilder::new();
-        let mut new_balloon_cfg = BalloonDeviceConfig {
-            amount_mib: 100,
-            deflate_on_oom: false,
-            stats_polling_interval_s: 0,
-        };
-        assert!(vm_resources.balloon.get().is_none());
         vm_resources
-            .set_balloon_device(new_balloon_cfg.clone())
+            .update_vm_config(&MachineConfigUpdate {
+                mem_size_mib: Some(1024),
+                ..Default::default()
+            })
             .unwrap();
 
-        let actual_balloon_cfg = vm_resources.balloon.get_config().unwrap();
-        assert_eq!(actual_balloon_cfg.amount_mib, new_balloon_cfg.amount_mib);
+        let update_device_memory = |vm_resources: &mut VmResources, guest_address, size_mib| {
+            vm_resources.update_vm_config(&MachineConfigUpdate {
+                device_memory: Some(Some(DeviceMemoryConfig {
+                    guest_address,
+                    size_mib,
+                })),
+                ..Default::default()
+            })
+        };
+
+        // Empty or unaligned windows are rejected.
         assert_eq!(
-            actual_balloon_cfg.deflate_on_oom,
-            new_balloon_cfg.deflate_on_oom
+            update_device_memory(&mut vm_resources, 64 << 30, 0),
+            Err(VmConfigError::InvalidDeviceMemory)
         );
         assert_eq!(
-            actual_balloon_cfg.stats_polling_interval_s,
-            new_balloon_cfg.stats_polling_interval_s
+            update_device_memory(&mut vm_resources, (64 << 30) + 0x1000, 1024),
+            Err(VmConfigError::InvalidDeviceMemory)
+        );
+        assert_eq!(
+            update_device_memory(&mut vm_resources, 64 << 30, 3),
+            Err(VmConfigError::InvalidDeviceMemory)
+        );
+        assert_eq!(
+            update_device_memory(&mut vm_resources, u64::MAX & !((2 << 20) - 1), 4),
+            Err(VmConfigError::InvalidDeviceMemory)
         );
 
-        let mut vm_resources = default_vm_resources();
-        vm_resources.balloon = BalloonBuilder::new();
-        new_balloon_cfg.amount_mib = 256;
+        // Windows overlapping the guest RAM or the MMIO area are rejected.
+        let ram = crate::arch::arch_memory_regions(1024 << 20);
+        assert_eq!(
+            update_device_memory(&mut vm_resources, ram[0].0 .0, 2),
+            Err(VmConfigError::DeviceMemoryOverlapsRam)
+        );
+        assert_eq!(
+            update_device_memory(&mut vm_resources, crate::arch::MMIO_MEM_START, 2),
+            Err(VmConfigError::DeviceMemoryOverlapsMmio)
+        );
+        assert!(vm_resources.vm_config.device_memory.is_none());
+
+        // A window right after the guest RAM is accepted and the RAM regions avoid it.
+        let (last_addr, last_size) = ram.last().unwrap();
+        let ram_end = last_addr.0 + *last_size as u64;
+        update_device_memory(&mut vm_resources, ram_end, 1024).unwrap();
+        let layout = MemoryLayout::from(&vm_resources.vm_config);
+        let window = layout.device_memory.unwrap();
+        assert_eq!(window.guest_address, ram_end);
+        assert_eq!(window.size, 1 << 30);
+        for region in layout.ram.iter() {
+            assert!(
+                region.guest_address + region.size <= window.guest_address
+                    || window.guest_address + window.size <= region.guest_address
+            );
+        }
+
+        // A partial update keeps the window.
         vm_resources
-            .set_balloon_device(new_balloon_cfg)
-            .unwrap_err();
+            .update_vm_config(&MachineConfigUpdate {
+                vcpu_count: Some(2),
+                ..Default::default()
+            })
+            .unwrap();
+        assert!(vm_resources.vm_config.device_memory.is_some());
+
+        // Replacing the whole configuration without a window removes it.
+        let mut machine_config = MachineConfig::from(&vm_resources.vm_config);
+        machine_config.device_memory = None;
+        vm_resources
+            .update_vm_config(&MachineConfigUpdate::from(machine_config))
+            .unwrap();
+        assert!(vm_resources.vm_config.device_memory.is_none());
+
+        // Growing the guest memory into the window is rejected.
+        assert_eq!(
+            vm_resources.update_vm_config(&MachineConfigUpdate {
+                mem_size_mib: Some(2048),
+                ..Default::default()
+            }),
+            Err(VmConfigError::DeviceMemoryOverlapsRam)
+        );
+        assert_eq!(vm_resources.vm_config.mem_size_mib, 1024);
     }
 
     #[test]
-    fn test_negative_restore_balloon_device_with_huge_pages() {
+    fn test_update_vm_config_replace() {
+        use crate::vmm_config::machine_config::{ThpAdvice, VcpuIdleStrategy};
+
         let mut vm_resources = default_vm_resources();
-        vm_resources.balloon = BalloonBuilder::new();
+        let update = MachineConfigUpdate {
+            memslot_size_mib: Some(Some(64)),
+            min_mem_size_mib: Some(Some(64)),
+            kvm_run_retries: Some(Some(3)),
+            thp_advice: Some(Some(ThpAdvice::Hugepage)),
+            vcpu_idle_strategy: Some(Some(VcpuIdleStrategy::Poll)),
+            serial_output_rate_limiter: Some(Some(TokenBucketConfig {
+                size: 1024,
+                one_time_burst: None,
+                refill_time: 100,
+            })),
+            max_desc_chain_len: Some(Some(16)),
+            #[cfg(target_arch = "x86_64")]
+            cache_levels: Some(Some(2)),
+            #[cfg(target_arch = "x86_64")]
+            pmu: Some(Some(false)),
+            ..Default::default()
+        };
+        vm_resources.update_vm_config(&update).unwrap();
+        let configured = vm_resources.vm_config.clone();
+
+        // A partial update keeps the fields it does not set.
         vm_resources
             .update_vm_config(&MachineConfigUpdate {
-                huge_pages: Some(HugePageConfig::Hugetlbfs2M),
+                vcpu_count: Some(2),
                 ..Default::default()
             })
             .unwrap();
-        let err = vm_resources
-            .update_from_restored_device(SharedDeviceType::Balloon(Arc::new(Mutex::new(
-                Balloon::new(128, false, 0, true).unwrap(),
-            ))))
-            .unwrap_err();
-        assert!(
-            matches!(
-                err,
-                ResourcesError::BalloonDevice(BalloonConfigError::HugePages)
-            ),
-            "{:?}",
-            err
+        assert_eq!(
+            vm_resources.vm_config,
+            VmConfig {
+                vcpu_count: 2,
+                ..configured
+            }
+        );
+
+        // Replacing the whole configuration resets the fields it does not set.
+        vm_resources
+            .update_vm_config(&MachineConfigUpdate::from(MachineConfig {
+                vcpu_count: 2,
+                ..Default::default()
+            }))
+            .unwrap();
+        assert_eq!(
+            vm_resources.vm_config,
+            VmConfig {
+                vcpu_count: 2,
+                ..Default::default()
+            }
         );
     }
 
     #[test]
-    fn test_set_entropy_device() {
+    fn test_update_vm_config_vendor_id() {
         let mut vm_resources = default_vm_resources();
-        vm_resources.entropy = EntropyDeviceBuilder::new();
-        let entropy_device_cfg = EntropyDeviceConfig::default();
+        let update_vendor_id = |vm_resources: &mut VmResources, vendor_id: &str| {
+            vm_resources.update_vm_config(&MachineConfigUpdate {
+                vendor_id: Some(Some(vendor_id.to_string())),
+                ..Default::default()
+            })
+        };
 
-        assert!(vm_resources.entropy.get().is_none());
-        vm_resources
-            .build_entropy_device(entropy_device_cfg.clone())
-            .unwrap();
+        #[cfg(target_arch = "aarch64")]
+        assert_eq!(
+            update_vendor_id(&mut vm_resources, "GenuineIntel"),
+            Err(VmConfigError::VendorIdNotSupported)
+        );
 
-        let actual_entropy_cfg = vm_resources.entropy.config().unwrap();
-        assert_eq!(actual_entropy_cfg, entropy_device_cfg);
+        #[cfg(target_arch = "x86_64")]
+        {
+            // The vendor ID must be exactly 12 printable ASCII characters.
+            for vendor_id in [
+                "",
+                "GenuineIntel1",
+                "Genuine",
+                "GenuineInte\n",
+                "GenuineInteł",
+            ] {
+                assert_eq!(
+                    update_vendor_id(&mut vm_resources, vendor_id),
+                    Err(VmConfigError::InvalidVendorId)
+                );
+            }
+            assert!(vm_resources.vm_config.cpuid.vendor_id.is_none());
+
+            update_vendor_id(&mut vm_resources, "AuthenticAMD").unwrap();
+            update_vendor_id(&mut vm_resources, "  Shanghai  ").unwrap();
+            assert_eq!(
+                vm_resources.vm_config.cpuid.vendor_id.as_deref(),
+                Some("  Shanghai  ")
+            );
+
+            // Updates that do not touch the vendor ID keep it.
+            vm_resources
+                .update_vm_config(&MachineConfigUpdate {
+                    vcpu_count: Some(2),
+                    ..Default::default()
+                })
+                .unwrap();
+            assert_eq!(
+                vm_resources.vm_config.cpuid.vendor_id.as_deref(),
+                Some("  Shanghai  ")
+            );
+        }
+    }
+
+    #[test]
+    fn test_update_vm_config_brand_string() {
+        let mut vm_resources = default_vm_resources();
+        let update_brand_string = |vm_resources: &mut VmResources, brand_string: &str| {
+            vm_resources.update_vm_config(&MachineConfigUpdate {
+                brand_string: Some(Some(brand_string.to_string())),
+                ..Default::default()
+            })
+        };
+
+        #[cfg(target_arch = "aarch64")]
+        assert_eq!(
+            update_brand_string(&mut vm_resources, "Fleet CPU"),
+            Err(VmConfigError::BrandStringNotSupported)
+        );
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            // The brand string must be 1 to 47 printable ASCII characters.
+            for brand_string in ["", "a".repeat(48).as_str(), "Fleet\nCPU", "Fleet CPUł"] {
+                assert_eq!(
+                    update_brand_string(&mut vm_resources, brand_string),
+                    Err(VmConfigError::InvalidBrandString)
+                );
+            }
+            assert!(vm_resources.vm_config.cpuid.brand_string.is_none());
+
+            update_brand_string(&mut vm_resources, &"a".repeat(47)).unwrap();
+            update_brand_string(&mut vm_resources, "Fleet Baseline CPU @ 2.40GHz").unwrap();
+            let machine_config = MachineConfig::from(&vm_resources.vm_config);
+            assert_eq!(
+                machine_config.brand_string.as_deref(),
+                Some("Fleet Baseline CPU @ 2.40GHz")
+            );
+        }
+    }
+
+    #[test]
+    fn test_update_vm_config_cache_levels() {
+        let mut vm_resources = default_vm_resources();
+        let update_cache_levels = |vm_resources: &mut VmResources, cache_levels: u8| {
+            vm_resources.update_vm_config(&MachineConfigUpdate {
+                cache_levels: Some(Some(cache_levels)),
+                ..Default::default()
+            })
+        };
+
+        #[cfg(target_arch = "aarch64")]
+        assert_eq!(
+            update_cache_levels(&mut vm_resources, 2),
+            Err(VmConfigError::CacheLevelsNotSupported)
+        );
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            use crate::vmm_config::machine_config::MAX_CACHE_LEVELS;
+
+            for cache_levels in [0, MAX_CACHE_LEVELS + 1, u8::MAX] {
+                assert_eq!(
+                    update_cache_levels(&mut vm_resources, cache_levels),
+                    Err(VmConfigError::InvalidCacheLevels)
+                );
+            }
+            assert!(vm_resources.vm_config.cpuid.cache_levels.is_none());
+
+            update_cache_levels(&mut vm_resources, 1).unwrap();
+            update_cache_levels(&mut vm_resources, MAX_CACHE_LEVELS).unwrap();
+            assert_eq!(
+                vm_resources.vm_config.cpuid.cache_levels,
+                Some(MAX_CACHE_LEVELS)
+            );
+            assert_eq!(
+                MachineConfig::from(&vm_resources.vm_config).cache_levels,
+                Some(MAX_CACHE_LEVELS)
+            );
+        }
+    }
+
+    #[test]
+    fn test_update_vm_config_cpu_signature() {
+        use crate::vmm_config::machine_config::CpuSignature;
+
+        let mut vm_resources = default_vm_resources();
+        let update_cpu_signature = |vm_resources: &mut VmResources, family, model, stepping| {
+            vm_resources.update_vm_config(&MachineConfigUpdate {
+                cpu_signature: Some(Some(CpuSignature {
+                    family,
+                    model,
+                    stepping,
+                })),
+                ..Default::default()
+            })
+        };
+
+        #[cfg(target_arch = "aarch64")]
+        assert_eq!(
+            update_cpu_signature(&mut vm_resources, 0x6, 0x55, 0x7),
+            Err(VmConfigError::CpuSignatureNotSupported)
+        );
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            use crate::vmm_config::machine_config::{MAX_CPU_FAMILY, MAX_CPU_STEPPING};
+
+            for (family, model, stepping) in [
+                (MAX_CPU_FAMILY + 1, 0, 0),
+                (0x6, 0, MAX_CPU_STEPPING + 1),
+                // The extended model is only used with families 0x6 and 0xf onwards.
+                (0x5, 0x10, 0),
+                (0xe, 0xff, 0),
+            ] {
+                assert_eq!(
+                    update_cpu_signature(&mut vm_resources, family, model, stepping),
+                    Err(VmConfigError::InvalidCpuSignature)
+                );
+            }
+            assert!(vm_resources.vm_config.cpuid.cpu_signature.is_none());
+
+            update_cpu_signature(&mut vm_resources, 0x5, 0xf, 0).unwrap();
+            update_cpu_signature(&mut vm_resources, 0x6, 0x55, 0x7).unwrap();
+            update_cpu_signature(&mut vm_resources, MAX_CPU_FAMILY, 0xff, MAX_CPU_STEPPING)
+                .unwrap();
+            let signature = Some(CpuSignature {
+                family: MAX_CPU_FAMILY,
+                model: 0xff,
+                stepping: MAX_CPU_STEPPING,
+            });
+            assert_eq!(vm_resources.vm_config.cpuid.cpu_signature, signature);
+            assert_eq!(
+                MachineConfig::from(&vm_resources.vm_config).cpu_signature,
+                signature
+            );
+        }
+    }
+
+    #[test]
+    fn test_update_vm_config_cpu_frequencies() {
+        use crate::vmm_config::machine_config::CpuFrequencies;
+
+        let mut vm_resources = default_vm_resources();
+        let update_cpu_frequencies =
+            |vm_resources: &mut VmResources, base_mhz, max_mhz, bus_mhz| {
+                vm_resources.update_vm_config(&MachineConfigUpdate {
+                    cpu_frequencies: Some(Some(CpuFrequencies {
+                        base_mhz,
+                        max_mhz,
+                        bus_mhz,
+                    })),
+                    ..Default::default()
+                })
+            };
+
+        #[cfg(target_arch = "aarch64")]
+        assert_eq!(
+            update_cpu_frequencies(&mut vm_resources, 2100, 3500, 100),
+            Err(VmConfigError::CpuFrequenciesNotSupported)
+        );
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            use crate::vmm_config::machine_config::CpuidMaxLeaves;
+
+            for (base_mhz, max_mhz, bus_mhz) in [
+                (0, 0, 0),
+                (2100, 3500, 0),
+                // The base frequency cannot exceed the maximum one.
+                (3600, 3500, 100),
+                // The bus frequency cannot exceed the base one.
+                (2100, 3500, 2200),
+            ] {
+                assert_eq!(
+                    update_cpu_frequencies(&mut vm_resources, base_mhz, max_mhz, bus_mhz),
+                    Err(VmConfigError::InvalidCpuFrequencies)
+                );
+            }
+            assert!(vm_resources.vm_config.cpuid.cpu_frequencies.is_none());
+
+            update_cpu_frequencies(&mut vm_resources, 100, 100, 100).unwrap();
+            update_cpu_frequencies(&mut vm_resources, 2100, 3500, 100).unwrap();
+            let frequencies = Some(CpuFrequencies {
+                base_mhz: 2100,
+                max_mhz: 3500,
+                bus_mhz: 100,
+            });
+            assert_eq!(vm_resources.vm_config.cpuid.cpu_frequencies, frequencies);
+            assert_eq!(
+                MachineConfig::from(&vm_resources.vm_config).cpu_frequencies,
+                frequencies
+            );
+            assert_eq!(
+                vm_resources.vm_config.advertised_cpu_frequencies(),
+                frequencies
+            );
+
+            // The leaf cannot be trimmed by the maximum standard leaf.
+            let max_cpuid_leaves = |standard| {
+                Some(Some(CpuidMaxLeaves {
+                    standard: Some(standard),
+                    extended: None,
+                }))
+            };
+            assert_eq!(
+                vm_resources.update_vm_config(&MachineConfigUpdate {
+                    max_cpuid_leaves: max_cpuid_leaves(0x15),
+                    ..Default::default()
+                }),
+                Err(VmConfigError::CpuFrequenciesAboveMaxCpuidLeaf)
+            );
+            vm_resources
+                .update_vm_config(&MachineConfigUpdate {
+                    max_cpuid_leaves: max_cpuid_leaves(0x16),
+                    ..Default::default()
+                })
+                .unwrap();
+
+            // Without configured frequencies, the host ones are not advertised above the
+            // maximum standard leaf.
+            vm_resources
+                .update_vm_config(&MachineConfigUpdate {
+                    cpu_frequencies: Some(None),
+                    max_cpuid_leaves: max_cpuid_leaves(0x15),
+                    ..Default::default()
+                })
+                .unwrap();
+            assert_eq!(vm_resources.vm_config.advertised_cpu_frequencies(), None);
+        }
+    }
+
+    #[test]
+    fn test_update_vm_config_cpu_topology() {
+        use crate::vmm_config::machine_config::CpuTopology;
+
+        let mut vm_resources = default_vm_resources();
+        let update_cpu_topology =
+            |vm_resources: &mut VmResources, vcpu_count, smt, (sockets, cores, threads)| {
+                vm_resources.update_vm_config(&MachineConfigUpdate {
+                    vcpu_count: Some(vcpu_count),
+                    smt: Some(smt),
+                    cpu_topology: Some(Some(CpuTopology {
+                        sockets,
+                        cores_per_socket: cores,
+                        threads_per_core: threads,
+                    })),
+                    ..Default::default()
+                })
+            };
+
+        #[cfg(target_arch = "aarch64")]
+        assert_eq!(
+            update_cpu_topology(&mut vm_resources, 4, false, (2, 2, 1)),
+            Err(VmConfigError::CpuTopologyNotSupported)
+        );
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            for (vcpu_count, smt, topology) in [
+                // The topology must account for all the vCPUs.
+                (4, false, (1, 2, 1)),
+                (4, false, (2, 4, 1)),
+                (4, false, (0, 4, 1)),
+                // The number of cores per socket must be a power of 2.
+                (6, false, (2, 3, 1)),
+                // The number of threads per core must follow SMT.
+                (4, false, (1, 2, 2)),
+                (4, true, (1, 4, 1)),
+                (8, true, (1, 2, 4)),
+            ] {
+                assert_eq!(
+                    update_cpu_topology(&mut vm_resources, vcpu_count, smt, topology),
+                    Err(VmConfigError::InvalidCpuTopology)
+                );
+            }
+            assert!(vm_resources.vm_config.cpuid.cpu_topology.is_none());
+
+            update_cpu_topology(&mut vm_resources, 1, true, (1, 1, 1)).unwrap();
+            update_cpu_topology(&mut vm_resources, 6, false, (3, 2, 1)).unwrap();
+            update_cpu_topology(&mut vm_resources, 16, true, (2, 4, 2)).unwrap();
+            let topology = Some(CpuTopology {
+                sockets: 2,
+                cores_per_socket: 4,
+                threads_per_core: 2,
+            });
+            assert_eq!(vm_resources.vm_config.cpuid.cpu_topology, topology);
+            assert_eq!(
+                MachineConfig::from(&vm_resources.vm_config).cpu_topology,
+                topology
+            );
+
+            // The configured topology is validated against later vCPU count updates.
+            assert_eq!(
+                vm_resources.update_vm_config(&MachineConfigUpdate {
+                    vcpu_count: Some(8),
+                    ..Default::default()
+                }),
+                Err(VmConfigError::InvalidCpuTopology)
+            );
+        }
+    }
+
+    #[test]
+    fn test_update_vm_config_min_mem_size() {
+        let mut vm_resources = default_vm_resources();
+        let update_min_mem_size =
+            |vm_resources: &mut VmResources, huge_pages, min_mem_size_mib: usize| {
+                vm_resources.update_vm_config(&MachineConfigUpdate {
+                    mem_size_mib: Some(1024),
+                    huge_pages: Some(huge_pages),
+                    min_mem_size_mib: Some(Some(min_mem_size_mib)),
+                    ..Default::default()
+                })
+            };
+
+        for (huge_pages, min_mem_size_mib) in [
+            (HugePageConfig::None, 0),
+            (HugePageConfig::None, 1025),
+            (HugePageConfig::Hugetlbfs2M, 511),
+        ] {
+            assert_eq!(
+                update_min_mem_size(&mut vm_resources, huge_pages, min_mem_size_mib),
+                Err(VmConfigError::InvalidMinMemorySize)
+            );
+        }
+        assert!(vm_resources.vm_config.min_mem_size_mib.is_none());
+
+        update_min_mem_size(&mut vm_resources, HugePageConfig::None, 1).unwrap();
+        update_min_mem_size(&mut vm_resources, HugePageConfig::None, 1024).unwrap();
+        update_min_mem_size(&mut vm_resources, HugePageConfig::Hugetlbfs2M, 512).unwrap();
+        assert_eq!(vm_resources.vm_config.min_mem_size_mib, Some(512));
+        assert_eq!(
+            MachineConfig::from(&vm_resources.vm_config).min_mem_size_mib,
+            Some(512)
+        );
+
+        // The minimum memory size is validated against later memory size updates.
+        assert_eq!(
+            vm_resources.update_vm_config(&MachineConfigUpdate {
+                mem_size_mib: Some(256),
+                ..Default::default()
+            }),
+            Err(VmConfigError::InvalidMinMemorySize)
+        );
+    }
+
+    #[test]
+    fn test_update_vm_config_invariant_tsc() {
+        let mut vm_resources = default_vm_resources();
+        let update = MachineConfigUpdate {
+            invariant_tsc: Some(Some(false)),
+            ..Default::default()
+        };
+
+        #[cfg(target_arch = "aarch64")]
+        assert_eq!(
+            vm_resources.update_vm_config(&update),
+            Err(VmConfigError::InvariantTscNotSupported)
+        );
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            vm_resources.update_vm_config(&update).unwrap();
+            assert_eq!(vm_resources.vm_config.cpuid.invariant_tsc, Some(false));
+
+            // The setting is kept across unrelated updates.
+            vm_resources
+                .update_vm_config(&MachineConfigUpdate {
+                    vcpu_count: Some(2),
+                    ..Default::default()
+                })
+                .unwrap();
+            assert_eq!(
+                MachineConfig::from(&vm_resources.vm_config).invariant_tsc,
+                Some(false)
+            );
+        }
+    }
+
+    #[test]
+    fn test_update_vm_config_max_cpuid_leaves() {
+        use crate::vmm_config::machine_config::CpuidMaxLeaves;
+
+        let mut vm_resources = default_vm_resources();
+        let update = |standard, extended| MachineConfigUpdate {
+            max_cpuid_leaves: Some(Some(CpuidMaxLeaves { standard, extended })),
+            ..Default::default()
+        };
+
+        #[cfg(target_arch = "aarch64")]
+        assert_eq!(
+            vm_resources.update_vm_config(&update(Some(0xd), None)),
+            Err(VmConfigError::MaxCpuidLeavesNotSupported)
+        );
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            for (standard, extended) in [
+                (Some(0x0), None),
+                (Some(0x4000_0000), None),
+                (None, Some(0x8000_0000)),
+                (None, Some(0x8001_0000)),
+                (None, Some(0x1)),
+            ] {
+                assert_eq!(
+                    vm_resources.update_vm_config(&update(standard, extended)),
+                    Err(VmConfigError::InvalidMaxCpuidLeaves)
+                );
+            }
+            assert!(vm_resources.vm_config.cpuid.max_cpuid_leaves.is_none());
+
+            vm_resources
+                .update_vm_config(&update(Some(0x1), Some(0x8000_ffff)))
+                .unwrap();
+            vm_resources
+                .update_vm_config(&update(Some(0xd), Some(0x8000_0008)))
+                .unwrap();
+            assert_eq!(
+                MachineConfig::from(&vm_resources.vm_config).max_cpuid_leaves,
+                Some(CpuidMaxLeaves {
+                    standard: Some(0xd),
+                    extended: Some(0x8000_0008),
+                })
+            );
+        }
+    }
+
+    #[test]
+    fn test_update_vm_config_cache_line_size() {
+        let mut vm_resources = default_vm_resources();
+        let update = |cache_line_size| MachineConfigUpdate {
+            cache_line_size: Some(Some(cache_line_size)),
+            ..Default::default()
+        };
+
+        #[cfg(target_arch = "aarch64")]
+        assert_eq!(
+            vm_resources.update_vm_config(&update(64)),
+            Err(VmConfigError::CacheLineSizeNotSupported)
+        );
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            for cache_line_size in [0, 4, 48, 96, 256] {
+                assert_eq!(
+                    vm_resources.update_vm_config(&update(cache_line_size)),
+                    Err(VmConfigError::InvalidCacheLineSize)
+                );
+            }
+            assert!(vm_resources.vm_config.cpuid.cache_line_size.is_none());
+
+            vm_resources.update_vm_config(&update(8)).unwrap();
+            vm_resources.update_vm_config(&update(128)).unwrap();
+            assert_eq!(
+                MachineConfig::from(&vm_resources.vm_config).cache_line_size,
+                Some(128)
+            );
+        }
+    }
+
+    #[test]
+    fn test_update_vm_config_avx_features() {
+        let mut vm_resources = default_vm_resources();
+        let update = MachineConfigUpdate {
+            avx_features: Some(Some(BTreeMap::from([(AvxFeature::Avx512f, false)]))),
+            ..Default::default()
+        };
+
+        #[cfg(target_arch = "aarch64")]
+        assert_eq!(
+            vm_resources.update_vm_config(&update),
+            Err(VmConfigError::AvxFeaturesNotSupported)
+        );
+
+        // The features are checked against the host when the CPUID is normalized.
+        #[cfg(target_arch = "x86_64")]
+        {
+            vm_resources.update_vm_config(&update).unwrap();
+            vm_resources
+                .update_vm_config(&MachineConfigUpdate::default())
+                .unwrap();
+            assert_eq!(
+                MachineConfig::from(&vm_resources.vm_config).avx_features,
+                update.avx_features.unwrap()
+            );
+        }
+    }
+
+    #[test]
+    fn test_update_vm_config_hypervisor_bit() {
+        let mut vm_resources = default_vm_resources();
+        let update = MachineConfigUpdate {
+            hypervisor_bit: Some(Some(false)),
+            ..Default::default()
+        };
+
+        #[cfg(target_arch = "aarch64")]
+        assert_eq!(
+            vm_resources.update_vm_config(&update),
+            Err(VmConfigError::HypervisorBitNotSupported)
+        );
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            vm_resources.update_vm_config(&update).unwrap();
+            vm_resources
+                .update_vm_config(&MachineConfigUpdate::default())
+                .unwrap();
+            assert_eq!(
+                MachineConfig::from(&vm_resources.vm_config).hypervisor_bit,
+                Some(false)
+            );
+        }
+    }
+
+    #[test]
+    fn test_update_vm_config_rng_instructions() {
+        let mut vm_resources = default_vm_resources();
+        let update = MachineConfigUpdate {
+            rdrand: Some(Some(false)),
+            ..Default::default()
+        };
+
+        #[cfg(target_arch = "aarch64")]
+        assert_eq!(
+            vm_resources.update_vm_config(&update),
+            Err(VmConfigError::RngInstructionsNotSupported)
+        );
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            vm_resources.update_vm_config(&update).unwrap();
+            vm_resources
+                .update_vm_config(&MachineConfigUpdate {
+                    rdseed: Some(Some(false)),
+                    ..Default::default()
+                })
+                .unwrap();
+            let machine_config = MachineConfig::from(&vm_resources.vm_config);
+            assert_eq!(machine_config.rdrand, Some(false));
+            assert_eq!(machine_config.rdseed, Some(false));
+        }
+    }
+
+    #[test]
+    fn test_update_vm_config_monitor_mwait() {
+        let mut vm_resources = default_vm_resources();
+        let update = MachineConfigUpdate {
+            monitor_mwait: Some(Some(false)),
+            ..Default::default()
+        };
+
+        #[cfg(target_arch = "aarch64")]
+        assert_eq!(
+            vm_resources.update_vm_config(&update),
+            Err(VmConfigError::MonitorMwaitNotSupported)
+        );
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            assert_eq!(vm_resources.vm_config.cpuid.monitor_mwait, None);
+            vm_resources.update_vm_config(&update).unwrap();
+            // Updates that do not touch the setting keep it.
+            vm_resources
+                .update_vm_config(&MachineConfigUpdate {
+                    vcpu_count: Some(2),
+                    ..Default::default()
+                })
+                .unwrap();
+            let machine_config = MachineConfig::from(&vm_resources.vm_config);
+            assert_eq!(machine_config.monitor_mwait, Some(false));
+        }
+    }
+
+    #[test]
+    fn test_update_vm_config_pmu() {
+        let mut vm_resources = default_vm_resources();
+        let update = MachineConfigUpdate {
+            pmu: Some(Some(true)),
+            ..Default::default()
+        };
+
+        #[cfg(target_arch = "aarch64")]
+        assert_eq!(
+            vm_resources.update_vm_config(&update),
+            Err(VmConfigError::PmuNotSupported)
+        );
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            assert_eq!(vm_resources.vm_config.cpuid.pmu, None);
+            vm_resources.update_vm_config(&update).unwrap();
+            assert_eq!(vm_resources.vm_config.cpuid.pmu, Some(true));
+            vm_resources
+                .update_vm_config(&MachineConfigUpdate {
+                    pmu: Some(Some(false)),
+                    ..Default::default()
+                })
+                .unwrap();
+            let machine_config = MachineConfig::from(&vm_resources.vm_config);
+            assert_eq!(machine_config.pmu, Some(false));
+        }
+    }
+
+    #[test]
+    fn test_update_vm_config_tsc_deadline() {
+        let mut vm_resources = default_vm_resources();
+        let update = MachineConfigUpdate {
+            tsc_deadline: Some(Some(false)),
+            ..Default::default()
+        };
+
+        #[cfg(target_arch = "aarch64")]
+        assert_eq!(
+            vm_resources.update_vm_config(&update),
+            Err(VmConfigError::TscDeadlineNotSupported)
+        );
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            assert_eq!(vm_resources.vm_config.cpuid.tsc_deadline, None);
+            vm_resources.update_vm_config(&update).unwrap();
+            // Updates that do not touch the setting keep it.
+            vm_resources
+                .update_vm_config(&MachineConfigUpdate {
+                    vcpu_count: Some(2),
+                    ..Default::default()
+                })
+                .unwrap();
+            let machine_config = MachineConfig::from(&vm_resources.vm_config);
+            assert_eq!(machine_config.tsc_deadline, Some(false));
+        }
+    }
+
+    #[test]
+    fn test_update_vm_config_serial_output_rate_limiter() {
+        let mut vm_resources = default_vm_resources();
+        let rate_limiter = TokenBucketConfig {
+            size: 4096,
+            one_time_burst: None,
+            refill_time: 1000,
+        };
+        let update = MachineConfigUpdate {
+            serial_output_rate_limiter: Some(Some(rate_limiter)),
+            ..Default::default()
+        };
+
+        vm_resources.update_vm_config(&update).unwrap();
+        vm_resources
+            .update_vm_config(&MachineConfigUpdate::default())
+            .unwrap();
+        assert_eq!(
+            MachineConfig::from(&vm_resources.vm_config).serial_output_rate_limiter,
+            Some(rate_limiter)
+        );
+    }
+
+    #[test]
+    fn test_update_vm_config_max_desc_chain_len() {
+        let mut vm_resources = default_vm_resources();
+        let update = |max_desc_chain_len| MachineConfigUpdate {
+            max_desc_chain_len: Some(Some(max_desc_chain_len)),
+            ..Default::default()
+        };
+
+        for max_desc_chain_len in [0, 2] {
+            assert_eq!(
+                vm_resources.update_vm_config(&update(max_desc_chain_len)),
+                Err(VmConfigError::InvalidMaxDescChainLen)
+            );
+        }
+        assert!(vm_resources.vm_config.max_desc_chain_len.is_none());
+
+        vm_resources.update_vm_config(&update(16)).unwrap();
+        vm_resources
+            .update_vm_config(&MachineConfigUpdate::default())
+            .unwrap();
+        assert_eq!(
+            MachineConfig::from(&vm_resources.vm_config).max_desc_chain_len,
+            Some(16)
+        );
+    }
+
+    #[test]
+    fn test_update_vm_config_mem_backing_file() {
+        let mut vm_resources = default_vm_resources();
+        let update = |path: &str| MachineConfigUpdate {
+            mem_backing_file: Some(Some(PathBuf::from(path))),
+            ..Default::default()
+        };
+
+        // Only files on tmpfs or hugetlbfs mounts can back guest memory.
+        assert_eq!(
+            vm_resources.update_vm_config(&update("/proc/fc_mem")),
+            Err(VmConfigError::InvalidMemBackingFile)
+        );
+        assert_eq!(
+            vm_resources.update_vm_config(&update("/nonexistent/fc_mem")),
+            Err(VmConfigError::InvalidMemBackingFile)
+        );
+        assert!(vm_resources.vm_config.mem_backing_file.is_none());
+
+        vm_resources
+            .update_vm_config(&update("/dev/shm/fc_mem"))
+            .unwrap();
+        assert_eq!(
+            MachineConfig::from(&vm_resources.vm_config).mem_backing_file,
+            Some(PathBuf::from("/dev/shm/fc_mem"))
+        );
+
+        // A tmpfs file cannot provide huge pages.
+        assert_eq!(
+            vm_resources.update_vm_config(&MachineConfigUpdate {
+                huge_pages: Some(HugePageConfig::Hugetlbfs2M),
+                ..Default::default()
+            }),
+            Err(VmConfigError::InvalidMemBackingFile)
+        );
+    }
+
+    #[test]
+    fn test_update_vm_config_thp_advice() {
+        use crate::vmm_config::machine_config::ThpAdvice;
+
+        let mut vm_resources = default_vm_resources();
+        assert_eq!(
+            vm_resources.update_vm_config(&MachineConfigUpdate {
+                huge_pages: Some(HugePageConfig::Hugetlbfs2M),
+                thp_advice: Some(Some(ThpAdvice::Hugepage)),
+                ..Default::default()
+            }),
+            Err(VmConfigError::ThpAdviceWithHugetlbfs)
+        );
+        assert!(vm_resources.vm_config.thp_advice.is_none());
+
+        vm_resources
+            .update_vm_config(&MachineConfigUpdate {
+                thp_advice: Some(Some(ThpAdvice::NoHugepage)),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            MachineConfig::from(&vm_resources.vm_config).thp_advice,
+            Some(ThpAdvice::NoHugepage)
+        );
+
+        // The advice is validated against later huge pages updates.
+        assert_eq!(
+            vm_resources.update_vm_config(&MachineConfigUpdate {
+                huge_pages: Some(HugePageConfig::Hugetlbfs2M),
+                ..Default::default()
+            }),
+            Err(VmConfigError::ThpAdviceWithHugetlbfs)
+        );
+
+        // The advice is applied to the allocated guest memory.
+        vm_resources.allocate_guest_memory().unwrap();
+    }
+
+    #[test]
+    fn test_update_vm_config_vcpu_idle_strategy() {
+        use crate::vmm_config::machine_config::VcpuIdleStrategy;
+
+        let mut vm_resources = default_vm_resources();
+        assert!(vm_resources.vm_config.vcpu_idle_strategy.is_none());
+
+        vm_resources
+            .update_vm_config(&MachineConfigUpdate {
+                vcpu_idle_strategy: Some(Some(VcpuIdleStrategy::Block)),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            MachineConfig::from(&vm_resources.vm_config).vcpu_idle_strategy,
+            Some(VcpuIdleStrategy::Block)
+        );
+
+        // Updates not touching the strategy preserve it.
+        vm_resources
+            .update_vm_config(&MachineConfigUpdate {
+                vcpu_count: Some(2),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            vm_resources.vm_config.vcpu_idle_strategy,
+            Some(VcpuIdleStrategy::Block)
+        );
+
+        assert_eq!(VcpuIdleStrategy::Block.halt_poll_ns(), 0);
+        assert!(VcpuIdleStrategy::Poll.halt_poll_ns() > 0);
+    }
+
+    #[cfg(feature = "mem-poison")]
+    #[test]
+    fn test_update_vm_config_mem_poison() {
+        use crate::utils::u64_to_usize;
+        use crate::vstate::memory::{Bytes, GuestMemory, GuestMemoryRegion, MemoryRegionAddress};
+
+        let mut vm_resources = default_vm_resources();
+        assert!(vm_resources.vm_config.mem_poison.is_none());
+
+        vm_resources
+            .update_vm_config(&MachineConfigUpdate {
+                mem_size_mib: Some(2),
+                mem_poison: Some(Some(0xa5)),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            MachineConfig::from(&vm_resources.vm_config).mem_poison,
+            Some(0xa5)
+        );
+
+        // The allocated guest memory holds the poison pattern.
+        let guest_memory = vm_resources.allocate_guest_memory().unwrap();
+        for region in guest_memory.iter() {
+            let mut data = vec![0u8; u64_to_usize(region.len())];
+            region
+                .read_slice(&mut data, MemoryRegionAddress(0))
+                .unwrap();
+            assert!(data.iter().all(|&byte| byte == 0xa5));
+        }
+    }
+
+    #[test]
+    fn test_allocate_with_fallback() {
+        // Simulates a host on which at most `available_mib` MiB can be allocated, recording the
+        // sizes of all the allocation attempts.
+        fn allocator(
+            available_mib: usize,
+            attempts: &mut Vec<usize>,
+        ) -> impl FnMut(usize) -> Result<usize, MemoryError> + '_ {
+            move |mem_size_mib| {
+                attempts.push(mem_size_mib);
+                if mem_size_mib <= available_mib {
+                    Ok(mem_size_mib)
+                } else {
+                    Err(MemoryError::MemfdSetLen(std::io::Error::from_raw_os_error(
+                        libc::ENOMEM,
+                    )))
+                }
+            }
+        }
+
+        // The requested size is allocated right away if possible.
+        let mut attempts = vec![];
+        let (memory, allocated_mib) =
+            allocate_with_fallback(1024, 1024, 1, allocator(1024, &mut attempts)).unwrap();
+        assert_eq!((memory, allocated_mib), (1024, 1024));
+        assert_eq!(attempts, vec![1024]);
+
+        // Without a lower minimum memory size, the error reports how much could be allocated.
+        let mut attempts = vec![];
+        let err = allocate_with_fallback(1024, 1024, 1, allocator(700, &mut attempts)).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                MemoryError::Allocation {
+                    requested_mib: 1024,
+                    allocatable_mib: 700,
+                    ..
+                }
+            ),
+            "{err:?}"
+        );
+        assert!(attempts.iter().all(|&size_mib| size_mib <= 1024));
+        assert!(attempts.len() <= 12, "{attempts:?}");
+
+        // The allocation falls back to the largest size that can be allocated, down to the
+        // minimum memory size.
+        let mut attempts = vec![];
+        let (memory, allocated_mib) =
+            allocate_with_fallback(1024, 512, 1, allocator(700, &mut attempts)).unwrap();
+        assert_eq!((memory, allocated_mib), (700, 700));
+        assert_eq!(attempts.last(), Some(&700));
+
+        let mut attempts = vec![];
+        let err = allocate_with_fallback(1024, 701, 1, allocator(700, &mut attempts)).unwrap_err();
+        assert!(matches!(
+            err,
+            MemoryError::Allocation {
+                allocatable_mib: 700,
+                ..
+            }
+        ));
+
+        // Sizes tried out are multiples of the alignment.
+        let mut attempts = vec![];
+        let (_, allocated_mib) =
+            allocate_with_fallback(1024, 2, 2, allocator(701, &mut attempts)).unwrap();
+        assert_eq!(allocated_mib, 700);
+        assert!(attempts.iter().all(|size_mib| size_mib % 2 == 0));
+
+        // Nothing could be allocated.
+        let mut attempts = vec![];
+        let err = allocate_with_fallback(1024, 1, 1, allocator(0, &mut attempts)).unwrap_err();
+        assert!(matches!(
+            err,
+            MemoryError::Allocation {
+                allocatable_mib: 0,
+                ..
+            }
+        ));
+        assert!(!attempts.contains(&0));
+    }
+
+    #[test]
+    fn test_allocate_guest_memory_fallback() {
+        // Simulates a host on which at most 64 MiB of guest memory can be allocated.
+        let allocate = |mem_size_mib| {
+            if mem_size_mib > 64 {
+                return Err(MemoryError::MemfdSetLen(std::io::Error::from_raw_os_error(
+                    libc::ENOMEM,
+                )));
+            }
+            GuestMemoryMmap::from_raw_regions(
+                &memory_regions(mem_size_mib, None),
+                false,
+                HugePageConfig::None,
+            )
+        };
+
+        let mut vm_resources = default_vm_resources();
+        vm_resources.vm_config.mem_size_mib = 128;
+        let err = vm_resources
+            .allocate_guest_memory_with(allocate)
+            .unwrap_err();
+        assert!(
+            matches!(
+                err,
+                MemoryError::Allocation {
+                    requested_mib: 128,
+                    allocatable_mib: 64,
+                    ..
+                }
+            ),
+            "{err:?}"
+        );
+
+        vm_resources.vm_config.min_mem_size_mib = Some(32);
+        let guest_memory = vm_resources.allocate_guest_memory_with(allocate).unwrap();
+        assert_eq!(crate::mem_size_mib(&guest_memory), 64);
+        // The machine configuration keeps the configured size.
+        assert_eq!(vm_resources.vm_config.mem_size_mib, 128);
+    }
+
+    #[test]
+    fn test_host_available_memory_mib() {
+        let meminfo = "MemTotal:       16318412 kB\nMemFree:         1022404 kB\nMemAvailable:    \
+                       8391740 kB\nHugePages_Total:     512\nHugePages_Free:      \
+                       300\nHugepagesize:       2048 kB\n";
+        assert_eq!(
+            host_available_memory_mib(meminfo, HugePageConfig::None),
+            Some(8195)
+        );
+        assert_eq!(
+            host_available_memory_mib(meminfo, HugePageConfig::Hugetlbfs2M),
+            Some(600)
+        );
+
+        // The pool of huge pages of another size is not reported.
+        let meminfo = meminfo.replace("2048 kB", "1048576 kB");
+        assert_eq!(
+            host_available_memory_mib(&meminfo, HugePageConfig::Hugetlbfs2M),
+            None
+        );
+        assert_eq!(host_available_memory_mib("", HugePageConfig::None), None);
+    }
+
+    #[test]
+    fn test_check_host_memory() {
+        // The available memory must fit the Firecracker overhead as well.
+        for policy in [HostMemoryPolicy::Warn, HostMemoryPolicy::Fail] {
+            check_host_memory(policy, 1024, Some(1024 + VMM_MEMORY_OVERHEAD_MIB)).unwrap();
+            check_host_memory(policy, 1024, None).unwrap();
+        }
+
+        check_host_memory(HostMemoryPolicy::Warn, 1024, Some(1024)).unwrap();
+        let err = check_host_memory(HostMemoryPolicy::Fail, 1024, Some(1024)).unwrap_err();
+        assert!(
+            matches!(
+                err,
+                MemoryError::InsufficientHostMemory {
+                    required_mib,
+                    available_mib: 1024,
+                } if required_mib == 1024 + VMM_MEMORY_OVERHEAD_MIB
+            ),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn test_update_vm_config_host_memory_check() {
+        let mut vm_resources = default_vm_resources();
+        assert!(vm_resources.vm_config.host_memory_check.is_none());
+
+        vm_resources
+            .update_vm_config(&MachineConfigUpdate {
+                host_memory_check: Some(Some(HostMemoryPolicy::Fail)),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            MachineConfig::from(&vm_resources.vm_config).host_memory_check,
+            Some(HostMemoryPolicy::Fail)
+        );
+
+        // The default memory size fits the host memory.
+        vm_resources.allocate_guest_memory().unwrap();
+
+        // No host has enough memory for the guest memory, which is not allocated.
+        vm_resources.vm_config.mem_size_mib = 1 << 40;
+        let err = vm_resources.allocate_guest_memory().unwrap_err();
+        assert!(
+            matches!(err, MemoryError::InsufficientHostMemory { .. }),
+            "{err:?}"
+        );
+    }
+
+    #[test]
+    fn test_update_vm_config_memslot_size() {
+        use crate::vmm_config::machine_config::MAX_MEMSLOTS;
+        use crate::vstate::memory::{Address, GuestMemory};
+
+        let mut vm_resources = default_vm_resources();
+        let update_memslot_size =
+            |vm_resources: &mut VmResources, mem_size_mib: usize, memslot_size_mib: usize| {
+                vm_resources.update_vm_config(&MachineConfigUpdate {
+                    mem_size_mib: Some(mem_size_mib),
+                    memslot_size_mib: Some(Some(memslot_size_mib)),
+                    ..Default::default()
+                })
+            };
+
+        assert_eq!(
+            update_memslot_size(&mut vm_resources, 128, 0),
+            Err(VmConfigError::InvalidMemslotSize)
+        );
+        assert_eq!(
+            update_memslot_size(&mut vm_resources, 1024, 1),
+            Err(VmConfigError::TooManyMemslots(1024))
+        );
+        assert_eq!(
+            update_memslot_size(&mut vm_resources, MAX_MEMSLOTS + 1, 1),
+            Err(VmConfigError::TooManyMemslots(MAX_MEMSLOTS + 1))
+        );
+        assert!(vm_resources.vm_config.memslot_size_mib.is_none());
+
+        // With huge pages, the memory slots must be a multiple of the huge page size.
+        vm_resources
+            .update_vm_config(&MachineConfigUpdate {
+                huge_pages: Some(HugePageConfig::Hugetlbfs2M),
+                ..Default::default()
+            })
+            .unwrap();
+        assert_eq!(
+            update_memslot_size(&mut vm_resources, 128, 3),
+            Err(VmConfigError::InvalidMemslotSize)
+        );
+        vm_resources
+            .update_vm_config(&MachineConfigUpdate {
+                huge_pages: Some(HugePageConfig::None),
+                ..Default::default()
+            })
+            .unwrap();
+
+        update_memslot_size(&mut vm_resources, MAX_MEMSLOTS, 1).unwrap();
+        update_memslot_size(&mut vm_resources, 100, 32).unwrap();
+        assert_eq!(vm_resources.vm_config.memslot_size_mib, Some(32));
+        assert_eq!(
+            MachineConfig::from(&vm_resources.vm_config).memslot_size_mib,
+            Some(32)
+        );
+
+        // The memory is split into slots of at most 32 MiB, the last one holding the remainder.
+        let regions = vm_resources.vm_config.memory_regions();
+        assert_eq!(
+            regions
+                .iter()
+                .map(|(_, size)| size >> 20)
+                .collect::<Vec<_>>(),
+            vec![32, 32, 32, 4]
+        );
+        for window in regions.windows(2) {
+            assert_eq!(window[0].0.unchecked_add(window[0].1 as u64), window[1].0);
+        }
+        assert_eq!(
+            vm_resources.allocate_guest_memory().unwrap().num_regions(),
+            regions.len()
+        );
+    }
+
+    #[test]
+    fn test_set_balloon_device() {
+        let mut vm_resources = default_vm_resources();
+        vm_resources.balloon = BalloonBuilder::new();
+        let mut new_balloon_cfg = BalloonDeviceConfig {
+            amount_mib: 100,
+            deflate_on_oom: false,
+            stats_polling_interval_s: 0,
+            free_page_reporting: false,
+        };
+        assert!(vm_resources.balloon.get().is_none());
+        vm_resources
+            .set_balloon_device(new_balloon_cfg.clone())
+            .unwrap();
+
+        let actual_balloon_cfg = vm_resources.balloon.get_config().unwrap();
+        assert_eq!(actual_balloon_cfg.amount_mib, new_balloon_cfg.amount_mib);
+        assert_eq!(
+            actual_balloon_cfg.deflate_on_oom,
+            new_balloon_cfg.deflate_on_oom
+        );
+        assert_eq!(
+            actual_balloon_cfg.stats_polling_interval_s,
+            new_balloon_cfg.stats_polling_interval_s
+        );
+
+        let mut vm_resources = default_vm_resources();
+        vm_resources.balloon = BalloonBuilder::new();
+        new_balloon_cfg.amount_mib = 256;
+        vm_resources
+            .set_balloon_device(new_balloon_cfg)
+            .unwrap_err();
+    }
+
+    #[test]
+    fn test_negative_restore_balloon_device_with_huge_pages() {
+        let mut vm_resources = default_vm_resources();
+        vm_resources.balloon = BalloonBuilder::new();
+        vm_resources
+            .update_vm_config(&MachineConfigUpdate {
+                huge_pages: Some(HugePageConfig::Hugetlbfs2M),
+                ..Default::default()
+            })
+            .unwrap();
+        let err = vm_resources
+            .update_from_restored_device(SharedDeviceType::Balloon(Arc::new(Mutex::new(
+                Balloon::new(128, false, 0, false, true).unwrap(),
+            ))))
+            .unwrap_err();
+        assert!(
+            matches!(
+                err,
+                ResourcesError::BalloonDevice(BalloonConfigError::HugePages)
+            ),
+            "{:?}",
+            err
+        );
+    }
+
+    #[test]
+    fn test_set_entropy_device() {
+        let mut vm_resources = default_vm_resources();
+        vm_resources.entropy = EntropyDeviceBuilder::new();
+        let entropy_device_cfg = EntropyDeviceConfig::default();
+
+        assert!(vm_resources.entropy.get().is_none());
+        vm_resources
+            .build_entropy_device(entropy_device_cfg.clone())
+            .unwrap();
+
+        let actual_entropy_cfg = vm_resources.entropy.config().unwrap();
+        assert_eq!(actual_entropy_cfg, entropy_device_cfg);
+    }
+
+    #[test]
+    fn test_set_watchdog_device() {
+        let mut vm_resources = default_vm_resources();
+        let watchdog_device_cfg = WatchdogDeviceConfig {
+            timeout_s: 30,
+            action: WatchdogAction::Reset,
+        };
+
+        assert!(vm_resources.watchdog.get().is_none());
+        vm_resources
+            .build_watchdog_device(watchdog_device_cfg.clone())
+            .unwrap();
+
+        let actual_watchdog_cfg = vm_resources.watchdog.config().unwrap();
+        assert_eq!(actual_watchdog_cfg, watchdog_device_cfg);
+
+        // A zero timeout is rejected.
+        let err = vm_resources
+            .build_watchdog_device(WatchdogDeviceConfig {
+                timeout_s: 0,
+                action: WatchdogAction::Log,
+            })
+            .unwrap_err();
+        assert!(matches!(err, WatchdogDeviceError::InvalidTimeout));
     }
 
     #[test]
@@ -1517,6 +3557,7 @@ mod tests {
             kernel_image_path: String::from(tmp_file.as_path().to_str().unwrap()),
             initrd_path: Some(String::from(tmp_file.as_path().to_str().unwrap())),
             boot_args: Some(cmdline.to_string()),
+            rng_seed: false,
         };
 
         let mut vm_resources = default_vm_resources();
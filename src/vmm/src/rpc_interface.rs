@@ -10,37 +10,62 @@ use utils::time::{get_time_us, ClockType};
 
 use super::builder::build_and_boot_microvm;
 use super::persist::{create_snapshot, restore_from_snapshot};
-use super::resources::VmResources;
+use super::resources::{ResourcesError, VmResources};
 use super::{Vmm, VmmError};
 use crate::builder::StartMicrovmError;
 use crate::cpu_config::templates::{CustomCpuTemplate, GuestConfigError};
-use crate::logger::{info, warn, LoggerConfig, *};
-use crate::mmds::data_store::{self, Mmds};
+use crate::logger::{info, warn, LoggerConfig, LoggerFilterUpdate, *};
+use crate::mmds::data_store::{self, Mmds, OutputFormat};
 use crate::persist::{CreateSnapshotError, RestoreFromSnapshotError, VmInfo};
 use crate::resources::VmmConfig;
 use crate::vmm_config::balloon::{
-    BalloonConfigError, BalloonDeviceConfig, BalloonStats, BalloonUpdateConfig,
-    BalloonUpdateStatsConfig,
+    BalloonConfigError, BalloonDeviceConfig, BalloonFeatures, BalloonStats, BalloonUpdateConfig,
+    BalloonUpdateStatsConfig, GuestMemoryCompaction,
 };
 use crate::vmm_config::boot_source::{BootSourceConfig, BootSourceConfigError};
+#[cfg(feature = "fault-injection")]
+use crate::vmm_config::devices::DeviceFaultRequest;
+use crate::vmm_config::devices::{
+    DeviceCapabilities, DeviceConfigSpace, DeviceConfigSpaceRequest, DeviceMemoryRequest,
+    DeviceMemoryUsage, VirtioDeviceType,
+};
 use crate::vmm_config::drive::{BlockDeviceConfig, BlockDeviceUpdateConfig, DriveError};
 use crate::vmm_config::entropy::{EntropyDeviceConfig, EntropyDeviceError};
-use crate::vmm_config::instance_info::InstanceInfo;
-use crate::vmm_config::machine_config::{MachineConfig, MachineConfigUpdate, VmConfigError};
-use crate::vmm_config::metrics::{MetricsConfig, MetricsConfigError};
+use crate::vmm_config::health::Health;
+use crate::vmm_config::instance_info::{InstanceInfo, VmState};
+use crate::vmm_config::kvm_capabilities::KvmCapabilities;
+use crate::vmm_config::machine_config::{
+    CpuFrequencies, MachineConfig, MachineConfigUpdate, MemoryLayout, VmConfigError,
+};
+use crate::vmm_config::metrics::{
+    DeviceMetricsRequest, FlushResult, MetricsConfig, MetricsConfigError,
+};
 use crate::vmm_config::mmds::{MmdsConfig, MmdsConfigError};
 use crate::vmm_config::net::{
-    NetworkInterfaceConfig, NetworkInterfaceError, NetworkInterfaceUpdateConfig,
+    NetRateLimiterState, NetworkInterfaceConfig, NetworkInterfaceError,
+    NetworkInterfaceUpdateConfig,
+};
+use crate::vmm_config::seccomp::SeccompInfo;
+use crate::vmm_config::snapshot::{
+    CreateSnapshotParams, LoadSnapshotParams, SnapshotPathError, SnapshotPathPlaceholders,
+    SnapshotPaths, SnapshotType,
 };
-use crate::vmm_config::snapshot::{CreateSnapshotParams, LoadSnapshotParams, SnapshotType};
 use crate::vmm_config::vsock::{VsockConfigError, VsockDeviceConfig};
-use crate::vmm_config::{self, RateLimiterUpdate};
-use crate::EventManager;
+use crate::vmm_config::watchdog::{WatchdogDeviceConfig, WatchdogDeviceError};
+use crate::vmm_config::{self, RateLimiterState, RateLimiterUpdate};
+use crate::{EventManager, VcpuRunState};
 
 /// This enum represents the public interface of the VMM. Each action contains various
 /// bits of information (ids, paths, etc.).
 #[derive(Debug, PartialEq, Eq)]
 pub enum VmmAction {
+    /// Replace the microVM configuration with the given JSON, in the format passed through
+    /// `--config-file`, if it is valid as a whole. This action can only be called before the
+    /// microVM has booted.
+    ApplyConfig(String),
+    /// Compact the guest memory by inflating the balloon by the given amount of MiB, then
+    /// deflating it back. This action can only be called after the microVM has booted.
+    CompactGuestMemory(u32),
     /// Configure the boot source of the microVM using as input the `ConfigureBootSource`. This
     /// action can only be called before the microVM has booted.
     ConfigureBootSource(BootSourceConfig),
@@ -57,18 +82,64 @@ pub enum VmmAction {
     GetBalloonConfig,
     /// Get the ballon device latest statistics.
     GetBalloonStats,
+    /// Get the live token bucket state of the rate limiter of the block device with the given
+    /// id. This action can only be called after the microVM has booted.
+    GetBlockRateLimiterState(String),
+    /// Get the balloon features negotiated with the guest driver. This action can only be called
+    /// after the microVM has booted.
+    GetBalloonFeatures,
+    /// Get the base, maximum and bus frequencies advertised to the guest in CPUID leaf 0x16.
+    GetCpuFrequencies,
+    /// Get the virtio features the implementation of a device type can offer.
+    GetDeviceCapabilities(VirtioDeviceType),
+    /// Get the raw configuration space of a single device. This action can only be called after
+    /// the microVM has booted.
+    GetDeviceConfigSpace(DeviceConfigSpaceRequest),
+    /// Get the guest memory used by the queues of a single device. This action can only be
+    /// called after the microVM has booted.
+    GetDeviceMemory(DeviceMemoryRequest),
+    /// Get the metrics of a single device.
+    GetDeviceMetrics(DeviceMetricsRequest),
+    /// Get the states of all devices, as they are persisted in a snapshot. This action can only
+    /// be called after the microVM has booted and only when the microVM is in `Paused` state.
+    GetDeviceStates,
     /// Get complete microVM configuration in JSON format.
     GetFullVmConfig,
     /// Get MMDS contents.
     GetMMDS,
+    /// Get the MMDS contents located at the given path, in the given format.
+    GetMMDSPath(String, OutputFormat),
+    /// Get the guest physical memory layout of the microVM.
+    GetMemoryLayout,
+    /// Get the configuration of the network interface with the given id.
+    GetNetworkInterface(String),
+    /// Get the live token bucket state of the rate limiters of the network interface with the
+    /// given id. This action can only be called after the microVM has booted.
+    GetNetRateLimiterState(String),
+    /// Get the run state of each vCPU. This action can only be called after the microVM has
+    /// booted.
+    GetVcpuStates,
     /// Get the machine configuration of the microVM.
     GetVmMachineConfig,
     /// Get microVM instance information.
     GetVmInstanceInfo,
     /// Get microVM version.
     GetVmmVersion,
-    /// Flush the metrics. This action can only be called after the logger has been configured.
+    /// Get the seccomp configuration applied at launch.
+    GetSeccompConfig,
+    /// Get the health indicators accumulated since they were last reset.
+    GetHealth,
+    /// Get the KVM capabilities of the host.
+    GetKvmCapabilities,
+    /// Reset the health indicators.
+    ResetHealth,
+    /// Flush the metrics and report the number of bytes written. This action can only be called
+    /// after the logger has been configured.
     FlushMetrics,
+    /// Inject a fault into the processing path of a single device. This action can only be
+    /// called after the microVM has booted.
+    #[cfg(feature = "fault-injection")]
+    InjectDeviceFault(DeviceFaultRequest),
     /// Add a new block device or update one that already exists using the `BlockDeviceConfig` as
     /// input. This action can only be called before the microVM has booted.
     InsertBlockDevice(BlockDeviceConfig),
@@ -84,12 +155,19 @@ pub enum VmmAction {
     PatchMMDS(Value),
     /// Pause the guest, by pausing the microVM VCPUs.
     Pause,
+    /// Pause the vCPU with the given index, leaving the other vCPUs running.
+    PauseVcpu(usize),
     /// Repopulate the MMDS contents.
     PutMMDS(Value),
+    /// Stop processing the queues of the block devices and complete their in-flight requests,
+    /// without pausing the vCPUs. Processing is restarted by `Resume`.
+    QuiesceIo,
     /// Configure the guest vCPU features.
     PutCpuConfiguration(CustomCpuTemplate),
     /// Resume the guest, by resuming the microVM VCPUs.
     Resume,
+    /// Resume the vCPU with the given index, previously paused through `PauseVcpu`.
+    ResumeVcpu(usize),
     /// Set the balloon device or update the one that already exists using the
     /// `BalloonDeviceConfig` as input. This action can only be called before the microVM
     /// has booted.
@@ -103,6 +181,9 @@ pub enum VmmAction {
     /// Set the entropy device using `EntropyDeviceConfig` as input. This action can only be called
     /// before the microVM has booted.
     SetEntropyDevice(EntropyDeviceConfig),
+    /// Set the watchdog device using `WatchdogDeviceConfig` as input. This action can only be
+    /// called before the microVM has booted.
+    SetWatchdogDevice(WatchdogDeviceConfig),
     /// Launch the microVM. This action can only be called before the microVM has booted.
     StartMicroVm,
     /// Send CTRL+ALT+DEL to the microVM, using the i8042 keyboard function. If an AT-keyboard
@@ -115,17 +196,27 @@ pub enum VmmAction {
     UpdateBalloonStatistics(BalloonUpdateStatsConfig),
     /// Update existing block device properties such as `path_on_host` or `rate_limiter`.
     UpdateBlockDevice(BlockDeviceUpdateConfig),
+    /// Update the bandwidth rate limiter of the entropy device, after microVM start.
+    UpdateEntropyDevice(EntropyDeviceConfig),
+    /// Update the levels of the logger using as input the `LoggerFilterUpdate`. This action can
+    /// be called both before and after the microVM has booted.
+    UpdateLoggerFilter(LoggerFilterUpdate),
     /// Update a network interface, after microVM start. Currently, the only updatable properties
     /// are the RX and TX rate limiters.
     UpdateNetworkInterface(NetworkInterfaceUpdateConfig),
     /// Update the microVM configuration (memory & vcpu) using `VmUpdateConfig` as input. This
     /// action can only be called before the microVM has booted.
     UpdateVmConfiguration(MachineConfigUpdate),
+    /// Check that the given JSON describes a valid microVM configuration, as passed through
+    /// `--config-file`, without applying it.
+    ValidateConfig(String),
 }
 
 /// Wrapper for all errors associated with VMM actions.
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
 pub enum VmmActionError {
+    /// Could not apply the microVM configuration: {0}
+    ApplyConfig(ResourcesError),
     /// Balloon config error: {0}
     BalloonConfig(#[from] BalloonConfigError),
     /// Boot source error: {0}
@@ -164,10 +255,16 @@ pub enum VmmActionError {
     OperationNotSupportedPostBoot,
     /// The requested operation is not supported before starting the microVM.
     OperationNotSupportedPreBoot,
+    /// Snapshot path error: {0}
+    SnapshotPath(#[from] SnapshotPathError),
     /// Start microvm error: {0}
     StartMicrovm(#[from] StartMicrovmError),
+    /// Invalid microVM configuration: {0}
+    ValidateConfig(ResourcesError),
     /// Vsock config error: {0}
     VsockConfig(#[from] VsockConfigError),
+    /// Watchdog device error: {0}
+    WatchdogDevice(#[from] WatchdogDeviceError),
 }
 
 /// The enum represents the response sent by the VMM in case of success. The response is either
@@ -179,18 +276,63 @@ pub enum VmmData {
     BalloonConfig(BalloonDeviceConfig),
     /// The latest balloon device statistics.
     BalloonStats(BalloonStats),
+    /// The balloon features negotiated with the guest driver.
+    BalloonFeatures(BalloonFeatures),
+    /// The frequencies advertised to the guest in CPUID leaf 0x16.
+    CpuFrequencies(CpuFrequencies),
+    /// The virtio features the implementation of a device type can offer.
+    DeviceCapabilities(DeviceCapabilities),
+    /// The raw configuration space of a single device.
+    DeviceConfigSpace(DeviceConfigSpace),
+    /// The guest memory used by the queues of a single device.
+    DeviceMemory(DeviceMemoryUsage),
+    /// The metrics of a single device.
+    DeviceMetrics(serde_json::Value),
+    /// The states of all devices.
+    DeviceStates(serde_json::Value),
     /// No data is sent on the channel.
     Empty,
+    /// The outcome of a guest memory compaction.
+    GuestMemoryCompaction(GuestMemoryCompaction),
+    /// The outcome of a metrics flush.
+    FlushResult(FlushResult),
     /// The complete microVM configuration in JSON format.
     FullVmConfig(VmmConfig),
     /// The microVM configuration represented by `VmConfig`.
     MachineConfiguration(MachineConfig),
+    /// The guest physical memory layout of the microVM.
+    MemoryLayout(MemoryLayout),
     /// Mmds contents.
     MmdsValue(serde_json::Value),
+    /// Mmds contents in IMDS plain text format.
+    MmdsText(String),
+    /// The configuration of a single network interface.
+    NetworkInterfaceConfig(NetworkInterfaceConfig),
+    /// The live state of the rate limiters of a single network interface.
+    NetRateLimiterState(NetRateLimiterState),
+    /// The live state of a single rate limiter.
+    RateLimiterState(RateLimiterState),
     /// The microVM instance information.
     InstanceInformation(InstanceInfo),
     /// The microVM version.
     VmmVersion(String),
+    /// The seccomp configuration in effect.
+    SeccompConfig(SeccompInfo),
+    /// The paths of the files a snapshot was created into.
+    SnapshotPaths(SnapshotPaths),
+    /// The health indicators of the microVM.
+    Health(Health),
+    /// The KVM capabilities of the host.
+    KvmCapabilities(KvmCapabilities),
+    /// The run state of each vCPU, in the order of their indices.
+    VcpuStates(Vec<VcpuRunState>),
+}
+
+/// Returns the frequencies advertised to the guest, shared by the two ApiControllers.
+fn cpu_frequencies(frequencies: Option<CpuFrequencies>) -> Result<VmmData, VmmActionError> {
+    frequencies.map(VmmData::CpuFrequencies).ok_or_else(|| {
+        VmmActionError::NotSupported("The CPU frequencies are not advertised.".to_string())
+    })
 }
 
 /// Trait used for deduplicating the MMDS request handling across the two ApiControllers.
@@ -203,12 +345,30 @@ trait MmdsRequestHandler {
         Ok(VmmData::MmdsValue(self.mmds().data_store_value()))
     }
 
+    fn get_mmds_path(
+        &mut self,
+        path: &str,
+        format: OutputFormat,
+    ) -> Result<VmmData, VmmActionError> {
+        let mmds = self.mmds();
+        match format {
+            OutputFormat::Json => mmds.get_subtree(path).map(VmmData::MmdsValue),
+            // The contents without a plain text form are returned as JSON.
+            OutputFormat::Imds => mmds.get_text(path).and_then(|text| match text {
+                Some(text) => Ok(VmmData::MmdsText(text)),
+                None => mmds.get_subtree(path).map(VmmData::MmdsValue),
+            }),
+        }
+        .map_err(VmmActionError::Mmds)
+    }
+
     fn patch_mmds(&mut self, value: serde_json::Value) -> Result<VmmData, VmmActionError> {
         self.mmds()
             .patch_data(value)
             .map(|()| VmmData::Empty)
             .map_err(|err| match err {
                 data_store::MmdsDatastoreError::DataStoreLimitExceeded => {
+                    METRICS.mmds.rejected_writes.inc();
                     VmmActionError::MmdsLimitExceeded(
                         data_store::MmdsDatastoreError::DataStoreLimitExceeded,
                     )
@@ -223,6 +383,7 @@ trait MmdsRequestHandler {
             .map(|()| VmmData::Empty)
             .map_err(|err| match err {
                 data_store::MmdsDatastoreError::DataStoreLimitExceeded => {
+                    METRICS.mmds.rejected_writes.inc();
                     VmmActionError::MmdsLimitExceeded(
                         data_store::MmdsDatastoreError::DataStoreLimitExceeded,
                     )
@@ -263,6 +424,26 @@ impl fmt::Debug for PrebootApiController<'_> {
     }
 }
 
+impl MmdsRequestHandler for &Mutex<Mmds> {
+    fn mmds(&mut self) -> MutexGuard<'_, Mmds> {
+        self.lock().expect("Poisoned lock")
+    }
+}
+
+/// Serves a request reading the MMDS contents from `mmds`, without going through the VMM.
+///
+/// Returns `None` if `request` does not read the MMDS contents.
+pub fn serve_mmds_read(
+    mut mmds: &Mutex<Mmds>,
+    request: &VmmAction,
+) -> Option<Result<VmmData, VmmActionError>> {
+    match request {
+        VmmAction::GetMMDS => Some(mmds.get_mmds()),
+        VmmAction::GetMMDSPath(path, format) => Some(mmds.get_mmds_path(path, *format)),
+        _ => None,
+    }
+}
+
 impl MmdsRequestHandler for PrebootApiController<'_> {
     fn mmds(&mut self) -> MutexGuard<'_, Mmds> {
         self.vm_resources.locked_mmds_or_default()
@@ -398,6 +579,7 @@ impl<'a> PrebootApiController<'a> {
 
         match request {
             // Supported operations allowed pre-boot.
+            ApplyConfig(config_json) => self.apply_config(&config_json),
             ConfigureBootSource(config) => self.set_boot_source(config),
             ConfigureLogger(logger_cfg) => crate::logger::LOGGER
                 .update(logger_cfg)
@@ -407,6 +589,9 @@ impl<'a> PrebootApiController<'a> {
                 .map(|()| VmmData::Empty)
                 .map_err(VmmActionError::Metrics),
             GetBalloonConfig => self.balloon_config(),
+            GetDeviceMetrics(request) => vmm_config::metrics::device_metrics(&request)
+                .map(VmmData::DeviceMetrics)
+                .map_err(VmmActionError::Metrics),
             GetFullVmConfig => {
                 warn!(
                     "If the VM was restored from snapshot, boot-source, machine-config.smt, and \
@@ -415,11 +600,31 @@ impl<'a> PrebootApiController<'a> {
                 Ok(VmmData::FullVmConfig((&*self.vm_resources).into()))
             }
             GetMMDS => self.get_mmds(),
+            GetMMDSPath(path, format) => self.get_mmds_path(&path, format),
+            GetMemoryLayout => Ok(VmmData::MemoryLayout(MemoryLayout::from(
+                &self.vm_resources.vm_config,
+            ))),
+            GetNetworkInterface(iface_id) => self.network_interface_config(&iface_id),
             GetVmMachineConfig => Ok(VmmData::MachineConfiguration(MachineConfig::from(
                 &self.vm_resources.vm_config,
             ))),
+            GetCpuFrequencies => {
+                cpu_frequencies(self.vm_resources.vm_config.advertised_cpu_frequencies())
+            }
             GetVmInstanceInfo => Ok(VmmData::InstanceInformation(self.instance_info.clone())),
             GetVmmVersion => Ok(VmmData::VmmVersion(self.instance_info.vmm_version.clone())),
+            GetSeccompConfig => Ok(VmmData::SeccompConfig(self.instance_info.seccomp.clone())),
+            GetHealth => Ok(VmmData::Health(Health::get())),
+            GetKvmCapabilities => KvmCapabilities::get()
+                .map(VmmData::KvmCapabilities)
+                .map_err(|err| VmmActionError::InternalVmm(VmmError::Vm(err))),
+            ResetHealth => {
+                Health::reset();
+                Ok(VmmData::Empty)
+            }
+            GetDeviceCapabilities(device_type) => {
+                Ok(VmmData::DeviceCapabilities(device_type.capabilities()))
+            }
             InsertBlockDevice(config) => self.insert_block_device(config),
             InsertNetworkDevice(config) => self.insert_net_device(config),
             LoadSnapshot(config) => self
@@ -435,19 +640,45 @@ impl<'a> PrebootApiController<'a> {
             SetMmdsConfiguration(config) => self.set_mmds_config(config),
             StartMicroVm => self.start_microvm(),
             UpdateVmConfiguration(config) => self.update_vm_config(config),
+            ValidateConfig(config_json) => VmResources::validate_json(
+                &config_json,
+                &self.instance_info,
+                self.vm_resources.mmds_size_limit,
+            )
+            .map(VmmData::FullVmConfig)
+            .map_err(VmmActionError::ValidateConfig),
             SetEntropyDevice(config) => self.set_entropy_device(config),
+            SetWatchdogDevice(config) => self.set_watchdog_device(config),
+            UpdateLoggerFilter(update) => {
+                crate::logger::LOGGER.update_filter(update);
+                Ok(VmmData::Empty)
+            }
             // Operations not allowed pre-boot.
-            CreateSnapshot(_)
+            CompactGuestMemory(_)
+            | CreateSnapshot(_)
             | FlushMetrics
+            | GetDeviceConfigSpace(_)
+            | GetDeviceMemory(_)
+            | GetDeviceStates
+            | GetBlockRateLimiterState(_)
+            | GetNetRateLimiterState(_)
+            | GetVcpuStates
             | Pause
+            | PauseVcpu(_)
+            | QuiesceIo
             | Resume
+            | ResumeVcpu(_)
             | GetBalloonStats
+            | GetBalloonFeatures
             | UpdateBalloon(_)
             | UpdateBalloonStatistics(_)
             | UpdateBlockDevice(_)
+            | UpdateEntropyDevice(_)
             | UpdateNetworkInterface(_) => Err(VmmActionError::OperationNotSupportedPreBoot),
             #[cfg(target_arch = "x86_64")]
             SendCtrlAltDel => Err(VmmActionError::OperationNotSupportedPreBoot),
+            #[cfg(feature = "fault-injection")]
+            InjectDeviceFault(_) => Err(VmmActionError::OperationNotSupportedPreBoot),
         }
     }
 
@@ -459,6 +690,22 @@ impl<'a> PrebootApiController<'a> {
             .map_err(VmmActionError::BalloonConfig)
     }
 
+    fn network_interface_config(&self, iface_id: &str) -> Result<VmmData, VmmActionError> {
+        self.vm_resources
+            .net_builder
+            .config(iface_id)
+            .map(VmmData::NetworkInterfaceConfig)
+            .map_err(VmmActionError::NetworkConfig)
+    }
+
+    fn apply_config(&mut self, config_json: &str) -> Result<VmmData, VmmActionError> {
+        self.boot_path = true;
+        self.vm_resources
+            .apply_json(config_json, &self.instance_info)
+            .map(|()| VmmData::Empty)
+            .map_err(VmmActionError::ApplyConfig)
+    }
+
     fn insert_block_device(&mut self, cfg: BlockDeviceConfig) -> Result<VmmData, VmmActionError> {
         self.boot_path = true;
         self.vm_resources
@@ -532,6 +779,15 @@ impl<'a> PrebootApiController<'a> {
         Ok(VmmData::Empty)
     }
 
+    fn set_watchdog_device(
+        &mut self,
+        cfg: WatchdogDeviceConfig,
+    ) -> Result<VmmData, VmmActionError> {
+        self.boot_path = true;
+        self.vm_resources.build_watchdog_device(cfg)?;
+        Ok(VmmData::Empty)
+    }
+
     // On success, this command will end the pre-boot stage and this controller
     // will be replaced by a runtime controller.
     fn start_microvm(&mut self) -> Result<VmmData, VmmActionError> {
@@ -623,6 +879,13 @@ impl RuntimeApiController {
         use self::VmmAction::*;
         match request {
             // Supported operations allowed post-boot.
+            CompactGuestMemory(target_free_mib) => self
+                .vmm
+                .lock()
+                .expect("Poisoned lock")
+                .compact_guest_memory(target_free_mib)
+                .map(|()| VmmData::Empty)
+                .map_err(|err| VmmActionError::BalloonConfig(BalloonConfigError::from(err))),
             CreateSnapshot(snapshot_create_cfg) => self.create_snapshot(&snapshot_create_cfg),
             FlushMetrics => self.flush_metrics(),
             GetBalloonConfig => self
@@ -639,21 +902,108 @@ impl RuntimeApiController {
                 .latest_balloon_stats()
                 .map(VmmData::BalloonStats)
                 .map_err(|err| VmmActionError::BalloonConfig(BalloonConfigError::from(err))),
+            GetBalloonFeatures => self
+                .vmm
+                .lock()
+                .expect("Poisoned lock")
+                .balloon_features()
+                .map(VmmData::BalloonFeatures)
+                .map_err(|err| VmmActionError::BalloonConfig(BalloonConfigError::from(err))),
+            GetDeviceCapabilities(device_type) => {
+                Ok(VmmData::DeviceCapabilities(device_type.capabilities()))
+            }
+            GetDeviceConfigSpace(request) => self
+                .vmm
+                .lock()
+                .expect("Poisoned lock")
+                .device_config_space(request.device_type, &request.device_id)
+                .map(VmmData::DeviceConfigSpace)
+                .map_err(VmmActionError::InternalVmm),
+            GetDeviceMemory(request) => self
+                .vmm
+                .lock()
+                .expect("Poisoned lock")
+                .device_memory_usage(request.device_type, &request.device_id)
+                .map(VmmData::DeviceMemory)
+                .map_err(VmmActionError::InternalVmm),
+            GetDeviceStates => self.get_device_states(),
+            GetBlockRateLimiterState(drive_id) => self
+                .vmm
+                .lock()
+                .expect("Poisoned lock")
+                .block_rate_limiter_state(&drive_id)
+                .map(VmmData::RateLimiterState)
+                .map_err(|err| VmmActionError::DriveConfig(DriveError::DeviceUpdate(err))),
+            GetNetRateLimiterState(iface_id) => self
+                .vmm
+                .lock()
+                .expect("Poisoned lock")
+                .net_rate_limiter_state(&iface_id)
+                .map(VmmData::NetRateLimiterState)
+                .map_err(|err| {
+                    VmmActionError::NetworkConfig(NetworkInterfaceError::DeviceUpdate(err))
+                }),
+            GetDeviceMetrics(request) => vmm_config::metrics::device_metrics(&request)
+                .map(VmmData::DeviceMetrics)
+                .map_err(VmmActionError::Metrics),
             GetFullVmConfig => Ok(VmmData::FullVmConfig((&self.vm_resources).into())),
             GetMMDS => self.get_mmds(),
+            GetMMDSPath(path, format) => self.get_mmds_path(&path, format),
+            GetMemoryLayout => Ok(VmmData::MemoryLayout(MemoryLayout::from(
+                &self.vm_resources.vm_config,
+            ))),
+            GetNetworkInterface(iface_id) => self.network_interface_config(&iface_id),
             GetVmMachineConfig => Ok(VmmData::MachineConfiguration(MachineConfig::from(
                 &self.vm_resources.vm_config,
             ))),
+            // The guest CPUID is only known once the microVM is built.
+            #[cfg(target_arch = "x86_64")]
+            GetCpuFrequencies => {
+                cpu_frequencies(self.vmm.lock().expect("Poisoned lock").cpu_frequencies())
+            }
+            #[cfg(target_arch = "aarch64")]
+            GetCpuFrequencies => {
+                cpu_frequencies(self.vm_resources.vm_config.advertised_cpu_frequencies())
+            }
+            GetVcpuStates => Ok(VmmData::VcpuStates(
+                self.vmm.lock().expect("Poisoned lock").vcpu_run_states(),
+            )),
             GetVmInstanceInfo => Ok(VmmData::InstanceInformation(
                 self.vmm.lock().expect("Poisoned lock").instance_info(),
             )),
             GetVmmVersion => Ok(VmmData::VmmVersion(
                 self.vmm.lock().expect("Poisoned lock").version(),
             )),
+            GetSeccompConfig => Ok(VmmData::SeccompConfig(
+                self.vmm
+                    .lock()
+                    .expect("Poisoned lock")
+                    .instance_info()
+                    .seccomp,
+            )),
+            GetHealth => Ok(VmmData::Health(Health::get())),
+            GetKvmCapabilities => KvmCapabilities::get()
+                .map(VmmData::KvmCapabilities)
+                .map_err(|err| VmmActionError::InternalVmm(VmmError::Vm(err))),
+            ResetHealth => {
+                Health::reset();
+                Ok(VmmData::Empty)
+            }
+            #[cfg(feature = "fault-injection")]
+            InjectDeviceFault(request) => self
+                .vmm
+                .lock()
+                .expect("Poisoned lock")
+                .inject_device_fault(request.device_type, &request.device_id, request.fault)
+                .map(|()| VmmData::Empty)
+                .map_err(VmmActionError::InternalVmm),
             PatchMMDS(value) => self.patch_mmds(value),
             Pause => self.pause(),
+            PauseVcpu(index) => self.pause_vcpu(index),
+            QuiesceIo => self.quiesce_io(),
             PutMMDS(value) => self.put_mmds(value),
             Resume => self.resume(),
+            ResumeVcpu(index) => self.resume_vcpu(index),
             #[cfg(target_arch = "x86_64")]
             SendCtrlAltDel => self.send_ctrl_alt_del(),
             UpdateBalloon(balloon_update) => self
@@ -671,10 +1021,16 @@ impl RuntimeApiController {
                 .map(|_| VmmData::Empty)
                 .map_err(|err| VmmActionError::BalloonConfig(BalloonConfigError::from(err))),
             UpdateBlockDevice(new_cfg) => self.update_block_device(new_cfg),
+            UpdateEntropyDevice(new_cfg) => self.update_rng_rate_limiter(new_cfg),
+            UpdateLoggerFilter(update) => {
+                crate::logger::LOGGER.update_filter(update);
+                Ok(VmmData::Empty)
+            }
             UpdateNetworkInterface(netif_update) => self.update_net_rate_limiters(netif_update),
 
             // Operations not allowed post-boot.
-            ConfigureBootSource(_)
+            ApplyConfig(_)
+            | ConfigureBootSource(_)
             | ConfigureLogger(_)
             | ConfigureMetrics(_)
             | InsertBlockDevice(_)
@@ -685,8 +1041,19 @@ impl RuntimeApiController {
             | SetVsockDevice(_)
             | SetMmdsConfiguration(_)
             | SetEntropyDevice(_)
+            | SetWatchdogDevice(_)
             | StartMicroVm
             | UpdateVmConfiguration(_) => Err(VmmActionError::OperationNotSupportedPostBoot),
+            ValidateConfig(config_json) => {
+                let instance_info = self.vmm.lock().expect("Poisoned lock").instance_info();
+                VmResources::validate_json(
+                    &config_json,
+                    &instance_info,
+                    self.vm_resources.mmds_size_limit,
+                )
+                .map(VmmData::FullVmConfig)
+                .map_err(VmmActionError::ValidateConfig)
+            }
         }
     }
 
@@ -708,6 +1075,30 @@ impl RuntimeApiController {
         Ok(VmmData::Empty)
     }
 
+    /// Pauses a single vCPU of the running microVM.
+    pub fn pause_vcpu(&mut self, index: usize) -> Result<VmmData, VmmActionError> {
+        self.vmm.lock().expect("Poisoned lock").pause_vcpu(index)?;
+        info!("Paused vCPU {}.", index);
+        Ok(VmmData::Empty)
+    }
+
+    /// Provides the outcome of the last completed guest memory compaction, once the balloon
+    /// device signaled its completion after a `CompactGuestMemory` request.
+    pub fn guest_memory_compaction(&mut self) -> Result<VmmData, VmmActionError> {
+        self.vmm
+            .lock()
+            .expect("Poisoned lock")
+            .guest_memory_compaction()
+            .map(VmmData::GuestMemoryCompaction)
+            .map_err(|err| VmmActionError::BalloonConfig(BalloonConfigError::from(err)))
+    }
+
+    /// Quiesces the IO of the block devices, leaving the vCPUs running.
+    pub fn quiesce_io(&mut self) -> Result<VmmData, VmmActionError> {
+        self.vmm.lock().expect("Poisoned lock").quiesce_io();
+        Ok(VmmData::Empty)
+    }
+
     /// Resumes the microVM by resuming the vCPUs.
     pub fn resume(&mut self) -> Result<VmmData, VmmActionError> {
         let resume_start_us = get_time_us(ClockType::Monotonic);
@@ -721,16 +1112,26 @@ impl RuntimeApiController {
         Ok(VmmData::Empty)
     }
 
+    /// Resumes a single vCPU of the running microVM.
+    pub fn resume_vcpu(&mut self, index: usize) -> Result<VmmData, VmmActionError> {
+        self.vmm.lock().expect("Poisoned lock").resume_vcpu(index)?;
+        info!("Resumed vCPU {}.", index);
+        Ok(VmmData::Empty)
+    }
+
     /// Write the metrics on user demand (flush). We use the word `flush` here to highlight the fact
     /// that the metrics will be written immediately.
     /// Defer to inner Vmm. We'll move to a variant where the Vmm simply exposes functionality like
     /// getting the dirty pages, and then we'll have the metrics flushing logic entirely on the
     /// outside.
     fn flush_metrics(&mut self) -> Result<VmmData, VmmActionError> {
-        // FIXME: we're losing the bool saying whether metrics were actually written.
         METRICS
-            .write()
-            .map(|_| VmmData::Empty)
+            .write_and_count()
+            .map(|written| {
+                VmmData::FlushResult(FlushResult {
+                    bytes_written: written.unwrap_or(0) as u64,
+                })
+            })
             .map_err(super::VmmError::Metrics)
             .map_err(VmmActionError::InternalVmm)
     }
@@ -746,13 +1147,43 @@ impl RuntimeApiController {
             .map_err(VmmActionError::InternalVmm)
     }
 
+    fn network_interface_config(&self, iface_id: &str) -> Result<VmmData, VmmActionError> {
+        self.vm_resources
+            .net_builder
+            .config(iface_id)
+            .map(VmmData::NetworkInterfaceConfig)
+            .map_err(VmmActionError::NetworkConfig)
+    }
+
+    fn get_device_states(&self) -> Result<VmmData, VmmActionError> {
+        let vmm = self.vmm.lock().expect("Poisoned lock");
+        if vmm.instance_info().state != VmState::Paused {
+            return Err(VmmActionError::NotSupported(
+                "The device states can only be retrieved while the microVM is paused.".to_string(),
+            ));
+        }
+
+        vmm.device_states()
+            .map(VmmData::DeviceStates)
+            .map_err(VmmActionError::InternalVmm)
+    }
+
     fn create_snapshot(
         &mut self,
         create_params: &CreateSnapshotParams,
     ) -> Result<VmmData, VmmActionError> {
         log_dev_preview_warning("Virtual machine snapshots", None);
 
+        if create_params.baseline_mem_file_path.is_some()
+            && create_params.snapshot_type != SnapshotType::Diff
+        {
+            return Err(VmmActionError::NotSupported(
+                "A baseline memory file can only be used for diff snapshots.".to_string(),
+            ));
+        }
+
         if create_params.snapshot_type == SnapshotType::Diff
+            && create_params.baseline_mem_file_path.is_none()
             && !self.vm_resources.vm_config.track_dirty_pages
         {
             return Err(VmmActionError::NotSupported(
@@ -761,11 +1192,42 @@ impl RuntimeApiController {
             ));
         }
 
+        // A restored microVM maps its memory from the memory snapshot, so it would silently stop
+        // sharing its memory through the backing file.
+        if self.vm_resources.vm_config.mem_backing_file.is_some() {
+            return Err(VmmActionError::NotSupported(
+                "Snapshots are not allowed on uVMs with guest memory backed by a file.".to_string(),
+            ));
+        }
+
         let mut locked_vmm = self.vmm.lock().unwrap();
         let vm_info = VmInfo::from(&self.vm_resources);
         let create_start_us = get_time_us(ClockType::Monotonic);
 
-        create_snapshot(&mut locked_vmm, &vm_info, create_params)?;
+        let instance_info = locked_vmm.instance_info();
+        let placeholders = SnapshotPathPlaceholders {
+            instance_id: instance_info.id,
+            timestamp: get_time_us(ClockType::Real) / 1_000_000,
+            vmm_version: instance_info.vmm_version,
+        };
+        let paths = placeholders
+            .resolve_paths(&create_params.snapshot_path, &create_params.mem_file_path)?;
+        let resolved_params = &CreateSnapshotParams {
+            snapshot_type: create_params.snapshot_type,
+            snapshot_path: paths.snapshot_path.clone(),
+            mem_file_path: paths.mem_file_path.clone(),
+            baseline_mem_file_path: create_params.baseline_mem_file_path.clone(),
+            mem_write_chunk_size_kib: create_params.mem_write_chunk_size_kib,
+        };
+        info!(
+            "Creating snapshot into {} and {}.",
+            paths.snapshot_path.display(),
+            paths.mem_file_path.display()
+        );
+
+        create_snapshot(&mut locked_vmm, &vm_info, resolved_params).inspect_err(|_| {
+            paths.remove_resolved_files(&create_params.snapshot_path, &create_params.mem_file_path)
+        })?;
 
         match create_params.snapshot_type {
             SnapshotType::Full => {
@@ -789,7 +1251,7 @@ impl RuntimeApiController {
                 );
             }
         }
-        Ok(VmmData::Empty)
+        Ok(VmmData::SnapshotPaths(paths))
     }
 
     /// Updates block device properties:
@@ -803,7 +1265,10 @@ impl RuntimeApiController {
         let mut vmm = self.vmm.lock().expect("Poisoned lock");
 
         // vhost-user-block updates
-        if new_cfg.path_on_host.is_none() && new_cfg.rate_limiter.is_none() {
+        if new_cfg.path_on_host.is_none()
+            && new_cfg.rate_limiter.is_none()
+            && new_cfg.resize.is_none()
+        {
             vmm.update_vhost_user_block_config(&new_cfg.drive_id)
                 .map(|()| VmmData::Empty)
                 .map_err(DriveError::DeviceUpdate)?;
@@ -824,9 +1289,31 @@ impl RuntimeApiController {
             .map(|()| VmmData::Empty)
             .map_err(DriveError::DeviceUpdate)?;
         }
+        if new_cfg.resize == Some(true) {
+            vmm.notify_block_device_resize(&new_cfg.drive_id)
+                .map(|()| VmmData::Empty)
+                .map_err(DriveError::DeviceUpdate)?;
+        }
         Ok(VmmData::Empty)
     }
 
+    /// Updates the rate limiter of the entropy device as described in `new_cfg`.
+    fn update_rng_rate_limiter(
+        &mut self,
+        new_cfg: EntropyDeviceConfig,
+    ) -> Result<VmmData, VmmActionError> {
+        if new_cfg.rate_limiter.is_some_and(|rl| rl.ops.is_some()) {
+            return Err(EntropyDeviceError::UpdateOpsRateLimiter.into());
+        }
+        self.vmm
+            .lock()
+            .expect("Poisoned lock")
+            .update_rng_rate_limiter(RateLimiterUpdate::from(new_cfg.rate_limiter).bandwidth)
+            .map(|()| VmmData::Empty)
+            .map_err(EntropyDeviceError::DeviceUpdate)
+            .map_err(VmmActionError::EntropyDevice)
+    }
+
     /// Updates configuration for an emulated net device as described in `new_cfg`.
     fn update_net_rate_limiters(
         &mut self,
@@ -853,13 +1340,22 @@ mod tests {
     use std::path::PathBuf;
 
     use seccompiler::BpfThreadMap;
+    use vmm_sys_util::tempfile::TempFile;
 
     use super::*;
     use crate::builder::tests::default_vmm;
+    use crate::device_manager::mmio::MmioError;
+    use crate::devices::virtio::balloon::BalloonError;
     use crate::devices::virtio::block::CacheType;
+    #[cfg(feature = "fault-injection")]
+    use crate::devices::virtio::fault_injection::DeviceFault;
     use crate::mmds::data_store::MmdsVersion;
+    use crate::vmm_config::machine_config::VmConfig;
+    use crate::vmm_config::seccomp::SeccompLevel;
     use crate::vmm_config::snapshot::{MemBackendConfig, MemBackendType};
-    use crate::HTTP_MAX_PAYLOAD_SIZE;
+    use crate::vmm_config::{RateLimiterConfig, TokenBucketConfig};
+    use crate::vstate::vcpu::SharedVcpuRunState;
+    use crate::{FcExitCode, Vcpu, VcpuHandle, HTTP_MAX_PAYLOAD_SIZE};
 
     fn default_preboot<'a>(
         vm_resources: &'a mut VmResources,
@@ -901,6 +1397,283 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_preboot_apply_config() {
+        let kernel_file = TempFile::new().unwrap();
+        let mut vm_resources = VmResources::default();
+        let mut evmgr = EventManager::new().unwrap();
+        let seccomp_filters = BpfThreadMap::new();
+        let mut preboot = default_preboot(&mut vm_resources, &mut evmgr, &seccomp_filters);
+
+        let res = preboot.handle_preboot_request(VmmAction::ApplyConfig(String::from(
+            r#"{"boot-source": {"kernel_image_path": "/invalid/path"}}"#,
+        )));
+        assert!(
+            matches!(
+                res,
+                Err(VmmActionError::ApplyConfig(ResourcesError::BootSource(_)))
+            ),
+            "{:?}",
+            res
+        );
+
+        let config_json = format!(
+            r#"{{
+                "boot-source": {{
+                    "kernel_image_path": "{}"
+                }},
+                "machine-config": {{
+                    "vcpu_count": 2,
+                    "mem_size_mib": 256
+                }}
+            }}"#,
+            kernel_file.as_path().to_str().unwrap()
+        );
+        assert_eq!(
+            preboot
+                .handle_preboot_request(VmmAction::ApplyConfig(config_json))
+                .unwrap(),
+            VmmData::Empty
+        );
+        // A snapshot cannot be loaded once the microVM is configured for boot.
+        assert!(preboot.boot_path);
+        assert_eq!(vm_resources.vm_config.vcpu_count, 2);
+    }
+
+    #[test]
+    fn test_preboot_validate_config() {
+        let kernel_file = TempFile::new().unwrap();
+        let config_json = format!(
+            r#"{{
+                "boot-source": {{
+                    "kernel_image_path": "{}"
+                }},
+                "machine-config": {{
+                    "vcpu_count": 2,
+                    "mem_size_mib": 256
+                }}
+            }}"#,
+            kernel_file.as_path().to_str().unwrap()
+        );
+        let res = preboot_request(VmmAction::ValidateConfig(config_json));
+        let Ok(VmmData::FullVmConfig(config)) = res else {
+            panic!("{:?}", res);
+        };
+        // The configuration is completed with the default values.
+        let config = serde_json::to_value(config).unwrap();
+        assert_eq!(
+            config["boot-source"]["kernel_image_path"],
+            kernel_file.as_path().to_str().unwrap()
+        );
+        assert_eq!(config["machine-config"]["vcpu_count"], 2);
+        assert_eq!(config["machine-config"]["smt"], false);
+        assert_eq!(config["drives"], serde_json::json!([]));
+
+        // Missing kernel file.
+        let res = preboot_request(VmmAction::ValidateConfig(String::from(
+            r#"{"boot-source": {"kernel_image_path": "/invalid/path"}}"#,
+        )));
+        assert!(
+            matches!(
+                res,
+                Err(VmmActionError::ValidateConfig(ResourcesError::BootSource(
+                    _
+                )))
+            ),
+            "{:?}",
+            res
+        );
+        // Missing boot source.
+        let res = preboot_request(VmmAction::ValidateConfig(String::from("{}")));
+        assert!(
+            matches!(
+                res,
+                Err(VmmActionError::ValidateConfig(ResourcesError::InvalidJson(
+                    _
+                )))
+            ),
+            "{:?}",
+            res
+        );
+    }
+
+    #[test]
+    fn test_preboot_get_seccomp_config() {
+        let mut vm_resources = VmResources::default();
+        let mut evmgr = EventManager::new().unwrap();
+        let seccomp_filters = BpfThreadMap::new();
+        let seccomp = SeccompInfo::new(SeccompLevel::Advanced, &seccomp_filters);
+        let instance_info = InstanceInfo {
+            seccomp: seccomp.clone(),
+            ..Default::default()
+        };
+        let mut preboot = PrebootApiController::new(
+            &seccomp_filters,
+            instance_info,
+            &mut vm_resources,
+            &mut evmgr,
+        );
+        assert_eq!(
+            preboot
+                .handle_preboot_request(VmmAction::GetSeccompConfig)
+                .unwrap(),
+            VmmData::SeccompConfig(seccomp)
+        );
+    }
+
+    #[test]
+    fn test_update_logger_filter() {
+        // The levels are updated by the logger tests, the update is empty so as not to interfere
+        // with the logs of the other tests.
+        let update = LoggerFilterUpdate {
+            level: None,
+            filter: None,
+        };
+        assert_eq!(
+            preboot_request(VmmAction::UpdateLoggerFilter(update.clone())).unwrap(),
+            VmmData::Empty
+        );
+        assert_eq!(
+            runtime_request(VmmAction::UpdateLoggerFilter(update)).unwrap(),
+            VmmData::Empty
+        );
+    }
+
+    #[test]
+    fn test_preboot_health() {
+        assert!(matches!(
+            preboot_request(VmmAction::GetHealth).unwrap(),
+            VmmData::Health(_)
+        ));
+        assert_eq!(
+            preboot_request(VmmAction::ResetHealth).unwrap(),
+            VmmData::Empty
+        );
+    }
+
+    #[test]
+    fn test_preboot_get_network_interface() {
+        let mut vm_resources = VmResources::default();
+        let mut evmgr = EventManager::new().unwrap();
+        let seccomp_filters = BpfThreadMap::new();
+        let mut preboot = default_preboot(&mut vm_resources, &mut evmgr, &seccomp_filters);
+        let netif = NetworkInterfaceConfig {
+            iface_id: String::from("netif"),
+            host_dev_name: String::from("tap_preboot_get"),
+            guest_mac: None,
+            rx_rate_limiter: None,
+            tx_rate_limiter: None,
+            mtu: Some(1500),
+        };
+        preboot
+            .handle_preboot_request(VmmAction::InsertNetworkDevice(netif.clone()))
+            .unwrap();
+
+        assert_eq!(
+            preboot
+                .handle_preboot_request(VmmAction::GetNetworkInterface(String::from("netif")))
+                .unwrap(),
+            VmmData::NetworkInterfaceConfig(netif)
+        );
+        assert!(matches!(
+            preboot.handle_preboot_request(VmmAction::GetNetworkInterface(String::from("unknown"))),
+            Err(VmmActionError::NetworkConfig(
+                NetworkInterfaceError::DeviceNotFound(_)
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_preboot_get_kvm_capabilities() {
+        assert!(matches!(
+            preboot_request(VmmAction::GetKvmCapabilities).unwrap(),
+            VmmData::KvmCapabilities(_)
+        ));
+    }
+
+    #[test]
+    fn test_preboot_get_device_capabilities() {
+        assert_eq!(
+            preboot_request(VmmAction::GetDeviceCapabilities(VirtioDeviceType::Net)).unwrap(),
+            VmmData::DeviceCapabilities(VirtioDeviceType::Net.capabilities())
+        );
+    }
+
+    #[test]
+    fn test_preboot_get_cpu_frequencies() {
+        let mut vm_resources = VmResources::default();
+        let mut evmgr = EventManager::new().unwrap();
+        let seccomp_filters = BpfThreadMap::new();
+        let mut preboot = default_preboot(&mut vm_resources, &mut evmgr, &seccomp_filters);
+
+        // Without configured frequencies, the host ones are reported.
+        let res = preboot.handle_preboot_request(VmmAction::GetCpuFrequencies);
+        match VmConfig::default().advertised_cpu_frequencies() {
+            Some(frequencies) => assert_eq!(res.unwrap(), VmmData::CpuFrequencies(frequencies)),
+            None => assert!(matches!(res, Err(VmmActionError::NotSupported(_)))),
+        }
+
+        #[cfg(target_arch = "x86_64")]
+        {
+            let frequencies = CpuFrequencies {
+                base_mhz: 2100,
+                max_mhz: 3500,
+                bus_mhz: 100,
+            };
+            preboot
+                .handle_preboot_request(VmmAction::UpdateVmConfiguration(MachineConfigUpdate {
+                    cpu_frequencies: Some(Some(frequencies)),
+                    ..Default::default()
+                }))
+                .unwrap();
+            assert_eq!(
+                preboot
+                    .handle_preboot_request(VmmAction::GetCpuFrequencies)
+                    .unwrap(),
+                VmmData::CpuFrequencies(frequencies)
+            );
+        }
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_runtime_get_cpu_frequencies() {
+        // The frequencies are read from the guest CPUID, not from the configuration or the host.
+        let vmm = Arc::new(Mutex::new(default_vmm()));
+        let mut vm_resources = VmResources::default();
+        vm_resources.vm_config.cpuid.cpu_frequencies = Some(CpuFrequencies {
+            base_mhz: 1000,
+            max_mhz: 1000,
+            bus_mhz: 100,
+        });
+        let mut runtime = RuntimeApiController::new(vm_resources, vmm.clone());
+        assert!(matches!(
+            runtime.handle_request(VmmAction::GetCpuFrequencies),
+            Err(VmmActionError::NotSupported(_))
+        ));
+
+        let frequencies = CpuFrequencies {
+            base_mhz: 2100,
+            max_mhz: 3500,
+            bus_mhz: 100,
+        };
+        vmm.lock().unwrap().cpu_frequencies = Some(frequencies);
+        assert_eq!(
+            runtime
+                .handle_request(VmmAction::GetCpuFrequencies)
+                .unwrap(),
+            VmmData::CpuFrequencies(frequencies)
+        );
+    }
+
+    #[test]
+    fn test_preboot_get_memory_layout() {
+        assert_eq!(
+            preboot_request(VmmAction::GetMemoryLayout).unwrap(),
+            VmmData::MemoryLayout(MemoryLayout::from(&VmConfig::default()))
+        );
+    }
+
     #[test]
     fn test_preboot_get_mmds() {
         assert_eq!(
@@ -917,6 +1690,104 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_serve_mmds_read() {
+        let mmds = Mutex::new(Mmds::default());
+        mmds.lock()
+            .unwrap()
+            .put_data(serde_json::json!({"latest": {"instance-id": "i-1234"}}))
+            .unwrap();
+
+        assert_eq!(
+            serve_mmds_read(&mmds, &VmmAction::GetMMDS)
+                .unwrap()
+                .unwrap(),
+            VmmData::MmdsValue(serde_json::json!({"latest": {"instance-id": "i-1234"}}))
+        );
+        assert_eq!(
+            serve_mmds_read(
+                &mmds,
+                &VmmAction::GetMMDSPath("/latest/instance-id".to_string(), OutputFormat::Imds)
+            )
+            .unwrap()
+            .unwrap(),
+            VmmData::MmdsText("i-1234".to_string())
+        );
+        // Requests modifying the MMDS contents are left to the VMM.
+        assert!(serve_mmds_read(&mmds, &VmmAction::PutMMDS(Value::Null)).is_none());
+    }
+
+    #[test]
+    fn test_runtime_get_mmds_path() {
+        let mmds = Arc::new(Mutex::new(Mmds::default()));
+        mmds.lock()
+            .unwrap()
+            .put_data(serde_json::json!({
+                "latest": {"meta-data": {"instance-id": "i-1234", "ami-launch-index": 0}},
+                "tags": ["a", "b"],
+                "spot": false
+            }))
+            .unwrap();
+        let request = |path: &str, format| {
+            runtime_request_with_mmds(
+                VmmAction::GetMMDSPath(path.to_string(), format),
+                mmds.clone(),
+            )
+        };
+
+        // A scalar is returned as is in plain text.
+        assert_eq!(
+            request("/latest/meta-data/instance-id", OutputFormat::Imds).unwrap(),
+            VmmData::MmdsText("i-1234".to_string())
+        );
+        assert_eq!(
+            request("/latest/meta-data/instance-id", OutputFormat::Json).unwrap(),
+            VmmData::MmdsValue(Value::String("i-1234".to_string()))
+        );
+        // An object is returned as the list of its keys in plain text.
+        assert_eq!(
+            request("/latest/", OutputFormat::Imds).unwrap(),
+            VmmData::MmdsText("meta-data/".to_string())
+        );
+        assert_eq!(
+            request("/latest", OutputFormat::Json).unwrap(),
+            VmmData::MmdsValue(serde_json::json!({
+                "meta-data": {"instance-id": "i-1234", "ami-launch-index": 0}
+            }))
+        );
+        // The root path lists the top level keys.
+        assert_eq!(
+            request("/", OutputFormat::Imds).unwrap(),
+            VmmData::MmdsText("latest/\nspot\ntags".to_string())
+        );
+        // Numbers and booleans are rendered as text too.
+        assert_eq!(
+            request("/latest/meta-data/ami-launch-index", OutputFormat::Imds).unwrap(),
+            VmmData::MmdsText("0".to_string())
+        );
+        assert_eq!(
+            request("/spot", OutputFormat::Imds).unwrap(),
+            VmmData::MmdsText("false".to_string())
+        );
+        // The contents without a plain text form are returned as JSON.
+        assert_eq!(
+            request("/tags", OutputFormat::Imds).unwrap(),
+            VmmData::MmdsValue(serde_json::json!(["a", "b"]))
+        );
+        assert!(matches!(
+            request("/latest/user-data", OutputFormat::Imds),
+            Err(VmmActionError::Mmds(
+                data_store::MmdsDatastoreError::NotFound
+            ))
+        ));
+        assert!(matches!(
+            request("/latest/user-data", OutputFormat::Json),
+            Err(VmmActionError::Mmds(
+                data_store::MmdsDatastoreError::NotFound
+            ))
+        ));
+    }
+
     #[test]
     fn test_preboot_put_mmds() {
         let mmds = Arc::new(Mutex::new(Mmds::default()));
@@ -1112,6 +1983,44 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_mmds_metrics() {
+        let mmds = Arc::new(Mutex::new(Mmds::default()));
+        let data = serde_json::json!({"key1": "value1"});
+        runtime_request_with_mmds(VmmAction::PutMMDS(data.clone()), mmds.clone()).unwrap();
+        // The metric is shared with the other tests writing to a data store, so check the size
+        // this data store reported instead.
+        assert_eq!(
+            mmds.lock().unwrap().data_store_bytes(),
+            serde_json::to_vec(&data).unwrap().len()
+        );
+
+        let patch = serde_json::json!({"key2": "value2"});
+        preboot_request_with_mmds(VmmAction::PatchMMDS(patch), mmds.clone()).unwrap();
+        let data = serde_json::json!({"key1": "value1", "key2": "value2"});
+        assert_eq!(
+            mmds.lock().unwrap().data_store_bytes(),
+            serde_json::to_vec(&data).unwrap().len()
+        );
+
+        // Writes exceeding the data store limit are counted as rejected and leave the data store
+        // size unchanged.
+        let data_store_bytes = mmds.lock().unwrap().data_store_bytes();
+        let rejected_writes = METRICS.mmds.rejected_writes.count();
+        let filling = (0..HTTP_MAX_PAYLOAD_SIZE).map(|_| "X").collect::<String>();
+        let data = serde_json::json!({ "key": filling });
+        assert!(matches!(
+            runtime_request_with_mmds(VmmAction::PutMMDS(data.clone()), mmds.clone()),
+            Err(VmmActionError::MmdsLimitExceeded(_))
+        ));
+        assert!(matches!(
+            preboot_request_with_mmds(VmmAction::PatchMMDS(data), mmds.clone()),
+            Err(VmmActionError::MmdsLimitExceeded(_))
+        ));
+        assert!(METRICS.mmds.rejected_writes.count() >= rejected_writes + 2);
+        assert_eq!(mmds.lock().unwrap().data_store_bytes(), data_store_bytes);
+    }
+
     #[test]
     fn test_preboot_disallowed() {
         fn check_unsupported(res: Result<VmmData, VmmActionError>) {
@@ -1123,9 +2032,42 @@ mod tests {
         }
 
         check_unsupported(preboot_request(VmmAction::FlushMetrics));
+        check_unsupported(preboot_request(VmmAction::GetDeviceConfigSpace(
+            DeviceConfigSpaceRequest {
+                device_type: VirtioDeviceType::Block,
+                device_id: String::from("rootfs"),
+            },
+        )));
+        check_unsupported(preboot_request(VmmAction::GetDeviceMemory(
+            DeviceMemoryRequest {
+                device_type: VirtioDeviceType::Block,
+                device_id: String::from("rootfs"),
+            },
+        )));
+        check_unsupported(preboot_request(VmmAction::GetDeviceStates));
+        #[cfg(feature = "fault-injection")]
+        check_unsupported(preboot_request(VmmAction::InjectDeviceFault(
+            DeviceFaultRequest {
+                device_type: VirtioDeviceType::Block,
+                device_id: String::from("rootfs"),
+                fault: DeviceFault::Clear,
+            },
+        )));
+        check_unsupported(preboot_request(VmmAction::GetBlockRateLimiterState(
+            String::from("rootfs"),
+        )));
+        check_unsupported(preboot_request(VmmAction::GetNetRateLimiterState(
+            String::from("netif"),
+        )));
         check_unsupported(preboot_request(VmmAction::Pause));
+        check_unsupported(preboot_request(VmmAction::GetVcpuStates));
+        check_unsupported(preboot_request(VmmAction::PauseVcpu(0)));
+        check_unsupported(preboot_request(VmmAction::QuiesceIo));
+        check_unsupported(preboot_request(VmmAction::ResumeVcpu(0)));
         check_unsupported(preboot_request(VmmAction::Resume));
         check_unsupported(preboot_request(VmmAction::GetBalloonStats));
+        check_unsupported(preboot_request(VmmAction::GetBalloonFeatures));
+        check_unsupported(preboot_request(VmmAction::CompactGuestMemory(8)));
         check_unsupported(preboot_request(VmmAction::UpdateBalloon(
             BalloonUpdateConfig { amount_mib: 0 },
         )));
@@ -1137,6 +2079,9 @@ mod tests {
         check_unsupported(preboot_request(VmmAction::UpdateBlockDevice(
             BlockDeviceUpdateConfig::default(),
         )));
+        check_unsupported(preboot_request(VmmAction::UpdateEntropyDevice(
+            EntropyDeviceConfig::default(),
+        )));
         check_unsupported(preboot_request(VmmAction::UpdateNetworkInterface(
             NetworkInterfaceUpdateConfig {
                 iface_id: String::new(),
@@ -1149,6 +2094,8 @@ mod tests {
                 snapshot_type: SnapshotType::Full,
                 snapshot_path: PathBuf::new(),
                 mem_file_path: PathBuf::new(),
+                baseline_mem_file_path: None,
+                mem_write_chunk_size_kib: None,
             },
         )));
         #[cfg(target_arch = "x86_64")]
@@ -1174,6 +2121,347 @@ mod tests {
         runtime.handle_request(request)
     }
 
+    #[test]
+    fn test_runtime_flush_metrics() {
+        let res = runtime_request(VmmAction::FlushMetrics);
+        assert!(matches!(res, Ok(VmmData::FlushResult(_))), "{:?}", res);
+    }
+
+    #[test]
+    fn test_runtime_get_device_capabilities() {
+        assert_eq!(
+            runtime_request(VmmAction::GetDeviceCapabilities(VirtioDeviceType::Block)).unwrap(),
+            VmmData::DeviceCapabilities(VirtioDeviceType::Block.capabilities())
+        );
+    }
+
+    #[test]
+    fn test_runtime_get_seccomp_config() {
+        assert_eq!(
+            runtime_request(VmmAction::GetSeccompConfig).unwrap(),
+            VmmData::SeccompConfig(SeccompInfo::default())
+        );
+    }
+
+    #[test]
+    fn test_runtime_health() {
+        assert!(matches!(
+            runtime_request(VmmAction::GetHealth).unwrap(),
+            VmmData::Health(_)
+        ));
+        assert_eq!(
+            runtime_request(VmmAction::ResetHealth).unwrap(),
+            VmmData::Empty
+        );
+    }
+
+    #[test]
+    fn test_runtime_get_rate_limiter_state() {
+        assert!(matches!(
+            runtime_request(VmmAction::GetBlockRateLimiterState(String::from("rootfs"))),
+            Err(VmmActionError::DriveConfig(DriveError::DeviceUpdate(_)))
+        ));
+        assert!(matches!(
+            runtime_request(VmmAction::GetNetRateLimiterState(String::from("netif"))),
+            Err(VmmActionError::NetworkConfig(
+                NetworkInterfaceError::DeviceUpdate(_)
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_runtime_update_entropy_device() {
+        let bucket = Some(TokenBucketConfig {
+            size: 1000,
+            one_time_burst: None,
+            refill_time: 100,
+        });
+        assert!(matches!(
+            runtime_request(VmmAction::UpdateEntropyDevice(EntropyDeviceConfig {
+                rate_limiter: Some(RateLimiterConfig {
+                    bandwidth: bucket,
+                    ops: None,
+                }),
+            })),
+            Err(VmmActionError::EntropyDevice(
+                EntropyDeviceError::DeviceUpdate(_)
+            ))
+        ));
+        assert!(matches!(
+            runtime_request(VmmAction::UpdateEntropyDevice(EntropyDeviceConfig {
+                rate_limiter: Some(RateLimiterConfig {
+                    bandwidth: None,
+                    ops: bucket,
+                }),
+            })),
+            Err(VmmActionError::EntropyDevice(
+                EntropyDeviceError::UpdateOpsRateLimiter
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_runtime_get_network_interface() {
+        assert!(matches!(
+            runtime_request(VmmAction::GetNetworkInterface(String::from("netif"))),
+            Err(VmmActionError::NetworkConfig(
+                NetworkInterfaceError::DeviceNotFound(_)
+            ))
+        ));
+    }
+
+    #[test]
+    fn test_runtime_get_kvm_capabilities() {
+        assert!(matches!(
+            runtime_request(VmmAction::GetKvmCapabilities).unwrap(),
+            VmmData::KvmCapabilities(_)
+        ));
+    }
+
+    #[test]
+    fn test_runtime_create_snapshot_baseline() {
+        let res = runtime_request(VmmAction::CreateSnapshot(CreateSnapshotParams {
+            snapshot_type: SnapshotType::Full,
+            snapshot_path: PathBuf::new(),
+            mem_file_path: PathBuf::new(),
+            baseline_mem_file_path: Some(PathBuf::new()),
+            mem_write_chunk_size_kib: None,
+        }));
+        assert!(
+            matches!(res, Err(VmmActionError::NotSupported(_))),
+            "{:?}",
+            res
+        );
+
+        // Without a baseline, diff snapshots need dirty page tracking.
+        let res = runtime_request(VmmAction::CreateSnapshot(CreateSnapshotParams {
+            snapshot_type: SnapshotType::Diff,
+            snapshot_path: PathBuf::new(),
+            mem_file_path: PathBuf::new(),
+            baseline_mem_file_path: None,
+            mem_write_chunk_size_kib: None,
+        }));
+        assert!(
+            matches!(res, Err(VmmActionError::NotSupported(_))),
+            "{:?}",
+            res
+        );
+    }
+
+    #[test]
+    fn test_runtime_create_snapshot_mem_backing_file() {
+        // The restored microVM would not be backed by the file anymore.
+        let mut vm_res = VmResources::default();
+        vm_res.vm_config.mem_backing_file = Some(PathBuf::from("/dev/shm/guest_mem"));
+        let vmm = Arc::new(Mutex::new(default_vmm()));
+        let mut runtime = RuntimeApiController::new(vm_res, vmm.clone());
+
+        let res = runtime.handle_request(VmmAction::CreateSnapshot(CreateSnapshotParams {
+            snapshot_type: SnapshotType::Full,
+            snapshot_path: PathBuf::new(),
+            mem_file_path: PathBuf::new(),
+            baseline_mem_file_path: None,
+            mem_write_chunk_size_kib: None,
+        }));
+        assert_eq!(
+            res.unwrap_err().to_string(),
+            "The requested operation is not supported: Snapshots are not allowed on uVMs with \
+             guest memory backed by a file."
+        );
+    }
+
+    #[test]
+    fn test_runtime_get_balloon_features() {
+        let res = runtime_request(VmmAction::GetBalloonFeatures);
+        assert!(
+            matches!(
+                res,
+                Err(VmmActionError::BalloonConfig(
+                    BalloonConfigError::CreateFailure(BalloonError::DeviceNotFound)
+                ))
+            ),
+            "{:?}",
+            res
+        );
+    }
+
+    #[test]
+    fn test_runtime_compact_guest_memory() {
+        let res = runtime_request(VmmAction::CompactGuestMemory(8));
+        assert!(
+            matches!(
+                res,
+                Err(VmmActionError::BalloonConfig(
+                    BalloonConfigError::CreateFailure(BalloonError::DeviceNotFound)
+                ))
+            ),
+            "{:?}",
+            res
+        );
+
+        let vmm = Arc::new(Mutex::new(default_vmm()));
+        let mut runtime = RuntimeApiController::new(VmResources::default(), vmm);
+        let res = runtime.guest_memory_compaction();
+        assert!(
+            matches!(
+                res,
+                Err(VmmActionError::BalloonConfig(
+                    BalloonConfigError::CreateFailure(BalloonError::DeviceNotFound)
+                ))
+            ),
+            "{:?}",
+            res
+        );
+    }
+
+    #[test]
+    fn test_runtime_get_device_config_space() {
+        let res = runtime_request(VmmAction::GetDeviceConfigSpace(DeviceConfigSpaceRequest {
+            device_type: VirtioDeviceType::Net,
+            device_id: String::from("eth0"),
+        }));
+        assert!(
+            matches!(
+                res,
+                Err(VmmActionError::InternalVmm(VmmError::DeviceManager(
+                    MmioError::DeviceNotFound
+                )))
+            ),
+            "{:?}",
+            res
+        );
+    }
+
+    #[test]
+    fn test_runtime_get_device_memory() {
+        let res = runtime_request(VmmAction::GetDeviceMemory(DeviceMemoryRequest {
+            device_type: VirtioDeviceType::Net,
+            device_id: String::from("eth0"),
+        }));
+        assert!(
+            matches!(
+                res,
+                Err(VmmActionError::InternalVmm(VmmError::DeviceManager(
+                    MmioError::DeviceNotFound
+                )))
+            ),
+            "{:?}",
+            res
+        );
+    }
+
+    #[test]
+    #[cfg(feature = "fault-injection")]
+    fn test_runtime_inject_device_fault() {
+        let res = runtime_request(VmmAction::InjectDeviceFault(DeviceFaultRequest {
+            device_type: VirtioDeviceType::Block,
+            device_id: String::from("rootfs"),
+            fault: DeviceFault::BlockReadError { count: 1 },
+        }));
+        assert!(
+            matches!(
+                res,
+                Err(VmmActionError::InternalVmm(VmmError::DeviceManager(
+                    MmioError::DeviceNotFound
+                )))
+            ),
+            "{:?}",
+            res
+        );
+
+        let res = runtime_request(VmmAction::InjectDeviceFault(DeviceFaultRequest {
+            device_type: VirtioDeviceType::Vsock,
+            device_id: String::from("vsock"),
+            fault: DeviceFault::QueueStall,
+        }));
+        assert!(
+            matches!(
+                res,
+                Err(VmmActionError::InternalVmm(
+                    VmmError::UnsupportedDeviceFault(
+                        DeviceFault::QueueStall,
+                        VirtioDeviceType::Vsock
+                    )
+                ))
+            ),
+            "{:?}",
+            res
+        );
+    }
+
+    #[test]
+    fn test_runtime_get_device_states() {
+        let res = runtime_request(VmmAction::GetDeviceStates);
+        assert!(
+            matches!(res, Err(VmmActionError::NotSupported(_))),
+            "{:?}",
+            res
+        );
+    }
+
+    #[test]
+    fn test_runtime_get_vcpu_states() {
+        Vcpu::register_kick_signal_handler();
+        let run_states = [
+            VcpuRunState::Running,
+            VcpuRunState::Paused,
+            VcpuRunState::InMmio,
+            VcpuRunState::Stopped,
+            VcpuRunState::Exited,
+        ];
+        let mut vmm = default_vmm();
+        for run_state in run_states {
+            // Mock vcpus, whose threads only wait for the `Finish` event sent when the Vmm stops.
+            let (event_sender, event_receiver) = std::sync::mpsc::channel();
+            let (_, response_receiver) = std::sync::mpsc::channel();
+            let vcpu_thread = std::thread::spawn(move || {
+                let _ = event_receiver.recv();
+            });
+            let shared_run_state = SharedVcpuRunState::default();
+            shared_run_state.set(run_state);
+            vmm.vcpus_handles.push(VcpuHandle::new(
+                event_sender,
+                response_receiver,
+                vcpu_thread,
+                shared_run_state,
+            ));
+        }
+
+        let vmm = Arc::new(Mutex::new(vmm));
+        let mut runtime = RuntimeApiController::new(VmResources::default(), vmm.clone());
+        assert_eq!(
+            runtime.handle_request(VmmAction::GetVcpuStates).unwrap(),
+            VmmData::VcpuStates(run_states.to_vec())
+        );
+        vmm.lock().unwrap().stop(FcExitCode::Ok);
+    }
+
+    #[test]
+    fn test_runtime_pause_vcpu_not_running() {
+        // Single vCPUs cannot change state while the microVM is not running.
+        for request in [VmmAction::PauseVcpu(0), VmmAction::ResumeVcpu(0)] {
+            let res = runtime_request(request);
+            assert!(
+                matches!(
+                    res,
+                    Err(VmmActionError::InternalVmm(
+                        VmmError::VcpuStateChangeNotAllowed
+                    ))
+                ),
+                "{:?}",
+                res
+            );
+        }
+    }
+
+    #[test]
+    fn test_runtime_get_memory_layout() {
+        assert_eq!(
+            runtime_request(VmmAction::GetMemoryLayout).unwrap(),
+            VmmData::MemoryLayout(MemoryLayout::from(&VmConfig::default()))
+        );
+    }
+
     #[test]
     fn test_runtime_get_vm_config() {
         assert_eq!(
@@ -1182,6 +2470,31 @@ mod tests {
         );
     }
 
+    #[test]
+    fn test_runtime_validate_config() {
+        let kernel_file = TempFile::new().unwrap();
+        let config_json = format!(
+            r#"{{"boot-source": {{"kernel_image_path": "{}"}}}}"#,
+            kernel_file.as_path().to_str().unwrap()
+        );
+        let res = runtime_request(VmmAction::ValidateConfig(config_json));
+        assert!(matches!(res, Ok(VmmData::FullVmConfig(_))), "{:?}", res);
+
+        let res = runtime_request(VmmAction::ValidateConfig(String::from(
+            r#"{"boot-source": {"kernel_image_path": "/invalid/path"}}"#,
+        )));
+        assert!(
+            matches!(
+                res,
+                Err(VmmActionError::ValidateConfig(ResourcesError::BootSource(
+                    _
+                )))
+            ),
+            "{:?}",
+            res
+        );
+    }
+
     #[test]
     fn test_runtime_disallowed() {
         fn check_unsupported(res: Result<VmmData, VmmActionError>) {
@@ -1201,6 +2514,8 @@ mod tests {
             show_level: Some(false),
             show_log_origin: Some(false),
             module: None,
+            format: None,
+            filter: None,
         })));
         check_unsupported(runtime_request(VmmAction::ConfigureMetrics(
             MetricsConfig {
@@ -1218,6 +2533,8 @@ mod tests {
                 path_on_host: Some(String::new()),
                 rate_limiter: None,
                 file_engine_type: None,
+                direct: None,
+                discard: None,
 
                 socket: None,
             },
@@ -1229,6 +2546,7 @@ mod tests {
                 guest_mac: None,
                 rx_rate_limiter: None,
                 tx_rate_limiter: None,
+                mtu: None,
             },
         )));
         check_unsupported(runtime_request(VmmAction::SetVsockDevice(
@@ -1251,6 +2569,7 @@ mod tests {
         check_unsupported(runtime_request(VmmAction::SetMmdsConfiguration(
             MmdsConfig {
                 ipv4_address: None,
+                rate_limiter: None,
                 version: MmdsVersion::default(),
                 network_interfaces: Vec::new(),
             },
@@ -1272,5 +2591,12 @@ mod tests {
         check_unsupported(runtime_request(VmmAction::SetEntropyDevice(
             EntropyDeviceConfig::default(),
         )));
+        check_unsupported(runtime_request(VmmAction::ApplyConfig(String::from("{}"))));
+        check_unsupported(runtime_request(VmmAction::SetWatchdogDevice(
+            WatchdogDeviceConfig {
+                timeout_s: 30,
+                action: Default::default(),
+            },
+        )));
     }
 }
@@ -0,0 +1,399 @@
+// Copyright 2024 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+//! Audit log of the syscalls denied by the seccomp filters.
+//!
+//! The `SIGSYS` handler can neither allocate nor take locks, so it only pushes a record to a
+//! fixed size lock-free ring and notifies an eventfd. A background thread drains the ring and
+//! appends one line per record to the audit file.
+
+use std::cell::Cell;
+use std::fs::{File, OpenOptions};
+use std::os::fd::{AsRawFd, RawFd};
+use std::path::Path;
+use std::sync::atomic::{AtomicI32, AtomicI64, AtomicU64, AtomicUsize, Ordering};
+use std::sync::Arc;
+use std::{io, thread};
+
+use seccompiler::BpfProgram;
+use utils::time::{get_time_us, ClockType};
+use vmm_sys_util::eventfd::EventFd;
+
+/// Number of records the ring can hold before the audit thread drains it.
+const RING_SIZE: usize = 64;
+/// Enough room for a record line, i.e. three 20 digit numbers and their labels.
+const LINE_SIZE: usize = 96;
+
+/// The ring shared between the `SIGSYS` handler and the audit thread.
+static RING: AuditRing = AuditRing::new();
+/// The audit file, or -1 if auditing is disabled.
+static AUDIT_FD: AtomicI32 = AtomicI32::new(-1);
+/// The eventfd waking up the audit thread, or -1 if auditing is disabled.
+static NOTIFY_FD: AtomicI32 = AtomicI32::new(-1);
+
+thread_local! {
+    // Const initialized without a destructor, so it can be read from a signal handler.
+    static THREAD_ID: Cell<i64> = const { Cell::new(0) };
+}
+
+/// Errors associated with the seccomp audit log.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum SeccompAuditError {
+    /// The seccomp audit log is already initialized.
+    AlreadyInitialized,
+    /// Cannot open the seccomp audit log: {0}
+    Open(io::Error),
+    /// Cannot create the seccomp audit eventfd: {0}
+    EventFd(io::Error),
+    /// Cannot spawn the seccomp audit thread: {0}
+    Spawn(io::Error),
+}
+
+/// A syscall denied by the seccomp filters.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct AuditRecord {
+    /// Real time at which the syscall was denied, in microseconds.
+    pub timestamp_us: u64,
+    /// Id of the thread issuing the syscall, 0 if the thread was not registered.
+    pub tid: i64,
+    /// Number of the denied syscall.
+    pub syscall: i64,
+}
+
+impl AuditRecord {
+    /// Formats the record as a single line, without allocating.
+    fn format(&self, buf: &mut [u8; LINE_SIZE]) -> usize {
+        let mut len = 0;
+        len += format_u64(self.timestamp_us, &mut buf[len..]);
+        len += copy_bytes(b" tid=", &mut buf[len..]);
+        len += format_i64(self.tid, &mut buf[len..]);
+        len += copy_bytes(b" syscall=", &mut buf[len..]);
+        len += format_i64(self.syscall, &mut buf[len..]);
+        len += copy_bytes(b"\n", &mut buf[len..]);
+        len
+    }
+}
+
+#[derive(Debug)]
+struct Slot {
+    // Position the slot is expected to be written at, plus one once it holds a record.
+    sequence: AtomicUsize,
+    timestamp_us: AtomicU64,
+    tid: AtomicI64,
+    syscall: AtomicI64,
+}
+
+impl Slot {
+    const fn new(sequence: usize) -> Self {
+        Slot {
+            sequence: AtomicUsize::new(sequence),
+            timestamp_us: AtomicU64::new(0),
+            tid: AtomicI64::new(0),
+            syscall: AtomicI64::new(0),
+        }
+    }
+}
+
+/// Bounded lock-free queue of audit records, with sequenced slots as described in
+/// <https://www.1024cores.net/home/lock-free-algorithms/queues/bounded-mpmc-queue>.
+///
+/// Both pushing and popping are async-signal-safe.
+#[derive(Debug)]
+struct AuditRing {
+    slots: [Slot; RING_SIZE],
+    enqueue_pos: AtomicUsize,
+    dequeue_pos: AtomicUsize,
+    dropped: AtomicU64,
+}
+
+impl AuditRing {
+    const fn new() -> Self {
+        let mut slots = [const { Slot::new(0) }; RING_SIZE];
+        let mut i = 0;
+        while i < RING_SIZE {
+            slots[i] = Slot::new(i);
+            i += 1;
+        }
+
+        AuditRing {
+            slots,
+            enqueue_pos: AtomicUsize::new(0),
+            dequeue_pos: AtomicUsize::new(0),
+            dropped: AtomicU64::new(0),
+        }
+    }
+
+    /// Pushes a record, or counts it as dropped if the ring is full.
+    fn push(&self, record: AuditRecord) -> bool {
+        let mut pos = self.enqueue_pos.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[pos % RING_SIZE];
+            let sequence = slot.sequence.load(Ordering::Acquire);
+
+            if sequence == pos {
+                match self.enqueue_pos.compare_exchange_weak(
+                    pos,
+                    pos + 1,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        slot.timestamp_us
+                            .store(record.timestamp_us, Ordering::Relaxed);
+                        slot.tid.store(record.tid, Ordering::Relaxed);
+                        slot.syscall.store(record.syscall, Ordering::Relaxed);
+                        slot.sequence.store(pos + 1, Ordering::Release);
+                        return true;
+                    }
+                    Err(current) => pos = current,
+                }
+            } else if sequence < pos {
+                // The slot still holds the record pushed one lap ago.
+                self.dropped.fetch_add(1, Ordering::Relaxed);
+                return false;
+            } else {
+                pos = self.enqueue_pos.load(Ordering::Relaxed);
+            }
+        }
+    }
+
+    /// Pops the oldest record, if any.
+    fn pop(&self) -> Option<AuditRecord> {
+        let mut pos = self.dequeue_pos.load(Ordering::Relaxed);
+        loop {
+            let slot = &self.slots[pos % RING_SIZE];
+            let sequence = slot.sequence.load(Ordering::Acquire);
+            let expected = pos + 1;
+
+            if sequence == expected {
+                match self.dequeue_pos.compare_exchange_weak(
+                    pos,
+                    expected,
+                    Ordering::Relaxed,
+                    Ordering::Relaxed,
+                ) {
+                    Ok(_) => {
+                        let record = AuditRecord {
+                            timestamp_us: slot.timestamp_us.load(Ordering::Relaxed),
+                            tid: slot.tid.load(Ordering::Relaxed),
+                            syscall: slot.syscall.load(Ordering::Relaxed),
+                        };
+                        slot.sequence.store(pos + RING_SIZE, Ordering::Release);
+                        return Some(record);
+                    }
+                    Err(current) => pos = current,
+                }
+            } else if sequence < expected {
+                // The slot has not been written yet, the ring is empty.
+                return None;
+            } else {
+                pos = self.dequeue_pos.load(Ordering::Relaxed);
+            }
+        }
+    }
+}
+
+/// Starts logging the denied syscalls to the file at `path`, using a background thread running
+/// under `seccomp_filter`.
+pub fn init(path: &Path, seccomp_filter: Arc<BpfProgram>) -> Result<(), SeccompAuditError> {
+    let file = OpenOptions::new()
+        .create(true)
+        .append(true)
+        .open(path)
+        .map_err(SeccompAuditError::Open)?;
+    let notify_evt = EventFd::new(0).map_err(SeccompAuditError::EventFd)?;
+
+    AUDIT_FD
+        .compare_exchange(-1, file.as_raw_fd(), Ordering::AcqRel, Ordering::Acquire)
+        .map_err(|_| SeccompAuditError::AlreadyInitialized)?;
+    NOTIFY_FD.store(notify_evt.as_raw_fd(), Ordering::Release);
+
+    thread::Builder::new()
+        .name("fc_seccomp_audit".to_owned())
+        .spawn(move || audit_thread(file, notify_evt, seccomp_filter))
+        .map_err(|err| {
+            NOTIFY_FD.store(-1, Ordering::Release);
+            AUDIT_FD.store(-1, Ordering::Release);
+            SeccompAuditError::Spawn(err)
+        })?;
+    Ok(())
+}
+
+fn audit_thread(file: File, notify_evt: EventFd, seccomp_filter: Arc<BpfProgram>) {
+    if let Err(err) = crate::seccomp_filters::install_filter("seccomp audit", &seccomp_filter) {
+        panic!(
+            "Failed to set the requested seccomp filters on the seccomp audit thread: {}",
+            err
+        );
+    }
+
+    // The file and eventfd are kept open for as long as the process runs.
+    while notify_evt.read().is_ok() {
+        drain(file.as_raw_fd());
+    }
+}
+
+/// Remembers the id of the current thread, to be reported in its audit records. Must be called
+/// before installing the seccomp filter of the thread, which may deny `gettid`.
+pub fn register_thread() {
+    // SAFETY: `gettid` has no preconditions.
+    let tid = unsafe { libc::syscall(libc::SYS_gettid) };
+    THREAD_ID.with(|id| id.set(tid));
+}
+
+/// Records a denied syscall. Async-signal-safe.
+pub fn record(syscall: i64) {
+    let notify_fd = NOTIFY_FD.load(Ordering::Acquire);
+    if notify_fd < 0 {
+        return;
+    }
+
+    RING.push(AuditRecord {
+        timestamp_us: get_time_us(ClockType::Real),
+        tid: THREAD_ID.with(Cell::get),
+        syscall,
+    });
+    let value = 1u64;
+    // SAFETY: Safe because the eventfd stays open for as long as the process runs, and the
+    // buffer is valid for the given length. Failing to notify only delays the record.
+    unsafe {
+        libc::write(
+            notify_fd,
+            (&value as *const u64).cast(),
+            std::mem::size_of::<u64>(),
+        )
+    };
+}
+
+/// Writes the pending records to the audit file from the calling thread. Async-signal-safe, to
+/// be used when the process exits before the audit thread gets to run.
+pub fn flush() {
+    let audit_fd = AUDIT_FD.load(Ordering::Acquire);
+    if audit_fd >= 0 {
+        drain(audit_fd);
+    }
+}
+
+fn drain(audit_fd: RawFd) {
+    let mut buf = [0u8; LINE_SIZE];
+
+    let dropped = RING.dropped.swap(0, Ordering::Relaxed);
+    if dropped > 0 {
+        let mut len = format_u64(dropped, &mut buf);
+        len += copy_bytes(b" records dropped\n", &mut buf[len..]);
+        write_line(audit_fd, &buf[..len]);
+    }
+
+    while let Some(record) = RING.pop() {
+        let len = record.format(&mut buf);
+        write_line(audit_fd, &buf[..len]);
+    }
+}
+
+fn write_line(audit_fd: RawFd, line: &[u8]) {
+    // SAFETY: Safe because the audit file stays open for as long as the process runs, and the
+    // buffer is valid for the given length. Each line is written with a single append, so lines
+    // written by concurrent drains do not interleave.
+    unsafe { libc::write(audit_fd, line.as_ptr().cast(), line.len()) };
+}
+
+fn copy_bytes(src: &[u8], dst: &mut [u8]) -> usize {
+    let len = src.len().min(dst.len());
+    dst[..len].copy_from_slice(&src[..len]);
+    len
+}
+
+fn format_u64(mut value: u64, dst: &mut [u8]) -> usize {
+    let mut digits = [0u8; 20];
+    let mut start = digits.len();
+    loop {
+        start -= 1;
+        // The remainder is a single digit.
+        #[allow(clippy::cast_possible_truncation)]
+        let digit = (value % 10) as u8;
+        digits[start] = b'0' + digit;
+        value /= 10;
+        if value == 0 {
+            break;
+        }
+    }
+    copy_bytes(&digits[start..], dst)
+}
+
+fn format_i64(value: i64, dst: &mut [u8]) -> usize {
+    if value < 0 {
+        let len = copy_bytes(b"-", dst);
+        len + format_u64(value.unsigned_abs(), &mut dst[len..])
+    } else {
+        format_u64(value.unsigned_abs(), dst)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn record(syscall: i64) -> AuditRecord {
+        AuditRecord {
+            timestamp_us: 1,
+            tid: 2,
+            syscall,
+        }
+    }
+
+    #[test]
+    fn test_audit_ring() {
+        let ring = AuditRing::new();
+        let size = i64::try_from(RING_SIZE).unwrap();
+        assert_eq!(ring.pop(), None);
+
+        // Records are popped in order, over several laps.
+        for syscall in 0..(3 * size) {
+            assert!(ring.push(record(syscall)));
+            assert_eq!(ring.pop(), Some(record(syscall)));
+        }
+        assert_eq!(ring.pop(), None);
+
+        // Records pushed to a full ring are dropped.
+        for syscall in 0..size {
+            assert!(ring.push(record(syscall)));
+        }
+        assert!(!ring.push(record(-1)));
+        assert_eq!(ring.dropped.load(Ordering::Relaxed), 1);
+        for syscall in 0..size {
+            assert_eq!(ring.pop(), Some(record(syscall)));
+        }
+        assert_eq!(ring.pop(), None);
+    }
+
+    #[test]
+    fn test_audit_record_format() {
+        let mut buf = [0u8; LINE_SIZE];
+        let len = AuditRecord {
+            timestamp_us: 1_700_000_000_000_000,
+            tid: 42,
+            syscall: 258,
+        }
+        .format(&mut buf);
+        assert_eq!(&buf[..len], b"1700000000000000 tid=42 syscall=258\n");
+
+        let len = AuditRecord {
+            timestamp_us: 0,
+            tid: 0,
+            syscall: -1,
+        }
+        .format(&mut buf);
+        assert_eq!(&buf[..len], b"0 tid=0 syscall=-1\n");
+
+        let len = AuditRecord {
+            timestamp_us: u64::MAX,
+            tid: i64::MIN,
+            syscall: i64::MAX,
+        }
+        .format(&mut buf);
+        assert_eq!(
+            &buf[..len],
+            format!("{} tid={} syscall={}\n", u64::MAX, i64::MIN, i64::MAX).as_bytes()
+        );
+    }
+}
@@ -1,8 +1,12 @@
 // Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
+use std::str::FromStr;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
-use seccompiler::BpfThreadMap;
+use seccompiler::{BpfProgramRef, BpfThreadMap, InstallationError};
+
+use crate::logger::{warn, IncMetric, METRICS};
 
 /// Retrieve empty seccomp filters.
 pub fn get_empty_filters() -> BpfThreadMap {
@@ -12,3 +16,125 @@ pub fn get_empty_filters() -> BpfThreadMap {
     map.insert("vcpu".to_string(), Arc::new(vec![]));
     map
 }
+
+/// What happens when the seccomp filter of a thread cannot be installed.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub enum SeccompFailurePolicy {
+    /// The thread fails, which terminates Firecracker or fails the microVM start.
+    #[default]
+    Fail,
+    /// A warning is logged and the thread runs without seccomp filter.
+    Warn,
+}
+
+/// Error type for [`<SeccompFailurePolicy as FromStr>::from_str`].
+#[derive(Debug, PartialEq, Eq, thiserror::Error)]
+#[error("Failed to parse string to seccomp failure policy: {0}")]
+pub struct SeccompFailurePolicyFromStrError(String);
+
+impl FromStr for SeccompFailurePolicy {
+    type Err = SeccompFailurePolicyFromStrError;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_ascii_lowercase().as_str() {
+            "fail" => Ok(Self::Fail),
+            "warn" => Ok(Self::Warn),
+            _ => Err(SeccompFailurePolicyFromStrError(String::from(s))),
+        }
+    }
+}
+
+static WARN_ON_FAILURE: AtomicBool = AtomicBool::new(false);
+
+/// Sets the policy applied by [`install_filter`]. Must be called before spawning the threads.
+pub fn set_failure_policy(policy: SeccompFailurePolicy) {
+    WARN_ON_FAILURE.store(policy == SeccompFailurePolicy::Warn, Ordering::Release);
+}
+
+/// Returns the policy applied by [`install_filter`].
+pub fn failure_policy() -> SeccompFailurePolicy {
+    match WARN_ON_FAILURE.load(Ordering::Acquire) {
+        true => SeccompFailurePolicy::Warn,
+        false => SeccompFailurePolicy::Fail,
+    }
+}
+
+/// Installs `filter` on the current thread, named `thread` in the logs.
+///
+/// Under the [`SeccompFailurePolicy::Warn`] policy, an installation failure is logged and counted
+/// in the `seccomp.install_fails` metric instead of being returned.
+pub fn install_filter(thread: &str, filter: BpfProgramRef) -> Result<(), InstallationError> {
+    install_filter_with_policy(thread, filter, failure_policy())
+}
+
+fn install_filter_with_policy(
+    thread: &str,
+    filter: BpfProgramRef,
+    policy: SeccompFailurePolicy,
+) -> Result<(), InstallationError> {
+    match seccompiler::apply_filter(filter) {
+        Err(err) if policy == SeccompFailurePolicy::Warn => {
+            METRICS.seccomp.install_fails.inc();
+            warn!(
+                "Running the {thread} thread without seccomp filter, it cannot be installed: {err}"
+            );
+            Ok(())
+        }
+        result => result,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use seccompiler::sock_filter;
+
+    use super::*;
+
+    #[test]
+    fn test_seccomp_failure_policy_from_str() {
+        assert_eq!(
+            SeccompFailurePolicy::from_str("fail"),
+            Ok(SeccompFailurePolicy::Fail)
+        );
+        assert_eq!(
+            SeccompFailurePolicy::from_str("WARN"),
+            Ok(SeccompFailurePolicy::Warn)
+        );
+        assert_eq!(
+            SeccompFailurePolicy::from_str("ignore"),
+            Err(SeccompFailurePolicyFromStrError(String::from("ignore")))
+        );
+    }
+
+    #[test]
+    fn test_install_filter_with_policy() {
+        // A filter longer than allowed by the kernel is rejected before anything is installed.
+        let instruction = sock_filter {
+            code: 0,
+            jt: 0,
+            jf: 0,
+            k: 0,
+        };
+        let filter = vec![instruction; usize::from(u16::MAX) + 1];
+
+        assert!(matches!(
+            install_filter_with_policy("test", &filter, SeccompFailurePolicy::Fail),
+            Err(InstallationError::FilterTooLarge)
+        ));
+        assert_eq!(METRICS.seccomp.install_fails.count(), 0);
+
+        install_filter_with_policy("test", &filter, SeccompFailurePolicy::Warn).unwrap();
+        assert_eq!(METRICS.seccomp.install_fails.count(), 1);
+
+        // An empty filter installs nothing, under any policy.
+        install_filter_with_policy("test", &[], SeccompFailurePolicy::Fail).unwrap();
+    }
+
+    #[test]
+    fn test_failure_policy() {
+        assert_eq!(failure_policy(), SeccompFailurePolicy::Fail);
+        set_failure_policy(SeccompFailurePolicy::Warn);
+        assert_eq!(failure_policy(), SeccompFailurePolicy::Warn);
+        set_failure_policy(SeccompFailurePolicy::Fail);
+        assert_eq!(failure_policy(), SeccompFailurePolicy::Fail);
+    }
+}
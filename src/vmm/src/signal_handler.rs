@@ -8,7 +8,7 @@ use log::error;
 
 use crate::logger::{IncMetric, StoreMetric, METRICS};
 use crate::utils::signal::register_signal_handler;
-use crate::FcExitCode;
+use crate::{seccomp_audit, FcExitCode};
 
 // The offset of `si_syscall` (offending syscall identifier) within the siginfo structure
 // expressed as an `(u)int*`.
@@ -72,6 +72,11 @@ fn log_sigsys_err(si_code: c_int, info: *mut siginfo_t) {
         "Shutting down VM after intercepting a bad syscall ({}).",
         syscall
     );
+
+    seccomp_audit::record(i64::from(syscall));
+    // The process exits right away, without giving the audit thread the chance to run.
+    #[cfg(not(test))]
+    seccomp_audit::flush();
 }
 
 fn empty_fn(_si_code: c_int, _info: *mut siginfo_t) {}
@@ -174,20 +179,28 @@ pub fn register_signal_handlers() -> vmm_sys_util::errno::Result<()> {
 #[cfg(test)]
 mod tests {
     #![allow(clippy::undocumented_unsafe_blocks)]
-    use std::{process, thread};
+    use std::sync::Arc;
+    use std::time::Duration;
+    use std::{fs, process, thread};
 
     use libc::syscall;
     use seccompiler::sock_filter;
+    use vmm_sys_util::tempfile::TempFile;
 
     use super::*;
 
     #[test]
     fn test_signal_handler() {
+        let audit_log = TempFile::new().unwrap();
+        seccomp_audit::init(audit_log.as_path(), Arc::new(vec![])).unwrap();
+
         let child = thread::spawn(move || {
             register_signal_handlers().unwrap();
 
             let filter = make_test_seccomp_bpf_filter();
 
+            seccomp_audit::register_thread();
+            let tid = unsafe { libc::syscall(libc::SYS_gettid) };
             seccompiler::apply_filter(&filter).unwrap();
             assert_eq!(METRICS.seccomp.num_faults.fetch(), 0);
 
@@ -235,8 +248,10 @@ mod tests {
             unsafe {
                 syscall(libc::SYS_kill, process::id(), SIGILL);
             }
+
+            tid
         });
-        child.join().unwrap();
+        let tid = child.join().unwrap();
 
         assert!(METRICS.seccomp.num_faults.fetch() >= 1);
         assert!(METRICS.signals.sigbus.fetch() >= 1);
@@ -246,6 +261,18 @@ mod tests {
         assert!(METRICS.signals.sigpipe.count() >= 1);
         assert!(METRICS.signals.sighup.fetch() >= 1);
         assert!(METRICS.signals.sigill.fetch() >= 1);
+
+        // The audit thread records the denied syscall.
+        let expected = format!(" tid={tid} syscall={}\n", libc::SYS_mkdirat);
+        let mut audit_records = String::new();
+        for _ in 0..100 {
+            audit_records = fs::read_to_string(audit_log.as_path()).unwrap();
+            if audit_records.contains(&expected) {
+                break;
+            }
+            thread::sleep(Duration::from_millis(10));
+        }
+        assert!(audit_records.contains(&expected), "{audit_records}");
     }
 
     fn make_test_seccomp_bpf_filter() -> Vec<sock_filter> {
@@ -45,6 +45,7 @@ impl MockBootSourceConfig {
             kernel_image_path: kernel_image_path(None),
             initrd_path: None,
             boot_args: None,
+            rng_seed: false,
         })
     }
 
@@ -102,6 +103,11 @@ impl MockVmConfig {
         self.0.track_dirty_pages = true;
         self
     }
+
+    pub fn with_vcpu_count(mut self, vcpu_count: u8) -> Self {
+        self.0.vcpu_count = vcpu_count;
+        self
+    }
 }
 
 generate_from!(MockBootSourceConfig, BootSourceConfig);
@@ -3,18 +3,20 @@
 
 #![allow(missing_docs)]
 
-use std::sync::{Arc, Mutex};
+use std::sync::{Arc, Mutex, OnceLock};
 
 use vm_memory::GuestAddress;
 use vmm_sys_util::tempdir::TempDir;
+use vmm_sys_util::tempfile::TempFile;
 
 use crate::builder::build_microvm_for_boot;
+use crate::logger::{LoggerConfig, LOGGER};
 use crate::resources::VmResources;
 use crate::seccomp_filters::get_empty_filters;
 use crate::test_utils::mock_resources::{MockBootSourceConfig, MockVmConfig, MockVmResources};
 use crate::vmm_config::boot_source::BootSourceConfig;
 use crate::vmm_config::instance_info::InstanceInfo;
-use crate::vmm_config::machine_config::HugePageConfig;
+use crate::vmm_config::machine_config::{HugePageConfig, MachineConfig};
 use crate::vstate::memory::{GuestMemoryExtension, GuestMemoryMmap};
 use crate::{EventManager, Vmm};
 
@@ -45,9 +47,22 @@ pub fn arch_mem(mem_size_bytes: usize) -> GuestMemoryMmap {
 }
 
 pub fn create_vmm(
-    _kernel_image: Option<&str>,
+    kernel_image: Option<&str>,
     is_diff: bool,
     boot_microvm: bool,
+) -> (Arc<Mutex<Vmm>>, EventManager) {
+    let vm_config = if is_diff {
+        MockVmConfig::new().with_dirty_page_tracking()
+    } else {
+        MockVmConfig::new()
+    };
+    create_vmm_with_vm_config(kernel_image, vm_config.into(), boot_microvm)
+}
+
+pub fn create_vmm_with_vm_config(
+    _kernel_image: Option<&str>,
+    vm_config: MachineConfig,
+    boot_microvm: bool,
 ) -> (Arc<Mutex<Vmm>>, EventManager) {
     let mut event_manager = EventManager::new().unwrap();
     let empty_seccomp_filters = get_empty_filters();
@@ -60,14 +75,10 @@ pub fn create_vmm(
         Some(kernel) => boot_source_cfg.with_kernel(kernel).into(),
         None => boot_source_cfg.into(),
     };
-    let mock_vm_res = MockVmResources::new().with_boot_source(boot_source_cfg);
-    let resources: VmResources = if is_diff {
-        mock_vm_res
-            .with_vm_config(MockVmConfig::new().with_dirty_page_tracking().into())
-            .into()
-    } else {
-        mock_vm_res.into()
-    };
+    let resources: VmResources = MockVmResources::new()
+        .with_boot_source(boot_source_cfg)
+        .with_vm_config(vm_config)
+        .into();
 
     let vmm = build_microvm_for_boot(
         &InstanceInfo::default(),
@@ -92,6 +103,17 @@ pub fn default_vmm_no_boot(kernel_image: Option<&str>) -> (Arc<Mutex<Vmm>>, Even
     create_vmm(kernel_image, false, false)
 }
 
+pub fn multi_vcpu_vmm(
+    kernel_image: Option<&str>,
+    vcpu_count: u8,
+) -> (Arc<Mutex<Vmm>>, EventManager) {
+    create_vmm_with_vm_config(
+        kernel_image,
+        MockVmConfig::new().with_vcpu_count(vcpu_count).into(),
+        true,
+    )
+}
+
 #[cfg(target_arch = "x86_64")]
 pub fn dirty_tracking_vmm(kernel_image: Option<&str>) -> (Arc<Mutex<Vmm>>, EventManager) {
     create_vmm(kernel_image, true, true)
@@ -137,3 +159,34 @@ pub fn create_tmp_socket() -> (TempDir, String) {
 
     (tmp_dir, tmp_socket_path)
 }
+
+/// File the logs of the process are written to, once `capture_logs` was called.
+static CAPTURED_LOGS: OnceLock<TempFile> = OnceLock::new();
+
+/// Redirects the logs of the process to a temporary file, shared by all the tests of the
+/// process. Tests must look for the lines they expect among the lines logged by the others.
+pub fn capture_logs() {
+    CAPTURED_LOGS.get_or_init(|| {
+        let file = TempFile::new().unwrap();
+        // The logger may already be installed by another test.
+        let _ = LOGGER.init();
+        LOGGER
+            .update(LoggerConfig {
+                log_path: Some(file.as_path().to_path_buf()),
+                level: None,
+                show_level: Some(true),
+                show_log_origin: None,
+                module: None,
+                format: None,
+                filter: None,
+            })
+            .unwrap();
+        file
+    });
+}
+
+/// Returns the logs written since `capture_logs` was first called.
+pub fn captured_logs() -> String {
+    let file = CAPTURED_LOGS.get().expect("Logs are not captured");
+    std::fs::read_to_string(file.as_path()).unwrap()
+}
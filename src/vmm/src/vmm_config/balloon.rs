@@ -5,7 +5,7 @@ use std::sync::{Arc, Mutex};
 
 use serde::{Deserialize, Serialize};
 
-pub use crate::devices::virtio::balloon::device::BalloonStats;
+pub use crate::devices::virtio::balloon::device::{BalloonFeatures, BalloonStats};
 pub use crate::devices::virtio::balloon::BALLOON_DEV_ID;
 use crate::devices::virtio::balloon::{Balloon, BalloonConfig};
 
@@ -44,6 +44,9 @@ pub struct BalloonDeviceConfig {
     /// Interval in seconds between refreshing statistics.
     #[serde(default)]
     pub stats_polling_interval_s: u16,
+    /// Option to let the guest report its free pages, so that they are freed on the host.
+    #[serde(default)]
+    pub free_page_reporting: bool,
 }
 
 impl From<BalloonConfig> for BalloonDeviceConfig {
@@ -52,6 +55,7 @@ impl From<BalloonConfig> for BalloonDeviceConfig {
             amount_mib: state.amount_mib,
             deflate_on_oom: state.deflate_on_oom,
             stats_polling_interval_s: state.stats_polling_interval_s,
+            free_page_reporting: state.free_page_reporting,
         }
     }
 }
@@ -76,6 +80,13 @@ pub struct BalloonUpdateStatsConfig {
     pub stats_polling_interval_s: u16,
 }
 
+/// The outcome of a guest memory compaction.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct GuestMemoryCompaction {
+    /// Amount of memory the guest gave back, in MiB.
+    pub reclaimed_mib: u32,
+}
+
 /// A builder for `Balloon` devices from 'BalloonDeviceConfig'.
 #[cfg_attr(not(test), derive(Default))]
 #[derive(Debug)]
@@ -96,6 +107,7 @@ impl BalloonBuilder {
             cfg.amount_mib,
             cfg.deflate_on_oom,
             cfg.stats_polling_interval_s,
+            cfg.free_page_reporting,
             // `restored` flag is false because this code path
             // is never called by snapshot restore functionality.
             false,
@@ -141,6 +153,7 @@ pub(crate) mod tests {
             amount_mib: 0,
             deflate_on_oom: false,
             stats_polling_interval_s: 0,
+            free_page_reporting: false,
         }
     }
 
@@ -151,6 +164,7 @@ pub(crate) mod tests {
             amount_mib: 0,
             deflate_on_oom: false,
             stats_polling_interval_s: 0,
+            free_page_reporting: false,
         };
         assert_eq!(default_balloon_config, balloon_config);
         let mut builder = BalloonBuilder::new();
@@ -172,12 +186,14 @@ pub(crate) mod tests {
             amount_mib: 5,
             deflate_on_oom: false,
             stats_polling_interval_s: 3,
+            free_page_reporting: false,
         };
 
         let actual_balloon_config = BalloonDeviceConfig::from(BalloonConfig {
             amount_mib: 5,
             deflate_on_oom: false,
             stats_polling_interval_s: 3,
+            free_page_reporting: false,
         });
 
         assert_eq!(expected_balloon_config, actual_balloon_config);
@@ -186,7 +202,7 @@ pub(crate) mod tests {
     #[test]
     fn test_set_device() {
         let mut builder = BalloonBuilder::new();
-        let balloon = Balloon::new(0, true, 0, true).unwrap();
+        let balloon = Balloon::new(0, true, 0, false, true).unwrap();
         builder.set_device(Arc::new(Mutex::new(balloon)));
         assert!(builder.inner.is_some());
     }
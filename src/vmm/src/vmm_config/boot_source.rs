@@ -31,6 +31,10 @@ pub struct BootSourceConfig {
     /// The boot arguments to pass to the kernel. If this field is uninitialized,
     /// DEFAULT_KERNEL_CMDLINE is used.
     pub boot_args: Option<String>,
+    /// If set to true, a seed for the kernel RNG is read from the host RNG and passed to the
+    /// guest through the boot parameters.
+    #[serde(default)]
+    pub rng_seed: bool,
 }
 
 /// Errors associated with actions on `BootSourceConfig`.
@@ -44,6 +48,9 @@ pub enum BootSourceConfigError {
     InvalidKernelCommandLine(String),
     /// Firecracker's huge pages support is incompatible with initrds.
     HugePagesAndInitRd,
+    #[cfg(target_arch = "aarch64")]
+    /// Passing an RNG seed to the guest kernel is not supported on aarch64.
+    RngSeedNotSupported,
 }
 
 /// Holds the kernel specification (both configuration as well as runtime details).
@@ -65,6 +72,8 @@ pub struct BootConfig {
     pub kernel_file: File,
     /// The descriptor to the initrd file, if there is one.
     pub initrd_file: Option<File>,
+    /// Whether to pass a seed for the kernel RNG to the guest.
+    pub rng_seed: bool,
 }
 
 impl BootConfig {
@@ -89,10 +98,16 @@ impl BootConfig {
             linux_loader::cmdline::Cmdline::try_from(cmdline_str, crate::arch::CMDLINE_MAX_SIZE)
                 .map_err(|err| InvalidKernelCommandLine(err.to_string()))?;
 
+        #[cfg(target_arch = "aarch64")]
+        if cfg.rng_seed {
+            return Err(BootSourceConfigError::RngSeedNotSupported);
+        }
+
         Ok(BootConfig {
             cmdline,
             kernel_file,
             initrd_file,
+            rng_seed: cfg.rng_seed,
         })
     }
 }
@@ -113,22 +128,43 @@ pub(crate) mod tests {
             boot_args: None,
             initrd_path: None,
             kernel_image_path: kernel_path,
+            rng_seed: false,
         };
 
         let boot_cfg = BootConfig::new(&boot_src_cfg).unwrap();
         assert!(boot_cfg.initrd_file.is_none());
+        assert!(!boot_cfg.rng_seed);
         assert_eq!(
             boot_cfg.cmdline.as_cstring().unwrap().as_bytes_with_nul(),
             [DEFAULT_KERNEL_CMDLINE.as_bytes(), b"\0"].concat()
         );
     }
 
+    #[test]
+    fn test_boot_config_rng_seed() {
+        let kernel_file = TempFile::new().unwrap();
+        let boot_src_cfg = BootSourceConfig {
+            kernel_image_path: kernel_file.as_path().to_str().unwrap().to_string(),
+            rng_seed: true,
+            ..Default::default()
+        };
+
+        #[cfg(target_arch = "x86_64")]
+        assert!(BootConfig::new(&boot_src_cfg).unwrap().rng_seed);
+        #[cfg(target_arch = "aarch64")]
+        assert!(matches!(
+            BootConfig::new(&boot_src_cfg),
+            Err(BootSourceConfigError::RngSeedNotSupported)
+        ));
+    }
+
     #[test]
     fn test_serde() {
         let boot_src_cfg = BootSourceConfig {
             boot_args: Some(DEFAULT_KERNEL_CMDLINE.to_string()),
             initrd_path: Some("/tmp/initrd".to_string()),
             kernel_image_path: "./vmlinux.bin".to_string(),
+            rng_seed: true,
         };
 
         let mut snapshot_data = vec![0u8; 1000];
@@ -0,0 +1,283 @@
+// Copyright 2024 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use serde::Serialize;
+
+#[cfg(feature = "fault-injection")]
+use crate::devices::virtio::fault_injection::DeviceFault;
+use crate::devices::virtio::queue::Queue;
+use crate::devices::virtio::vsock::TYPE_VSOCK;
+use crate::devices::virtio::{
+    balloon, block, net, rng, vsock, watchdog, TYPE_BALLOON, TYPE_BLOCK, TYPE_NET, TYPE_RNG,
+    TYPE_WATCHDOG,
+};
+
+/// Types of virtio devices that can be addressed individually through the `/devices` API.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum VirtioDeviceType {
+    /// Block device (virtio or vhost-user), identified by its drive id.
+    Block,
+    /// Network device, identified by its interface id.
+    Net,
+    /// Vsock device.
+    Vsock,
+    /// Balloon device.
+    Balloon,
+    /// Entropy device.
+    Rng,
+    /// Watchdog device.
+    Watchdog,
+}
+
+impl VirtioDeviceType {
+    /// The virtio device type, as defined by the virtio specification.
+    pub fn virtio_type(&self) -> u32 {
+        match self {
+            VirtioDeviceType::Block => TYPE_BLOCK,
+            VirtioDeviceType::Net => TYPE_NET,
+            VirtioDeviceType::Vsock => TYPE_VSOCK,
+            VirtioDeviceType::Balloon => TYPE_BALLOON,
+            VirtioDeviceType::Rng => TYPE_RNG,
+            VirtioDeviceType::Watchdog => TYPE_WATCHDOG,
+        }
+    }
+
+    /// The virtio features the device implementation can offer to the guest.
+    pub fn capabilities(&self) -> DeviceCapabilities {
+        let (features, optional_features): (u64, &[(u32, &'static str)]) = match self {
+            VirtioDeviceType::Block => (
+                block::virtio::device::AVAIL_FEATURES,
+                &block::virtio::device::OPTIONAL_FEATURES,
+            ),
+            VirtioDeviceType::Net => (net::device::AVAIL_FEATURES, &net::device::OPTIONAL_FEATURES),
+            VirtioDeviceType::Vsock => (vsock::AVAIL_FEATURES, &[]),
+            VirtioDeviceType::Balloon => (
+                balloon::device::AVAIL_FEATURES,
+                &balloon::device::OPTIONAL_FEATURES,
+            ),
+            VirtioDeviceType::Rng => (rng::device::AVAIL_FEATURES, &[]),
+            VirtioDeviceType::Watchdog => (watchdog::device::AVAIL_FEATURES, &[]),
+        };
+
+        DeviceCapabilities {
+            virtio_type: self.virtio_type(),
+            features,
+            optional_features: optional_features
+                .iter()
+                .map(|&(bit, config_option)| OptionalFeature { bit, config_option })
+                .collect(),
+        }
+    }
+}
+
+/// A virtio feature offered only when the device is configured accordingly.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct OptionalFeature {
+    /// The feature bit.
+    pub bit: u32,
+    /// The device configuration option that makes the device offer the feature.
+    pub config_option: &'static str,
+}
+
+/// Struct used in GET `/device-capabilities/{type}` API call.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DeviceCapabilities {
+    /// The virtio device type, as defined by the virtio specification.
+    pub virtio_type: u32,
+    /// Feature bits offered by the device regardless of its configuration.
+    pub features: u64,
+    /// Feature bits offered depending on the device configuration.
+    pub optional_features: Vec<OptionalFeature>,
+}
+
+/// Identifies the device whose guest memory usage is requested.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceMemoryRequest {
+    /// Type of the device.
+    pub device_type: VirtioDeviceType,
+    /// Id of the device, as it was configured.
+    pub device_id: String,
+}
+
+/// Guest memory used by a single virtqueue.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct QueueMemoryUsage {
+    /// The maximum queue size offered by the device.
+    pub max_size: u16,
+    /// The queue size selected by the driver.
+    pub size: u16,
+    /// Bytes of guest memory used by the descriptor table and the rings of the queue.
+    pub bytes: u64,
+}
+
+/// Struct used in GET `/devices/{type}/{id}/memory` API call.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DeviceMemoryUsage {
+    /// Guest memory used by each queue of the device.
+    pub queues: Vec<QueueMemoryUsage>,
+    /// Bytes of guest memory used by all the queues of the device.
+    pub total_bytes: u64,
+}
+
+impl DeviceMemoryUsage {
+    /// Accounts the guest memory used by the given queues.
+    pub fn new(queues: &[Queue]) -> Self {
+        let queues: Vec<QueueMemoryUsage> = queues
+            .iter()
+            .map(|queue| QueueMemoryUsage {
+                max_size: queue.max_size,
+                size: queue.size,
+                bytes: queue.guest_memory_size() as u64,
+            })
+            .collect();
+        let total_bytes = queues.iter().map(|queue| queue.bytes).sum();
+
+        DeviceMemoryUsage {
+            queues,
+            total_bytes,
+        }
+    }
+}
+
+/// Identifies the device whose configuration space is requested.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceConfigSpaceRequest {
+    /// Type of the device.
+    pub device_type: VirtioDeviceType,
+    /// Id of the device, as it was configured.
+    pub device_id: String,
+}
+
+/// Identifies the device a fault is injected into, through PUT
+/// `/devices/{type}/{id}/inject-fault`.
+#[cfg(feature = "fault-injection")]
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceFaultRequest {
+    /// Type of the device.
+    pub device_type: VirtioDeviceType,
+    /// Id of the device, as it was configured.
+    pub device_id: String,
+    /// The fault injected into the device.
+    pub fault: DeviceFault,
+}
+
+/// Struct used in GET `/devices/{type}/{id}/config-space` API call.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct DeviceConfigSpace {
+    /// The raw bytes of the device configuration space, as a hex string.
+    pub config_space: String,
+}
+
+impl DeviceConfigSpace {
+    /// Encodes the given configuration space bytes.
+    pub fn new(config_space: &[u8]) -> Self {
+        DeviceConfigSpace {
+            config_space: config_space
+                .iter()
+                .map(|byte| format!("{byte:02x}"))
+                .collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::devices::virtio::gen::virtio_blk::{
+        VIRTIO_BLK_F_FLUSH, VIRTIO_BLK_F_RO, VIRTIO_F_VERSION_1,
+    };
+    use crate::devices::virtio::gen::virtio_ring::VIRTIO_RING_F_EVENT_IDX;
+
+    #[test]
+    fn test_block_capabilities() {
+        let capabilities = VirtioDeviceType::Block.capabilities();
+        assert_eq!(capabilities.virtio_type, TYPE_BLOCK);
+        assert_eq!(
+            capabilities.features,
+            (1 << VIRTIO_F_VERSION_1) | (1 << VIRTIO_RING_F_EVENT_IDX)
+        );
+        assert_eq!(
+            capabilities.optional_features,
+            vec![
+                OptionalFeature {
+                    bit: VIRTIO_BLK_F_FLUSH,
+                    config_option: "cache_type",
+                },
+                OptionalFeature {
+                    bit: VIRTIO_BLK_F_RO,
+                    config_option: "is_read_only",
+                },
+            ]
+        );
+    }
+
+    #[test]
+    fn test_device_capabilities() {
+        for device_type in [
+            VirtioDeviceType::Block,
+            VirtioDeviceType::Net,
+            VirtioDeviceType::Vsock,
+            VirtioDeviceType::Balloon,
+            VirtioDeviceType::Rng,
+            VirtioDeviceType::Watchdog,
+        ] {
+            let capabilities = device_type.capabilities();
+            assert_eq!(capabilities.virtio_type, device_type.virtio_type());
+            // All devices are modern virtio devices.
+            assert_ne!(capabilities.features & (1 << VIRTIO_F_VERSION_1), 0);
+            // Optional features are not offered unconditionally.
+            for feature in capabilities.optional_features {
+                assert_eq!(capabilities.features & (1 << feature.bit), 0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_device_memory_usage() {
+        let mut queues = vec![Queue::new(256), Queue::new(128)];
+        queues[0].size = 256;
+        queues[1].size = 64;
+
+        let usage = DeviceMemoryUsage::new(&queues);
+        assert_eq!(
+            usage.queues,
+            vec![
+                QueueMemoryUsage {
+                    max_size: 256,
+                    size: 256,
+                    bytes: 256 * 16 + (6 + 256 * 2) + (6 + 256 * 8),
+                },
+                QueueMemoryUsage {
+                    max_size: 128,
+                    size: 64,
+                    bytes: 64 * 16 + (6 + 64 * 2) + (6 + 64 * 8),
+                },
+            ]
+        );
+        assert_eq!(
+            usage.total_bytes,
+            usage.queues[0].bytes + usage.queues[1].bytes
+        );
+
+        assert_eq!(
+            DeviceMemoryUsage::new(&[]),
+            DeviceMemoryUsage {
+                queues: vec![],
+                total_bytes: 0,
+            }
+        );
+    }
+
+    #[test]
+    fn test_device_config_space() {
+        assert_eq!(DeviceConfigSpace::new(&[]).config_space, "");
+        assert_eq!(
+            DeviceConfigSpace::new(&[0x00, 0x01, 0xab, 0xff]).config_space,
+            "0001abff"
+        );
+        assert_eq!(
+            serde_json::to_value(DeviceConfigSpace::new(&[0x10])).unwrap(),
+            serde_json::json!({"config_space": "10"})
+        );
+    }
+}
@@ -58,6 +58,12 @@ pub struct BlockDeviceConfig {
     // pub file_engine_type: FileEngineType,
     #[serde(rename = "io_engine")]
     pub file_engine_type: Option<FileEngineType>,
+    /// If set to true, the backing file is opened with `O_DIRECT`, bypassing the host page
+    /// cache. Only supported by the Sync IO engine.
+    pub direct: Option<bool>,
+    /// If set to true, the discard and write zeroes requests of the guest deallocate the ranges
+    /// from the backing file. Not supported by read-only drives and the Unsafe cache type.
+    pub discard: Option<bool>,
 
     // VhostUserBlock specific fields
     /// Path to the vhost-user socket.
@@ -77,6 +83,9 @@ pub struct BlockDeviceUpdateConfig {
     pub path_on_host: Option<String>,
     /// New rate limiter config.
     pub rate_limiter: Option<RateLimiterConfig>,
+    /// If set to true, the size of the block file is re-read and the guest is notified if it
+    /// changed.
+    pub resize: Option<bool>,
 }
 
 /// Wrapper for the collection that holds all the Block Devices
@@ -166,6 +175,35 @@ impl BlockBuilder {
         Ok(())
     }
 
+    /// Checks the configurations of block devices inserted in order, without creating the devices,
+    /// and returns the configurations the resulting list of devices would have.
+    pub fn check_configs(
+        configs: Vec<BlockDeviceConfig>,
+    ) -> Result<Vec<BlockDeviceConfig>, DriveError> {
+        let mut checked: VecDeque<BlockDeviceConfig> = VecDeque::new();
+        for config in configs {
+            let position = checked.iter().position(|c| c.drive_id == config.drive_id);
+            let has_root_device = checked.front().is_some_and(|c| c.is_root_device);
+            if config.is_root_device && has_root_device && position != Some(0) {
+                return Err(DriveError::RootBlockDeviceAlreadyAdded);
+            }
+
+            let config = Block::check_config(&config).map_err(DriveError::CreateBlockDevice)?;
+            let configured_as_root = config.is_root_device;
+            match position {
+                None if configured_as_root => checked.push_front(config),
+                None => checked.push_back(config),
+                Some(index) => {
+                    checked[index] = config;
+                    if index != 0 && configured_as_root {
+                        checked.swap(0, index);
+                    }
+                }
+            }
+        }
+        Ok(checked.into())
+    }
+
     /// Returns a vec with the structures used to configure the devices.
     pub fn configs(&self) -> Vec<BlockDeviceConfig> {
         self.devices
@@ -202,6 +240,8 @@ mod tests {
                 path_on_host: self.path_on_host.clone(),
                 rate_limiter: self.rate_limiter,
                 file_engine_type: self.file_engine_type,
+                direct: self.direct,
+                discard: self.discard,
 
                 socket: self.socket.clone(),
             }
@@ -229,6 +269,8 @@ mod tests {
             path_on_host: Some(dummy_path),
             rate_limiter: None,
             file_engine_type: None,
+            direct: None,
+            discard: None,
 
             socket: None,
         };
@@ -261,6 +303,8 @@ mod tests {
             path_on_host: Some(dummy_path),
             rate_limiter: None,
             file_engine_type: None,
+            direct: None,
+            discard: None,
 
             socket: None,
         };
@@ -290,6 +334,8 @@ mod tests {
             path_on_host: Some(dummy_path_1),
             rate_limiter: None,
             file_engine_type: None,
+            direct: None,
+            discard: None,
 
             socket: None,
         };
@@ -306,6 +352,8 @@ mod tests {
             path_on_host: Some(dummy_path_2),
             rate_limiter: None,
             file_engine_type: None,
+            direct: None,
+            discard: None,
 
             socket: None,
         };
@@ -333,6 +381,8 @@ mod tests {
             path_on_host: Some(dummy_path_1),
             rate_limiter: None,
             file_engine_type: None,
+            direct: None,
+            discard: None,
 
             socket: None,
         };
@@ -349,6 +399,8 @@ mod tests {
             path_on_host: Some(dummy_path_2),
             rate_limiter: None,
             file_engine_type: None,
+            direct: None,
+            discard: None,
 
             socket: None,
         };
@@ -365,6 +417,8 @@ mod tests {
             path_on_host: Some(dummy_path_3),
             rate_limiter: None,
             file_engine_type: None,
+            direct: None,
+            discard: None,
 
             socket: None,
         };
@@ -406,6 +460,8 @@ mod tests {
             path_on_host: Some(dummy_path_1),
             rate_limiter: None,
             file_engine_type: None,
+            direct: None,
+            discard: None,
 
             socket: None,
         };
@@ -422,6 +478,8 @@ mod tests {
             path_on_host: Some(dummy_path_2),
             rate_limiter: None,
             file_engine_type: None,
+            direct: None,
+            discard: None,
 
             socket: None,
         };
@@ -438,6 +496,8 @@ mod tests {
             path_on_host: Some(dummy_path_3),
             rate_limiter: None,
             file_engine_type: None,
+            direct: None,
+            discard: None,
 
             socket: None,
         };
@@ -480,6 +540,8 @@ mod tests {
             path_on_host: Some(dummy_path_1.clone()),
             rate_limiter: None,
             file_engine_type: None,
+            direct: None,
+            discard: None,
 
             socket: None,
         };
@@ -496,6 +558,8 @@ mod tests {
             path_on_host: Some(dummy_path_2.clone()),
             rate_limiter: None,
             file_engine_type: None,
+            direct: None,
+            discard: None,
 
             socket: None,
         };
@@ -560,6 +624,8 @@ mod tests {
             path_on_host: Some(dummy_path_1),
             rate_limiter: None,
             file_engine_type: None,
+            direct: None,
+            discard: None,
 
             socket: None,
         };
@@ -576,6 +642,8 @@ mod tests {
             path_on_host: Some(dummy_path_2),
             rate_limiter: None,
             file_engine_type: None,
+            direct: None,
+            discard: None,
 
             socket: None,
         };
@@ -602,6 +670,8 @@ mod tests {
             path_on_host: Some(dummy_file.as_path().to_str().unwrap().to_string()),
             rate_limiter: None,
             file_engine_type: Some(FileEngineType::Sync),
+            direct: Some(false),
+            discard: None,
 
             socket: None,
         };
@@ -630,6 +700,8 @@ mod tests {
             path_on_host: Some(backing_file.as_path().to_str().unwrap().to_string()),
             rate_limiter: None,
             file_engine_type: None,
+            direct: None,
+            discard: None,
 
             socket: None,
         };
@@ -8,6 +8,7 @@ use serde::{Deserialize, Serialize};
 
 use super::RateLimiterConfig;
 use crate::devices::virtio::rng::{Entropy, EntropyError};
+use crate::VmmError;
 
 /// This struct represents the strongly typed equivalent of the json body from entropy device
 /// related requests.
@@ -35,6 +36,10 @@ pub enum EntropyDeviceError {
     CreateDevice(#[from] EntropyError),
     /// Could not create RateLimiter from configuration: {0}
     CreateRateLimiter(#[from] std::io::Error),
+    /// The ops bucket of the entropy device rate limiter cannot be updated.
+    UpdateOpsRateLimiter,
+    /// Could not update the entropy device: {0}
+    DeviceUpdate(#[from] VmmError),
 }
 
 /// A builder type used to construct an Entropy device
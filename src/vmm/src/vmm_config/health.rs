@@ -0,0 +1,93 @@
+// Copyright 2024 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::sync::Mutex;
+
+use serde::Serialize;
+
+use crate::logger::{FirecrackerMetrics, IncMetric, METRICS};
+
+/// Values of the health indicators when they were last reset.
+static BASELINE: Mutex<Health> = Mutex::new(Health { vcpu_failures: 0 });
+
+/// Struct used in GET `/health` API call.
+///
+/// Panics and seccomp faults are not reported, since they terminate Firecracker before they
+/// could be read.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct Health {
+    /// Number of vCPU errors.
+    pub vcpu_failures: u64,
+}
+
+impl Health {
+    /// Reads the health indicators accumulated since they were last reset.
+    pub fn get() -> Self {
+        Self::since_baseline(&METRICS, &BASELINE)
+    }
+
+    /// Resets the health indicators. The metrics they are derived from are not affected.
+    pub fn reset() {
+        Self::reset_baseline(&METRICS, &BASELINE);
+    }
+
+    fn current(metrics: &FirecrackerMetrics) -> Self {
+        Health {
+            vcpu_failures: metrics.vcpu.failures.count(),
+        }
+    }
+
+    fn since_baseline(metrics: &FirecrackerMetrics, baseline: &Mutex<Health>) -> Self {
+        let baseline = baseline.lock().expect("Poisoned lock");
+        let current = Self::current(metrics);
+
+        Health {
+            vcpu_failures: current.vcpu_failures.saturating_sub(baseline.vcpu_failures),
+        }
+    }
+
+    fn reset_baseline(metrics: &FirecrackerMetrics, baseline: &Mutex<Health>) {
+        *baseline.lock().expect("Poisoned lock") = Self::current(metrics);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_health() {
+        let metrics = FirecrackerMetrics::new();
+        let baseline = Mutex::new(Health::default());
+        assert_eq!(
+            Health::since_baseline(&metrics, &baseline),
+            Health::default()
+        );
+
+        metrics.vcpu.failures.add(3);
+        assert_eq!(
+            Health::since_baseline(&metrics, &baseline),
+            Health { vcpu_failures: 3 }
+        );
+
+        // Resetting clears the health indicators, but not the metrics.
+        Health::reset_baseline(&metrics, &baseline);
+        assert_eq!(
+            Health::since_baseline(&metrics, &baseline),
+            Health::default()
+        );
+        assert_eq!(metrics.vcpu.failures.count(), 3);
+
+        // Only faults occurring after the reset are reported.
+        metrics.vcpu.failures.inc();
+        assert_eq!(
+            Health::since_baseline(&metrics, &baseline),
+            Health { vcpu_failures: 1 }
+        );
+
+        assert_eq!(
+            serde_json::to_value(Health::default()).unwrap(),
+            serde_json::json!({"vcpu_failures": 0})
+        );
+    }
+}
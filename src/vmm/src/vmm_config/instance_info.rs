@@ -4,6 +4,8 @@ use std::fmt::{self, Display, Formatter};
 
 use serde::{ser, Serialize};
 
+use super::seccomp::SeccompInfo;
+
 /// Enumerates microVM runtime states.
 #[derive(Clone, Debug, Default, PartialEq, Eq)]
 pub enum VmState {
@@ -46,4 +48,15 @@ pub struct InstanceInfo {
     pub vmm_version: String,
     /// The name of the application that runs the microVM.
     pub app_name: String,
+    /// The time at which the microVM first started running, in microseconds since the Unix
+    /// epoch.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub boot_timestamp_us: Option<u64>,
+    /// The time elapsed since the microVM first started running, in microseconds.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub uptime_us: Option<u64>,
+    /// The seccomp configuration applied at launch. It is reported separately, through
+    /// `GET /seccomp`, so it is not part of the serialized instance information.
+    #[serde(skip)]
+    pub seccomp: SeccompInfo,
 }
@@ -0,0 +1,134 @@
+// Copyright 2024 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::collections::BTreeMap;
+
+use kvm_ioctls::Kvm;
+use serde::Serialize;
+
+use crate::vstate::vm::VmError;
+
+macro_rules! capabilities {
+    ($($cap:ident),* $(,)?) => {
+        [$((stringify!($cap), kvm_bindings::$cap)),*]
+    };
+}
+
+/// KVM capabilities reported in GET `/host/kvm-capabilities`, along with their names.
+const CAPABILITIES: &[(&str, u32)] = &capabilities!(
+    KVM_CAP_IOEVENTFD,
+    KVM_CAP_IRQFD,
+    KVM_CAP_USER_MEMORY,
+    KVM_CAP_MP_STATE,
+    KVM_CAP_NR_MEMSLOTS,
+    KVM_CAP_NR_VCPUS,
+    KVM_CAP_MAX_VCPUS,
+    KVM_CAP_IMMEDIATE_EXIT,
+    KVM_CAP_HALT_POLL,
+);
+
+/// Architecture specific KVM capabilities reported in GET `/host/kvm-capabilities`.
+#[cfg(target_arch = "x86_64")]
+const ARCH_CAPABILITIES: &[(&str, u32)] = &capabilities!(
+    KVM_CAP_IRQCHIP,
+    KVM_CAP_SET_TSS_ADDR,
+    KVM_CAP_PIT2,
+    KVM_CAP_PIT_STATE2,
+    KVM_CAP_ADJUST_CLOCK,
+    KVM_CAP_DEBUGREGS,
+    KVM_CAP_VCPU_EVENTS,
+    KVM_CAP_XCRS,
+    KVM_CAP_XSAVE,
+    KVM_CAP_EXT_CPUID,
+    KVM_CAP_TSC_CONTROL,
+    KVM_CAP_GET_TSC_KHZ,
+    KVM_CAP_DIRTY_LOG_RING,
+);
+
+/// Architecture specific KVM capabilities reported in GET `/host/kvm-capabilities`.
+#[cfg(target_arch = "aarch64")]
+const ARCH_CAPABILITIES: &[(&str, u32)] = &capabilities!(
+    KVM_CAP_ARM_PSCI_0_2,
+    KVM_CAP_DEVICE_CTRL,
+    KVM_CAP_ONE_REG,
+    KVM_CAP_ARM_PMU_V3,
+);
+
+/// Availability of a single KVM capability on the host.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct KvmCapabilityInfo {
+    /// Whether the capability is supported by the host KVM.
+    pub available: bool,
+    /// The value returned by `KVM_CHECK_EXTENSION` for the capability, e.g. the maximum number
+    /// of memory slots for `KVM_CAP_NR_MEMSLOTS`.
+    pub value: i32,
+}
+
+/// Struct used in GET `/host/kvm-capabilities` API call.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct KvmCapabilities {
+    /// The KVM capabilities queried, by name.
+    pub capabilities: BTreeMap<&'static str, KvmCapabilityInfo>,
+}
+
+impl KvmCapabilities {
+    /// Queries the host KVM for the capabilities relevant to Firecracker.
+    pub fn get() -> Result<Self, VmError> {
+        let kvm = Kvm::new().map_err(VmError::Kvm)?;
+        Ok(Self::new(|cap| kvm.check_extension_raw(u64::from(cap))))
+    }
+
+    fn new(check_extension: impl Fn(u32) -> i32) -> Self {
+        let capabilities = CAPABILITIES
+            .iter()
+            .chain(ARCH_CAPABILITIES)
+            .map(|&(name, cap)| {
+                let value = check_extension(cap);
+                (
+                    name,
+                    KvmCapabilityInfo {
+                        available: value > 0,
+                        value,
+                    },
+                )
+            })
+            .collect();
+
+        KvmCapabilities { capabilities }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_kvm_capabilities() {
+        let capabilities = KvmCapabilities::get().unwrap().capabilities;
+        assert_eq!(
+            capabilities.len(),
+            CAPABILITIES.len() + ARCH_CAPABILITIES.len()
+        );
+        // Firecracker cannot create a microVM without these.
+        assert!(capabilities["KVM_CAP_IOEVENTFD"].available);
+        assert!(capabilities["KVM_CAP_USER_MEMORY"].available);
+        assert!(capabilities["KVM_CAP_NR_MEMSLOTS"].value > 0);
+
+        let value = serde_json::to_value(KvmCapabilities::new(|cap| {
+            if cap == kvm_bindings::KVM_CAP_NR_MEMSLOTS {
+                509
+            } else {
+                0
+            }
+        }))
+        .unwrap();
+        assert_eq!(
+            value["capabilities"]["KVM_CAP_NR_MEMSLOTS"],
+            serde_json::json!({"available": true, "value": 509})
+        );
+        assert_eq!(
+            value["capabilities"]["KVM_CAP_IRQFD"],
+            serde_json::json!({"available": false, "value": 0})
+        );
+    }
+}
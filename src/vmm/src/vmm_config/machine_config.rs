@@ -1,16 +1,50 @@
 // Copyright 2018 Amazon.com, Inc. or its affiliates. All Rights Reserved.
 // SPDX-License-Identifier: Apache-2.0
+use std::collections::BTreeMap;
+use std::ffi::CString;
 use std::fmt::Debug;
+use std::os::unix::ffi::OsStrExt;
+use std::path::{Path, PathBuf};
 
 use serde::{Deserialize, Serialize};
 
+use super::TokenBucketConfig;
+use crate::arch::{arch_memory_regions, MMIO_MEM_SIZE, MMIO_MEM_START};
 use crate::cpu_config::templates::{CpuTemplateType, CustomCpuTemplate, StaticCpuTemplate};
+use crate::vstate::memory::{Address, GuestAddress};
 
 /// The default memory size of the VM, in MiB.
 pub const DEFAULT_MEM_SIZE_MIB: usize = 128;
 /// Firecracker aims to support small scale workloads only, so limit the maximum
 /// vCPUs supported.
 pub const MAX_SUPPORTED_VCPUS: u8 = 32;
+/// The start address and size of the device memory window must be a multiple of this value, in
+/// MiB.
+pub const DEVICE_MEMORY_ALIGNMENT_MIB: usize = 2;
+/// The length of the CPU vendor ID string, in bytes.
+pub const VENDOR_ID_LEN: usize = 12;
+/// The maximum length of the CPU brand string, in bytes. The 48 bytes of CPUID leaves
+/// 0x80000002-0x80000004 include a NUL terminator.
+pub const BRAND_STRING_MAX_LEN: usize = 47;
+/// The maximum number of cache levels that can be advertised to the guest.
+pub const MAX_CACHE_LEVELS: u8 = 7;
+/// The maximum number of memory slots guest RAM can be split into. This is the smallest number of
+/// user memory slots offered by the KVM versions Firecracker supports; the limit reported by KVM is
+/// checked again when the guest memory is registered.
+pub const MAX_MEMSLOTS: usize = 509;
+/// The maximum CPU family that can be encoded in CPUID leaf 0x1.
+pub const MAX_CPU_FAMILY: u16 = 0xf + 0xff;
+/// The maximum CPU stepping that can be encoded in CPUID leaf 0x1.
+pub const MAX_CPU_STEPPING: u8 = 0xf;
+/// The standard CPUID leaves, reported up to the maximum found in leaf 0x0.
+pub const STANDARD_CPUID_LEAVES: std::ops::Range<u32> = 0x0..0x4000_0000;
+/// The processor frequency information CPUID leaf, advertising [`CpuFrequencies`].
+pub const CPU_FREQUENCIES_LEAF: u32 = 0x16;
+/// The extended CPUID leaves, reported up to the maximum found in leaf 0x80000000.
+pub const EXTENDED_CPUID_LEAVES: std::ops::Range<u32> = 0x8000_0000..0x8001_0000;
+/// The minimum length of the virtio descriptor chains that can be configured, the number of
+/// descriptors of the shortest block request (header, data and status).
+pub const MIN_DESC_CHAIN_LEN: u16 = 3;
 
 /// Errors associated with configuring the microVM.
 #[rustfmt::skip]
@@ -33,6 +67,87 @@ pub enum VmConfigError {
     BalloonAndHugePages,
     /// Firecracker's huge pages support is incompatible with initrds.
     InitrdAndHugePages,
+    /// The device memory window must have a non-zero size, and both its start address and size must be a multiple of {DEVICE_MEMORY_ALIGNMENT_MIB:} MiB.
+    InvalidDeviceMemory,
+    /// The device memory window overlaps the guest RAM.
+    DeviceMemoryOverlapsRam,
+    /// The device memory window overlaps the MMIO area.
+    DeviceMemoryOverlapsMmio,
+    /// The CPU vendor ID must consist of exactly {VENDOR_ID_LEN:} printable ASCII characters.
+    InvalidVendorId,
+    /// Configuring the CPU vendor ID is not supported on aarch64.
+    #[cfg(target_arch = "aarch64")]
+    VendorIdNotSupported,
+    /// The CPU brand string must consist of 1 to {BRAND_STRING_MAX_LEN:} printable ASCII characters.
+    InvalidBrandString,
+    /// Configuring the CPU brand string is not supported on aarch64.
+    #[cfg(target_arch = "aarch64")]
+    BrandStringNotSupported,
+    /// The number of cache levels must be between 1 and {MAX_CACHE_LEVELS:}.
+    InvalidCacheLevels,
+    /// Configuring the number of cache levels is not supported on aarch64.
+    #[cfg(target_arch = "aarch64")]
+    CacheLevelsNotSupported,
+    /// The memory slot size (MiB) is either 0, or not a multiple of the configured page size.
+    InvalidMemslotSize,
+    /// Splitting the guest memory into memory slots of the configured size requires {0} slots, more than the maximum of {MAX_MEMSLOTS:}.
+    TooManyMemslots(usize),
+    /// The CPU family must be at most {MAX_CPU_FAMILY:#x} and the stepping at most {MAX_CPU_STEPPING:#x}. Models above 0xf require a family of 0x6, or of at least 0xf.
+    InvalidCpuSignature,
+    /// Configuring the CPU family, model and stepping is not supported on aarch64.
+    #[cfg(target_arch = "aarch64")]
+    CpuSignatureNotSupported,
+    /// The CPU frequencies must be non-zero, with the base frequency at most the maximum frequency and the bus frequency at most the base frequency.
+    InvalidCpuFrequencies,
+    /// Configuring the CPU frequencies is not supported on aarch64.
+    #[cfg(target_arch = "aarch64")]
+    CpuFrequenciesNotSupported,
+    /// The CPU frequencies are advertised in CPUID leaf {CPU_FREQUENCIES_LEAF:#x}, which the maximum standard CPUID leaf must include.
+    CpuFrequenciesAboveMaxCpuidLeaf,
+    /// The CPU topology must account for exactly the configured number of vCPUs, have a power of 2 number of cores per socket, and 2 threads per core if SMT is enabled or 1 otherwise.
+    InvalidCpuTopology,
+    /// Configuring the CPU topology is not supported on aarch64.
+    #[cfg(target_arch = "aarch64")]
+    CpuTopologyNotSupported,
+    /// The minimum memory size (MiB) is either 0, larger than the memory size, or not a multiple of the configured page size.
+    InvalidMinMemorySize,
+    /// Configuring the invariant TSC is not supported on aarch64.
+    #[cfg(target_arch = "aarch64")]
+    InvariantTscNotSupported,
+    /// Transparent huge page advice cannot be applied to guest memory backed by hugetlbfs pages.
+    ThpAdviceWithHugetlbfs,
+    /// The maximum standard CPUID leaf must be between 0x1 and 0x3fffffff, and the maximum extended CPUID leaf between 0x80000001 and 0x8000ffff.
+    InvalidMaxCpuidLeaves,
+    /// Configuring the maximum CPUID leaves is not supported on aarch64.
+    #[cfg(target_arch = "aarch64")]
+    MaxCpuidLeavesNotSupported,
+    /// The cache line size must be a power of 2 between 8 and 128 bytes.
+    InvalidCacheLineSize,
+    /// Configuring the cache line size is not supported on aarch64.
+    #[cfg(target_arch = "aarch64")]
+    CacheLineSizeNotSupported,
+    /// The maximum length of the virtio descriptor chains must be at least 3.
+    InvalidMaxDescChainLen,
+    /// The memory backing file must be located in an existing directory of a hugetlbfs mount if huge pages are configured, or of a tmpfs mount otherwise.
+    InvalidMemBackingFile,
+    /// Configuring the AVX features is not supported on aarch64.
+    #[cfg(target_arch = "aarch64")]
+    AvxFeaturesNotSupported,
+    /// Configuring the hypervisor present bit is not supported on aarch64.
+    #[cfg(target_arch = "aarch64")]
+    HypervisorBitNotSupported,
+    /// Configuring the RDRAND and RDSEED instructions is not supported on aarch64.
+    #[cfg(target_arch = "aarch64")]
+    RngInstructionsNotSupported,
+    /// Configuring the MONITOR and MWAIT instructions is not supported on aarch64.
+    #[cfg(target_arch = "aarch64")]
+    MonitorMwaitNotSupported,
+    /// Configuring the PMU is not supported on aarch64.
+    #[cfg(target_arch = "aarch64")]
+    PmuNotSupported,
+    /// Configuring the TSC deadline timer is not supported on aarch64.
+    #[cfg(target_arch = "aarch64")]
+    TscDeadlineNotSupported,
 }
 
 /// Describes the possible (huge)page configurations for a microVM's memory.
@@ -50,13 +165,16 @@ impl HugePageConfig {
     /// Checks whether the given memory size (in MiB) is valid for this [`HugePageConfig`], e.g.
     /// whether it is a multiple of the page size
     fn is_valid_mem_size(&self, mem_size_mib: usize) -> bool {
-        let divisor = match self {
+        mem_size_mib % self.mem_size_alignment_mib() == 0
+    }
+
+    /// Returns the granularity, in MiB, of memory sizes valid for this [`HugePageConfig`].
+    pub fn mem_size_alignment_mib(&self) -> usize {
+        match self {
             // Any integer memory size expressed in MiB will be a multiple of 4096KiB.
             HugePageConfig::None => 1,
             HugePageConfig::Hugetlbfs2M => 2,
-        };
-
-        mem_size_mib % divisor == 0
+        }
     }
 
     /// Returns the flags required to pass to `mmap`, in addition to `MAP_ANONYMOUS`, to
@@ -91,6 +209,478 @@ impl From<HugePageConfig> for Option<memfd::HugetlbSize> {
     }
 }
 
+/// Transparent huge page advice applied to the guest memory with `madvise`.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ThpAdvice {
+    /// Back guest memory by transparent huge pages where possible (`MADV_HUGEPAGE`).
+    Hugepage,
+    /// Never back guest memory by transparent huge pages (`MADV_NOHUGEPAGE`).
+    NoHugepage,
+}
+
+impl ThpAdvice {
+    /// Returns the `madvise` advice corresponding to this [`ThpAdvice`].
+    pub fn madvise_advice(&self) -> libc::c_int {
+        match self {
+            ThpAdvice::Hugepage => libc::MADV_HUGEPAGE,
+            ThpAdvice::NoHugepage => libc::MADV_NOHUGEPAGE,
+        }
+    }
+}
+
+/// What to do if the host memory available when the microVM starts does not fit the guest memory
+/// and the memory overhead of Firecracker.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum HostMemoryPolicy {
+    /// Log a warning and start the microVM.
+    Warn,
+    /// Fail to start the microVM.
+    Fail,
+}
+
+/// Time in nanoseconds KVM polls for a wakeup event before blocking a halted vCPU thread, when
+/// using the [`VcpuIdleStrategy::Poll`] strategy.
+pub const IDLE_POLL_NS: u32 = 200_000;
+
+/// How a vCPU thread waits for an interrupt while the guest is halted.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub enum VcpuIdleStrategy {
+    /// Block the vCPU thread as soon as the guest halts, until an interrupt is pending.
+    Block,
+    /// Poll for a pending interrupt for a while before blocking the vCPU thread, so that the
+    /// guest is re-entered immediately on wakeups arriving shortly after it halts.
+    Poll,
+}
+
+impl VcpuIdleStrategy {
+    /// Returns the KVM halt polling interval in nanoseconds corresponding to this
+    /// [`VcpuIdleStrategy`].
+    pub fn halt_poll_ns(&self) -> u32 {
+        match self {
+            VcpuIdleStrategy::Block => 0,
+            VcpuIdleStrategy::Poll => IDLE_POLL_NS,
+        }
+    }
+}
+
+/// Describes a guest physical address window reserved for device memory. The window is never
+/// backed by guest RAM.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct DeviceMemoryConfig {
+    /// Guest physical address at which the window starts.
+    pub guest_address: u64,
+    /// The size of the window in MiB.
+    pub size_mib: usize,
+}
+
+/// Describes the family, model and stepping of the CPU reported to the guest in CPUID leaf 0x1.
+/// The values are the displayed ones, which are split into the base and extended fields of the
+/// leaf when it is normalized.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CpuSignature {
+    /// The CPU family.
+    pub family: u16,
+    /// The CPU model.
+    pub model: u8,
+    /// The CPU stepping.
+    pub stepping: u8,
+}
+
+impl CpuSignature {
+    /// Checks that the signature can be encoded in CPUID leaf 0x1. The extended model field is
+    /// only taken into account by the guest for families 0x6 and 0xf onwards.
+    fn validate(&self) -> Result<(), VmConfigError> {
+        let extended_model = self.model > 0xf && self.family != 0x6 && self.family < 0xf;
+        if self.family > MAX_CPU_FAMILY || self.stepping > MAX_CPU_STEPPING || extended_model {
+            return Err(VmConfigError::InvalidCpuSignature);
+        }
+
+        Ok(())
+    }
+}
+
+/// Describes the frequencies, in MHz, advertised to the guest in the processor frequency
+/// information leaf 0x16.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CpuFrequencies {
+    /// The base frequency of the processor.
+    pub base_mhz: u16,
+    /// The maximum frequency of the processor.
+    pub max_mhz: u16,
+    /// The bus (reference) frequency.
+    pub bus_mhz: u16,
+}
+
+impl CpuFrequencies {
+    /// Checks that the frequencies are consistent with each other.
+    fn validate(&self) -> Result<(), VmConfigError> {
+        if self.bus_mhz == 0 || self.bus_mhz > self.base_mhz || self.base_mhz > self.max_mhz {
+            return Err(VmConfigError::InvalidCpuFrequencies);
+        }
+
+        Ok(())
+    }
+
+    /// Returns the frequencies the host reports in CPUID leaf 0x16, if it reports any.
+    #[cfg(target_arch = "x86_64")]
+    pub fn host() -> Option<Self> {
+        let leaf =
+            crate::cpu_config::x86_64::cpuid::common::get_cpuid(CPU_FREQUENCIES_LEAF, 0).ok()?;
+        Self::from_registers(leaf.eax, leaf.ebx, leaf.ecx)
+    }
+
+    /// Returns the frequencies `cpuid` reports in leaf 0x16, if its maximum standard leaf
+    /// includes the leaf and it reports any.
+    #[cfg(target_arch = "x86_64")]
+    pub fn from_cpuid(cpuid: &impl crate::cpu_config::x86_64::cpuid::CpuidTrait) -> Option<Self> {
+        use crate::cpu_config::x86_64::cpuid::CpuidKey;
+
+        let max_leaf = cpuid.get(&CpuidKey::leaf(0x0))?.result.eax;
+        if max_leaf < CPU_FREQUENCIES_LEAF {
+            return None;
+        }
+        let leaf = &cpuid.get(&CpuidKey::leaf(CPU_FREQUENCIES_LEAF))?.result;
+        Self::from_registers(leaf.eax, leaf.ebx, leaf.ecx)
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    fn from_registers(eax: u32, ebx: u32, ecx: u32) -> Option<Self> {
+        // The frequencies are in bits 0..16 of the leaf registers.
+        #[allow(clippy::cast_possible_truncation)]
+        let frequencies = CpuFrequencies {
+            base_mhz: eax as u16,
+            max_mhz: ebx as u16,
+            bus_mhz: ecx as u16,
+        };
+        // Processors which do not enumerate the frequencies report zeroes in the leaf.
+        frequencies.validate().ok().map(|()| frequencies)
+    }
+}
+
+/// Describes the CPU topology presented to the guest in the extended topology leaf 0xB.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CpuTopology {
+    /// The number of sockets.
+    pub sockets: u8,
+    /// The number of cores in each socket.
+    pub cores_per_socket: u8,
+    /// The number of threads in each core.
+    pub threads_per_core: u8,
+}
+
+impl CpuTopology {
+    /// Returns the number of logical CPUs in each socket.
+    pub fn cpus_per_socket(&self) -> u8 {
+        self.cores_per_socket * self.threads_per_core
+    }
+
+    /// Checks that the topology describes `vcpu_count` vCPUs. As the index of a vCPU is also its
+    /// APIC ID, the number of cores per socket must be a power of 2 for the APIC ID to be split
+    /// into thread, core and socket IDs. The number of threads per core follows the SMT setting.
+    fn validate(&self, vcpu_count: u8, smt: bool) -> Result<(), VmConfigError> {
+        let threads_per_core = threads_per_core(vcpu_count, smt);
+        let cpu_count = u16::from(self.sockets)
+            * u16::from(self.cores_per_socket)
+            * u16::from(self.threads_per_core);
+        if cpu_count != u16::from(vcpu_count)
+            || !self.cores_per_socket.is_power_of_two()
+            || self.threads_per_core != threads_per_core
+        {
+            return Err(VmConfigError::InvalidCpuTopology);
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the number of threads per core presented to the guest: 2 if SMT is enabled and there
+/// is more than one vCPU, 1 otherwise.
+pub fn threads_per_core(vcpu_count: u8, smt: bool) -> u8 {
+    1 + u8::from(smt && vcpu_count > 1)
+}
+
+// Filesystem magic numbers, see /usr/include/linux/magic.h .
+const TMPFS_MAGIC: u64 = 0x0102_1994;
+const HUGETLBFS_MAGIC: u64 = 0x9584_58f6;
+
+/// Checks that the memory backing file is to be created on a hugetlbfs mount if huge pages are
+/// configured, or on a tmpfs mount otherwise, so that its pages are the guest memory itself.
+fn validate_mem_backing_file(path: &Path, huge_pages: HugePageConfig) -> Result<(), VmConfigError> {
+    // The file itself is only created when allocating the guest memory.
+    let dir = match path.parent() {
+        Some(dir) if !dir.as_os_str().is_empty() => dir,
+        _ => Path::new("."),
+    };
+    let dir = CString::new(dir.as_os_str().as_bytes())
+        .map_err(|_| VmConfigError::InvalidMemBackingFile)?;
+
+    // SAFETY: `statfs` is a plain old data struct, for which all zeroes is a valid value.
+    let mut stat: libc::statfs = unsafe { std::mem::zeroed() };
+    // SAFETY: Safe because the path is a valid C string and `stat` is a valid buffer.
+    if unsafe { libc::statfs(dir.as_ptr(), &mut stat) } != 0 {
+        return Err(VmConfigError::InvalidMemBackingFile);
+    }
+
+    // The type of `f_type` depends on the architecture and the C library.
+    #[allow(clippy::unnecessary_cast, clippy::cast_sign_loss)]
+    let fs_type = stat.f_type as u64;
+    let expected_fs_type = if huge_pages.is_hugetlbfs() {
+        HUGETLBFS_MAGIC
+    } else {
+        TMPFS_MAGIC
+    };
+    if fs_type != expected_fs_type {
+        return Err(VmConfigError::InvalidMemBackingFile);
+    }
+    Ok(())
+}
+
+/// Describes the maximum standard and extended CPUID leaves advertised to the guest in leaves 0x0
+/// and 0x80000000. The leaves above them are removed from the guest CPUID.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct CpuidMaxLeaves {
+    /// The maximum standard leaf.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub standard: Option<u32>,
+    /// The maximum extended leaf.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub extended: Option<u32>,
+}
+
+impl CpuidMaxLeaves {
+    /// Checks that each maximum lies in its range of leaves and keeps the leaf right after the
+    /// range base, which every guest relies on.
+    fn validate(&self) -> Result<(), VmConfigError> {
+        let in_range = |max_leaf: Option<u32>, leaves: std::ops::Range<u32>| {
+            max_leaf.is_none_or(|leaf| leaf > leaves.start && leaves.contains(&leaf))
+        };
+        if !in_range(self.standard, STANDARD_CPUID_LEAVES)
+            || !in_range(self.extended, EXTENDED_CPUID_LEAVES)
+        {
+            return Err(VmConfigError::InvalidMaxCpuidLeaves);
+        }
+
+        Ok(())
+    }
+}
+
+/// AVX and AVX-512 features enumerated in CPUID leaf 0x7, subleaf 0x0, named after the
+/// corresponding Linux `/proc/cpuinfo` flags.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord, Deserialize, Serialize)]
+#[serde(rename_all = "snake_case")]
+pub enum AvxFeature {
+    /// Advanced Vector Extensions 2.
+    Avx2,
+    /// AVX-512 Foundation.
+    Avx512f,
+    /// AVX-512 Doubleword and Quadword Instructions.
+    Avx512dq,
+    /// AVX-512 Integer Fused Multiply-Add Instructions.
+    Avx512ifma,
+    /// AVX-512 Prefetch Instructions.
+    Avx512pf,
+    /// AVX-512 Exponential and Reciprocal Instructions.
+    Avx512er,
+    /// AVX-512 Conflict Detection Instructions.
+    Avx512cd,
+    /// AVX-512 Byte and Word Instructions.
+    Avx512bw,
+    /// AVX-512 Vector Length Extensions.
+    Avx512vl,
+    /// AVX-512 Vector Byte Manipulation Instructions.
+    Avx512vbmi,
+    /// AVX-512 Vector Byte Manipulation Instructions 2.
+    Avx512Vbmi2,
+    /// AVX-512 Vector Neural Network Instructions.
+    Avx512Vnni,
+    /// AVX-512 Bit Algorithms.
+    Avx512Bitalg,
+    /// AVX-512 Vector Population Count Instructions.
+    Avx512Vpopcntdq,
+    /// AVX-512 4-iteration Vector Neural Network Instructions Word Variable Precision.
+    #[serde(rename = "avx512_4vnniw")]
+    Avx5124Vnniw,
+    /// AVX-512 4-iteration Fused Multiply Accumulation Packed Single Precision.
+    #[serde(rename = "avx512_4fmaps")]
+    Avx5124Fmaps,
+    /// AVX-512 Vector Pair Intersection Instructions.
+    Avx512Vp2intersect,
+    /// AVX-512 Half Precision Floating Point Instructions.
+    Avx512Fp16,
+}
+
+impl DeviceMemoryConfig {
+    /// Returns the guest physical address range `[start, end)` covered by the window.
+    fn range(&self) -> Result<(u64, u64), VmConfigError> {
+        let alignment = (DEVICE_MEMORY_ALIGNMENT_MIB as u64) << 20;
+        let size = u64::try_from(self.size_mib)
+            .ok()
+            .and_then(|size_mib| size_mib.checked_mul(1 << 20))
+            .ok_or(VmConfigError::InvalidDeviceMemory)?;
+
+        if size == 0 || self.guest_address % alignment != 0 || size % alignment != 0 {
+            return Err(VmConfigError::InvalidDeviceMemory);
+        }
+
+        let end = self
+            .guest_address
+            .checked_add(size)
+            .ok_or(VmConfigError::InvalidDeviceMemory)?;
+
+        Ok((self.guest_address, end))
+    }
+
+    /// Checks that the window is well formed and does not overlap the guest RAM of a microVM with
+    /// `mem_size_mib` MiB of memory, nor the MMIO area.
+    fn validate(&self, mem_size_mib: usize) -> Result<(), VmConfigError> {
+        let (start, end) = self.range()?;
+        let overlaps = |region_start: u64, region_size: u64| {
+            start < region_start + region_size && region_start < end
+        };
+
+        if arch_memory_regions(mem_size_mib << 20)
+            .iter()
+            .any(|(addr, size)| overlaps(addr.0, *size as u64))
+        {
+            return Err(VmConfigError::DeviceMemoryOverlapsRam);
+        }
+
+        if overlaps(MMIO_MEM_START, MMIO_MEM_SIZE) {
+            return Err(VmConfigError::DeviceMemoryOverlapsMmio);
+        }
+
+        Ok(())
+    }
+}
+
+/// Returns the guest RAM regions of a microVM with `mem_size_mib` MiB of memory, each split into
+/// regions of at most `memslot_size_mib` MiB, if set. Each region is registered with KVM as a
+/// separate memory slot.
+pub(crate) fn memory_regions(
+    mem_size_mib: usize,
+    memslot_size_mib: Option<usize>,
+) -> Vec<(GuestAddress, usize)> {
+    let regions = arch_memory_regions(mem_size_mib << 20);
+    let Some(memslot_size) = memslot_size_mib.map(|size_mib| size_mib.saturating_mul(1 << 20))
+    else {
+        return regions;
+    };
+
+    regions
+        .into_iter()
+        .flat_map(|(start, size)| {
+            (0..size).step_by(memslot_size).map(move |offset| {
+                (
+                    start.unchecked_add(offset as u64),
+                    memslot_size.min(size - offset),
+                )
+            })
+        })
+        .collect()
+}
+
+/// A guest physical memory region, as reported by the GET `/memory/layout` API call.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize)]
+pub struct MemoryRegionInfo {
+    /// Guest physical address at which the region starts.
+    pub guest_address: u64,
+    /// The size of the region in bytes.
+    pub size: u64,
+}
+
+/// Struct used in GET `/memory/layout` API call.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize)]
+pub struct MemoryLayout {
+    /// The guest RAM regions.
+    pub ram: Vec<MemoryRegionInfo>,
+    /// The area reserved for MMIO devices.
+    pub mmio: MemoryRegionInfo,
+    /// The reserved device memory window, if configured.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub device_memory: Option<MemoryRegionInfo>,
+}
+
+impl From<&VmConfig> for MemoryLayout {
+    fn from(value: &VmConfig) -> Self {
+        let ram = value
+            .memory_regions()
+            .into_iter()
+            .map(|(addr, size)| MemoryRegionInfo {
+                guest_address: addr.0,
+                size: size as u64,
+            })
+            .collect();
+
+        Self {
+            ram,
+            mmio: MemoryRegionInfo {
+                guest_address: MMIO_MEM_START,
+                size: MMIO_MEM_SIZE,
+            },
+            device_memory: value.device_memory.map(|window| MemoryRegionInfo {
+                guest_address: window.guest_address,
+                size: (window.size_mib as u64) << 20,
+            }),
+        }
+    }
+}
+
+/// CPUID settings of the machine configuration (x86_64 only). They are applied when the CPUID of
+/// each vCPU is normalized, after the CPU template, so they take precedence over the
+/// `cpuid_modifiers` of a custom CPU template changing the same bits. Settings left to `None`
+/// keep the default normalization.
+#[derive(Clone, Debug, Default, PartialEq, Eq)]
+pub struct CpuidNormalizeOptions {
+    /// CPU vendor ID reported to the guest in CPUID leaf 0x0, instead of the host one.
+    pub vendor_id: Option<String>,
+    /// CPU brand string reported to the guest in CPUID leaves 0x80000002-0x80000004, instead of
+    /// the default one.
+    pub brand_string: Option<String>,
+    /// Number of cache levels advertised to the guest in CPUID leaf 0x4.
+    pub cache_levels: Option<u8>,
+    /// CPU family, model and stepping reported to the guest in CPUID leaf 0x1.
+    pub cpu_signature: Option<CpuSignature>,
+    /// Base, maximum and bus frequencies reported to the guest in CPUID leaf 0x16.
+    pub cpu_frequencies: Option<CpuFrequencies>,
+    /// Sockets, cores per socket and threads per core presented to the guest in CPUID leaf 0xB.
+    pub cpu_topology: Option<CpuTopology>,
+    /// Whether an invariant TSC is advertised to the guest in CPUID leaf 0x80000007, instead of
+    /// the host value. It can only be advertised if the host has one.
+    pub invariant_tsc: Option<bool>,
+    /// Maximum standard and extended CPUID leaves advertised to the guest, instead of the host
+    /// ones.
+    pub max_cpuid_leaves: Option<CpuidMaxLeaves>,
+    /// Cache line size, in bytes, advertised to the guest in CPUID instead of the host one.
+    pub cache_line_size: Option<u16>,
+    /// AVX and AVX-512 features of CPUID leaf 0x7 advertised to the guest. Features mapped to
+    /// `false` are masked, features mapped to `true` are required to be supported by the host.
+    pub avx_features: Option<BTreeMap<AvxFeature, bool>>,
+    /// Whether the hypervisor present bit of CPUID leaf 0x1 is advertised to the guest, which it
+    /// is by default.
+    pub hypervisor_bit: Option<bool>,
+    /// Whether to advertise the RDRAND instruction to the guest (CPUID leaf 0x1, ECX bit 30).
+    /// The host value is passed through by default.
+    pub rdrand: Option<bool>,
+    /// Whether to advertise the RDSEED instruction to the guest (CPUID leaf 0x7, EBX bit 18).
+    /// The host value is passed through by default.
+    pub rdseed: Option<bool>,
+    /// Whether to advertise the MONITOR and MWAIT instructions to the guest (CPUID leaf 0x1, ECX
+    /// bit 3, and leaf 0x5). What KVM reports is passed through by default.
+    pub monitor_mwait: Option<bool>,
+    /// Whether to advertise the architectural PMU reported by KVM to the guest (CPUID leaf 0xA),
+    /// which is cleared by default. Unless `true`, the KVM PMU of the microVM is disabled as well,
+    /// by default only where KVM allows it. Only supported on Intel.
+    pub pmu: Option<bool>,
+    /// Whether to advertise the TSC deadline mode of the local APIC timer to the guest (CPUID
+    /// leaf 0x1, ECX bit 24), which is advertised by default.
+    pub tsc_deadline: Option<bool>,
+}
+
 /// Struct used in PUT `/machine-config` API call.
 #[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
@@ -111,6 +701,96 @@ pub struct MachineConfig {
     /// Configures what page size Firecracker should use to back guest memory.
     #[serde(default)]
     pub huge_pages: HugePageConfig,
+    /// Guest physical address window reserved for device memory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_memory: Option<DeviceMemoryConfig>,
+    /// See [`CpuidNormalizeOptions::vendor_id`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vendor_id: Option<String>,
+    /// See [`CpuidNormalizeOptions::brand_string`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub brand_string: Option<String>,
+    /// See [`CpuidNormalizeOptions::cache_levels`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_levels: Option<u8>,
+    /// Maximum size in MiB of the memory slots the guest memory is split into.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memslot_size_mib: Option<usize>,
+    /// See [`CpuidNormalizeOptions::cpu_signature`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_signature: Option<CpuSignature>,
+    /// See [`CpuidNormalizeOptions::cpu_frequencies`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_frequencies: Option<CpuFrequencies>,
+    /// See [`CpuidNormalizeOptions::cpu_topology`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_topology: Option<CpuTopology>,
+    /// Smallest memory size in MiB the guest memory allocation may fall back to, if the memory
+    /// size cannot be allocated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_mem_size_mib: Option<usize>,
+    /// What to do if the host available memory does not fit the guest memory when the microVM
+    /// starts. Not checked by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_memory_check: Option<HostMemoryPolicy>,
+    /// Maximum number of consecutive spurious `KVM_RUN` exits a vCPU re-enters the guest after,
+    /// before failing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kvm_run_retries: Option<u32>,
+    /// See [`CpuidNormalizeOptions::invariant_tsc`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invariant_tsc: Option<bool>,
+    /// Transparent huge page advice applied to the guest memory after it is allocated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thp_advice: Option<ThpAdvice>,
+    /// See [`CpuidNormalizeOptions::max_cpuid_leaves`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cpuid_leaves: Option<CpuidMaxLeaves>,
+    /// How vCPU threads wait for an interrupt while the guest is halted, instead of the KVM
+    /// default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vcpu_idle_strategy: Option<VcpuIdleStrategy>,
+    /// Byte the guest memory is filled with when it is allocated, instead of zeroes. Insecure,
+    /// only meant for detecting reads of uninitialized memory in the guest while debugging.
+    #[cfg(feature = "mem-poison")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mem_poison: Option<u8>,
+    /// See [`CpuidNormalizeOptions::cache_line_size`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_line_size: Option<u16>,
+    /// Path of a file on a tmpfs or hugetlbfs mount backing the guest memory, which other
+    /// processes can map to access the same memory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mem_backing_file: Option<PathBuf>,
+    /// See [`CpuidNormalizeOptions::avx_features`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avx_features: Option<BTreeMap<AvxFeature, bool>>,
+    /// See [`CpuidNormalizeOptions::hypervisor_bit`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hypervisor_bit: Option<bool>,
+    /// See [`CpuidNormalizeOptions::rdrand`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rdrand: Option<bool>,
+    /// See [`CpuidNormalizeOptions::rdseed`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rdseed: Option<bool>,
+    /// See [`CpuidNormalizeOptions::monitor_mwait`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitor_mwait: Option<bool>,
+    /// See [`CpuidNormalizeOptions::pmu`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pmu: Option<bool>,
+    /// See [`CpuidNormalizeOptions::tsc_deadline`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tsc_deadline: Option<bool>,
+    /// Rate limiter of the guest output to the serial console, in bytes. Output exceeding
+    /// the limit is dropped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serial_output_rate_limiter: Option<TokenBucketConfig>,
+    /// Maximum number of descriptors in the virtio descriptor chains the devices process.
+    /// Longer chains are given back to the guest driver without being processed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_desc_chain_len: Option<u16>,
     /// GDB socket address.
     #[cfg(feature = "gdb")]
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -128,7 +808,9 @@ impl Default for MachineConfig {
 /// This struct mirrors all the fields in `MachineConfig`.
 /// All fields are optional, but at least one needs to be specified.
 /// If a field is `Some(value)` then we assume an update is requested
-/// for that field.
+/// for that field. The fields which are optional in `MachineConfig` are
+/// reset by `Some(None)`, which is only set when the whole machine
+/// configuration is replaced by a PUT request.
 #[derive(Clone, Default, Debug, PartialEq, Eq, Deserialize, Serialize)]
 #[serde(deny_unknown_fields)]
 pub struct MachineConfigUpdate {
@@ -150,6 +832,96 @@ pub struct MachineConfigUpdate {
     /// Configures what page size Firecracker should use to back guest memory.
     #[serde(default, skip_serializing_if = "Option::is_none")]
     pub huge_pages: Option<HugePageConfig>,
+    /// Guest physical address window reserved for device memory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub device_memory: Option<Option<DeviceMemoryConfig>>,
+    /// See [`CpuidNormalizeOptions::vendor_id`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vendor_id: Option<Option<String>>,
+    /// See [`CpuidNormalizeOptions::brand_string`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub brand_string: Option<Option<String>>,
+    /// See [`CpuidNormalizeOptions::cache_levels`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_levels: Option<Option<u8>>,
+    /// Maximum size in MiB of the memory slots the guest memory is split into.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub memslot_size_mib: Option<Option<usize>>,
+    /// See [`CpuidNormalizeOptions::cpu_signature`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_signature: Option<Option<CpuSignature>>,
+    /// See [`CpuidNormalizeOptions::cpu_frequencies`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_frequencies: Option<Option<CpuFrequencies>>,
+    /// See [`CpuidNormalizeOptions::cpu_topology`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cpu_topology: Option<Option<CpuTopology>>,
+    /// Smallest memory size in MiB the guest memory allocation may fall back to, if the memory
+    /// size cannot be allocated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub min_mem_size_mib: Option<Option<usize>>,
+    /// What to do if the host available memory does not fit the guest memory when the microVM
+    /// starts. Not checked by default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub host_memory_check: Option<Option<HostMemoryPolicy>>,
+    /// Maximum number of consecutive spurious `KVM_RUN` exits a vCPU re-enters the guest after,
+    /// before failing.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub kvm_run_retries: Option<Option<u32>>,
+    /// See [`CpuidNormalizeOptions::invariant_tsc`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub invariant_tsc: Option<Option<bool>>,
+    /// Transparent huge page advice applied to the guest memory after it is allocated.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub thp_advice: Option<Option<ThpAdvice>>,
+    /// See [`CpuidNormalizeOptions::max_cpuid_leaves`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_cpuid_leaves: Option<Option<CpuidMaxLeaves>>,
+    /// How vCPU threads wait for an interrupt while the guest is halted, instead of the KVM
+    /// default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub vcpu_idle_strategy: Option<Option<VcpuIdleStrategy>>,
+    /// Byte the guest memory is filled with when it is allocated, instead of zeroes. Insecure,
+    /// only meant for detecting reads of uninitialized memory in the guest while debugging.
+    #[cfg(feature = "mem-poison")]
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mem_poison: Option<Option<u8>>,
+    /// See [`CpuidNormalizeOptions::cache_line_size`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub cache_line_size: Option<Option<u16>>,
+    /// Path of a file on a tmpfs or hugetlbfs mount backing the guest memory, which other
+    /// processes can map to access the same memory.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mem_backing_file: Option<Option<PathBuf>>,
+    /// See [`CpuidNormalizeOptions::avx_features`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub avx_features: Option<Option<BTreeMap<AvxFeature, bool>>>,
+    /// See [`CpuidNormalizeOptions::hypervisor_bit`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub hypervisor_bit: Option<Option<bool>>,
+    /// See [`CpuidNormalizeOptions::rdrand`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rdrand: Option<Option<bool>>,
+    /// See [`CpuidNormalizeOptions::rdseed`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub rdseed: Option<Option<bool>>,
+    /// See [`CpuidNormalizeOptions::monitor_mwait`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub monitor_mwait: Option<Option<bool>>,
+    /// See [`CpuidNormalizeOptions::pmu`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub pmu: Option<Option<bool>>,
+    /// See [`CpuidNormalizeOptions::tsc_deadline`].
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub tsc_deadline: Option<Option<bool>>,
+    /// Rate limiter of the guest output to the serial console, in bytes. Output exceeding
+    /// the limit is dropped.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub serial_output_rate_limiter: Option<Option<TokenBucketConfig>>,
+    /// Maximum number of descriptors in the virtio descriptor chains the devices process.
+    /// Longer chains are given back to the guest driver without being processed.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub max_desc_chain_len: Option<Option<u16>>,
     /// GDB socket address.
     #[cfg(feature = "gdb")]
     #[serde(default, skip_serializing_if = "Option::is_none")]
@@ -174,6 +946,34 @@ impl From<MachineConfig> for MachineConfigUpdate {
             cpu_template: cfg.cpu_template,
             track_dirty_pages: Some(cfg.track_dirty_pages),
             huge_pages: Some(cfg.huge_pages),
+            device_memory: Some(cfg.device_memory),
+            vendor_id: Some(cfg.vendor_id),
+            brand_string: Some(cfg.brand_string),
+            cache_levels: Some(cfg.cache_levels),
+            memslot_size_mib: Some(cfg.memslot_size_mib),
+            cpu_signature: Some(cfg.cpu_signature),
+            cpu_frequencies: Some(cfg.cpu_frequencies),
+            cpu_topology: Some(cfg.cpu_topology),
+            min_mem_size_mib: Some(cfg.min_mem_size_mib),
+            host_memory_check: Some(cfg.host_memory_check),
+            kvm_run_retries: Some(cfg.kvm_run_retries),
+            invariant_tsc: Some(cfg.invariant_tsc),
+            thp_advice: Some(cfg.thp_advice),
+            max_cpuid_leaves: Some(cfg.max_cpuid_leaves),
+            vcpu_idle_strategy: Some(cfg.vcpu_idle_strategy),
+            #[cfg(feature = "mem-poison")]
+            mem_poison: Some(cfg.mem_poison),
+            cache_line_size: Some(cfg.cache_line_size),
+            mem_backing_file: Some(cfg.mem_backing_file),
+            avx_features: Some(cfg.avx_features),
+            hypervisor_bit: Some(cfg.hypervisor_bit),
+            rdrand: Some(cfg.rdrand),
+            rdseed: Some(cfg.rdseed),
+            monitor_mwait: Some(cfg.monitor_mwait),
+            pmu: Some(cfg.pmu),
+            tsc_deadline: Some(cfg.tsc_deadline),
+            serial_output_rate_limiter: Some(cfg.serial_output_rate_limiter),
+            max_desc_chain_len: Some(cfg.max_desc_chain_len),
             #[cfg(feature = "gdb")]
             gdb_socket_path: cfg.gdb_socket_path,
         }
@@ -195,6 +995,39 @@ pub struct VmConfig {
     pub track_dirty_pages: bool,
     /// Configures what page size Firecracker should use to back guest memory.
     pub huge_pages: HugePageConfig,
+    /// Guest physical address window reserved for device memory.
+    pub device_memory: Option<DeviceMemoryConfig>,
+    /// CPUID settings, applied when the CPUID of each vCPU is normalized.
+    pub cpuid: CpuidNormalizeOptions,
+    /// Maximum size in MiB of the memory slots the guest memory is split into.
+    pub memslot_size_mib: Option<usize>,
+    /// Smallest memory size in MiB the guest memory allocation may fall back to, if the memory
+    /// size cannot be allocated.
+    pub min_mem_size_mib: Option<usize>,
+    /// What to do if the host available memory does not fit the guest memory when the microVM
+    /// starts. Not checked by default.
+    pub host_memory_check: Option<HostMemoryPolicy>,
+    /// Maximum number of consecutive spurious `KVM_RUN` exits a vCPU re-enters the guest after,
+    /// before failing.
+    pub kvm_run_retries: Option<u32>,
+    /// Transparent huge page advice applied to the guest memory after it is allocated.
+    pub thp_advice: Option<ThpAdvice>,
+    /// How vCPU threads wait for an interrupt while the guest is halted, instead of the KVM
+    /// default.
+    pub vcpu_idle_strategy: Option<VcpuIdleStrategy>,
+    /// Byte the guest memory is filled with when it is allocated, instead of zeroes. Insecure,
+    /// only meant for detecting reads of uninitialized memory in the guest while debugging.
+    #[cfg(feature = "mem-poison")]
+    pub mem_poison: Option<u8>,
+    /// Path of a file on a tmpfs or hugetlbfs mount backing the guest memory, which other
+    /// processes can map to access the same memory.
+    pub mem_backing_file: Option<PathBuf>,
+    /// Rate limiter of the guest output to the serial console, in bytes. Output exceeding
+    /// the limit is dropped.
+    pub serial_output_rate_limiter: Option<TokenBucketConfig>,
+    /// Maximum number of descriptors in the virtio descriptor chains the devices process.
+    /// Longer chains are given back to the guest driver without being processed.
+    pub max_desc_chain_len: Option<u16>,
     /// GDB socket address.
     #[cfg(feature = "gdb")]
     pub gdb_socket_path: Option<String>,
@@ -206,6 +1039,33 @@ impl VmConfig {
         self.cpu_template = Some(CpuTemplateType::Custom(cpu_template));
     }
 
+    /// Returns the guest RAM regions, each registered with KVM as a separate memory slot.
+    pub fn memory_regions(&self) -> Vec<(GuestAddress, usize)> {
+        memory_regions(self.mem_size_mib, self.memslot_size_mib)
+    }
+
+    /// Returns the frequencies that will be advertised to the guest in CPUID leaf 0x16: the ones
+    /// configured by the user, or else the host ones KVM passes through, if the host reports
+    /// any and the configured maximum standard leaf does not hide the leaf.
+    ///
+    /// This is only an estimate of the guest CPUID, which a CPU template can still alter, so
+    /// once the microVM is built the frequencies are read from the CPUID of its vCPUs instead.
+    pub fn advertised_cpu_frequencies(&self) -> Option<CpuFrequencies> {
+        #[cfg(target_arch = "x86_64")]
+        return self.cpuid.cpu_frequencies.or_else(|| {
+            let max_standard_leaf = self
+                .cpuid
+                .max_cpuid_leaves
+                .and_then(|max_leaves| max_leaves.standard);
+            if max_standard_leaf.is_some_and(|leaf| leaf < CPU_FREQUENCIES_LEAF) {
+                return None;
+            }
+            CpuFrequencies::host()
+        });
+        #[cfg(target_arch = "aarch64")]
+        return self.cpuid.cpu_frequencies;
+    }
+
     /// Updates [`VmConfig`] with [`MachineConfigUpdate`].
     /// Mapping for cpu template update:
     /// StaticCpuTemplate::None -> None
@@ -238,6 +1098,221 @@ impl VmConfig {
             return Err(VmConfigError::InvalidMemorySize);
         }
 
+        let device_memory = update.device_memory.unwrap_or(self.device_memory);
+        if let Some(window) = device_memory {
+            window.validate(mem_size_mib)?;
+        }
+
+        let vendor_id = update
+            .vendor_id
+            .clone()
+            .unwrap_or_else(|| self.cpuid.vendor_id.clone());
+        #[cfg(target_arch = "aarch64")]
+        if vendor_id.is_some() {
+            return Err(VmConfigError::VendorIdNotSupported);
+        }
+
+        if let Some(vendor_id) = &vendor_id {
+            if vendor_id.len() != VENDOR_ID_LEN
+                || !vendor_id.bytes().all(|b| b.is_ascii_graphic() || b == b' ')
+            {
+                return Err(VmConfigError::InvalidVendorId);
+            }
+        }
+
+        let brand_string = update
+            .brand_string
+            .clone()
+            .unwrap_or_else(|| self.cpuid.brand_string.clone());
+        #[cfg(target_arch = "aarch64")]
+        if brand_string.is_some() {
+            return Err(VmConfigError::BrandStringNotSupported);
+        }
+
+        if let Some(brand_string) = &brand_string {
+            if brand_string.is_empty()
+                || brand_string.len() > BRAND_STRING_MAX_LEN
+                || !brand_string
+                    .bytes()
+                    .all(|b| b.is_ascii_graphic() || b == b' ')
+            {
+                return Err(VmConfigError::InvalidBrandString);
+            }
+        }
+
+        let cache_levels = update.cache_levels.unwrap_or(self.cpuid.cache_levels);
+        #[cfg(target_arch = "aarch64")]
+        if cache_levels.is_some() {
+            return Err(VmConfigError::CacheLevelsNotSupported);
+        }
+
+        if let Some(levels) = cache_levels {
+            if levels == 0 || levels > MAX_CACHE_LEVELS {
+                return Err(VmConfigError::InvalidCacheLevels);
+            }
+        }
+
+        let memslot_size_mib = update.memslot_size_mib.unwrap_or(self.memslot_size_mib);
+        if let Some(size_mib) = memslot_size_mib {
+            if size_mib == 0 || !page_config.is_valid_mem_size(size_mib) {
+                return Err(VmConfigError::InvalidMemslotSize);
+            }
+
+            let memslots = memory_regions(mem_size_mib, memslot_size_mib).len();
+            if memslots > MAX_MEMSLOTS {
+                return Err(VmConfigError::TooManyMemslots(memslots));
+            }
+        }
+
+        let cpu_signature = update.cpu_signature.unwrap_or(self.cpuid.cpu_signature);
+        #[cfg(target_arch = "aarch64")]
+        if cpu_signature.is_some() {
+            return Err(VmConfigError::CpuSignatureNotSupported);
+        }
+
+        if let Some(signature) = cpu_signature {
+            signature.validate()?;
+        }
+
+        let cpu_frequencies = update.cpu_frequencies.unwrap_or(self.cpuid.cpu_frequencies);
+        #[cfg(target_arch = "aarch64")]
+        if cpu_frequencies.is_some() {
+            return Err(VmConfigError::CpuFrequenciesNotSupported);
+        }
+
+        if let Some(frequencies) = cpu_frequencies {
+            frequencies.validate()?;
+        }
+
+        let cpu_topology = update.cpu_topology.unwrap_or(self.cpuid.cpu_topology);
+        #[cfg(target_arch = "aarch64")]
+        if cpu_topology.is_some() {
+            return Err(VmConfigError::CpuTopologyNotSupported);
+        }
+
+        if let Some(topology) = cpu_topology {
+            topology.validate(vcpu_count, smt)?;
+        }
+
+        let min_mem_size_mib = update.min_mem_size_mib.unwrap_or(self.min_mem_size_mib);
+        if let Some(size_mib) = min_mem_size_mib {
+            if size_mib == 0 || size_mib > mem_size_mib || !page_config.is_valid_mem_size(size_mib)
+            {
+                return Err(VmConfigError::InvalidMinMemorySize);
+            }
+        }
+
+        let host_memory_check = update.host_memory_check.unwrap_or(self.host_memory_check);
+
+        let invariant_tsc = update.invariant_tsc.unwrap_or(self.cpuid.invariant_tsc);
+        #[cfg(target_arch = "aarch64")]
+        if invariant_tsc.is_some() {
+            return Err(VmConfigError::InvariantTscNotSupported);
+        }
+
+        let thp_advice = update.thp_advice.unwrap_or(self.thp_advice);
+        if thp_advice.is_some() && page_config.is_hugetlbfs() {
+            return Err(VmConfigError::ThpAdviceWithHugetlbfs);
+        }
+
+        let max_cpuid_leaves = update
+            .max_cpuid_leaves
+            .unwrap_or(self.cpuid.max_cpuid_leaves);
+        #[cfg(target_arch = "aarch64")]
+        if max_cpuid_leaves.is_some() {
+            return Err(VmConfigError::MaxCpuidLeavesNotSupported);
+        }
+
+        if let Some(max_leaves) = max_cpuid_leaves {
+            max_leaves.validate()?;
+        }
+
+        // The leaf is added for the configured frequencies, but trimmed along with the leaves
+        // above the maximum standard leaf afterwards.
+        let max_standard_leaf = max_cpuid_leaves.and_then(|max_leaves| max_leaves.standard);
+        if cpu_frequencies.is_some()
+            && max_standard_leaf.is_some_and(|leaf| leaf < CPU_FREQUENCIES_LEAF)
+        {
+            return Err(VmConfigError::CpuFrequenciesAboveMaxCpuidLeaf);
+        }
+
+        let vcpu_idle_strategy = update.vcpu_idle_strategy.unwrap_or(self.vcpu_idle_strategy);
+        #[cfg(feature = "mem-poison")]
+        let mem_poison = update.mem_poison.unwrap_or(self.mem_poison);
+
+        let cache_line_size = update.cache_line_size.unwrap_or(self.cpuid.cache_line_size);
+        #[cfg(target_arch = "aarch64")]
+        if cache_line_size.is_some() {
+            return Err(VmConfigError::CacheLineSizeNotSupported);
+        }
+
+        // The size is advertised in 8 bits wide CPUID fields, some in bytes and some in
+        // multiples of 8 bytes.
+        if let Some(size) = cache_line_size {
+            if !(8..=128).contains(&size) || !size.is_power_of_two() {
+                return Err(VmConfigError::InvalidCacheLineSize);
+            }
+        }
+
+        let mem_backing_file = update
+            .mem_backing_file
+            .clone()
+            .unwrap_or_else(|| self.mem_backing_file.clone());
+        if let Some(path) = &mem_backing_file {
+            validate_mem_backing_file(path, page_config)?;
+        }
+
+        // Whether the features required by the user are supported is only known once the CPUID
+        // is normalized.
+        let avx_features = update
+            .avx_features
+            .clone()
+            .unwrap_or_else(|| self.cpuid.avx_features.clone());
+        #[cfg(target_arch = "aarch64")]
+        if avx_features.is_some() {
+            return Err(VmConfigError::AvxFeaturesNotSupported);
+        }
+
+        let hypervisor_bit = update.hypervisor_bit.unwrap_or(self.cpuid.hypervisor_bit);
+        #[cfg(target_arch = "aarch64")]
+        if hypervisor_bit.is_some() {
+            return Err(VmConfigError::HypervisorBitNotSupported);
+        }
+
+        let rdrand = update.rdrand.unwrap_or(self.cpuid.rdrand);
+        let rdseed = update.rdseed.unwrap_or(self.cpuid.rdseed);
+        #[cfg(target_arch = "aarch64")]
+        if rdrand.is_some() || rdseed.is_some() {
+            return Err(VmConfigError::RngInstructionsNotSupported);
+        }
+
+        let monitor_mwait = update.monitor_mwait.unwrap_or(self.cpuid.monitor_mwait);
+        #[cfg(target_arch = "aarch64")]
+        if monitor_mwait.is_some() {
+            return Err(VmConfigError::MonitorMwaitNotSupported);
+        }
+
+        let pmu = update.pmu.unwrap_or(self.cpuid.pmu);
+        #[cfg(target_arch = "aarch64")]
+        if pmu.is_some() {
+            return Err(VmConfigError::PmuNotSupported);
+        }
+
+        let tsc_deadline = update.tsc_deadline.unwrap_or(self.cpuid.tsc_deadline);
+        #[cfg(target_arch = "aarch64")]
+        if tsc_deadline.is_some() {
+            return Err(VmConfigError::TscDeadlineNotSupported);
+        }
+
+        let serial_output_rate_limiter = update
+            .serial_output_rate_limiter
+            .unwrap_or(self.serial_output_rate_limiter);
+
+        let max_desc_chain_len = update.max_desc_chain_len.unwrap_or(self.max_desc_chain_len);
+        if max_desc_chain_len.is_some_and(|len| len < MIN_DESC_CHAIN_LEN) {
+            return Err(VmConfigError::InvalidMaxDescChainLen);
+        }
+
         let cpu_template = match update.cpu_template {
             None => self.cpu_template.clone(),
             Some(StaticCpuTemplate::None) => None,
@@ -251,6 +1326,36 @@ impl VmConfig {
             cpu_template,
             track_dirty_pages: update.track_dirty_pages.unwrap_or(self.track_dirty_pages),
             huge_pages: page_config,
+            device_memory,
+            cpuid: CpuidNormalizeOptions {
+                vendor_id,
+                brand_string,
+                cache_levels,
+                cpu_signature,
+                cpu_frequencies,
+                cpu_topology,
+                invariant_tsc,
+                max_cpuid_leaves,
+                cache_line_size,
+                avx_features,
+                hypervisor_bit,
+                rdrand,
+                rdseed,
+                monitor_mwait,
+                pmu,
+                tsc_deadline,
+            },
+            memslot_size_mib,
+            min_mem_size_mib,
+            host_memory_check,
+            kvm_run_retries: update.kvm_run_retries.unwrap_or(self.kvm_run_retries),
+            thp_advice,
+            vcpu_idle_strategy,
+            #[cfg(feature = "mem-poison")]
+            mem_poison,
+            mem_backing_file,
+            serial_output_rate_limiter,
+            max_desc_chain_len,
             #[cfg(feature = "gdb")]
             gdb_socket_path: update.gdb_socket_path.clone(),
         })
@@ -266,6 +1371,19 @@ impl Default for VmConfig {
             cpu_template: None,
             track_dirty_pages: false,
             huge_pages: HugePageConfig::None,
+            device_memory: None,
+            cpuid: CpuidNormalizeOptions::default(),
+            memslot_size_mib: None,
+            min_mem_size_mib: None,
+            host_memory_check: None,
+            kvm_run_retries: None,
+            thp_advice: None,
+            vcpu_idle_strategy: None,
+            #[cfg(feature = "mem-poison")]
+            mem_poison: None,
+            mem_backing_file: None,
+            serial_output_rate_limiter: None,
+            max_desc_chain_len: None,
             #[cfg(feature = "gdb")]
             gdb_socket_path: None,
         }
@@ -281,6 +1399,34 @@ impl From<&VmConfig> for MachineConfig {
             cpu_template: value.cpu_template.as_ref().map(|template| template.into()),
             track_dirty_pages: value.track_dirty_pages,
             huge_pages: value.huge_pages,
+            device_memory: value.device_memory,
+            vendor_id: value.cpuid.vendor_id.clone(),
+            brand_string: value.cpuid.brand_string.clone(),
+            cache_levels: value.cpuid.cache_levels,
+            memslot_size_mib: value.memslot_size_mib,
+            cpu_signature: value.cpuid.cpu_signature,
+            cpu_frequencies: value.cpuid.cpu_frequencies,
+            cpu_topology: value.cpuid.cpu_topology,
+            min_mem_size_mib: value.min_mem_size_mib,
+            host_memory_check: value.host_memory_check,
+            kvm_run_retries: value.kvm_run_retries,
+            invariant_tsc: value.cpuid.invariant_tsc,
+            thp_advice: value.thp_advice,
+            max_cpuid_leaves: value.cpuid.max_cpuid_leaves,
+            vcpu_idle_strategy: value.vcpu_idle_strategy,
+            #[cfg(feature = "mem-poison")]
+            mem_poison: value.mem_poison,
+            cache_line_size: value.cpuid.cache_line_size,
+            mem_backing_file: value.mem_backing_file.clone(),
+            avx_features: value.cpuid.avx_features.clone(),
+            hypervisor_bit: value.cpuid.hypervisor_bit,
+            rdrand: value.cpuid.rdrand,
+            rdseed: value.cpuid.rdseed,
+            monitor_mwait: value.cpuid.monitor_mwait,
+            pmu: value.cpuid.pmu,
+            tsc_deadline: value.cpuid.tsc_deadline,
+            serial_output_rate_limiter: value.serial_output_rate_limiter,
+            max_desc_chain_len: value.max_desc_chain_len,
             #[cfg(feature = "gdb")]
             gdb_socket_path: value.gdb_socket_path.clone(),
         }
@@ -5,8 +5,12 @@
 use std::path::PathBuf;
 
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 use super::open_file_nonblock;
+use crate::devices::virtio::block::virtio::metrics::BlockMetricsPerDevice;
+use crate::devices::virtio::net::metrics::NetMetricsPerDevice;
+use crate::devices::virtio::vhost_user_metrics::VhostUserMetricsPerDevice;
 use crate::logger::{FcLineWriter, METRICS};
 
 /// Strongly typed structure used to describe the metrics system.
@@ -21,6 +25,46 @@ pub struct MetricsConfig {
 pub enum MetricsConfigError {
     /// Cannot initialize the metrics system due to bad user input: {0}
     InitializationFailure(String),
+    /// No metrics found for the {0} device with id `{1}`.
+    DeviceNotFound(&'static str, String),
+    /// Cannot serialize the device metrics: {0}
+    Serialization(String),
+}
+
+/// Outcome of a metrics flush requested through the API.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct FlushResult {
+    /// Number of bytes written to the metrics destination. It is 0 if the metrics system was not
+    /// configured.
+    pub bytes_written: u64,
+}
+
+/// Types of devices having metrics kept at per device granularity.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum MetricsDeviceType {
+    /// Block device (virtio or vhost-user), identified by its drive id.
+    Block,
+    /// Network device, identified by its interface id.
+    Net,
+}
+
+impl MetricsDeviceType {
+    /// Name of the device type, as used in the API paths.
+    pub fn name(&self) -> &'static str {
+        match self {
+            MetricsDeviceType::Block => "block",
+            MetricsDeviceType::Net => "net",
+        }
+    }
+}
+
+/// Identifies the device whose metrics are requested.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct DeviceMetricsRequest {
+    /// Type of the device.
+    pub device_type: MetricsDeviceType,
+    /// Id of the device, as it was configured.
+    pub device_id: String,
 }
 
 /// Configures the metrics as described in `metrics_cfg`.
@@ -34,11 +78,36 @@ pub fn init_metrics(metrics_cfg: MetricsConfig) -> Result<(), MetricsConfigError
         .map_err(|err| MetricsConfigError::InitializationFailure(err.to_string()))
 }
 
+/// Returns the metrics subtree of the device described by `request`.
+/// The metrics are read without resetting them, so the counters hold the totals since the
+/// device was created and the next flush still reports them.
+pub fn device_metrics(request: &DeviceMetricsRequest) -> Result<Value, MetricsConfigError> {
+    let device_id = request.device_id.as_str();
+    let metrics = match request.device_type {
+        MetricsDeviceType::Block => match BlockMetricsPerDevice::get(device_id) {
+            Some(metrics) => Some(serde_json::to_value(metrics.values())),
+            // vhost-user block devices register their metrics as `block_{drive_id}`.
+            None => VhostUserMetricsPerDevice::get(&format!("block_{}", device_id))
+                .map(|metrics| serde_json::to_value(metrics.values())),
+        },
+        MetricsDeviceType::Net => NetMetricsPerDevice::get(device_id)
+            .map(|metrics| serde_json::to_value(metrics.values())),
+    };
+
+    metrics
+        .ok_or_else(|| {
+            MetricsConfigError::DeviceNotFound(request.device_type.name(), device_id.to_string())
+        })?
+        .map_err(|err| MetricsConfigError::Serialization(err.to_string()))
+}
+
 #[cfg(test)]
 mod tests {
     use vmm_sys_util::tempfile::TempFile;
 
     use super::*;
+    use crate::devices::virtio::block::virtio::metrics::BlockDeviceMetricsValues;
+    use crate::logger::IncMetric;
 
     #[test]
     fn test_init_metrics() {
@@ -57,4 +126,51 @@ mod tests {
         init_metrics(desc.clone()).unwrap();
         init_metrics(desc).unwrap_err();
     }
+
+    #[test]
+    fn test_device_metrics() {
+        let drv_metrics = BlockMetricsPerDevice::alloc(String::from("scoped_drv0"));
+        BlockMetricsPerDevice::alloc(String::from("scoped_drv1"));
+        drv_metrics.read_count.add(3);
+
+        let request = DeviceMetricsRequest {
+            device_type: MetricsDeviceType::Block,
+            device_id: String::from("scoped_drv0"),
+        };
+        // Only the subtree of the requested device is returned.
+        let value = device_metrics(&request).unwrap();
+        let expected = serde_json::to_value(BlockDeviceMetricsValues::default()).unwrap();
+        assert_eq!(
+            value.as_object().unwrap().keys().collect::<Vec<_>>(),
+            expected.as_object().unwrap().keys().collect::<Vec<_>>()
+        );
+        assert_eq!(value["read_count"], 3);
+        assert_eq!(value["read_agg"]["sum_us"], 0);
+
+        // Reading the metrics does not reset the counters of the device.
+        drv_metrics.read_count.add(2);
+        let value = device_metrics(&request).unwrap();
+        assert_eq!(value["read_count"], 5);
+        let value = device_metrics(&request).unwrap();
+        assert_eq!(value["read_count"], 5);
+
+        NetMetricsPerDevice::alloc(String::from("scoped_net0"));
+        let value = device_metrics(&DeviceMetricsRequest {
+            device_type: MetricsDeviceType::Net,
+            device_id: String::from("scoped_net0"),
+        })
+        .unwrap();
+        assert!(value.get("rx_packets_count").is_some());
+
+        // The device id is scoped to the device type.
+        let err = device_metrics(&DeviceMetricsRequest {
+            device_type: MetricsDeviceType::Net,
+            device_id: String::from("scoped_drv0"),
+        })
+        .unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "No metrics found for the net device with id `scoped_drv0`."
+        );
+    }
 }
@@ -6,6 +6,8 @@ use serde::{Deserialize, Serialize};
 
 use crate::mmds::data_store;
 use crate::mmds::data_store::MmdsVersion;
+use crate::rate_limiter::TokenBucket;
+use crate::vmm_config::TokenBucketConfig;
 
 /// Keeps the MMDS configuration.
 #[derive(Clone, Debug, PartialEq, Eq, Deserialize, Serialize)]
@@ -18,6 +20,9 @@ pub struct MmdsConfig {
     pub network_interfaces: Vec<String>,
     /// MMDS IPv4 configured address.
     pub ipv4_address: Option<Ipv4Addr>,
+    /// Limits the rate of MMDS requests on each network interface. Every TCP segment carrying
+    /// request data consumes a token.
+    pub rate_limiter: Option<TokenBucketConfig>,
 }
 
 impl MmdsConfig {
@@ -36,6 +41,17 @@ impl MmdsConfig {
     pub fn ipv4_addr(&self) -> Option<Ipv4Addr> {
         self.ipv4_address
     }
+
+    /// Returns a new token bucket limiting the rate of MMDS requests, if a limit was configured.
+    pub fn rate_limiter(&self) -> Option<TokenBucket> {
+        self.rate_limiter.and_then(|config| {
+            TokenBucket::new(
+                config.size,
+                config.one_time_burst.unwrap_or(0),
+                config.refill_time,
+            )
+        })
+    }
 }
 
 /// MMDS configuration related errors.
@@ -16,12 +16,18 @@ use crate::rate_limiter::{BucketUpdate, RateLimiter, TokenBucket};
 pub mod balloon;
 /// Wrapper for configuring the microVM boot source.
 pub mod boot_source;
+/// Wrapper for querying individual virtio devices attached to the microVM.
+pub mod devices;
 /// Wrapper for configuring the block devices.
 pub mod drive;
 /// Wrapper for configuring the entropy device attached to the microVM.
 pub mod entropy;
+/// Wrapper over the health indicators of the microVM.
+pub mod health;
 /// Wrapper over the microVM general information attached to the microVM.
 pub mod instance_info;
+/// Wrapper over the KVM capabilities of the host.
+pub mod kvm_capabilities;
 /// Wrapper for configuring the memory and CPU of the microVM.
 pub mod machine_config;
 /// Wrapper for configuring the metrics.
@@ -30,10 +36,14 @@ pub mod metrics;
 pub mod mmds;
 /// Wrapper for configuring the network devices attached to the microVM.
 pub mod net;
+/// Wrapper over the seccomp configuration applied to the Firecracker threads.
+pub mod seccomp;
 /// Wrapper for configuring microVM snapshots and the microVM state.
 pub mod snapshot;
 /// Wrapper for configuring the vsock devices attached to the microVM.
 pub mod vsock;
+/// Wrapper for configuring the watchdog device attached to the microVM.
+pub mod watchdog;
 
 // TODO: Migrate the VMM public-facing code (i.e. interface) to use stateless structures,
 // for receiving data/args, such as the below `RateLimiterConfig` and `TokenBucketConfig`.
@@ -164,6 +174,56 @@ impl RateLimiterConfig {
     }
 }
 
+/// Live state of a token bucket.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct TokenBucketState {
+    /// See TokenBucket::size.
+    pub size: u64,
+    /// The tokens currently available in the bucket, including the ones refilled since it was
+    /// last used.
+    pub budget: u64,
+    /// The remaining one time burst budget.
+    pub one_time_burst: u64,
+    /// See TokenBucket::refill_time.
+    pub refill_time: u64,
+    /// Milliseconds elapsed since the bucket was last used or refilled.
+    pub last_update_ms_ago: u64,
+}
+
+impl From<&TokenBucket> for TokenBucketState {
+    fn from(tb: &TokenBucket) -> Self {
+        TokenBucketState {
+            size: tb.capacity(),
+            budget: tb.replenished_budget(),
+            one_time_burst: tb.one_time_burst(),
+            refill_time: tb.refill_time_ms(),
+            last_update_ms_ago: u64::try_from(tb.time_since_last_update().as_millis())
+                .unwrap_or(u64::MAX),
+        }
+    }
+}
+
+/// Live state of a RateLimiter, used in GET `/drives/{id}/rate-limiter/state` API call. Disabled
+/// buckets are omitted.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct RateLimiterState {
+    /// State of the RateLimiter::bandwidth bucket.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub bandwidth: Option<TokenBucketState>,
+    /// State of the RateLimiter::ops bucket.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub ops: Option<TokenBucketState>,
+}
+
+impl From<&RateLimiter> for RateLimiterState {
+    fn from(rl: &RateLimiter) -> Self {
+        RateLimiterState {
+            bandwidth: rl.bandwidth().map(TokenBucketState::from),
+            ops: rl.ops().map(TokenBucketState::from),
+        }
+    }
+}
+
 /// Create and opens a File for writing to it.
 /// In case we open a FIFO, in order to not block the instance if nobody is consuming the message
 /// that is flushed to the two pipes, we are opening it with `O_NONBLOCK` flag.
@@ -179,6 +239,7 @@ fn open_file_nonblock(path: &Path) -> Result<File, std::io::Error> {
 #[cfg(test)]
 mod tests {
     use super::*;
+    use crate::rate_limiter::TokenType;
 
     const SIZE: u64 = 1024 * 1024;
     const ONE_TIME_BURST: u64 = 1024;
@@ -227,4 +288,34 @@ mod tests {
         assert_eq!(generated_rl_conf, rl_conf);
         assert_eq!(generated_rl_conf.into_option(), Some(rl_conf));
     }
+
+    #[test]
+    fn test_rate_limiter_state() {
+        // The buckets take long enough to refill for no token to be added during the test.
+        let mut rl = RateLimiter::new(SIZE, ONE_TIME_BURST, 1_000_000, 0, 0, 0).unwrap();
+        let state = RateLimiterState::from(&rl);
+        assert_eq!(state.ops, None);
+        let bandwidth = state.bandwidth.unwrap();
+        assert_eq!(bandwidth.size, SIZE);
+        assert_eq!(bandwidth.budget, SIZE);
+        assert_eq!(bandwidth.one_time_burst, ONE_TIME_BURST);
+        assert_eq!(bandwidth.refill_time, 1_000_000);
+
+        // The one time burst is consumed first.
+        assert!(rl.consume(ONE_TIME_BURST + 100, TokenType::Bytes));
+        let bandwidth = RateLimiterState::from(&rl).bandwidth.unwrap();
+        assert_eq!(bandwidth.one_time_burst, 0);
+        assert_eq!(bandwidth.budget, SIZE - 100);
+
+        assert!(rl.consume(SIZE / 2, TokenType::Bytes));
+        assert_eq!(
+            RateLimiterState::from(&rl).bandwidth.unwrap().budget,
+            SIZE / 2 - 100
+        );
+
+        assert_eq!(
+            serde_json::to_value(RateLimiterState::default()).unwrap(),
+            serde_json::json!({})
+        );
+    }
 }
@@ -7,11 +7,14 @@ use std::sync::{Arc, Mutex};
 
 use serde::{Deserialize, Serialize};
 
-use super::RateLimiterConfig;
-use crate::devices::virtio::net::{Net, TapError};
+use super::{RateLimiterConfig, RateLimiterState};
+use crate::devices::virtio::net::{Net, Tap, TapError};
 use crate::utils::net::mac::MacAddr;
 use crate::VmmError;
 
+/// Smallest MTU that can be advertised to the guest, as required for IPv4.
+const MIN_MTU: u16 = 68;
+
 /// This struct represents the strongly typed equivalent of the json body from net iface
 /// related requests.
 #[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
@@ -27,6 +30,9 @@ pub struct NetworkInterfaceConfig {
     pub rx_rate_limiter: Option<RateLimiterConfig>,
     /// Rate Limiter for transmitted packages.
     pub tx_rate_limiter: Option<RateLimiterConfig>,
+    /// MTU advertised to the guest. If not set, the guest driver picks its own default.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub mtu: Option<u16>,
 }
 
 impl From<&Net> for NetworkInterfaceConfig {
@@ -39,6 +45,26 @@ impl From<&Net> for NetworkInterfaceConfig {
             guest_mac: net.guest_mac().copied(),
             rx_rate_limiter: rx_rl.into_option(),
             tx_rate_limiter: tx_rl.into_option(),
+            mtu: net.mtu(),
+        }
+    }
+}
+
+/// Live state of the rate limiters of a network interface, used in GET
+/// `/network-interfaces/{id}/rate-limiter/state` API call.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct NetRateLimiterState {
+    /// State of the rate limiter for received packages.
+    pub rx_rate_limiter: RateLimiterState,
+    /// State of the rate limiter for transmitted packages.
+    pub tx_rate_limiter: RateLimiterState,
+}
+
+impl From<&Net> for NetRateLimiterState {
+    fn from(net: &Net) -> Self {
+        NetRateLimiterState {
+            rx_rate_limiter: net.rx_rate_limiter().into(),
+            tx_rate_limiter: net.tx_rate_limiter().into(),
         }
     }
 }
@@ -65,10 +91,16 @@ pub enum NetworkInterfaceError {
     CreateNetworkDevice(#[from] crate::devices::virtio::net::NetError),
     /// Cannot create the rate limiter: {0}
     CreateRateLimiter(#[from] std::io::Error),
+    /// The network interface does not exist: {0}
+    DeviceNotFound(String),
     /// Unable to update the net device: {0}
     DeviceUpdate(#[from] VmmError),
     /// The MAC address is already in use: {0}
     GuestMacAddressInUse(String),
+    /// The host device name is already in use: {0}
+    HostDevNameInUse(String),
+    /// The MTU {0} is smaller than the minimum of 68 bytes.
+    InvalidMtu(u16),
     /// Cannot open/create the tap device: {0}
     OpenTap(#[from] TapError),
 }
@@ -103,12 +135,28 @@ impl NetBuilder {
         self.net_devices.push(device);
     }
 
+    /// Removes the network device with the given interface id from the builder, returning it if
+    /// it was found.
+    pub fn remove(&mut self, iface_id: &str) -> Option<Arc<Mutex<Net>>> {
+        let index = self
+            .net_devices
+            .iter()
+            .position(|net| net.lock().expect("Poisoned lock").id() == iface_id)?;
+        Some(self.net_devices.remove(index))
+    }
+
     /// Builds a network device based on a network interface config. Keeps a device reference
     /// in the builder's internal list.
     pub fn build(
         &mut self,
         netif_config: NetworkInterfaceConfig,
     ) -> Result<Arc<Mutex<Net>>, NetworkInterfaceError> {
+        if let Some(mtu) = netif_config.mtu {
+            if mtu < MIN_MTU {
+                return Err(NetworkInterfaceError::InvalidMtu(mtu));
+            }
+        }
+
         if let Some(ref mac_address) = netif_config.guest_mac {
             let mac_conflict = |net: &Arc<Mutex<Net>>| {
                 let net = net.lock().expect("Poisoned lock");
@@ -141,6 +189,42 @@ impl NetBuilder {
         Ok(net)
     }
 
+    /// Checks the configurations of network devices built in order, without creating the devices,
+    /// and returns the configurations the resulting list of devices would have.
+    pub fn check_configs(
+        configs: Vec<NetworkInterfaceConfig>,
+    ) -> Result<Vec<NetworkInterfaceConfig>, NetworkInterfaceError> {
+        let mut checked: Vec<NetworkInterfaceConfig> = Vec::new();
+        for config in configs {
+            if let Some(mtu) = config.mtu {
+                if mtu < MIN_MTU {
+                    return Err(NetworkInterfaceError::InvalidMtu(mtu));
+                }
+            }
+            Tap::check_if_name(&config.host_dev_name)?;
+
+            let others = || checked.iter().filter(|c| c.iface_id != config.iface_id);
+            if let Some(ref mac_address) = config.guest_mac {
+                if others().any(|c| c.guest_mac.as_ref() == Some(mac_address)) {
+                    return Err(NetworkInterfaceError::GuestMacAddressInUse(
+                        mac_address.to_string(),
+                    ));
+                }
+            }
+            if others().any(|c| c.host_dev_name == config.host_dev_name) {
+                return Err(NetworkInterfaceError::HostDevNameInUse(
+                    config.host_dev_name,
+                ));
+            }
+
+            if let Some(index) = checked.iter().position(|c| c.iface_id == config.iface_id) {
+                checked.swap_remove(index);
+            }
+            checked.push(config);
+        }
+        Ok(checked)
+    }
+
     /// Creates a Net device from a NetworkInterfaceConfig.
     pub fn create_net(cfg: NetworkInterfaceConfig) -> Result<Net, NetworkInterfaceError> {
         let rx_rate_limiter = cfg
@@ -155,14 +239,29 @@ impl NetBuilder {
             .map_err(NetworkInterfaceError::CreateRateLimiter)?;
 
         // Create and return the Net device
-        crate::devices::virtio::net::Net::new(
+        let mut net = crate::devices::virtio::net::Net::new(
             cfg.iface_id,
             &cfg.host_dev_name,
             cfg.guest_mac,
             rx_rate_limiter.unwrap_or_default(),
             tx_rate_limiter.unwrap_or_default(),
         )
-        .map_err(NetworkInterfaceError::CreateNetworkDevice)
+        .map_err(NetworkInterfaceError::CreateNetworkDevice)?;
+        if let Some(mtu) = cfg.mtu {
+            net.set_mtu(mtu);
+        }
+
+        Ok(net)
+    }
+
+    /// Returns the structure used to configure the net device with the given id.
+    pub fn config(&self, iface_id: &str) -> Result<NetworkInterfaceConfig, NetworkInterfaceError> {
+        self.net_devices
+            .iter()
+            .map(|net| net.lock().expect("Poisoned lock"))
+            .find(|net| net.id() == iface_id)
+            .map(|net| NetworkInterfaceConfig::from(net.deref()))
+            .ok_or_else(|| NetworkInterfaceError::DeviceNotFound(iface_id.to_string()))
     }
 
     /// Returns a vec with the structures used to configure the net devices.
@@ -195,6 +294,7 @@ mod tests {
             guest_mac: Some(MacAddr::from_str(mac).unwrap()),
             rx_rate_limiter: RateLimiterConfig::default().into_option(),
             tx_rate_limiter: RateLimiterConfig::default().into_option(),
+            mtu: None,
         }
     }
 
@@ -206,6 +306,7 @@ mod tests {
                 guest_mac: self.guest_mac,
                 rx_rate_limiter: None,
                 tx_rate_limiter: None,
+                mtu: self.mtu,
             }
         }
     }
@@ -323,6 +424,33 @@ mod tests {
         let configs = net_builder.configs();
         assert_eq!(configs.len(), 1);
         assert_eq!(configs.first().unwrap(), &net_if_cfg);
+        assert_eq!(net_builder.config(net_id).unwrap(), net_if_cfg);
+        assert_eq!(
+            net_builder.config("unknown").unwrap_err().to_string(),
+            NetworkInterfaceError::DeviceNotFound("unknown".to_string()).to_string()
+        );
+    }
+
+    #[test]
+    fn test_net_mtu() {
+        let mut net_builder = NetBuilder::new();
+        let mut net_if_cfg = create_netif("id", "dev", "01:23:45:67:89:0c");
+
+        net_if_cfg.mtu = Some(MIN_MTU - 1);
+        assert_eq!(
+            net_builder
+                .build(net_if_cfg.clone())
+                .err()
+                .unwrap()
+                .to_string(),
+            NetworkInterfaceError::InvalidMtu(MIN_MTU - 1).to_string()
+        );
+        assert_eq!(net_builder.net_devices.len(), 0);
+
+        net_if_cfg.mtu = Some(9000);
+        let net = net_builder.build(net_if_cfg.clone()).unwrap();
+        assert_eq!(net.lock().unwrap().mtu(), Some(9000));
+        assert_eq!(net_builder.config("id").unwrap(), net_if_cfg);
     }
 
     #[test]
@@ -0,0 +1,92 @@
+// Copyright 2024 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::collections::BTreeMap;
+
+use seccompiler::BpfThreadMap;
+use serde::Serialize;
+
+/// Seccomp filtering level selected when Firecracker was launched.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub enum SeccompLevel {
+    /// Seccomp filtering disabled.
+    #[default]
+    None,
+    /// Default, advanced filters.
+    Advanced,
+    /// Default filters, logging the denied syscalls to the kernel audit log instead of
+    /// terminating. A debugging aid, not to be used in production.
+    Log,
+    /// Custom, user-provided filters.
+    Custom,
+}
+
+/// Serializable struct describing the seccomp configuration in effect.
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize)]
+pub struct SeccompInfo {
+    /// The seccomp filtering level.
+    pub level: SeccompLevel,
+    /// CRC64 of the BPF filter installed on each thread category, as a hex string.
+    pub filter_hashes: BTreeMap<String, String>,
+}
+
+impl SeccompInfo {
+    /// Describes the given filters, as selected for the given level.
+    pub fn new(level: SeccompLevel, filters: &BpfThreadMap) -> Self {
+        let filter_hashes = filters
+            .iter()
+            .map(|(thread, filter)| {
+                let crc = filter.iter().fold(0, |crc, insn| {
+                    let crc = crc64::crc64(crc, &insn.code.to_le_bytes());
+                    let crc = crc64::crc64(crc, &[insn.jt, insn.jf]);
+                    crc64::crc64(crc, &insn.k.to_le_bytes())
+                });
+                (thread.clone(), format!("{crc:016x}"))
+            })
+            .collect();
+
+        SeccompInfo {
+            level,
+            filter_hashes,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::sync::Arc;
+
+    use seccompiler::sock_filter;
+
+    use super::*;
+    use crate::seccomp_filters::get_empty_filters;
+
+    #[test]
+    fn test_seccomp_info() {
+        let info = SeccompInfo::new(SeccompLevel::None, &get_empty_filters());
+        assert_eq!(info.level, SeccompLevel::None);
+        assert_eq!(info.filter_hashes.len(), 3);
+        for hash in info.filter_hashes.values() {
+            assert_eq!(hash, "0000000000000000");
+        }
+
+        let mut filters = get_empty_filters();
+        let insn = sock_filter {
+            code: 0x06,
+            jt: 0,
+            jf: 0,
+            k: 0x7fff_0000,
+        };
+        filters.insert("vcpu".to_string(), Arc::new(vec![insn]));
+        let info = SeccompInfo::new(SeccompLevel::Custom, &filters);
+        assert_eq!(info.level, SeccompLevel::Custom);
+        assert_ne!(info.filter_hashes["vcpu"], info.filter_hashes["vmm"]);
+        // The hash is deterministic.
+        assert_eq!(info, SeccompInfo::new(SeccompLevel::Custom, &filters));
+
+        assert_eq!(
+            serde_json::to_value(&info).unwrap()["level"],
+            serde_json::json!("Custom")
+        );
+    }
+}
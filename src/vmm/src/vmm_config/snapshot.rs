@@ -3,7 +3,9 @@
 
 //! Configurations used in the snapshotting context.
 
-use std::path::PathBuf;
+use std::fs::OpenOptions;
+use std::io::ErrorKind;
+use std::path::{Path, PathBuf};
 
 /// For crates that depend on `vmm` we export.
 pub use semver::Version;
@@ -45,6 +47,155 @@ pub struct CreateSnapshotParams {
     pub snapshot_path: PathBuf,
     /// Path to the file that will contain the guest memory.
     pub mem_file_path: PathBuf,
+    /// Path to a memory file of a previous snapshot to diff the guest memory against. When set,
+    /// a `Diff` snapshot contains the pages whose contents differ from this file, instead of
+    /// the pages dirtied since the last snapshot.
+    #[serde(default)]
+    pub baseline_mem_file_path: Option<PathBuf>,
+    /// Size of the writes of the guest memory to the memory file, in KiB. By default, each
+    /// contiguous memory range is written at once.
+    #[serde(default)]
+    pub mem_write_chunk_size_kib: Option<u32>,
+}
+
+/// Smallest size of the writes of the guest memory to the memory file, in KiB.
+pub const MIN_MEM_WRITE_CHUNK_SIZE_KIB: u32 = 4;
+/// Largest size of the writes of the guest memory to the memory file, in KiB.
+pub const MAX_MEM_WRITE_CHUNK_SIZE_KIB: u32 = 1024 * 1024;
+
+/// Errors associated with the placeholders of the snapshot file paths.
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum SnapshotPathError {
+    /// Unknown placeholder in snapshot file path: {0}
+    UnknownPlaceholder(String),
+    /// Cannot create snapshot file {0:?}: {1}
+    CreateFile(PathBuf, std::io::Error),
+}
+
+/// The paths of the files a snapshot was created into, once their placeholders were expanded.
+/// Returned by PUT `/snapshot/create`.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct SnapshotPaths {
+    /// Path to the file that contains the microVM state.
+    pub snapshot_path: PathBuf,
+    /// Path to the file that contains the guest memory.
+    pub mem_file_path: PathBuf,
+}
+
+/// Values of the placeholders that can be used in the paths of the files a snapshot is created
+/// into: `{instance_id}`, `{timestamp}` and `{vmm_version}`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct SnapshotPathPlaceholders {
+    /// The ID of the microVM.
+    pub instance_id: String,
+    /// Seconds since the Unix epoch.
+    pub timestamp: u64,
+    /// The version of the VMM.
+    pub vmm_version: String,
+}
+
+impl SnapshotPathPlaceholders {
+    /// Expands the placeholders in `path`. Returns `None` if `path` has no placeholders.
+    fn expand(&self, path: &str) -> Result<Option<String>, SnapshotPathError> {
+        let mut expanded = String::new();
+        let mut rest = path;
+        let mut has_placeholders = false;
+        while let Some(start) = rest.find('{') {
+            let Some(len) = rest[start..].find('}') else {
+                break;
+            };
+            expanded.push_str(&rest[..start]);
+            match &rest[start + 1..start + len] {
+                "instance_id" => expanded.push_str(&self.instance_id),
+                "timestamp" => expanded.push_str(&self.timestamp.to_string()),
+                "vmm_version" => expanded.push_str(&self.vmm_version),
+                name => return Err(SnapshotPathError::UnknownPlaceholder(name.to_string())),
+            }
+            has_placeholders = true;
+            rest = &rest[start + len + 1..];
+        }
+        if !has_placeholders {
+            return Ok(None);
+        }
+        expanded.push_str(rest);
+        Ok(Some(expanded))
+    }
+
+    /// Expands the placeholders in `path` and creates the file it names, which validates that
+    /// the file can be written. If the file already exists, a `-N` suffix is added to its name,
+    /// N being the smallest number for which it does not, so that snapshots are not overwritten.
+    /// Paths without placeholders are returned unchanged.
+    pub fn resolve(&self, path: &Path) -> Result<PathBuf, SnapshotPathError> {
+        let expanded = match path.to_str() {
+            Some(path) => self.expand(path)?,
+            None => None,
+        };
+        let Some(expanded) = expanded.map(PathBuf::from) else {
+            return Ok(path.to_path_buf());
+        };
+
+        let file_name = expanded
+            .file_name()
+            .and_then(|name| name.to_str())
+            .unwrap_or_default()
+            .to_string();
+        let (stem, extension) = match file_name.rfind('.') {
+            Some(dot) if dot > 0 => file_name.split_at(dot),
+            _ => (file_name.as_str(), ""),
+        };
+
+        let mut candidate = expanded.clone();
+        let mut suffix = 0;
+        loop {
+            match OpenOptions::new()
+                .write(true)
+                .create_new(true)
+                .open(&candidate)
+            {
+                Ok(_) => return Ok(candidate),
+                Err(err) if err.kind() == ErrorKind::AlreadyExists => {
+                    suffix += 1;
+                    candidate = expanded.with_file_name(format!("{stem}-{suffix}{extension}"));
+                }
+                Err(err) => return Err(SnapshotPathError::CreateFile(candidate, err)),
+            }
+        }
+    }
+
+    /// Resolves the paths of the files of a snapshot, see [`Self::resolve`]. If the memory file
+    /// path cannot be resolved, the file created for the state file path is removed.
+    pub fn resolve_paths(
+        &self,
+        snapshot_path: &Path,
+        mem_file_path: &Path,
+    ) -> Result<SnapshotPaths, SnapshotPathError> {
+        let resolved_snapshot_path = self.resolve(snapshot_path)?;
+        let resolved_mem_file_path = self.resolve(mem_file_path).inspect_err(|_| {
+            remove_resolved_file(snapshot_path, &resolved_snapshot_path);
+        })?;
+        Ok(SnapshotPaths {
+            snapshot_path: resolved_snapshot_path,
+            mem_file_path: resolved_mem_file_path,
+        })
+    }
+}
+
+impl SnapshotPaths {
+    /// Removes the files created while resolving the placeholders of the requested paths, e.g.
+    /// when the snapshot they were created for fails. The files the requested paths without
+    /// placeholders name are left untouched.
+    pub fn remove_resolved_files(&self, snapshot_path: &Path, mem_file_path: &Path) {
+        remove_resolved_file(snapshot_path, &self.snapshot_path);
+        remove_resolved_file(mem_file_path, &self.mem_file_path);
+    }
+}
+
+/// Removes the file [`SnapshotPathPlaceholders::resolve`] created for `path`, if any. Paths with
+/// placeholders always differ from their resolved paths, and paths without are not created.
+fn remove_resolved_file(path: &Path, resolved: &Path) {
+    if path != resolved {
+        let _ = std::fs::remove_file(resolved);
+    }
 }
 
 /// Stores the configuration that will be used for loading a snapshot.
@@ -110,3 +261,121 @@ pub struct Vm {
     /// The microVM state, which can be `paused` or `resumed`.
     pub state: VmState,
 }
+
+#[cfg(test)]
+mod tests {
+    use vmm_sys_util::tempdir::TempDir;
+
+    use super::*;
+
+    fn placeholders() -> SnapshotPathPlaceholders {
+        SnapshotPathPlaceholders {
+            instance_id: "vm0".to_string(),
+            timestamp: 1_700_000_000,
+            vmm_version: "1.10.0".to_string(),
+        }
+    }
+
+    #[test]
+    fn test_expand_placeholders() {
+        let placeholders = placeholders();
+        assert_eq!(
+            placeholders
+                .expand("/snap-{instance_id}-{timestamp}-{vmm_version}")
+                .unwrap()
+                .unwrap(),
+            "/snap-vm0-1700000000-1.10.0"
+        );
+        assert_eq!(
+            placeholders
+                .expand("{timestamp}{timestamp}")
+                .unwrap()
+                .unwrap(),
+            "17000000001700000000"
+        );
+        // Unterminated placeholders are kept as they are.
+        assert_eq!(
+            placeholders
+                .expand("{instance_id}-{timestamp")
+                .unwrap()
+                .unwrap(),
+            "vm0-{timestamp"
+        );
+        assert_eq!(placeholders.expand("/snap-{timestamp").unwrap(), None);
+        assert_eq!(placeholders.expand("/snapshot").unwrap(), None);
+
+        assert!(matches!(
+            placeholders.expand("/snap-{id}"),
+            Err(SnapshotPathError::UnknownPlaceholder(name)) if name == "id"
+        ));
+    }
+
+    #[test]
+    fn test_resolve_snapshot_path() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.as_path();
+        let placeholders = placeholders();
+
+        // Paths without placeholders are left untouched, and no file is created.
+        let path = dir.join("snapshot");
+        assert_eq!(placeholders.resolve(&path).unwrap(), path);
+        assert!(!path.exists());
+
+        let path = dir.join("snap-{instance_id}-{timestamp}.state");
+        let first = placeholders.resolve(&path).unwrap();
+        assert_eq!(first, dir.join("snap-vm0-1700000000.state"));
+        assert!(first.exists());
+
+        // Existing snapshots are not overwritten.
+        let second = placeholders.resolve(&path).unwrap();
+        assert_eq!(second, dir.join("snap-vm0-1700000000-1.state"));
+        let third = placeholders.resolve(&path).unwrap();
+        assert_eq!(third, dir.join("snap-vm0-1700000000-2.state"));
+
+        let path = dir.join("mem-{instance_id}");
+        assert_eq!(placeholders.resolve(&path).unwrap(), dir.join("mem-vm0"));
+        assert_eq!(placeholders.resolve(&path).unwrap(), dir.join("mem-vm0-1"));
+
+        // The expanded path must be writable.
+        let path = dir.join("missing").join("snap-{timestamp}");
+        assert!(matches!(
+            placeholders.resolve(&path),
+            Err(SnapshotPathError::CreateFile(..))
+        ));
+    }
+
+    #[test]
+    fn test_resolve_snapshot_paths() {
+        let tmp_dir = TempDir::new().unwrap();
+        let dir = tmp_dir.as_path();
+        let placeholders = placeholders();
+
+        let snapshot_path = dir.join("snap-{instance_id}");
+        let mem_file_path = dir.join("mem");
+        let paths = placeholders
+            .resolve_paths(&snapshot_path, &mem_file_path)
+            .unwrap();
+        assert_eq!(
+            paths,
+            SnapshotPaths {
+                snapshot_path: dir.join("snap-vm0"),
+                mem_file_path: mem_file_path.clone(),
+            }
+        );
+        assert!(paths.snapshot_path.exists());
+
+        // Only the files created for paths with placeholders are removed.
+        std::fs::write(&mem_file_path, b"mem").unwrap();
+        paths.remove_resolved_files(&snapshot_path, &mem_file_path);
+        assert!(!paths.snapshot_path.exists());
+        assert!(mem_file_path.exists());
+
+        // The file created for the state file is removed if the memory file cannot be created.
+        let missing = dir.join("missing").join("mem-{timestamp}");
+        assert!(matches!(
+            placeholders.resolve_paths(&snapshot_path, &missing),
+            Err(SnapshotPathError::CreateFile(..))
+        ));
+        assert!(!dir.join("snap-vm0").exists());
+    }
+}
@@ -2,6 +2,7 @@
 // SPDX-License-Identifier: Apache-2.0
 
 use std::convert::TryFrom;
+use std::path::Path;
 use std::sync::{Arc, Mutex};
 
 use serde::{Deserialize, Serialize};
@@ -17,6 +18,8 @@ pub enum VsockConfigError {
     CreateVsockBackend(VsockUnixBackendError),
     /// Cannot create vsock device: {0}
     CreateVsockDevice(VsockError),
+    /// The directory of the vsock socket does not exist: {0}
+    InvalidUdsPath(String),
 }
 
 /// This struct represents the strongly typed equivalent of the json body
@@ -80,9 +83,7 @@ impl VsockBuilder {
     /// If an entry already exists, it will overwrite it.
     pub fn insert(&mut self, cfg: VsockDeviceConfig) -> Result<(), VsockConfigError> {
         // Make sure to drop the old one and remove the socket before creating a new one.
-        if let Some(existing) = self.inner.take() {
-            std::fs::remove_file(existing.uds_path).map_err(VsockUnixBackendError::UnixBind)?;
-        }
+        self.remove()?;
         self.inner = Some(VsockAndUnixPath {
             uds_path: cfg.uds_path.clone(),
             vsock: Arc::new(Mutex::new(Self::create_unixsock_vsock(cfg)?)),
@@ -90,6 +91,26 @@ impl VsockBuilder {
         Ok(())
     }
 
+    /// Drops the Vsock, if present, and removes its socket.
+    pub fn remove(&mut self) -> Result<(), VsockConfigError> {
+        if let Some(existing) = self.inner.take() {
+            drop(existing.vsock);
+            std::fs::remove_file(existing.uds_path).map_err(VsockUnixBackendError::UnixBind)?;
+        }
+        Ok(())
+    }
+
+    /// Checks `cfg` without creating the device, which would bind the socket.
+    pub fn check_config(cfg: &VsockDeviceConfig) -> Result<(), VsockConfigError> {
+        let dir = Path::new(&cfg.uds_path).parent();
+        if cfg.uds_path.is_empty()
+            || dir.is_some_and(|dir| !dir.as_os_str().is_empty() && !dir.is_dir())
+        {
+            return Err(VsockConfigError::InvalidUdsPath(cfg.uds_path.clone()));
+        }
+        Ok(())
+    }
+
     /// Provides a reference to the Vsock if present.
     pub fn get(&self) -> Option<&MutexVsockUnix> {
         self.inner.as_ref().map(|pair| &pair.vsock)
@@ -0,0 +1,128 @@
+// Copyright 2024 Amazon.com, Inc. or its affiliates. All Rights Reserved.
+// SPDX-License-Identifier: Apache-2.0
+
+use std::ops::Deref;
+use std::sync::{Arc, Mutex};
+
+use serde::{Deserialize, Serialize};
+
+pub use crate::devices::virtio::watchdog::WatchdogAction;
+use crate::devices::virtio::watchdog::{Watchdog, WatchdogError};
+
+/// This struct represents the strongly typed equivalent of the json body from watchdog device
+/// related requests.
+#[derive(Debug, Clone, PartialEq, Eq, Deserialize, Serialize)]
+#[serde(deny_unknown_fields)]
+pub struct WatchdogDeviceConfig {
+    /// Interval, in seconds, in which the guest has to pet the watchdog.
+    pub timeout_s: u32,
+    /// Action taken when the guest fails to pet the watchdog in time.
+    #[serde(default)]
+    pub action: WatchdogAction,
+}
+
+impl From<&Watchdog> for WatchdogDeviceConfig {
+    fn from(dev: &Watchdog) -> Self {
+        WatchdogDeviceConfig {
+            timeout_s: dev.timeout_s(),
+            action: dev.action(),
+        }
+    }
+}
+
+/// Errors that can occur while handling configuration for
+/// a watchdog device
+#[derive(Debug, thiserror::Error, displaydoc::Display)]
+pub enum WatchdogDeviceError {
+    /// Could not create Watchdog device: {0}
+    CreateDevice(#[from] WatchdogError),
+    /// The watchdog timeout must be greater than 0 seconds.
+    InvalidTimeout,
+}
+
+/// A builder type used to construct a Watchdog device
+#[derive(Debug, Default)]
+pub struct WatchdogDeviceBuilder(Option<Arc<Mutex<Watchdog>>>);
+
+impl WatchdogDeviceBuilder {
+    /// Create a new instance for the builder
+    pub fn new() -> Self {
+        Self(None)
+    }
+
+    /// Build a watchdog device and return a (counted) reference to it protected by a mutex
+    pub fn build(
+        &mut self,
+        config: WatchdogDeviceConfig,
+    ) -> Result<Arc<Mutex<Watchdog>>, WatchdogDeviceError> {
+        Self::check_config(&config)?;
+        let dev = Arc::new(Mutex::new(Watchdog::new(config.timeout_s, config.action)?));
+        self.0 = Some(dev.clone());
+
+        Ok(dev)
+    }
+
+    /// Checks `config` without creating the device.
+    pub fn check_config(config: &WatchdogDeviceConfig) -> Result<(), WatchdogDeviceError> {
+        if config.timeout_s == 0 {
+            return Err(WatchdogDeviceError::InvalidTimeout);
+        }
+        Ok(())
+    }
+
+    /// Insert a new watchdog device from a configuration object
+    pub fn insert(&mut self, config: WatchdogDeviceConfig) -> Result<(), WatchdogDeviceError> {
+        let _ = self.build(config)?;
+        Ok(())
+    }
+
+    /// Get a reference to the watchdog device, if present
+    pub fn get(&self) -> Option<&Arc<Mutex<Watchdog>>> {
+        self.0.as_ref()
+    }
+
+    /// Get the configuration of the watchdog device (if any)
+    pub fn config(&self) -> Option<WatchdogDeviceConfig> {
+        self.0
+            .as_ref()
+            .map(|dev| WatchdogDeviceConfig::from(dev.lock().unwrap().deref()))
+    }
+
+    /// Set the watchdog device from an already created object
+    pub fn set_device(&mut self, device: Arc<Mutex<Watchdog>>) {
+        self.0 = Some(device);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_watchdog_device_create() {
+        let config = WatchdogDeviceConfig {
+            timeout_s: 10,
+            action: WatchdogAction::Stop,
+        };
+        let mut builder = WatchdogDeviceBuilder::new();
+        assert!(builder.get().is_none());
+
+        builder.insert(config.clone()).unwrap();
+        assert!(builder.get().is_some());
+        assert_eq!(builder.config().unwrap(), config);
+    }
+
+    #[test]
+    fn test_watchdog_invalid_timeout() {
+        let config = WatchdogDeviceConfig {
+            timeout_s: 0,
+            action: WatchdogAction::Log,
+        };
+        let mut builder = WatchdogDeviceBuilder::new();
+        assert!(matches!(
+            builder.insert(config),
+            Err(WatchdogDeviceError::InvalidTimeout)
+        ));
+        assert!(builder.get().is_none());
+    }
+}
@@ -16,11 +16,13 @@ pub use vm_memory::{
     address, Address, ByteValued, Bytes, FileOffset, GuestAddress, GuestMemory, GuestMemoryRegion,
     GuestUsize, MemoryRegionAddress, MmapRegion,
 };
-use vm_memory::{Error as VmMemoryError, GuestMemoryError, WriteVolatile};
+use vm_memory::{
+    Error as VmMemoryError, GuestMemoryError, VolatileMemoryError, VolatileSlice, WriteVolatile,
+};
 use vmm_sys_util::errno;
 
 use crate::utils::{get_page_size, u64_to_usize};
-use crate::vmm_config::machine_config::HugePageConfig;
+use crate::vmm_config::machine_config::{HugePageConfig, ThpAdvice};
 use crate::DirtyBitmap;
 
 /// Type of GuestMemoryMmap.
@@ -51,8 +53,32 @@ pub enum MemoryError {
     Memfd(memfd::Error),
     /// Cannot resize memfd file: {0}
     MemfdSetLen(std::io::Error),
+    /// Cannot resize the memory backing file: {0}
+    BackingFileSetLen(std::io::Error),
+    /// The memory backing file is {0} bytes long, but the guest memory is {1} bytes.
+    BackingFileSize(u64, u64),
     /// Cannot restore hugetlbfs backed snapshot by mapping the memory file. Please use uffd.
     HugetlbfsSnapshot,
+    /// Cannot apply transparent huge page advice to guest memory: {0}
+    Madvise(std::io::Error),
+    /// Cannot allocate {requested_mib} MiB of guest memory, at most {allocatable_mib} MiB could be
+    /// allocated: {source}
+    Allocation {
+        /// The memory size that was requested, in MiB.
+        requested_mib: usize,
+        /// The largest memory size that could be allocated, in MiB.
+        allocatable_mib: usize,
+        /// The error returned when allocating the requested memory size.
+        source: Box<MemoryError>,
+    },
+    /// The guest memory requires {required_mib} MiB of host memory, including the Firecracker
+    /// overhead, but only {available_mib} MiB are available.
+    InsufficientHostMemory {
+        /// The guest memory size and the Firecracker overhead, in MiB.
+        required_mib: usize,
+        /// The host memory available to the guest memory, in MiB.
+        available_mib: usize,
+    },
 }
 
 /// Defines the interface for snapshotting memory.
@@ -60,13 +86,20 @@ pub trait GuestMemoryExtension
 where
     Self: Sized,
 {
-    /// Creates a GuestMemoryMmap with `size` in MiB backed by a memfd.
+    /// Creates a GuestMemoryMmap from raw regions backed by a single memfd.
     fn memfd_backed(
-        mem_size_mib: usize,
+        regions: &[(GuestAddress, usize)],
         track_dirty_pages: bool,
         huge_pages: HugePageConfig,
     ) -> Result<Self, MemoryError>;
 
+    /// Creates a GuestMemoryMmap from raw regions backed by a shared mapping of `file`.
+    fn file_backed(
+        regions: &[(GuestAddress, usize)],
+        file: &File,
+        track_dirty_pages: bool,
+    ) -> Result<Self, MemoryError>;
+
     /// Creates a GuestMemoryMmap from raw regions.
     fn from_raw_regions(
         regions: &[(GuestAddress, usize)],
@@ -106,11 +139,26 @@ where
         dirty_bitmap: &DirtyBitmap,
     ) -> Result<(), MemoryError>;
 
+    /// Dumps all pages of GuestMemoryMmap whose contents differ from the same pages of the
+    /// `baseline` memory dump to a writer.
+    fn dump_diff<T: WriteVolatile + std::io::Seek, B: std::io::Read>(
+        &self,
+        writer: &mut T,
+        baseline: &mut B,
+    ) -> Result<(), MemoryError>;
+
     /// Resets all the memory region bitmaps
     fn reset_dirty(&self);
 
     /// Store the dirty bitmap in internal store
     fn store_dirty_bitmap(&self, dirty_bitmap: &DirtyBitmap, page_size: usize);
+
+    /// Applies the transparent huge page advice to all memory regions.
+    fn advise_thp(&self, advice: ThpAdvice) -> Result<(), MemoryError>;
+
+    /// Fills all memory regions with the given byte.
+    #[cfg(feature = "mem-poison")]
+    fn poison(&self, pattern: u8);
 }
 
 /// State of a guest memory region saved to file/buffer.
@@ -134,26 +182,45 @@ pub struct GuestMemoryState {
 }
 
 impl GuestMemoryExtension for GuestMemoryMmap {
-    /// Creates a GuestMemoryMmap with `size` in MiB backed by a memfd.
+    /// Creates a GuestMemoryMmap from raw regions backed by a single memfd.
     fn memfd_backed(
-        mem_size_mib: usize,
+        regions: &[(GuestAddress, usize)],
         track_dirty_pages: bool,
         huge_pages: HugePageConfig,
     ) -> Result<Self, MemoryError> {
+        let mem_size_mib = regions
+            .iter()
+            .map(|(_, region_size)| region_size)
+            .sum::<usize>()
+            >> 20;
         let memfd_file = create_memfd(mem_size_mib, huge_pages.into())?.into_file();
 
-        let mut offset: u64 = 0;
-        let regions = crate::arch::arch_memory_regions(mem_size_mib << 20)
-            .iter()
-            .map(|(guest_address, region_size)| {
-                let file_clone = memfd_file.try_clone().map_err(MemoryError::FileError)?;
-                let file_offset = FileOffset::new(file_clone, offset);
-                offset += *region_size as u64;
-                Ok((file_offset, *guest_address, *region_size))
-            })
-            .collect::<Result<Vec<_>, MemoryError>>()?;
+        Self::from_raw_regions_file(
+            consecutive_file_regions(&memfd_file, regions)?,
+            track_dirty_pages,
+            true,
+        )
+    }
 
-        Self::from_raw_regions_file(regions, track_dirty_pages, true)
+    /// Creates a GuestMemoryMmap from raw regions backed by a shared mapping of `file`, which is
+    /// resized to the guest memory size. The regions are laid out consecutively in the file.
+    fn file_backed(
+        regions: &[(GuestAddress, usize)],
+        file: &File,
+        track_dirty_pages: bool,
+    ) -> Result<Self, MemoryError> {
+        let mem_size = regions
+            .iter()
+            .map(|(_, region_size)| *region_size as u64)
+            .sum::<u64>();
+        file.set_len(mem_size)
+            .map_err(MemoryError::BackingFileSetLen)?;
+
+        Self::from_raw_regions_file(
+            consecutive_file_regions(file, regions)?,
+            track_dirty_pages,
+            true,
+        )
     }
 
     /// Creates a GuestMemoryMmap from raw regions backed by anonymous memory.
@@ -358,6 +425,70 @@ impl GuestMemoryExtension for GuestMemoryMmap {
         write_result.map_err(MemoryError::WriteMemory)
     }
 
+    /// Dumps all pages of GuestMemoryMmap whose contents differ from the same pages of the
+    /// `baseline` memory dump to a writer.
+    fn dump_diff<T: WriteVolatile + std::io::Seek, B: std::io::Read>(
+        &self,
+        writer: &mut T,
+        baseline: &mut B,
+    ) -> Result<(), MemoryError> {
+        let mut writer_offset = 0;
+        let page_size = get_page_size().map_err(MemoryError::PageSize)?;
+        let mut baseline_page = vec![0u8; page_size];
+        let mut guest_page = vec![0u8; page_size];
+
+        self.iter().try_for_each(|region| {
+            let region_len = u64_to_usize(region.len());
+            let write_batch = |writer: &mut T,
+                               batch_start: usize,
+                               write_size: usize|
+             -> Result<(), MemoryError> {
+                let slice = region
+                    .get_slice(MemoryRegionAddress(batch_start as u64), write_size)
+                    .map_err(MemoryError::WriteMemory)?;
+                writer
+                    .write_all_volatile(&slice)
+                    .map_err(|err| MemoryError::WriteMemory(err.into()))
+            };
+            let mut write_size = 0;
+            let mut diff_batch_start = 0;
+
+            for page_offset in (0..region_len).step_by(page_size) {
+                let len = page_size.min(region_len - page_offset);
+                baseline
+                    .read_exact(&mut baseline_page[..len])
+                    .map_err(MemoryError::FileError)?;
+                region
+                    .get_slice(MemoryRegionAddress(page_offset as u64), len)
+                    .map_err(MemoryError::WriteMemory)?
+                    .copy_to(&mut guest_page[..len]);
+
+                if guest_page[..len] != baseline_page[..len] {
+                    // We are at the start of a new batch of modified pages.
+                    if write_size == 0 {
+                        // Seek forward over the pages matching the baseline.
+                        writer
+                            .seek(SeekFrom::Start(writer_offset + page_offset as u64))
+                            .map_err(MemoryError::FileError)?;
+                        diff_batch_start = page_offset;
+                    }
+                    write_size += len;
+                } else if write_size > 0 {
+                    // We are at the end of a batch of modified pages.
+                    write_batch(writer, diff_batch_start, write_size)?;
+                    write_size = 0;
+                }
+            }
+
+            if write_size > 0 {
+                write_batch(writer, diff_batch_start, write_size)?;
+            }
+            writer_offset += region.len();
+
+            Ok(())
+        })
+    }
+
     /// Resets all the memory region bitmaps
     fn reset_dirty(&self) {
         self.iter().for_each(|region| {
@@ -386,6 +517,50 @@ impl GuestMemoryExtension for GuestMemoryMmap {
             }
         });
     }
+
+    /// Applies the transparent huge page advice to all memory regions.
+    fn advise_thp(&self, advice: ThpAdvice) -> Result<(), MemoryError> {
+        self.iter().try_for_each(|region| {
+            // SAFETY: The address and length describe a valid mapping owned by the region.
+            let ret = unsafe {
+                libc::madvise(
+                    region.as_ptr().cast(),
+                    region.size(),
+                    advice.madvise_advice(),
+                )
+            };
+            if ret < 0 {
+                return Err(MemoryError::Madvise(std::io::Error::last_os_error()));
+            }
+            Ok(())
+        })
+    }
+
+    /// Fills all memory regions with the given byte.
+    #[cfg(feature = "mem-poison")]
+    fn poison(&self, pattern: u8) {
+        self.iter().for_each(|region| {
+            // SAFETY: The address and length describe a valid mapping owned by the region.
+            unsafe { std::ptr::write_bytes(region.as_ptr(), pattern, region.size()) };
+        })
+    }
+}
+
+/// Maps the given regions to consecutive offsets of `file`.
+fn consecutive_file_regions(
+    file: &File,
+    regions: &[(GuestAddress, usize)],
+) -> Result<Vec<(FileOffset, GuestAddress, usize)>, MemoryError> {
+    let mut offset: u64 = 0;
+    regions
+        .iter()
+        .map(|(guest_address, region_size)| {
+            let file_clone = file.try_clone().map_err(MemoryError::FileError)?;
+            let file_offset = FileOffset::new(file_clone, offset);
+            offset += *region_size as u64;
+            Ok((file_offset, *guest_address, *region_size))
+        })
+        .collect()
 }
 
 fn create_memfd(
@@ -419,13 +594,47 @@ fn create_memfd(
     Ok(mem_file)
 }
 
+/// Writer passing the contents of a memory dump to `writer` in writes of at most `chunk_size`
+/// bytes, instead of one write per contiguous memory range.
+#[derive(Debug)]
+pub struct ChunkedWriter<'a, T> {
+    writer: &'a mut T,
+    chunk_size: usize,
+}
+
+impl<'a, T> ChunkedWriter<'a, T> {
+    /// Creates a writer splitting the writes to `writer` in chunks of `chunk_size` bytes.
+    pub fn new(writer: &'a mut T, chunk_size: usize) -> Self {
+        assert!(chunk_size > 0, "The write chunk size cannot be 0.");
+        ChunkedWriter { writer, chunk_size }
+    }
+}
+
+impl<T: WriteVolatile> WriteVolatile for ChunkedWriter<'_, T> {
+    fn write_volatile<B: BitmapSlice>(
+        &mut self,
+        buf: &VolatileSlice<B>,
+    ) -> Result<usize, VolatileMemoryError> {
+        let len = buf.len().min(self.chunk_size);
+        self.writer.write_volatile(&buf.subslice(0, len)?)
+    }
+}
+
+impl<T: std::io::Seek> std::io::Seek for ChunkedWriter<'_, T> {
+    fn seek(&mut self, pos: SeekFrom) -> std::io::Result<u64> {
+        self.writer.seek(pos)
+    }
+}
+
 #[cfg(test)]
 mod tests {
     #![allow(clippy::undocumented_unsafe_blocks)]
 
     use std::collections::HashMap;
     use std::io::{Read, Seek};
+    use std::os::unix::fs::FileExt;
 
+    use vmm_sys_util::seek_hole::SeekHole;
     use vmm_sys_util::tempfile::TempFile;
 
     use super::*;
@@ -469,6 +678,45 @@ mod tests {
         }
     }
 
+    #[test]
+    fn test_file_backed() {
+        let page_size = get_page_size().unwrap();
+        let regions = vec![
+            (GuestAddress(0), page_size),
+            (GuestAddress(0x10_0000), 2 * page_size),
+        ];
+        let file = TempFile::new().unwrap().into_file();
+
+        let guest_memory = GuestMemoryMmap::file_backed(&regions, &file, false).unwrap();
+        assert_eq!(file.metadata().unwrap().len(), 3 * page_size as u64);
+        assert_eq!(guest_memory.num_regions(), 2);
+
+        // Guest writes are visible through the file, the regions being laid out consecutively.
+        guest_memory
+            .write_obj(0x1234_5678u32, GuestAddress(0x10_0000 + 8))
+            .unwrap();
+        let mut data = [0u8; 4];
+        file.read_exact_at(&mut data, page_size as u64 + 8).unwrap();
+        assert_eq!(u32::from_le_bytes(data), 0x1234_5678);
+
+        // And the other way around.
+        file.write_all_at(&0xdead_beefu32.to_le_bytes(), 16)
+            .unwrap();
+        assert_eq!(
+            guest_memory.read_obj::<u32>(GuestAddress(16)).unwrap(),
+            0xdead_beef
+        );
+
+        // Another mapping of the file shares the same memory.
+        let other_memory = GuestMemoryMmap::file_backed(&regions, &file, false).unwrap();
+        assert_eq!(
+            other_memory
+                .read_obj::<u32>(GuestAddress(0x10_0000 + 8))
+                .unwrap(),
+            0x1234_5678
+        );
+    }
+
     #[test]
     fn test_from_raw_regions_file() {
         let region_size = 0x10000;
@@ -837,6 +1085,182 @@ mod tests {
         assert_eq!(expected_first_region, diff_file_content);
     }
 
+    #[test]
+    fn test_dump_diff() {
+        let page_size = get_page_size().unwrap();
+
+        // Two regions of two pages each, with a one page gap between them.
+        let region_1_address = GuestAddress(0);
+        let region_2_address = GuestAddress(page_size as u64 * 3);
+        let region_size = page_size * 2;
+        let mem_regions = [
+            (region_1_address, region_size),
+            (region_2_address, region_size),
+        ];
+        let guest_memory =
+            GuestMemoryMmap::from_raw_regions(&mem_regions, false, HugePageConfig::None).unwrap();
+
+        let zeros = vec![0u8; page_size];
+        let ones = vec![1u8; page_size];
+        let twos = vec![2u8; page_size];
+        let threes = vec![3u8; page_size];
+
+        // Fill the first region with 1s and the second with 2s, and take the baseline.
+        guest_memory
+            .write(
+                &[ones.as_slice(), ones.as_slice()].concat(),
+                region_1_address,
+            )
+            .unwrap();
+        guest_memory
+            .write(
+                &[twos.as_slice(), twos.as_slice()].concat(),
+                region_2_address,
+            )
+            .unwrap();
+        let mut baseline_file = TempFile::new().unwrap().into_file();
+        guest_memory.dump(&mut baseline_file).unwrap();
+
+        // Nothing is written when the memory matches the baseline.
+        baseline_file.seek(SeekFrom::Start(0)).unwrap();
+        let mut diff_file = TempFile::new().unwrap().into_file();
+        guest_memory
+            .dump_diff(&mut diff_file, &mut baseline_file)
+            .unwrap();
+        assert_eq!(diff_file.metadata().unwrap().len(), 0);
+
+        // Modify the second page of the first region and the first page of the second region.
+        guest_memory
+            .write(&threes, GuestAddress(page_size as u64))
+            .unwrap();
+        guest_memory.write(&zeros, region_2_address).unwrap();
+
+        baseline_file.seek(SeekFrom::Start(0)).unwrap();
+        let mut diff_file = TempFile::new().unwrap().into_file();
+        guest_memory
+            .dump_diff(&mut diff_file, &mut baseline_file)
+            .unwrap();
+
+        // Check that only the modified pages are dumped.
+        let mut diff_file_content = Vec::new();
+        diff_file.seek(SeekFrom::Start(0)).unwrap();
+        diff_file.read_to_end(&mut diff_file_content).unwrap();
+        assert_eq!(
+            diff_file_content,
+            [zeros.as_slice(), threes.as_slice(), zeros.as_slice()].concat()
+        );
+
+        // Reconstruct the full memory by merging the diff on top of the baseline.
+        let mut merged = Vec::new();
+        baseline_file.seek(SeekFrom::Start(0)).unwrap();
+        baseline_file.read_to_end(&mut merged).unwrap();
+        let mut cursor = 0;
+        while let Some(data_start) = diff_file.seek_data(cursor).unwrap() {
+            let data_end = diff_file
+                .seek_hole(data_start)
+                .unwrap()
+                .unwrap_or(diff_file_content.len() as u64);
+            let range = u64_to_usize(data_start)..u64_to_usize(data_end);
+            merged[range.clone()].copy_from_slice(&diff_file_content[range]);
+            cursor = data_end;
+        }
+
+        let mut full_file = TempFile::new().unwrap().into_file();
+        guest_memory.dump(&mut full_file).unwrap();
+        let mut full_file_content = Vec::new();
+        full_file.seek(SeekFrom::Start(0)).unwrap();
+        full_file.read_to_end(&mut full_file_content).unwrap();
+        assert_eq!(merged, full_file_content);
+        assert_eq!(
+            merged,
+            [
+                ones.as_slice(),
+                threes.as_slice(),
+                zeros.as_slice(),
+                twos.as_slice()
+            ]
+            .concat()
+        );
+    }
+
+    #[derive(Default)]
+    struct RecordingWriter {
+        contents: Vec<u8>,
+        write_sizes: Vec<usize>,
+    }
+
+    impl WriteVolatile for RecordingWriter {
+        fn write_volatile<B: BitmapSlice>(
+            &mut self,
+            buf: &VolatileSlice<B>,
+        ) -> Result<usize, VolatileMemoryError> {
+            self.write_sizes.push(buf.len());
+            self.contents.write_volatile(buf)
+        }
+    }
+
+    #[test]
+    fn test_dump_chunked() {
+        let page_size = get_page_size().unwrap();
+
+        // Two regions of four and two pages, with a one page gap between them.
+        let mem_regions = [
+            (GuestAddress(0), page_size * 4),
+            (GuestAddress(page_size as u64 * 5), page_size * 2),
+        ];
+        let guest_memory =
+            GuestMemoryMmap::from_raw_regions(&mem_regions, false, HugePageConfig::None).unwrap();
+        for (address, size) in mem_regions {
+            let contents: Vec<u8> = (0..size).map(|i| (i % 251) as u8).collect();
+            guest_memory.write(&contents, address).unwrap();
+        }
+
+        // Without chunking, each region is dumped in a single write.
+        let mut expected = RecordingWriter::default();
+        guest_memory.dump(&mut expected).unwrap();
+        assert_eq!(expected.write_sizes, [page_size * 4, page_size * 2]);
+
+        let dirty_bitmap: DirtyBitmap = HashMap::from([(0, vec![0b1001]), (1, vec![0b10])]);
+        let mut expected_dirty = TempFile::new().unwrap().into_file();
+        guest_memory
+            .dump_dirty(&mut expected_dirty, &dirty_bitmap)
+            .unwrap();
+        let mut expected_dirty_contents = Vec::new();
+        expected_dirty.rewind().unwrap();
+        expected_dirty
+            .read_to_end(&mut expected_dirty_contents)
+            .unwrap();
+
+        for chunk_size in [1, 100, page_size, page_size * 3, page_size * 16] {
+            let mut writer = RecordingWriter::default();
+            guest_memory
+                .dump(&mut ChunkedWriter::new(&mut writer, chunk_size))
+                .unwrap();
+            assert_eq!(writer.contents, expected.contents);
+            assert!(writer.write_sizes.iter().all(|size| *size <= chunk_size));
+            assert_eq!(
+                writer.write_sizes.len(),
+                mem_regions
+                    .iter()
+                    .map(|(_, size)| size.div_ceil(chunk_size))
+                    .sum::<usize>()
+            );
+
+            // Chunking does not change where the dirty pages are written either.
+            let mut dirty = TempFile::new().unwrap().into_file();
+            guest_memory
+                .dump_dirty(
+                    &mut ChunkedWriter::new(&mut dirty, chunk_size),
+                    &dirty_bitmap,
+                )
+                .unwrap();
+            let mut dirty_contents = Vec::new();
+            dirty.rewind().unwrap();
+            dirty.read_to_end(&mut dirty_contents).unwrap();
+            assert_eq!(dirty_contents, expected_dirty_contents);
+        }
+    }
+
     #[test]
     fn test_store_dirty_bitmap() {
         let page_size = get_page_size().unwrap();
@@ -873,6 +1297,77 @@ mod tests {
         });
     }
 
+    #[test]
+    fn test_advise_thp() {
+        // Returns the flags of the mapping containing `addr`, as reported in /proc/self/smaps.
+        fn vm_flags(addr: usize) -> Vec<String> {
+            let smaps = std::fs::read_to_string("/proc/self/smaps").unwrap();
+            smaps
+                .lines()
+                .skip_while(|line| {
+                    let range = line.split_whitespace().next().unwrap_or_default();
+                    let Some((start, end)) = range.split_once('-') else {
+                        return true;
+                    };
+                    match (
+                        usize::from_str_radix(start, 16),
+                        usize::from_str_radix(end, 16),
+                    ) {
+                        (Ok(start), Ok(end)) => !(start..end).contains(&addr),
+                        _ => true,
+                    }
+                })
+                .find_map(|line| line.strip_prefix("VmFlags:"))
+                .unwrap()
+                .split_whitespace()
+                .map(str::to_string)
+                .collect()
+        }
+
+        let region_size = 0x400000;
+        let regions = vec![
+            (GuestAddress(0x0), region_size),
+            (GuestAddress(0x400000), region_size),
+        ];
+        let guest_memory =
+            GuestMemoryMmap::from_raw_regions(&regions, false, HugePageConfig::None).unwrap();
+
+        guest_memory.advise_thp(ThpAdvice::Hugepage).unwrap();
+        for region in guest_memory.iter() {
+            let flags = vm_flags(region.as_ptr() as usize);
+            assert!(flags.iter().any(|flag| flag == "hg"));
+            assert!(!flags.iter().any(|flag| flag == "nh"));
+        }
+
+        guest_memory.advise_thp(ThpAdvice::NoHugepage).unwrap();
+        for region in guest_memory.iter() {
+            let flags = vm_flags(region.as_ptr() as usize);
+            assert!(!flags.iter().any(|flag| flag == "hg"));
+            assert!(flags.iter().any(|flag| flag == "nh"));
+        }
+    }
+
+    #[cfg(feature = "mem-poison")]
+    #[test]
+    fn test_poison() {
+        let region_size = 0x10000;
+        let regions = vec![
+            (GuestAddress(0x0), region_size),
+            (GuestAddress(0x10000), region_size),
+        ];
+        let guest_memory =
+            GuestMemoryMmap::from_raw_regions(&regions, false, HugePageConfig::None).unwrap();
+
+        guest_memory.poison(0xa5);
+        for region in guest_memory.iter() {
+            let mut data = vec![0u8; region_size];
+            region
+                .read_slice(&mut data, MemoryRegionAddress(0))
+                .unwrap();
+            assert!(data.iter().all(|&byte| byte == 0xa5));
+        }
+    }
+
     #[test]
     fn test_create_memfd() {
         let size = 1;
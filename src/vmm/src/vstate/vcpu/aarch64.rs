@@ -68,6 +68,8 @@ pub struct KvmVcpu {
 pub(super) struct Peripherals {
     /// mmio bus.
     pub mmio_bus: Option<crate::devices::Bus>,
+    /// KVM exit statistics.
+    pub run_stats: super::VcpuRunStats,
 }
 
 impl KvmVcpu {
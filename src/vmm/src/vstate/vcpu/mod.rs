@@ -6,9 +6,11 @@
 // found in the THIRD-PARTY file.
 
 use std::cell::Cell;
+#[cfg(target_arch = "x86_64")]
+use std::collections::BTreeMap;
 #[cfg(feature = "gdb")]
 use std::os::fd::AsRawFd;
-use std::sync::atomic::{fence, Ordering};
+use std::sync::atomic::{fence, AtomicU8, Ordering};
 use std::sync::mpsc::{channel, Receiver, Sender, TryRecvError};
 use std::sync::{Arc, Barrier};
 use std::{fmt, io, thread};
@@ -20,6 +22,7 @@ use kvm_ioctls::VcpuFd;
 use libc::{c_int, c_void, siginfo_t};
 use log::{error, info, warn};
 use seccompiler::{BpfProgram, BpfProgramRef};
+use serde::Serialize;
 use vmm_sys_util::errno;
 use vmm_sys_util::eventfd::EventFd;
 
@@ -29,6 +32,8 @@ use crate::gdb::target::{get_raw_tid, GdbTargetError};
 use crate::logger::{IncMetric, METRICS};
 use crate::utils::signal::{register_signal_handler, sigrtmin, Killable};
 use crate::utils::sm::StateMachine;
+#[cfg(target_arch = "x86_64")]
+use crate::vmm_config::machine_config::CpuidNormalizeOptions;
 use crate::vstate::vm::Vm;
 use crate::FcExitCode;
 
@@ -47,6 +52,10 @@ pub use x86_64::{KvmVcpuError, *};
 /// Signal number (SIGRTMIN) used to kick Vcpus.
 pub const VCPU_RTSIG_OFFSET: i32 = 0;
 
+/// Default maximum number of consecutive spurious `KVM_RUN` exits a vcpu re-enters the guest
+/// after, before failing.
+pub const DEFAULT_KVM_RUN_RETRIES: u32 = 1024;
+
 /// Errors associated with the wrappers over KVM ioctls.
 #[derive(Debug, thiserror::Error, displaydoc::Display)]
 pub enum VcpuError {
@@ -80,6 +89,9 @@ pub struct VcpuConfig {
     pub smt: bool,
     /// Configuration for vCPU
     pub cpu_config: CpuConfiguration,
+    /// CPUID settings of the machine configuration, applied when the CPUID is normalized.
+    #[cfg(target_arch = "x86_64")]
+    pub cpuid_options: CpuidNormalizeOptions,
 }
 
 // Using this for easier explicit type-casting to help IDEs interpret the code.
@@ -120,6 +132,12 @@ pub struct Vcpu {
     response_receiver: Option<Receiver<VcpuResponse>>,
     /// The transmitting end of the responses channel owned by the vcpu side.
     response_sender: Sender<VcpuResponse>,
+    /// Maximum number of consecutive spurious exits after which the vcpu re-enters the guest.
+    kvm_run_retries: u32,
+    /// Number of consecutive spurious exits received so far.
+    spurious_exits: u32,
+    /// Run state of the vcpu, shared with its handle.
+    run_state: SharedVcpuRunState,
 }
 
 impl Vcpu {
@@ -226,9 +244,18 @@ impl Vcpu {
             #[cfg(feature = "gdb")]
             gdb_event: None,
             kvm_vcpu,
+            kvm_run_retries: DEFAULT_KVM_RUN_RETRIES,
+            spurious_exits: 0,
+            run_state: SharedVcpuRunState::default(),
         })
     }
 
+    /// Sets the maximum number of consecutive spurious `KVM_RUN` exits after which this vcpu
+    /// re-enters the guest.
+    pub fn set_kvm_run_retries(&mut self, kvm_run_retries: u32) {
+        self.kvm_run_retries = kvm_run_retries;
+    }
+
     /// Sets a MMIO bus for this vcpu.
     pub fn set_mmio_bus(&mut self, mmio_bus: crate::devices::Bus) {
         self.kvm_vcpu.peripherals.mmio_bus = Some(mmio_bus);
@@ -261,6 +288,7 @@ impl Vcpu {
     ) -> Result<VcpuHandle, StartThreadedError> {
         let event_sender = self.event_sender.take().expect("vCPU already started");
         let response_receiver = self.response_receiver.take().unwrap();
+        let run_state = self.run_state.clone();
         let vcpu_thread = thread::Builder::new()
             .name(format!("fc_vcpu {}", self.kvm_vcpu.index))
             .spawn(move || {
@@ -276,6 +304,7 @@ impl Vcpu {
             event_sender,
             response_receiver,
             vcpu_thread,
+            run_state,
         ))
     }
 
@@ -286,9 +315,10 @@ impl Vcpu {
     /// anything useful.
     pub fn run(&mut self, seccomp_filter: BpfProgramRef) {
         // Load seccomp filters for this vCPU thread.
-        // Execution panics if filters cannot be loaded, use --no-seccomp if skipping filters
-        // altogether is the desired behaviour.
-        if let Err(err) = seccompiler::apply_filter(seccomp_filter) {
+        // Execution panics if filters cannot be loaded, unless the failure policy is to warn.
+        // Use --no-seccomp if skipping filters altogether is the desired behaviour.
+        crate::seccomp_audit::register_thread();
+        if let Err(err) = crate::seccomp_filters::install_filter("vCPU", seccomp_filter) {
             panic!(
                 "Failed to set the requested seccomp filters on vCPU {}: Error: {}",
                 self.kvm_vcpu.index, err
@@ -305,10 +335,13 @@ impl Vcpu {
         // No point in ticking the state machine if there are no external events.
         loop {
             match self.run_emulation() {
-                // Emulation ran successfully, continue.
-                Ok(VcpuEmulation::Handled) => (),
+                // Emulation ran successfully or the exit was spurious, continue.
+                Ok(VcpuEmulation::Handled | VcpuEmulation::Spurious) => (),
                 // Emulation was interrupted, check external events.
-                Ok(VcpuEmulation::Interrupted) => break,
+                Ok(VcpuEmulation::Interrupted) => {
+                    self.kvm_vcpu.peripherals.run_stats.interrupted_exits += 1;
+                    break;
+                }
                 // If the guest was rebooted or halted:
                 // - vCPU0 will always exit out of `KVM_RUN` with KVM_EXIT_SHUTDOWN or KVM_EXIT_HLT.
                 // - the other vCPUs won't ever exit out of `KVM_RUN`, but they won't consume CPU.
@@ -317,6 +350,7 @@ impl Vcpu {
                 // If the emulation requests a pause lets do this
                 #[cfg(feature = "gdb")]
                 Ok(VcpuEmulation::Paused) => {
+                    self.run_state.set(VcpuRunState::Paused);
                     return StateMachine::next(Self::paused);
                 }
                 // Emulation errors lead to vCPU exit.
@@ -331,7 +365,7 @@ impl Vcpu {
         match self.event_receiver.try_recv() {
             // Running ---- Pause ----> Paused
             Ok(VcpuEvent::Pause) => {
-                // Nothing special to do.
+                self.run_state.set(VcpuRunState::Paused);
                 self.response_sender
                     .send(VcpuResponse::Paused)
                     .expect("vcpu channel unexpectedly closed");
@@ -370,6 +404,11 @@ impl Vcpu {
                     )))
                     .expect("vcpu channel unexpectedly closed");
             }
+            Ok(VcpuEvent::GetRunStats) => {
+                self.response_sender
+                    .send(VcpuResponse::RunStats(self.kvm_vcpu.peripherals.run_stats))
+                    .expect("vcpu channel unexpectedly closed");
+            }
             Ok(VcpuEvent::Finish) => return StateMachine::finish(),
             // Unhandled exit of the other end.
             Err(TryRecvError::Disconnected) => {
@@ -395,7 +434,7 @@ impl Vcpu {
                     );
                     self.kvm_vcpu.fd.set_kvm_immediate_exit(0);
                 }
-                // Nothing special to do.
+                self.run_state.set(VcpuRunState::Running);
                 self.response_sender
                     .send(VcpuResponse::Resumed)
                     .expect("vcpu channel unexpectedly closed");
@@ -441,6 +480,12 @@ impl Vcpu {
 
                 StateMachine::next(Self::paused)
             }
+            Ok(VcpuEvent::GetRunStats) => {
+                self.response_sender
+                    .send(VcpuResponse::RunStats(self.kvm_vcpu.peripherals.run_stats))
+                    .expect("vcpu channel unexpectedly closed");
+                StateMachine::next(Self::paused)
+            }
             Ok(VcpuEvent::Finish) => StateMachine::finish(),
             // Unhandled exit of the other end.
             Err(_) => {
@@ -469,6 +514,10 @@ impl Vcpu {
         // Vmm initiated teardown starts from `pub fn Vmm::stop()` (step 4).
         // Once `vmm.shutdown_exit_code` becomes `Some(exit_code)`, it is the upper layer's
         // responsibility to break main event loop and propagate the exit code value.
+        self.run_state.set(match exit_code {
+            FcExitCode::Ok => VcpuRunState::Stopped,
+            _ => VcpuRunState::Exited,
+        });
         // Signal Vmm of Vcpu exit.
         if let Err(err) = self.exit_evt.write(1) {
             METRICS.vcpu.failures.inc();
@@ -497,7 +546,7 @@ impl Vcpu {
             return Ok(VcpuEmulation::Interrupted);
         }
 
-        match self.kvm_vcpu.fd.run() {
+        let emulation = match self.kvm_vcpu.fd.run() {
             Err(ref err) if err.errno() == libc::EINTR => {
                 self.kvm_vcpu.fd.set_kvm_immediate_exit(0);
                 // Notify that this KVM_RUN was interrupted.
@@ -513,8 +562,50 @@ impl Vcpu {
 
                 Ok(VcpuEmulation::Paused)
             }
-            emulation_result => handle_kvm_exit(&mut self.kvm_vcpu.peripherals, emulation_result),
+            emulation_result => {
+                let in_mmio = matches!(
+                    emulation_result,
+                    Ok(VcpuExit::MmioRead(..) | VcpuExit::MmioWrite(..))
+                );
+                if in_mmio {
+                    self.run_state.set(VcpuRunState::InMmio);
+                }
+                let emulation = handle_kvm_exit(&mut self.kvm_vcpu.peripherals, emulation_result);
+                if in_mmio {
+                    self.run_state.set(VcpuRunState::Running);
+                }
+                emulation
+            }
+        };
+
+        self.check_spurious_exits(emulation)
+    }
+
+    /// Counts consecutive spurious exits, failing the emulation once there are more of them
+    /// than the configured number of `KVM_RUN` retries.
+    fn check_spurious_exits(
+        &mut self,
+        emulation: Result<VcpuEmulation, VcpuError>,
+    ) -> Result<VcpuEmulation, VcpuError> {
+        if !matches!(emulation, Ok(VcpuEmulation::Spurious)) {
+            self.spurious_exits = 0;
+            return emulation;
+        }
+
+        METRICS.vcpu.spurious_exits.inc();
+        self.spurious_exits = self.spurious_exits.saturating_add(1);
+        if self.spurious_exits > self.kvm_run_retries {
+            METRICS.vcpu.failures.inc();
+            error!(
+                "Received {} consecutive spurious KVM exits on vcpu {}.",
+                self.spurious_exits, self.kvm_vcpu.index
+            );
+            return Err(VcpuError::FaultyKvmExit(format!(
+                "Received {} consecutive spurious KVM exits.",
+                self.spurious_exits
+            )));
         }
+        emulation
     }
 }
 
@@ -531,6 +622,7 @@ fn handle_kvm_exit(
                     mmio_bus.read(addr, data);
                     METRICS.vcpu.exit_mmio_read.inc();
                 }
+                peripherals.run_stats.mmio_reads += 1;
                 Ok(VcpuEmulation::Handled)
             }
             VcpuExit::MmioWrite(addr, data) => {
@@ -539,10 +631,12 @@ fn handle_kvm_exit(
                     mmio_bus.write(addr, data);
                     METRICS.vcpu.exit_mmio_write.inc();
                 }
+                peripherals.run_stats.mmio_writes += 1;
                 Ok(VcpuEmulation::Handled)
             }
             VcpuExit::Hlt => {
                 info!("Received KVM_EXIT_HLT signal");
+                peripherals.run_stats.hlt_exits += 1;
                 Ok(VcpuEmulation::Stopped)
             }
             VcpuExit::Shutdown => {
@@ -600,7 +694,7 @@ fn handle_kvm_exit(
         // The unwrap on raw_os_error can only fail if we have a logic
         // error in our code in which case it is better to panic.
         Err(ref err) => match err.errno() {
-            libc::EAGAIN => Ok(VcpuEmulation::Handled),
+            libc::EAGAIN => Ok(VcpuEmulation::Spurious),
             libc::ENOSYS => {
                 METRICS.vcpu.failures.inc();
                 error!("Received ENOSYS error because KVM failed to emulate an instruction.");
@@ -637,6 +731,66 @@ pub enum VcpuEvent {
     SaveState,
     /// Event to dump CPU configuration of a paused Vcpu.
     DumpCpuConfig,
+    /// Event to retrieve the KVM exit statistics of the Vcpu.
+    GetRunStats,
+}
+
+/// Counters of the KVM exits handled by a Vcpu since it was created.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq)]
+pub struct VcpuRunStats {
+    /// Number of MMIO read exits.
+    pub mmio_reads: u64,
+    /// Number of MMIO write exits.
+    pub mmio_writes: u64,
+    /// Number of PIO read exits.
+    pub pio_reads: u64,
+    /// Number of PIO write exits.
+    pub pio_writes: u64,
+    /// Number of HLT exits.
+    pub hlt_exits: u64,
+    /// Number of times the emulation of the running Vcpu was interrupted by a signal, such as a
+    /// kick from the VMM.
+    pub interrupted_exits: u64,
+}
+
+/// Fine-grained run state of a Vcpu, as tracked by its thread.
+#[repr(u8)]
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize)]
+pub enum VcpuRunState {
+    /// The Vcpu is paused, which it is until the microVM starts.
+    #[default]
+    Paused,
+    /// The Vcpu runs guest code, or handles a KVM exit other than an MMIO access.
+    Running,
+    /// The Vcpu emulates an MMIO access of the guest.
+    InMmio,
+    /// The Vcpu thread stopped running the guest, because the guest shut down or rebooted.
+    Stopped,
+    /// The Vcpu stopped on an emulation error.
+    Exited,
+}
+
+/// [`VcpuRunState`] shared by a Vcpu thread with its handle, which reads it without
+/// interrupting the Vcpu.
+#[derive(Clone, Debug, Default)]
+pub struct SharedVcpuRunState(Arc<AtomicU8>);
+
+impl SharedVcpuRunState {
+    /// Returns the current run state.
+    pub fn get(&self) -> VcpuRunState {
+        match self.0.load(Ordering::Relaxed) {
+            state if state == VcpuRunState::Running as u8 => VcpuRunState::Running,
+            state if state == VcpuRunState::InMmio as u8 => VcpuRunState::InMmio,
+            state if state == VcpuRunState::Stopped as u8 => VcpuRunState::Stopped,
+            state if state == VcpuRunState::Exited as u8 => VcpuRunState::Exited,
+            _ => VcpuRunState::Paused,
+        }
+    }
+
+    /// Sets the current run state.
+    pub fn set(&self, state: VcpuRunState) {
+        self.0.store(state as u8, Ordering::Relaxed);
+    }
 }
 
 /// List of responses that the Vcpu reports.
@@ -655,6 +809,8 @@ pub enum VcpuResponse {
     SavedState(Box<VcpuState>),
     /// Vcpu is in the state where CPU config is dumped.
     DumpedCpuConfig(Box<CpuConfiguration>),
+    /// KVM exit statistics of the Vcpu.
+    RunStats(VcpuRunStats),
 }
 
 impl fmt::Debug for VcpuResponse {
@@ -668,6 +824,7 @@ impl fmt::Debug for VcpuResponse {
             Error(ref err) => write!(f, "VcpuResponse::Error({:?})", err),
             NotAllowed(ref reason) => write!(f, "VcpuResponse::NotAllowed({})", reason),
             DumpedCpuConfig(_) => write!(f, "VcpuResponse::DumpedCpuConfig"),
+            RunStats(stats) => write!(f, "VcpuResponse::RunStats({:?})", stats),
         }
     }
 }
@@ -680,6 +837,7 @@ pub struct VcpuHandle {
     // Rust JoinHandles have to be wrapped in Option if you ever plan on 'join()'ing them.
     // We want to be able to join these threads in tests.
     vcpu_thread: Option<thread::JoinHandle<()>>,
+    run_state: SharedVcpuRunState,
 }
 
 /// Error type for [`VcpuHandle::send_event`].
@@ -694,15 +852,18 @@ impl VcpuHandle {
     /// + `event_sender`: [`Sender`] to communicate [`VcpuEvent`] to control the vcpu.
     /// + `response_received`: [`Received`] from which the vcpu's responses can be read.
     /// + `vcpu_thread`: A [`JoinHandle`] for the vcpu thread.
+    /// + `run_state`: The [`SharedVcpuRunState`] updated by the vcpu thread.
     pub fn new(
         event_sender: Sender<VcpuEvent>,
         response_receiver: Receiver<VcpuResponse>,
         vcpu_thread: thread::JoinHandle<()>,
+        run_state: SharedVcpuRunState,
     ) -> Self {
         Self {
             event_sender,
             response_receiver,
             vcpu_thread: Some(vcpu_thread),
+            run_state,
         }
     }
     /// Sends event to vCPU.
@@ -728,6 +889,11 @@ impl VcpuHandle {
     pub fn response_receiver(&self) -> &Receiver<VcpuResponse> {
         &self.response_receiver
     }
+
+    /// Returns the current run state of the vcpu.
+    pub fn run_state(&self) -> VcpuRunState {
+        self.run_state.get()
+    }
 }
 
 // Wait for the Vcpu thread to finish execution
@@ -749,6 +915,8 @@ impl Drop for VcpuHandle {
 pub enum VcpuEmulation {
     /// Handled.
     Handled,
+    /// Spurious exit, the guest should simply be re-entered.
+    Spurious,
     /// Interrupted.
     Interrupted,
     /// Stopped.
@@ -846,7 +1014,7 @@ pub(crate) mod tests {
             &mut vcpu.kvm_vcpu.peripherals,
             Err(errno::Error::new(libc::EAGAIN)),
         );
-        assert_eq!(res.unwrap(), VcpuEmulation::Handled);
+        assert_eq!(res.unwrap(), VcpuEmulation::Spurious);
 
         let res = handle_kvm_exit(
             &mut vcpu.kvm_vcpu.peripherals,
@@ -892,6 +1060,11 @@ pub(crate) mod tests {
             Ok(VcpuExit::MmioWrite(addr, &[0, 0, 0, 0])),
         );
         assert_eq!(res.unwrap(), VcpuEmulation::Handled);
+
+        let stats = vcpu.kvm_vcpu.peripherals.run_stats;
+        assert_eq!(stats.mmio_reads, 1);
+        assert_eq!(stats.mmio_writes, 1);
+        assert_eq!(stats.hlt_exits, 1);
     }
 
     impl PartialEq for VcpuResponse {
@@ -900,11 +1073,12 @@ pub(crate) mod tests {
             // Guard match with no wildcard to make sure we catch new enum variants.
             match self {
                 Paused | Resumed | Exited(_) => (),
-                Error(_) | NotAllowed(_) | SavedState(_) | DumpedCpuConfig(_) => (),
+                Error(_) | NotAllowed(_) | SavedState(_) | DumpedCpuConfig(_) | RunStats(_) => (),
             };
             match (self, other) {
                 (Paused, Paused) | (Resumed, Resumed) => true,
                 (Exited(code), Exited(other_code)) => code == other_code,
+                (RunStats(stats), RunStats(other_stats)) => stats == other_stats,
                 (NotAllowed(_), NotAllowed(_))
                 | (SavedState(_), SavedState(_))
                 | (DumpedCpuConfig(_), DumpedCpuConfig(_)) => true,
@@ -991,6 +1165,7 @@ pub(crate) mod tests {
                             cpuid: Cpuid::try_from(_vm.supported_cpuid().clone()).unwrap(),
                             msrs: BTreeMap::new(),
                         },
+                        cpuid_options: CpuidNormalizeOptions::default(),
                     },
                 )
                 .expect("failed to configure vcpu");
@@ -1020,6 +1195,45 @@ pub(crate) mod tests {
         (vcpu_handle, vcpu_exit_evt)
     }
 
+    #[test]
+    fn test_spurious_exits() {
+        let (_, mut vcpu, _) = setup_vcpu(0x1000);
+        vcpu.set_kvm_run_retries(2);
+        let spurious_exit = |vcpu: &mut Vcpu| {
+            let emulation = handle_kvm_exit(
+                &mut vcpu.kvm_vcpu.peripherals,
+                Err(errno::Error::new(libc::EAGAIN)),
+            );
+            vcpu.check_spurious_exits(emulation)
+        };
+
+        // Spurious exits re-enter the guest and are counted.
+        let spurious_exits = METRICS.vcpu.spurious_exits.count();
+        for _ in 0..2 {
+            assert_eq!(spurious_exit(&mut vcpu).unwrap(), VcpuEmulation::Spurious);
+        }
+        assert_eq!(METRICS.vcpu.spurious_exits.count(), spurious_exits + 2);
+
+        // Any other exit resets the count of consecutive spurious exits.
+        let emulation = handle_kvm_exit(&mut vcpu.kvm_vcpu.peripherals, Ok(VcpuExit::Hlt));
+        assert_eq!(
+            vcpu.check_spurious_exits(emulation).unwrap(),
+            VcpuEmulation::Stopped
+        );
+        for _ in 0..2 {
+            assert_eq!(spurious_exit(&mut vcpu).unwrap(), VcpuEmulation::Spurious);
+        }
+
+        // Exceeding the number of retries fails the emulation.
+        let failures = METRICS.vcpu.failures.count();
+        assert_eq!(
+            spurious_exit(&mut vcpu).unwrap_err().to_string(),
+            "Received error signaling kvm exit: Received 3 consecutive spurious KVM exits."
+        );
+        assert_eq!(METRICS.vcpu.spurious_exits.count(), spurious_exits + 5);
+        assert!(METRICS.vcpu.failures.count() > failures);
+    }
+
     #[test]
     fn test_set_mmio_bus() {
         let (_, mut vcpu, _) = setup_vcpu(0x1000);
@@ -1110,6 +1324,89 @@ pub(crate) mod tests {
         assert!(success.load(Ordering::Acquire));
     }
 
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_idle_block_strategy() {
+        use crate::vmm_config::machine_config::VcpuIdleStrategy;
+        use crate::vstate::memory::Bytes;
+
+        fn thread_cpu_time() -> std::time::Duration {
+            let mut ts = libc::timespec {
+                tv_sec: 0,
+                tv_nsec: 0,
+            };
+            // SAFETY: Safe because the parameters are valid.
+            let ret = unsafe { libc::clock_gettime(libc::CLOCK_THREAD_CPUTIME_ID, &mut ts) };
+            assert_eq!(ret, 0);
+            std::time::Duration::new(
+                u64::try_from(ts.tv_sec).unwrap(),
+                u32::try_from(ts.tv_nsec).unwrap(),
+            )
+        }
+
+        Vcpu::register_kick_signal_handler();
+        let (vm, mut vcpu, vm_mem) = setup_vcpu(0x10000);
+        // Halt polling can only be configured on Linux 5.16 or newer.
+        if !vm.can_set_halt_poll_ns() {
+            return;
+        }
+        vm.set_halt_poll_ns(VcpuIdleStrategy::Block.halt_poll_ns())
+            .unwrap();
+
+        // Real mode guest code halting in a loop with interrupts disabled: `hlt; jmp $-1`.
+        let code_addr = 0x1000;
+        vm_mem
+            .write_slice(&[0xf4, 0xeb, 0xfd], GuestAddress(code_addr))
+            .unwrap();
+        let mut sregs = vcpu.kvm_vcpu.fd.get_sregs().unwrap();
+        sregs.cs.base = 0;
+        sregs.cs.selector = 0;
+        vcpu.kvm_vcpu.fd.set_sregs(&sregs).unwrap();
+        let regs = kvm_bindings::kvm_regs {
+            rip: code_addr,
+            rflags: 0x2,
+            ..Default::default()
+        };
+        vcpu.kvm_vcpu.fd.set_regs(&regs).unwrap();
+
+        let barrier = Arc::new(Barrier::new(2));
+        let vcpu_barrier = barrier.clone();
+        let handle = std::thread::Builder::new()
+            .name("test_idle_block_strategy".to_string())
+            .spawn(move || {
+                vcpu.init_thread_local_data().unwrap();
+                vcpu_barrier.wait();
+                let wall_start = std::time::Instant::now();
+                let cpu_start = thread_cpu_time();
+                // The guest halts until the vcpu gets kicked out of `KVM_RUN`.
+                let interrupted = matches!(
+                    vcpu.kvm_vcpu.fd.run(),
+                    Err(err) if err.errno() == libc::EINTR
+                );
+                (
+                    interrupted,
+                    wall_start.elapsed(),
+                    thread_cpu_time() - cpu_start,
+                )
+            })
+            .expect("cannot start thread");
+
+        barrier.wait();
+        std::thread::sleep(std::time::Duration::from_millis(500));
+        handle
+            .kill(sigrtmin() + VCPU_RTSIG_OFFSET)
+            .expect("failed to signal thread");
+        let (interrupted, wall_time, cpu_time) = handle.join().expect("failed to join thread");
+
+        assert!(interrupted);
+        assert!(wall_time >= std::time::Duration::from_millis(400));
+        // The idle vcpu thread sleeps instead of polling for interrupts.
+        assert!(
+            cpu_time < wall_time / 10,
+            "{cpu_time:?} out of {wall_time:?}"
+        );
+    }
+
     // Sends an event to a vcpu and expects a particular response.
     fn queue_event_expect_response(handle: &VcpuHandle, event: VcpuEvent, response: VcpuResponse) {
         handle
@@ -1246,6 +1543,51 @@ pub(crate) mod tests {
         vcpu_handle.send_event(VcpuEvent::Finish).unwrap();
     }
 
+    #[test]
+    fn test_vcpu_run_stats() {
+        let (vcpu_handle, _) = vcpu_configured_for_boot();
+
+        // A paused vcpu that never ran reports zeroed stats.
+        queue_event_expect_response(
+            &vcpu_handle,
+            VcpuEvent::GetRunStats,
+            VcpuResponse::RunStats(VcpuRunStats::default()),
+        );
+
+        vcpu_handle.send_event(VcpuEvent::Finish).unwrap();
+    }
+
+    #[test]
+    fn test_vcpu_run_state() {
+        let (vcpu_handle, _) = vcpu_configured_for_boot();
+
+        // A vcpu is paused until it is resumed.
+        assert_eq!(vcpu_handle.run_state(), VcpuRunState::Paused);
+        queue_event_expect_response(&vcpu_handle, VcpuEvent::Resume, VcpuResponse::Resumed);
+        assert!(matches!(
+            vcpu_handle.run_state(),
+            VcpuRunState::Running | VcpuRunState::InMmio
+        ));
+        queue_event_expect_response(&vcpu_handle, VcpuEvent::Pause, VcpuResponse::Paused);
+        assert_eq!(vcpu_handle.run_state(), VcpuRunState::Paused);
+        vcpu_handle.send_event(VcpuEvent::Finish).unwrap();
+
+        // A vcpu whose guest shut down is stopped, while a vcpu stopped on error is exited.
+        for (exit_code, run_state) in [
+            (FcExitCode::Ok, VcpuRunState::Stopped),
+            (FcExitCode::GenericError, VcpuRunState::Exited),
+        ] {
+            let (_vm, mut vcpu, _) = setup_vcpu(0x1000);
+            vcpu.event_sender
+                .as_ref()
+                .unwrap()
+                .send(VcpuEvent::Finish)
+                .unwrap();
+            let _ = vcpu.exit(exit_code);
+            assert_eq!(vcpu.run_state.get(), run_state);
+        }
+    }
+
     #[test]
     fn test_vcpu_rtsig_offset() {
         validate_signal_num(sigrtmin() + VCPU_RTSIG_OFFSET).unwrap();
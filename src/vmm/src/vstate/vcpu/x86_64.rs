@@ -23,6 +23,7 @@ use crate::arch::x86_64::msr::{create_boot_msr_entries, MsrError};
 use crate::arch::x86_64::regs::{SetupFpuError, SetupRegistersError, SetupSpecialRegistersError};
 use crate::cpu_config::x86_64::{cpuid, CpuConfiguration};
 use crate::logger::{IncMetric, METRICS};
+use crate::vmm_config::machine_config::threads_per_core;
 use crate::vstate::memory::{Address, GuestAddress, GuestMemoryMmap};
 use crate::vstate::vcpu::{VcpuConfig, VcpuEmulation};
 use crate::vstate::vm::Vm;
@@ -155,6 +156,8 @@ pub(super) struct Peripherals {
     pub pio_bus: Option<crate::devices::Bus>,
     /// Mmio bus.
     pub mmio_bus: Option<crate::devices::Bus>,
+    /// KVM exit statistics.
+    pub run_stats: super::VcpuRunStats,
 }
 
 impl KvmVcpu {
@@ -201,7 +204,9 @@ impl KvmVcpu {
             // The total number of logical CPUs.
             vcpu_config.vcpu_count,
             // The number of bits needed to enumerate logical CPUs per core.
-            u8::from(vcpu_config.vcpu_count > 1 && vcpu_config.smt),
+            u8::from(threads_per_core(vcpu_config.vcpu_count, vcpu_config.smt) > 1),
+            // The CPUID settings configured by the user.
+            &vcpu_config.cpuid_options,
         )?;
 
         // Set CPUID.
@@ -281,7 +286,7 @@ impl KvmVcpu {
     /// # Errors
     ///
     /// * When [`kvm_ioctls::VcpuFd::get_cpuid2`] returns errors.
-    fn get_cpuid(&self) -> Result<kvm_bindings::CpuId, KvmVcpuError> {
+    pub(crate) fn get_cpuid(&self) -> Result<kvm_bindings::CpuId, KvmVcpuError> {
         let mut cpuid = self
             .fd
             .get_cpuid2(KVM_MAX_CPUID_ENTRIES)
@@ -629,7 +634,10 @@ impl Peripherals {
     /// Runs the vCPU in KVM context and handles the kvm exit reason.
     ///
     /// Returns error or enum specifying whether emulation was handled or interrupted.
-    pub fn run_arch_emulation(&self, exit: VcpuExit) -> Result<VcpuEmulation, super::VcpuError> {
+    pub fn run_arch_emulation(
+        &mut self,
+        exit: VcpuExit,
+    ) -> Result<VcpuEmulation, super::VcpuError> {
         match exit {
             VcpuExit::IoIn(addr, data) => {
                 if let Some(pio_bus) = &self.pio_bus {
@@ -637,6 +645,7 @@ impl Peripherals {
                     pio_bus.read(u64::from(addr), data);
                     METRICS.vcpu.exit_io_in.inc();
                 }
+                self.run_stats.pio_reads += 1;
                 Ok(VcpuEmulation::Handled)
             }
             VcpuExit::IoOut(addr, data) => {
@@ -645,6 +654,7 @@ impl Peripherals {
                     pio_bus.write(u64::from(addr), data);
                     METRICS.vcpu.exit_io_out.inc();
                 }
+                self.run_stats.pio_writes += 1;
                 Ok(VcpuEmulation::Handled)
             }
             unexpected_exit => {
@@ -722,9 +732,15 @@ mod tests {
     use crate::arch::x86_64::cpu_model::CpuModel;
     use crate::cpu_config::templates::{
         CpuConfiguration, CpuTemplateType, CustomCpuTemplate, GetCpuTemplate, GuestConfigError,
-        StaticCpuTemplate,
+        RegisterValueFilter, StaticCpuTemplate,
+    };
+    use crate::cpu_config::x86_64::cpuid::{
+        Cpuid, CpuidEntry, CpuidKey, CpuidRegisters, CpuidTrait, KvmCpuidFlags,
+    };
+    use crate::cpu_config::x86_64::custom_cpu_template::{
+        CpuidLeafModifier, CpuidRegister, CpuidRegisterModifier,
     };
-    use crate::cpu_config::x86_64::cpuid::{Cpuid, CpuidEntry, CpuidKey};
+    use crate::vmm_config::machine_config::{CpuFrequencies, CpuSignature, CpuidNormalizeOptions};
     use crate::vstate::vm::tests::setup_vm;
     use crate::vstate::vm::Vm;
 
@@ -780,6 +796,7 @@ mod tests {
             vcpu_count: 1,
             smt: false,
             cpu_config,
+            cpuid_options: CpuidNormalizeOptions::default(),
         })
     }
 
@@ -903,6 +920,7 @@ mod tests {
                 cpuid: Cpuid::try_from(vm.supported_cpuid().clone()).unwrap(),
                 msrs: BTreeMap::new(),
             },
+            cpuid_options: CpuidNormalizeOptions::default(),
         };
         vcpu.configure(&vm_mem, GuestAddress(0), &vcpu_config)
             .unwrap();
@@ -965,12 +983,351 @@ mod tests {
                 cpuid: Cpuid::try_from(vm.supported_cpuid().clone()).unwrap(),
                 msrs: BTreeMap::new(),
             },
+            cpuid_options: CpuidNormalizeOptions::default(),
         };
         vcpu.configure(&vm_mem, GuestAddress(0), &vcpu_config)
             .unwrap();
         vcpu.dump_cpu_config().unwrap();
     }
 
+    #[test]
+    fn test_configure_vcpu_vendor_id() {
+        // Test that a configured vendor ID is reported in leaf 0x0, while the vendor-specific
+        // normalization keeps following the host.
+        let (vm, mut vcpu, vm_mem) = setup_vcpu(0x10000);
+        let cpuid = Cpuid::try_from(vm.supported_cpuid().clone()).unwrap();
+        let vendor_id = match cpuid {
+            Cpuid::Intel(_) => *b"AuthenticAMD",
+            Cpuid::Amd(_) => *b"GenuineIntel",
+        };
+        let vcpu_config = VcpuConfig {
+            vcpu_count: 1,
+            smt: false,
+            cpu_config: CpuConfiguration {
+                cpuid: cpuid.clone(),
+                msrs: BTreeMap::new(),
+            },
+            cpuid_options: CpuidNormalizeOptions {
+                vendor_id: Some(String::from_utf8(vendor_id.to_vec()).unwrap()),
+                ..Default::default()
+            },
+        };
+        vcpu.configure(&vm_mem, GuestAddress(0), &vcpu_config)
+            .unwrap();
+
+        let guest_cpuid = vcpu.get_cpuid().unwrap();
+        assert_eq!(guest_cpuid.vendor_id(), Some(vendor_id));
+
+        // The rest of the normalization is still applied.
+        let guest_cpuid = Cpuid::try_from(guest_cpuid).unwrap();
+        assert_eq!(
+            guest_cpuid.inner()[&CpuidKey::leaf(0x0)].result.eax,
+            cpuid.inner()[&CpuidKey::leaf(0x0)].result.eax
+        );
+        // Hypervisor bit.
+        assert_ne!(
+            guest_cpuid.inner()[&CpuidKey::leaf(0x1)].result.ecx & (1 << 31),
+            0
+        );
+    }
+
+    #[test]
+    fn test_configure_vcpu_cache_levels() {
+        // Test that the configured number of cache levels is advertised in leaf 0x4.
+        let (vm, mut vcpu, vm_mem) = setup_vcpu(0x10000);
+        let cpuid = Cpuid::try_from(vm.supported_cpuid().clone()).unwrap();
+        let is_intel = matches!(cpuid, Cpuid::Intel(_));
+        let vcpu_config = VcpuConfig {
+            vcpu_count: 1,
+            smt: false,
+            cpu_config: CpuConfiguration {
+                cpuid,
+                msrs: BTreeMap::new(),
+            },
+            cpuid_options: CpuidNormalizeOptions {
+                cache_levels: Some(1),
+                ..Default::default()
+            },
+        };
+        let res = vcpu.configure(&vm_mem, GuestAddress(0), &vcpu_config);
+
+        if !is_intel {
+            assert_eq!(
+                res,
+                Err(KvmVcpuConfigureError::NormalizeCpuidError(
+                    cpuid::NormalizeCpuidError::CacheLevelsNotSupported
+                ))
+            );
+            return;
+        }
+        res.unwrap();
+
+        let guest_cpuid = Cpuid::try_from(vcpu.get_cpuid().unwrap()).unwrap();
+        let levels: Vec<u32> = guest_cpuid
+            .inner()
+            .iter()
+            .filter(|(key, entry)| key.leaf == 0x4 && entry.result.eax != 0)
+            .map(|(_, entry)| (entry.result.eax >> 5) & 0x7)
+            .collect();
+        assert!(!levels.is_empty());
+        assert!(levels.iter().all(|&level| level == 1), "{:?}", levels);
+    }
+
+    #[test]
+    fn test_configure_vcpu_rng_instructions() {
+        // Test that RDRAND and RDSEED configured as not advertised are masked in the CPUID the
+        // guest sees, even when the host supports them.
+        let (vm, mut vcpu, vm_mem) = setup_vcpu(0x10000);
+        let vcpu_config = VcpuConfig {
+            vcpu_count: 1,
+            smt: false,
+            cpu_config: CpuConfiguration {
+                cpuid: Cpuid::try_from(vm.supported_cpuid().clone()).unwrap(),
+                msrs: BTreeMap::new(),
+            },
+            cpuid_options: CpuidNormalizeOptions {
+                rdrand: Some(false),
+                rdseed: Some(false),
+                ..Default::default()
+            },
+        };
+        vcpu.configure(&vm_mem, GuestAddress(0), &vcpu_config)
+            .unwrap();
+
+        let guest_cpuid = Cpuid::try_from(vcpu.get_cpuid().unwrap()).unwrap();
+        // RDRAND.
+        assert_eq!(
+            guest_cpuid.inner()[&CpuidKey::leaf(0x1)].result.ecx & (1 << 30),
+            0
+        );
+        // RDSEED.
+        if let Some(entry) = guest_cpuid.inner().get(&CpuidKey::subleaf(0x7, 0x0)) {
+            assert_eq!(entry.result.ebx & (1 << 18), 0);
+        }
+    }
+
+    #[test]
+    fn test_configure_vcpu_cpu_signature() {
+        // Test that the configured family, model and stepping are reported in leaf 0x1.
+        let (vm, mut vcpu, vm_mem) = setup_vcpu(0x10000);
+        let vcpu_config = VcpuConfig {
+            vcpu_count: 1,
+            smt: false,
+            cpu_config: CpuConfiguration {
+                cpuid: Cpuid::try_from(vm.supported_cpuid().clone()).unwrap(),
+                msrs: BTreeMap::new(),
+            },
+            cpuid_options: CpuidNormalizeOptions {
+                cpu_signature: Some(CpuSignature {
+                    family: 0x6,
+                    model: 0x55,
+                    stepping: 0x4,
+                }),
+                ..Default::default()
+            },
+        };
+        vcpu.configure(&vm_mem, GuestAddress(0), &vcpu_config)
+            .unwrap();
+
+        let guest_cpuid = Cpuid::try_from(vcpu.get_cpuid().unwrap()).unwrap();
+        let eax = guest_cpuid.get(&CpuidKey::leaf(0x1)).unwrap().result.eax;
+        assert_eq!(
+            CpuModel::from(&eax),
+            CpuModel {
+                extended_family: 0,
+                extended_model: 0x5,
+                family: 0x6,
+                model: 0x5,
+                stepping: 0x4,
+            }
+        );
+    }
+
+    #[test]
+    fn test_configure_vcpu_cpu_frequencies() {
+        // Test that the configured frequencies are reported in leaf 0x16.
+        let (vm, mut vcpu, vm_mem) = setup_vcpu(0x10000);
+        let vcpu_config = VcpuConfig {
+            vcpu_count: 1,
+            smt: false,
+            cpu_config: CpuConfiguration {
+                cpuid: Cpuid::try_from(vm.supported_cpuid().clone()).unwrap(),
+                msrs: BTreeMap::new(),
+            },
+            cpuid_options: CpuidNormalizeOptions {
+                cpu_frequencies: Some(CpuFrequencies {
+                    base_mhz: 2100,
+                    max_mhz: 3500,
+                    bus_mhz: 100,
+                }),
+                ..Default::default()
+            },
+        };
+        vcpu.configure(&vm_mem, GuestAddress(0), &vcpu_config)
+            .unwrap();
+
+        let guest_cpuid = Cpuid::try_from(vcpu.get_cpuid().unwrap()).unwrap();
+        let leaf_16 = guest_cpuid.get(&CpuidKey::leaf(0x16)).unwrap().result;
+        assert_eq!(
+            (leaf_16.eax, leaf_16.ebx, leaf_16.ecx, leaf_16.edx),
+            (2100, 3500, 100, 0)
+        );
+        let max_leaf = guest_cpuid.get(&CpuidKey::leaf(0x0)).unwrap().result.eax;
+        assert!(max_leaf >= 0x16, "{:#x}", max_leaf);
+    }
+
+    #[test]
+    fn test_configure_vcpu_pmu() {
+        // Test that the architectural PMU leaf is only kept if the PMU is enabled.
+        for pmu in [None, Some(false), Some(true)] {
+            let (vm, mut vcpu, vm_mem) = setup_vcpu(0x10000);
+            let supported_cpuid = Cpuid::try_from(vm.supported_cpuid().clone()).unwrap();
+            let is_intel = matches!(supported_cpuid, Cpuid::Intel(_));
+            let supported_leaf_a = supported_cpuid.get(&CpuidKey::leaf(0xA)).cloned();
+            let vcpu_config = VcpuConfig {
+                vcpu_count: 1,
+                smt: false,
+                cpu_config: CpuConfiguration {
+                    cpuid: supported_cpuid,
+                    msrs: BTreeMap::new(),
+                },
+                cpuid_options: CpuidNormalizeOptions {
+                    pmu,
+                    ..Default::default()
+                },
+            };
+            let pmu_supported = supported_leaf_a
+                .as_ref()
+                .is_some_and(|leaf_a| leaf_a.result.eax & 0xff != 0);
+            let res = vcpu.configure(&vm_mem, GuestAddress(0), &vcpu_config);
+            // AMD does not describe its PMU in leaf 0xA.
+            if !is_intel {
+                if pmu == Some(true) {
+                    assert_eq!(
+                        res,
+                        Err(KvmVcpuConfigureError::NormalizeCpuidError(
+                            cpuid::NormalizeCpuidError::PmuNotSupported
+                        ))
+                    );
+                } else {
+                    res.unwrap();
+                }
+                continue;
+            }
+            if pmu == Some(true) && !pmu_supported {
+                res.unwrap_err();
+                continue;
+            }
+            res.unwrap();
+
+            let guest_cpuid = Cpuid::try_from(vcpu.get_cpuid().unwrap()).unwrap();
+            let leaf_a = guest_cpuid.get(&CpuidKey::leaf(0xA)).unwrap();
+            if pmu == Some(true) {
+                assert_eq!(Some(leaf_a), supported_leaf_a.as_ref());
+            } else {
+                assert_eq!(leaf_a.result, CpuidRegisters::default());
+            }
+        }
+    }
+
+    #[test]
+    fn test_configure_vcpu_tsc_deadline() {
+        // Test that the TSC deadline timer is advertised unless disabled, along with the APIC.
+        for tsc_deadline in [None, Some(false), Some(true)] {
+            let (vm, mut vcpu, vm_mem) = setup_vcpu(0x10000);
+            let vcpu_config = VcpuConfig {
+                vcpu_count: 1,
+                smt: false,
+                cpu_config: CpuConfiguration {
+                    cpuid: Cpuid::try_from(vm.supported_cpuid().clone()).unwrap(),
+                    msrs: BTreeMap::new(),
+                },
+                cpuid_options: CpuidNormalizeOptions {
+                    tsc_deadline,
+                    ..Default::default()
+                },
+            };
+            vcpu.configure(&vm_mem, GuestAddress(0), &vcpu_config)
+                .unwrap();
+
+            let guest_cpuid = Cpuid::try_from(vcpu.get_cpuid().unwrap()).unwrap();
+            let leaf_1 = guest_cpuid.get(&CpuidKey::leaf(0x1)).unwrap();
+            let advertised = leaf_1.result.ecx & (1 << 24) != 0;
+            assert_eq!(advertised, tsc_deadline != Some(false));
+            // The TSC deadline mode is a mode of the local APIC timer.
+            if advertised {
+                assert_ne!(leaf_1.result.edx & (1 << 9), 0);
+            }
+        }
+    }
+
+    #[test]
+    fn test_configure_vcpu_options_override_template() {
+        // Test that a CPUID setting of the machine configuration takes precedence over a custom
+        // template modifying the same bit, as the template is applied before normalization.
+        let (vm, mut vcpu, vm_mem) = setup_vcpu(0x10000);
+        let template = CustomCpuTemplate {
+            cpuid_modifiers: vec![CpuidLeafModifier {
+                leaf: 0x1,
+                subleaf: 0x0,
+                flags: KvmCpuidFlags::EMPTY,
+                modifiers: vec![CpuidRegisterModifier {
+                    register: CpuidRegister::Ecx,
+                    bitmap: RegisterValueFilter {
+                        filter: 1 << 31,
+                        value: 1 << 31,
+                    },
+                }],
+            }],
+            ..Default::default()
+        };
+        let mut vcpu_config = create_vcpu_config(&vm, &vcpu, &template).unwrap();
+        let leaf_1 = vcpu_config
+            .cpu_config
+            .cpuid
+            .get(&CpuidKey::leaf(0x1))
+            .unwrap();
+        assert_ne!(leaf_1.result.ecx & (1 << 31), 0);
+
+        vcpu_config.cpuid_options.hypervisor_bit = Some(false);
+        vcpu.configure(&vm_mem, GuestAddress(0), &vcpu_config)
+            .unwrap();
+
+        let guest_cpuid = Cpuid::try_from(vcpu.get_cpuid().unwrap()).unwrap();
+        let leaf_1 = guest_cpuid.get(&CpuidKey::leaf(0x1)).unwrap();
+        assert_eq!(leaf_1.result.ecx & (1 << 31), 0);
+    }
+
+    #[test]
+    fn test_configure_vcpu_smt() {
+        // Test that the SMT setting is reflected in the threads per core of leaf 0xB.
+        for (vcpu_count, smt, threads) in [(2, true, 2), (2, false, 1), (1, true, 1)] {
+            let (vm, mut vcpu, vm_mem) = setup_vcpu(0x10000);
+            let vcpu_config = VcpuConfig {
+                vcpu_count,
+                smt,
+                cpu_config: CpuConfiguration {
+                    cpuid: Cpuid::try_from(vm.supported_cpuid().clone()).unwrap(),
+                    msrs: BTreeMap::new(),
+                },
+                cpuid_options: CpuidNormalizeOptions::default(),
+            };
+            vcpu.configure(&vm_mem, GuestAddress(0), &vcpu_config)
+                .unwrap();
+
+            let guest_cpuid = Cpuid::try_from(vcpu.get_cpuid().unwrap()).unwrap();
+            let Some(smt_level) = guest_cpuid.get(&CpuidKey::subleaf(0xb, 0x0)) else {
+                // The host does not report the extended topology leaf.
+                continue;
+            };
+            assert_eq!(smt_level.result.ebx & 0xffff, threads);
+            // The APIC ID shift to the core level is the number of bits enumerating the threads.
+            assert_eq!(smt_level.result.eax & 0x1f, threads - 1);
+            let core_level = guest_cpuid.get(&CpuidKey::subleaf(0xb, 0x1)).unwrap();
+            assert_eq!(core_level.result.ebx & 0xffff, u32::from(vcpu_count));
+        }
+    }
+
     #[test]
     #[allow(clippy::redundant_clone)]
     fn test_is_tsc_scaling_required() {
@@ -11,10 +11,13 @@ use std::fmt;
 #[cfg(target_arch = "x86_64")]
 use kvm_bindings::{
     kvm_clock_data, kvm_irqchip, kvm_pit_config, kvm_pit_state2, CpuId, MsrList,
-    KVM_CLOCK_TSC_STABLE, KVM_IRQCHIP_IOAPIC, KVM_IRQCHIP_PIC_MASTER, KVM_IRQCHIP_PIC_SLAVE,
-    KVM_MAX_CPUID_ENTRIES, KVM_PIT_SPEAKER_DUMMY,
+    KVM_CAP_PMU_CAPABILITY, KVM_CLOCK_TSC_STABLE, KVM_IRQCHIP_IOAPIC, KVM_IRQCHIP_PIC_MASTER,
+    KVM_IRQCHIP_PIC_SLAVE, KVM_MAX_CPUID_ENTRIES, KVM_PIT_SPEAKER_DUMMY, KVM_PMU_CAP_DISABLE,
+};
+use kvm_bindings::{
+    kvm_enable_cap, kvm_userspace_memory_region, KVM_API_VERSION, KVM_CAP_HALT_POLL,
+    KVM_MEM_LOG_DIRTY_PAGES,
 };
-use kvm_bindings::{kvm_userspace_memory_region, KVM_API_VERSION, KVM_MEM_LOG_DIRTY_PAGES};
 use kvm_ioctls::{Kvm, VmFd};
 use serde::{Deserialize, Serialize};
 
@@ -46,6 +49,11 @@ pub enum VmError {
     NotEnoughMemorySlots,
     /// Cannot set the memory regions: {0}
     SetUserMemoryRegion(kvm_ioctls::Error),
+    /// Cannot set the halt polling interval: {0}
+    SetHaltPoll(kvm_ioctls::Error),
+    #[cfg(target_arch = "x86_64")]
+    /// Cannot disable the PMU: {0}
+    DisablePmu(kvm_ioctls::Error),
     #[cfg(target_arch = "aarch64")]
     /// Error creating the global interrupt controller: {0}
     VmCreateGIC(crate::arch::aarch64::gic::GicError),
@@ -122,6 +130,8 @@ pub struct Vm {
     supported_cpuid: CpuId,
     #[cfg(target_arch = "x86_64")]
     msrs_to_save: MsrList,
+    #[cfg(target_arch = "x86_64")]
+    pmu_disabled: bool,
 
     // Arm specific fields.
     // On aarch64 we need to keep around the fd obtained by creating the VGIC device.
@@ -173,6 +183,7 @@ impl Vm {
                 kvm_cap_modifiers,
                 supported_cpuid,
                 msrs_to_save,
+                pmu_disabled: false,
             })
         }
     }
@@ -257,6 +268,22 @@ impl Vm {
     pub fn fd(&self) -> &VmFd {
         &self.fd
     }
+
+    /// Returns whether KVM allows setting the halt polling time of this VM (Linux 5.16 or newer).
+    pub fn can_set_halt_poll_ns(&self) -> bool {
+        self.fd.check_extension_raw(u64::from(KVM_CAP_HALT_POLL)) != 0
+    }
+
+    /// Sets the time in nanoseconds KVM polls for a wakeup event before blocking the thread of a
+    /// halted vCPU of this VM.
+    pub fn set_halt_poll_ns(&self, halt_poll_ns: u32) -> Result<(), VmError> {
+        let cap = kvm_enable_cap {
+            cap: KVM_CAP_HALT_POLL,
+            args: [u64::from(halt_poll_ns), 0, 0, 0],
+            ..Default::default()
+        };
+        self.fd.enable_cap(&cap).map_err(VmError::SetHaltPoll)
+    }
 }
 
 #[cfg(target_arch = "aarch64")]
@@ -393,6 +420,26 @@ impl Vm {
         self.fd.create_pit2(pit_config).map_err(VmError::VmSetup)
     }
 
+    /// Returns whether KVM allows disabling the PMU of this VM.
+    pub fn can_disable_pmu(&self) -> bool {
+        self.fd
+            .check_extension_raw(u64::from(KVM_CAP_PMU_CAPABILITY))
+            != 0
+    }
+
+    /// Disables the PMU of this VM, so that its vCPUs cannot access the performance monitoring
+    /// counters whatever their CPUID. Must be called before the vCPUs are created.
+    pub fn disable_pmu(&mut self) -> Result<(), VmError> {
+        let cap = kvm_enable_cap {
+            cap: KVM_CAP_PMU_CAPABILITY,
+            args: [u64::from(KVM_PMU_CAP_DISABLE), 0, 0, 0],
+            ..Default::default()
+        };
+        self.fd.enable_cap(&cap).map_err(VmError::DisablePmu)?;
+        self.pmu_disabled = true;
+        Ok(())
+    }
+
     /// Saves and returns the Kvm Vm state.
     pub fn save_state(&self) -> Result<VmState, VmError> {
         let pitstate = self.fd.get_pit2().map_err(VmError::VmGetPit2)?;
@@ -432,6 +479,7 @@ impl Vm {
             pic_slave,
             ioapic,
             kvm_cap_modifiers: self.kvm_cap_modifiers.clone(),
+            pmu_disabled: self.pmu_disabled,
         })
     }
 }
@@ -450,6 +498,8 @@ pub struct VmState {
 
     /// Additional capabilities that were specified in cpu template.
     pub kvm_cap_modifiers: Vec<KvmCapability>,
+    /// Whether the PMU of the VM is disabled.
+    pub pmu_disabled: bool,
 }
 
 #[cfg(target_arch = "x86_64")]
@@ -489,6 +539,36 @@ pub(crate) mod tests {
         Vm::new(vec![]).unwrap();
     }
 
+    #[test]
+    fn test_set_halt_poll_ns() {
+        let vm = Vm::new(vec![]).unwrap();
+        if !vm.can_set_halt_poll_ns() {
+            vm.set_halt_poll_ns(0).unwrap_err();
+            return;
+        }
+        vm.set_halt_poll_ns(0).unwrap();
+        vm.set_halt_poll_ns(200_000).unwrap();
+    }
+
+    #[cfg(target_arch = "x86_64")]
+    #[test]
+    fn test_disable_pmu() {
+        let kvm = Kvm::new().unwrap();
+        let mut vm = Vm::new(vec![]).unwrap();
+        if kvm.check_extension_raw(u64::from(KVM_CAP_PMU_CAPABILITY)) == 0 {
+            assert!(!vm.can_disable_pmu());
+            vm.disable_pmu().unwrap_err();
+            assert!(!vm.pmu_disabled);
+            return;
+        }
+        assert!(vm.can_disable_pmu());
+        vm.disable_pmu().unwrap();
+
+        // Whether the PMU is disabled is saved with the VM state.
+        vm.setup_irqchip().unwrap();
+        assert!(vm.save_state().unwrap().pmu_disabled);
+    }
+
     #[test]
     fn test_combine_capabilities() {
         // Default caps for x86_64 and aarch64 both have KVM_CAP_IOEVENTFD and don't have
@@ -516,6 +596,37 @@ pub(crate) mod tests {
         vm.memory_init(&gm, true).unwrap();
     }
 
+    #[test]
+    fn test_vm_memory_init_memslot_split() {
+        use crate::vmm_config::machine_config::{HugePageConfig, MachineConfigUpdate, VmConfig};
+        use crate::vstate::memory::{GuestMemory, GuestMemoryExtension};
+
+        let vm_config = VmConfig::default()
+            .update(&MachineConfigUpdate {
+                mem_size_mib: Some(64),
+                memslot_size_mib: Some(Some(16)),
+                ..Default::default()
+            })
+            .unwrap();
+        let gm = GuestMemoryMmap::from_raw_regions(
+            &vm_config.memory_regions(),
+            true,
+            HugePageConfig::None,
+        )
+        .unwrap();
+        assert_eq!(gm.num_regions(), 4);
+
+        let vm = Vm::new(vec![]).expect("Cannot create new vm");
+        vm.memory_init(&gm, true).unwrap();
+
+        // Dirty pages are tracked separately for each memory slot.
+        for slot in 0..4 {
+            let bitmap = vm.fd().get_dirty_log(slot, 16 << 20).unwrap();
+            assert_eq!(bitmap.len(), (16 << 20) / 4096 / 64);
+        }
+        vm.fd().get_dirty_log(4, 16 << 20).unwrap_err();
+    }
+
     #[cfg(target_arch = "x86_64")]
     #[test]
     fn test_vm_save_restore_state() {
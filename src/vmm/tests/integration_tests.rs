@@ -7,7 +7,9 @@ use std::time::Duration;
 
 use vmm::builder::build_and_boot_microvm;
 use vmm::devices::virtio::block::CacheType;
-use vmm::persist::{snapshot_state_sanity_check, MicrovmState, MicrovmStateError, VmInfo};
+use vmm::persist::{
+    snapshot_state_sanity_check, CreateSnapshotError, MicrovmState, MicrovmStateError, VmInfo,
+};
 use vmm::resources::VmResources;
 use vmm::rpc_interface::{
     LoadSnapshotError, PrebootApiController, RuntimeApiController, VmmAction, VmmActionError,
@@ -17,7 +19,7 @@ use vmm::snapshot::Snapshot;
 #[cfg(target_arch = "x86_64")]
 use vmm::test_utils::dirty_tracking_vmm;
 use vmm::test_utils::mock_resources::{MockVmResources, NOISY_KERNEL_IMAGE};
-use vmm::test_utils::{create_vmm, default_vmm, default_vmm_no_boot};
+use vmm::test_utils::{create_vmm, default_vmm, default_vmm_no_boot, multi_vcpu_vmm};
 use vmm::vmm_config::balloon::BalloonDeviceConfig;
 use vmm::vmm_config::boot_source::BootSourceConfig;
 use vmm::vmm_config::drive::BlockDeviceConfig;
@@ -28,7 +30,7 @@ use vmm::vmm_config::snapshot::{
     CreateSnapshotParams, LoadSnapshotParams, MemBackendConfig, MemBackendType, SnapshotType,
 };
 use vmm::vmm_config::vsock::VsockDeviceConfig;
-use vmm::{DumpCpuConfigError, EventManager, FcExitCode};
+use vmm::{DumpCpuConfigError, EventManager, FcExitCode, VmmError};
 use vmm_sys_util::tempfile::TempFile;
 
 #[test]
@@ -84,6 +86,27 @@ fn test_build_microvm() {
     );
 }
 
+#[test]
+fn test_instance_uptime() {
+    let (vmm, mut _evtmgr) = default_vmm_no_boot(None);
+    let instance_info = vmm.lock().unwrap().instance_info();
+    assert_eq!(instance_info.boot_timestamp_us, None);
+    assert_eq!(instance_info.uptime_us, None);
+
+    vmm.lock().unwrap().resume_vm().unwrap();
+    let instance_info = vmm.lock().unwrap().instance_info();
+    let boot_timestamp_us = instance_info.boot_timestamp_us.unwrap();
+    let uptime_us = instance_info.uptime_us.unwrap();
+
+    thread::sleep(Duration::from_millis(1));
+    let instance_info = vmm.lock().unwrap().instance_info();
+    // The boot timestamp is recorded once, while the uptime keeps increasing.
+    assert_eq!(instance_info.boot_timestamp_us, Some(boot_timestamp_us));
+    assert!(instance_info.uptime_us.unwrap() > uptime_us);
+
+    vmm.lock().unwrap().stop(FcExitCode::Ok);
+}
+
 #[test]
 fn test_pause_resume_microvm() {
     // Tests that pausing and resuming a microVM work as expected.
@@ -102,6 +125,113 @@ fn test_pause_resume_microvm() {
     vmm.lock().unwrap().stop(FcExitCode::Ok);
 }
 
+#[test]
+fn test_pause_resume_vcpu() {
+    // Tests that single vCPUs can be paused and resumed while the other vCPUs keep running.
+    let (vmm, _) = multi_vcpu_vmm(Some(NOISY_KERNEL_IMAGE), 2);
+
+    let mut api_controller = RuntimeApiController::new(VmResources::default(), vmm.clone());
+
+    // The noisy kernel keeps writing to the serial console, so the exits caused by the guest only
+    // increase while its vCPU runs. Querying the stats kicks the running vCPUs, so the
+    // interrupted exits are left out, as they are caused by the query itself.
+    let guest_exits = || -> Vec<u64> {
+        vmm.lock()
+            .unwrap()
+            .vcpu_run_stats()
+            .unwrap()
+            .iter()
+            .map(|stats| {
+                stats.mmio_reads
+                    + stats.mmio_writes
+                    + stats.pio_reads
+                    + stats.pio_writes
+                    + stats.hlt_exits
+            })
+            .collect()
+    };
+    let assert_progress = |running: [bool; 2]| {
+        let before = guest_exits();
+        thread::sleep(Duration::from_millis(10));
+        let after = guest_exits();
+        for (idx, running) in running.into_iter().enumerate() {
+            if running {
+                assert!(after[idx] > before[idx], "vCPU {idx} made no progress");
+            } else {
+                assert_eq!(after[idx], before[idx], "paused vCPU {idx} made progress");
+            }
+        }
+    };
+
+    // Both vCPUs make progress once the microVM is booted.
+    assert_progress([true, true]);
+
+    api_controller
+        .handle_request(VmmAction::PauseVcpu(0))
+        .unwrap();
+    // Pausing a paused vCPU again does not fail.
+    api_controller
+        .handle_request(VmmAction::PauseVcpu(0))
+        .unwrap();
+    assert_eq!(vmm.lock().unwrap().instance_info().state, VmState::Running);
+    // The paused vCPU stops making progress, while the other one keeps running.
+    assert_progress([false, true]);
+
+    api_controller
+        .handle_request(VmmAction::ResumeVcpu(0))
+        .unwrap();
+    assert_progress([true, true]);
+
+    let res = api_controller.handle_request(VmmAction::PauseVcpu(2));
+    assert!(
+        matches!(
+            res,
+            Err(VmmActionError::InternalVmm(VmmError::InvalidVcpuIndex(2)))
+        ),
+        "{:?}",
+        res
+    );
+
+    // Once the microVM is paused, its vCPUs are only resumed together.
+    api_controller
+        .handle_request(VmmAction::PauseVcpu(1))
+        .unwrap();
+    assert_progress([true, false]);
+    api_controller.handle_request(VmmAction::Pause).unwrap();
+    assert_progress([false, false]);
+    let res = api_controller.handle_request(VmmAction::ResumeVcpu(1));
+    assert!(
+        matches!(
+            res,
+            Err(VmmActionError::InternalVmm(
+                VmmError::VcpuStateChangeNotAllowed
+            ))
+        ),
+        "{:?}",
+        res
+    );
+    api_controller.handle_request(VmmAction::Resume).unwrap();
+    assert_progress([true, true]);
+
+    vmm.lock().unwrap().stop(FcExitCode::Ok);
+}
+
+#[test]
+fn test_quiesce_io_microvm() {
+    // Tests that quiescing IO leaves the vCPUs running.
+    let (vmm, _) = default_vmm(None);
+
+    let mut api_controller = RuntimeApiController::new(VmResources::default(), vmm.clone());
+
+    api_controller.handle_request(VmmAction::QuiesceIo).unwrap();
+    assert_eq!(vmm.lock().unwrap().instance_info().state, VmState::Running);
+    // The vCPUs can still be paused and resumed, which also resumes IO processing.
+    api_controller.handle_request(VmmAction::Pause).unwrap();
+    api_controller.handle_request(VmmAction::Resume).unwrap();
+
+    vmm.lock().unwrap().stop(FcExitCode::Ok);
+}
+
 #[test]
 fn test_dirty_bitmap_error() {
     // Error case: dirty tracking disabled.
@@ -115,6 +245,10 @@ fn test_dirty_bitmap_error() {
         format!("{:?}", vmm.lock().unwrap().get_dirty_bitmap().err()),
         "Some(DirtyBitmap(Error(2)))"
     );
+    assert_eq!(
+        format!("{:?}", vmm.lock().unwrap().dirty_page_count().err()),
+        "Some(DirtyBitmap(Error(2)))"
+    );
     vmm.lock().unwrap().stop(FcExitCode::Ok);
 }
 
@@ -137,6 +271,20 @@ fn test_dirty_bitmap_success() {
         })
         .sum();
     assert!(num_dirty_pages > 0);
+
+    // The guest keeps dirtying pages, which are counted without building the bitmap.
+    thread::sleep(Duration::from_millis(100));
+    let dirty_page_count = vmm.lock().unwrap().dirty_page_count().unwrap();
+    assert!(dirty_page_count > 0);
+
+    // Counting the dirty pages doesn't drop them from the next bitmap.
+    let bitmap = vmm.lock().unwrap().get_dirty_bitmap().unwrap();
+    let num_dirty_pages: u64 = bitmap
+        .values()
+        .flatten()
+        .map(|n| u64::from(n.count_ones()))
+        .sum();
+    assert!(num_dirty_pages >= dirty_page_count);
     vmm.lock().unwrap().stop(FcExitCode::Ok);
 }
 
@@ -213,6 +361,8 @@ fn verify_create_snapshot(is_diff: bool) -> (TempFile, TempFile) {
         snapshot_type,
         snapshot_path: snapshot_file.as_path().to_path_buf(),
         mem_file_path: memory_file.as_path().to_path_buf(),
+        baseline_mem_file_path: None,
+        mem_write_chunk_size_kib: None,
     };
 
     controller
@@ -289,6 +439,54 @@ fn test_create_and_load_snapshot() {
     verify_load_snapshot(snapshot_file, memory_file);
 }
 
+#[test]
+fn test_create_snapshot_mem_write_chunk_size() {
+    let snapshot_file = TempFile::new().unwrap();
+    let memory_file = TempFile::new().unwrap();
+
+    let (vmm, _) = create_vmm(Some(NOISY_KERNEL_IMAGE), false, true);
+    let resources = VmResources {
+        vm_config: VmConfig {
+            mem_size_mib: 1,
+            ..Default::default()
+        },
+        ..Default::default()
+    };
+    let mut controller = RuntimeApiController::new(resources, vmm.clone());
+    thread::sleep(Duration::from_millis(200));
+    controller.handle_request(VmmAction::Pause).unwrap();
+
+    let snapshot_params = |mem_write_chunk_size_kib| CreateSnapshotParams {
+        snapshot_type: SnapshotType::Full,
+        snapshot_path: snapshot_file.as_path().to_path_buf(),
+        mem_file_path: memory_file.as_path().to_path_buf(),
+        baseline_mem_file_path: None,
+        mem_write_chunk_size_kib: Some(mem_write_chunk_size_kib),
+    };
+
+    // The chunk size must be between 4 KiB and 1 GiB.
+    for chunk_size_kib in [0, 3, 1024 * 1024 + 1] {
+        let err = controller
+            .handle_request(VmmAction::CreateSnapshot(snapshot_params(chunk_size_kib)))
+            .unwrap_err();
+        assert!(
+            matches!(
+                err,
+                VmmActionError::CreateSnapshot(CreateSnapshotError::InvalidMemWriteChunkSize(size))
+                    if size == chunk_size_kib
+            ),
+            "{err:?}"
+        );
+    }
+
+    controller
+        .handle_request(VmmAction::CreateSnapshot(snapshot_params(4)))
+        .unwrap();
+    vmm.lock().unwrap().stop(FcExitCode::Ok);
+
+    verify_load_snapshot(snapshot_file, memory_file);
+}
+
 #[test]
 fn test_snapshot_load_sanity_checks() {
     use vmm::persist::SnapShotStateSanityCheckError;
@@ -377,6 +575,8 @@ fn test_preboot_load_snap_disallowed_after_boot_resources() {
         path_on_host: Some(tmp_file),
         rate_limiter: None,
         file_engine_type: None,
+        direct: None,
+        discard: None,
 
         socket: None,
     };
@@ -390,6 +590,7 @@ fn test_preboot_load_snap_disallowed_after_boot_resources() {
         guest_mac: None,
         rx_rate_limiter: None,
         tx_rate_limiter: None,
+        mtu: None,
     });
     verify_load_snap_disallowed_after_boot_resources(req, "InsertNetworkDevice");
 
@@ -67,7 +67,7 @@ class Resource:
         kwargs = {key: val for key, val in kwargs.items() if val is not None}
         url = self._api.endpoint + path
         res = self._api.session.request(method, url, json=kwargs)
-        if res.status_code != HTTPStatus.NO_CONTENT:
+        if res.status_code not in (HTTPStatus.NO_CONTENT, HTTPStatus.OK):
             json = res.json()
             msg = res.content
             if "fault_message" in json:
@@ -121,3 +121,4 @@ class Api:
         self.snapshot_load = Resource(self, "/snapshot/load")
         self.cpu_config = Resource(self, "/cpu-config")
         self.entropy = Resource(self, "/entropy")
+        self.seccomp = Resource(self, "/seccomp")
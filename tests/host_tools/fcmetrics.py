@@ -73,6 +73,17 @@ def validate_fc_metrics(metrics):
         "max_us",
         "sum_us",
     ]
+
+    latency_histogram_fields = [
+        "le_100us",
+        "le_1ms",
+        "le_10ms",
+        "le_100ms",
+        "le_1s",
+        "le_10s",
+        "le_inf",
+        "sum_us",
+    ]
     block_metrics = [
         "activate_fails",
         "cfg_fails",
@@ -81,6 +92,7 @@ def validate_fc_metrics(metrics):
         "execute_fails",
         "invalid_reqs_count",
         "flush_count",
+        "discard_count",
         "queue_event_count",
         "rate_limiter_event_count",
         "update_count",
@@ -91,7 +103,9 @@ def validate_fc_metrics(metrics):
         "write_count",
         "rate_limiter_throttled_events",
         "io_engine_throttled_events",
+        "io_engine_fallbacks",
         "remaining_reqs_count",
+        "readonly_write_rejects",
         {"read_agg": latency_agg_metrics_fields},
         {"write_agg": latency_agg_metrics_fields},
     ]
@@ -133,6 +147,7 @@ def validate_fc_metrics(metrics):
             "process_startup_time_cpu_us",
             "sync_response_fails",
             "sync_vmm_send_timeout_count",
+            "slow_request_count",
         ],
         "balloon": [
             "activate_fails",
@@ -140,7 +155,10 @@ def validate_fc_metrics(metrics):
             "stats_updates_count",
             "stats_update_fails",
             "deflate_count",
+            "free_page_report_count",
             "event_fails",
+            "compaction_count",
+            "compaction_reclaimed_mib",
         ],
         "block": block_metrics,
         "deprecated_api": [
@@ -178,6 +196,7 @@ def validate_fc_metrics(metrics):
             "metrics_fails",
             "missed_log_count",
             "log_fails",
+            "suppressed_log_count",
         ],
         "mmds": [
             "rx_accepted",
@@ -185,12 +204,15 @@ def validate_fc_metrics(metrics):
             "rx_accepted_unusual",
             "rx_bad_eth",
             "rx_count",
+            "rx_throttled",
             "tx_bytes",
             "tx_count",
             "tx_errors",
             "tx_frames",
             "connections_created",
             "connections_destroyed",
+            "data_store_bytes",
+            "rejected_writes",
         ],
         "net": net_metrics,
         "patch_api_requests": [
@@ -198,6 +220,8 @@ def validate_fc_metrics(metrics):
             "drive_fails",
             "network_count",
             "network_fails",
+            "logger_count",
+            "logger_fails",
             "machine_cfg_count",
             "machine_cfg_fails",
             "mmds_count",
@@ -227,6 +251,7 @@ def validate_fc_metrics(metrics):
         ],
         "seccomp": [
             "num_faults",
+            "install_fails",
         ],
         "vcpu": [
             "exit_io_in",
@@ -235,6 +260,7 @@ def validate_fc_metrics(metrics):
             "exit_mmio_write",
             "failures",
             "kvmclock_ctrl_fails",
+            "spurious_exits",
             {"exit_io_in_agg": latency_agg_metrics_fields},
             {"exit_io_out_agg": latency_agg_metrics_fields},
             {"exit_mmio_read_agg": latency_agg_metrics_fields},
@@ -243,6 +269,7 @@ def validate_fc_metrics(metrics):
         "vmm": [
             "device_events",
             "panic_count",
+            "oversized_desc_chains",
         ],
         "uart": [
             "error_count",
@@ -251,6 +278,7 @@ def validate_fc_metrics(metrics):
             "missed_write_count",
             "read_count",
             "write_count",
+            "rate_limited_bytes",
         ],
         "signals": [
             "sigbus",
@@ -292,6 +320,12 @@ def validate_fc_metrics(metrics):
             "entropy_rate_limiter_throttled",
             "rate_limiter_event_count",
         ],
+        "watchdog": [
+            "activate_fails",
+            "event_fails",
+            "pet_count",
+            "expired_count",
+        ],
     }
 
     # validate timestamp before jsonschema validation which some more time
@@ -329,6 +363,12 @@ def validate_fc_metrics(metrics):
             firecracker_metrics[metrics_name] = net_metrics
 
     firecracker_metrics_schema = create_metrics_schema_objects(firecracker_metrics)
+    # The API latency histograms are keyed by the action types of the requests handled so far.
+    firecracker_metrics_schema["properties"]["api_latencies"] = {
+        "type": "object",
+        "additionalProperties": create_metrics_schema_objects(latency_histogram_fields),
+    }
+    firecracker_metrics_schema["required"].append("api_latencies")
 
     jsonschema.validate(instance=metrics, schema=firecracker_metrics_schema)
 
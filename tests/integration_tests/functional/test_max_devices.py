@@ -56,9 +56,6 @@ def test_attach_too_many_devices(uvm_plain):
 
     # Attempting to start a microVM with more than
     # `MAX_DEVICES_ATTACHED` devices should fail.
-    error_str = (
-        "Failed to allocate requested resource: The requested resource"
-        " is not available."
-    )
+    error_str = "No IRQ left for device .*: The requested resource is not available."
     with pytest.raises(RuntimeError, match=error_str):
         test_microvm.start()
@@ -2,6 +2,8 @@
 # SPDX-License-Identifier: Apache-2.0
 """Tests for the virtio-rng device"""
 
+import platform
+
 import pytest
 
 from framework.utils import check_entropy
@@ -99,6 +101,31 @@ def test_rng_present(uvm_any):
     check_entropy(vm.ssh)
 
 
+def test_rng_without_rdrand_rdseed(microvm_factory, guest_kernel, rootfs):
+    """
+    Test a guest microVM with RDRAND and RDSEED masked and ensure that it
+    falls back to the virtio-rng device for entropy
+    """
+
+    if platform.machine() != "x86_64":
+        pytest.skip("RDRAND and RDSEED are only configurable on x86_64")
+
+    vm = microvm_factory.build(guest_kernel, rootfs)
+    vm.spawn()
+    vm.basic_config()
+    vm.api.machine_config.patch(rdrand=False, rdseed=False)
+    vm.add_net_iface()
+    vm.api.entropy.put()
+    vm.start()
+
+    flags = vm.ssh.check_output("grep -m1 '^flags' /proc/cpuinfo").stdout.split()
+    assert "rdrand" not in flags
+    assert "rdseed" not in flags
+
+    assert_virtio_rng_is_current_hwrng_device(vm.ssh)
+    check_entropy(vm.ssh)
+
+
 def _get_percentage_difference(measured, base):
     """Return the percentage delta between the arguments."""
     if measured == base:
@@ -185,6 +185,37 @@ def test_default_seccomp_level(uvm_plain):
     utils.assert_seccomp_level(test_microvm.firecracker_pid, "2")
 
 
+def test_api_seccomp_config(uvm_plain):
+    """
+    Test that `GET /seccomp` reports the seccomp level passed at launch.
+    """
+    test_microvm = uvm_plain
+    test_microvm.jailer.extra_args.update({"no-seccomp": None})
+    test_microvm.spawn()
+
+    seccomp = test_microvm.api.seccomp.get().json()
+    assert seccomp["level"] == "None"
+    assert set(seccomp["filter_hashes"]) == {"api", "vcpu", "vmm"}
+    assert all(hash == "0" * 16 for hash in seccomp["filter_hashes"].values())
+
+    test_microvm.basic_config()
+    test_microvm.start()
+    assert test_microvm.api.seccomp.get().json() == seccomp
+
+
+def test_api_default_seccomp_config(uvm_plain):
+    """
+    Test that `GET /seccomp` reports the default seccomp level.
+    """
+    test_microvm = uvm_plain
+    test_microvm.spawn()
+
+    seccomp = test_microvm.api.seccomp.get().json()
+    assert seccomp["level"] == "Advanced"
+    assert set(seccomp["filter_hashes"]) == {"api", "vcpu", "vmm"}
+    assert all(hash != "0" * 16 for hash in seccomp["filter_hashes"].values())
+
+
 def test_seccomp_rust_panic(bin_seccomp_paths, seccompiler):
     """
     Test seccompiler-bin with `demo_panic`.